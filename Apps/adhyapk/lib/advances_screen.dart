@@ -0,0 +1,97 @@
+import 'package:flutter/material.dart';
+import 'package:provider/provider.dart';
+import 'api_service.dart';
+
+const _months = [
+  'Jan', 'Feb', 'Mar', 'Apr', 'May', 'Jun', 'Jul', 'Aug', 'Sep', 'Oct', 'Nov', 'Dec',
+];
+
+String _money(dynamic v) => 'Rs. ${((v ?? 0) as num).toStringAsFixed(2)}';
+
+class AdvancesScreen extends StatefulWidget {
+  @override
+  _AdvancesScreenState createState() => _AdvancesScreenState();
+}
+
+class _AdvancesScreenState extends State<AdvancesScreen> {
+  late Future<Map<String, dynamic>?> _summary;
+
+  @override
+  void initState() {
+    super.initState();
+    _summary = Provider.of<ApiService>(context, listen: false).getAdvances();
+  }
+
+  @override
+  Widget build(BuildContext context) {
+    return Scaffold(
+      appBar: AppBar(
+        title: Text("My Advances"),
+        backgroundColor: Colors.blue[800],
+      ),
+      body: FutureBuilder<Map<String, dynamic>?>(
+        future: _summary,
+        builder: (context, snapshot) {
+          if (snapshot.connectionState == ConnectionState.waiting) {
+            return Center(child: CircularProgressIndicator());
+          }
+          final summary = snapshot.data;
+          if (summary == null) {
+            return Center(child: Text("Could not load advances.", style: TextStyle(color: Colors.grey)));
+          }
+          final advances = (summary['advances'] ?? []) as List<dynamic>;
+          return ListView(
+            padding: EdgeInsets.all(16),
+            children: [
+              Card(
+                child: ListTile(
+                  title: Text("Total outstanding"),
+                  subtitle: Text("Recovered ${_money(summary['monthlyRecovery'])} per month"),
+                  trailing: Text(
+                    _money(summary['totalOutstanding']),
+                    style: TextStyle(fontSize: 18, fontWeight: FontWeight.bold),
+                  ),
+                ),
+              ),
+              SizedBox(height: 8),
+              if (advances.isEmpty)
+                Padding(
+                  padding: EdgeInsets.all(24),
+                  child: Center(child: Text("No open advances.", style: TextStyle(color: Colors.grey))),
+                ),
+              for (final a in advances) _AdvanceTile(advance: a),
+            ],
+          );
+        },
+      ),
+    );
+  }
+}
+
+class _AdvanceTile extends StatelessWidget {
+  final dynamic advance;
+
+  _AdvanceTile({required this.advance});
+
+  @override
+  Widget build(BuildContext context) {
+    final next = advance['nextInstallment'];
+    final paused = advance['status'] == 'paused';
+    final nextText = next == null
+        ? "No further installments"
+        : "Next: ${_money(next['amount'])} in ${_months[((next['month'] ?? 1) as int) - 1]} ${next['year']}";
+    return Card(
+      child: ListTile(
+        leading: Icon(
+          advance['kind'] == 'loan' ? Icons.account_balance : Icons.payments,
+          color: Colors.blue[800],
+        ),
+        title: Text("${advance['kind'] == 'loan' ? 'Loan' : 'Advance'} of ${_money(advance['principal'])}"),
+        subtitle: Text(paused
+            ? "Recovery paused"
+            : "$nextText · ${advance['remainingInstallments']} left"),
+        trailing: Text(_money(advance['principalOutstanding'])),
+      ),
+    );
+  }
+}
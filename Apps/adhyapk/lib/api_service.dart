@@ -1,4 +1,5 @@
 import 'dart:convert';
+import 'dart:io';
 import 'package:http/http.dart' as http;
 import 'package:flutter_secure_storage/flutter_secure_storage.dart';
 
@@ -49,6 +50,82 @@ class ApiService {
     }
   }
 
+  Future<Map<String, String>> _authHeaders() async {
+    final token = await storage.read(key: 'jwt_token');
+    return {'Authorization': 'Bearer ${token ?? ''}'};
+  }
+
+  /// Approved payslips for the signed-in teacher, newest first.
+  Future<List<dynamic>> getPayslips() async {
+    try {
+      final response = await http.get(
+        Uri.parse('$baseUrl/payslips'),
+        headers: await _authHeaders(),
+      );
+      if (response.statusCode == 200) {
+        final data = jsonDecode(response.body);
+        return data['data'] ?? [];
+      }
+      return [];
+    } catch (e) {
+      print("Payslips Error: $e");
+      return [];
+    }
+  }
+
+  Future<Map<String, dynamic>?> getPayslip(String runId) async {
+    try {
+      final response = await http.get(
+        Uri.parse('$baseUrl/payslips/$runId'),
+        headers: await _authHeaders(),
+      );
+      if (response.statusCode == 200) {
+        final data = jsonDecode(response.body);
+        return data['data'];
+      }
+      return null;
+    } catch (e) {
+      print("Payslip Error: $e");
+      return null;
+    }
+  }
+
+  /// Downloads the payslip PDF and returns the saved file.
+  Future<File?> downloadPayslip(String runId, String fileName) async {
+    try {
+      final response = await http.get(
+        Uri.parse('$baseUrl/payslips/$runId/pdf'),
+        headers: await _authHeaders(),
+      );
+      if (response.statusCode == 200) {
+        final file = File('${Directory.systemTemp.path}/$fileName.pdf');
+        await file.writeAsBytes(response.bodyBytes);
+        return file;
+      }
+      return null;
+    } catch (e) {
+      print("Payslip Download Error: $e");
+      return null;
+    }
+  }
+
+  /// Outstanding salary advances and loans with their next installment.
+  Future<Map<String, dynamic>?> getAdvances() async {
+    try {
+      final response = await http.get(
+        Uri.parse('$baseUrl/advances'),
+        headers: await _authHeaders(),
+      );
+      if (response.statusCode == 200) {
+        return jsonDecode(response.body)['data'];
+      }
+      return null;
+    } catch (e) {
+      print("Advances Error: $e");
+      return null;
+    }
+  }
+
   Future<bool> isLoggedIn() async {
     final token = await storage.read(key: 'jwt_token');
     return token != null && token.isNotEmpty;
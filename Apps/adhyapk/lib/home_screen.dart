@@ -1,4 +1,6 @@
 import 'package:flutter/material.dart';
+import 'advances_screen.dart';
+import 'payslips_screen.dart';
 
 class HomeScreen extends StatelessWidget {
   @override
@@ -16,6 +18,24 @@ class HomeScreen extends StatelessWidget {
             SizedBox(height: 16),
             Text("Login Successful!", style: TextStyle(fontSize: 24, fontWeight: FontWeight.bold)),
             Text("Session stored locally.", style: TextStyle(color: Colors.grey)),
+            SizedBox(height: 24),
+            ElevatedButton.icon(
+              icon: Icon(Icons.receipt_long),
+              label: Text("My Payslips"),
+              onPressed: () => Navigator.push(
+                context,
+                MaterialPageRoute(builder: (_) => PayslipsScreen()),
+              ),
+            ),
+            SizedBox(height: 12),
+            ElevatedButton.icon(
+              icon: Icon(Icons.account_balance_wallet),
+              label: Text("My Advances"),
+              onPressed: () => Navigator.push(
+                context,
+                MaterialPageRoute(builder: (_) => AdvancesScreen()),
+              ),
+            ),
           ],
         ),
       ),
@@ -0,0 +1,170 @@
+import 'package:flutter/material.dart';
+import 'package:provider/provider.dart';
+import 'api_service.dart';
+
+const _months = [
+  'January', 'February', 'March', 'April', 'May', 'June',
+  'July', 'August', 'September', 'October', 'November', 'December',
+];
+
+String _period(dynamic slip) {
+  final month = (slip['month'] ?? 1) as int;
+  return '${_months[(month - 1).clamp(0, 11)]} ${slip['year']}';
+}
+
+String _money(dynamic v) => 'Rs. ${((v ?? 0) as num).toStringAsFixed(2)}';
+
+class PayslipsScreen extends StatefulWidget {
+  @override
+  _PayslipsScreenState createState() => _PayslipsScreenState();
+}
+
+class _PayslipsScreenState extends State<PayslipsScreen> {
+  late Future<List<dynamic>> _payslips;
+
+  @override
+  void initState() {
+    super.initState();
+    _payslips = Provider.of<ApiService>(context, listen: false).getPayslips();
+  }
+
+  @override
+  Widget build(BuildContext context) {
+    return Scaffold(
+      appBar: AppBar(
+        title: Text("My Payslips"),
+        backgroundColor: Colors.blue[800],
+      ),
+      body: FutureBuilder<List<dynamic>>(
+        future: _payslips,
+        builder: (context, snapshot) {
+          if (snapshot.connectionState == ConnectionState.waiting) {
+            return Center(child: CircularProgressIndicator());
+          }
+          final slips = snapshot.data ?? [];
+          if (slips.isEmpty) {
+            return Center(child: Text("No payslips yet.", style: TextStyle(color: Colors.grey)));
+          }
+          return ListView.separated(
+            itemCount: slips.length,
+            separatorBuilder: (_, __) => Divider(height: 1),
+            itemBuilder: (context, i) {
+              final slip = slips[i];
+              return ListTile(
+                leading: Icon(Icons.receipt_long, color: Colors.blue[800]),
+                title: Text(_period(slip)),
+                subtitle: Text("Net pay ${_money(slip['netPay'])}"),
+                trailing: Icon(Icons.chevron_right),
+                onTap: () => Navigator.push(
+                  context,
+                  MaterialPageRoute(builder: (_) => PayslipDetailScreen(runId: slip['runId'])),
+                ),
+              );
+            },
+          );
+        },
+      ),
+    );
+  }
+}
+
+class PayslipDetailScreen extends StatefulWidget {
+  final String runId;
+
+  PayslipDetailScreen({required this.runId});
+
+  @override
+  _PayslipDetailScreenState createState() => _PayslipDetailScreenState();
+}
+
+class _PayslipDetailScreenState extends State<PayslipDetailScreen> {
+  late Future<Map<String, dynamic>?> _payslip;
+  bool _downloading = false;
+
+  @override
+  void initState() {
+    super.initState();
+    _payslip = Provider.of<ApiService>(context, listen: false).getPayslip(widget.runId);
+  }
+
+  void _download(Map<String, dynamic> slip) async {
+    setState(() => _downloading = true);
+    final apiService = Provider.of<ApiService>(context, listen: false);
+    final file = await apiService.downloadPayslip(
+      widget.runId,
+      'payslip_${slip['year']}_${slip['month']}',
+    );
+    setState(() => _downloading = false);
+
+    ScaffoldMessenger.of(context).showSnackBar(
+      SnackBar(content: Text(file != null ? "Saved to ${file.path}" : "Download failed. Check connection.")),
+    );
+  }
+
+  Widget _section(String title, List<dynamic> rows) {
+    return Column(
+      crossAxisAlignment: CrossAxisAlignment.start,
+      children: [
+        Padding(
+          padding: EdgeInsets.only(top: 16, bottom: 8),
+          child: Text(title, style: TextStyle(fontWeight: FontWeight.bold, color: Colors.blue[800])),
+        ),
+        if (rows.isEmpty) Text("None", style: TextStyle(color: Colors.grey)),
+        ...rows.map((r) => Padding(
+              padding: EdgeInsets.symmetric(vertical: 4),
+              child: Row(
+                mainAxisAlignment: MainAxisAlignment.spaceBetween,
+                children: [Text('${r['label']}'), Text(_money(r['amount']))],
+              ),
+            )),
+      ],
+    );
+  }
+
+  @override
+  Widget build(BuildContext context) {
+    return Scaffold(
+      appBar: AppBar(
+        title: Text("Payslip"),
+        backgroundColor: Colors.blue[800],
+      ),
+      body: FutureBuilder<Map<String, dynamic>?>(
+        future: _payslip,
+        builder: (context, snapshot) {
+          if (snapshot.connectionState == ConnectionState.waiting) {
+            return Center(child: CircularProgressIndicator());
+          }
+          final slip = snapshot.data;
+          if (slip == null) {
+            return Center(child: Text("Payslip not available.", style: TextStyle(color: Colors.grey)));
+          }
+          return ListView(
+            padding: EdgeInsets.all(16),
+            children: [
+              Text(_period(slip), style: TextStyle(fontSize: 22, fontWeight: FontWeight.bold)),
+              Text('${slip['employeeName'] ?? ''} (${slip['employeeId']})', style: TextStyle(color: Colors.grey)),
+              _section("Earnings", slip['earnings'] ?? []),
+              _section("Deductions", slip['deductions'] ?? []),
+              Divider(height: 32),
+              Row(
+                mainAxisAlignment: MainAxisAlignment.spaceBetween,
+                children: [
+                  Text("Net Pay", style: TextStyle(fontSize: 18, fontWeight: FontWeight.bold)),
+                  Text(_money(slip['netPay']), style: TextStyle(fontSize: 18, fontWeight: FontWeight.bold, color: Colors.green[700])),
+                ],
+              ),
+              SizedBox(height: 24),
+              ElevatedButton.icon(
+                icon: _downloading
+                    ? SizedBox(width: 16, height: 16, child: CircularProgressIndicator(strokeWidth: 2))
+                    : Icon(Icons.download),
+                label: Text("Download PDF"),
+                onPressed: _downloading ? null : () => _download(slip),
+              ),
+            ],
+          );
+        },
+      ),
+    );
+  }
+}
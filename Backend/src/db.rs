@@ -315,6 +315,33 @@ impl DbClient {
         .execute(&pool)
         .await?;
 
+        // Coupon validity window, caps and referrer attribution
+        sqlx::query(
+            "ALTER TABLE referral_coupons
+             ADD COLUMN IF NOT EXISTS valid_from DATE,
+             ADD COLUMN IF NOT EXISTS valid_until DATE,
+             ADD COLUMN IF NOT EXISTS per_student_limit INTEGER DEFAULT 0,
+             ADD COLUMN IF NOT EXISTS budget DECIMAL(12,2) DEFAULT 0,
+             ADD COLUMN IF NOT EXISTS discount_given DECIMAL(12,2) DEFAULT 0,
+             ADD COLUMN IF NOT EXISTS min_fee_amount DECIMAL(12,2) DEFAULT 0,
+             ADD COLUMN IF NOT EXISTS max_discount DECIMAL(12,2) DEFAULT 0,
+             ADD COLUMN IF NOT EXISTS referrer_type VARCHAR(50),
+             ADD COLUMN IF NOT EXISTS referrer_id VARCHAR(255),
+             ADD COLUMN IF NOT EXISTS referrer_name TEXT",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "ALTER TABLE coupon_usage_log
+             ADD COLUMN IF NOT EXISTS fee_amount DECIMAL(12,2),
+             ADD COLUMN IF NOT EXISTS referrer_type VARCHAR(50),
+             ADD COLUMN IF NOT EXISTS referrer_id VARCHAR(255),
+             ADD COLUMN IF NOT EXISTS is_admission BOOLEAN DEFAULT FALSE",
+        )
+        .execute(&pool)
+        .await?;
+
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS responsibilities (
                 id SERIAL PRIMARY KEY,
@@ -472,7 +499,8 @@ impl DbClient {
              ADD COLUMN IF NOT EXISTS fee_type VARCHAR(50) DEFAULT 'monthly',
              ADD COLUMN IF NOT EXISTS fee_interval INTEGER DEFAULT 1,
              ADD COLUMN IF NOT EXISTS schedule_type VARCHAR(50) DEFAULT 'daily',
-             ADD COLUMN IF NOT EXISTS schedule_data JSONB DEFAULT '[]'"
+             ADD COLUMN IF NOT EXISTS schedule_data JSONB DEFAULT '[]',
+             ADD COLUMN IF NOT EXISTS subject_teachers JSONB DEFAULT '[]'"
         )
         .execute(&pool)
         .await?;
@@ -552,6 +580,883 @@ impl DbClient {
         .execute(&pool)
         .await?;
 
+        // Working days a leave covers, counted against the employee's balance
+        sqlx::query("ALTER TABLE leave_applications ADD COLUMN IF NOT EXISTS days DECIMAL(6,1)")
+            .execute(&pool)
+            .await?;
+
+        // The same days split by calendar year, for leaves that run over New Year
+        sqlx::query("ALTER TABLE leave_applications ADD COLUMN IF NOT EXISTS days_by_year JSONB")
+            .execute(&pool)
+            .await?;
+
+        // Approval chain snapshot taken when the leave is applied for, the step it is waiting
+        // on, and the full history of decisions, comments and escalations
+        sqlx::query(
+            "ALTER TABLE leave_applications
+             ADD COLUMN IF NOT EXISTS approval_chain JSONB DEFAULT '{}',
+             ADD COLUMN IF NOT EXISTS approval_step INT DEFAULT 0,
+             ADD COLUMN IF NOT EXISTS step_started_at TIMESTAMPTZ DEFAULT NOW(),
+             ADD COLUMN IF NOT EXISTS timeline JSONB DEFAULT '[]'",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Attendance rows an approved leave marked, and the record each replaced so a
+        // cancellation can put it back. Rows marked before the column existed carry the leave ID
+        // in the description.
+        sqlx::query(
+            "ALTER TABLE attendance
+             ADD COLUMN IF NOT EXISTS leave_id VARCHAR(255),
+             ADD COLUMN IF NOT EXISTS pre_leave JSONB",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "UPDATE attendance SET leave_id = description, description = NULL
+             WHERE leave_id IS NULL AND status = 'leave' AND role = 'employee' AND description LIKE 'LV%'",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Leave approval chains per employee type ('*' applies to types without their own)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS leave_approval_chains (
+                school_id VARCHAR(255) NOT NULL,
+                employee_type VARCHAR(100) NOT NULL,
+                steps JSONB NOT NULL DEFAULT '[]',
+                escalate_after_hours INT,
+                updated_by VARCHAR(255),
+                updated_at TIMESTAMPTZ DEFAULT NOW(),
+                PRIMARY KEY (school_id, employee_type)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Approvers handing their approvals to someone else for a date range
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS leave_delegations (
+                delegation_id VARCHAR(255) PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                approver_id VARCHAR(255) NOT NULL,
+                delegate_id VARCHAR(255) NOT NULL,
+                from_date DATE NOT NULL,
+                to_date DATE NOT NULL,
+                reason TEXT,
+                revoked BOOLEAN NOT NULL DEFAULT FALSE,
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // School-wide holidays (also created lazily by the attendance routes)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS school_holidays (
+                id TEXT PRIMARY KEY, school_id TEXT NOT NULL,
+                title TEXT NOT NULL, description TEXT DEFAULT '',
+                from_date TEXT NOT NULL, to_date TEXT NOT NULL,
+                classes JSONB DEFAULT '[]',
+                exempt_employees JSONB DEFAULT '[]',
+                exempt_students JSONB DEFAULT '[]',
+                created_at TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Per-school leave types; codes missing here fall back to the built-in defaults
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS leave_types (
+                school_id VARCHAR(255) NOT NULL,
+                code VARCHAR(50) NOT NULL,
+                name VARCHAR(255) NOT NULL,
+                annual_quota DECIMAL(6,1) NOT NULL DEFAULT 0,
+                accrual VARCHAR(20) NOT NULL DEFAULT 'yearly',
+                carry_forward_max DECIMAL(6,1) NOT NULL DEFAULT 0,
+                paid BOOLEAN NOT NULL DEFAULT TRUE,
+                count_holidays BOOLEAN NOT NULL DEFAULT FALSE,
+                gender VARCHAR(20),
+                active BOOLEAN NOT NULL DEFAULT TRUE,
+                updated_at TIMESTAMPTZ DEFAULT NOW(),
+                PRIMARY KEY (school_id, code)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Carried-forward opening balance and manual adjustments per employee, type and year
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS leave_balances (
+                school_id VARCHAR(255) NOT NULL,
+                employee_id VARCHAR(255) NOT NULL,
+                leave_type VARCHAR(50) NOT NULL,
+                year INT NOT NULL,
+                opening DECIMAL(6,1) NOT NULL DEFAULT 0,
+                adjustment DECIMAL(6,1) NOT NULL DEFAULT 0,
+                updated_at TIMESTAMPTZ DEFAULT NOW(),
+                PRIMARY KEY (school_id, employee_id, leave_type, year)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Fee collection ledger (one row per receipt, feeds the day-book)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS fee_payments (
+                id SERIAL PRIMARY KEY,
+                receipt_no VARCHAR(255) UNIQUE NOT NULL,
+                school_id VARCHAR(255) NOT NULL,
+                student_id VARCHAR(255) NOT NULL,
+                amount DECIMAL(12,2) NOT NULL,
+                payment_mode VARCHAR(50) NOT NULL DEFAULT 'cash',
+                collected_by VARCHAR(255),
+                fee_head VARCHAR(255) NOT NULL DEFAULT 'General',
+                reference TEXT,
+                paid_on DATE NOT NULL DEFAULT CURRENT_DATE,
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_fee_payments_school_date ON fee_payments(school_id, paid_on)",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("CREATE SEQUENCE IF NOT EXISTS fee_receipt_seq")
+            .execute(&pool)
+            .await?;
+        // The fee a receipt paid: a custom fee ID, or 'general' for the running student_fees balance.
+        // Older receipts only have the head's name, so they are matched back to it once.
+        sqlx::query("ALTER TABLE fee_payments ADD COLUMN IF NOT EXISTS fee_id VARCHAR(255)")
+            .execute(&pool)
+            .await?;
+        sqlx::query(
+            "UPDATE fee_payments p SET fee_id = COALESCE(
+                (SELECT cf.fee_id FROM custom_fees cf WHERE cf.school_id = p.school_id AND cf.fee_name = p.fee_head
+                 ORDER BY cf.created_at DESC LIMIT 1),
+                'general')
+             WHERE p.fee_id IS NULL",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Online fee payments: one order per checkout, posted once on a verified callback
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS payment_orders (
+                id SERIAL PRIMARY KEY,
+                order_id VARCHAR(255) UNIQUE NOT NULL,
+                school_id VARCHAR(255) NOT NULL,
+                student_id VARCHAR(255) NOT NULL,
+                amount DECIMAL(12,2) NOT NULL,
+                currency VARCHAR(10) NOT NULL DEFAULT 'INR',
+                gateway VARCHAR(50) NOT NULL,
+                gateway_order_id VARCHAR(255),
+                gateway_payment_id VARCHAR(255),
+                items JSONB NOT NULL DEFAULT '[]',
+                status VARCHAR(50) NOT NULL DEFAULT 'created',
+                receipt JSONB,
+                error TEXT,
+                created_at TIMESTAMPTZ DEFAULT NOW(),
+                paid_at TIMESTAMPTZ
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        // Order IDs come from a sequence so two checkouts in the same millisecond can't clash
+        sqlx::query("CREATE SEQUENCE IF NOT EXISTS payment_order_seq")
+            .execute(&pool)
+            .await?;
+
+        // Bank statement imports and their credit lines awaiting or after reconciliation
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS bank_statement_imports (
+                id SERIAL PRIMARY KEY,
+                import_id VARCHAR(255) UNIQUE NOT NULL,
+                school_id VARCHAR(255) NOT NULL,
+                file_name TEXT,
+                bank_format VARCHAR(50),
+                total_lines INTEGER DEFAULT 0,
+                credit_lines INTEGER DEFAULT 0,
+                posted INTEGER DEFAULT 0,
+                review INTEGER DEFAULT 0,
+                duplicates INTEGER DEFAULT 0,
+                imported_by VARCHAR(255),
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS bank_statement_lines (
+                id SERIAL PRIMARY KEY,
+                line_id VARCHAR(255) UNIQUE NOT NULL,
+                import_id VARCHAR(255) NOT NULL,
+                school_id VARCHAR(255) NOT NULL,
+                txn_date DATE NOT NULL,
+                narration TEXT,
+                reference TEXT,
+                amount DECIMAL(12,2) NOT NULL,
+                line_hash VARCHAR(64) NOT NULL,
+                status VARCHAR(50) NOT NULL DEFAULT 'review',
+                match_method VARCHAR(50),
+                student_id VARCHAR(255),
+                candidates JSONB DEFAULT '[]',
+                reason TEXT,
+                receipt JSONB,
+                resolved_by VARCHAR(255),
+                resolved_at TIMESTAMPTZ,
+                created_at TIMESTAMPTZ DEFAULT NOW(),
+                UNIQUE(school_id, line_hash)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Notifications (templates, delivery log) and fee reminder rules
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS notification_templates (
+                id SERIAL PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                template_key VARCHAR(100) NOT NULL,
+                subject TEXT,
+                body TEXT NOT NULL,
+                updated_at TIMESTAMPTZ DEFAULT NOW(),
+                UNIQUE(school_id, template_key)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS notification_log (
+                id SERIAL PRIMARY KEY,
+                notification_id VARCHAR(255) UNIQUE NOT NULL,
+                school_id VARCHAR(255) NOT NULL,
+                channel VARCHAR(50) NOT NULL,
+                recipient VARCHAR(255) NOT NULL,
+                template_key VARCHAR(100),
+                ref_type VARCHAR(50),
+                ref_id VARCHAR(255),
+                dedupe_key VARCHAR(255),
+                body TEXT NOT NULL,
+                status VARCHAR(50) NOT NULL DEFAULT 'queued',
+                provider_ref TEXT,
+                error TEXT,
+                created_at TIMESTAMPTZ DEFAULT NOW(),
+                UNIQUE(school_id, dedupe_key)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS reminder_rules (
+                id SERIAL PRIMARY KEY,
+                rule_id VARCHAR(255) UNIQUE NOT NULL,
+                school_id VARCHAR(255) NOT NULL,
+                name TEXT NOT NULL,
+                offset_days INTEGER NOT NULL DEFAULT 0,
+                repeat_every_days INTEGER NOT NULL DEFAULT 0,
+                channel VARCHAR(50) NOT NULL DEFAULT 'sms',
+                template_key VARCHAR(100) NOT NULL DEFAULT 'fee_due_reminder',
+                active BOOLEAN NOT NULL DEFAULT TRUE,
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Payroll runs: one per school per month, locked once approved
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS payroll_runs (
+                id SERIAL PRIMARY KEY,
+                run_id VARCHAR(255) UNIQUE NOT NULL,
+                school_id VARCHAR(255) NOT NULL,
+                month INTEGER NOT NULL,
+                year INTEGER NOT NULL,
+                status VARCHAR(50) NOT NULL DEFAULT 'draft',
+                totals JSONB NOT NULL DEFAULT '{}',
+                created_by VARCHAR(255),
+                approved_by VARCHAR(255),
+                approved_at TIMESTAMPTZ,
+                reopened_by VARCHAR(255),
+                reopened_at TIMESTAMPTZ,
+                reopen_reason TEXT,
+                created_at TIMESTAMPTZ DEFAULT NOW(),
+                updated_at TIMESTAMPTZ DEFAULT NOW(),
+                UNIQUE(school_id, month, year)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS payroll_run_lines (
+                id SERIAL PRIMARY KEY,
+                run_id VARCHAR(255) NOT NULL,
+                school_id VARCHAR(255) NOT NULL,
+                employee_id VARCHAR(255) NOT NULL,
+                employee_name TEXT,
+                breakdown JSONB NOT NULL DEFAULT '{}',
+                gross_salary DECIMAL(12,2) NOT NULL DEFAULT 0,
+                deductions DECIMAL(12,2) NOT NULL DEFAULT 0,
+                net_pay DECIMAL(12,2) NOT NULL DEFAULT 0,
+                created_at TIMESTAMPTZ DEFAULT NOW(),
+                UNIQUE(run_id, employee_id)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS payroll_statutory_settings (
+                school_id VARCHAR(255) PRIMARY KEY,
+                settings JSONB NOT NULL DEFAULT '{}',
+                updated_by VARCHAR(255),
+                updated_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS employee_advances (
+                advance_id VARCHAR(255) PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                employee_id VARCHAR(255) NOT NULL,
+                kind VARCHAR(50) NOT NULL DEFAULT 'advance',
+                principal DECIMAL(12,2) NOT NULL,
+                interest_rate DECIMAL(6,2) NOT NULL DEFAULT 0,
+                tenure_months INTEGER NOT NULL DEFAULT 1,
+                emi_amount DECIMAL(12,2) NOT NULL,
+                monthly_cap DECIMAL(12,2),
+                start_month INTEGER NOT NULL,
+                start_year INTEGER NOT NULL,
+                principal_outstanding DECIMAL(12,2) NOT NULL,
+                status VARCHAR(50) NOT NULL DEFAULT 'active',
+                reason TEXT,
+                created_by VARCHAR(255),
+                created_at TIMESTAMPTZ DEFAULT NOW(),
+                updated_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS employee_advance_transactions (
+                id SERIAL PRIMARY KEY,
+                advance_id VARCHAR(255) NOT NULL,
+                school_id VARCHAR(255) NOT NULL,
+                employee_id VARCHAR(255) NOT NULL,
+                kind VARCHAR(50) NOT NULL,
+                amount DECIMAL(12,2) NOT NULL DEFAULT 0,
+                principal_part DECIMAL(12,2) NOT NULL DEFAULT 0,
+                interest_part DECIMAL(12,2) NOT NULL DEFAULT 0,
+                run_id VARCHAR(255),
+                month INTEGER,
+                year INTEGER,
+                note TEXT,
+                created_by VARCHAR(255),
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Balances from the old single `advanceBalance` field become one-installment advances
+        sqlx::query(
+            "INSERT INTO employee_advances (advance_id, school_id, employee_id, principal, emi_amount,
+                start_month, start_year, principal_outstanding, reason, created_by)
+             SELECT 'ADV' || employee_id || '-legacy', school_id, employee_id,
+                    (data->>'advanceBalance')::FLOAT, (data->>'advanceBalance')::FLOAT,
+                    EXTRACT(MONTH FROM NOW())::INT, EXTRACT(YEAR FROM NOW())::INT,
+                    (data->>'advanceBalance')::FLOAT, 'Migrated advance balance', 'system'
+             FROM employees
+             WHERE COALESCE((data->>'advanceBalance')::FLOAT, 0) > 0
+             ON CONFLICT (advance_id) DO NOTHING",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "UPDATE employees SET data = data - 'advanceBalance'
+             WHERE COALESCE((data->>'advanceBalance')::FLOAT, 0) > 0",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS salary_transfer_batches (
+                batch_id VARCHAR(255) PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                run_id VARCHAR(255) NOT NULL,
+                bank_format VARCHAR(50) NOT NULL,
+                debit_account VARCHAR(50) NOT NULL,
+                debit_ifsc VARCHAR(20),
+                payment_date DATE NOT NULL,
+                narration TEXT,
+                status VARCHAR(50) NOT NULL DEFAULT 'generated',
+                line_count INTEGER NOT NULL DEFAULT 0,
+                total_amount DECIMAL(14,2) NOT NULL DEFAULT 0,
+                created_by VARCHAR(255),
+                response_imported_at TIMESTAMPTZ,
+                created_at TIMESTAMPTZ DEFAULT NOW(),
+                updated_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS salary_transfer_lines (
+                id SERIAL PRIMARY KEY,
+                batch_id VARCHAR(255) NOT NULL,
+                school_id VARCHAR(255) NOT NULL,
+                run_id VARCHAR(255) NOT NULL,
+                employee_id VARCHAR(255) NOT NULL,
+                employee_name TEXT,
+                account_number VARCHAR(50) NOT NULL,
+                ifsc VARCHAR(20) NOT NULL,
+                bank_name TEXT,
+                amount DECIMAL(12,2) NOT NULL,
+                mode VARCHAR(10) NOT NULL,
+                reference VARCHAR(255) NOT NULL,
+                status VARCHAR(50) NOT NULL DEFAULT 'pending',
+                utr VARCHAR(255),
+                failure_reason TEXT,
+                updated_at TIMESTAMPTZ DEFAULT NOW(),
+                UNIQUE(batch_id, employee_id)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // An employee is paid through at most one live transfer line per payroll run
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS salary_transfer_lines_run_employee
+             ON salary_transfer_lines (school_id, run_id, employee_id) WHERE status IN ('pending', 'paid')",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS salary_revisions (
+                revision_id VARCHAR(255) PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                employee_id VARCHAR(255) NOT NULL,
+                effective_month INTEGER NOT NULL,
+                effective_year INTEGER NOT NULL,
+                params JSONB NOT NULL DEFAULT '{}',
+                reason TEXT,
+                created_by VARCHAR(255),
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Class timetable periods; day_of_week is 1 (Monday) to 7, NULL for every school day
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS class_periods (
+                id SERIAL PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                class_id VARCHAR(255) NOT NULL,
+                name VARCHAR(255),
+                start_time TIME,
+                end_time TIME,
+                teacher_id VARCHAR(255),
+                subject_id VARCHAR(255),
+                day_of_week SMALLINT,
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "ALTER TABLE class_periods
+             ADD COLUMN IF NOT EXISTS day_of_week SMALLINT,
+             ADD COLUMN IF NOT EXISTS section VARCHAR(255),
+             ADD COLUMN IF NOT EXISTS room_id VARCHAR(255)",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Settings the timetable generator works from, and the timetables it produced
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS timetable_settings (
+                school_id VARCHAR(255) PRIMARY KEY,
+                config JSONB NOT NULL,
+                updated_by VARCHAR(255),
+                updated_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS timetable_drafts (
+                draft_id VARCHAR(50) PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                status VARCHAR(20) NOT NULL DEFAULT 'draft',
+                config JSONB NOT NULL,
+                pins JSONB NOT NULL DEFAULT '[]',
+                entries JSONB NOT NULL DEFAULT '[]',
+                unsatisfied JSONB NOT NULL DEFAULT '[]',
+                stats JSONB NOT NULL DEFAULT '{}',
+                seed BIGINT,
+                created_by VARCHAR(255),
+                created_at TIMESTAMPTZ DEFAULT NOW(),
+                published_by VARCHAR(255),
+                published_at TIMESTAMPTZ
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_timetable_drafts_school ON timetable_drafts (school_id, created_at)")
+            .execute(&pool)
+            .await?;
+
+        // Cover assigned for an absent teacher's period on a given date
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS substitutions (
+                substitution_id VARCHAR(255) PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                date DATE NOT NULL,
+                period_id INTEGER NOT NULL,
+                class_id VARCHAR(255),
+                subject_id VARCHAR(255),
+                start_time TIME,
+                end_time TIME,
+                absent_teacher_id VARCHAR(255) NOT NULL,
+                substitute_id VARCHAR(255) NOT NULL,
+                reason VARCHAR(100),
+                status VARCHAR(20) NOT NULL DEFAULT 'assigned',
+                assigned_by VARCHAR(255),
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // One live substitution per period and day
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS substitutions_period_day
+             ON substitutions (school_id, date, period_id) WHERE status = 'assigned'",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Employee document vault; files live in document storage under storage_key
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS employee_documents (
+                document_id VARCHAR(255) PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                employee_id VARCHAR(255) NOT NULL,
+                document_type VARCHAR(50) NOT NULL,
+                title VARCHAR(255),
+                file_name VARCHAR(255) NOT NULL,
+                content_type VARCHAR(100),
+                size_bytes BIGINT NOT NULL DEFAULT 0,
+                storage_key TEXT NOT NULL,
+                education_id INTEGER,
+                experience_id INTEGER,
+                document_number VARCHAR(100),
+                issued_on DATE,
+                expires_on DATE,
+                verification_status VARCHAR(20) NOT NULL DEFAULT 'pending',
+                verified_by VARCHAR(255),
+                verified_at TIMESTAMPTZ,
+                verification_remarks TEXT,
+                uploaded_by VARCHAR(255),
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS employee_documents_employee
+             ON employee_documents (school_id, employee_id)",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Per-school attendance rules (correction window and the like)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS attendance_settings (
+                school_id VARCHAR(255) PRIMARY KEY,
+                settings JSONB NOT NULL DEFAULT '{}',
+                updated_by VARCHAR(255),
+                updated_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Biometric / RFID terminals; serial numbers are unique across schools since
+        // ADMS terminals identify themselves only by serial
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS attendance_devices (
+                device_id VARCHAR(255) PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                serial_number VARCHAR(100) NOT NULL UNIQUE,
+                name VARCHAR(255),
+                protocol VARCHAR(20) NOT NULL DEFAULT 'adms',
+                location VARCHAR(255),
+                api_key VARCHAR(255) NOT NULL,
+                active BOOLEAN NOT NULL DEFAULT TRUE,
+                last_seen_at TIMESTAMPTZ,
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Enrolled ID (PIN / card number) on the school's terminals to a student or employee
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS device_enrollments (
+                school_id VARCHAR(255) NOT NULL,
+                enroll_id VARCHAR(100) NOT NULL,
+                role VARCHAR(50) NOT NULL,
+                user_id VARCHAR(255) NOT NULL,
+                created_at TIMESTAMPTZ DEFAULT NOW(),
+                PRIMARY KEY (school_id, enroll_id)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Raw punches in terminal local time; role/user_id stay NULL until the ID is enrolled
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS device_punches (
+                id SERIAL PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                device_id VARCHAR(255) NOT NULL,
+                enroll_id VARCHAR(100) NOT NULL,
+                punch_time TIMESTAMP NOT NULL,
+                punch_type VARCHAR(20) NOT NULL DEFAULT 'unknown',
+                verify_mode VARCHAR(50),
+                role VARCHAR(50),
+                user_id VARCHAR(255),
+                raw TEXT,
+                received_at TIMESTAMPTZ DEFAULT NOW(),
+                UNIQUE (device_id, enroll_id, punch_time)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // 'device' on attendance rows built from punches; other rows were marked by hand and
+        // punches leave them alone
+        sqlx::query("ALTER TABLE attendance ADD COLUMN IF NOT EXISTS source VARCHAR(20)")
+            .execute(&pool)
+            .await?;
+
+        // Teacher self check-ins from the app (QR scan or geofence), rejected attempts included
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS self_checkins (
+                id SERIAL PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                employee_id VARCHAR(255) NOT NULL,
+                date DATE NOT NULL,
+                action VARCHAR(10) NOT NULL,
+                method VARCHAR(20) NOT NULL,
+                accepted BOOLEAN NOT NULL,
+                reason TEXT,
+                device_id VARCHAR(255),
+                latitude DOUBLE PRECISION,
+                longitude DOUBLE PRECISION,
+                accuracy_meters DOUBLE PRECISION,
+                distance_meters DOUBLE PRECISION,
+                flags JSONB NOT NULL DEFAULT '[]',
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("ALTER TABLE self_checkins ADD COLUMN IF NOT EXISTS display_id VARCHAR(255)")
+            .execute(&pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_self_checkins_school_date ON self_checkins (school_id, date)")
+            .execute(&pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_self_checkins_device ON self_checkins (school_id, device_id)")
+            .execute(&pool)
+            .await?;
+
+        // Same-day absence alerts to guardians and the reasons they send back
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS absence_alerts (
+                school_id VARCHAR(255) NOT NULL,
+                student_id VARCHAR(255) NOT NULL,
+                date DATE NOT NULL,
+                attendance_status VARCHAR(20) NOT NULL,
+                channel VARCHAR(20) NOT NULL,
+                recipient VARCHAR(255),
+                notification_id VARCHAR(255),
+                status VARCHAR(20) NOT NULL,
+                reply TEXT,
+                replied_via VARCHAR(255),
+                replied_at TIMESTAMPTZ,
+                created_at TIMESTAMPTZ DEFAULT NOW(),
+                PRIMARY KEY (school_id, student_id, date)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("ALTER TABLE notification_log ADD COLUMN IF NOT EXISTS status_updated_at TIMESTAMPTZ")
+            .execute(&pool)
+            .await?;
+        // Sends tried for an alert and when the last one went out, for retry backoff
+        sqlx::query(
+            "ALTER TABLE absence_alerts
+             ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 0,
+             ADD COLUMN IF NOT EXISTS last_attempt_at TIMESTAMPTZ",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Recurring weekly offs (every Sunday, second Saturday...); no row means Sundays only
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS holiday_rules (
+                school_id VARCHAR(255) PRIMARY KEY,
+                rules JSONB NOT NULL DEFAULT '[]',
+                updated_by VARCHAR(255),
+                updated_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Change log written by attendance, fees, payroll and exams; attendance history reads it
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS audit_logs (
+                id SERIAL PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                target_type VARCHAR(50) NOT NULL,
+                target_id VARCHAR(255) NOT NULL,
+                action VARCHAR(100) NOT NULL,
+                data JSONB,
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_logs_target ON audit_logs (school_id, target_type, target_id)")
+            .execute(&pool)
+            .await?;
+
+        // Requests to change attendance, with evidence, waiting for an approver
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS attendance_corrections (
+                request_id VARCHAR(50) PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                role VARCHAR(20) NOT NULL,
+                user_id VARCHAR(255) NOT NULL,
+                date DATE NOT NULL,
+                requested JSONB NOT NULL,
+                current_record JSONB,
+                locked BOOLEAN NOT NULL DEFAULT FALSE,
+                reason TEXT NOT NULL,
+                evidence JSONB NOT NULL DEFAULT '[]',
+                status VARCHAR(20) NOT NULL DEFAULT 'pending',
+                requested_by VARCHAR(255),
+                requested_via VARCHAR(20),
+                decided_by VARCHAR(255),
+                decision_note TEXT,
+                decided_at TIMESTAMPTZ,
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_attendance_corrections_status ON attendance_corrections (school_id, status)")
+            .execute(&pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_attendance_corrections_user ON attendance_corrections (school_id, user_id, date)")
+            .execute(&pool)
+            .await?;
+
+        // Student attendance per timetable period; the subject is kept as it was when marked
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS period_attendance (
+                id SERIAL PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                date DATE NOT NULL,
+                period_id INTEGER NOT NULL,
+                class_id VARCHAR(255) NOT NULL,
+                subject_id VARCHAR(255),
+                student_id VARCHAR(255) NOT NULL,
+                status VARCHAR(20) NOT NULL,
+                remarks TEXT,
+                marked_by VARCHAR(255),
+                created_at TIMESTAMPTZ DEFAULT NOW(),
+                updated_at TIMESTAMPTZ DEFAULT NOW(),
+                UNIQUE(school_id, date, period_id, student_id)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_period_attendance_class ON period_attendance (school_id, class_id, date)")
+            .execute(&pool)
+            .await?;
+
+        // Academic sessions; classes, fees and exams carry the session they belong to
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS academic_sessions (
+                id SERIAL PRIMARY KEY,
+                session_id VARCHAR(255) UNIQUE NOT NULL,
+                school_id VARCHAR(255) NOT NULL,
+                name VARCHAR(100) NOT NULL,
+                start_date DATE NOT NULL,
+                end_date DATE NOT NULL,
+                status VARCHAR(20) NOT NULL DEFAULT 'upcoming',
+                created_by VARCHAR(255),
+                created_at TIMESTAMPTZ DEFAULT NOW(),
+                archived_by VARCHAR(255),
+                archived_at TIMESTAMPTZ,
+                UNIQUE(school_id, name)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("ALTER TABLE classes ADD COLUMN IF NOT EXISTS session_id VARCHAR(255)")
+            .execute(&pool)
+            .await?;
+        sqlx::query("ALTER TABLE custom_fees ADD COLUMN IF NOT EXISTS session_id VARCHAR(255)")
+            .execute(&pool)
+            .await?;
+        sqlx::query("ALTER TABLE IF EXISTS exams ADD COLUMN IF NOT EXISTS session_id VARCHAR(255)")
+            .execute(&pool)
+            .await?;
+
+        // Each student's class in a session and the year-end decision taken for them
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS session_enrollments (
+                id SERIAL PRIMARY KEY,
+                school_id VARCHAR(255) NOT NULL,
+                session_id VARCHAR(255) NOT NULL,
+                student_id VARCHAR(255) NOT NULL,
+                class_id VARCHAR(255) NOT NULL,
+                section VARCHAR(50),
+                roll_number INTEGER,
+                outcome VARCHAR(20),
+                next_class_id VARCHAR(255),
+                next_section VARCHAR(50),
+                carried_balance DECIMAL(12,2) NOT NULL DEFAULT 0,
+                decided_by VARCHAR(255),
+                decided_at TIMESTAMPTZ,
+                UNIQUE(school_id, session_id, student_id)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
         println!("Connecting to Redis...");
 
         let cfg = Config::from_url(redis_url);
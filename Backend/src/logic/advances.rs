@@ -0,0 +1,84 @@
+use serde_json::{json, Value};
+
+fn round2(v: f64) -> f64 {
+    (v * 100.0).round() / 100.0
+}
+
+fn num(v: &Value) -> f64 {
+    v.as_f64().unwrap_or(0.0)
+}
+
+/// Reducing-balance EMI for `principal` over `months` at `annual_rate` percent.
+pub fn emi(principal: f64, annual_rate: f64, months: u32) -> f64 {
+    let n = months.max(1) as f64;
+    let r = annual_rate / 1200.0;
+    if r <= 0.0 {
+        return round2(principal / n);
+    }
+    let f = (1.0 + r).powf(n);
+    round2(principal * r * f / (f - 1.0))
+}
+
+/// This month's interest on the advance's outstanding principal.
+pub fn monthly_interest(advance: &Value) -> f64 {
+    round2(num(&advance["principalOutstanding"]) * num(&advance["interestRate"]) / 1200.0)
+}
+
+/// Installment payroll should recover for `month`/`year`, as `(due, interest)`.
+///
+/// Nothing is due before the start month or while the advance is paused or closed. The
+/// installment is the EMI (less in the final month) limited by the monthly recovery cap.
+pub fn installment_due(advance: &Value, month: i32, year: i32) -> Option<(f64, f64)> {
+    if advance["status"] != "active" {
+        return None;
+    }
+    let start = advance["startYear"].as_i64().unwrap_or(0) * 12 + advance["startMonth"].as_i64().unwrap_or(0);
+    if (year as i64) * 12 + (month as i64) < start {
+        return None;
+    }
+    let principal = num(&advance["principalOutstanding"]);
+    if principal <= 0.0 {
+        return None;
+    }
+    let interest = monthly_interest(advance);
+    let mut due = num(&advance["emiAmount"]).min(principal + interest);
+    let cap = num(&advance["monthlyCap"]);
+    if cap > 0.0 {
+        due = due.min(cap);
+    }
+    Some((round2(due), interest.min(due)))
+}
+
+/// Projected installments from `month`/`year` until the outstanding principal is repaid.
+pub fn project_schedule(advance: &Value, month: i32, year: i32) -> Vec<Value> {
+    let start = advance["startYear"].as_i64().unwrap_or(0) as i32 * 12 + advance["startMonth"].as_i64().unwrap_or(0) as i32;
+    let mut key = (year * 12 + month).max(start);
+    let mut adv = advance.clone();
+    adv["status"] = json!("active");
+    let mut rows = Vec::new();
+    // Guard against a cap below the monthly interest, which would never repay
+    while rows.len() < 360 {
+        let (m, y) = ((key - 1) % 12 + 1, (key - 1) / 12);
+        let (due, interest) = match installment_due(&adv, m, y) {
+            Some(d) => d,
+            None => break,
+        };
+        let principal_part = round2(due - interest);
+        if principal_part <= 0.0 {
+            break;
+        }
+        let balance = round2(num(&adv["principalOutstanding"]) - principal_part).max(0.0);
+        adv["principalOutstanding"] = json!(balance);
+        rows.push(json!({
+            "installment": rows.len() + 1,
+            "month": m,
+            "year": y,
+            "amount": due,
+            "principal": principal_part,
+            "interest": interest,
+            "balance": balance
+        }));
+        key += 1;
+    }
+    rows
+}
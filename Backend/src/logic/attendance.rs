@@ -0,0 +1,175 @@
+use chrono::{DateTime, Duration, NaiveDateTime, NaiveTime, Utc};
+use serde_json::{json, Value};
+
+/// Attendance rules used until a school saves its own. `correctionWindowHours` is how long
+/// after attendance is first taken that it can still be edited directly; after that it is
+/// locked and only changes through an approved correction request. The shift times
+/// (local `HH:MM`) and grace periods decide when a punch-in is late or a punch-out early, and
+/// punches closer together than `minPunchGapMinutes` count as one.
+///
+/// `selfCheckIn` governs teachers checking in from the app: the QR code on display rotates every
+/// `qrRotationSeconds` (the previous code stays valid for one more window), `geofence` is
+/// `{latitude, longitude, radiusMeters}` and stays `null` until the school sets its location,
+/// fixes less accurate than `maxLocationAccuracyMeters` are flagged, and a phone counts as
+/// shared when another teacher used it within `sharedDeviceLookbackDays`.
+///
+/// `subjectAttendanceThreshold` is the per-subject percentage (75 for most boards) below which
+/// a student shows on the exam eligibility report.
+///
+/// `absenceAlerts` (off until a school turns it on) messages guardians on `channel` once
+/// `cutoffTime` has passed for students marked absent and, with `includeUnmarked`, for students
+/// left unmarked in a class whose attendance was taken.
+pub fn default_settings() -> Value {
+    json!({
+        "correctionWindowHours": 48,
+        "shiftStart": "08:00",
+        "shiftEnd": "14:00",
+        "lateGraceMinutes": 10,
+        "earlyLeaveGraceMinutes": 10,
+        "minPunchGapMinutes": 5,
+        "subjectAttendanceThreshold": 75,
+        "selfCheckIn": {
+            "enabled": true,
+            "qrRotationSeconds": 30,
+            "geofence": null,
+            "maxLocationAccuracyMeters": 100,
+            "sharedDeviceLookbackDays": 30
+        },
+        "absenceAlerts": {
+            "enabled": false,
+            "cutoffTime": "10:00",
+            "channel": "sms",
+            "includeUnmarked": true
+        }
+    })
+}
+
+/// Statuses a correction request can ask for; leave goes through leave applications.
+pub const CORRECTABLE_STATUSES: [&str; 4] = ["present", "absent", "late", "half_day"];
+
+/// Whether a record first saved at `created_at` (RFC 3339) is past the correction window.
+/// Without a window nothing locks.
+pub fn correction_locked(settings: &Value, created_at: Option<&str>, now: DateTime<Utc>) -> bool {
+    let Some(hours) = settings["correctionWindowHours"].as_f64() else { return false };
+    created_at
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .is_some_and(|t| now > t.with_timezone(&Utc) + Duration::minutes((hours * 60.0) as i64))
+}
+
+/// A history entry for a change to one day's attendance: the record before and after (`null`
+/// when there was none or it was removed), who made the change and why.
+pub fn change_entry(date: &str, before: Option<&Value>, after: Option<&Value>, changed_by: Option<&str>, reason: Option<&str>) -> Value {
+    json!({"date": date, "before": before, "after": after, "changedBy": changed_by, "reason": reason})
+}
+
+/// Checks an `absenceAlerts` settings block before it is saved.
+pub fn validate_absence_alerts(block: &Value) -> Result<(), String> {
+    if block["cutoffTime"].as_str().and_then(|t| NaiveTime::parse_from_str(t, "%H:%M").ok()).is_none() {
+        return Err("absenceAlerts.cutoffTime must be HH:MM".to_string());
+    }
+    match block["channel"].as_str() {
+        Some("sms" | "whatsapp" | "email" | "push") => Ok(()),
+        _ => Err("absenceAlerts.channel must be sms, whatsapp, email or push".to_string()),
+    }
+}
+
+/// Overlays a school's saved settings on the defaults, key by key.
+pub fn merge_settings(saved: &Value) -> Value {
+    let mut merged = default_settings();
+    if let (Some(target), Some(src)) = (merged.as_object_mut(), saved.as_object()) {
+        for (k, v) in src {
+            target.insert(k.clone(), v.clone());
+        }
+    }
+    merged
+}
+
+/// Statuses a teacher can mark for a student.
+pub const CLASS_STATUSES: &[&str] = &["present", "absent", "late", "half_day"];
+
+/// Accepts the spellings clients send (`half-day`, `Half Day`, `P`/`A`/`L`/`H`) and returns the
+/// stored status.
+pub fn normalize_status(status: &str) -> Option<&'static str> {
+    match status.trim().to_lowercase().replace(['-', ' '], "_").as_str() {
+        "present" | "p" => Some("present"),
+        "absent" | "a" => Some("absent"),
+        "late" | "l" => Some("late"),
+        "half_day" | "halfday" | "h" => Some("half_day"),
+        _ => None,
+    }
+}
+
+/// Attendance percentage where a half day counts as half and late counts as present.
+pub fn attendance_percent(present: usize, late: usize, half_day: usize, marked: usize) -> f64 {
+    if marked == 0 {
+        return 0.0;
+    }
+    let attended = present as f64 + late as f64 + half_day as f64 * 0.5;
+    (attended * 10000.0 / marked as f64).round() / 100.0
+}
+
+/// Counts per status over `{status}` rows, with the attendance percentage.
+pub fn status_summary(rows: &[Value]) -> Value {
+    let count = |s: &str| rows.iter().filter(|r| r["status"] == s).count();
+    let (present, absent, late, half_day) = (count("present"), count("absent"), count("late"), count("half_day"));
+    let marked = present + absent + late + half_day;
+    json!({
+        "marked": marked,
+        "present": present,
+        "absent": absent,
+        "late": late,
+        "halfDay": half_day,
+        "attendancePercent": attendance_percent(present, late, half_day, marked)
+    })
+}
+
+fn shift_time(settings: &Value, key: &str) -> Option<NaiveTime> {
+    settings[key].as_str().and_then(|t| NaiveTime::parse_from_str(t, "%H:%M").ok())
+}
+
+fn minutes(settings: &Value, key: &str) -> i64 {
+    settings[key].as_i64().unwrap_or(0)
+}
+
+/// Attendance for one person and day from their punch times (local). The first punch is the
+/// check-in; the last is the check-out if it comes at least `minPunchGapMinutes` later.
+/// Returns `{inTime, outTime, totalMinutes, status: present|late, late, earlyLeave}`.
+pub fn from_punches(punches: &[NaiveDateTime], settings: &Value) -> Option<Value> {
+    let first = *punches.iter().min()?;
+    let last = *punches.iter().max()?;
+    let out = (last - first >= Duration::minutes(minutes(settings, "minPunchGapMinutes"))).then_some(last);
+
+    let late = shift_time(settings, "shiftStart")
+        .is_some_and(|start| first.time() > start + Duration::minutes(minutes(settings, "lateGraceMinutes")));
+    let early_leave = match (out, shift_time(settings, "shiftEnd")) {
+        (Some(o), Some(end)) => o.time() < end - Duration::minutes(minutes(settings, "earlyLeaveGraceMinutes")),
+        _ => false,
+    };
+    Some(json!({
+        "inTime": first,
+        "outTime": out,
+        "totalMinutes": out.map(|o| (o - first).num_minutes()),
+        "status": if late { "late" } else { "present" },
+        "late": late,
+        "earlyLeave": early_leave
+    }))
+}
+
+/// Register cell for a stored status. Days off are shown by the caller as `S` (Sunday), `WO`
+/// (another weekly off) or `H` (holiday).
+pub fn register_code(status: &str) -> &'static str {
+    match status {
+        "present" => "P",
+        "absent" => "A",
+        "late" => "L",
+        "half_day" => "HD",
+        "leave" => "LV",
+        "holiday" => "H",
+        _ => "?",
+    }
+}
+
+/// `Xh Ym`, the format attendance `total_time` is stored in.
+pub fn format_duration(total_minutes: i64) -> String {
+    format!("{}h {}m", total_minutes / 60, total_minutes % 60)
+}
@@ -0,0 +1,260 @@
+use calamine::{open_workbook_auto_from_rs, Data, Reader};
+use chrono::{Datelike, NaiveDate};
+use std::io::Cursor;
+
+/// One transaction row from a bank statement, normalised across bank layouts.
+#[derive(Debug, Clone)]
+pub struct StatementLine {
+    pub date: NaiveDate,
+    pub narration: String,
+    pub reference: String,
+    pub credit: f64,
+    pub debit: f64,
+}
+
+pub struct ParsedStatement {
+    /// Which header layout was recognised, e.g. "sbi", "hdfc" or "generic".
+    pub format: String,
+    pub lines: Vec<StatementLine>,
+}
+
+/// Column positions found in a statement's header row.
+#[derive(Default)]
+struct Columns {
+    date: Option<usize>,
+    narration: Option<usize>,
+    reference: Option<usize>,
+    credit: Option<usize>,
+    debit: Option<usize>,
+    /// Single amount column with a separate Dr/Cr marker (some ICICI/PNB exports).
+    amount: Option<usize>,
+    dr_cr: Option<usize>,
+}
+
+pub(crate) fn normalise(h: &str) -> String {
+    h.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == ' ')
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn detect_columns(row: &[String]) -> Option<Columns> {
+    let mut c = Columns::default();
+    for (i, raw) in row.iter().enumerate() {
+        let h = normalise(raw);
+        if h.is_empty() {
+            continue;
+        }
+        let is_txn_date = h == "date" || h.contains("txn date") || h.contains("tran date") || h.contains("transaction date");
+        let is_value_date = h.starts_with("value d");
+        // The transaction date wins over the value date when both are present
+        if is_txn_date || is_value_date {
+            if is_txn_date || c.date.is_none() {
+                c.date = Some(i);
+            }
+        } else if h.contains("narration") || h.contains("description") || h.contains("particulars") || h.contains("remarks") || h == "details" {
+            c.narration = Some(i);
+        } else if h.contains("ref") || h.contains("chq") || h.contains("cheque") || h.contains("utr") {
+            c.reference.get_or_insert(i);
+        } else if h.contains("deposit") || h == "credit" || h.starts_with("credit ") || h == "cr" || h.contains("credit amount") {
+            c.credit = Some(i);
+        } else if h.contains("withdrawal") || h == "debit" || h.starts_with("debit ") || h == "dr" || h.contains("debit amount") {
+            c.debit = Some(i);
+        } else if h == "drcr" || h == "dr cr" || h == "cr dr" || h == "type" {
+            c.dr_cr = Some(i);
+        } else if h == "amount" || h.starts_with("amount ") || h.contains("transaction amount") {
+            c.amount = Some(i);
+        }
+    }
+    let has_amount = c.credit.is_some() || (c.amount.is_some() && c.dr_cr.is_some());
+    if c.date.is_some() && c.narration.is_some() && has_amount {
+        Some(c)
+    } else {
+        None
+    }
+}
+
+fn guess_format(header: &[String]) -> String {
+    let h = header.iter().map(|s| normalise(s)).collect::<Vec<_>>().join("|");
+    if h.contains("txn date") && h.contains("ref no") {
+        "sbi"
+    } else if h.contains("narration") && h.contains("withdrawal amt") {
+        "hdfc"
+    } else if h.contains("transaction remarks") {
+        "icici"
+    } else if h.contains("tran date") && h.contains("particulars") {
+        "axis"
+    } else if h.contains("withdrawal dr") || h.contains("deposit cr") {
+        "kotak"
+    } else {
+        "generic"
+    }
+    .to_string()
+}
+
+/// Parses `12,345.00`, `1,23,456.50 Cr`, `-500`, `(500.00)` and empty cells. A leading minus
+/// or accounting brackets make the amount negative; empty or unreadable cells are zero.
+pub(crate) fn parse_amount(s: &str) -> f64 {
+    let s = s.trim();
+    let negative = s.starts_with('-') || (s.starts_with('(') && s.ends_with(')'));
+    let cleaned: String = s.chars().filter(|c| c.is_ascii_digit() || *c == '.').collect();
+    let amount = cleaned.parse::<f64>().unwrap_or(0.0);
+    if negative {
+        -amount
+    } else {
+        amount
+    }
+}
+
+pub fn parse_date(s: &str) -> Option<NaiveDate> {
+    let s = s.trim();
+    // Drop a time part such as "01/04/2024 10:15:00"
+    let s = s.split_whitespace().take(3).collect::<Vec<_>>();
+    let joined = s.join(" ");
+    let candidates = [joined.as_str(), s.first().copied().unwrap_or("")];
+    const FORMATS: [&str; 11] = [
+        "%d/%m/%Y", "%d-%m-%Y", "%d.%m.%Y", "%d/%m/%y", "%d-%m-%y", "%Y-%m-%d",
+        "%d-%b-%Y", "%d %b %Y", "%d-%b-%y", "%d %b %y", "%d/%b/%Y",
+    ];
+    for c in candidates {
+        for f in FORMATS {
+            // `%Y` also accepts "24", so two-digit years fall through to the `%y` formats
+            match NaiveDate::parse_from_str(c, f) {
+                Ok(d) if d.year() >= 1970 => return Some(d),
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
+fn cell_to_string(d: &Data) -> String {
+    match d {
+        Data::Empty => String::new(),
+        Data::String(s) => s.clone(),
+        Data::Float(f) => f.to_string(),
+        Data::Int(i) => i.to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(dt) => dt
+            .as_datetime()
+            .map(|d| d.date().format("%Y-%m-%d").to_string())
+            .unwrap_or_default(),
+        Data::DateTimeIso(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+pub(crate) fn read_rows(file_name: &str, bytes: &[u8]) -> Result<Vec<Vec<String>>, String> {
+    let lower = file_name.to_lowercase();
+    let is_excel = lower.ends_with(".xlsx")
+        || lower.ends_with(".xls")
+        || lower.ends_with(".xlsb")
+        || lower.ends_with(".ods")
+        || bytes.starts_with(b"PK\x03\x04")
+        || bytes.starts_with(&[0xD0, 0xCF, 0x11, 0xE0]);
+
+    if is_excel {
+        let mut workbook = open_workbook_auto_from_rs(Cursor::new(bytes.to_vec()))
+            .map_err(|e| format!("Could not open spreadsheet: {}", e))?;
+        let range = workbook
+            .worksheet_range_at(0)
+            .ok_or("Spreadsheet has no sheets")?
+            .map_err(|e| format!("Could not read sheet: {}", e))?;
+        return Ok(range
+            .rows()
+            .map(|r| r.iter().map(cell_to_string).collect())
+            .collect());
+    }
+
+    let text = String::from_utf8_lossy(bytes);
+    let delimiter = if text.lines().take(30).any(|l| l.matches('\t').count() >= 3) { b'\t' } else { b',' };
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(text.as_bytes());
+    let mut rows = Vec::new();
+    for rec in reader.records() {
+        let rec = rec.map_err(|e| format!("Invalid CSV: {}", e))?;
+        rows.push(rec.iter().map(|s| s.to_string()).collect());
+    }
+    Ok(rows)
+}
+
+/// Reads a bank statement export (CSV/TSV or Excel) and returns its transaction rows.
+/// Banner rows above the header (account details, period) and footer totals are skipped.
+pub fn parse_statement(file_name: &str, bytes: &[u8]) -> Result<ParsedStatement, String> {
+    let rows = read_rows(file_name, bytes)?;
+    let (header_idx, cols) = rows
+        .iter()
+        .enumerate()
+        .take(60)
+        .find_map(|(i, r)| detect_columns(r).map(|c| (i, c)))
+        .ok_or("Could not find the transaction header (date, narration and credit columns)")?;
+    let format = guess_format(&rows[header_idx]);
+
+    let get = |row: &[String], idx: Option<usize>| -> String {
+        idx.and_then(|i| row.get(i)).map(|s| s.trim().to_string()).unwrap_or_default()
+    };
+
+    let mut lines = Vec::new();
+    for row in rows.iter().skip(header_idx + 1) {
+        let date = match parse_date(&get(row, cols.date)) {
+            Some(d) => d,
+            None => continue,
+        };
+        let (credit, debit) = match (cols.credit, cols.amount, cols.dr_cr) {
+            (Some(_), _, _) => {
+                // A negative credit is money going out (a reversal), and a negative debit money coming in
+                let (credit, debit) = (parse_amount(&get(row, cols.credit)), parse_amount(&get(row, cols.debit)));
+                (credit.max(0.0) - debit.min(0.0), debit.max(0.0) - credit.min(0.0))
+            }
+            (None, Some(_), Some(_)) => {
+                let amount = parse_amount(&get(row, cols.amount)).abs();
+                if get(row, cols.dr_cr).to_lowercase().starts_with('c') {
+                    (amount, 0.0)
+                } else {
+                    (0.0, amount)
+                }
+            }
+            _ => (0.0, 0.0),
+        };
+        if credit == 0.0 && debit == 0.0 {
+            continue;
+        }
+        lines.push(StatementLine {
+            date,
+            narration: get(row, cols.narration),
+            reference: get(row, cols.reference),
+            credit,
+            debit,
+        });
+    }
+
+    Ok(ParsedStatement { format, lines })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_amount_formats() {
+        assert_eq!(parse_amount("12,345.00"), 12345.0);
+        assert_eq!(parse_amount("1,23,456.50 Cr"), 123456.5);
+        assert_eq!(parse_amount("(500.00)"), -500.0);
+        assert_eq!(parse_amount("-75"), -75.0);
+        assert_eq!(parse_amount(""), 0.0);
+    }
+
+    #[test]
+    fn negative_credit_is_read_as_a_debit() {
+        let csv = "Date,Narration,Credit,Debit\n01/04/2024,Reversal,(500.00),\n02/04/2024,Fee STU1001,1000,\n";
+        let parsed = parse_statement("statement.csv", csv.as_bytes()).expect("parses");
+        let amounts: Vec<(f64, f64)> = parsed.lines.iter().map(|l| (l.credit, l.debit)).collect();
+        assert_eq!(amounts, vec![(0.0, 500.0), (1000.0, 0.0)]);
+    }
+}
@@ -0,0 +1,152 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde_json::{json, Value};
+
+/// One punch read from a terminal, in the terminal's local time.
+#[derive(Debug, Clone)]
+pub struct Punch {
+    pub enroll_id: String,
+    pub time: NaiveDateTime,
+    /// `in`, `out` or `unknown`
+    pub punch_type: &'static str,
+    pub verify_mode: Option<String>,
+    pub raw: String,
+}
+
+/// ZKTeco attendance states: 0 check-in, 1 check-out, 2 break-out, 3 break-in, 4 overtime-in,
+/// 5 overtime-out.
+fn adms_punch_type(state: &str) -> &'static str {
+    match state.trim() {
+        "0" | "3" | "4" => "in",
+        "1" | "2" | "5" => "out",
+        _ => "unknown",
+    }
+}
+
+/// ZKTeco verify modes as reported in ATTLOG.
+fn adms_verify_mode(mode: &str) -> Option<String> {
+    let name = match mode.trim() {
+        "" => return None,
+        "0" => "password",
+        "1" => "fingerprint",
+        "2" | "4" => "card",
+        "15" => "face",
+        other => other,
+    };
+    Some(name.to_string())
+}
+
+fn parse_time(s: &str) -> Option<NaiveDateTime> {
+    let s = s.trim();
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M"))
+        .ok()
+        .or_else(|| chrono::DateTime::parse_from_rfc3339(s).ok().map(|t| t.with_timezone(&chrono::Local).naive_local()))
+}
+
+/// Parses an ADMS (iClock push) `ATTLOG` body: one punch per line,
+/// `PIN \t YYYY-MM-DD HH:MM:SS \t state \t verify \t workcode ...`. Lines that can't be read
+/// are returned separately so the device can still be acknowledged.
+pub fn parse_adms_attlog(body: &str) -> (Vec<Punch>, Vec<String>) {
+    let mut punches = Vec::new();
+    let mut rejected = Vec::new();
+    for line in body.lines().map(|l| l.trim_end_matches('\r')).filter(|l| !l.trim().is_empty()) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let parsed = match (fields.first(), fields.get(1).and_then(|t| parse_time(t))) {
+            (Some(pin), Some(time)) if !pin.trim().is_empty() => Some(Punch {
+                enroll_id: pin.trim().to_string(),
+                time,
+                punch_type: fields.get(2).map(|s| adms_punch_type(s)).unwrap_or("unknown"),
+                verify_mode: fields.get(3).and_then(|m| adms_verify_mode(m)),
+                raw: line.to_string(),
+            }),
+            _ => None,
+        };
+        match parsed {
+            Some(p) => punches.push(p),
+            None => rejected.push(line.to_string()),
+        }
+    }
+    (punches, rejected)
+}
+
+/// Parses the generic JSON format: `[{ enrollId, time, type?: in|out, verifyMode? }]` where
+/// `time` is local `YYYY-MM-DD HH:MM:SS` or RFC 3339.
+pub fn parse_json_punches(list: &[Value]) -> (Vec<Punch>, Vec<String>) {
+    let mut punches = Vec::new();
+    let mut rejected = Vec::new();
+    for p in list {
+        let enroll_id = p["enrollId"]
+            .as_str()
+            .map(|s| s.trim().to_string())
+            .or_else(|| p["enrollId"].as_i64().map(|n| n.to_string()))
+            .filter(|s| !s.is_empty());
+        match (enroll_id, p["time"].as_str().and_then(parse_time)) {
+            (Some(enroll_id), Some(time)) => punches.push(Punch {
+                enroll_id,
+                time,
+                punch_type: match p["type"].as_str() {
+                    Some("in") => "in",
+                    Some("out") => "out",
+                    _ => "unknown",
+                },
+                verify_mode: p["verifyMode"].as_str().map(|s| s.to_string()),
+                raw: p.to_string(),
+            }),
+            _ => rejected.push(p.to_string()),
+        }
+    }
+    (punches, rejected)
+}
+
+/// Reply to the ADMS handshake (`GET /iclock/cdata?SN=...`): tells the terminal to push
+/// attendance logs in real time and not to send user or photo data.
+pub fn adms_handshake(serial: &str) -> String {
+    [
+        format!("GET OPTION FROM: {}", serial),
+        "ATTLOGStamp=None".to_string(),
+        "OPERLOGStamp=9999".to_string(),
+        "ATTPHOTOStamp=None".to_string(),
+        "ErrorDelay=30".to_string(),
+        "Delay=10".to_string(),
+        "TransTimes=00:00;14:05".to_string(),
+        "TransInterval=1".to_string(),
+        "TransFlag=TransData AttLog".to_string(),
+        "TimeZone=0".to_string(),
+        "Realtime=1".to_string(),
+        "Encrypt=None".to_string(),
+    ]
+    .join("\n")
+}
+
+/// Builds an ADMS `ATTLOG` body like a terminal would send for `date`: a check-in and a
+/// check-out per enrolled ID. `jitter` returns a pseudo-random offset in minutes (0..range);
+/// roughly one in five check-ins lands after the grace period and one in ten leaves early.
+pub fn simulate_attlog(
+    enroll_ids: &[String],
+    date: NaiveDate,
+    shift_start: NaiveTime,
+    shift_end: NaiveTime,
+    mut jitter: impl FnMut(i64) -> i64,
+) -> String {
+    let mut lines = Vec::new();
+    for pin in enroll_ids {
+        let arrive = if jitter(5) == 0 { jitter(40) + 15 } else { -jitter(20) };
+        let leave = if jitter(10) == 0 { -(jitter(60) + 20) } else { jitter(30) };
+        let check_in = date.and_time(shift_start) + chrono::Duration::minutes(arrive);
+        let check_out = date.and_time(shift_end) + chrono::Duration::minutes(leave);
+        let verify = if jitter(2) == 0 { "1" } else { "4" };
+        lines.push(format!("{}\t{}\t0\t{}\t0\t0", pin, check_in.format("%Y-%m-%d %H:%M:%S"), verify));
+        lines.push(format!("{}\t{}\t1\t{}\t0\t0", pin, check_out.format("%Y-%m-%d %H:%M:%S"), verify));
+    }
+    lines.join("\n")
+}
+
+pub fn punch_json(p: &Punch) -> Value {
+    json!({
+        "enrollId": p.enroll_id,
+        "time": p.time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        "type": p.punch_type,
+        "verifyMode": p.verify_mode
+    })
+}
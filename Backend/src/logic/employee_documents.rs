@@ -0,0 +1,108 @@
+use chrono::{Months, NaiveDate};
+use serde_json::{json, Value};
+
+/// Document types the vault knows about. `required` types must be on file for every employee;
+/// `linkedTo` types are needed once per education (`education`) or past job (`experience`)
+/// record. `validityMonths` sets the expiry from the issue date when none is given.
+pub fn document_types() -> Vec<Value> {
+    vec![
+        json!({"code": "id_proof", "name": "ID Proof", "required": true, "linkedTo": null, "validityMonths": null}),
+        json!({"code": "address_proof", "name": "Address Proof", "required": false, "linkedTo": null, "validityMonths": null}),
+        json!({"code": "degree", "name": "Degree / Certificate", "required": false, "linkedTo": "education", "validityMonths": null}),
+        json!({"code": "experience_letter", "name": "Experience Letter", "required": false, "linkedTo": "experience", "validityMonths": null}),
+        json!({"code": "police_verification", "name": "Police Verification", "required": true, "linkedTo": null, "validityMonths": 36}),
+        json!({"code": "medical_fitness", "name": "Medical Fitness Certificate", "required": false, "linkedTo": null, "validityMonths": 12}),
+        json!({"code": "other", "name": "Other", "required": false, "linkedTo": null, "validityMonths": null}),
+    ]
+}
+
+pub fn document_type(code: &str) -> Option<Value> {
+    document_types().into_iter().find(|t| t["code"] == code)
+}
+
+/// Expiry for a new document: the one given, else the issue date plus the type's validity.
+pub fn expiry_date(doc_type: &Value, issued_on: Option<NaiveDate>, expires_on: Option<NaiveDate>) -> Option<NaiveDate> {
+    expires_on.or_else(|| {
+        let months = doc_type["validityMonths"].as_u64()?;
+        issued_on?.checked_add_months(Months::new(months as u32))
+    })
+}
+
+fn date(v: &Value) -> Option<NaiveDate> {
+    v.as_str().and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+}
+
+/// `valid`, `expiring` (within `within_days` of `as_of`) or `expired`.
+pub fn expiry_state(doc: &Value, as_of: NaiveDate, within_days: i64) -> &'static str {
+    match date(&doc["expiresOn"]) {
+        Some(d) if d < as_of => "expired",
+        Some(d) if (d - as_of).num_days() <= within_days => "expiring",
+        _ => "valid",
+    }
+}
+
+/// The document that currently stands for a requirement: the newest one not rejected,
+/// falling back to the newest rejected one so the rejection is reported.
+fn current<'a>(docs: &[&'a Value]) -> Option<&'a Value> {
+    let newest = |pred: &dyn Fn(&Value) -> bool| {
+        docs.iter()
+            .filter(|d| pred(d))
+            .max_by(|a, b| a["createdAt"].as_str().cmp(&b["createdAt"].as_str()))
+            .copied()
+    };
+    newest(&|d| d["verificationStatus"] != "rejected").or_else(|| newest(&|_| true))
+}
+
+fn issue(requirement: Value, doc: Option<&Value>, as_of: NaiveDate, within_days: i64) -> Option<Value> {
+    let problem = match doc {
+        None => "missing",
+        Some(d) if d["verificationStatus"] == "rejected" => "rejected",
+        Some(d) => match expiry_state(d, as_of, within_days) {
+            "expired" => "expired",
+            "expiring" => "expiring",
+            _ if d["verificationStatus"] != "verified" => "pending_verification",
+            _ => return None,
+        },
+    };
+    let mut item = requirement;
+    item["issue"] = json!(problem);
+    item["documentId"] = doc.map(|d| d["documentId"].clone()).unwrap_or(Value::Null);
+    item["expiresOn"] = doc.map(|d| d["expiresOn"].clone()).unwrap_or(Value::Null);
+    Some(item)
+}
+
+/// Compliance of one employee's documents. Every required type, every education record and
+/// every past (non-current) job must be backed by a verified, unexpired document; anything else
+/// is listed under `issues`. Expiring documents are reported but don't make the employee
+/// non-compliant.
+pub fn employee_compliance(employee: &Value, documents: &[Value], as_of: NaiveDate, within_days: i64) -> Value {
+    let mut issues = Vec::new();
+    for t in document_types().iter().filter(|t| t["required"] == true) {
+        let docs: Vec<&Value> = documents.iter().filter(|d| d["documentType"] == t["code"]).collect();
+        issues.extend(issue(json!({"documentType": t["code"], "name": t["name"]}), current(&docs), as_of, within_days));
+    }
+    for edu in employee["education"].as_array().into_iter().flatten() {
+        let docs: Vec<&Value> = documents.iter().filter(|d| d["educationId"] == edu["id"]).collect();
+        let requirement = json!({
+            "documentType": "degree",
+            "name": format!("{} - {}", edu["educationLevel"].as_str().unwrap_or(""), edu["instituteName"].as_str().unwrap_or("")),
+            "educationId": edu["id"]
+        });
+        issues.extend(issue(requirement, current(&docs), as_of, within_days));
+    }
+    for exp in employee["experience"].as_array().into_iter().flatten().filter(|e| e["isCurrent"] != true) {
+        let docs: Vec<&Value> = documents.iter().filter(|d| d["experienceId"] == exp["id"]).collect();
+        let requirement = json!({
+            "documentType": "experience_letter",
+            "name": exp["organizationName"],
+            "experienceId": exp["id"]
+        });
+        issues.extend(issue(requirement, current(&docs), as_of, within_days));
+    }
+    let blocking = issues.iter().filter(|i| i["issue"] != "expiring").count();
+    json!({
+        "compliant": blocking == 0,
+        "documents": documents.len(),
+        "issues": issues
+    })
+}
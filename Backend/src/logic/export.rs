@@ -0,0 +1,174 @@
+use serde_json::Value;
+
+/// A flat, column-ordered report that can be rendered to CSV or PDF.
+pub struct Table {
+    pub title: String,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Builds a table from JSON objects, picking `columns` as (header, key) pairs.
+    pub fn from_values(title: &str, columns: &[(&str, &str)], rows: &[Value]) -> Self {
+        Table {
+            title: title.to_string(),
+            headers: columns.iter().map(|(h, _)| h.to_string()).collect(),
+            rows: rows
+                .iter()
+                .map(|r| columns.iter().map(|(_, k)| cell(&r[*k])).collect())
+                .collect(),
+        }
+    }
+}
+
+fn cell(v: &Value) -> String {
+    match v {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if n.is_f64() => format!("{:.2}", f),
+            _ => n.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+pub fn to_csv(table: &Table) -> String {
+    let escape = |s: &str| {
+        if s.contains(',') || s.contains('"') || s.contains('\n') {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    };
+    let mut out = String::new();
+    out.push_str(&table.headers.iter().map(|h| escape(h)).collect::<Vec<_>>().join(","));
+    out.push('\n');
+    for row in &table.rows {
+        out.push_str(&row.iter().map(|c| escape(c)).collect::<Vec<_>>().join(","));
+        out.push('\n');
+    }
+    out
+}
+
+// Landscape A4 in points, Courier 8pt so columns line up without font metrics.
+const PAGE_W: u32 = 842;
+const PAGE_H: u32 = 595;
+const FONT_SIZE: u32 = 8;
+const LINE_H: u32 = 11;
+const MARGIN: u32 = 36;
+const MAX_COLS: usize = 190;
+
+/// Renders a table as a plain monospaced PDF (one or more pages).
+pub fn to_pdf(table: &Table) -> Vec<u8> {
+    let widths: Vec<usize> = (0..table.headers.len())
+        .map(|i| {
+            table
+                .rows
+                .iter()
+                .map(|r| r.get(i).map(|c| c.chars().count()).unwrap_or(0))
+                .chain(std::iter::once(table.headers[i].chars().count()))
+                .max()
+                .unwrap_or(0)
+                .min(40)
+        })
+        .collect();
+    let fmt_row = |cells: &[String]| {
+        let mut line = cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| {
+                let c: String = c.chars().take(*w).collect();
+                format!("{:<width$}", c, width = *w)
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        line.truncate(MAX_COLS);
+        line
+    };
+
+    let mut lines = vec![table.title.clone(), String::new(), fmt_row(&table.headers)];
+    lines.push("-".repeat(lines[2].len().min(MAX_COLS)));
+    lines.extend(table.rows.iter().map(|r| fmt_row(r)));
+
+    let per_page = ((PAGE_H - 2 * MARGIN) / LINE_H) as usize;
+    let pages: Vec<&[String]> = lines.chunks(per_page.max(1)).collect();
+    render_pages(&pages, PAGE_W, PAGE_H)
+}
+
+/// Renders pre-formatted monospaced lines (payslips, letters) on portrait A4 pages.
+pub fn lines_to_pdf(lines: &[String]) -> Vec<u8> {
+    let per_page = ((PAGE_W - 2 * MARGIN) / LINE_H) as usize;
+    let pages: Vec<&[String]> = lines.chunks(per_page.max(1)).collect();
+    render_pages(&pages, PAGE_H, PAGE_W)
+}
+
+fn pdf_text(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{}", c),
+            '₹' => "Rs.".to_string(),
+            c if c.is_ascii() && !c.is_ascii_control() => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+fn render_pages(pages: &[&[String]], page_w: u32, page_h: u32) -> Vec<u8> {
+    // Object layout: 1 catalog, 2 pages, 3 font, then (page, content) pairs.
+    let mut objects: Vec<String> = Vec::new();
+    let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", 4 + i * 2)).collect();
+    objects.push("<< /Type /Catalog /Pages 2 0 R >>".to_string());
+    objects.push(format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        kids.join(" "),
+        pages.len()
+    ));
+    objects.push("<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>".to_string());
+
+    for (i, page) in pages.iter().enumerate() {
+        let mut stream = format!(
+            "BT /F1 {} Tf {} TL {} {} Td\n",
+            FONT_SIZE,
+            LINE_H,
+            MARGIN,
+            page_h - MARGIN
+        );
+        for line in page.iter() {
+            stream.push_str(&format!("({}) Tj T*\n", pdf_text(line)));
+        }
+        stream.push_str("ET");
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            page_w,
+            page_h,
+            5 + i * 2
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}\nendstream",
+            stream.len(),
+            stream
+        ));
+    }
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, obj) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, obj).as_bytes());
+    }
+    let xref_at = out.len();
+    out.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for off in offsets {
+        out.extend_from_slice(format!("{:010} 00000 n \n", off).as_bytes());
+    }
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref_at
+        )
+        .as_bytes(),
+    );
+    out
+}
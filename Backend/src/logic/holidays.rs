@@ -0,0 +1,551 @@
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde_json::{json, Value};
+
+/// Day names as stored on a rule, their iCalendar `BYDAY` codes and chrono weekdays.
+const WEEKDAYS: [(&str, &str, Weekday); 7] = [
+    ("monday", "MO", Weekday::Mon),
+    ("tuesday", "TU", Weekday::Tue),
+    ("wednesday", "WE", Weekday::Wed),
+    ("thursday", "TH", Weekday::Thu),
+    ("friday", "FR", Weekday::Fri),
+    ("saturday", "SA", Weekday::Sat),
+    ("sunday", "SU", Weekday::Sun),
+];
+
+fn weekday_of(name: &str) -> Option<Weekday> {
+    let name = name.trim().to_lowercase();
+    if name.len() < 2 {
+        return None;
+    }
+    WEEKDAYS
+        .iter()
+        .find(|(full, code, _)| full.starts_with(&name) || code.eq_ignore_ascii_case(&name))
+        .map(|(_, _, d)| *d)
+}
+
+fn weekday_entry(day: Weekday) -> (&'static str, &'static str) {
+    let (full, code, _) = WEEKDAYS[day.num_days_from_monday() as usize];
+    (full, code)
+}
+
+/// The weekly offs a school has until it saves its own: every Sunday.
+pub fn default_rules() -> Vec<Value> {
+    vec![json!({"name": "Sunday", "weekday": "sunday", "weeks": []})]
+}
+
+fn ordinal(n: i64) -> String {
+    match n {
+        -1 => "last".to_string(),
+        1 => "1st".to_string(),
+        2 => "2nd".to_string(),
+        3 => "3rd".to_string(),
+        n => format!("{}th", n),
+    }
+}
+
+/// Checks a recurring rule and fills in its name. A rule is `{name?, weekday, weeks?}`;
+/// `weeks` lists which occurrences of the weekday in a month are off (1-5, `-1` for the
+/// last) and is empty when every week is.
+pub fn validate_rule(rule: &Value) -> Result<Value, String> {
+    let day = rule["weekday"]
+        .as_str()
+        .and_then(weekday_of)
+        .ok_or("weekday must be a day name such as \"saturday\"")?;
+    let mut weeks: Vec<i64> = match &rule["weeks"] {
+        Value::Null => Vec::new(),
+        Value::Array(a) => a
+            .iter()
+            .map(|w| w.as_i64().filter(|w| (1..=5).contains(w) || *w == -1))
+            .collect::<Option<_>>()
+            .ok_or("weeks must be numbers from 1 to 5, or -1 for the last")?,
+        _ => return Err("weeks must be a list".to_string()),
+    };
+    weeks.sort_unstable_by_key(|w| if *w == -1 { 6 } else { *w });
+    weeks.dedup();
+    let (full, _) = weekday_entry(day);
+    let day_title = format!("{}{}", full[..1].to_uppercase(), &full[1..]);
+    let name = rule["name"]
+        .as_str()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| {
+            if weeks.is_empty() {
+                format!("Every {}", day_title)
+            } else {
+                let which: Vec<String> = weeks.iter().map(|w| ordinal(*w)).collect();
+                format!("{} {}", which.join(" and "), day_title)
+            }
+        });
+    Ok(json!({"name": name, "weekday": full, "weeks": weeks}))
+}
+
+/// Whether a recurring rule makes `date` a day off.
+pub fn rule_matches(rule: &Value, date: NaiveDate) -> bool {
+    if rule["weekday"].as_str().and_then(weekday_of) != Some(date.weekday()) {
+        return false;
+    }
+    let weeks = match rule["weeks"].as_array() {
+        Some(w) if !w.is_empty() => w,
+        _ => return true,
+    };
+    let nth = (date.day0() / 7 + 1) as i64;
+    let last = (date + Duration::days(7)).month() != date.month();
+    weeks.iter().filter_map(|w| w.as_i64()).any(|w| w == nth || (w == -1 && last))
+}
+
+/// A school's days off: its recurring weekly rules and its dated holidays
+/// (`{title, fromDate, toDate, exemptEmployees, exemptStudents}`). Exempt lists only apply to
+/// dated holidays; a weekly off is off for everyone.
+pub struct HolidayCalendar {
+    rules: Vec<Value>,
+    holidays: Vec<Value>,
+}
+
+impl HolidayCalendar {
+    pub fn new(rules: Vec<Value>, holidays: Vec<Value>) -> Self {
+        Self { rules, holidays }
+    }
+
+    /// Name of the weekly rule that makes `date` a day off.
+    pub fn weekly_off(&self, date: NaiveDate) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|r| rule_matches(r, date))
+            .map(|r| r["name"].as_str().unwrap_or("Weekly off"))
+    }
+
+    /// Dated holidays covering `date`.
+    pub fn holidays_on(&self, date: NaiveDate) -> impl Iterator<Item = &Value> {
+        let key = date.to_string();
+        self.holidays.iter().filter(move |h| {
+            h["fromDate"].as_str().is_some_and(|f| f <= key.as_str())
+                && h["toDate"].as_str().or(h["fromDate"].as_str()).is_some_and(|t| t >= key.as_str())
+        })
+    }
+
+    /// Why `date` is off for the whole school.
+    pub fn off_reason(&self, date: NaiveDate) -> Option<String> {
+        self.weekly_off(date)
+            .map(|r| r.to_string())
+            .or_else(|| self.holidays_on(date).next().and_then(|h| h["title"].as_str()).map(|t| t.to_string()))
+    }
+
+    /// Why `date` is off for one person; `None` when they work it. `role` is `employee` or
+    /// `student` and picks the exempt list that applies.
+    pub fn off_reason_for(&self, date: NaiveDate, role: &str, user_id: &str) -> Option<String> {
+        if let Some(rule) = self.weekly_off(date) {
+            return Some(rule.to_string());
+        }
+        let exempt_key = if role == "student" { "exemptStudents" } else { "exemptEmployees" };
+        self.holidays_on(date)
+            .find(|h| !h[exempt_key].as_array().is_some_and(|ex| ex.iter().any(|e| e == user_id)))
+            .map(|h| h["title"].as_str().unwrap_or("Holiday").to_string())
+    }
+
+    /// Exempt list of the first holiday on `date` for `role`.
+    pub fn exempt_on(&self, date: NaiveDate, role: &str) -> Vec<String> {
+        let exempt_key = if role == "student" { "exemptStudents" } else { "exemptEmployees" };
+        self.holidays_on(date)
+            .next()
+            .and_then(|h| h[exempt_key].as_array())
+            .map(|a| a.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+            .unwrap_or_default()
+    }
+
+    /// Working days from `from` to `to` inclusive, for the school or, given `(role, userId)`,
+    /// for one person.
+    pub fn working_days(&self, from: NaiveDate, to: NaiveDate, person: Option<(&str, &str)>) -> Vec<NaiveDate> {
+        from.iter_days()
+            .take_while(|d| *d <= to)
+            .filter(|d| match person {
+                Some((role, user)) => self.off_reason_for(*d, role, user).is_none(),
+                None => self.off_reason(*d).is_none(),
+            })
+            .collect()
+    }
+}
+
+// ─── iCalendar ───────────────────────────────────────────────────────────────
+
+fn ics_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn ics_unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+/// Folds a content line at 75 octets as RFC 5545 requires.
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+fn ics_date(d: NaiveDate) -> String {
+    d.format("%Y%m%d").to_string()
+}
+
+/// The calendar as an `.ics` file: one all-day event per holiday and one recurring event per
+/// weekly rule, starting on its first occurrence on or after `from`. `stamp` is the DTSTAMP
+/// (`YYYYMMDDTHHMMSSZ`).
+pub fn to_ics(school_id: &str, calendar_name: &str, holidays: &[Value], rules: &[Value], from: NaiveDate, stamp: &str) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Modern School//Holiday Calendar//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{}", ics_escape(calendar_name)),
+    ];
+    for (i, h) in holidays.iter().enumerate() {
+        let Some(start) = h["fromDate"].as_str().and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()) else { continue };
+        let end = h["toDate"]
+            .as_str()
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            .filter(|d| *d >= start)
+            .unwrap_or(start);
+        let uid = h["id"].as_str().map(|s| s.to_string()).unwrap_or_else(|| format!("{}-{}", ics_date(start), i));
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}@{}", uid, school_id));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART;VALUE=DATE:{}", ics_date(start)));
+        // DTEND is exclusive for all-day events
+        lines.push(format!("DTEND;VALUE=DATE:{}", ics_date(end + Duration::days(1))));
+        lines.push(format!("SUMMARY:{}", ics_escape(h["title"].as_str().unwrap_or("Holiday"))));
+        if let Some(desc) = h["description"].as_str().filter(|d| !d.trim().is_empty()) {
+            lines.push(format!("DESCRIPTION:{}", ics_escape(desc)));
+        }
+        lines.push("TRANSP:TRANSPARENT".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+    for rule in rules {
+        let Some(day) = rule["weekday"].as_str().and_then(weekday_of) else { continue };
+        let Some(first) = from.iter_days().take(62).find(|d| rule_matches(rule, *d)) else { continue };
+        let (full, code) = weekday_entry(day);
+        let weeks: Vec<i64> = rule["weeks"].as_array().map(|w| w.iter().filter_map(|x| x.as_i64()).collect()).unwrap_or_default();
+        let rrule = if weeks.is_empty() {
+            format!("FREQ=WEEKLY;BYDAY={}", code)
+        } else {
+            let days: Vec<String> = weeks.iter().map(|w| format!("{}{}", w, code)).collect();
+            format!("FREQ=MONTHLY;BYDAY={}", days.join(","))
+        };
+        let suffix: Vec<String> = weeks.iter().map(|w| w.to_string()).collect();
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:rule-{}-{}@{}", full, suffix.join("-"), school_id));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART;VALUE=DATE:{}", ics_date(first)));
+        lines.push(format!("DTEND;VALUE=DATE:{}", ics_date(first + Duration::days(1))));
+        lines.push(format!("RRULE:{}", rrule));
+        lines.push(format!("SUMMARY:{}", ics_escape(rule["name"].as_str().unwrap_or("Weekly off"))));
+        lines.push("TRANSP:TRANSPARENT".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|l| fold(l)).collect()
+}
+
+/// Date part of a DTSTART/DTEND value, and whether it carried a time other than midnight.
+fn ics_value_date(value: &str) -> Option<(NaiveDate, bool)> {
+    let date = NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()?;
+    let timed = value.get(8..).is_some_and(|t| t.len() > 1 && !t.trim_start_matches('T').starts_with("000000"));
+    Some((date, timed))
+}
+
+/// Turns a weekly or nth-weekday-of-month RRULE into a recurring rule. Anything else (yearly
+/// repeats, intervals, counts) isn't a weekly off and returns `None`.
+pub fn rule_from_rrule(rrule: &str, name: &str) -> Option<Value> {
+    let parts: std::collections::HashMap<String, String> = rrule
+        .split(';')
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.trim().to_uppercase(), v.trim().to_uppercase()))
+        .collect();
+    if parts.get("INTERVAL").is_some_and(|i| i != "1") || parts.contains_key("COUNT") {
+        return None;
+    }
+    let by_day: Vec<&str> = parts.get("BYDAY")?.split(',').collect();
+    let split = |d: &str| -> Option<(Option<i64>, Weekday)> {
+        let at = d.len().checked_sub(2)?;
+        let (n, code) = d.split_at(at);
+        let day = weekday_of(code)?;
+        Some((if n.is_empty() { None } else { Some(n.trim_start_matches('+').parse().ok()?) }, day))
+    };
+    let days: Vec<(Option<i64>, Weekday)> = by_day.iter().map(|d| split(d)).collect::<Option<_>>()?;
+    let day = days.first()?.1;
+    if days.iter().any(|(_, d)| *d != day) {
+        return None;
+    }
+    let weeks: Vec<i64> = match parts.get("FREQ").map(|s| s.as_str()) {
+        Some("WEEKLY") if days.iter().all(|(n, _)| n.is_none()) => Vec::new(),
+        Some("MONTHLY") => match parts.get("BYSETPOS") {
+            Some(pos) => pos.split(',').map(|p| p.parse().ok()).collect::<Option<_>>()?,
+            None => days.iter().map(|(n, _)| *n).collect::<Option<_>>()?,
+        },
+        _ => return None,
+    };
+    validate_rule(&json!({"name": name, "weekday": weekday_entry(day).0, "weeks": weeks})).ok()
+}
+
+/// Reads the events of an `.ics` file: `{uid, title, description, fromDate, toDate, rrule}`
+/// with inclusive dates. Events without a readable start are returned in the second list
+/// with the reason.
+pub fn parse_ics(text: &str) -> Result<(Vec<Value>, Vec<Value>), String> {
+    // Unfold continuation lines first
+    let mut lines: Vec<String> = Vec::new();
+    for raw in text.split('\n') {
+        let raw = raw.trim_end_matches('\r');
+        match raw.strip_prefix(' ').or_else(|| raw.strip_prefix('\t')) {
+            Some(cont) if !lines.is_empty() => lines.last_mut().expect("checked non-empty").push_str(cont),
+            _ => lines.push(raw.to_string()),
+        }
+    }
+    if !lines.iter().any(|l| l.trim().eq_ignore_ascii_case("BEGIN:VCALENDAR")) {
+        return Err("Not an iCalendar file: BEGIN:VCALENDAR is missing".to_string());
+    }
+
+    let mut events = Vec::new();
+    let mut skipped = Vec::new();
+    let mut current: Option<std::collections::HashMap<String, String>> = None;
+    for line in lines {
+        let trimmed = line.trim();
+        if trimmed.eq_ignore_ascii_case("BEGIN:VEVENT") {
+            current = Some(std::collections::HashMap::new());
+            continue;
+        }
+        if trimmed.eq_ignore_ascii_case("END:VEVENT") {
+            let Some(props) = current.take() else { continue };
+            let title = props.get("SUMMARY").map(|s| ics_unescape(s).trim().to_string()).unwrap_or_default();
+            let Some((start, _)) = props.get("DTSTART").and_then(|v| ics_value_date(v)) else {
+                skipped.push(json!({"title": title, "reason": "DTSTART is missing or unreadable"}));
+                continue;
+            };
+            let end = match props.get("DTEND").and_then(|v| ics_value_date(v)) {
+                // An end at midnight, or a date-only end, is exclusive
+                Some((end, false)) if end > start => end - Duration::days(1),
+                Some((end, _)) if end >= start => end,
+                _ => start,
+            };
+            events.push(json!({
+                "uid": props.get("UID"),
+                "title": if title.is_empty() { "Holiday".to_string() } else { title },
+                "description": props.get("DESCRIPTION").map(|d| ics_unescape(d)).unwrap_or_default(),
+                "fromDate": start.to_string(),
+                "toDate": end.to_string(),
+                "rrule": props.get("RRULE"),
+            }));
+            continue;
+        }
+        let Some(props) = current.as_mut() else { continue };
+        let Some((name, value)) = trimmed.split_once(':') else { continue };
+        let name = name.split(';').next().unwrap_or("").trim().to_uppercase();
+        props.entry(name).or_insert_with(|| value.to_string());
+    }
+    Ok((events, skipped))
+}
+
+// ─── Gazetted holidays ───────────────────────────────────────────────────────
+
+/// Western Easter Sunday (anonymous Gregorian algorithm).
+fn easter(year: i32) -> Option<NaiveDate> {
+    let (a, b, c) = (year % 19, year / 100, year % 100);
+    let (d, e) = (b / 4, b % 4);
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let (i, k) = (c / 4, c % 4);
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+/// Festival dates on the central government's gazetted list, which follow the lunar calendar
+/// and are notified each year. Years missing here are reported so they can be added by hand.
+const CENTRAL_FESTIVALS: &[(i32, u32, u32, &str)] = &[
+    (2025, 3, 14, "Holi"),
+    (2025, 3, 31, "Id-ul-Fitr"),
+    (2025, 4, 10, "Mahavir Jayanti"),
+    (2025, 5, 12, "Buddha Purnima"),
+    (2025, 6, 7, "Id-ul-Zuha (Bakrid)"),
+    (2025, 7, 6, "Muharram"),
+    (2025, 8, 16, "Janmashtami"),
+    (2025, 9, 5, "Milad-un-Nabi"),
+    (2025, 10, 2, "Dussehra"),
+    (2025, 10, 20, "Diwali (Deepavali)"),
+    (2025, 11, 5, "Guru Nanak's Birthday"),
+    (2026, 3, 4, "Holi"),
+    (2026, 3, 21, "Id-ul-Fitr"),
+    (2026, 3, 31, "Mahavir Jayanti"),
+    (2026, 5, 1, "Buddha Purnima"),
+    (2026, 5, 27, "Id-ul-Zuha (Bakrid)"),
+    (2026, 6, 26, "Muharram"),
+    (2026, 8, 26, "Milad-un-Nabi"),
+    (2026, 9, 4, "Janmashtami"),
+    (2026, 10, 20, "Dussehra"),
+    (2026, 11, 8, "Diwali (Deepavali)"),
+    (2026, 11, 24, "Guru Nanak's Birthday"),
+];
+
+const NATIONAL_DAYS: &[(u32, u32, &str)] = &[
+    (1, 26, "Republic Day"),
+    (8, 15, "Independence Day"),
+    (10, 2, "Gandhi Jayanti"),
+    (12, 25, "Christmas"),
+];
+
+/// A state's days with a fixed date: `(code, name, [(month, day, title)])`.
+type StateDays = (&'static str, &'static str, &'static [(u32, u32, &'static str)]);
+
+const STATE_DAYS: &[StateDays] = &[
+    ("BR", "Bihar", &[(3, 22, "Bihar Diwas")]),
+    ("GA", "Goa", &[(12, 19, "Goa Liberation Day")]),
+    ("GJ", "Gujarat", &[(5, 1, "Gujarat Day")]),
+    ("HR", "Haryana", &[(11, 1, "Haryana Day")]),
+    ("KA", "Karnataka", &[(11, 1, "Kannada Rajyotsava")]),
+    ("MH", "Maharashtra", &[(5, 1, "Maharashtra Day")]),
+    ("OD", "Odisha", &[(4, 1, "Utkal Divas")]),
+    ("TG", "Telangana", &[(6, 2, "Telangana Formation Day")]),
+];
+
+/// States with their own gazetted days, as `{code, name}`.
+pub fn supported_states() -> Vec<Value> {
+    STATE_DAYS.iter().map(|(code, name, _)| json!({"code": code, "name": name})).collect()
+}
+
+/// Gazetted holidays between `from` and `to`: national days, Good Friday, the central list's
+/// festivals and, when given, the state's own days, as `{date, title, kind}`. Also returns the
+/// years in range that have no festival list yet.
+pub fn gazetted_holidays(from: NaiveDate, to: NaiveDate, state: Option<&str>) -> Result<(Vec<Value>, Vec<i32>), String> {
+    let state_days = match state.map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        Some(s) => {
+            let found = STATE_DAYS
+                .iter()
+                .find(|(code, name, _)| code.eq_ignore_ascii_case(s) || name.eq_ignore_ascii_case(s))
+                .ok_or_else(|| {
+                    let names: Vec<&str> = STATE_DAYS.iter().map(|(c, _, _)| *c).collect();
+                    format!("No state list for {}; available: {}", s, names.join(", "))
+                })?;
+            found.2
+        }
+        None => &[],
+    };
+
+    let mut out = Vec::new();
+    let mut missing = Vec::new();
+    for year in from.year()..=to.year() {
+        let mut add = |date: Option<NaiveDate>, title: &str, kind: &str| {
+            if let Some(d) = date.filter(|d| *d >= from && *d <= to) {
+                out.push(json!({"date": d.to_string(), "title": title, "kind": kind}));
+            }
+        };
+        for (m, d, title) in NATIONAL_DAYS {
+            add(NaiveDate::from_ymd_opt(year, *m, *d), title, "national");
+        }
+        add(easter(year).map(|e| e - Duration::days(2)), "Good Friday", "national");
+        let festivals: Vec<_> = CENTRAL_FESTIVALS.iter().filter(|(y, ..)| *y == year).collect();
+        if festivals.is_empty() {
+            missing.push(year);
+        }
+        for (y, m, d, title) in festivals {
+            add(NaiveDate::from_ymd_opt(*y, *m, *d), title, "festival");
+        }
+        for (m, d, title) in state_days {
+            add(NaiveDate::from_ymd_opt(year, *m, *d), title, "state");
+        }
+    }
+    out.sort_by(|a, b| a["date"].as_str().cmp(&b["date"].as_str()));
+    Ok((out, missing))
+}
+
+/// `2026-27` style academic year, April to March.
+pub fn academic_year_range(year: &str) -> Option<(NaiveDate, NaiveDate)> {
+    let start: i32 = year.trim().get(..4)?.parse().ok()?;
+    Some((NaiveDate::from_ymd_opt(start, 4, 1)?, NaiveDate::from_ymd_opt(start + 1, 3, 31)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn nth_and_last_weekday_rules() {
+        let rule = validate_rule(&json!({"weekday": "sat", "weeks": [-1, 2]})).unwrap();
+        assert_eq!(rule["name"], "2nd and last Saturday");
+        // October 2026 Saturdays: 3, 10, 17, 24, 31
+        let off: Vec<u32> = [3, 10, 17, 24, 31]
+            .into_iter()
+            .filter(|d| rule_matches(&rule, NaiveDate::from_ymd_opt(2026, 10, *d).unwrap()))
+            .collect();
+        assert_eq!(off, vec![10, 31]);
+        assert!(!rule_matches(&rule, date("2026-10-11")));
+    }
+
+    #[test]
+    fn weekly_off_applies_to_everyone_but_holidays_honour_exemptions() {
+        let calendar = HolidayCalendar::new(
+            default_rules(),
+            vec![json!({"title": "Diwali", "fromDate": "2026-11-08", "toDate": "2026-11-09", "exemptEmployees": ["E1"]})],
+        );
+        assert_eq!(calendar.weekly_off(date("2026-11-08")), Some("Sunday"));
+        assert_eq!(calendar.off_reason_for(date("2026-11-08"), "employee", "E1").as_deref(), Some("Sunday"));
+        assert_eq!(calendar.off_reason_for(date("2026-11-09"), "employee", "E1"), None);
+        assert_eq!(calendar.off_reason_for(date("2026-11-09"), "student", "E1").as_deref(), Some("Diwali"));
+        assert_eq!(calendar.working_days(date("2026-11-08"), date("2026-11-10"), None), vec![date("2026-11-10")]);
+    }
+
+    #[test]
+    fn ics_round_trip() {
+        let holidays = [json!({
+            "id": "H1",
+            "title": "Autumn break, with a title long enough to need folding across more than one line",
+            "fromDate": "2026-10-19",
+            "toDate": "2026-10-21",
+            "description": "Line one\nLine two; done"
+        })];
+        let rules = [validate_rule(&json!({"weekday": "saturday", "weeks": [2, 4]})).unwrap()];
+        let ics = to_ics("S1", "Test School", &holidays, &rules, date("2026-10-01"), "20261001T000000Z");
+        assert!(ics.lines().all(|l| l.len() <= 75));
+
+        let (events, skipped) = parse_ics(&ics).unwrap();
+        assert!(skipped.is_empty());
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["title"], holidays[0]["title"]);
+        assert_eq!(events[0]["description"], holidays[0]["description"]);
+        assert_eq!((events[0]["fromDate"].as_str(), events[0]["toDate"].as_str()), (Some("2026-10-19"), Some("2026-10-21")));
+
+        assert_eq!(events[1]["fromDate"], "2026-10-10");
+        let rule = rule_from_rrule(events[1]["rrule"].as_str().unwrap(), events[1]["title"].as_str().unwrap()).unwrap();
+        assert_eq!(rule, rules[0]);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::logic::holidays::HolidayCalendar;
+use chrono::{Datelike, NaiveDate};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Leave types every school starts with. `annualQuota` is in days; `accrual` is `yearly`
+/// (whole quota on 1 January), `monthly` (a twelfth each month) or `none` (no entitlement).
+/// Unpaid types are the only ones that reduce salary.
+pub fn default_leave_types() -> Vec<Value> {
+    vec![
+        json!({"code": "casual", "name": "Casual Leave", "annualQuota": 12.0, "accrual": "yearly",
+               "carryForwardMax": 0.0, "paid": true, "countHolidays": false, "gender": null, "active": true}),
+        json!({"code": "sick", "name": "Sick Leave", "annualQuota": 12.0, "accrual": "yearly",
+               "carryForwardMax": 30.0, "paid": true, "countHolidays": false, "gender": null, "active": true}),
+        json!({"code": "earned", "name": "Earned Leave", "annualQuota": 15.0, "accrual": "monthly",
+               "carryForwardMax": 45.0, "paid": true, "countHolidays": false, "gender": null, "active": true}),
+        // Maternity benefit runs in calendar days, holidays included
+        json!({"code": "maternity", "name": "Maternity Leave", "annualQuota": 182.0, "accrual": "yearly",
+               "carryForwardMax": 0.0, "paid": true, "countHolidays": true, "gender": "female", "active": true}),
+        json!({"code": "lwp", "name": "Leave Without Pay", "annualQuota": 0.0, "accrual": "none",
+               "carryForwardMax": 0.0, "paid": false, "countHolidays": false, "gender": null, "active": true}),
+    ]
+}
+
+/// Overlays a school's saved leave types on the defaults by `code`; saved codes not in the
+/// defaults are appended.
+pub fn merge_leave_types(saved: &[Value]) -> Vec<Value> {
+    let mut types = default_leave_types();
+    for s in saved {
+        match types.iter_mut().find(|t| t["code"] == s["code"]) {
+            Some(t) => {
+                if let (Some(target), Some(src)) = (t.as_object_mut(), s.as_object()) {
+                    for (k, v) in src {
+                        target.insert(k.clone(), v.clone());
+                    }
+                }
+            }
+            None => types.push(s.clone()),
+        }
+    }
+    types
+}
+
+fn num(v: &Value) -> f64 {
+    v.as_f64().unwrap_or(0.0)
+}
+
+fn round2(v: f64) -> f64 {
+    (v * 100.0).round() / 100.0
+}
+
+/// Types with no entitlement (leave without pay) can be taken without a balance.
+pub fn is_unlimited(leave_type: &Value) -> bool {
+    leave_type["accrual"] == "none"
+}
+
+/// Dates between `from` and `to` (inclusive) that count against the leave. The school's
+/// weekly offs and holidays are skipped unless the type counts them (`countHolidays`).
+pub fn leave_dates(leave_type: &Value, from: NaiveDate, to: NaiveDate, calendar: &HolidayCalendar) -> Vec<NaiveDate> {
+    let count_all = leave_type["countHolidays"].as_bool().unwrap_or(false);
+    from.iter_days()
+        .take_while(|d| *d <= to)
+        .filter(|d| count_all || calendar.off_reason(*d).is_none())
+        .collect()
+}
+
+/// Leave days per calendar year. Balances reset every January, so a leave running over New
+/// Year draws each year's share from that year's balance.
+pub fn days_by_year(dates: &[NaiveDate]) -> BTreeMap<i32, f64> {
+    let mut split = BTreeMap::new();
+    for d in dates {
+        *split.entry(d.year()).or_insert(0.0) += 1.0;
+    }
+    split
+}
+
+/// Entitlement earned in `year` up to `as_of`: the full quota for yearly accrual, a twelfth
+/// per month started for monthly accrual.
+pub fn accrued(leave_type: &Value, year: i32, as_of: NaiveDate) -> f64 {
+    let quota = num(&leave_type["annualQuota"]);
+    match leave_type["accrual"].as_str().unwrap_or("yearly") {
+        "none" => 0.0,
+        "monthly" => {
+            let months = if as_of.year() > year {
+                12
+            } else if as_of.year() < year {
+                0
+            } else {
+                as_of.month()
+            };
+            round2(quota * months as f64 / 12.0)
+        }
+        _ => quota,
+    }
+}
+
+/// Balance of one leave type for one year. `pending` is days applied for and awaiting a
+/// decision; they are held back from `available`, which is null for unlimited types.
+pub fn balance(leave_type: &Value, opening: f64, adjustment: f64, accrued: f64, used: f64, pending: f64) -> Value {
+    let closing = round2(opening + accrued + adjustment - used);
+    json!({
+        "leaveType": leave_type["code"],
+        "name": leave_type["name"],
+        "paid": leave_type["paid"],
+        "opening": opening,
+        "accrued": accrued,
+        "adjustment": adjustment,
+        "used": used,
+        "pending": pending,
+        "closing": closing,
+        "available": if is_unlimited(leave_type) { Value::Null } else { json!(round2(closing - pending)) }
+    })
+}
+
+/// Opening balance carried into the next year: the closing balance limited by `carryForwardMax`.
+pub fn carry_forward(leave_type: &Value, closing: f64) -> f64 {
+    closing.clamp(0.0, num(&leave_type["carryForwardMax"]))
+}
@@ -1 +1,18 @@
+pub mod advances;
+pub mod attendance;
+pub mod bank_statement;
+pub mod device_punch;
+pub mod employee_documents;
+pub mod export;
+pub mod holidays;
+pub mod leave;
 pub mod ocr_pipeline;
+pub mod payroll;
+pub mod payslip;
+pub mod salary_transfer;
+pub mod self_checkin;
+pub mod sessions;
+pub mod statutory;
+pub mod subject_attendance;
+pub mod substitution;
+pub mod timetable;
@@ -0,0 +1,187 @@
+use crate::logic::statutory::{compute_statutory, StatutoryInputs};
+use serde_json::{json, Value};
+
+/// Days used to turn a monthly salary into a per-day rate for absence deductions.
+pub const PAYROLL_DAYS: f64 = 30.0;
+
+/// Month-specific figures that are not stored on the employee record.
+#[derive(Debug, Clone, Default)]
+pub struct SalaryInputs {
+    /// Sum of `totalPrice` of the employee's assigned responsibilities/spaces.
+    pub responsibilities_total: f64,
+    pub absent_days: f64,
+    /// Payroll month (1-12), used for February PT slabs and the TDS projection.
+    pub month: u32,
+    /// The school's merged statutory settings; `Value::Null` skips statutory deductions.
+    pub statutory: Value,
+    /// Approved earlier months of the same financial year: {gross, pfEmployee, professionalTax, tds}
+    pub ytd: Value,
+    /// Installments due this month from the employee's active advances: [{advanceId, due, interest}]
+    pub advances: Vec<Value>,
+    /// Arrears from backdated salary revisions, one entry per earlier month: [{month, year, amount}]
+    pub arrears: Vec<Value>,
+}
+
+/// Salary fields a revision can set; everything else stays on the employee record.
+pub const REVISION_KEYS: [&str; 9] = [
+    "baseSalary", "incrementPercent", "experienceYears", "experienceRate", "tenureMonths",
+    "tenureRate", "bonus", "aid", "allowances",
+];
+
+/// The latest revision effective on or before `month`/`year`. Revisions are `{effectiveMonth,
+/// effectiveYear, createdAt, params}` and apply to whole payroll months.
+pub fn revision_in_force(revisions: &[Value], month: i32, year: i32) -> Option<&Value> {
+    let key = |r: &Value| {
+        r["effectiveYear"].as_i64().unwrap_or(0) * 12 + r["effectiveMonth"].as_i64().unwrap_or(0)
+    };
+    revisions
+        .iter()
+        .filter(|r| key(r) <= year as i64 * 12 + month as i64)
+        .max_by(|a, b| {
+            key(a)
+                .cmp(&key(b))
+                .then_with(|| a["createdAt"].as_str().cmp(&b["createdAt"].as_str()))
+        })
+}
+
+/// The employee record with a revision's salary fields laid over it.
+pub fn apply_revision(emp: &Value, revision: Option<&Value>) -> Value {
+    let mut out = emp.clone();
+    if let (Some(obj), Some(params)) = (out.as_object_mut(), revision.and_then(|r| r["params"].as_object())) {
+        for key in REVISION_KEYS {
+            if let Some(v) = params.get(key) {
+                obj.insert(key.to_string(), v.clone());
+            }
+        }
+    }
+    out
+}
+
+fn field(emp: &Value, k: &str) -> f64 {
+    emp[k]
+        .as_f64()
+        .or_else(|| emp[k].as_str().and_then(|s| s.parse().ok()))
+        .unwrap_or(0.0)
+}
+
+/// Fixed monthly allowances (`allowances: {"HRA": 2000, ...}`) as label/amount pairs.
+pub fn allowance_lines(emp: &Value) -> Vec<(String, f64)> {
+    emp["allowances"]
+        .as_object()
+        .map(|m| {
+            m.iter()
+                .map(|(k, v)| (k.clone(), v.as_f64().or_else(|| v.as_str().and_then(|s| s.parse().ok())).unwrap_or(0.0)))
+                .filter(|(_, v)| *v != 0.0)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Full-month gross before absences and arrears.
+pub fn monthly_gross(emp: &Value, responsibilities_total: f64) -> f64 {
+    let base_salary = field(emp, "baseSalary");
+    base_salary
+        + base_salary * field(emp, "incrementPercent") / 100.0
+        + responsibilities_total
+        + field(emp, "experienceYears") * field(emp, "experienceRate")
+        + field(emp, "tenureMonths") * field(emp, "tenureRate")
+        + field(emp, "bonus")
+        + field(emp, "aid")
+        + allowance_lines(emp).iter().map(|(_, v)| v).sum::<f64>()
+}
+
+fn round2(v: f64) -> f64 {
+    (v * 100.0).round() / 100.0
+}
+
+/// The one salary formula used by the salary breakdown and by payroll runs.
+///
+/// Earnings are base salary plus increment, responsibility/space, experience and tenure
+/// components, bonus, aid and fixed allowances. Absences are deducted at gross / 30 per day
+/// and arrears from backdated revisions are added on top. Statutory deductions (PF, ESI,
+/// PT, TDS) are worked out on the earned pay, and the scheduled advance installments are
+/// recovered from what is left.
+pub fn compute_salary(emp: &Value, inputs: &SalaryInputs) -> Value {
+    let num = |k: &str| field(emp, k);
+
+    let base_salary = num("baseSalary");
+    let increment = base_salary * num("incrementPercent") / 100.0;
+    let exp_component = num("experienceYears") * num("experienceRate");
+    let tenure_component = num("tenureMonths") * num("tenureRate");
+    let bonus = num("bonus");
+    let aid = num("aid");
+    let spaces_component = inputs.responsibilities_total;
+    let allowances = allowance_lines(emp);
+    let allowances_total: f64 = allowances.iter().map(|(_, v)| v).sum();
+
+    let monthly = monthly_gross(emp, spaces_component);
+    let per_day = monthly / PAYROLL_DAYS;
+    let absence_deduction = (per_day * inputs.absent_days).min(monthly);
+    let arrears_total: f64 = inputs.arrears.iter().map(|a| a["amount"].as_f64().unwrap_or(0.0)).sum();
+    let gross_salary = monthly + arrears_total;
+    let earned_gross = (gross_salary - absence_deduction).max(0.0);
+
+    let mut statutory = if inputs.statutory.is_null() {
+        json!({"employeeTotal": 0.0, "lines": []})
+    } else {
+        let paid_share = if monthly > 0.0 { (monthly - absence_deduction) / monthly } else { 0.0 };
+        compute_statutory(
+            emp,
+            &StatutoryInputs {
+                settings: &inputs.statutory,
+                earned_basic: (base_salary + increment) * paid_share,
+                gross: monthly,
+                earned_gross,
+                one_off: arrears_total,
+                month: inputs.month,
+                ytd: &inputs.ytd,
+            },
+        )
+    };
+    let statutory_lines = statutory["lines"].take();
+    let statutory_total = statutory["employeeTotal"].as_f64().unwrap_or(0.0).min(earned_gross);
+
+    // Installments are taken in order while pay remains; a short month recovers less
+    let mut available = (earned_gross - statutory_total).max(0.0);
+    let mut advance_recoveries = Vec::new();
+    for adv in &inputs.advances {
+        let amount = adv["due"].as_f64().unwrap_or(0.0).min(available);
+        if amount <= 0.0 {
+            continue;
+        }
+        available -= amount;
+        advance_recoveries.push(json!({
+            "advanceId": adv["advanceId"],
+            "amount": round2(amount),
+            "interest": round2(adv["interest"].as_f64().unwrap_or(0.0).min(amount))
+        }));
+    }
+    let advance_recovery: f64 = advance_recoveries.iter().map(|a| a["amount"].as_f64().unwrap_or(0.0)).sum();
+    let deductions = absence_deduction + statutory_total + advance_recovery;
+
+    json!({
+        "baseSalary": round2(base_salary),
+        "incrementAmount": round2(increment),
+        "spacesComponent": round2(spaces_component),
+        "experienceComponent": round2(exp_component),
+        "tenureComponent": round2(tenure_component),
+        "bonus": round2(bonus),
+        "aid": round2(aid),
+        "allowances": allowances.iter().map(|(k, v)| json!({"label": k, "amount": round2(*v)})).collect::<Vec<_>>(),
+        "allowancesTotal": round2(allowances_total),
+        "arrears": inputs.arrears,
+        "arrearsTotal": round2(arrears_total),
+        "grossSalary": round2(gross_salary),
+        "perDayRate": round2(per_day),
+        "absentDays": inputs.absent_days,
+        "absenceDeduction": round2(absence_deduction),
+        "earnedGross": round2(earned_gross),
+        "statutoryDeductions": statutory_lines,
+        "statutoryTotal": round2(statutory_total),
+        "statutory": statutory,
+        "advanceRecovery": round2(advance_recovery),
+        "advanceRecoveries": advance_recoveries,
+        "deductions": round2(deductions),
+        "netMonthlySalary": round2((gross_salary - deductions).max(0.0))
+    })
+}
@@ -0,0 +1,181 @@
+use crate::logic::export::lines_to_pdf;
+use serde_json::{json, Value};
+
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September",
+    "October", "November", "December",
+];
+
+fn amount(v: &Value) -> f64 {
+    v.as_f64().unwrap_or(0.0)
+}
+
+/// Builds the payslip for one employee's line in a payroll run. Zero-value components are left out.
+pub fn build_payslip(school_name: &str, run: &Value, line: &Value, employee: &Value) -> Value {
+    let b = &line["breakdown"];
+    let month = run["month"].as_u64().unwrap_or(1).clamp(1, 12) as usize;
+
+    let mut earnings: Vec<Value> = [
+        ("Basic Salary", "baseSalary"),
+        ("Increment", "incrementAmount"),
+        ("Experience Allowance", "experienceComponent"),
+        ("Tenure Allowance", "tenureComponent"),
+        ("Responsibility Allowance", "spacesComponent"),
+        ("Bonus", "bonus"),
+        ("Aid", "aid"),
+    ]
+    .iter()
+    .filter(|(_, k)| amount(&b[*k]) != 0.0)
+    .map(|(label, k)| json!({"label": label, "amount": amount(&b[*k])}))
+    .collect();
+    for a in b["allowances"].as_array().into_iter().flatten() {
+        if amount(&a["amount"]) != 0.0 {
+            earnings.push(json!({"label": a["label"], "amount": amount(&a["amount"])}));
+        }
+    }
+    if amount(&b["arrearsTotal"]) != 0.0 {
+        earnings.push(json!({"label": "Salary Arrears", "amount": amount(&b["arrearsTotal"])}));
+    }
+    if earnings.is_empty() {
+        earnings.push(json!({"label": "Basic Salary", "amount": 0.0}));
+    }
+
+    let mut deductions: Vec<Value> = Vec::new();
+    if amount(&b["absenceDeduction"]) > 0.0 {
+        deductions.push(json!({
+            "label": format!("Absence ({} days)", b["absentDays"]),
+            "amount": amount(&b["absenceDeduction"])
+        }));
+    }
+    if amount(&b["advanceRecovery"]) > 0.0 {
+        deductions.push(json!({"label": "Advance Recovery", "amount": amount(&b["advanceRecovery"])}));
+    }
+    // Statutory lines (PF, ESI, PT, TDS) are carried on the breakdown as [{label, amount}]
+    for d in b["statutoryDeductions"].as_array().into_iter().flatten() {
+        if amount(&d["amount"]) > 0.0 {
+            deductions.push(json!({"label": d["label"], "amount": amount(&d["amount"])}));
+        }
+    }
+
+    json!({
+        "runId": run["runId"],
+        "month": run["month"],
+        "year": run["year"],
+        "period": format!("{} {}", MONTHS[month - 1], run["year"]),
+        "status": run["status"],
+        "schoolName": school_name,
+        "employeeId": line["employeeId"],
+        "employeeName": line["employeeName"],
+        "designation": employee["designation"].as_str().or(employee["employeeType"].as_str()),
+        "absentDays": b["absentDays"],
+        "earnings": earnings,
+        "deductions": deductions,
+        "grossSalary": amount(&b["grossSalary"]),
+        "totalDeductions": amount(&b["deductions"]),
+        "netPay": amount(&b["netMonthlySalary"])
+    })
+}
+
+/// Renders a payslip built by [`build_payslip`] as a one-page PDF.
+pub fn payslip_pdf(slip: &Value) -> Vec<u8> {
+    const WIDTH: usize = 72;
+    let text = |v: &Value| v.as_str().map(|s| s.to_string()).unwrap_or_else(|| v.to_string());
+    let row = |label: &str, value: f64| {
+        let label: String = label.chars().take(WIDTH - 16).collect();
+        format!("{:<w$}{:>16.2}", label, value, w = WIDTH - 16)
+    };
+    let rule = "-".repeat(WIDTH);
+
+    let mut lines = vec![
+        text(&slip["schoolName"]),
+        format!("PAYSLIP - {}", text(&slip["period"])),
+        rule.clone(),
+        format!("Employee ID : {}", text(&slip["employeeId"])),
+        format!("Name        : {}", text(&slip["employeeName"])),
+    ];
+    if let Some(d) = slip["designation"].as_str() {
+        lines.push(format!("Designation : {}", d));
+    }
+    lines.push(format!("Absent Days : {}", text(&slip["absentDays"])));
+    lines.push(rule.clone());
+
+    lines.push("EARNINGS".to_string());
+    for e in slip["earnings"].as_array().into_iter().flatten() {
+        lines.push(row(&text(&e["label"]), amount(&e["amount"])));
+    }
+    lines.push(row("Gross Earnings", amount(&slip["grossSalary"])));
+    lines.push(String::new());
+
+    lines.push("DEDUCTIONS".to_string());
+    let deductions = slip["deductions"].as_array().cloned().unwrap_or_default();
+    if deductions.is_empty() {
+        lines.push(row("None", 0.0));
+    }
+    for d in &deductions {
+        lines.push(row(&text(&d["label"]), amount(&d["amount"])));
+    }
+    lines.push(row("Total Deductions", amount(&slip["totalDeductions"])));
+    lines.push(rule.clone());
+    lines.push(row("NET PAY (Rs.)", amount(&slip["netPay"])));
+    lines.push(rule);
+    lines.push(String::new());
+    lines.push("This is a computer-generated payslip and does not need a signature.".to_string());
+
+    lines_to_pdf(&lines)
+}
+
+/// Renders the Form 16-style yearly statement from `PayrollService::annual_tax_statement`.
+pub fn annual_statement_pdf(stmt: &Value) -> Vec<u8> {
+    const WIDTH: usize = 72;
+    let text = |v: &Value| v.as_str().map(|s| s.to_string()).unwrap_or_else(|| v.to_string());
+    let row = |label: &str, value: f64| {
+        let label: String = label.chars().take(WIDTH - 16).collect();
+        format!("{:<w$}{:>16.2}", label, value, w = WIDTH - 16)
+    };
+    let rule = "-".repeat(WIDTH);
+    let emp = &stmt["employee"];
+    let c = &stmt["computation"];
+
+    let mut lines = vec![
+        text(&stmt["employer"]["name"]),
+        format!(
+            "ANNUAL SALARY & TAX STATEMENT - FY {} (AY {})",
+            text(&stmt["financialYear"]),
+            text(&stmt["assessmentYear"])
+        ),
+        rule.clone(),
+        format!("Employee ID : {}", text(&emp["employeeId"])),
+        format!("Name        : {}", text(&emp["name"])),
+        format!("PAN         : {}", emp["pan"].as_str().unwrap_or("-")),
+        format!("Tax Regime  : {}", text(&stmt["taxRegime"]).to_uppercase()),
+        rule.clone(),
+        format!("{:<10}{:>12}{:>10}{:>10}{:>10}{:>10}{:>10}", "Month", "Earned", "PF", "ESI", "PT", "TDS", "Net"),
+    ];
+    for r in stmt["rows"].as_array().into_iter().flatten() {
+        lines.push(format!(
+            "{:<10}{:>12.2}{:>10.2}{:>10.2}{:>10.2}{:>10.2}{:>10.2}",
+            text(&r["period"]),
+            amount(&r["earnedGross"]),
+            amount(&r["pfEmployee"]),
+            amount(&r["esiEmployee"]),
+            amount(&r["professionalTax"]),
+            amount(&r["tds"]),
+            amount(&r["netPay"])
+        ));
+    }
+    lines.push(rule.clone());
+
+    lines.push("TAX COMPUTATION".to_string());
+    lines.push(row("Gross Salary", amount(&c["grossSalary"])));
+    lines.push(row("Less: Standard Deduction", amount(&c["standardDeduction"])));
+    lines.push(row("Less: Other Deductions (PT, Chapter VI-A)", amount(&c["otherDeductions"])));
+    lines.push(row("Taxable Income", amount(&c["taxableIncome"])));
+    lines.push(row("Tax Payable (incl. cess)", amount(&c["taxPayable"])));
+    lines.push(row("Tax Deducted at Source", amount(&c["tdsDeducted"])));
+    lines.push(row("Balance (payable / -refundable)", amount(&c["balance"])));
+    lines.push(rule);
+    lines.push(String::new());
+    lines.push("Computer-generated statement based on approved payroll runs.".to_string());
+
+    lines_to_pdf(&lines)
+}
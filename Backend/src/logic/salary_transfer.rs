@@ -0,0 +1,216 @@
+use crate::logic::bank_statement::{normalise, parse_amount, read_rows};
+use crate::logic::export::Table;
+use serde_json::Value;
+
+/// Bulk-upload layouts we can produce. Column order follows each bank's corporate
+/// net-banking bulk payment template; `generic` suits banks without a fixed layout.
+pub const BANK_FORMATS: [&str; 5] = ["sbi", "hdfc", "icici", "axis", "generic"];
+
+/// RBI's RTGS minimum; smaller transfers go by NEFT.
+pub const RTGS_MINIMUM: f64 = 200000.0;
+
+/// `ABCD0123456`: four letters, a zero, then six letters or digits.
+pub fn valid_ifsc(ifsc: &str) -> bool {
+    let b = ifsc.as_bytes();
+    b.len() == 11
+        && b[..4].iter().all(|c| c.is_ascii_uppercase())
+        && b[4] == b'0'
+        && b[5..].iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+pub fn valid_account_number(acc: &str) -> bool {
+    (6..=18).contains(&acc.len()) && acc.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Chooses NEFT, RTGS or an internal transfer ("FT") when payer and payee share a bank.
+pub fn transfer_mode(requested: Option<&str>, amount: f64, bene_ifsc: &str, debit_ifsc: &str) -> String {
+    if let Some(m) = requested.map(|m| m.to_uppercase()).filter(|m| m == "NEFT" || m == "RTGS" || m == "FT") {
+        return m;
+    }
+    if debit_ifsc.len() >= 4 && bene_ifsc.get(..4) == debit_ifsc.get(..4) {
+        "FT".to_string()
+    } else if amount >= RTGS_MINIMUM {
+        "RTGS".to_string()
+    } else {
+        "NEFT".to_string()
+    }
+}
+
+fn s(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Builds the bulk-transfer sheet for a batch.
+///
+/// `batch` carries `debitAccount`, `debitIfsc`, `paymentDate` (YYYY-MM-DD) and `narration`;
+/// each line carries `reference`, `employeeId`, `employeeName`, `accountNumber`, `ifsc`,
+/// `bankName`, `amount` and `mode`.
+pub fn build_transfer_file(format: &str, batch: &Value, lines: &[Value]) -> Result<Table, String> {
+    let date = s(&batch["paymentDate"]);
+    // Most bank templates want DD/MM/YYYY
+    let dmy = chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+        .map(|d| d.format("%d/%m/%Y").to_string())
+        .unwrap_or(date.clone());
+    let debit = s(&batch["debitAccount"]);
+    let narration = s(&batch["narration"]);
+    let amount = |l: &Value| format!("{:.2}", l["amount"].as_f64().unwrap_or(0.0));
+    let name = |l: &Value| s(&l["employeeName"]).chars().filter(|c| c.is_alphanumeric() || *c == ' ').take(35).collect::<String>();
+
+    let (headers, rows): (Vec<&str>, Vec<Vec<String>>) = match format {
+        "sbi" => (
+            vec!["Beneficiary Name", "Beneficiary Account No", "IFSC", "Amount", "Payment Mode", "Debit Account No", "Value Date", "Reference No", "Remarks"],
+            lines
+                .iter()
+                .map(|l| {
+                    vec![name(l), s(&l["accountNumber"]), s(&l["ifsc"]), amount(l), s(&l["mode"]), debit.clone(), dmy.clone(), s(&l["reference"]), narration.clone()]
+                })
+                .collect(),
+        ),
+        "hdfc" => (
+            vec!["Transaction Type", "Beneficiary Code", "Beneficiary Account Number", "Instrument Amount", "Beneficiary Name", "Customer Reference Number", "Payment Details", "Value Date", "IFSC Code", "Beneficiary Bank Name", "Debit Account Number"],
+            lines
+                .iter()
+                .map(|l| {
+                    // HDFC codes: N = NEFT, R = RTGS, I = within HDFC
+                    let code = match s(&l["mode"]).as_str() {
+                        "RTGS" => "R",
+                        "FT" => "I",
+                        _ => "N",
+                    };
+                    vec![code.to_string(), s(&l["employeeId"]), s(&l["accountNumber"]), amount(l), name(l), s(&l["reference"]), narration.clone(), dmy.clone(), s(&l["ifsc"]), s(&l["bankName"]), debit.clone()]
+                })
+                .collect(),
+        ),
+        "icici" => (
+            vec!["PYMT_PROD_TYPE_CODE", "PYMT_MODE", "DEBIT_ACC_NO", "BNF_NAME", "BENE_ACC_NO", "BENE_IFSC", "AMOUNT", "DEBIT_NARR", "CREDIT_NARR", "PYMT_DATE", "REF_NO"],
+            lines
+                .iter()
+                .map(|l| {
+                    vec!["PAB_VENDOR".to_string(), s(&l["mode"]), debit.clone(), name(l), s(&l["accountNumber"]), s(&l["ifsc"]), amount(l), narration.clone(), narration.clone(), dmy.clone(), s(&l["reference"])]
+                })
+                .collect(),
+        ),
+        "axis" => (
+            vec!["Payment Method", "Amount", "Value Date", "Beneficiary Name", "Beneficiary Account No", "Beneficiary IFSC", "Debit Account No", "Customer Reference No", "Remarks"],
+            lines
+                .iter()
+                .map(|l| {
+                    let code = match s(&l["mode"]).as_str() {
+                        "RTGS" => "R",
+                        "FT" => "I",
+                        _ => "N",
+                    };
+                    vec![code.to_string(), amount(l), dmy.clone(), name(l), s(&l["accountNumber"]), s(&l["ifsc"]), debit.clone(), s(&l["reference"]), narration.clone()]
+                })
+                .collect(),
+        ),
+        "generic" => (
+            vec!["Reference", "Employee ID", "Beneficiary Name", "Account Number", "IFSC", "Bank", "Amount", "Mode", "Payment Date", "Debit Account", "Narration"],
+            lines
+                .iter()
+                .map(|l| {
+                    vec![s(&l["reference"]), s(&l["employeeId"]), name(l), s(&l["accountNumber"]), s(&l["ifsc"]), s(&l["bankName"]), amount(l), s(&l["mode"]), date.clone(), debit.clone(), narration.clone()]
+                })
+                .collect(),
+        ),
+        other => return Err(format!("Unknown bank format '{}'; use one of {}", other, BANK_FORMATS.join(", "))),
+    };
+
+    Ok(Table {
+        title: format!("Salary transfer {}", s(&batch["batchId"])),
+        headers: headers.into_iter().map(|h| h.to_string()).collect(),
+        rows,
+    })
+}
+
+/// One row of a bank's response (status) file.
+#[derive(Debug, Clone)]
+pub struct TransferResult {
+    pub reference: String,
+    pub account_number: String,
+    pub amount: f64,
+    /// paid, failed or pending
+    pub status: String,
+    pub utr: String,
+    pub reason: String,
+}
+
+#[derive(Default)]
+struct ResponseColumns {
+    reference: Option<usize>,
+    account: Option<usize>,
+    amount: Option<usize>,
+    status: Option<usize>,
+    utr: Option<usize>,
+    reason: Option<usize>,
+}
+
+fn detect_response_columns(row: &[String]) -> Option<ResponseColumns> {
+    let mut c = ResponseColumns::default();
+    for (i, raw) in row.iter().enumerate() {
+        let h = normalise(raw);
+        if h.contains("utr") || h.contains("bank ref") || h.contains("transaction id") {
+            c.utr.get_or_insert(i);
+        } else if h.contains("ref") || h == "beneficiary code" {
+            c.reference.get_or_insert(i);
+        } else if h.contains("account") && !h.contains("debit") {
+            c.account.get_or_insert(i);
+        } else if h.contains("amount") {
+            c.amount.get_or_insert(i);
+        } else if h.contains("reason") || h.contains("remark") || h.contains("error") || h.contains("description") {
+            c.reason.get_or_insert(i);
+        } else if h.contains("status") {
+            c.status.get_or_insert(i);
+        }
+    }
+    if c.status.is_some() && (c.reference.is_some() || c.account.is_some()) {
+        Some(c)
+    } else {
+        None
+    }
+}
+
+/// Maps a bank's status text onto paid / failed / pending.
+pub fn normalise_status(text: &str) -> &'static str {
+    let t = text.to_lowercase();
+    if ["fail", "reject", "return", "invalid", "cancel"].iter().any(|k| t.contains(k)) {
+        "failed"
+    } else if ["success", "paid", "processed", "credited", "complete", "executed"].iter().any(|k| t.contains(k)) {
+        "paid"
+    } else {
+        "pending"
+    }
+}
+
+/// Reads a bank's response file (CSV/TSV or Excel). Rows need a status column and either our
+/// customer reference or the beneficiary account number.
+pub fn parse_transfer_response(file_name: &str, bytes: &[u8]) -> Result<Vec<TransferResult>, String> {
+    let rows = read_rows(file_name, bytes)?;
+    let (header_idx, cols) = rows
+        .iter()
+        .enumerate()
+        .take(30)
+        .find_map(|(i, r)| detect_response_columns(r).map(|c| (i, c)))
+        .ok_or("Could not find the response header (status plus reference or account number columns)")?;
+
+    let get = |row: &[String], idx: Option<usize>| -> String {
+        idx.and_then(|i| row.get(i)).map(|s| s.trim().to_string()).unwrap_or_default()
+    };
+    Ok(rows
+        .iter()
+        .skip(header_idx + 1)
+        .filter(|r| !get(r, cols.status).is_empty())
+        .map(|r| TransferResult {
+            reference: get(r, cols.reference),
+            account_number: get(r, cols.account).trim_start_matches('\'').to_string(),
+            amount: parse_amount(&get(r, cols.amount)).abs(),
+            status: normalise_status(&get(r, cols.status)).to_string(),
+            utr: get(r, cols.utr),
+            reason: get(r, cols.reason),
+        })
+        .collect())
+}
@@ -0,0 +1,101 @@
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn secret() -> Result<String, &'static str> {
+    std::env::var("CHECKIN_QR_SECRET")
+        .or_else(|_| std::env::var("JWT_SECRET"))
+        .map_err(|_| "QR check-in is not configured; set CHECKIN_QR_SECRET")
+}
+
+fn signature(school_id: &str, display_id: &str, window: i64) -> Result<String, &'static str> {
+    let mut mac = HmacSha256::new_from_slice(secret()?.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("checkin|{}|{}|{}", school_id, display_id, window).as_bytes());
+    Ok(hex::encode(&mac.finalize().into_bytes()[..16]))
+}
+
+/// The QR token shown by display `display_id` for the rotation window containing `now` (unix
+/// seconds): `<displayId>.<window>.<signature>`, with the time the window ends.
+pub fn qr_token(school_id: &str, display_id: &str, now: i64, rotation_seconds: i64) -> Result<(String, i64), &'static str> {
+    let rotation = rotation_seconds.max(5);
+    let window = now / rotation;
+    Ok((
+        format!("{}.{}.{}", display_id, window, signature(school_id, display_id, window)?),
+        (window + 1) * rotation,
+    ))
+}
+
+/// Checks a scanned token against the current and the previous rotation window and returns the
+/// display it was shown on.
+pub fn verify_qr_token(school_id: &str, token: &str, now: i64, rotation_seconds: i64) -> Result<String, &'static str> {
+    let rotation = rotation_seconds.max(5);
+    let mut parts = token.trim().rsplitn(3, '.');
+    let (sig, window, display_id) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(sig), Some(window), Some(display_id)) if !display_id.is_empty() => (sig, window, display_id),
+        _ => return Err("Invalid QR code"),
+    };
+    let window: i64 = window.parse().map_err(|_| "Invalid QR code")?;
+    let expected = signature(school_id, display_id, window)?;
+    if !constant_time_eq(sig.as_bytes(), expected.as_bytes()) {
+        return Err("QR code is not from this school");
+    }
+    let current = now / rotation;
+    if window > current || window < current - 1 {
+        return Err("QR code has expired; scan the code currently on display");
+    }
+    Ok(display_id.to_string())
+}
+
+/// Constant-time comparison so signatures can't be guessed byte by byte.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Great-circle distance in metres between two `(latitude, longitude)` points.
+pub fn distance_meters(a: (f64, f64), b: (f64, f64)) -> f64 {
+    const EARTH_RADIUS_M: f64 = 6_371_000.0;
+    let (lat1, lat2) = (a.0.to_radians(), b.0.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (b.1 - a.1).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+/// `(latitude, longitude, radiusMeters)` of a configured geofence.
+pub fn geofence(settings: &Value) -> Option<(f64, f64, f64)> {
+    let fence = &settings["selfCheckIn"]["geofence"];
+    Some((
+        fence["latitude"].as_f64()?,
+        fence["longitude"].as_f64()?,
+        fence["radiusMeters"].as_f64().unwrap_or(150.0),
+    ))
+}
+
+/// Validates a `selfCheckIn` settings block before it is saved.
+pub fn validate_settings(block: &Value) -> Result<(), String> {
+    if block.is_null() {
+        return Ok(());
+    }
+    if !block.is_object() {
+        return Err("selfCheckIn must be an object".to_string());
+    }
+    if let Some(r) = block.get("qrRotationSeconds").filter(|v| !v.is_null()) {
+        if r.as_i64().is_none_or(|r| !(5..=3600).contains(&r)) {
+            return Err("qrRotationSeconds must be between 5 and 3600".to_string());
+        }
+    }
+    let fence = &block["geofence"];
+    if !fence.is_null() {
+        let lat = fence["latitude"].as_f64().ok_or("geofence.latitude is required")?;
+        let lon = fence["longitude"].as_f64().ok_or("geofence.longitude is required")?;
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err("geofence coordinates are out of range".to_string());
+        }
+        if fence["radiusMeters"].as_f64().is_some_and(|r| r <= 0.0) {
+            return Err("geofence.radiusMeters must be positive".to_string());
+        }
+    }
+    Ok(())
+}
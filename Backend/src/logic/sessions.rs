@@ -0,0 +1,75 @@
+use crate::logic::holidays::academic_year_range;
+use chrono::NaiveDate;
+use serde_json::Value;
+
+/// `upcoming` sessions are being set up, one `active` session takes the year's fees and exams,
+/// and `archived` sessions are read-only once the year-end rollover closes them.
+pub const SESSION_STATUSES: &[&str] = &["upcoming", "active", "archived"];
+
+/// What happens to a student at year end: `pass` moves them to the next class, `detain` keeps
+/// them where they are, and `leave` takes them off the rolls (transfer, drop-out or passing out).
+pub const PROMOTION_OUTCOMES: &[&str] = &["pass", "detain", "leave"];
+
+pub fn normalize_outcome(outcome: &str) -> Option<&'static str> {
+    match outcome.trim().to_lowercase().replace(['-', ' '], "_").as_str() {
+        "pass" | "passed" | "promote" | "promoted" => Some("pass"),
+        "detain" | "detained" | "retain" | "repeat" | "fail" | "failed" => Some("detain"),
+        "leave" | "left" | "tc" | "transfer" | "transferred" | "pass_out" | "passed_out" | "graduate" => Some("leave"),
+        _ => None,
+    }
+}
+
+/// Name and dates of a session. A `2026-27` style name on its own gets April to March.
+pub fn validate_session(data: &Value) -> Result<(String, NaiveDate, NaiveDate), String> {
+    let name = data["name"].as_str().map(str::trim).filter(|n| !n.is_empty()).ok_or("name is required")?;
+    let date = |key: &str| -> Result<Option<NaiveDate>, String> {
+        data[key]
+            .as_str()
+            .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| format!("{} must be YYYY-MM-DD", key)))
+            .transpose()
+    };
+    let (start, end) = match (date("startDate")?, date("endDate")?) {
+        (Some(start), Some(end)) => (start, end),
+        (None, None) => academic_year_range(name).ok_or("Give startDate and endDate, or name the session like 2026-27")?,
+        _ => return Err("Give both startDate and endDate".into()),
+    };
+    if end <= start {
+        return Err("endDate must be after startDate".into());
+    }
+    if (end - start).num_days() > 550 {
+        return Err("A session can't run longer than 18 months".into());
+    }
+    Ok((name.to_string(), start, end))
+}
+
+/// Where a class sits in the school's progression: Nursery, LKG and UKG before 1, then the
+/// class number in digits or Roman numerals (`Class 7`, `Grade VII`, `7th`).
+pub fn class_rank(name: &str) -> Option<i32> {
+    let lower = name.to_lowercase();
+    for (word, rank) in [("nursery", -3), ("pre", -3), ("lkg", -2), ("ukg", -1)] {
+        if lower.split(|c: char| !c.is_alphanumeric()).any(|w| w == word) {
+            return Some(rank);
+        }
+    }
+    let digits: String = lower.chars().skip_while(|c| !c.is_ascii_digit()).take_while(|c| c.is_ascii_digit()).collect();
+    if let Ok(n) = digits.parse() {
+        return Some(n);
+    }
+    const ROMAN: [&str; 12] = ["i", "ii", "iii", "iv", "v", "vi", "vii", "viii", "ix", "x", "xi", "xii"];
+    lower
+        .split(|c: char| !c.is_alphanumeric())
+        .find_map(|w| ROMAN.iter().position(|r| *r == w))
+        .map(|i| i as i32 + 1)
+}
+
+/// The class students of `class` usually move up to: the nearest higher-ranked class.
+/// `None` for the top class, or when the names don't say.
+pub fn suggest_next_class<'a>(class: &Value, classes: &'a [Value]) -> Option<&'a Value> {
+    let rank = class_rank(class["name"].as_str()?)?;
+    classes
+        .iter()
+        .filter_map(|c| Some((class_rank(c["name"].as_str()?)?, c)))
+        .filter(|(r, _)| *r > rank)
+        .min_by_key(|(r, _)| *r)
+        .map(|(_, c)| c)
+}
@@ -0,0 +1,330 @@
+use serde_json::{json, Value};
+
+/// Statutory rules used when a school has not saved its own. Rates are percentages, amounts are
+/// monthly rupees except the income-tax slabs, which are annual.
+pub fn default_settings() -> Value {
+    json!({
+        "pf": {
+            "enabled": true,
+            "employeeRate": 12.0,
+            "employerRate": 12.0,
+            "epsRate": 8.33,
+            "wageCeiling": 15000.0,
+            // Contribute on actual basic above the ceiling when false
+            "restrictToCeiling": true
+        },
+        "esi": {
+            "enabled": true,
+            "employeeRate": 0.75,
+            "employerRate": 3.25,
+            "wageThreshold": 21000.0
+        },
+        "pt": {
+            "enabled": true,
+            // State code picked from `stateSlabs`; employees may override with `ptState`
+            "state": "",
+            // Custom slabs replace the state table when non-empty: [{min, max, amount, februaryAmount}]
+            "slabs": [],
+            "stateSlabs": {
+                "KA": [{"min": 25000.0, "max": null, "amount": 200.0}],
+                "MH": [
+                    {"min": 7501.0, "max": 10000.0, "amount": 175.0},
+                    {"min": 10001.0, "max": null, "amount": 200.0, "februaryAmount": 300.0}
+                ],
+                "WB": [
+                    {"min": 10001.0, "max": 15000.0, "amount": 110.0},
+                    {"min": 15001.0, "max": 25000.0, "amount": 130.0},
+                    {"min": 25001.0, "max": 40000.0, "amount": 150.0},
+                    {"min": 40001.0, "max": null, "amount": 200.0}
+                ],
+                "GJ": [{"min": 12000.0, "max": null, "amount": 200.0}],
+                "AP": [
+                    {"min": 15001.0, "max": 20000.0, "amount": 150.0},
+                    {"min": 20001.0, "max": null, "amount": 200.0}
+                ],
+                "TS": [
+                    {"min": 15001.0, "max": 20000.0, "amount": 150.0},
+                    {"min": 20001.0, "max": null, "amount": 200.0}
+                ]
+            }
+        },
+        "tds": {
+            "enabled": true,
+            "defaultRegime": "new",
+            "cessRate": 4.0,
+            "newRegime": {
+                "standardDeduction": 75000.0,
+                "rebateLimit": 1200000.0,
+                // Tax just above the rebate limit is capped at the income over it (87A proviso)
+                "marginalRelief": true,
+                "slabs": [
+                    {"upTo": 400000.0, "rate": 0.0},
+                    {"upTo": 800000.0, "rate": 5.0},
+                    {"upTo": 1200000.0, "rate": 10.0},
+                    {"upTo": 1600000.0, "rate": 15.0},
+                    {"upTo": 2000000.0, "rate": 20.0},
+                    {"upTo": 2400000.0, "rate": 25.0},
+                    {"upTo": null, "rate": 30.0}
+                ]
+            },
+            "oldRegime": {
+                "standardDeduction": 50000.0,
+                "rebateLimit": 500000.0,
+                "marginalRelief": false,
+                "section80CLimit": 150000.0,
+                "slabs": [
+                    {"upTo": 250000.0, "rate": 0.0},
+                    {"upTo": 500000.0, "rate": 5.0},
+                    {"upTo": 1000000.0, "rate": 20.0},
+                    {"upTo": null, "rate": 30.0}
+                ]
+            }
+        }
+    })
+}
+
+/// Overlays a school's saved settings on the defaults, one section (pf/esi/pt/tds) at a time.
+pub fn merge_settings(saved: &Value) -> Value {
+    let mut merged = default_settings();
+    for section in ["pf", "esi", "pt", "tds"] {
+        if let (Some(target), Some(src)) = (merged[section].as_object_mut(), saved[section].as_object()) {
+            for (k, v) in src {
+                target.insert(k.clone(), v.clone());
+            }
+        }
+    }
+    merged
+}
+
+fn num(v: &Value) -> f64 {
+    v.as_f64()
+        .or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+        .unwrap_or(0.0)
+}
+
+fn flag(v: &Value, default: bool) -> bool {
+    v.as_bool().unwrap_or(default)
+}
+
+/// Months left in the Indian financial year (April-March), counting `month` itself.
+pub fn remaining_fy_months(month: u32) -> f64 {
+    if month >= 4 {
+        (16 - month) as f64
+    } else {
+        (4 - month.max(1)) as f64
+    }
+}
+
+/// Start year of the financial year `month`/`year` falls in (2025 for Jan 2026).
+pub fn fy_start_year(month: u32, year: i32) -> i32 {
+    if month >= 4 {
+        year
+    } else {
+        year - 1
+    }
+}
+
+fn professional_tax(pt: &Value, state: &str, earned_gross: f64, month: u32) -> f64 {
+    let custom = pt["slabs"].as_array().filter(|s| !s.is_empty());
+    let slabs = match custom {
+        Some(s) => s.clone(),
+        None => pt["stateSlabs"][state].as_array().cloned().unwrap_or_default(),
+    };
+    slabs
+        .iter()
+        .find(|s| earned_gross >= num(&s["min"]) && (s["max"].is_null() || earned_gross <= num(&s["max"])))
+        .map(|s| {
+            if month == 2 && !s["februaryAmount"].is_null() {
+                num(&s["februaryAmount"])
+            } else {
+                num(&s["amount"])
+            }
+        })
+        .unwrap_or(0.0)
+}
+
+/// Annual income tax (with cess) on `taxable` income under one regime's slabs and rebate.
+/// With `marginalRelief`, tax before cess never exceeds the income above the rebate limit.
+pub fn annual_tax(regime: &Value, taxable: f64, cess_rate: f64) -> f64 {
+    let rebate_limit = num(&regime["rebateLimit"]);
+    if taxable <= rebate_limit {
+        return 0.0;
+    }
+    let mut tax = 0.0;
+    let mut lower = 0.0;
+    for slab in regime["slabs"].as_array().into_iter().flatten() {
+        let upper = if slab["upTo"].is_null() { f64::INFINITY } else { num(&slab["upTo"]) };
+        if taxable > lower {
+            tax += (taxable.min(upper) - lower) * num(&slab["rate"]) / 100.0;
+        }
+        lower = upper;
+    }
+    if rebate_limit > 0.0 && flag(&regime["marginalRelief"], true) {
+        tax = tax.min(taxable - rebate_limit);
+    }
+    tax * (1.0 + cess_rate / 100.0)
+}
+
+/// Taxable income for the year after the regime's standard deduction and, under the old
+/// regime, the employee's declared deductions, employee PF (80C) and professional tax.
+pub fn taxable_income(
+    tds: &Value,
+    regime_name: &str,
+    emp: &Value,
+    annual_gross: f64,
+    annual_pf: f64,
+    annual_pt: f64,
+) -> f64 {
+    let regime = &tds[if regime_name == "old" { "oldRegime" } else { "newRegime" }];
+    let mut taxable = annual_gross - num(&regime["standardDeduction"]);
+    if regime_name == "old" {
+        let d = &emp["taxDeclarations"];
+        let c80 = (num(&d["section80C"]) + annual_pf).min(num(&regime["section80CLimit"]));
+        taxable -= c80 + num(&d["section80D"]) + num(&d["hraExemption"]) + num(&d["otherDeductions"]) + annual_pt;
+    }
+    taxable.max(0.0)
+}
+
+/// Everything the statutory calculation needs besides the employee record.
+pub struct StatutoryInputs<'a> {
+    pub settings: &'a Value,
+    /// Basic pay (base + increment) after absence, the PF wage base.
+    pub earned_basic: f64,
+    /// Full-month gross, used for the ESI eligibility threshold.
+    pub gross: f64,
+    /// Gross after absence deduction, including `one_off`.
+    pub earned_gross: f64,
+    /// Part of `earned_gross` paid only this month (arrears), left out of the TDS projection.
+    pub one_off: f64,
+    pub month: u32,
+    /// Earlier approved months of the same financial year: {gross, pfEmployee, professionalTax, tds}
+    pub ytd: &'a Value,
+}
+
+/// Employee and employer statutory contributions for one month.
+///
+/// Returns `{pfWages, pfEmployee, pfEmployerEpf, pfEmployerEps, esiWages, esiEmployee,
+/// esiEmployer, professionalTax, tds, taxRegime, projectedTaxableIncome, projectedAnnualTax,
+/// employeeTotal, lines}` where `lines` are the payslip deduction rows.
+pub fn compute_statutory(emp: &Value, inp: &StatutoryInputs) -> Value {
+    let s = inp.settings;
+    let round = |v: f64| v.round();
+
+    // Provident fund
+    let (pf, pf_on) = (&s["pf"], flag(&s["pf"]["enabled"], true) && flag(&emp["pfApplicable"], true));
+    let pf_wages = if !pf_on {
+        0.0
+    } else if flag(&pf["restrictToCeiling"], true) {
+        inp.earned_basic.min(num(&pf["wageCeiling"]))
+    } else {
+        inp.earned_basic
+    };
+    let pf_employee = round(pf_wages * num(&pf["employeeRate"]) / 100.0);
+    let eps = round(pf_wages.min(num(&pf["wageCeiling"])) * num(&pf["epsRate"]) / 100.0);
+    let pf_employer = round(pf_wages * num(&pf["employerRate"]) / 100.0);
+    let pf_employer_epf = (pf_employer - eps).max(0.0);
+
+    // Employees' State Insurance, only under the wage threshold
+    let esi = &s["esi"];
+    let esi_on = flag(&esi["enabled"], true)
+        && flag(&emp["esiApplicable"], true)
+        && inp.gross <= num(&esi["wageThreshold"]);
+    let esi_wages = if esi_on { inp.earned_gross } else { 0.0 };
+    let esi_employee = (esi_wages * num(&esi["employeeRate"]) / 100.0).ceil();
+    let esi_employer = (esi_wages * num(&esi["employerRate"]) / 100.0).ceil();
+
+    // Professional tax
+    let pt = &s["pt"];
+    let pt_state = emp["ptState"].as_str().filter(|v| !v.is_empty()).or(pt["state"].as_str()).unwrap_or("");
+    let professional_tax = if flag(&pt["enabled"], true) {
+        professional_tax(pt, pt_state, inp.earned_gross, inp.month)
+    } else {
+        0.0
+    };
+
+    // Income tax: project the year from YTD plus this month's pay for the months left
+    let tds_cfg = &s["tds"];
+    let regime = emp["taxRegime"]
+        .as_str()
+        .filter(|r| *r == "old" || *r == "new")
+        .or(tds_cfg["defaultRegime"].as_str())
+        .unwrap_or("new")
+        .to_string();
+    let remaining = remaining_fy_months(inp.month);
+    let ytd = inp.ytd;
+    let annual_gross = num(&ytd["gross"]) + (inp.earned_gross - inp.one_off) * remaining + inp.one_off;
+    let annual_pf = num(&ytd["pfEmployee"]) + pf_employee * remaining;
+    let annual_pt = num(&ytd["professionalTax"]) + professional_tax * remaining;
+    let taxable = taxable_income(tds_cfg, &regime, emp, annual_gross, annual_pf, annual_pt);
+    let regime_cfg = &tds_cfg[if regime == "old" { "oldRegime" } else { "newRegime" }];
+    let annual = annual_tax(regime_cfg, taxable, num(&tds_cfg["cessRate"]));
+    let tds = if flag(&tds_cfg["enabled"], true) {
+        round(((annual - num(&ytd["tds"])) / remaining).max(0.0))
+    } else {
+        0.0
+    };
+
+    let mut lines = Vec::new();
+    for (code, label, amount) in [
+        ("pf", "Provident Fund (EPF)", pf_employee),
+        ("esi", "ESI", esi_employee),
+        ("pt", "Professional Tax", professional_tax),
+        ("tds", "Income Tax (TDS)", tds),
+    ] {
+        if amount > 0.0 {
+            lines.push(json!({"code": code, "label": label, "amount": amount}));
+        }
+    }
+
+    json!({
+        "pfWages": pf_wages,
+        "pfEmployee": pf_employee,
+        "pfEmployerEpf": pf_employer_epf,
+        "pfEmployerEps": eps,
+        "esiWages": esi_wages,
+        "esiEmployee": esi_employee,
+        "esiEmployer": esi_employer,
+        "ptState": pt_state,
+        "professionalTax": professional_tax,
+        "tds": tds,
+        "taxRegime": regime,
+        "projectedTaxableIncome": taxable.round(),
+        "projectedAnnualTax": annual.round(),
+        "employeeTotal": pf_employee + esi_employee + professional_tax + tds,
+        "lines": lines
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn no_tax_up_to_the_rebate_limit() {
+        let regime = &default_settings()["tds"]["newRegime"];
+        assert_eq!(annual_tax(regime, 1_200_000.0, 4.0), 0.0);
+    }
+
+    #[test]
+    fn marginal_relief_caps_tax_at_the_income_over_the_limit() {
+        let regime = &default_settings()["tds"]["newRegime"];
+        // Slab tax is 61,500; only 10,000 is over the limit
+        assert!(close(annual_tax(regime, 1_210_000.0, 4.0), 10_400.0));
+    }
+
+    #[test]
+    fn slab_tax_applies_once_it_is_below_the_excess() {
+        let regime = &default_settings()["tds"]["newRegime"];
+        assert!(close(annual_tax(regime, 2_000_000.0, 4.0), 208_000.0));
+    }
+
+    #[test]
+    fn old_regime_has_no_marginal_relief() {
+        let regime = &default_settings()["tds"]["oldRegime"];
+        assert!(close(annual_tax(regime, 500_100.0, 0.0), 12_520.0));
+    }
+}
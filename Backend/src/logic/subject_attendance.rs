@@ -0,0 +1,123 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Statuses a teacher can mark for a student in one period. `excused` is for periods missed on
+/// school duty or with a medical note: they don't count as held, so they neither help nor hurt
+/// the percentage.
+pub const PERIOD_STATUSES: &[&str] = &["present", "absent", "late", "excused"];
+
+/// Accepts the spellings clients send (`P`/`A`/`L`/`E`, `on duty`, `medical`) and returns the
+/// stored status.
+pub fn normalize_period_status(status: &str) -> Option<&'static str> {
+    match status.trim().to_lowercase().replace(['-', ' '], "_").as_str() {
+        "present" | "p" => Some("present"),
+        "absent" | "a" => Some("absent"),
+        "late" | "l" => Some("late"),
+        "excused" | "e" | "on_duty" | "od" | "medical" | "ml" => Some("excused"),
+        _ => None,
+    }
+}
+
+fn text<'a>(v: &'a Value, key: &str) -> &'a str {
+    v[key].as_str().unwrap_or("")
+}
+
+/// Whether `student` sits the periods of `subject`. Compulsory subjects are taken by the whole
+/// class; an elective only by students whose `enrolledSubjects` (IDs, names or
+/// `{id|subjectId, name|subjectName}`) name it. Students with no enrolment list are assumed to
+/// take everything, since older records never filled it in.
+pub fn takes_subject(student: &Value, subject: Option<&Value>) -> bool {
+    let Some(subject) = subject.filter(|s| s["isCompulsory"] == false) else { return true };
+    let enrolled = student["enrolledSubjects"].as_array().map(|a| a.as_slice()).unwrap_or(&[]);
+    if enrolled.is_empty() {
+        return true;
+    }
+    let names: Vec<String> = ["id", "name"]
+        .iter()
+        .map(|k| text(subject, k).trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+    enrolled.iter().any(|e| {
+        let keys = match e {
+            Value::String(s) => vec![s.as_str()],
+            _ => ["id", "subjectId", "name", "subjectName"].iter().map(|k| text(e, k)).collect(),
+        };
+        keys.iter().any(|k| names.contains(&k.trim().to_lowercase()))
+    })
+}
+
+/// One student's periods in one subject.
+#[derive(Default, Clone, Copy)]
+pub struct SubjectTally {
+    pub present: usize,
+    pub late: usize,
+    pub absent: usize,
+    pub excused: usize,
+}
+
+impl SubjectTally {
+    pub fn add(&mut self, status: &str) {
+        match status {
+            "present" => self.present += 1,
+            "late" => self.late += 1,
+            "absent" => self.absent += 1,
+            "excused" => self.excused += 1,
+            _ => {}
+        }
+    }
+
+    /// Periods that count towards the percentage; excused ones are left out.
+    pub fn held(&self) -> usize {
+        self.present + self.late + self.absent
+    }
+
+    /// Late counts as attended.
+    pub fn attended(&self) -> usize {
+        self.present + self.late
+    }
+
+    pub fn percent(&self) -> f64 {
+        match self.held() {
+            0 => 0.0,
+            held => (self.attended() as f64 * 10000.0 / held as f64).round() / 100.0,
+        }
+    }
+
+    pub fn to_json(self, threshold: f64) -> Value {
+        json!({
+            "held": self.held(),
+            "attended": self.attended(),
+            "present": self.present,
+            "late": self.late,
+            "absent": self.absent,
+            "excused": self.excused,
+            "attendancePercent": self.percent(),
+            "belowThreshold": self.held() > 0 && self.percent() < threshold,
+            "periodsNeeded": periods_needed(self.attended(), self.held(), threshold)
+        })
+    }
+}
+
+/// Tallies `{studentId, subjectId, status}` records per student and subject.
+pub fn tally(records: &[Value]) -> BTreeMap<(String, String), SubjectTally> {
+    let mut tallies: BTreeMap<(String, String), SubjectTally> = BTreeMap::new();
+    for r in records {
+        let key = (text(r, "studentId").to_string(), text(r, "subjectId").to_string());
+        tallies.entry(key).or_default().add(text(r, "status"));
+    }
+    tallies
+}
+
+/// How many more periods in a row the student must attend to reach `threshold` percent;
+/// `None` when no number of periods gets there (a 100% threshold after any absence).
+pub fn periods_needed(attended: usize, held: usize, threshold: f64) -> Option<usize> {
+    let (attended, held, t) = (attended as f64, held as f64, threshold / 100.0);
+    if held == 0.0 || attended >= t * held - 1e-9 {
+        return Some(0);
+    }
+    if t >= 1.0 {
+        return None;
+    }
+    // (attended + n) / (held + n) >= t  =>  n >= (t * held - attended) / (1 - t)
+    Some(((t * held - attended) / (1.0 - t) - 1e-9).ceil() as usize)
+}
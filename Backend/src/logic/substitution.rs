@@ -0,0 +1,147 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Times are "HH:MM" (or "HH:MM:SS"), so string comparison orders them correctly.
+fn overlaps(a_start: &str, a_end: &str, b_start: &str, b_end: &str) -> bool {
+    a_start < b_end && b_start < a_end
+}
+
+fn text<'a>(v: &'a Value, key: &str) -> &'a str {
+    v[key].as_str().unwrap_or("")
+}
+
+/// Subjects an employee can teach: the `subject`/`subjects` on their record (names or IDs)
+/// plus every subject they already teach in the timetable.
+pub fn teacher_subjects(employee: &Value, week_periods: &[Value]) -> Vec<String> {
+    let employee_id = text(employee, "employeeId");
+    let mut subjects: Vec<String> = text(employee, "subject")
+        .split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+    subjects.extend(
+        employee["subjects"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|s| s.as_str().map(|s| s.trim().to_lowercase())),
+    );
+    for p in week_periods.iter().filter(|p| p["teacherId"] == employee_id) {
+        for key in ["subjectId", "subjectName"] {
+            let s = text(p, key).trim().to_lowercase();
+            if !s.is_empty() {
+                subjects.push(s);
+            }
+        }
+    }
+    subjects.sort();
+    subjects.dedup();
+    subjects
+}
+
+fn qualified(subjects: &[String], period: &Value) -> bool {
+    ["subjectId", "subjectName"]
+        .iter()
+        .map(|k| text(period, k).trim().to_lowercase())
+        .any(|s| !s.is_empty() && subjects.contains(&s))
+}
+
+/// Inputs for proposing substitutes on one day.
+pub struct SubstitutionDay<'a> {
+    /// The day's periods: `{periodId, classId, className, name, startTime, endTime, teacherId, subjectId, subjectName}`
+    pub periods: &'a [Value],
+    /// Every period in the timetable, used to work out who teaches what
+    pub all_periods: &'a [Value],
+    /// Teaching staff: employee records with `employeeId`
+    pub teachers: &'a [Value],
+    /// Employee ID to absence reason (`absent` or a leave type)
+    pub absent: &'a HashMap<String, String>,
+    /// Substitutions already assigned this day: `{periodId, substituteId, startTime, endTime}`
+    pub assigned: &'a [Value],
+    /// Substitutions each teacher has taken in the balancing window (usually the week)
+    pub load: &'a HashMap<String, i64>,
+}
+
+/// Proposes a substitute for each period whose teacher is away and that has no substitute yet.
+///
+/// Candidates are teachers present that day with no class or substitution overlapping the
+/// period, ranked qualified first, then by fewest substitutions in the window, then by fewest
+/// periods that day. `suggested` is chosen greedily in period order, so one teacher is never
+/// suggested twice for the same time and the load spreads across the day.
+pub fn propose(day: &SubstitutionDay, max_candidates: usize) -> Vec<Value> {
+    let mut load = day.load.clone();
+    // Busy slots per teacher: own periods plus substitutions (existing and suggested)
+    let mut busy: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    for p in day.periods {
+        busy.entry(text(p, "teacherId").to_string())
+            .or_default()
+            .push((text(p, "startTime").to_string(), text(p, "endTime").to_string()));
+    }
+    for a in day.assigned {
+        busy.entry(text(a, "substituteId").to_string())
+            .or_default()
+            .push((text(a, "startTime").to_string(), text(a, "endTime").to_string()));
+    }
+    let periods_today: HashMap<String, usize> = busy.iter().map(|(k, v)| (k.clone(), v.len())).collect();
+    let subjects: HashMap<String, Vec<String>> = day
+        .teachers
+        .iter()
+        .map(|t| (text(t, "employeeId").to_string(), teacher_subjects(t, day.all_periods)))
+        .collect();
+
+    let mut uncovered: Vec<&Value> = day
+        .periods
+        .iter()
+        .filter(|p| day.absent.contains_key(text(p, "teacherId")))
+        .filter(|p| !day.assigned.iter().any(|a| a["periodId"] == p["periodId"]))
+        .collect();
+    uncovered.sort_by(|a, b| text(a, "startTime").cmp(text(b, "startTime")));
+
+    let mut out = Vec::new();
+    for period in uncovered {
+        let (start, end) = (text(period, "startTime"), text(period, "endTime"));
+        let mut candidates: Vec<Value> = day
+            .teachers
+            .iter()
+            .filter_map(|t| {
+                let id = text(t, "employeeId");
+                if id.is_empty() || day.absent.contains_key(id) {
+                    return None;
+                }
+                if busy.get(id).is_some_and(|slots| slots.iter().any(|(s, e)| overlaps(s, e, start, end))) {
+                    return None;
+                }
+                Some(json!({
+                    "employeeId": id,
+                    "name": t["name"],
+                    "qualified": qualified(subjects.get(id).map(|s| s.as_slice()).unwrap_or(&[]), period),
+                    "load": load.get(id).copied().unwrap_or(0),
+                    "periodsToday": periods_today.get(id).copied().unwrap_or(0)
+                }))
+            })
+            .collect();
+        candidates.sort_by_key(|c| {
+            (
+                !c["qualified"].as_bool().unwrap_or(false),
+                c["load"].as_i64().unwrap_or(0),
+                c["periodsToday"].as_u64().unwrap_or(0),
+                c["employeeId"].as_str().unwrap_or("").to_string(),
+            )
+        });
+        candidates.truncate(max_candidates);
+
+        let suggested = candidates.first().map(|c| c["employeeId"].as_str().unwrap_or("").to_string());
+        if let Some(id) = &suggested {
+            *load.entry(id.clone()).or_insert(0) += 1;
+            busy.entry(id.clone()).or_default().push((start.to_string(), end.to_string()));
+        }
+        out.push(json!({
+            "period": period,
+            "absentTeacherId": period["teacherId"],
+            "reason": day.absent.get(text(period, "teacherId")),
+            "candidates": candidates,
+            "suggested": suggested
+        }));
+    }
+    out
+}
@@ -0,0 +1,870 @@
+use chrono::NaiveTime;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+pub const DAY_NAMES: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+fn text<'a>(v: &'a Value, key: &str) -> &'a str {
+    v[key].as_str().unwrap_or("")
+}
+
+fn opt_text(v: &Value, key: &str) -> Option<String> {
+    v[key].as_str().map(|s| s.trim()).filter(|s| !s.is_empty()).map(|s| s.to_string())
+}
+
+fn parse_time(v: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(v.trim(), "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(v.trim(), "%H:%M:%S"))
+        .ok()
+}
+
+fn positive(v: &Value, field: &str, what: &str) -> Result<Option<u64>, String> {
+    match &v[field] {
+        Value::Null => Ok(None),
+        n => n.as_u64().filter(|n| *n > 0).map(Some).ok_or_else(|| format!("{} {} must be a positive whole number", what, field)),
+    }
+}
+
+/// Checks generator settings and fills in defaults.
+///
+/// `days` are the weekdays the school teaches (1 = Monday, default Monday to Saturday) and
+/// `slots` the teaching periods of a day in order (`{name?, startTime, endTime}`; breaks are
+/// simply left out). `classes` (`{classId, section?}`) limits which classes are scheduled;
+/// empty means every class and section. Each requirement is
+/// `{classId, section?, subjectId, periodsPerWeek, teacherId?, roomCategory?, maxPerDay?}`: no
+/// section applies it to every section, no teacher picks one of the subject's teachers, and
+/// `roomCategory` needs a free space of that category (a lab) for every period. `teachers` holds
+/// `{employeeId, maxPeriodsPerDay?, maxPeriodsPerWeek?, unavailable: [{day, slot?}]}` (no slot =
+/// the whole day) and `fixed` the slots that never move (`{classId, section?, day, slot, label?,
+/// subjectId?, teacherId?, roomId?}`), like assembly or games.
+pub fn validate_config(config: &Value) -> Result<Value, String> {
+    let mut days: Vec<u64> = match config["days"].as_array() {
+        Some(days) => days
+            .iter()
+            .map(|d| d.as_u64().filter(|d| (1..=7).contains(d)).ok_or("days must be numbers 1 (Monday) to 7 (Sunday)"))
+            .collect::<Result<_, _>>()?,
+        None => (1..=6).collect(),
+    };
+    days.sort();
+    days.dedup();
+    if days.is_empty() {
+        return Err("Give at least one teaching day".into());
+    }
+
+    let mut slots = Vec::new();
+    for (i, s) in config["slots"].as_array().ok_or("slots is required")?.iter().enumerate() {
+        let (start, end) = (parse_time(text(s, "startTime")), parse_time(text(s, "endTime")));
+        let (Some(start), Some(end)) = (start, end) else {
+            return Err(format!("Slot {} needs startTime and endTime as HH:MM", i + 1));
+        };
+        if end <= start {
+            return Err(format!("Slot {} ends before it starts", i + 1));
+        }
+        slots.push((start, end, opt_text(s, "name")));
+    }
+    if slots.is_empty() {
+        return Err("Give at least one teaching slot".into());
+    }
+    slots.sort_by_key(|s| s.0);
+    if slots.windows(2).any(|w| w[1].0 < w[0].1) {
+        return Err("Slots overlap".into());
+    }
+    let slots: Vec<Value> = slots
+        .iter()
+        .enumerate()
+        .map(|(i, (start, end, name))| {
+            json!({
+                "slot": i + 1,
+                "name": name.clone().unwrap_or_else(|| format!("Period {}", i + 1)),
+                "startTime": start.format("%H:%M").to_string(),
+                "endTime": end.format("%H:%M").to_string()
+            })
+        })
+        .collect();
+    let week = (days.len() * slots.len()) as u64;
+    let check_slot = |v: &Value, what: &str| -> Result<(), String> {
+        let day = v["day"].as_u64().unwrap_or(0);
+        if !days.contains(&day) {
+            return Err(format!("{} day must be one of the teaching days", what));
+        }
+        match v["slot"].as_u64() {
+            Some(s) if (1..=slots.len() as u64).contains(&s) => Ok(()),
+            _ => Err(format!("{} slot must be 1 to {}", what, slots.len())),
+        }
+    };
+
+    let classes: Vec<Value> = config["classes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|c| {
+            let class_id = opt_text(c, "classId").ok_or("Each class needs a classId")?;
+            Ok(json!({"classId": class_id, "section": opt_text(c, "section")}))
+        })
+        .collect::<Result<_, String>>()?;
+
+    let mut requirements = Vec::new();
+    for r in config["requirements"].as_array().ok_or("requirements is required")? {
+        let class_id = opt_text(r, "classId").ok_or("Each requirement needs a classId")?;
+        let subject_id = opt_text(r, "subjectId").ok_or("Each requirement needs a subjectId")?;
+        let what = format!("{} {}", class_id, subject_id);
+        let per_week = positive(r, "periodsPerWeek", &what)?.ok_or_else(|| format!("{} needs periodsPerWeek", what))?;
+        if per_week > week {
+            return Err(format!("{} asks for {} periods but the week has {}", what, per_week, week));
+        }
+        requirements.push(json!({
+            "classId": class_id,
+            "section": opt_text(r, "section"),
+            "subjectId": subject_id,
+            "periodsPerWeek": per_week,
+            "teacherId": opt_text(r, "teacherId"),
+            "roomCategory": opt_text(r, "roomCategory"),
+            "maxPerDay": positive(r, "maxPerDay", &what)?
+        }));
+    }
+
+    let mut teachers = Vec::new();
+    for t in config["teachers"].as_array().into_iter().flatten() {
+        let id = opt_text(t, "employeeId").ok_or("Each teacher needs an employeeId")?;
+        let unavailable: Vec<Value> = t["unavailable"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|u| {
+                if !days.contains(&u["day"].as_u64().unwrap_or(0)) {
+                    return Err(format!("{} unavailable day must be one of the teaching days", id));
+                }
+                if !u["slot"].is_null() {
+                    check_slot(u, &format!("{} unavailable", id))?;
+                }
+                Ok(json!({"day": u["day"], "slot": u["slot"]}))
+            })
+            .collect::<Result<_, String>>()?;
+        teachers.push(json!({
+            "employeeId": id,
+            "maxPeriodsPerDay": positive(t, "maxPeriodsPerDay", &id)?,
+            "maxPeriodsPerWeek": positive(t, "maxPeriodsPerWeek", &id)?,
+            "unavailable": unavailable
+        }));
+    }
+
+    let mut fixed = Vec::new();
+    for f in config["fixed"].as_array().into_iter().flatten() {
+        let class_id = opt_text(f, "classId").ok_or("Each fixed slot needs a classId")?;
+        check_slot(f, &format!("Fixed slot for {}", class_id))?;
+        if opt_text(f, "label").is_none() && opt_text(f, "subjectId").is_none() {
+            return Err(format!("Fixed slot for {} needs a label or subjectId", class_id));
+        }
+        fixed.push(json!({
+            "classId": class_id,
+            "section": opt_text(f, "section"),
+            "day": f["day"],
+            "slot": f["slot"],
+            "label": opt_text(f, "label"),
+            "subjectId": opt_text(f, "subjectId"),
+            "teacherId": opt_text(f, "teacherId"),
+            "roomId": opt_text(f, "roomId")
+        }));
+    }
+
+    Ok(json!({
+        "days": days,
+        "slots": slots,
+        "classes": classes,
+        "requirements": requirements,
+        "teachers": teachers,
+        "teacherMaxPerDay": positive(config, "teacherMaxPerDay", "")?,
+        "fixed": fixed
+    }))
+}
+
+/// A pin or fixed slot applies to the group when the class matches and it names no section or
+/// the group's own.
+fn applies(v: &Value, group: &Value) -> bool {
+    v["classId"] == group["classId"] && (v["section"].is_null() || v["section"] == group["section"])
+}
+
+/// Everything the generator works from.
+pub struct TimetableInput<'a> {
+    /// Output of `validate_config`
+    pub config: &'a Value,
+    /// Class groups to schedule: `{classId, className, section}`
+    pub groups: &'a [Value],
+    /// Subject records by ID, for names and `subjectTeachers`
+    pub subjects: &'a HashMap<String, Value>,
+    /// Spaces: `{spaceId, name, category}`
+    pub rooms: &'a [Value],
+    /// Lessons placed by hand that regeneration keeps: `{classId, section?, day, slot, subjectId, teacherId?, roomId?}`
+    pub pins: &'a [Value],
+    /// Varies tie-breaks so regenerating gives a different timetable
+    pub seed: u64,
+}
+
+#[derive(Clone)]
+struct Lesson {
+    group: usize,
+    subject: String,
+    teacher: Option<String>,
+    room_category: Option<String>,
+    max_per_day: usize,
+}
+
+#[derive(Clone)]
+struct Entry {
+    group: usize,
+    day: u64,
+    slot: u64,
+    subject: Option<String>,
+    teacher: Option<String>,
+    room: Option<String>,
+    label: Option<String>,
+    kind: &'static str,
+    lesson: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Block {
+    ClassBusy,
+    TeacherBusy,
+    TeacherUnavailable,
+    TeacherDayLimit,
+    TeacherWeekLimit,
+    SubjectDayLimit,
+    NoRoom,
+}
+
+impl Block {
+    fn describe(self) -> &'static str {
+        match self {
+            Block::ClassBusy => "the class has no free slot left",
+            Block::TeacherBusy => "the teacher is busy in every slot the class has free",
+            Block::TeacherUnavailable => "the teacher is unavailable in the slots the class has free",
+            Block::TeacherDayLimit => "the teacher would go over their periods-per-day limit",
+            Block::TeacherWeekLimit => "the teacher would go over their periods-per-week limit",
+            Block::SubjectDayLimit => "the subject would repeat more than allowed in a day",
+            Block::NoRoom => "no room of the required category is free",
+        }
+    }
+}
+
+/// Slot bookings and loads while the timetable is built.
+struct Board<'a> {
+    days: Vec<u64>,
+    slot_count: u64,
+    lessons: Vec<Lesson>,
+    entries: Vec<Option<Entry>>,
+    group_busy: HashMap<(usize, u64, u64), usize>,
+    teacher_busy: HashMap<(String, u64, u64), usize>,
+    room_busy: HashSet<(String, u64, u64)>,
+    teacher_day: HashMap<(String, u64), usize>,
+    teacher_week: HashMap<String, usize>,
+    subject_day: HashMap<(usize, String, u64), usize>,
+    unavailable: HashSet<(String, u64, u64)>,
+    limits: HashMap<String, (Option<usize>, Option<usize>)>,
+    default_day_limit: Option<usize>,
+    rooms: BTreeMap<String, Vec<&'a str>>,
+}
+
+impl<'a> Board<'a> {
+    fn teacher_limits(&self, teacher: &str) -> (Option<usize>, Option<usize>) {
+        let (day, week) = self.limits.get(teacher).copied().unwrap_or((None, None));
+        (day.or(self.default_day_limit), week)
+    }
+
+    fn free_room(&self, category: &str, day: u64, slot: u64) -> Option<String> {
+        self.rooms
+            .get(&category.to_lowercase())?
+            .iter()
+            .find(|r| !self.room_busy.contains(&(r.to_string(), day, slot)))
+            .map(|r| r.to_string())
+    }
+
+    /// Where `lesson` can go at `day`/`slot`, and the room it would use.
+    fn check(&self, lesson: &Lesson, day: u64, slot: u64) -> Result<Option<String>, (Block, Option<usize>)> {
+        if let Some(e) = self.group_busy.get(&(lesson.group, day, slot)) {
+            return Err((Block::ClassBusy, Some(*e)));
+        }
+        if let Some(t) = &lesson.teacher {
+            if self.unavailable.contains(&(t.clone(), day, slot)) || self.unavailable.contains(&(t.clone(), day, 0)) {
+                return Err((Block::TeacherUnavailable, None));
+            }
+            if let Some(e) = self.teacher_busy.get(&(t.clone(), day, slot)) {
+                return Err((Block::TeacherBusy, Some(*e)));
+            }
+            let (day_limit, week_limit) = self.teacher_limits(t);
+            if day_limit.is_some_and(|l| self.teacher_day.get(&(t.clone(), day)).copied().unwrap_or(0) >= l) {
+                return Err((Block::TeacherDayLimit, None));
+            }
+            if week_limit.is_some_and(|l| self.teacher_week.get(t).copied().unwrap_or(0) >= l) {
+                return Err((Block::TeacherWeekLimit, None));
+            }
+        }
+        if self.subject_day.get(&(lesson.group, lesson.subject.clone(), day)).copied().unwrap_or(0) >= lesson.max_per_day {
+            return Err((Block::SubjectDayLimit, None));
+        }
+        match &lesson.room_category {
+            Some(category) if self.rooms.contains_key(&category.to_lowercase()) => {
+                self.free_room(category, day, slot).map(Some).ok_or((Block::NoRoom, None))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn book(&mut self, entry: Entry) -> usize {
+        let idx = self.entries.len();
+        let (g, day, slot) = (entry.group, entry.day, entry.slot);
+        self.group_busy.insert((g, day, slot), idx);
+        if let Some(t) = &entry.teacher {
+            self.teacher_busy.insert((t.clone(), day, slot), idx);
+            *self.teacher_day.entry((t.clone(), day)).or_default() += 1;
+            *self.teacher_week.entry(t.clone()).or_default() += 1;
+        }
+        if let Some(r) = &entry.room {
+            self.room_busy.insert((r.clone(), day, slot));
+        }
+        if let Some(s) = &entry.subject {
+            *self.subject_day.entry((g, s.clone(), day)).or_default() += 1;
+        }
+        self.entries.push(Some(entry));
+        idx
+    }
+
+    fn unbook(&mut self, idx: usize) -> Option<Entry> {
+        let entry = self.entries[idx].take()?;
+        let (g, day, slot) = (entry.group, entry.day, entry.slot);
+        self.group_busy.remove(&(g, day, slot));
+        if let Some(t) = &entry.teacher {
+            self.teacher_busy.remove(&(t.clone(), day, slot));
+            *self.teacher_day.entry((t.clone(), day)).or_default() -= 1;
+            *self.teacher_week.entry(t.clone()).or_default() -= 1;
+        }
+        if let Some(r) = &entry.room {
+            self.room_busy.remove(&(r.clone(), day, slot));
+        }
+        if let Some(s) = &entry.subject {
+            *self.subject_day.entry((g, s.clone(), day)).or_default() -= 1;
+        }
+        Some(entry)
+    }
+
+    fn place(&mut self, lesson_idx: usize, day: u64, slot: u64, room: Option<String>) -> usize {
+        let lesson = &self.lessons[lesson_idx];
+        self.book(Entry {
+            group: lesson.group,
+            day,
+            slot,
+            subject: Some(lesson.subject.clone()),
+            teacher: lesson.teacher.clone(),
+            room,
+            label: None,
+            kind: "generated",
+            lesson: Some(lesson_idx),
+        })
+    }
+
+    fn slots(&self) -> Vec<(u64, u64)> {
+        self.days.iter().flat_map(|d| (1..=self.slot_count).map(move |s| (*d, s))).collect()
+    }
+
+    /// The free slot that spreads the lesson best: fewest periods of the subject that day, then
+    /// the lightest day for the teacher, with `jitter` breaking ties.
+    fn best_slot(&self, lesson_idx: usize, skip: Option<(u64, u64)>, jitter: &mut Rng) -> Option<(u64, u64, Option<String>)> {
+        let lesson = &self.lessons[lesson_idx];
+        self.slots()
+            .into_iter()
+            .filter(|s| Some(*s) != skip)
+            .filter_map(|(day, slot)| {
+                let room = self.check(lesson, day, slot).ok()?;
+                let same_subject = self.subject_day.get(&(lesson.group, lesson.subject.clone(), day)).copied().unwrap_or(0);
+                let teacher_day = lesson.teacher.as_ref().and_then(|t| self.teacher_day.get(&(t.clone(), day))).copied().unwrap_or(0);
+                let score = same_subject * 100 + teacher_day * 10 + jitter.below(10) as usize;
+                Some((score, day, slot, room))
+            })
+            .min_by_key(|(score, day, slot, _)| (*score, *day, *slot))
+            .map(|(_, day, slot, room)| (day, slot, room))
+    }
+
+    /// Frees a slot for the lesson by moving one generated lesson that blocks it elsewhere.
+    fn place_by_moving(&mut self, lesson_idx: usize, jitter: &mut Rng) -> bool {
+        for (day, slot) in self.slots() {
+            let blocker = match self.check(&self.lessons[lesson_idx], day, slot) {
+                Err((Block::ClassBusy | Block::TeacherBusy, Some(e))) => e,
+                _ => continue,
+            };
+            if self.entries[blocker].as_ref().is_none_or(|e| e.kind != "generated") {
+                continue;
+            }
+            let moved = self.unbook(blocker).expect("blocker is booked");
+            let moved_lesson = moved.lesson.expect("generated entries have a lesson");
+            if let Ok(room) = self.check(&self.lessons[lesson_idx], day, slot) {
+                let placed = self.place(lesson_idx, day, slot, room);
+                if let Some((d, s, r)) = self.best_slot(moved_lesson, Some((day, slot)), jitter) {
+                    self.place(moved_lesson, d, s, r);
+                    return true;
+                }
+                self.unbook(placed);
+            }
+            self.book(moved);
+        }
+        false
+    }
+
+    /// The most common reason the lesson fits nowhere.
+    fn why_not(&self, lesson: &Lesson) -> &'static str {
+        let mut counts: BTreeMap<Block, usize> = BTreeMap::new();
+        for (day, slot) in self.slots() {
+            if let Err((block, _)) = self.check(lesson, day, slot) {
+                *counts.entry(block).or_default() += 1;
+            }
+        }
+        // A busy class explains nothing when other slots were blocked for a more specific reason
+        let specific = counts.iter().filter(|(b, _)| **b != Block::ClassBusy).max_by_key(|(_, n)| **n);
+        specific.or(counts.iter().next()).map(|(b, _)| b.describe()).unwrap_or("no slot fits")
+    }
+}
+
+/// Small deterministic generator for tie-breaks (xorshift).
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n.max(1)
+    }
+}
+
+/// Builds a clash-free week: no class, teacher or room is in two places at once, and teacher
+/// availability, load limits and per-day subject limits hold. Fixed slots go in first, then
+/// pins, then the remaining lessons, hardest to place first; a lesson with no free slot may move
+/// one generated lesson out of its way. Whatever still doesn't fit is listed in `unsatisfied`.
+pub fn generate(input: &TimetableInput) -> Value {
+    let config = input.config;
+    let days: Vec<u64> = config["days"].as_array().into_iter().flatten().filter_map(|d| d.as_u64()).collect();
+    let slots = config["slots"].as_array().cloned().unwrap_or_default();
+    let mut unsatisfied: Vec<Value> = Vec::new();
+    let group_label = |g: &Value| match g["section"].as_str() {
+        Some(s) => format!("{} {}", text(g, "className"), s),
+        None => text(g, "className").to_string(),
+    };
+
+    let mut rooms: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for r in input.rooms {
+        rooms.entry(text(r, "category").to_lowercase()).or_default().push(text(r, "spaceId"));
+    }
+    let mut board = Board {
+        days: days.clone(),
+        slot_count: slots.len() as u64,
+        lessons: Vec::new(),
+        entries: Vec::new(),
+        group_busy: HashMap::new(),
+        teacher_busy: HashMap::new(),
+        room_busy: HashSet::new(),
+        teacher_day: HashMap::new(),
+        teacher_week: HashMap::new(),
+        subject_day: HashMap::new(),
+        unavailable: HashSet::new(),
+        limits: HashMap::new(),
+        default_day_limit: config["teacherMaxPerDay"].as_u64().map(|n| n as usize),
+        rooms,
+    };
+    for t in config["teachers"].as_array().into_iter().flatten() {
+        let id = text(t, "employeeId").to_string();
+        for u in t["unavailable"].as_array().into_iter().flatten() {
+            board.unavailable.insert((id.clone(), u["day"].as_u64().unwrap_or(0), u["slot"].as_u64().unwrap_or(0)));
+        }
+        let limit = |k: &str| t[k].as_u64().map(|n| n as usize);
+        board.limits.insert(id, (limit("maxPeriodsPerDay"), limit("maxPeriodsPerWeek")));
+    }
+
+    // Fixed slots
+    for f in config["fixed"].as_array().into_iter().flatten() {
+        for (g, group) in input.groups.iter().enumerate().filter(|(_, g)| applies(f, g)) {
+            let (day, slot) = (f["day"].as_u64().unwrap_or(0), f["slot"].as_u64().unwrap_or(0));
+            let teacher = opt_text(f, "teacherId");
+            let clash = board.group_busy.contains_key(&(g, day, slot))
+                || teacher.as_ref().is_some_and(|t| board.teacher_busy.contains_key(&(t.clone(), day, slot)));
+            if clash {
+                unsatisfied.push(json!({
+                    "type": "fixed",
+                    "classId": group["classId"],
+                    "section": group["section"],
+                    "message": format!("Fixed slot on {} slot {} for {} clashes with another fixed slot", DAY_NAMES[day as usize - 1], slot, group_label(group))
+                }));
+                continue;
+            }
+            board.book(Entry {
+                group: g,
+                day,
+                slot,
+                subject: opt_text(f, "subjectId"),
+                teacher,
+                room: opt_text(f, "roomId"),
+                label: opt_text(f, "label"),
+                kind: "fixed",
+                lesson: None,
+            });
+        }
+    }
+
+    // Lessons each requirement still needs, with a teacher picked for it
+    let mut assigned_load: HashMap<String, u64> = HashMap::new();
+    let mut needed: Vec<(Lesson, u64)> = Vec::new();
+    for r in config["requirements"].as_array().into_iter().flatten() {
+        let matching: Vec<usize> = input.groups.iter().enumerate().filter(|(_, g)| applies(r, g)).map(|(i, _)| i).collect();
+        if matching.is_empty() {
+            unsatisfied.push(json!({
+                "type": "requirement",
+                "classId": r["classId"],
+                "section": r["section"],
+                "subjectId": r["subjectId"],
+                "message": format!("{} {} isn't among the classes being scheduled", text(r, "classId"), text(r, "section"))
+            }));
+            continue;
+        }
+        let subject_id = text(r, "subjectId").to_string();
+        let subject = input.subjects.get(&subject_id);
+        let per_week = r["periodsPerWeek"].as_u64().unwrap_or(0);
+        for g in matching {
+            let teacher = opt_text(r, "teacherId").or_else(|| {
+                // The subject's least-loaded teacher so far
+                subject?["subjectTeachers"]
+                    .as_array()?
+                    .iter()
+                    .filter_map(|t| t.as_str())
+                    .min_by_key(|t| (assigned_load.get(*t).copied().unwrap_or(0), t.to_string()))
+                    .map(|t| t.to_string())
+            });
+            let group = &input.groups[g];
+            if teacher.is_none() {
+                unsatisfied.push(json!({
+                    "type": "teacher",
+                    "classId": group["classId"],
+                    "section": group["section"],
+                    "subjectId": subject_id,
+                    "message": format!("No teacher for {} in {}; its periods are scheduled without one", subject_name(input.subjects, &subject_id), group_label(group))
+                }));
+            }
+            if let Some(category) = opt_text(r, "roomCategory").filter(|c| !board.rooms.contains_key(&c.to_lowercase())) {
+                unsatisfied.push(json!({
+                    "type": "room",
+                    "classId": group["classId"],
+                    "section": group["section"],
+                    "subjectId": subject_id,
+                    "message": format!("No space of category {} for {} in {}; scheduled without a room", category, subject_name(input.subjects, &subject_id), group_label(group))
+                }));
+            }
+            if let Some(t) = &teacher {
+                *assigned_load.entry(t.clone()).or_default() += per_week;
+            }
+            let max_per_day = r["maxPerDay"].as_u64().unwrap_or_else(|| per_week.div_ceil(days.len() as u64).max(1));
+            needed.push((
+                Lesson {
+                    group: g,
+                    subject: subject_id.clone(),
+                    teacher,
+                    room_category: opt_text(r, "roomCategory"),
+                    max_per_day: max_per_day as usize,
+                },
+                per_week,
+            ));
+        }
+    }
+
+    // Pins, then fixed slots with a subject, count towards the requirement they match
+    for p in input.pins {
+        let day = p["day"].as_u64().unwrap_or(0);
+        let slot = p["slot"].as_u64().unwrap_or(0);
+        let subject_id = text(p, "subjectId");
+        let Some(g) = input.groups.iter().position(|g| g["classId"] == p["classId"] && g["section"] == p["section"]) else {
+            unsatisfied.push(json!({"type": "pin", "pin": p, "message": "Pinned class isn't among the classes being scheduled"}));
+            continue;
+        };
+        if !days.contains(&day) || !(1..=board.slot_count).contains(&slot) {
+            unsatisfied.push(json!({"type": "pin", "pin": p, "message": "Pinned slot isn't in the week"}));
+            continue;
+        }
+        let requirement = needed.iter().position(|(l, n)| l.group == g && l.subject == subject_id && *n > 0);
+        let mut lesson = match requirement {
+            Some(r) => needed[r].0.clone(),
+            None => Lesson {
+                group: g,
+                subject: subject_id.to_string(),
+                teacher: None,
+                room_category: None,
+                max_per_day: usize::MAX,
+            },
+        };
+        if let Some(t) = opt_text(p, "teacherId") {
+            lesson.teacher = Some(t);
+        }
+        let room = opt_text(p, "roomId");
+        let clash = board.group_busy.contains_key(&(g, day, slot))
+            || lesson.teacher.as_ref().is_some_and(|t| board.teacher_busy.contains_key(&(t.clone(), day, slot)))
+            || room.as_ref().is_some_and(|r| board.room_busy.contains(&(r.clone(), day, slot)));
+        if clash {
+            unsatisfied.push(json!({
+                "type": "pin",
+                "pin": p,
+                "message": format!("Pinned {} for {} on {} slot {} clashes with another booking", subject_name(input.subjects, subject_id), group_label(&input.groups[g]), DAY_NAMES[day as usize - 1], slot)
+            }));
+            continue;
+        }
+        if let Some(r) = requirement {
+            needed[r].1 -= 1;
+        }
+        let room = room.or_else(|| lesson.room_category.as_ref().and_then(|c| board.free_room(c, day, slot)));
+        board.book(Entry {
+            group: g,
+            day,
+            slot,
+            subject: Some(lesson.subject.clone()),
+            teacher: lesson.teacher.clone(),
+            room,
+            label: None,
+            kind: "pinned",
+            lesson: None,
+        });
+    }
+    let fixed_subjects: Vec<(usize, String)> = board
+        .entries
+        .iter()
+        .flatten()
+        .filter(|e| e.kind == "fixed")
+        .filter_map(|e| Some((e.group, e.subject.clone()?)))
+        .collect();
+    for (g, subject) in fixed_subjects {
+        if let Some((_, n)) = needed.iter_mut().find(|(l, n)| l.group == g && l.subject == subject && *n > 0) {
+            *n -= 1;
+        }
+    }
+
+    // Capacity checks worth reporting even if some lessons still fit
+    let week = (days.len() * slots.len()) as u64;
+    for (g, group) in input.groups.iter().enumerate() {
+        let demand: u64 = needed.iter().filter(|(l, _)| l.group == g).map(|(_, n)| n).sum();
+        let free = week - board.group_busy.keys().filter(|(bg, _, _)| *bg == g).count() as u64;
+        if demand > free {
+            unsatisfied.push(json!({
+                "type": "capacity",
+                "classId": group["classId"],
+                "section": group["section"],
+                "message": format!("{} needs {} periods but has {} free slots", group_label(group), demand, free)
+            }));
+        }
+    }
+    let mut demand_by_teacher: BTreeMap<String, u64> = BTreeMap::new();
+    for (l, n) in &needed {
+        if let Some(t) = &l.teacher {
+            *demand_by_teacher.entry(t.clone()).or_default() += n;
+        }
+    }
+    for (t, demand) in &demand_by_teacher {
+        let total = demand + board.teacher_week.get(t).copied().unwrap_or(0) as u64;
+        if let Some(limit) = board.teacher_limits(t).1.filter(|l| total > *l as u64) {
+            unsatisfied.push(json!({
+                "type": "teacherLoad",
+                "teacherId": t,
+                "message": format!("{} is given {} periods a week but can take at most {}", t, total, limit)
+            }));
+        }
+    }
+
+    // Hardest lessons first: fewest usable slots, then the busiest teachers
+    let mut rng = Rng(input.seed.max(1).wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1);
+    for (lesson, n) in needed {
+        for _ in 0..n {
+            board.lessons.push(lesson.clone());
+        }
+    }
+    let mut order: Vec<(usize, usize, u64, u64)> = (0..board.lessons.len())
+        .map(|i| {
+            let l = &board.lessons[i];
+            let usable = board.slots().into_iter().filter(|(d, s)| board.check(l, *d, *s).is_ok()).count();
+            let busy = l.teacher.as_ref().and_then(|t| demand_by_teacher.get(t)).copied().unwrap_or(0);
+            (usable, i, busy, rng.below(1000))
+        })
+        .collect();
+    order.sort_by_key(|(usable, _, busy, jitter)| (*usable, std::cmp::Reverse(*busy), *jitter));
+
+    let mut unplaced: BTreeMap<(usize, String), (usize, &'static str)> = BTreeMap::new();
+    for (_, i, _, _) in order {
+        if let Some((day, slot, room)) = board.best_slot(i, None, &mut rng) {
+            board.place(i, day, slot, room);
+        } else if !board.place_by_moving(i, &mut rng) {
+            let lesson = board.lessons[i].clone();
+            let reason = board.why_not(&lesson);
+            let e = unplaced.entry((lesson.group, lesson.subject.clone())).or_insert((0, reason));
+            e.0 += 1;
+        }
+    }
+    for ((g, subject), (count, reason)) in &unplaced {
+        let group = &input.groups[*g];
+        unsatisfied.push(json!({
+            "type": "unplaced",
+            "classId": group["classId"],
+            "section": group["section"],
+            "subjectId": subject,
+            "periods": count,
+            "message": format!("{} period(s) of {} for {} couldn't be placed: {}", count, subject_name(input.subjects, subject), group_label(group), reason)
+        }));
+    }
+
+    let mut entries: Vec<Value> = board
+        .entries
+        .iter()
+        .flatten()
+        .map(|e| {
+            let group = &input.groups[e.group];
+            let slot = &slots[e.slot as usize - 1];
+            json!({
+                "classId": group["classId"],
+                "className": group["className"],
+                "section": group["section"],
+                "day": e.day,
+                "slot": e.slot,
+                "name": slot["name"],
+                "startTime": slot["startTime"],
+                "endTime": slot["endTime"],
+                "subjectId": e.subject,
+                "subjectName": e.subject.as_ref().map(|s| subject_name(input.subjects, s)),
+                "teacherId": e.teacher,
+                "roomId": e.room,
+                "label": e.label,
+                "kind": e.kind
+            })
+        })
+        .collect();
+    entries.sort_by_key(|e| (text(e, "classId").to_string(), text(e, "section").to_string(), e["day"].as_u64(), e["slot"].as_u64()));
+    let required = board.lessons.len();
+    let unplaced_count: usize = unplaced.values().map(|(n, _)| n).sum();
+    json!({
+        "entries": entries,
+        "unsatisfied": unsatisfied,
+        "stats": {
+            "lessons": required,
+            "placed": required - unplaced_count,
+            "unplaced": unplaced_count,
+            "teacherLoad": board.teacher_week.iter().filter(|(_, n)| **n > 0).collect::<BTreeMap<_, _>>()
+        }
+    })
+}
+
+fn subject_name(subjects: &HashMap<String, Value>, subject_id: &str) -> String {
+    subjects.get(subject_id).and_then(|s| s["name"].as_str()).unwrap_or(subject_id).to_string()
+}
+
+/// Clashes between timetable entries (`{classId, section, day, slot, teacherId, roomId}`): the
+/// same class, teacher or room booked twice in one slot.
+pub fn clashes(entries: &[Value]) -> Vec<String> {
+    let mut seen: HashMap<(String, String, u64, u64), &Value> = HashMap::new();
+    let mut out = Vec::new();
+    for e in entries {
+        let (day, slot) = (e["day"].as_u64().unwrap_or(0), e["slot"].as_u64().unwrap_or(0));
+        let class = format!("{}|{}", text(e, "classId"), text(e, "section"));
+        let keys = [("class", Some(class)), ("teacher", opt_text(e, "teacherId")), ("room", opt_text(e, "roomId"))];
+        for (kind, key) in keys {
+            let Some(key) = key else { continue };
+            if seen.insert((kind.to_string(), key.clone(), day, slot), e).is_some() {
+                out.push(format!("{} {} is booked twice on {} slot {}", kind, key.replace('|', " ").trim(), DAY_NAMES[day as usize - 1], slot));
+            }
+        }
+    }
+    out
+}
+
+/// Periods grouped into a week grid: one row per day with its periods in time order. Periods
+/// without a day (`dayOfWeek` null) run every day.
+pub fn week_grid(periods: &[Value], days: &[u64]) -> Vec<Value> {
+    days.iter()
+        .map(|d| {
+            let mut today: Vec<&Value> = periods
+                .iter()
+                .filter(|p| p["dayOfWeek"].as_u64().is_none_or(|pd| pd == *d))
+                .collect();
+            today.sort_by(|a, b| text(a, "startTime").cmp(text(b, "startTime")));
+            json!({"day": d, "dayName": DAY_NAMES[*d as usize - 1], "periods": today})
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(extra: Value) -> Value {
+        let mut c = json!({
+            "days": [1, 2, 3],
+            "slots": [
+                {"startTime": "08:00", "endTime": "08:45"},
+                {"startTime": "08:45", "endTime": "09:30"},
+                {"startTime": "09:45", "endTime": "10:30"}
+            ],
+            "requirements": [
+                {"classId": "C1", "subjectId": "MATH", "periodsPerWeek": 3, "teacherId": "T1"},
+                {"classId": "C1", "subjectId": "ENG", "periodsPerWeek": 3, "teacherId": "T2"},
+                {"classId": "C2", "subjectId": "MATH", "periodsPerWeek": 3, "teacherId": "T1"},
+                {"classId": "C2", "subjectId": "ENG", "periodsPerWeek": 3, "teacherId": "T2"}
+            ],
+            "teachers": [{"employeeId": "T1", "unavailable": [{"day": 1, "slot": 1}]}]
+        });
+        for (k, v) in extra.as_object().into_iter().flatten() {
+            c[k] = v.clone();
+        }
+        validate_config(&c).expect("valid config")
+    }
+
+    fn run(config: &Value, pins: &[Value]) -> Value {
+        let groups = [
+            json!({"classId": "C1", "className": "Class 1", "section": null}),
+            json!({"classId": "C2", "className": "Class 2", "section": null}),
+        ];
+        let subjects = HashMap::new();
+        generate(&TimetableInput { config, groups: &groups, subjects: &subjects, rooms: &[], pins, seed: 7 })
+    }
+
+    fn entries(result: &Value) -> Vec<Value> {
+        result["entries"].as_array().cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn generates_a_clash_free_week() {
+        let result = run(&config(json!({})), &[]);
+        assert_eq!(result["stats"]["unplaced"], 0);
+        let entries = entries(&result);
+        assert_eq!(entries.len(), 12);
+        assert!(clashes(&entries).is_empty());
+        assert!(!entries.iter().any(|e| e["teacherId"] == "T1" && e["day"] == 1 && e["slot"] == 1));
+    }
+
+    #[test]
+    fn keeps_pins_and_counts_them_towards_the_requirement() {
+        let pins = [json!({"classId": "C1", "section": null, "day": 2, "slot": 1, "subjectId": "ENG", "teacherId": "T2"})];
+        let entries = entries(&run(&config(json!({})), &pins));
+        let pinned: Vec<&Value> = entries.iter().filter(|e| e["kind"] == "pinned").collect();
+        assert_eq!(pinned.len(), 1);
+        assert_eq!((pinned[0]["day"].as_u64(), pinned[0]["slot"].as_u64()), (Some(2), Some(1)));
+        let english = entries.iter().filter(|e| e["classId"] == "C1" && e["subjectId"] == "ENG").count();
+        assert_eq!(english, 3);
+        assert!(clashes(&entries).is_empty());
+    }
+
+    #[test]
+    fn reports_a_pin_that_clashes_with_a_fixed_slot() {
+        let config = config(json!({"fixed": [{"classId": "C1", "day": 1, "slot": 1, "label": "Assembly"}]}));
+        let pins = [json!({"classId": "C1", "section": null, "day": 1, "slot": 1, "subjectId": "MATH"})];
+        let result = run(&config, &pins);
+        assert!(result["unsatisfied"].as_array().unwrap().iter().any(|u| u["type"] == "pin"));
+        assert!(clashes(&entries(&result)).is_empty());
+    }
+
+    #[test]
+    fn clashes_finds_double_bookings() {
+        let entries = [
+            json!({"classId": "C1", "day": 1, "slot": 1, "teacherId": "T1"}),
+            json!({"classId": "C2", "day": 1, "slot": 1, "teacherId": "T1"}),
+        ];
+        assert_eq!(clashes(&entries), vec!["teacher T1 is booked twice on Monday slot 1".to_string()]);
+    }
+}
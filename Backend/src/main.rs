@@ -82,6 +82,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting Nightly Cashier background task...");
     crate::super_admin::billing_job::start_daily_billing_job(state.clone()).await;
 
+    println!("Starting fee reminder background task...");
+    crate::services::reminder_service::start_fee_reminder_job(state.clone()).await;
+    crate::services::payroll_service::start_payroll_draft_job(state.clone()).await;
+    crate::services::leave_service::start_leave_carry_forward_job(state.clone()).await;
+    crate::services::leave_service::start_leave_escalation_job(state.clone()).await;
+    crate::services::employee_document_service::start_document_expiry_job(state.clone()).await;
+    crate::services::absence_alert_service::start_absence_alert_job(state.clone()).await;
+
+    // The mock checkout settles orders without a real payment, so it exists only in mock mode
+    let mut payment_routes = Router::new().route("/:gateway/callback", post(routes::payments::payment_callback));
+    if crate::services::payment_service::mock_gateway_enabled() {
+        payment_routes = payment_routes.route("/mock/checkout/:orderId", post(routes::payments::mock_checkout));
+    }
+
+    // The device simulator writes made-up punches into attendance, so it is mounted only when switched on
+    let mut simulator_routes = Router::new();
+    if crate::services::device_service::simulator_enabled() {
+        simulator_routes = simulator_routes.route("/:schoolId/:deviceId/simulate", post(routes::devices::simulate_device));
+    }
+
     // CORS Layer
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -228,6 +248,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     "/:schoolId/employees/:employeeId",
                     delete(routes::employees::delete_employee),
                 )
+                // Document vault and verification
+                .route(
+                    "/:schoolId/employees/:employeeId/documents",
+                    get(routes::employee_documents::list_documents)
+                        .post(routes::employee_documents::upload_document),
+                )
+                .route(
+                    "/:schoolId/employees/:employeeId/documents/:documentId",
+                    delete(routes::employee_documents::delete_document),
+                )
+                .route(
+                    "/:schoolId/employees/:employeeId/documents/:documentId/file",
+                    get(routes::employee_documents::download_document),
+                )
+                .route(
+                    "/:schoolId/employees/:employeeId/documents/:documentId/verification",
+                    post(routes::employee_documents::verify_document),
+                )
+                .route("/:schoolId/documents/types", get(routes::employee_documents::list_document_types))
+                .route("/:schoolId/documents/compliance", get(routes::employee_documents::compliance_report))
                 .route(
                     "/:schoolId/:employeeId/salary-breakdown",
                     get(routes::emppay::get_salary_breakdown),
@@ -351,6 +391,116 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .route(
                     "/:schoolId/holidays/:holidayId",
                     axum::routing::delete(routes::attendance::delete_school_holiday),
+                )
+                // Weekly-off rules, .ics import/export, gazetted lists and working-day counts
+                .route(
+                    "/:schoolId/holidays/rules",
+                    axum::routing::get(routes::attendance::get_holiday_rules)
+                        .put(routes::attendance::update_holiday_rules),
+                )
+                .route(
+                    "/:schoolId/holidays/calendar",
+                    axum::routing::get(routes::attendance::get_holiday_calendar),
+                )
+                .route(
+                    "/:schoolId/holidays/working-days",
+                    axum::routing::get(routes::attendance::get_working_days),
+                )
+                .route(
+                    "/:schoolId/holidays/ics",
+                    axum::routing::get(routes::attendance::export_holidays_ics)
+                        .post(routes::attendance::import_holidays_ics),
+                )
+                .route(
+                    "/:schoolId/holidays/gazetted",
+                    axum::routing::get(routes::attendance::preview_gazetted_holidays)
+                        .post(routes::attendance::import_gazetted_holidays),
+                )
+                .route(
+                    "/:schoolId/settings",
+                    axum::routing::get(routes::attendance::get_attendance_settings)
+                        .put(routes::attendance::update_attendance_settings),
+                )
+                // Monthly register grid and absentee analytics (json, csv or pdf)
+                .route(
+                    "/:schoolId/register",
+                    axum::routing::get(routes::attendance::get_attendance_register),
+                )
+                .route(
+                    "/:schoolId/analytics",
+                    axum::routing::get(routes::attendance::get_attendance_analytics),
+                )
+                // Same-day absence alerts to guardians
+                .route(
+                    "/:schoolId/absence-alerts",
+                    axum::routing::get(routes::attendance::list_absence_alerts),
+                )
+                .route(
+                    "/:schoolId/absence-alerts/run",
+                    axum::routing::post(routes::attendance::run_absence_alerts),
+                )
+                .route(
+                    "/:schoolId/absence-alerts/reason",
+                    axum::routing::post(routes::attendance::record_absence_reason),
+                )
+                // Attendance correction requests, their evidence and the change history
+                .route(
+                    "/:schoolId/corrections",
+                    axum::routing::get(routes::attendance::list_corrections)
+                        .post(routes::attendance::create_correction),
+                )
+                .route(
+                    "/:schoolId/corrections/:requestId",
+                    axum::routing::get(routes::attendance::get_correction),
+                )
+                .route(
+                    "/:schoolId/corrections/:requestId/decision",
+                    axum::routing::post(routes::attendance::decide_correction),
+                )
+                .route(
+                    "/:schoolId/corrections/:requestId/cancel",
+                    axum::routing::post(routes::attendance::cancel_correction),
+                )
+                .route(
+                    "/:schoolId/corrections/:requestId/evidence",
+                    axum::routing::post(routes::attendance::upload_correction_evidence),
+                )
+                .route(
+                    "/:schoolId/corrections/:requestId/evidence/:index",
+                    axum::routing::get(routes::attendance::download_correction_evidence),
+                )
+                .route(
+                    "/:schoolId/history",
+                    axum::routing::get(routes::attendance::get_attendance_history),
+                )
+                // Teacher self check-in: rotating QR for the display and the attempt log
+                .route(
+                    "/:schoolId/check-in/qr",
+                    axum::routing::get(routes::attendance::get_check_in_qr),
+                )
+                .route(
+                    "/:schoolId/check-in/log",
+                    axum::routing::get(routes::attendance::get_check_in_log),
+                )
+                // Period-wise subject attendance, its reports and exam eligibility
+                .route(
+                    "/:schoolId/periods/:periodId/:date",
+                    axum::routing::get(routes::attendance::get_period_attendance)
+                        .put(routes::attendance::mark_period_attendance),
+                )
+                .route(
+                    "/:schoolId/subjects/report",
+                    axum::routing::get(routes::attendance::get_subject_attendance_report),
+                )
+                .route(
+                    "/:schoolId/subjects/eligibility",
+                    axum::routing::get(routes::attendance::get_subject_eligibility),
+                )
+                // Whole-class sheet for one date
+                .route(
+                    "/:schoolId/classes/:classId/:date",
+                    axum::routing::get(routes::attendance::get_class_attendance)
+                        .put(routes::attendance::mark_class_attendance),
                 ),
         )
         // Legacy alias kept for backward compat
@@ -385,6 +535,54 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     "/:schoolId/student/:studentId/discount",
                     post(routes::fees::apply_discount),
                 )
+                // Fee Reports (?from=&to=&format=json|csv|pdf)
+                .route(
+                    "/:schoolId/reports/daybook",
+                    get(routes::reports::fee_day_book),
+                )
+                .route(
+                    "/:schoolId/reports/collection",
+                    get(routes::reports::fee_collection_vs_demand),
+                )
+                .route(
+                    "/:schoolId/reports/defaulters",
+                    get(routes::reports::fee_defaulters),
+                )
+                .route(
+                    "/:schoolId/reports/concessions",
+                    get(routes::reports::fee_concessions),
+                )
+                .route(
+                    "/:schoolId/reports/referrals",
+                    get(routes::reports::referral_report),
+                )
+                // Bank statement import and reconciliation
+                .route(
+                    "/:schoolId/bank-statements",
+                    get(routes::reconciliation::list_bank_statements)
+                        .post(routes::reconciliation::import_bank_statement),
+                )
+                .route(
+                    "/:schoolId/bank-statements/:importId/lines",
+                    get(routes::reconciliation::list_statement_lines),
+                )
+                .route(
+                    "/:schoolId/reconciliation/queue",
+                    get(routes::reconciliation::review_queue),
+                )
+                .route(
+                    "/:schoolId/reconciliation/lines/:lineId/resolve",
+                    post(routes::reconciliation::resolve_statement_line),
+                )
+                // Online payments that could not be posted
+                .route(
+                    "/:schoolId/payment-orders/failed",
+                    get(routes::payments::failed_orders),
+                )
+                .route(
+                    "/:schoolId/payment-orders/:orderId/resolve",
+                    post(routes::payments::resolve_order),
+                )
                 // Custom Fees
                 .route(
                     "/:schoolId/custom",
@@ -409,7 +607,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 )
                 .route(
                     "/:schoolId/coupons/:couponId",
-                    delete(routes::fees::delete_coupon),
+                    put(routes::fees::update_coupon).delete(routes::fees::delete_coupon),
                 )
                 .route(
                     "/:schoolId/coupons/:couponId/block",
@@ -427,10 +625,106 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         )
         .nest(
             "/api/payroll",
-            Router::new().route(
-                "/:schoolId/employees/:employeeId",
-                post(routes::emppay::set_base_salary),
-            ),
+            Router::new()
+                .route(
+                    "/:schoolId/employees/:employeeId",
+                    post(routes::emppay::set_base_salary),
+                )
+                // Monthly payroll runs: draft -> approved (locked) -> reopened
+                .route(
+                    "/:schoolId/runs",
+                    get(routes::emppay::list_payroll_runs).post(routes::emppay::create_payroll_run),
+                )
+                .route(
+                    "/:schoolId/runs/:runId",
+                    get(routes::emppay::get_payroll_run),
+                )
+                .route(
+                    "/:schoolId/runs/:runId/recompute",
+                    post(routes::emppay::recompute_payroll_run),
+                )
+                .route(
+                    "/:schoolId/runs/:runId/approve",
+                    post(routes::emppay::approve_payroll_run),
+                )
+                .route(
+                    "/:schoolId/runs/:runId/reopen",
+                    post(routes::emppay::reopen_payroll_run),
+                )
+                .route(
+                    "/:schoolId/runs/:runId/register",
+                    get(routes::emppay::payroll_register),
+                )
+                .route(
+                    "/:schoolId/runs/:runId/payslips/:employeeId",
+                    get(routes::emppay::get_employee_payslip),
+                )
+                .route(
+                    "/:schoolId/employees/:employeeId/payslips",
+                    get(routes::emppay::list_employee_payslips),
+                )
+                // Statutory deductions: PF, ESI, professional tax, TDS
+                .route(
+                    "/:schoolId/statutory-settings",
+                    get(routes::emppay::get_statutory_settings).put(routes::emppay::update_statutory_settings),
+                )
+                .route(
+                    "/:schoolId/runs/:runId/challans",
+                    get(routes::emppay::statutory_challans),
+                )
+                .route(
+                    "/:schoolId/employees/:employeeId/tax-statement",
+                    get(routes::emppay::annual_tax_statement),
+                )
+                // Salary advances and loans recovered through payroll in EMIs
+                .route("/:schoolId/advances", get(routes::emppay::list_advances))
+                .route(
+                    "/:schoolId/employees/:employeeId/advances",
+                    get(routes::emppay::employee_advance_summary).post(routes::emppay::create_advance),
+                )
+                .route("/:schoolId/advances/:advanceId", get(routes::emppay::get_advance))
+                .route(
+                    "/:schoolId/advances/:advanceId/prepay",
+                    post(routes::emppay::prepay_advance),
+                )
+                .route(
+                    "/:schoolId/advances/:advanceId/pause",
+                    post(routes::emppay::pause_advance),
+                )
+                .route(
+                    "/:schoolId/advances/:advanceId/resume",
+                    post(routes::emppay::resume_advance),
+                )
+                .route(
+                    "/:schoolId/advances/:advanceId/waive",
+                    post(routes::emppay::waive_advance),
+                )
+                // Dated salary revisions; backdated ones pay arrears in the next run
+                .route(
+                    "/:schoolId/employees/:employeeId/salary-revisions",
+                    get(routes::emppay::list_salary_revisions).post(routes::emppay::create_salary_revision),
+                )
+                // Bulk salary transfer files and bank response imports
+                .route(
+                    "/:schoolId/employees/:employeeId/bank-account",
+                    put(routes::emppay::set_bank_account),
+                )
+                .route(
+                    "/:schoolId/runs/:runId/bank-transfers",
+                    get(routes::emppay::list_transfer_batches).post(routes::emppay::create_transfer_batch),
+                )
+                .route(
+                    "/:schoolId/bank-transfers/:batchId",
+                    get(routes::emppay::get_transfer_batch),
+                )
+                .route(
+                    "/:schoolId/bank-transfers/:batchId/file",
+                    get(routes::emppay::download_transfer_file),
+                )
+                .route(
+                    "/:schoolId/bank-transfers/:batchId/response",
+                    post(routes::emppay::import_transfer_response),
+                ),
         )
         // Communication & Resource Routes (Flattened)
         .route(
@@ -460,9 +754,41 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "/api/documentbox/:schoolId",
             get(routes::documentbox::list_documents),
         )
-        .route(
-            "/api/reminder/:schoolId",
-            get(routes::reminder::list_reminders),
+        .nest(
+            "/api/reminder",
+            Router::new()
+                .route("/:schoolId", get(routes::reminder::list_reminders))
+                .route(
+                    "/:schoolId/rules",
+                    get(routes::reminder::list_reminder_rules)
+                        .post(routes::reminder::create_reminder_rule),
+                )
+                .route(
+                    "/:schoolId/rules/:ruleId",
+                    delete(routes::reminder::delete_reminder_rule),
+                )
+                .route(
+                    "/:schoolId/fees/run",
+                    post(routes::reminder::run_fee_reminders),
+                ),
+        )
+        .nest("/api/payments", payment_routes)
+        .nest(
+            "/api/notifications",
+            Router::new()
+                .route(
+                    "/:schoolId/templates",
+                    get(routes::notifications::list_templates)
+                        .post(routes::notifications::save_template),
+                )
+                .route(
+                    "/:schoolId/templates/:templateKey",
+                    delete(routes::notifications::delete_template),
+                )
+                .route("/:schoolId/log", get(routes::notifications::list_log))
+                // Called by the messaging relay: delivery receipts and guardian replies
+                .route("/delivery", post(routes::notifications::delivery_receipt))
+                .route("/inbound", post(routes::notifications::inbound_message)),
         )
         .nest(
             "/api/responsibility",
@@ -489,7 +815,125 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .route("/:schoolId/:leaveId/approve", post(routes::leave::approve_leave))
                 .route("/:schoolId/:leaveId/reject", post(routes::leave::reject_leave))
                 .route("/:schoolId/:leaveId/extend", post(routes::leave::extend_leave))
-                .route("/:schoolId/:leaveId/reduce", post(routes::leave::reduce_leave)),
+                .route("/:schoolId/:leaveId/reduce", post(routes::leave::reduce_leave))
+                // Leave types, balances and year-end carry-forward
+                .route(
+                    "/:schoolId/types",
+                    get(routes::leave::list_leave_types).put(routes::leave::save_leave_type),
+                )
+                .route("/:schoolId/balances", get(routes::leave::list_leave_balances))
+                .route(
+                    "/:schoolId/employees/:employeeId/balance-adjustments",
+                    post(routes::leave::adjust_leave_balance),
+                )
+                .route("/:schoolId/carry-forward", post(routes::leave::carry_forward_leave))
+                // Approval chains, delegation and escalation
+                .route(
+                    "/:schoolId/approval-chains",
+                    get(routes::leave::list_approval_chains).put(routes::leave::save_approval_chain),
+                )
+                .route(
+                    "/:schoolId/approval-chains/:employeeType",
+                    delete(routes::leave::delete_approval_chain),
+                )
+                .route("/:schoolId/approvals/:approverId", get(routes::leave::pending_approvals))
+                .route(
+                    "/:schoolId/delegations",
+                    get(routes::leave::list_delegations).post(routes::leave::create_delegation),
+                )
+                .route(
+                    "/:schoolId/delegations/:delegationId",
+                    delete(routes::leave::revoke_delegation),
+                )
+                .route("/:schoolId/escalate", post(routes::leave::escalate_overdue_leaves))
+                .route("/:schoolId/:leaveId/comments", post(routes::leave::add_leave_comment))
+                .route("/:schoolId/:leaveId", get(routes::leave::get_leave)),
+        )
+        .nest(
+            "/api/devices",
+            Router::new()
+                .route("/:schoolId", get(routes::devices::list_devices).post(routes::devices::register_device))
+                .route(
+                    "/:schoolId/enrollments",
+                    get(routes::devices::list_enrollments).put(routes::devices::save_enrollments),
+                )
+                .route("/:schoolId/enrollments/:enrollId", delete(routes::devices::delete_enrollment))
+                .route(
+                    "/:schoolId/punches",
+                    get(routes::devices::list_punches).post(routes::devices::push_punches),
+                )
+                .route("/:schoolId/reprocess", post(routes::devices::reprocess_punches))
+                .route(
+                    "/:schoolId/:deviceId",
+                    put(routes::devices::update_device).delete(routes::devices::delete_device),
+                )
+                .merge(simulator_routes),
+        )
+        // ZKTeco ADMS terminals push to these fixed paths
+        .route(
+            "/iclock/cdata",
+            get(routes::devices::adms_handshake).post(routes::devices::adms_push),
+        )
+        .route("/iclock/getrequest", get(routes::devices::adms_poll))
+        .route("/iclock/devicecmd", post(routes::devices::adms_poll))
+        .nest(
+            "/api/sessions",
+            Router::new()
+                .route(
+                    "/:schoolId",
+                    get(routes::sessions::list_sessions).post(routes::sessions::create_session),
+                )
+                .route(
+                    "/:schoolId/:sessionId",
+                    get(routes::sessions::get_session).put(routes::sessions::update_session),
+                )
+                // Year-end wizard: review, decide class by class, then complete
+                .route("/:schoolId/:sessionId/rollover", get(routes::sessions::rollover_overview))
+                .route(
+                    "/:schoolId/:sessionId/rollover/classes/:classId",
+                    put(routes::sessions::save_class_promotions),
+                )
+                .route(
+                    "/:schoolId/:sessionId/rollover/complete",
+                    post(routes::sessions::complete_rollover),
+                ),
+        )
+        .nest(
+            "/api/timetable",
+            Router::new()
+                .route("/:schoolId/classes/:classId/periods", post(routes::timetable::add_period))
+                .route("/:schoolId/periods", get(routes::timetable::list_periods))
+                // Cover for absent teachers
+                .route(
+                    "/:schoolId/substitutions/proposals",
+                    get(routes::timetable::substitution_proposals),
+                )
+                .route("/:schoolId/substitutions/load", get(routes::timetable::substitution_load))
+                .route(
+                    "/:schoolId/substitutions",
+                    get(routes::timetable::list_substitutions).post(routes::timetable::assign_substitutions),
+                )
+                .route(
+                    "/:schoolId/substitutions/:substitutionId",
+                    delete(routes::timetable::cancel_substitution),
+                )
+                // Generator: settings -> draft -> pins/regenerate -> publish
+                .route(
+                    "/:schoolId/generator/settings",
+                    get(routes::timetable::get_generator_settings).put(routes::timetable::save_generator_settings),
+                )
+                .route(
+                    "/:schoolId/generator/drafts",
+                    get(routes::timetable::list_timetable_drafts).post(routes::timetable::generate_timetable),
+                )
+                .route("/:schoolId/generator/drafts/:draftId", get(routes::timetable::get_timetable_draft))
+                .route("/:schoolId/generator/drafts/:draftId/pins", put(routes::timetable::pin_lessons))
+                .route(
+                    "/:schoolId/generator/drafts/:draftId/publish",
+                    post(routes::timetable::publish_timetable),
+                )
+                .route("/:schoolId/views/classes/:classId", get(routes::timetable::class_timetable))
+                .route("/:schoolId/views/teachers/:teacherId", get(routes::timetable::teacher_timetable)),
         )
 
         .route(
@@ -22,6 +22,16 @@ pub struct Repositories {
     pub responsibility: Arc<dyn ResponsibilityRepository + Send + Sync>,
     pub task: Arc<dyn TaskRepository + Send + Sync>,
     pub leave: Arc<dyn LeaveRepository + Send + Sync>,
+    pub report: Arc<dyn ReportRepository + Send + Sync>,
+    pub notification: Arc<dyn NotificationRepository + Send + Sync>,
+    pub payment: Arc<dyn PaymentRepository + Send + Sync>,
+    pub reconciliation: Arc<dyn ReconciliationRepository + Send + Sync>,
+    pub payroll: Arc<dyn PayrollRepository + Send + Sync>,
+    pub timetable: Arc<dyn TimetableRepository + Send + Sync>,
+    pub employee_documents: Arc<dyn EmployeeDocumentRepository + Send + Sync>,
+    pub attendance: Arc<dyn AttendanceRepository + Send + Sync>,
+    pub devices: Arc<dyn DeviceRepository + Send + Sync>,
+    pub sessions: Arc<dyn SessionRepository + Send + Sync>,
     pub db_client: Arc<crate::db::DbClient>,
 }
 
@@ -85,6 +95,36 @@ pub async fn initialize_repositories(
     let leave_repo = Arc::new(crate::repository::postgres::PostgresLeaveRepository {
         client: db_client.clone(),
     });
+    let report_repo = Arc::new(crate::repository::postgres::PostgresReportRepository {
+        client: db_client.clone(),
+    });
+    let notification_repo = Arc::new(crate::repository::postgres::PostgresNotificationRepository {
+        client: db_client.clone(),
+    });
+    let payment_repo = Arc::new(crate::repository::postgres::PostgresPaymentRepository {
+        client: db_client.clone(),
+    });
+    let reconciliation_repo = Arc::new(crate::repository::postgres::PostgresReconciliationRepository {
+        client: db_client.clone(),
+    });
+    let payroll_repo = Arc::new(crate::repository::postgres::PostgresPayrollRepository {
+        client: db_client.clone(),
+    });
+    let timetable_repo = Arc::new(crate::repository::postgres::PostgresTimetableRepository {
+        client: db_client.clone(),
+    });
+    let employee_document_repo = Arc::new(crate::repository::postgres::PostgresEmployeeDocumentRepository {
+        client: db_client.clone(),
+    });
+    let attendance_repo = Arc::new(crate::repository::postgres::PostgresAttendanceRepository {
+        client: db_client.clone(),
+    });
+    let device_repo = Arc::new(crate::repository::postgres::PostgresDeviceRepository {
+        client: db_client.clone(),
+    });
+    let session_repo = Arc::new(crate::repository::postgres::PostgresSessionRepository {
+        client: db_client.clone(),
+    });
 
     Repositories {
         auth: auth_repo,
@@ -102,6 +142,16 @@ pub async fn initialize_repositories(
         responsibility: responsibility_repo,
         task: task_repo,
         leave: leave_repo,
+        report: report_repo,
+        notification: notification_repo,
+        payment: payment_repo,
+        reconciliation: reconciliation_repo,
+        payroll: payroll_repo,
+        timetable: timetable_repo,
+        employee_documents: employee_document_repo,
+        attendance: attendance_repo,
+        devices: device_repo,
+        sessions: session_repo,
         db_client,
     }
 }
@@ -228,6 +228,32 @@ impl StudentRepository for PostgresStudentRepository {
         }).collect())
     }
 
+    async fn get_student_contacts(
+        &self,
+        school_id: &str,
+        student_ids: &[String],
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT student_id, contact, alternative_contact, email FROM students
+             WHERE school_id = $1 AND student_id = ANY($2)",
+        )
+        .bind(school_id)
+        .bind(student_ids)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "studentId": r.get::<String, _>("student_id"),
+                    "contact": r.get::<Option<String>, _>("contact"),
+                    "alternativeContact": r.get::<Option<String>, _>("alternative_contact"),
+                    "email": r.get::<Option<String>, _>("email"),
+                })
+            })
+            .collect())
+    }
+
     async fn get_student(
         &self,
         school_id: &str,
@@ -380,8 +406,8 @@ impl AcademicRepository for PostgresAcademicRepository {
         data: Value,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
         sqlx::query(
-            "INSERT INTO classes (id, school_id, name, total_students, total_teachers, total_periods, room_number, class_fees, sections, streams) 
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) ON CONFLICT (school_id, id) DO NOTHING"
+            "INSERT INTO classes (id, school_id, name, total_students, total_teachers, total_periods, room_number, class_fees, sections, streams, session_id) 
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) ON CONFLICT (school_id, id) DO NOTHING"
         )
         .bind(data["id"].as_str())
         .bind(school_id)
@@ -393,6 +419,7 @@ impl AcademicRepository for PostgresAcademicRepository {
         .bind(data["classFees"].as_f64().unwrap_or(0.0))
         .bind(data["sections"].clone())
         .bind(data["streams"].clone())
+        .bind(data["sessionId"].as_str())
         .execute(&self.client.pool)
         .await?;
         Ok(data)
@@ -431,6 +458,7 @@ impl AcademicRepository for PostgresAcademicRepository {
                     "class_fees": r.get::<f64, _>("class_fees"),
                     "sections": sections,
                     "streams": streams,
+                    "sessionId": r.get::<Option<String>, _>("session_id"),
                 })
             })
             .collect())
@@ -501,7 +529,7 @@ impl AcademicRepository for PostgresAcademicRepository {
         class_id: &str,
         data: Value,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        sqlx::query("INSERT INTO class_periods (school_id, class_id, name, start_time, end_time, teacher_id, subject_id) VALUES ($1, $2, $3, $4, $5, $6, $7)")
+        sqlx::query("INSERT INTO class_periods (school_id, class_id, name, start_time, end_time, teacher_id, subject_id, day_of_week, section, room_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)")
             .bind(school_id)
             .bind(class_id)
             .bind(data["name"].as_str())
@@ -509,6 +537,9 @@ impl AcademicRepository for PostgresAcademicRepository {
             .bind(data["endTime"].as_str().map(|s| s.parse::<chrono::NaiveTime>().unwrap_or_else(|_| chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap())))
             .bind(data["teacherId"].as_str())
             .bind(data["subjectId"].as_str())
+            .bind(data["dayOfWeek"].as_i64().filter(|d| (1..=7).contains(d)).map(|d| d as i16))
+            .bind(data["section"].as_str().filter(|s| !s.is_empty()))
+            .bind(data["roomId"].as_str().filter(|r| !r.is_empty()))
             .execute(&self.client.pool).await?;
         Ok(())
     }
@@ -541,8 +572,8 @@ impl AcademicRepository for PostgresAcademicRepository {
             id
         };
 
-        sqlx::query("INSERT INTO subjects (id, school_id, name, class_id, class_name, fees, is_compulsory, category, fee_type, fee_interval, schedule_type, schedule_data) 
-                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) 
+        sqlx::query("INSERT INTO subjects (id, school_id, name, class_id, class_name, fees, is_compulsory, category, fee_type, fee_interval, schedule_type, schedule_data, subject_teachers) 
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, COALESCE($13, '[]'::JSONB)) 
                      ON CONFLICT (school_id, id) DO UPDATE SET 
                         name = EXCLUDED.name, 
                         class_id = EXCLUDED.class_id, 
@@ -553,7 +584,8 @@ impl AcademicRepository for PostgresAcademicRepository {
                         fee_type = EXCLUDED.fee_type,
                         fee_interval = EXCLUDED.fee_interval,
                         schedule_type = EXCLUDED.schedule_type,
-                        schedule_data = EXCLUDED.schedule_data")
+                        schedule_data = EXCLUDED.schedule_data,
+                        subject_teachers = EXCLUDED.subject_teachers")
             .bind(&subject_id)
             .bind(school_id)
             .bind(data["subjectName"].as_str())
@@ -566,6 +598,7 @@ impl AcademicRepository for PostgresAcademicRepository {
             .bind(data["feeInterval"].as_i64().unwrap_or(1) as i32)
             .bind(data["scheduleType"].as_str().unwrap_or("daily"))
             .bind(data["scheduleData"].clone())
+            .bind(data.get("subjectTeachers").filter(|t| t.is_array()).cloned())
             .execute(&self.client.pool).await?;
         Ok(data)
     }
@@ -602,6 +635,7 @@ impl AcademicRepository for PostgresAcademicRepository {
                 let fee_interval = r.get::<Option<i32>, _>("fee_interval").unwrap_or(1);
                 let schedule_type = r.get::<Option<String>, _>("schedule_type").unwrap_or_else(|| "daily".to_string());
                 let schedule_data = r.get::<Option<Value>, _>("schedule_data").unwrap_or(json!([]));
+                let subject_teachers = r.try_get::<Option<Value>, _>("subject_teachers").ok().flatten().unwrap_or(json!([]));
                 
                 json!({
                     "id": id,
@@ -623,6 +657,7 @@ impl AcademicRepository for PostgresAcademicRepository {
                     "feeInterval": fee_interval,
                     "scheduleType": schedule_type,
                     "scheduleData": schedule_data,
+                    "subjectTeachers": subject_teachers,
                 })
             })
             .collect())
@@ -633,11 +668,17 @@ impl AcademicRepository for PostgresAcademicRepository {
         school_id: &str,
         data: Value,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
-        sqlx::query("INSERT INTO exams (school_id, name, start_date, end_date) VALUES ($1, $2, $3, $4) ON CONFLICT (school_id, name) DO UPDATE SET start_date = EXCLUDED.start_date")
+        // Exams belong to the named session, else the active one
+        sqlx::query(
+            "INSERT INTO exams (school_id, name, start_date, end_date, session_id)
+             VALUES ($1, $2, $3, $4, COALESCE($5, (SELECT session_id FROM academic_sessions WHERE school_id = $1 AND status = 'active')))
+             ON CONFLICT (school_id, name) DO UPDATE SET start_date = EXCLUDED.start_date",
+        )
             .bind(school_id)
             .bind(data["name"].as_str())
             .bind(data["startDate"].as_str().map(|d| d.parse::<chrono::NaiveDate>().unwrap_or_else(|_| chrono::Utc::now().date_naive())))
             .bind(data["endDate"].as_str().map(|d| d.parse::<chrono::NaiveDate>().unwrap_or_else(|_| chrono::Utc::now().date_naive())))
+            .bind(data["sessionId"].as_str())
             .execute(&self.client.pool).await?;
         Ok(data)
     }
@@ -648,7 +689,13 @@ impl AcademicRepository for PostgresAcademicRepository {
             .await?;
         Ok(rows
             .into_iter()
-            .map(|r| json!({"id": r.get::<i32, _>("id"), "name": r.get::<String, _>("name")}))
+            .map(|r| {
+                json!({
+                    "id": r.get::<i32, _>("id"),
+                    "name": r.get::<String, _>("name"),
+                    "sessionId": r.get::<Option<String>, _>("session_id")
+                })
+            })
             .collect())
     }
     async fn add_student_exam(
@@ -970,6 +1017,24 @@ impl EmployeeRepository for PostgresEmployeeRepository {
         let next_val: i64 = row.get(0);
         Ok(format!("E{:04}", next_val))
     }
+
+    async fn find_employee_id(
+        &self,
+        school_id: &str,
+        ident: &str,
+    ) -> Result<Option<String>, AppError> {
+        let row = sqlx::query(
+            "SELECT employee_id FROM employees
+             WHERE school_id = $1 AND (employee_id = $2 OR data->>'phone' = $2 OR data->>'altPhone' = $2 OR data->>'contact' = $2)
+             ORDER BY (employee_id = $2) DESC
+             LIMIT 1"
+        )
+        .bind(school_id)
+        .bind(ident)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.map(|r| r.get::<String, _>("employee_id")))
+    }
 }
 
 
@@ -1440,10 +1505,170 @@ impl ResourceRepository for PostgresResourceRepository {
 }
 
 // --- Operations Repository ---
+/// Next receipt number from `fee_receipt_seq`, unique even for payments in the same instant.
+async fn next_receipt_no<'e, E: sqlx::PgExecutor<'e>>(executor: E) -> Result<String, AppError> {
+    let n: i64 = sqlx::query_scalar("SELECT nextval('fee_receipt_seq')").fetch_one(executor).await?;
+    Ok(format!("RCPT{:06}", n))
+}
+
+/// Books fee payments inside the caller's transaction; see `OperationsRepository::post_fee_payments`.
+async fn book_fee_payments(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    school_id: &str,
+    student_id: &str,
+    items: &[Value],
+    details: &Value,
+) -> Result<Value, AppError> {
+    let base = match details["receiptNo"].as_str() {
+        Some(r) => r.to_string(),
+        None => next_receipt_no(&mut **tx).await?,
+    };
+    let paid_on = details["paidOn"]
+        .as_str()
+        .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        .unwrap_or_else(|| chrono::Local::now().date_naive());
+    let payment_mode = details["paymentMode"].as_str().unwrap_or("cash");
+    let now = chrono::Local::now().to_rfc3339();
+
+    let mut lines = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let amount = item["amount"].as_f64().unwrap_or(0.0);
+        if amount <= 0.0 {
+            return Err("Payment amounts must be positive".into());
+        }
+        let fee_id = item["feeId"].as_str().unwrap_or("general");
+        let receipt_no = if items.len() > 1 { format!("{}-{}", base, i + 1) } else { base.clone() };
+
+        // Lower the balance first; the guard in the WHERE clause stops overpayment under concurrency
+        let (action, history, balance) = if fee_id == "general" {
+            let row = sqlx::query(
+                "UPDATE student_fees SET pending_amount = pending_amount - $3
+                 WHERE school_id = $1 AND student_id = $2 AND pending_amount >= $3
+                 RETURNING (pending_amount + $3)::FLOAT8 AS previous, pending_amount::FLOAT8 AS pending",
+            )
+            .bind(school_id)
+            .bind(student_id)
+            .bind(amount)
+            .fetch_optional(&mut **tx)
+            .await?
+            .ok_or("Pay amount exceeds pending amount")?;
+            let (previous, pending) = (row.get::<f64, _>("previous"), row.get::<f64, _>("pending"));
+            (
+                "payment",
+                json!({"payAmount": amount, "previousPending": previous, "newPending": pending, "receiptNo": receipt_no, "date": now}),
+                json!({"previousPending": previous, "newPending": pending}),
+            )
+        } else {
+            let row = sqlx::query(
+                "UPDATE custom_fee_records
+                 SET paid_amount = paid_amount + $4,
+                     status = CASE WHEN paid_amount + $4 >= amount + COALESCE(penalty_accrued, 0) THEN 'paid' ELSE 'partial' END,
+                     payments = COALESCE(payments, '[]'::jsonb) || jsonb_build_array($5::jsonb),
+                     updated_at = NOW()
+                 WHERE school_id = $1 AND student_id = $2 AND fee_id = $3 AND status NOT IN ('paid', 'waived', 'carried_forward')
+                   AND NOT EXISTS (SELECT 1 FROM custom_fees f WHERE f.school_id = custom_fee_records.school_id AND f.fee_id = custom_fee_records.fee_id AND f.status = 'archived')
+                 RETURNING paid_amount::FLOAT8 AS paid_amount, status",
+            )
+            .bind(school_id)
+            .bind(student_id)
+            .bind(fee_id)
+            .bind(amount)
+            .bind(json!({
+                "amount": amount,
+                "receiptNo": receipt_no,
+                "mode": payment_mode,
+                "reference": details["reference"],
+                "date": now
+            }))
+            .fetch_optional(&mut **tx)
+            .await?
+            .ok_or_else(|| format!("Nothing is due on fee '{}', or it belongs to an archived session", fee_id))?;
+            (
+                "custom_fee_payment",
+                json!({"feeId": fee_id, "payAmount": amount, "receiptNo": receipt_no, "date": now}),
+                json!({"paidAmount": row.get::<f64, _>("paid_amount"), "status": row.get::<String, _>("status")}),
+            )
+        };
+
+        let fee_head = item["feeName"].as_str().unwrap_or("General");
+        sqlx::query(
+            "INSERT INTO fee_payments (receipt_no, school_id, student_id, amount, payment_mode, collected_by, fee_head, reference, paid_on, fee_id)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+        )
+        .bind(&receipt_no)
+        .bind(school_id)
+        .bind(student_id)
+        .bind(amount)
+        .bind(payment_mode)
+        .bind(details["collectedBy"].as_str())
+        .bind(fee_head)
+        .bind(details["reference"].as_str())
+        .bind(paid_on)
+        .bind(fee_id)
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query("INSERT INTO audit_logs (school_id, target_type, target_id, action, data) VALUES ($1, 'fee', $2, $3, $4)")
+            .bind(school_id)
+            .bind(student_id)
+            .bind(action)
+            .bind(history)
+            .execute(&mut **tx)
+            .await?;
+
+        let mut line = json!({
+            "receiptNo": receipt_no,
+            "studentId": student_id,
+            "amount": amount,
+            "paymentMode": payment_mode,
+            "collectedBy": details["collectedBy"],
+            "feeId": fee_id,
+            "feeHead": fee_head,
+            "reference": details["reference"],
+            "paidOn": paid_on.to_string()
+        });
+        if let (Some(line), Some(balance)) = (line.as_object_mut(), balance.as_object()) {
+            line.extend(balance.clone());
+        }
+        lines.push(line);
+    }
+    Ok(json!({"receiptNo": base, "lines": lines}))
+}
+
 pub struct PostgresOperationsRepository {
     pub client: Arc<DbClient>,
 }
 
+fn coupon_to_json(r: &sqlx::postgres::PgRow) -> Value {
+    let dec = |col: &str| -> f64 {
+        r.get::<Option<bigdecimal::BigDecimal>, _>(col)
+            .and_then(|d| d.to_string().parse().ok())
+            .unwrap_or(0.0)
+    };
+    json!({
+        "couponId": r.get::<String, _>("coupon_id"),
+        "couponName": r.get::<String, _>("coupon_name"),
+        "discountType": r.get::<String, _>("discount_type"),
+        "discountValue": r.get::<bigdecimal::BigDecimal, _>("discount_value").to_string(),
+        "maxUses": r.get::<i32, _>("max_uses"),
+        "currentUses": r.get::<i32, _>("current_uses"),
+        "assignedEmployeeId": r.get::<Option<String>, _>("assigned_employee_id"),
+        "employeeReward": r.get::<bigdecimal::BigDecimal, _>("employee_reward").to_string(),
+        "description": r.get::<Option<String>, _>("description"),
+        "status": r.get::<String, _>("status"),
+        "validFrom": r.get::<Option<chrono::NaiveDate>, _>("valid_from").map(|d| d.to_string()),
+        "validUntil": r.get::<Option<chrono::NaiveDate>, _>("valid_until").map(|d| d.to_string()),
+        "perStudentLimit": r.get::<Option<i32>, _>("per_student_limit").unwrap_or(0),
+        "budget": dec("budget"),
+        "discountGiven": dec("discount_given"),
+        "minFeeAmount": dec("min_fee_amount"),
+        "maxDiscount": dec("max_discount"),
+        "referrerType": r.get::<Option<String>, _>("referrer_type"),
+        "referrerId": r.get::<Option<String>, _>("referrer_id"),
+        "referrerName": r.get::<Option<String>, _>("referrer_name"),
+        "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339()
+    })
+}
+
 #[async_trait]
 impl OperationsRepository for PostgresOperationsRepository {
     async fn mark_attendance(
@@ -1454,7 +1679,7 @@ impl OperationsRepository for PostgresOperationsRepository {
         date: &str,
         data: Value,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        sqlx::query("INSERT INTO attendance (school_id, role, user_id, date, status, in_time, out_time, total_time) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (school_id, user_id, date) DO UPDATE SET status = EXCLUDED.status, in_time = EXCLUDED.in_time, out_time = EXCLUDED.out_time, total_time = EXCLUDED.total_time")
+        sqlx::query("INSERT INTO attendance (school_id, role, user_id, date, status, in_time, out_time, total_time) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (school_id, role, user_id, date) DO UPDATE SET status = EXCLUDED.status, in_time = EXCLUDED.in_time, out_time = EXCLUDED.out_time, total_time = EXCLUDED.total_time, source = NULL")
             .bind(school_id)
             .bind(role)
             .bind(user_id)
@@ -1676,14 +1901,133 @@ impl OperationsRepository for PostgresOperationsRepository {
             .bind(school_id).bind(fee_id).bind(action).bind(data).execute(&self.client.pool).await?;
         Ok(())
     }
+    async fn add_fee_payment(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let receipt_no = match data["receiptNo"].as_str() {
+            Some(r) => r.to_string(),
+            None => next_receipt_no(&self.client.pool).await?,
+        };
+        let paid_on = data["paidOn"]
+            .as_str()
+            .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            .unwrap_or_else(|| chrono::Local::now().date_naive());
+        let payment_mode = data["paymentMode"].as_str().unwrap_or("cash");
+        let fee_head = data["feeHead"].as_str().unwrap_or("General");
+        let fee_id = data["feeId"].as_str().unwrap_or("general");
+
+        sqlx::query(
+            "INSERT INTO fee_payments (receipt_no, school_id, student_id, amount, payment_mode, collected_by, fee_head, reference, paid_on, fee_id)
+             VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10)"
+        )
+        .bind(&receipt_no)
+        .bind(school_id)
+        .bind(student_id)
+        .bind(data["amount"].as_f64().unwrap_or(0.0))
+        .bind(payment_mode)
+        .bind(data["collectedBy"].as_str())
+        .bind(fee_head)
+        .bind(data["reference"].as_str())
+        .bind(paid_on)
+        .bind(fee_id)
+        .execute(&self.client.pool).await?;
+
+        Ok(json!({
+            "receiptNo": receipt_no,
+            "studentId": student_id,
+            "amount": data["amount"],
+            "paymentMode": payment_mode,
+            "collectedBy": data["collectedBy"],
+            "feeId": fee_id,
+            "feeHead": fee_head,
+            "reference": data["reference"],
+            "paidOn": paid_on.to_string()
+        }))
+    }
+    async fn post_fee_payments(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        items: &[Value],
+        details: &Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut tx = self.client.pool.begin().await?;
+        let posted = book_fee_payments(&mut tx, school_id, student_id, items, details).await?;
+        tx.commit().await?;
+        Ok(posted)
+    }
+    async fn pay_custom_fee(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        fee_id: &str,
+        amount: f64,
+        payment: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query(
+            "UPDATE custom_fee_records
+             SET paid_amount = paid_amount + $4,
+                 status = CASE WHEN paid_amount + $4 >= amount + COALESCE(penalty_accrued, 0) THEN 'paid' ELSE 'partial' END,
+                 payments = COALESCE(payments, '[]'::jsonb) || jsonb_build_array($5::jsonb),
+                 updated_at = NOW()
+             WHERE school_id = $1 AND student_id = $2 AND fee_id = $3 AND status <> 'carried_forward'
+               AND NOT EXISTS (SELECT 1 FROM custom_fees f WHERE f.school_id = custom_fee_records.school_id AND f.fee_id = custom_fee_records.fee_id AND f.status = 'archived')
+             RETURNING paid_amount::FLOAT8 AS paid_amount, status"
+        )
+        .bind(school_id)
+        .bind(student_id)
+        .bind(fee_id)
+        .bind(amount)
+        .bind(payment)
+        .fetch_optional(&self.client.pool)
+        .await?
+        .ok_or("Custom fee record not found, or it belongs to an archived session")?;
+
+        Ok(json!({
+            "feeId": fee_id,
+            "studentId": student_id,
+            "paidAmount": row.get::<f64, _>("paid_amount"),
+            "status": row.get::<String, _>("status")
+        }))
+    }
     async fn update_employee_salary_params(
         &self,
         school_id: &str,
         employee_id: &str,
         data: Value,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        sqlx::query("UPDATE employees SET base_salary = COALESCE($1, base_salary), bonus = COALESCE($2, bonus) WHERE school_id = $3 AND employee_id = $4")
-            .bind(data["baseSalary"].as_f64()).bind(data["bonus"].as_f64()).bind(school_id).bind(employee_id).execute(&self.client.pool).await?;
+        // Salary parameters live in the employee's data document alongside the rest of the profile
+        let mut patch = serde_json::Map::new();
+        for key in ["baseSalary", "bonus", "aid", "incrementPercent", "experienceYears", "experienceRate", "tenureMonths", "tenureRate"] {
+            if let Some(v) = data[key].as_f64() {
+                patch.insert(key.to_string(), json!(v));
+            }
+        }
+        if data["allowances"].is_object() {
+            patch.insert("allowances".to_string(), data["allowances"].clone());
+        }
+        // Statutory profile: regime and declarations for TDS, PT state, PF/ESI opt-outs and IDs
+        for key in ["taxRegime", "ptState", "pan", "uan", "esiNumber"] {
+            if let Some(v) = data[key].as_str() {
+                patch.insert(key.to_string(), json!(v));
+            }
+        }
+        for key in ["pfApplicable", "esiApplicable"] {
+            if let Some(v) = data[key].as_bool() {
+                patch.insert(key.to_string(), json!(v));
+            }
+        }
+        if data["taxDeclarations"].is_object() {
+            patch.insert("taxDeclarations".to_string(), data["taxDeclarations"].clone());
+        }
+        let result = sqlx::query("UPDATE employees SET data = data || $3, updated_at = NOW() WHERE school_id = $1 AND employee_id = $2")
+            .bind(school_id).bind(employee_id).bind(Value::Object(patch)).execute(&self.client.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err("Employee not found".into());
+        }
         Ok(())
     }
     async fn add_employee_payment(
@@ -1769,8 +2113,9 @@ impl OperationsRepository for PostgresOperationsRepository {
         let description = data["description"].as_str();
 
         sqlx::query(
-            "INSERT INTO custom_fees (fee_id, school_id, fee_name, fee_type, amount, scope, target_classes, target_students, due_date, has_penalty, penalty_per_day, description)
-             VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9::date,$10,$11,$12)"
+            "INSERT INTO custom_fees (fee_id, school_id, fee_name, fee_type, amount, scope, target_classes, target_students, due_date, has_penalty, penalty_per_day, description, session_id)
+             VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9::date,$10,$11,$12,
+                     COALESCE($13, (SELECT session_id FROM academic_sessions WHERE school_id = $2 AND status = 'active')))"
         )
         .bind(&fee_id)
         .bind(school_id)
@@ -1784,6 +2129,7 @@ impl OperationsRepository for PostgresOperationsRepository {
         .bind(has_penalty)
         .bind(penalty_per_day)
         .bind(description)
+        .bind(data["sessionId"].as_str())
         .execute(&self.client.pool).await?;
 
         Ok(json!({"feeId": fee_id, "feeName": fee_name, "amount": amount}))
@@ -1810,6 +2156,7 @@ impl OperationsRepository for PostgresOperationsRepository {
                 "penaltyPerDay": r.get::<bigdecimal::BigDecimal, _>("penalty_per_day").to_string(),
                 "description": r.get::<Option<String>, _>("description"),
                 "status": r.get::<String, _>("status"),
+                "sessionId": r.get::<Option<String>, _>("session_id"),
                 "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339()
             })
         }).collect())
@@ -1837,6 +2184,9 @@ impl OperationsRepository for PostgresOperationsRepository {
             .bind(school_id).bind(fee_id)
             .fetch_optional(&self.client.pool).await?;
         let fee_row = fee_row.ok_or("Custom fee not found")?;
+        if fee_row.get::<String, _>("status") == "archived" {
+            return Err("This fee belongs to an archived session".into());
+        }
 
         let amount: bigdecimal::BigDecimal = fee_row.get("amount");
         let scope: String = fee_row.get("scope");
@@ -1921,6 +2271,10 @@ impl OperationsRepository for PostgresOperationsRepository {
                 } else { 0.0 }
             } else { 0.0 };
 
+            // A balance carried into a new session is owed on the new session's fee instead
+            let status = r.get::<String, _>("status");
+            let total_due = if status == "carried_forward" { 0.0 } else { amount + penalty - paid };
+
             json!({
                 "feeId": r.get::<String, _>("fee_id"),
                 "feeName": r.get::<String, _>("fee_name"),
@@ -1928,8 +2282,8 @@ impl OperationsRepository for PostgresOperationsRepository {
                 "amount": amount,
                 "paidAmount": paid,
                 "penalty": penalty,
-                "totalDue": amount + penalty - paid,
-                "status": r.get::<String, _>("status"),
+                "totalDue": total_due,
+                "status": status,
                 "dueDate": due_date.map(|d| d.to_string()),
                 "hasPenalty": has_penalty,
                 "penaltyPerDay": penalty_per_day,
@@ -2043,40 +2397,130 @@ impl OperationsRepository for PostgresOperationsRepository {
         let assigned_employee_id = data["assignedEmployeeId"].as_str();
         let employee_reward = data["employeeReward"].as_f64().unwrap_or(0.0);
         let description = data["description"].as_str();
+        let valid_from = data["validFrom"].as_str().and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        let valid_until = data["validUntil"].as_str().and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        let per_student_limit = data["perStudentLimit"].as_i64().unwrap_or(0) as i32;
+        let budget = data["budget"].as_f64().unwrap_or(0.0);
+        let min_fee_amount = data["minFeeAmount"].as_f64().unwrap_or(0.0);
+        let max_discount = data["maxDiscount"].as_f64().unwrap_or(0.0);
+        let referrer_type = data["referrerType"].as_str();
+        let referrer_id = data["referrerId"].as_str();
+        let referrer_name = match (data["referrerName"].as_str(), referrer_type, referrer_id) {
+            (Some(n), _, _) => Some(n.to_string()),
+            (None, Some("student"), Some(id)) => sqlx::query("SELECT name FROM students WHERE school_id = $1 AND student_id = $2")
+                .bind(school_id).bind(id)
+                .fetch_optional(&self.client.pool).await?
+                .map(|r| r.get::<String, _>("name")),
+            _ => None,
+        };
 
         sqlx::query(
-            "INSERT INTO referral_coupons (coupon_id, school_id, coupon_name, discount_type, discount_value, max_uses, assigned_employee_id, employee_reward, description)
-             VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)"
+            "INSERT INTO referral_coupons (coupon_id, school_id, coupon_name, discount_type, discount_value, max_uses, assigned_employee_id, employee_reward, description,
+                valid_from, valid_until, per_student_limit, budget, min_fee_amount, max_discount, referrer_type, referrer_id, referrer_name)
+             VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14,$15,$16,$17,$18)"
         )
         .bind(&coupon_id).bind(school_id).bind(&coupon_name)
         .bind(discount_type).bind(discount_value).bind(max_uses)
         .bind(assigned_employee_id).bind(employee_reward).bind(description)
+        .bind(valid_from).bind(valid_until).bind(per_student_limit)
+        .bind(budget).bind(min_fee_amount).bind(max_discount)
+        .bind(referrer_type).bind(referrer_id).bind(&referrer_name)
         .execute(&self.client.pool).await?;
 
         Ok(json!({"couponId": coupon_id, "couponName": coupon_name}))
     }
 
+    async fn update_coupon(
+        &self,
+        school_id: &str,
+        coupon_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let date = |k: &str| data[k].as_str().and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        // Empty strings clear the validity window; missing keys leave it unchanged
+        let clear_from = data["validFrom"].as_str() == Some("");
+        let clear_until = data["validUntil"].as_str() == Some("");
+        let result = sqlx::query(
+            "UPDATE referral_coupons SET
+                discount_type = COALESCE($3, discount_type),
+                discount_value = COALESCE($4, discount_value),
+                max_uses = COALESCE($5, max_uses),
+                employee_reward = COALESCE($6, employee_reward),
+                description = COALESCE($7, description),
+                valid_from = CASE WHEN $8 THEN NULL ELSE COALESCE($9, valid_from) END,
+                valid_until = CASE WHEN $10 THEN NULL ELSE COALESCE($11, valid_until) END,
+                per_student_limit = COALESCE($12, per_student_limit),
+                budget = COALESCE($13, budget),
+                min_fee_amount = COALESCE($14, min_fee_amount),
+                max_discount = COALESCE($15, max_discount),
+                referrer_type = COALESCE($16, referrer_type),
+                referrer_id = COALESCE($17, referrer_id),
+                referrer_name = COALESCE($18, referrer_name)
+             WHERE school_id = $1 AND coupon_id = $2"
+        )
+        .bind(school_id).bind(coupon_id)
+        .bind(data["discountType"].as_str())
+        .bind(data["discountValue"].as_f64())
+        .bind(data["maxUses"].as_i64().map(|v| v as i32))
+        .bind(data["employeeReward"].as_f64())
+        .bind(data["description"].as_str())
+        .bind(clear_from).bind(date("validFrom"))
+        .bind(clear_until).bind(date("validUntil"))
+        .bind(data["perStudentLimit"].as_i64().map(|v| v as i32))
+        .bind(data["budget"].as_f64())
+        .bind(data["minFeeAmount"].as_f64())
+        .bind(data["maxDiscount"].as_f64())
+        .bind(data["referrerType"].as_str())
+        .bind(data["referrerId"].as_str())
+        .bind(data["referrerName"].as_str())
+        .execute(&self.client.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err("Coupon not found".into());
+        }
+        self.get_coupon(school_id, coupon_id).await?.ok_or_else(|| "Coupon not found".into())
+    }
+
     async fn get_coupons(
         &self,
         school_id: &str,
     ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
         let rows = sqlx::query("SELECT * FROM referral_coupons WHERE school_id = $1 ORDER BY created_at DESC")
             .bind(school_id).fetch_all(&self.client.pool).await?;
-        Ok(rows.into_iter().map(|r| {
-            json!({
-                "couponId": r.get::<String, _>("coupon_id"),
-                "couponName": r.get::<String, _>("coupon_name"),
-                "discountType": r.get::<String, _>("discount_type"),
-                "discountValue": r.get::<bigdecimal::BigDecimal, _>("discount_value").to_string(),
-                "maxUses": r.get::<i32, _>("max_uses"),
-                "currentUses": r.get::<i32, _>("current_uses"),
-                "assignedEmployeeId": r.get::<Option<String>, _>("assigned_employee_id"),
-                "employeeReward": r.get::<bigdecimal::BigDecimal, _>("employee_reward").to_string(),
-                "description": r.get::<Option<String>, _>("description"),
-                "status": r.get::<String, _>("status"),
-                "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339()
-            })
-        }).collect())
+        Ok(rows.iter().map(coupon_to_json).collect())
+    }
+
+    async fn get_coupon(
+        &self,
+        school_id: &str,
+        coupon_id: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query("SELECT * FROM referral_coupons WHERE school_id = $1 AND coupon_id = $2")
+            .bind(school_id).bind(coupon_id)
+            .fetch_optional(&self.client.pool).await?;
+        Ok(row.as_ref().map(coupon_to_json))
+    }
+
+    async fn get_coupon_by_name(
+        &self,
+        school_id: &str,
+        coupon_name: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query("SELECT * FROM referral_coupons WHERE school_id = $1 AND coupon_name = $2")
+            .bind(school_id).bind(coupon_name)
+            .fetch_optional(&self.client.pool).await?;
+        Ok(row.as_ref().map(coupon_to_json))
+    }
+
+    async fn count_student_coupon_uses(
+        &self,
+        school_id: &str,
+        coupon_id: &str,
+        student_id: &str,
+    ) -> Result<i64, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query("SELECT COUNT(*) AS cnt FROM coupon_usage_log WHERE school_id = $1 AND coupon_id = $2 AND student_id = $3")
+            .bind(school_id).bind(coupon_id).bind(student_id)
+            .fetch_one(&self.client.pool).await?;
+        Ok(row.get::<i64, _>("cnt"))
     }
 
     async fn delete_coupon(
@@ -2102,95 +2546,86 @@ impl OperationsRepository for PostgresOperationsRepository {
         Ok(())
     }
 
-    async fn validate_coupon(
-        &self,
-        school_id: &str,
-        coupon_name: &str,
-    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
-        let row = sqlx::query("SELECT * FROM referral_coupons WHERE school_id = $1 AND coupon_name = $2")
-            .bind(school_id).bind(coupon_name)
-            .fetch_optional(&self.client.pool).await?;
-
-        match row {
-            None => Ok(None),
-            Some(r) => {
-                let status: String = r.get("status");
-                let max_uses: i32 = r.get("max_uses");
-                let current_uses: i32 = r.get("current_uses");
-
-                if status != "active" {
-                    return Ok(Some(json!({"valid": false, "reason": "Coupon is blocked"})));
-                }
-                if max_uses > 0 && current_uses >= max_uses {
-                    return Ok(Some(json!({"valid": false, "reason": "Coupon usage limit reached"})));
-                }
-
-                Ok(Some(json!({
-                    "valid": true,
-                    "couponId": r.get::<String, _>("coupon_id"),
-                    "couponName": r.get::<String, _>("coupon_name"),
-                    "discountType": r.get::<String, _>("discount_type"),
-                    "discountValue": r.get::<bigdecimal::BigDecimal, _>("discount_value").to_string(),
-                    "assignedEmployeeId": r.get::<Option<String>, _>("assigned_employee_id"),
-                    "employeeReward": r.get::<bigdecimal::BigDecimal, _>("employee_reward").to_string()
-                })))
-            }
-        }
-    }
-
     async fn use_coupon(
         &self,
         school_id: &str,
         coupon_id: &str,
         student_id: &str,
         discount: f64,
+        details: Value,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
-        // 1. Fetch coupon
-        let row = sqlx::query("SELECT * FROM referral_coupons WHERE school_id = $1 AND coupon_id = $2")
+        let mut tx = self.client.pool.begin().await?;
+        // 1. Claim a use; the caps are re-checked here so concurrent redemptions cannot overshoot them.
+        // The row lock makes redemptions of one coupon queue, so the usage count below is current.
+        sqlx::query("SELECT 1 FROM referral_coupons WHERE school_id = $1 AND coupon_id = $2 FOR UPDATE")
             .bind(school_id).bind(coupon_id)
-            .fetch_optional(&self.client.pool).await?;
-        let coupon = row.ok_or("Coupon not found")?;
+            .fetch_optional(&mut *tx).await?
+            .ok_or("Coupon not found")?;
+        let claimed = sqlx::query(
+            "UPDATE referral_coupons SET current_uses = current_uses + 1, discount_given = discount_given + $3
+             WHERE school_id = $1 AND coupon_id = $2 AND status = 'active'
+               AND (max_uses = 0 OR current_uses < max_uses)
+               AND (budget = 0 OR discount_given + $3 <= budget)
+               AND (COALESCE(per_student_limit, 0) = 0 OR per_student_limit > (
+                   SELECT COUNT(*) FROM coupon_usage_log u
+                   WHERE u.school_id = $1 AND u.coupon_id = $2 AND u.student_id = $4))
+             RETURNING assigned_employee_id, employee_reward, referrer_type, referrer_id"
+        )
+        .bind(school_id).bind(coupon_id).bind(discount).bind(student_id)
+        .fetch_optional(&mut *tx).await?;
+        let coupon = claimed.ok_or("Coupon is blocked, exhausted, over its budget or already used by this student")?;
 
         let assigned_emp: Option<String> = coupon.get("assigned_employee_id");
         let reward: f64 = coupon.get::<bigdecimal::BigDecimal, _>("employee_reward").to_string().parse().unwrap_or(0.0);
 
-        // 2. Increment usage
-        sqlx::query("UPDATE referral_coupons SET current_uses = current_uses + 1 WHERE school_id = $1 AND coupon_id = $2")
-            .bind(school_id).bind(coupon_id)
-            .execute(&self.client.pool).await?;
+        // 2. New admissions are counted towards the referrer: flagged by the caller, or a student added in the last 30 days
+        let is_admission = match details["isAdmission"].as_bool() {
+            Some(v) => v,
+            None => sqlx::query("SELECT created_at > NOW() - INTERVAL '30 days' AS is_new FROM students WHERE school_id = $1 AND student_id = $2")
+                .bind(school_id).bind(student_id)
+                .fetch_optional(&mut *tx).await?
+                .and_then(|r| r.get::<Option<bool>, _>("is_new"))
+                .unwrap_or(false),
+        };
 
         // 3. Log usage
         sqlx::query(
-            "INSERT INTO coupon_usage_log (school_id, coupon_id, student_id, discount_applied, employee_id, reward_paid)
-             VALUES ($1,$2,$3,$4,$5,$6)"
+            "INSERT INTO coupon_usage_log (school_id, coupon_id, student_id, discount_applied, employee_id, reward_paid, fee_amount, referrer_type, referrer_id, is_admission)
+             VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10)"
         )
         .bind(school_id).bind(coupon_id).bind(student_id)
         .bind(discount).bind(&assigned_emp).bind(reward)
-        .execute(&self.client.pool).await?;
+        .bind(details["feeAmount"].as_f64())
+        .bind(coupon.get::<Option<String>, _>("referrer_type"))
+        .bind(coupon.get::<Option<String>, _>("referrer_id"))
+        .bind(is_admission)
+        .execute(&mut *tx).await?;
 
         // 4. Award employee commission (add to bonus via employee data JSONB)
         let mut reward_msg = String::from("No employee assigned");
         if let Some(ref emp_id) = assigned_emp {
             if reward > 0.0 {
                 // Get employee data, add to bonus
-                let emp_row = sqlx::query("SELECT data FROM employees WHERE school_id = $1 AND employee_id = $2")
+                let emp_row = sqlx::query("SELECT data FROM employees WHERE school_id = $1 AND employee_id = $2 FOR UPDATE")
                     .bind(school_id).bind(emp_id)
-                    .fetch_optional(&self.client.pool).await?;
+                    .fetch_optional(&mut *tx).await?;
                 if let Some(er) = emp_row {
                     let mut emp_data: Value = er.get("data");
                     let current_bonus = emp_data["bonus"].as_f64().unwrap_or(0.0);
                     emp_data["bonus"] = json!(current_bonus + reward);
                     sqlx::query("UPDATE employees SET data = $3 WHERE school_id = $1 AND employee_id = $2")
                         .bind(school_id).bind(emp_id).bind(&emp_data)
-                        .execute(&self.client.pool).await?;
+                        .execute(&mut *tx).await?;
                     reward_msg = format!("₹{} added to {}'s bonus", reward, emp_id);
                 }
             }
         }
 
+        tx.commit().await?;
         Ok(json!({
             "used": true,
             "discount": discount,
+            "isAdmission": is_admission,
             "rewardMessage": reward_msg
         }))
     }
@@ -2355,372 +2790,4834 @@ impl ReminderRepository for PostgresReminderRepository {
             .map(|r| json!({"id": r.get::<i32, _>("id"), "title": r.get::<String, _>("title")}))
             .collect())
     }
-}
-
-// --- DocumentBox Repository ---
-pub struct PostgresDocumentBoxRepository {
-    pub client: Arc<DbClient>,
-}
 
-#[async_trait]
-impl DocumentBoxRepository for PostgresDocumentBoxRepository {
-    async fn add_document(
+    async fn add_reminder_rule(
         &self,
         school_id: &str,
         data: Value,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
-        sqlx::query("INSERT INTO document_box (school_id, user_id, doc_type, file_url) VALUES ($1, $2, $3, $4)")
-            .bind(school_id)
-            .bind(data["userId"].as_str())
-            .bind(data["docType"].as_str())
-            .bind(data["fileUrl"].as_str())
-            .execute(&self.client.pool).await?;
-        Ok(data)
+        let rule_id = format!("RR{}", chrono::Utc::now().timestamp_millis());
+        let name = data["name"].as_str().unwrap_or("Fee reminder");
+        let offset_days = data["offsetDays"].as_i64().unwrap_or(0) as i32;
+        let repeat_every_days = data["repeatEveryDays"].as_i64().unwrap_or(0).max(0) as i32;
+        let channel = data["channel"].as_str().unwrap_or("sms");
+        let template_key = data["templateKey"].as_str().unwrap_or("fee_due_reminder");
+        let active = data["active"].as_bool().unwrap_or(true);
+
+        sqlx::query(
+            "INSERT INTO reminder_rules (rule_id, school_id, name, offset_days, repeat_every_days, channel, template_key, active)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+        )
+        .bind(&rule_id)
+        .bind(school_id)
+        .bind(name)
+        .bind(offset_days)
+        .bind(repeat_every_days)
+        .bind(channel)
+        .bind(template_key)
+        .bind(active)
+        .execute(&self.client.pool).await?;
+
+        Ok(json!({
+            "ruleId": rule_id,
+            "name": name,
+            "offsetDays": offset_days,
+            "repeatEveryDays": repeat_every_days,
+            "channel": channel,
+            "templateKey": template_key,
+            "active": active
+        }))
     }
 
-    async fn get_documents(
+    async fn get_reminder_rules(
         &self,
         school_id: &str,
     ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
-        let rows = sqlx::query("SELECT * FROM document_box WHERE school_id = $1")
+        let rows = sqlx::query("SELECT * FROM reminder_rules WHERE school_id = $1 ORDER BY offset_days")
             .bind(school_id)
             .fetch_all(&self.client.pool)
             .await?;
         Ok(rows
             .into_iter()
-            .map(
-                |r| json!({"id": r.get::<i32, _>("id"), "docType": r.get::<String, _>("doc_type")}),
-            )
+            .map(|r| json!({
+                "ruleId": r.get::<String, _>("rule_id"),
+                "name": r.get::<String, _>("name"),
+                "offsetDays": r.get::<i32, _>("offset_days"),
+                "repeatEveryDays": r.get::<i32, _>("repeat_every_days"),
+                "channel": r.get::<String, _>("channel"),
+                "templateKey": r.get::<String, _>("template_key"),
+                "active": r.get::<bool, _>("active")
+            }))
             .collect())
     }
-}
 
-// --- School Repository ---
-pub struct PostgresSchoolRepository {
-    pub client: Arc<DbClient>,
-}
+    async fn delete_reminder_rule(
+        &self,
+        school_id: &str,
+        rule_id: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        sqlx::query("DELETE FROM reminder_rules WHERE school_id = $1 AND rule_id = $2")
+            .bind(school_id)
+            .bind(rule_id)
+            .execute(&self.client.pool)
+            .await?;
+        Ok(())
+    }
 
-#[async_trait]
-impl SchoolRepository for PostgresSchoolRepository {
-    async fn get_school(
+    async fn get_fee_due_items(
         &self,
         school_id: &str,
-    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
-        let row = sqlx::query(
-            "SELECT school_id, school_name, data, wallet_balance, per_student_rate, billing_status, 
-             trial_ends_at, last_billing_date, status, is_blocked
-             FROM schools WHERE school_id = $1"
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT r.fee_id, cf.fee_name, cf.due_date,
+                    (r.amount + COALESCE(r.penalty_accrued, 0) - COALESCE(r.paid_amount, 0))::FLOAT8 AS outstanding,
+                    s.student_id, s.name, s.class_name, s.contact, s.email
+             FROM custom_fee_records r
+             JOIN custom_fees cf ON cf.fee_id = r.fee_id AND cf.school_id = r.school_id
+             JOIN students s ON s.school_id = r.school_id AND s.student_id = r.student_id
+             WHERE r.school_id = $1 AND cf.due_date IS NOT NULL AND cf.status = 'active'
+               AND r.status NOT IN ('paid', 'waived', 'carried_forward')
+               AND r.amount + COALESCE(r.penalty_accrued, 0) > COALESCE(r.paid_amount, 0)
+               AND s.status = 'active'
+             ORDER BY s.student_id, cf.due_date"
         )
         .bind(school_id)
-        .fetch_optional(&self.client.pool)
+        .fetch_all(&self.client.pool)
         .await?;
-        Ok(
-            row.map(|r| {
-                json!({
-                    "schoolId": r.get::<String, _>("school_id"), 
-                    "schoolName": r.get::<String, _>("school_name"),
-                    "data": r.get::<Value, _>("data"),
-                    "walletBalance": r.get::<bigdecimal::BigDecimal, _>("wallet_balance").to_string(),
-                    "perStudentRate": r.get::<bigdecimal::BigDecimal, _>("per_student_rate").to_string(),
-                    "billingStatus": r.get::<String, _>("billing_status"),
-                    "trialEndsAt": r.try_get::<chrono::DateTime<chrono::Utc>, _>("trial_ends_at").ok().map(|t| t.to_rfc3339()),
-                    "lastBillingDate": r.try_get::<chrono::DateTime<chrono::Utc>, _>("last_billing_date").ok().map(|t| t.to_rfc3339()),
-                    "status": r.get::<String, _>("status"),
-                    "isBlocked": r.get::<bool, _>("is_blocked")
-                })
-            })
-        )
+
+        Ok(rows
+            .into_iter()
+            .map(|r| json!({
+                "feeId": r.get::<String, _>("fee_id"),
+                "feeName": r.get::<String, _>("fee_name"),
+                "dueDate": r.get::<chrono::NaiveDate, _>("due_date").to_string(),
+                "amount": r.get::<f64, _>("outstanding"),
+                "studentId": r.get::<String, _>("student_id"),
+                "studentName": r.get::<Option<String>, _>("name"),
+                "className": r.get::<String, _>("class_name"),
+                "contact": r.get::<Option<String>, _>("contact"),
+                "email": r.get::<Option<String>, _>("email")
+            }))
+            .collect())
+    }
+
+    async fn get_active_school_ids(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query("SELECT school_id FROM schools WHERE status = 'active' AND is_blocked = FALSE")
+            .fetch_all(&self.client.pool)
+            .await?;
+        Ok(rows.into_iter().map(|r| r.get::<String, _>("school_id")).collect())
     }
 }
 
-// --- Responsibility Repository ---
-pub struct PostgresResponsibilityRepository {
+// --- Payment Repository ---
+pub struct PostgresPaymentRepository {
     pub client: Arc<DbClient>,
 }
 
+fn payment_order_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "orderId": r.get::<String, _>("order_id"),
+        "schoolId": r.get::<String, _>("school_id"),
+        "studentId": r.get::<String, _>("student_id"),
+        "amount": r.get::<f64, _>("amount_f"),
+        "currency": r.get::<String, _>("currency"),
+        "gateway": r.get::<String, _>("gateway"),
+        "gatewayOrderId": r.get::<Option<String>, _>("gateway_order_id"),
+        "gatewayPaymentId": r.get::<Option<String>, _>("gateway_payment_id"),
+        "items": r.get::<Value, _>("items"),
+        "status": r.get::<String, _>("status"),
+        "receipt": r.get::<Option<Value>, _>("receipt"),
+        "error": r.get::<Option<String>, _>("error"),
+        "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339(),
+        "paidAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("paid_at").map(|t| t.to_rfc3339())
+    })
+}
+
 #[async_trait]
-impl ResponsibilityRepository for PostgresResponsibilityRepository {
-    async fn get_responsibilities(
+impl PaymentRepository for PostgresPaymentRepository {
+    async fn next_order_id(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let n: i64 = sqlx::query_scalar("SELECT nextval('payment_order_seq')").fetch_one(&self.client.pool).await?;
+        Ok(format!("ORD{:06}", n))
+    }
+
+    async fn create_order(
         &self,
         school_id: &str,
-    ) -> Result<Vec<Value>, AppError> {
-        let rows = sqlx::query(
-            "SELECT * FROM responsibilities WHERE school_id = $1",
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        sqlx::query(
+            "INSERT INTO payment_orders (order_id, school_id, student_id, amount, currency, gateway, gateway_order_id, items)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
         )
+        .bind(data["orderId"].as_str())
         .bind(school_id)
-        .fetch_all(&self.client.pool)
+        .bind(data["studentId"].as_str())
+        .bind(data["amount"].as_f64().unwrap_or(0.0))
+        .bind(data["currency"].as_str().unwrap_or("INR"))
+        .bind(data["gateway"].as_str())
+        .bind(data["gatewayOrderId"].as_str())
+        .bind(&data["items"])
+        .execute(&self.client.pool)
         .await?;
-        Ok(rows
-            .into_iter()
-            .map(|r| json!({
-                "responsibilityId": r.get::<String, _>("responsibility_id"),
-                "schoolId": r.get::<String, _>("school_id"),
-                "name": r.get::<String, _>("name"),
-                "description": r.get::<Option<String>, _>("description"),
-                "perDayPrice": r.get::<bigdecimal::BigDecimal, _>("per_day_price").to_f64().unwrap_or(0.0),
-                "timePeriod": r.get::<i32, _>("time_period"),
-                "spaceCategory": r.get::<Option<String>, _>("space_category").unwrap_or_default(),
-                "responsibilityField": r.get::<Option<String>, _>("responsibility_field").unwrap_or_default(),
-                "spaceId": r.get::<Option<String>, _>("space_id").unwrap_or_default(),
-                "workLevel": r.get::<Option<String>, _>("work_level").unwrap_or_default(),
-                "workAmount": r.try_get::<bigdecimal::BigDecimal, _>("work_amount").unwrap_or_default().to_f64().unwrap_or(0.0),
-                "workPeriod": r.get::<Option<String>, _>("work_period").unwrap_or_default(),
-                "customDates": r.get::<Option<Value>, _>("custom_dates").unwrap_or(json!([])),
-                "totalPrice": r.try_get::<bigdecimal::BigDecimal, _>("total_price").unwrap_or_default().to_f64().unwrap_or(0.0),
-                "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339()
-            }))
-            .collect())
+        Ok(data)
     }
 
-    async fn add_responsibility(
+    async fn get_order(
         &self,
-        school_id: &str,
-        data: Value,
-    ) -> Result<Value, AppError> {
-        let res_id = format!("RES{}", chrono::Utc::now().timestamp_millis());
-        let name = data["name"].as_str().ok_or("Name is required")?;
-        let description = data["description"].as_str();
-
-        let per_day_price = data["perDayPrice"].as_f64().unwrap_or(0.0);
-        let time_period = data["timePeriod"].as_i64().unwrap_or(0) as i32;
-        let space_category = data["spaceCategory"].as_str();
-        let responsibility_field = data["responsibilityField"].as_str();
-        let space_id = data["spaceId"].as_str();
+        order_id: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query("SELECT *, amount::FLOAT8 AS amount_f FROM payment_orders WHERE order_id = $1")
+            .bind(order_id)
+            .fetch_optional(&self.client.pool)
+            .await?;
+        Ok(row.as_ref().map(payment_order_json))
+    }
 
-        let work_level = data["workLevel"].as_str();
-        let work_amount = data["workAmount"].as_f64().unwrap_or(0.0);
-        let work_period = data["workPeriod"].as_str();
-        let custom_dates = data["customDates"].clone();
-        let total_price = data["totalPrice"].as_f64().unwrap_or(0.0);
+    async fn get_order_by_gateway_ref(
+        &self,
+        gateway: &str,
+        gateway_order_id: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query(
+            "SELECT *, amount::FLOAT8 AS amount_f FROM payment_orders WHERE gateway = $1 AND gateway_order_id = $2",
+        )
+        .bind(gateway)
+        .bind(gateway_order_id)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(payment_order_json))
+    }
 
-        sqlx::query(
-            "INSERT INTO responsibilities (responsibility_id, school_id, name, description, per_day_price, time_period, space_category, responsibility_field, space_id, work_level, work_amount, work_period, custom_dates, total_price)
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"
+    async fn get_student_orders(
+        &self,
+        school_id: &str,
+        student_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT *, amount::FLOAT8 AS amount_f FROM payment_orders
+             WHERE school_id = $1 AND student_id = $2 ORDER BY created_at DESC",
         )
-        .bind(&res_id)
         .bind(school_id)
-        .bind(name)
-        .bind(description)
-        .bind(bigdecimal::BigDecimal::from_f64(per_day_price).unwrap_or_default())
-        .bind(time_period)
-        .bind(space_category)
-        .bind(responsibility_field)
-        .bind(space_id)
-        .bind(work_level)
-        .bind(bigdecimal::BigDecimal::from_f64(work_amount).unwrap_or_default())
-        .bind(work_period)
-        .bind(if custom_dates.is_null() { json!([]) } else { custom_dates })
-        .bind(bigdecimal::BigDecimal::from_f64(total_price).unwrap_or_default())
+        .bind(student_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(payment_order_json).collect())
+    }
+
+    async fn claim_order(
+        &self,
+        order_id: &str,
+        gateway_payment_id: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let res = sqlx::query(
+            "UPDATE payment_orders SET status = 'processing', gateway_payment_id = $2
+             WHERE order_id = $1 AND status = 'created'",
+        )
+        .bind(order_id)
+        .bind(gateway_payment_id)
         .execute(&self.client.pool)
         .await?;
+        Ok(res.rows_affected() == 1)
+    }
 
-        if let Some(space_ids) = data["spaceIds"].as_array() {
-            for space_id_val in space_ids {
-                if let Some(sid) = space_id_val.as_str() {
-                    let _ = sqlx::query("INSERT INTO responsibility_spaces (responsibility_id, school_id, space_id) VALUES ($1, $2, $3)")
-                        .bind(&res_id).bind(school_id).bind(sid).execute(&self.client.pool).await;
-                }
-            }
-        }
+    async fn get_failed_orders(&self, school_id: &str) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT *, amount::FLOAT8 AS amount_f FROM payment_orders
+             WHERE school_id = $1 AND status = 'posting_failed' ORDER BY created_at",
+        )
+        .bind(school_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(payment_order_json).collect())
+    }
 
-        let mut ret = data.clone();
-        ret["responsibilityId"] = json!(res_id);
-        Ok(ret)
+    async fn resolve_failed_order(
+        &self,
+        order_id: &str,
+        status: &str,
+        error: Option<String>,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let res = sqlx::query(
+            "UPDATE payment_orders SET status = $2, error = $3
+             WHERE order_id = $1 AND status = 'posting_failed'",
+        )
+        .bind(order_id)
+        .bind(status)
+        .bind(error)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(res.rows_affected() == 1)
     }
 
-    async fn assign_responsibility(
+    async fn complete_order(
         &self,
-        school_id: &str,
-        employee_id: &str,
-        responsibility_id: &str,
-    ) -> Result<(), AppError> {
+        order_id: &str,
+        status: &str,
+        receipt: Option<Value>,
+        error: Option<String>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
         sqlx::query(
-            "INSERT INTO employee_responsibilities (school_id, employee_id, responsibility_id)
-             VALUES ($1, $2, $3) ON CONFLICT (school_id, employee_id, responsibility_id) DO NOTHING"
+            "UPDATE payment_orders SET status = $2, receipt = $3, error = $4,
+                paid_at = CASE WHEN $2 = 'paid' THEN NOW() ELSE paid_at END
+             WHERE order_id = $1",
         )
-        .bind(school_id)
-        .bind(employee_id)
-        .bind(responsibility_id)
+        .bind(order_id)
+        .bind(status)
+        .bind(receipt)
+        .bind(error)
         .execute(&self.client.pool)
         .await?;
         Ok(())
     }
+}
 
-    async fn remove_responsibility(
+// --- Reconciliation Repository ---
+pub struct PostgresReconciliationRepository {
+    pub client: Arc<DbClient>,
+}
+
+fn statement_line_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "lineId": r.get::<String, _>("line_id"),
+        "importId": r.get::<String, _>("import_id"),
+        "txnDate": r.get::<chrono::NaiveDate, _>("txn_date").to_string(),
+        "narration": r.get::<Option<String>, _>("narration"),
+        "reference": r.get::<Option<String>, _>("reference"),
+        "amount": r.get::<f64, _>("amount_f"),
+        "status": r.get::<String, _>("status"),
+        "matchMethod": r.get::<Option<String>, _>("match_method"),
+        "studentId": r.get::<Option<String>, _>("student_id"),
+        "candidates": r.get::<Option<Value>, _>("candidates").unwrap_or(json!([])),
+        "reason": r.get::<Option<String>, _>("reason"),
+        "receipt": r.get::<Option<Value>, _>("receipt"),
+        "resolvedBy": r.get::<Option<String>, _>("resolved_by"),
+        "resolvedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("resolved_at").map(|t| t.to_rfc3339())
+    })
+}
+
+#[async_trait]
+impl ReconciliationRepository for PostgresReconciliationRepository {
+    async fn create_import(
         &self,
         school_id: &str,
-        employee_id: &str,
-        responsibility_id: &str,
-    ) -> Result<(), AppError> {
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
         sqlx::query(
-            "DELETE FROM employee_responsibilities WHERE school_id = $1 AND employee_id = $2 AND responsibility_id = $3"
+            "INSERT INTO bank_statement_imports (import_id, school_id, file_name, bank_format, total_lines,
+                credit_lines, posted, review, duplicates, imported_by)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"
         )
+        .bind(data["importId"].as_str())
         .bind(school_id)
-        .bind(employee_id)
-        .bind(responsibility_id)
+        .bind(data["fileName"].as_str())
+        .bind(data["bankFormat"].as_str())
+        .bind(data["totalLines"].as_i64().unwrap_or(0) as i32)
+        .bind(data["creditLines"].as_i64().unwrap_or(0) as i32)
+        .bind(data["posted"].as_i64().unwrap_or(0) as i32)
+        .bind(data["review"].as_i64().unwrap_or(0) as i32)
+        .bind(data["duplicates"].as_i64().unwrap_or(0) as i32)
+        .bind(data["importedBy"].as_str())
         .execute(&self.client.pool)
         .await?;
-        Ok(())
+        Ok(data)
     }
 
-    async fn get_employee_responsibilities(
+    async fn get_imports(
         &self,
         school_id: &str,
-        employee_id: &str,
-    ) -> Result<Vec<Value>, AppError> {
-        let rows = sqlx::query(
-            "SELECT r.* FROM responsibilities r
-             JOIN employee_responsibilities er ON r.responsibility_id = er.responsibility_id AND r.school_id = er.school_id
-             WHERE er.school_id = $1 AND er.employee_id = $2"
-        )
-        .bind(school_id)
-        .bind(employee_id)
-        .fetch_all(&self.client.pool)
-        .await?;
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query("SELECT * FROM bank_statement_imports WHERE school_id = $1 ORDER BY created_at DESC")
+            .bind(school_id)
+            .fetch_all(&self.client.pool)
+            .await?;
         Ok(rows
             .into_iter()
             .map(|r| json!({
-                "responsibilityId": r.get::<String, _>("responsibility_id"),
-                "name": r.get::<String, _>("name"),
-                "description": r.get::<Option<String>, _>("description"),
-                "perDayPrice": r.get::<bigdecimal::BigDecimal, _>("per_day_price").to_f64().unwrap_or(0.0),
-                "timePeriod": r.get::<i32, _>("time_period"),
-                "spaceCategory": r.get::<Option<String>, _>("space_category").unwrap_or_default(),
-                "responsibilityField": r.get::<Option<String>, _>("responsibility_field").unwrap_or_default(),
-                "spaceId": r.get::<Option<String>, _>("space_id").unwrap_or_default(),
-                "workLevel": r.get::<Option<String>, _>("work_level").unwrap_or_default(),
-                "workAmount": r.try_get::<bigdecimal::BigDecimal, _>("work_amount").unwrap_or_default().to_f64().unwrap_or(0.0),
-                "workPeriod": r.get::<Option<String>, _>("work_period").unwrap_or_default(),
-                "customDates": r.get::<Option<Value>, _>("custom_dates").unwrap_or(json!([])),
-                "totalPrice": r.try_get::<bigdecimal::BigDecimal, _>("total_price").unwrap_or_default().to_f64().unwrap_or(0.0),
+                "importId": r.get::<String, _>("import_id"),
+                "fileName": r.get::<Option<String>, _>("file_name"),
+                "bankFormat": r.get::<Option<String>, _>("bank_format"),
+                "totalLines": r.get::<Option<i32>, _>("total_lines"),
+                "creditLines": r.get::<Option<i32>, _>("credit_lines"),
+                "posted": r.get::<Option<i32>, _>("posted"),
+                "review": r.get::<Option<i32>, _>("review"),
+                "duplicates": r.get::<Option<i32>, _>("duplicates"),
+                "importedBy": r.get::<Option<String>, _>("imported_by"),
                 "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339()
             }))
             .collect())
     }
-}
-
-
-// --- Task Repository ---
-pub struct PostgresTaskRepository {
-    pub client: Arc<DbClient>,
-}
 
-#[async_trait]
-impl TaskRepository for PostgresTaskRepository {
-    async fn get_tasks(&self, school_id: &str) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
-        let rows =
-            sqlx::query("SELECT * FROM audit_logs WHERE school_id = $1 AND target_type = 'task'")
-                .bind(school_id)
-                .fetch_all(&self.client.pool)
-                .await?;
-        Ok(rows
-            .into_iter()
-            .map(|r| json!({"id": r.get::<i32, _>("id"), "data": r.get::<Value, _>("data")}))
-            .collect())
+    async fn statement_line_exists(
+        &self,
+        school_id: &str,
+        line_hash: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query("SELECT 1 FROM bank_statement_lines WHERE school_id = $1 AND line_hash = $2")
+            .bind(school_id)
+            .bind(line_hash)
+            .fetch_optional(&self.client.pool)
+            .await?;
+        Ok(row.is_some())
     }
-}
-
-pub struct PostgresLeaveRepository {
-    pub client: Arc<crate::db::DbClient>,
-}
 
-#[async_trait]
-impl LeaveRepository for PostgresLeaveRepository {
-    async fn add_leave(
+    async fn add_statement_line(
         &self,
         school_id: &str,
         data: Value,
-    ) -> Result<Value, AppError> {
-        let leave_id = format!("LV{}", chrono::Utc::now().timestamp_millis());
-        let employee_id = data["employeeId"].as_str().ok_or("Employee ID is required")?;
-        let employee_name = data["employeeName"].as_str().unwrap_or("");
-        let reason = data["reason"].as_str().unwrap_or("");
-        let leave_type = data["leaveType"].as_str().unwrap_or("casual");
-        let from_date = chrono::NaiveDate::parse_from_str(data["fromDate"].as_str().unwrap_or(""), "%Y-%m-%d").unwrap_or_default();
-        let to_date = chrono::NaiveDate::parse_from_str(data["toDate"].as_str().unwrap_or(""), "%Y-%m-%d").unwrap_or_default();
-
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let txn_date = data["txnDate"]
+            .as_str()
+            .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            .ok_or("Invalid transaction date")?;
         sqlx::query(
-            "INSERT INTO leave_applications (
-                leave_id, school_id, employee_id, employee_name, reason, leave_type, from_date, to_date
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            "INSERT INTO bank_statement_lines (line_id, import_id, school_id, txn_date, narration, reference,
+                amount, line_hash, status, match_method, student_id, candidates, reason)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"
         )
-        .bind(&leave_id)
+        .bind(data["lineId"].as_str())
+        .bind(data["importId"].as_str())
         .bind(school_id)
-        .bind(employee_id)
-        .bind(employee_name)
-        .bind(reason)
-        .bind(leave_type)
-        .bind(from_date)
-        .bind(to_date)
+        .bind(txn_date)
+        .bind(data["narration"].as_str())
+        .bind(data["reference"].as_str())
+        .bind(data["amount"].as_f64().unwrap_or(0.0))
+        .bind(data["lineHash"].as_str())
+        .bind(data["status"].as_str().unwrap_or("review"))
+        .bind(data["matchMethod"].as_str())
+        .bind(data["studentId"].as_str())
+        .bind(data.get("candidates").cloned().unwrap_or(json!([])))
+        .bind(data["reason"].as_str())
         .execute(&self.client.pool)
         .await?;
-
-        let mut res = data.clone();
-        res["leaveId"] = json!(leave_id);
-        Ok(res)
+        Ok(data)
     }
 
-    async fn get_leaves(
+    async fn get_statement_lines(
         &self,
         school_id: &str,
-    ) -> Result<Vec<Value>, AppError> {
+        import_id: Option<String>,
+        status: Option<String>,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
         let rows = sqlx::query(
-            "SELECT leave_id, employee_id, employee_name, reason, leave_type, from_date, to_date, status
-             FROM leave_applications WHERE school_id = $1 ORDER BY created_at DESC",
+            "SELECT *, amount::FLOAT8 AS amount_f FROM bank_statement_lines
+             WHERE school_id = $1 AND ($2::TEXT IS NULL OR import_id = $2) AND ($3::TEXT IS NULL OR status = $3)
+             ORDER BY txn_date, id"
         )
         .bind(school_id)
+        .bind(import_id)
+        .bind(status)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(statement_line_json).collect())
+    }
+
+    async fn get_statement_line(
+        &self,
+        school_id: &str,
+        line_id: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query(
+            "SELECT *, amount::FLOAT8 AS amount_f FROM bank_statement_lines WHERE school_id = $1 AND line_id = $2",
+        )
+        .bind(school_id)
+        .bind(line_id)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(statement_line_json))
+    }
+
+    async fn update_statement_line(
+        &self,
+        school_id: &str,
+        line_id: &str,
+        data: Value,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let result = sqlx::query(
+            "UPDATE bank_statement_lines SET
+                status = COALESCE($3, status),
+                match_method = COALESCE($4, match_method),
+                student_id = COALESCE($5, student_id),
+                reason = $6,
+                receipt = COALESCE($7, receipt),
+                resolved_by = COALESCE($8, resolved_by),
+                resolved_at = CASE WHEN $8::TEXT IS NOT NULL THEN NOW() ELSE resolved_at END
+             WHERE school_id = $1 AND line_id = $2 AND status = 'review'"
+        )
+        .bind(school_id)
+        .bind(line_id)
+        .bind(data["status"].as_str())
+        .bind(data["matchMethod"].as_str())
+        .bind(data["studentId"].as_str())
+        .bind(data["reason"].as_str())
+        .bind(data.get("receipt").filter(|r| !r.is_null()).cloned())
+        .bind(data["resolvedBy"].as_str())
+        .execute(&self.client.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err("Statement line is no longer in review".into());
+        }
+        Ok(())
+    }
+
+    async fn post_statement_line(
+        &self,
+        school_id: &str,
+        line_id: &str,
+        items: &[Value],
+        data: &Value,
+        details: &Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut tx = self.client.pool.begin().await?;
+        // Claim first: a concurrent resolver waits on the row lock and then finds it gone from review
+        let claimed = sqlx::query(
+            "UPDATE bank_statement_lines SET status = 'posting'
+             WHERE school_id = $1 AND line_id = $2 AND status = 'review'
+             RETURNING amount::FLOAT8 AS amount_f, txn_date"
+        )
+        .bind(school_id)
+        .bind(line_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or("Statement line is no longer in review")?;
+        let student_id = data["studentId"].as_str().ok_or("studentId is required to post a line")?;
+
+        let posted = book_fee_payments(&mut tx, school_id, student_id, items, details).await?;
+        let receipt = json!({
+            "receiptNo": posted["receiptNo"],
+            "studentId": student_id,
+            "amount": claimed.get::<f64, _>("amount_f"),
+            "paidOn": claimed.get::<chrono::NaiveDate, _>("txn_date").to_string(),
+            "lines": posted["lines"]
+        });
+        sqlx::query(
+            "UPDATE bank_statement_lines SET
+                status = 'posted',
+                match_method = COALESCE($3, match_method),
+                student_id = $4,
+                reason = NULL,
+                receipt = $5,
+                resolved_by = $6,
+                resolved_at = NOW()
+             WHERE school_id = $1 AND line_id = $2"
+        )
+        .bind(school_id)
+        .bind(line_id)
+        .bind(data["matchMethod"].as_str())
+        .bind(student_id)
+        .bind(&receipt)
+        .bind(data["resolvedBy"].as_str())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(receipt)
+    }
+
+    async fn get_pending_balances(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT student_id, pending_amount::FLOAT8 AS pending_amount FROM student_fees
+             WHERE school_id = $1 AND pending_amount > 0"
+        )
+        .bind(school_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| json!({
+                "studentId": r.get::<String, _>("student_id"),
+                "pendingAmount": r.get::<f64, _>("pending_amount")
+            }))
+            .collect())
+    }
+}
+
+// --- Payroll Repository ---
+/// Books an advance transaction on `conn`; see `PayrollRepository::apply_advance_transaction`.
+async fn book_advance_transaction(
+    conn: &mut sqlx::PgConnection,
+    school_id: &str,
+    advance_id: &str,
+    data: &Value,
+) -> Result<Value, AppError> {
+    let kind = data["kind"].as_str().unwrap_or("recovery");
+    let principal_part = data["principalPart"].as_f64().unwrap_or(0.0);
+    let settled_status = if kind == "waiver" { "waived" } else { "closed" };
+    // Payroll recovers only active advances; prepayments and waivers also apply to paused ones
+    let row = sqlx::query(&format!(
+        "UPDATE employee_advances SET
+            principal_outstanding = GREATEST(principal_outstanding - $3::FLOAT::DECIMAL, 0),
+            status = CASE WHEN principal_outstanding - $3::FLOAT::DECIMAL <= 0.005 THEN $4 ELSE status END,
+            updated_at = NOW()
+         WHERE school_id = $1 AND advance_id = $2
+           AND (status = 'active' OR (status = 'paused' AND $5 <> 'recovery'))
+           AND principal_outstanding >= $3::FLOAT::DECIMAL - 0.005
+         RETURNING {}",
+        ADVANCE_COLUMNS
+    ))
+    .bind(school_id)
+    .bind(advance_id)
+    .bind(principal_part)
+    .bind(settled_status)
+    .bind(kind)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or("Advance is not open or the amount exceeds the outstanding balance")?;
+    sqlx::query(
+        "INSERT INTO employee_advance_transactions (advance_id, school_id, employee_id, kind, amount,
+            principal_part, interest_part, run_id, month, year, note, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"
+    )
+    .bind(advance_id)
+    .bind(school_id)
+    .bind(row.get::<String, _>("employee_id"))
+    .bind(kind)
+    .bind(data["amount"].as_f64().unwrap_or(0.0))
+    .bind(principal_part)
+    .bind(data["interestPart"].as_f64().unwrap_or(0.0))
+    .bind(data["runId"].as_str())
+    .bind(data["month"].as_i64().map(|m| m as i32))
+    .bind(data["year"].as_i64().map(|y| y as i32))
+    .bind(data["note"].as_str())
+    .bind(data["by"].as_str())
+    .execute(&mut *conn)
+    .await?;
+    Ok(advance_json(&row))
+}
+
+pub struct PostgresPayrollRepository {
+    pub client: Arc<DbClient>,
+}
+
+fn payroll_run_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "runId": r.get::<String, _>("run_id"),
+        "month": r.get::<i32, _>("month"),
+        "year": r.get::<i32, _>("year"),
+        "status": r.get::<String, _>("status"),
+        "totals": r.get::<Value, _>("totals"),
+        "createdBy": r.get::<Option<String>, _>("created_by"),
+        "approvedBy": r.get::<Option<String>, _>("approved_by"),
+        "approvedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("approved_at").map(|t| t.to_rfc3339()),
+        "reopenedBy": r.get::<Option<String>, _>("reopened_by"),
+        "reopenedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("reopened_at").map(|t| t.to_rfc3339()),
+        "reopenReason": r.get::<Option<String>, _>("reopen_reason"),
+        "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339(),
+        "updatedAt": r.get::<chrono::DateTime<chrono::Utc>, _>("updated_at").to_rfc3339()
+    })
+}
+
+const ADVANCE_COLUMNS: &str = "advance_id, employee_id, kind, principal::FLOAT AS principal,
+    interest_rate::FLOAT AS interest_rate, tenure_months, emi_amount::FLOAT AS emi_amount,
+    monthly_cap::FLOAT AS monthly_cap, start_month, start_year,
+    principal_outstanding::FLOAT AS principal_outstanding, status, reason, created_by, created_at, updated_at";
+
+fn advance_json(r: &sqlx::postgres::PgRow) -> Value {
+    let f = |col: &str| r.get::<Option<f64>, _>(col);
+    json!({
+        "advanceId": r.get::<String, _>("advance_id"),
+        "employeeId": r.get::<String, _>("employee_id"),
+        "kind": r.get::<String, _>("kind"),
+        "principal": f("principal"),
+        "interestRate": f("interest_rate"),
+        "tenureMonths": r.get::<i32, _>("tenure_months"),
+        "emiAmount": f("emi_amount"),
+        "monthlyCap": f("monthly_cap"),
+        "startMonth": r.get::<i32, _>("start_month"),
+        "startYear": r.get::<i32, _>("start_year"),
+        "principalOutstanding": f("principal_outstanding"),
+        "status": r.get::<String, _>("status"),
+        "reason": r.get::<Option<String>, _>("reason"),
+        "createdBy": r.get::<Option<String>, _>("created_by"),
+        "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339(),
+        "updatedAt": r.get::<chrono::DateTime<chrono::Utc>, _>("updated_at").to_rfc3339()
+    })
+}
+
+fn salary_revision_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "revisionId": r.get::<String, _>("revision_id"),
+        "employeeId": r.get::<String, _>("employee_id"),
+        "effectiveMonth": r.get::<i32, _>("effective_month"),
+        "effectiveYear": r.get::<i32, _>("effective_year"),
+        "params": r.get::<Value, _>("params"),
+        "reason": r.get::<Option<String>, _>("reason"),
+        "createdBy": r.get::<Option<String>, _>("created_by"),
+        "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339()
+    })
+}
+
+const TRANSFER_BATCH_COLUMNS: &str = "batch_id, run_id, bank_format, debit_account, debit_ifsc, payment_date,
+    narration, status, line_count, total_amount::FLOAT AS total_amount, created_by, response_imported_at, created_at";
+
+fn transfer_batch_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "batchId": r.get::<String, _>("batch_id"),
+        "runId": r.get::<String, _>("run_id"),
+        "bankFormat": r.get::<String, _>("bank_format"),
+        "debitAccount": r.get::<String, _>("debit_account"),
+        "debitIfsc": r.get::<Option<String>, _>("debit_ifsc"),
+        "paymentDate": r.get::<chrono::NaiveDate, _>("payment_date").to_string(),
+        "narration": r.get::<Option<String>, _>("narration"),
+        "status": r.get::<String, _>("status"),
+        "lineCount": r.get::<i32, _>("line_count"),
+        "totalAmount": r.get::<f64, _>("total_amount"),
+        "createdBy": r.get::<Option<String>, _>("created_by"),
+        "responseImportedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("response_imported_at").map(|t| t.to_rfc3339()),
+        "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339()
+    })
+}
+
+#[async_trait]
+impl PayrollRepository for PostgresPayrollRepository {
+    async fn create_run(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let run_id = format!("PR{}", chrono::Utc::now().timestamp_millis());
+        let row = sqlx::query(
+            "INSERT INTO payroll_runs (run_id, school_id, month, year, created_by)
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING *"
+        )
+        .bind(&run_id)
+        .bind(school_id)
+        .bind(data["month"].as_i64().unwrap_or(0) as i32)
+        .bind(data["year"].as_i64().unwrap_or(0) as i32)
+        .bind(data["createdBy"].as_str())
+        .fetch_one(&self.client.pool)
+        .await?;
+        Ok(payroll_run_json(&row))
+    }
+
+    async fn get_runs(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query("SELECT * FROM payroll_runs WHERE school_id = $1 ORDER BY year DESC, month DESC")
+            .bind(school_id)
+            .fetch_all(&self.client.pool)
+            .await?;
+        Ok(rows.iter().map(payroll_run_json).collect())
+    }
+
+    async fn get_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query("SELECT * FROM payroll_runs WHERE school_id = $1 AND run_id = $2")
+            .bind(school_id)
+            .bind(run_id)
+            .fetch_optional(&self.client.pool)
+            .await?;
+        Ok(row.as_ref().map(payroll_run_json))
+    }
+
+    async fn get_run_for_month(
+        &self,
+        school_id: &str,
+        month: i32,
+        year: i32,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query("SELECT * FROM payroll_runs WHERE school_id = $1 AND month = $2 AND year = $3")
+            .bind(school_id)
+            .bind(month)
+            .bind(year)
+            .fetch_optional(&self.client.pool)
+            .await?;
+        Ok(row.as_ref().map(payroll_run_json))
+    }
+
+    async fn save_run_lines(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        lines: Vec<Value>,
+        totals: Value,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        sqlx::query("DELETE FROM payroll_run_lines WHERE school_id = $1 AND run_id = $2")
+            .bind(school_id)
+            .bind(run_id)
+            .execute(&self.client.pool)
+            .await?;
+        for line in &lines {
+            let b = &line["breakdown"];
+            sqlx::query(
+                "INSERT INTO payroll_run_lines (run_id, school_id, employee_id, employee_name, breakdown, gross_salary, deductions, net_pay)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+            )
+            .bind(run_id)
+            .bind(school_id)
+            .bind(line["employeeId"].as_str())
+            .bind(line["employeeName"].as_str())
+            .bind(b)
+            .bind(b["grossSalary"].as_f64().unwrap_or(0.0))
+            .bind(b["deductions"].as_f64().unwrap_or(0.0))
+            .bind(b["netMonthlySalary"].as_f64().unwrap_or(0.0))
+            .execute(&self.client.pool)
+            .await?;
+        }
+        sqlx::query("UPDATE payroll_runs SET totals = $3, updated_at = NOW() WHERE school_id = $1 AND run_id = $2")
+            .bind(school_id)
+            .bind(run_id)
+            .bind(&totals)
+            .execute(&self.client.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_run_lines(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT employee_id, employee_name, breakdown FROM payroll_run_lines
+             WHERE school_id = $1 AND run_id = $2 ORDER BY employee_name, employee_id"
+        )
+        .bind(school_id)
+        .bind(run_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| json!({
+                "employeeId": r.get::<String, _>("employee_id"),
+                "employeeName": r.get::<Option<String>, _>("employee_name"),
+                "breakdown": r.get::<Value, _>("breakdown")
+            }))
+            .collect())
+    }
+
+    async fn transition_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        from_status: &str,
+        to_status: &str,
+        data: Value,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let result = sqlx::query(
+            "UPDATE payroll_runs SET status = $4,
+                approved_by = CASE WHEN $4 = 'approved' THEN $5 ELSE approved_by END,
+                approved_at = CASE WHEN $4 = 'approved' THEN NOW() ELSE approved_at END,
+                reopened_by = CASE WHEN $4 = 'reopened' THEN $5 ELSE reopened_by END,
+                reopened_at = CASE WHEN $4 = 'reopened' THEN NOW() ELSE reopened_at END,
+                reopen_reason = CASE WHEN $4 = 'reopened' THEN $6 ELSE reopen_reason END,
+                updated_at = NOW()
+             WHERE school_id = $1 AND run_id = $2 AND status = $3"
+        )
+        .bind(school_id)
+        .bind(run_id)
+        .bind(from_status)
+        .bind(to_status)
+        .bind(data["by"].as_str())
+        .bind(data["reason"].as_str())
+        .execute(&self.client.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn approve_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        from_status: &str,
+        approved_by: &str,
+        recoveries: &[Value],
+        history: &[Value],
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let mut tx = self.client.pool.begin().await?;
+        let result = sqlx::query(
+            "UPDATE payroll_runs SET status = 'approved', approved_by = $4, approved_at = NOW(), updated_at = NOW()
+             WHERE school_id = $1 AND run_id = $2 AND status = $3"
+        )
+        .bind(school_id)
+        .bind(run_id)
+        .bind(from_status)
+        .bind(approved_by)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        for rec in recoveries {
+            book_advance_transaction(&mut tx, school_id, rec["advanceId"].as_str().unwrap_or(""), rec).await?;
+        }
+        for entry in history {
+            sqlx::query("INSERT INTO audit_logs (school_id, target_type, target_id, action, data) VALUES ($1, 'payroll_log', $2, 'payroll_approved', $3)")
+                .bind(school_id)
+                .bind(entry["employeeId"].as_str())
+                .bind(&entry["data"])
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn is_month_locked(
+        &self,
+        school_id: &str,
+        month: i32,
+        year: i32,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query(
+            "SELECT 1 FROM payroll_runs WHERE school_id = $1 AND month = $2 AND year = $3 AND status = 'approved'"
+        )
+        .bind(school_id)
+        .bind(month)
+        .bind(year)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.is_some())
+    }
+
+    async fn get_employee_absences(
+        &self,
+        school_id: &str,
+        month: i32,
+        year: i32,
+        unpaid_leave_types: &[String],
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT user_id, COUNT(*) AS days FROM attendance
+             WHERE school_id = $1 AND role = 'employee'
+               AND (status = 'absent' OR (status = 'leave' AND reason = ANY($4)))
+               AND EXTRACT(MONTH FROM date) = $2 AND EXTRACT(YEAR FROM date) = $3
+             GROUP BY user_id"
+        )
+        .bind(school_id)
+        .bind(month)
+        .bind(year)
+        .bind(unpaid_leave_types)
+        .fetch_all(&self.client.pool)
+        .await?;
+        let mut out = serde_json::Map::new();
+        for r in rows {
+            out.insert(r.get::<String, _>("user_id"), json!(r.get::<i64, _>("days")));
+        }
+        Ok(Value::Object(out))
+    }
+
+    async fn get_run_line(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        employee_id: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query(
+            "SELECT employee_id, employee_name, breakdown FROM payroll_run_lines
+             WHERE school_id = $1 AND run_id = $2 AND employee_id = $3"
+        )
+        .bind(school_id)
+        .bind(run_id)
+        .bind(employee_id)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.map(|r| json!({
+            "employeeId": r.get::<String, _>("employee_id"),
+            "employeeName": r.get::<Option<String>, _>("employee_name"),
+            "breakdown": r.get::<Value, _>("breakdown")
+        })))
+    }
+
+    async fn get_employee_run_lines(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        approved_only: bool,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT r.run_id, r.month, r.year, r.status, r.approved_at,
+                    l.gross_salary::FLOAT AS gross, l.deductions::FLOAT AS deductions, l.net_pay::FLOAT AS net
+             FROM payroll_run_lines l
+             JOIN payroll_runs r ON r.school_id = l.school_id AND r.run_id = l.run_id
+             WHERE l.school_id = $1 AND l.employee_id = $2 AND (NOT $3 OR r.status = 'approved')
+             ORDER BY r.year DESC, r.month DESC"
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(approved_only)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| json!({
+                "runId": r.get::<String, _>("run_id"),
+                "month": r.get::<i32, _>("month"),
+                "year": r.get::<i32, _>("year"),
+                "status": r.get::<String, _>("status"),
+                "approvedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("approved_at").map(|t| t.to_rfc3339()),
+                "grossSalary": r.get::<f64, _>("gross"),
+                "deductions": r.get::<f64, _>("deductions"),
+                "netPay": r.get::<f64, _>("net")
+            }))
+            .collect())
+    }
+
+    async fn get_statutory_settings(
+        &self,
+        school_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query("SELECT settings FROM payroll_statutory_settings WHERE school_id = $1")
+            .bind(school_id)
+            .fetch_optional(&self.client.pool)
+            .await?;
+        Ok(row.map(|r| r.get::<Value, _>("settings")).unwrap_or(Value::Null))
+    }
+
+    async fn save_statutory_settings(
+        &self,
+        school_id: &str,
+        settings: Value,
+        updated_by: Option<String>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        sqlx::query(
+            "INSERT INTO payroll_statutory_settings (school_id, settings, updated_by)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (school_id) DO UPDATE SET settings = $2, updated_by = $3, updated_at = NOW()"
+        )
+        .bind(school_id)
+        .bind(&settings)
+        .bind(updated_by)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_approved_lines_between(
+        &self,
+        school_id: &str,
+        employee_id: Option<&str>,
+        from_key: i32,
+        to_key: i32,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT r.run_id, r.month, r.year, l.employee_id, l.employee_name, l.breakdown
+             FROM payroll_run_lines l
+             JOIN payroll_runs r ON r.school_id = l.school_id AND r.run_id = l.run_id
+             WHERE l.school_id = $1 AND r.status = 'approved'
+               AND ($2::VARCHAR IS NULL OR l.employee_id = $2)
+               AND r.year * 12 + r.month BETWEEN $3 AND $4
+             ORDER BY r.year, r.month, l.employee_name"
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(from_key)
+        .bind(to_key)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| json!({
+                "runId": r.get::<String, _>("run_id"),
+                "month": r.get::<i32, _>("month"),
+                "year": r.get::<i32, _>("year"),
+                "employeeId": r.get::<String, _>("employee_id"),
+                "employeeName": r.get::<Option<String>, _>("employee_name"),
+                "breakdown": r.get::<Value, _>("breakdown")
+            }))
+            .collect())
+    }
+
+    async fn create_advance(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let advance_id = format!("ADV{}", chrono::Utc::now().timestamp_millis());
+        let row = sqlx::query(&format!(
+            "INSERT INTO employee_advances (advance_id, school_id, employee_id, kind, principal, interest_rate,
+                tenure_months, emi_amount, monthly_cap, start_month, start_year, principal_outstanding, reason, created_by)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $5, $12, $13)
+             RETURNING {}",
+            ADVANCE_COLUMNS
+        ))
+        .bind(&advance_id)
+        .bind(school_id)
+        .bind(data["employeeId"].as_str())
+        .bind(data["kind"].as_str().unwrap_or("advance"))
+        .bind(data["principal"].as_f64().unwrap_or(0.0))
+        .bind(data["interestRate"].as_f64().unwrap_or(0.0))
+        .bind(data["tenureMonths"].as_i64().unwrap_or(1) as i32)
+        .bind(data["emiAmount"].as_f64().unwrap_or(0.0))
+        .bind(data["monthlyCap"].as_f64())
+        .bind(data["startMonth"].as_i64().unwrap_or(1) as i32)
+        .bind(data["startYear"].as_i64().unwrap_or(0) as i32)
+        .bind(data["reason"].as_str())
+        .bind(data["createdBy"].as_str())
+        .fetch_one(&self.client.pool)
+        .await?;
+        sqlx::query(
+            "INSERT INTO employee_advance_transactions (advance_id, school_id, employee_id, kind, amount, principal_part, note, created_by)
+             VALUES ($1, $2, $3, 'disbursal', $4, $4, $5, $6)"
+        )
+        .bind(&advance_id)
+        .bind(school_id)
+        .bind(data["employeeId"].as_str())
+        .bind(data["principal"].as_f64().unwrap_or(0.0))
+        .bind(data["reason"].as_str())
+        .bind(data["createdBy"].as_str())
+        .execute(&self.client.pool)
+        .await?;
+        Ok(advance_json(&row))
+    }
+
+    async fn get_advances(
+        &self,
+        school_id: &str,
+        employee_id: Option<&str>,
+        status: Option<&str>,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM employee_advances
+             WHERE school_id = $1 AND ($2::VARCHAR IS NULL OR employee_id = $2) AND ($3::VARCHAR IS NULL OR status = $3)
+             ORDER BY start_year, start_month, created_at",
+            ADVANCE_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(status)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(advance_json).collect())
+    }
+
+    async fn get_advance(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query(&format!("SELECT {} FROM employee_advances WHERE school_id = $1 AND advance_id = $2", ADVANCE_COLUMNS))
+            .bind(school_id)
+            .bind(advance_id)
+            .fetch_optional(&self.client.pool)
+            .await?;
+        Ok(row.as_ref().map(advance_json))
+    }
+
+    async fn set_advance_status(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+        from_status: &str,
+        to_status: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let result = sqlx::query(
+            "UPDATE employee_advances SET status = $4, updated_at = NOW()
+             WHERE school_id = $1 AND advance_id = $2 AND status = $3"
+        )
+        .bind(school_id)
+        .bind(advance_id)
+        .bind(from_status)
+        .bind(to_status)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn apply_advance_transaction(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut tx = self.client.pool.begin().await?;
+        let advance = book_advance_transaction(&mut tx, school_id, advance_id, &data).await?;
+        tx.commit().await?;
+        Ok(advance)
+    }
+
+    async fn reverse_run_recoveries(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        sqlx::query(
+            "UPDATE employee_advances a SET
+                principal_outstanding = a.principal_outstanding + t.principal,
+                status = CASE WHEN a.status = 'closed' THEN 'active' ELSE a.status END,
+                updated_at = NOW()
+             FROM (SELECT advance_id, SUM(principal_part) AS principal FROM employee_advance_transactions
+                   WHERE school_id = $1 AND run_id = $2 AND kind = 'recovery' GROUP BY advance_id) t
+             WHERE a.school_id = $1 AND a.advance_id = t.advance_id"
+        )
+        .bind(school_id)
+        .bind(run_id)
+        .execute(&self.client.pool)
+        .await?;
+        sqlx::query("DELETE FROM employee_advance_transactions WHERE school_id = $1 AND run_id = $2 AND kind = 'recovery'")
+            .bind(school_id)
+            .bind(run_id)
+            .execute(&self.client.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_advance_transactions(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT kind, amount::FLOAT AS amount, principal_part::FLOAT AS principal_part,
+                    interest_part::FLOAT AS interest_part, run_id, month, year, note, created_by, created_at
+             FROM employee_advance_transactions WHERE school_id = $1 AND advance_id = $2 ORDER BY created_at, id"
+        )
+        .bind(school_id)
+        .bind(advance_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| json!({
+                "kind": r.get::<String, _>("kind"),
+                "amount": r.get::<f64, _>("amount"),
+                "principalPart": r.get::<f64, _>("principal_part"),
+                "interestPart": r.get::<f64, _>("interest_part"),
+                "runId": r.get::<Option<String>, _>("run_id"),
+                "month": r.get::<Option<i32>, _>("month"),
+                "year": r.get::<Option<i32>, _>("year"),
+                "note": r.get::<Option<String>, _>("note"),
+                "createdBy": r.get::<Option<String>, _>("created_by"),
+                "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339()
+            }))
+            .collect())
+    }
+
+    async fn create_salary_revision(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let revision_id = format!("SRV{}", chrono::Utc::now().timestamp_millis());
+        let row = sqlx::query(
+            "INSERT INTO salary_revisions (revision_id, school_id, employee_id, effective_month, effective_year, params, reason, created_by)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+             RETURNING *"
+        )
+        .bind(&revision_id)
+        .bind(school_id)
+        .bind(data["employeeId"].as_str())
+        .bind(data["effectiveMonth"].as_i64().unwrap_or(1) as i32)
+        .bind(data["effectiveYear"].as_i64().unwrap_or(0) as i32)
+        .bind(&data["params"])
+        .bind(data["reason"].as_str())
+        .bind(data["createdBy"].as_str())
+        .fetch_one(&self.client.pool)
+        .await?;
+        Ok(salary_revision_json(&row))
+    }
+
+    async fn get_salary_revisions(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT * FROM salary_revisions WHERE school_id = $1 AND employee_id = $2
+             ORDER BY effective_year, effective_month, created_at"
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(salary_revision_json).collect())
+    }
+
+    async fn set_employee_bank_account(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        account: Value,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let result = sqlx::query(
+            "UPDATE employees SET data = jsonb_set(data, '{bankAccount}', $3), updated_at = NOW()
+             WHERE school_id = $1 AND employee_id = $2"
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(&account)
+        .execute(&self.client.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err("Employee not found".into());
+        }
+        Ok(())
+    }
+
+    async fn create_transfer_batch(
+        &self,
+        school_id: &str,
+        batch: Value,
+        lines: Vec<Value>,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let batch_id = format!("STB{}", chrono::Utc::now().timestamp_millis());
+        let total: f64 = lines.iter().map(|l| l["amount"].as_f64().unwrap_or(0.0)).sum();
+        let payment_date = chrono::NaiveDate::parse_from_str(batch["paymentDate"].as_str().unwrap_or(""), "%Y-%m-%d")?;
+        let mut tx = self.client.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO salary_transfer_batches (batch_id, school_id, run_id, bank_format, debit_account, debit_ifsc,
+                payment_date, narration, line_count, total_amount, created_by)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10::FLOAT::DECIMAL, $11)"
+        )
+        .bind(&batch_id)
+        .bind(school_id)
+        .bind(batch["runId"].as_str())
+        .bind(batch["bankFormat"].as_str())
+        .bind(batch["debitAccount"].as_str())
+        .bind(batch["debitIfsc"].as_str())
+        .bind(payment_date)
+        .bind(batch["narration"].as_str())
+        .bind(lines.len() as i32)
+        .bind(total)
+        .bind(batch["createdBy"].as_str())
+        .execute(&mut *tx)
+        .await?;
+        // A concurrent batch for the same run may have taken an employee since the caller checked
+        for l in &lines {
+            let inserted = sqlx::query(
+                "INSERT INTO salary_transfer_lines (batch_id, school_id, run_id, employee_id, employee_name,
+                    account_number, ifsc, bank_name, amount, mode, reference)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9::FLOAT::DECIMAL, $10, $11)
+                 ON CONFLICT (school_id, run_id, employee_id) WHERE status IN ('pending', 'paid') DO NOTHING"
+            )
+            .bind(&batch_id)
+            .bind(school_id)
+            .bind(batch["runId"].as_str())
+            .bind(l["employeeId"].as_str())
+            .bind(l["employeeName"].as_str())
+            .bind(l["accountNumber"].as_str())
+            .bind(l["ifsc"].as_str())
+            .bind(l["bankName"].as_str())
+            .bind(l["amount"].as_f64().unwrap_or(0.0))
+            .bind(l["mode"].as_str())
+            .bind(l["reference"].as_str())
+            .execute(&mut *tx)
+            .await?;
+            if inserted.rows_affected() == 0 {
+                return Err(format!(
+                    "{} is already in another transfer batch for this run",
+                    l["employeeName"].as_str().or(l["employeeId"].as_str()).unwrap_or("An employee")
+                )
+                .into());
+            }
+        }
+        tx.commit().await?;
+        self.get_transfer_batch(school_id, &batch_id)
+            .await?
+            .ok_or_else(|| "Transfer batch was not saved".into())
+    }
+
+    async fn get_transfer_batches(
+        &self,
+        school_id: &str,
+        run_id: Option<&str>,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM salary_transfer_batches
+             WHERE school_id = $1 AND ($2::VARCHAR IS NULL OR run_id = $2) ORDER BY created_at DESC",
+            TRANSFER_BATCH_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(run_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(transfer_batch_json).collect())
+    }
+
+    async fn get_transfer_batch(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM salary_transfer_batches WHERE school_id = $1 AND batch_id = $2",
+            TRANSFER_BATCH_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(batch_id)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(transfer_batch_json))
+    }
+
+    async fn get_transfer_lines(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT employee_id, employee_name, account_number, ifsc, bank_name, amount::FLOAT AS amount,
+                    mode, reference, status, utr, failure_reason, updated_at
+             FROM salary_transfer_lines WHERE school_id = $1 AND batch_id = $2 ORDER BY employee_name, employee_id"
+        )
+        .bind(school_id)
+        .bind(batch_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| json!({
+                "employeeId": r.get::<String, _>("employee_id"),
+                "employeeName": r.get::<Option<String>, _>("employee_name"),
+                "accountNumber": r.get::<String, _>("account_number"),
+                "ifsc": r.get::<String, _>("ifsc"),
+                "bankName": r.get::<Option<String>, _>("bank_name"),
+                "amount": r.get::<f64, _>("amount"),
+                "mode": r.get::<String, _>("mode"),
+                "reference": r.get::<String, _>("reference"),
+                "status": r.get::<String, _>("status"),
+                "utr": r.get::<Option<String>, _>("utr"),
+                "failureReason": r.get::<Option<String>, _>("failure_reason"),
+                "updatedAt": r.get::<chrono::DateTime<chrono::Utc>, _>("updated_at").to_rfc3339()
+            }))
+            .collect())
+    }
+
+    async fn get_run_transferred_employees(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT employee_id FROM salary_transfer_lines
+             WHERE school_id = $1 AND run_id = $2 AND status IN ('pending', 'paid')"
+        )
+        .bind(school_id)
+        .bind(run_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.into_iter().map(|r| r.get::<String, _>("employee_id")).collect())
+    }
+
+    async fn update_transfer_line(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+        employee_id: &str,
+        data: Value,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let result = sqlx::query(
+            "UPDATE salary_transfer_lines SET status = $4, utr = COALESCE($5, utr), failure_reason = $6, updated_at = NOW()
+             WHERE school_id = $1 AND batch_id = $2 AND employee_id = $3"
+        )
+        .bind(school_id)
+        .bind(batch_id)
+        .bind(employee_id)
+        .bind(data["status"].as_str())
+        .bind(data["utr"].as_str().filter(|u| !u.is_empty()))
+        .bind(data["reason"].as_str().filter(|r| !r.is_empty()))
+        .execute(&self.client.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn refresh_transfer_batch(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        sqlx::query(
+            "UPDATE salary_transfer_batches b SET
+                status = CASE
+                    WHEN s.pending = s.total THEN 'generated'
+                    WHEN s.paid = s.total THEN 'completed'
+                    WHEN s.failed = s.total THEN 'failed'
+                    ELSE 'partial' END,
+                response_imported_at = NOW(),
+                updated_at = NOW()
+             FROM (SELECT COUNT(*) AS total,
+                          COUNT(*) FILTER (WHERE status = 'paid') AS paid,
+                          COUNT(*) FILTER (WHERE status = 'failed') AS failed,
+                          COUNT(*) FILTER (WHERE status = 'pending') AS pending
+                   FROM salary_transfer_lines WHERE school_id = $1 AND batch_id = $2) s
+             WHERE b.school_id = $1 AND b.batch_id = $2"
+        )
+        .bind(school_id)
+        .bind(batch_id)
+        .execute(&self.client.pool)
+        .await?;
+        self.get_transfer_batch(school_id, batch_id).await
+    }
+}
+
+// --- Notification Repository ---
+pub struct PostgresNotificationRepository {
+    pub client: Arc<DbClient>,
+}
+
+#[async_trait]
+impl NotificationRepository for PostgresNotificationRepository {
+    async fn get_template(
+        &self,
+        school_id: &str,
+        template_key: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query(
+            "SELECT template_key, subject, body FROM notification_templates WHERE school_id = $1 AND template_key = $2",
+        )
+        .bind(school_id)
+        .bind(template_key)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.map(|r| json!({
+            "templateKey": r.get::<String, _>("template_key"),
+            "subject": r.get::<Option<String>, _>("subject"),
+            "body": r.get::<String, _>("body")
+        })))
+    }
+
+    async fn get_templates(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT template_key, subject, body, updated_at FROM notification_templates WHERE school_id = $1 ORDER BY template_key",
+        )
+        .bind(school_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| json!({
+                "templateKey": r.get::<String, _>("template_key"),
+                "subject": r.get::<Option<String>, _>("subject"),
+                "body": r.get::<String, _>("body"),
+                "updatedAt": r.get::<chrono::DateTime<chrono::Utc>, _>("updated_at").to_rfc3339()
+            }))
+            .collect())
+    }
+
+    async fn upsert_template(
+        &self,
+        school_id: &str,
+        template_key: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let body = data["body"].as_str().ok_or("Template body is required")?;
+        sqlx::query(
+            "INSERT INTO notification_templates (school_id, template_key, subject, body)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (school_id, template_key)
+             DO UPDATE SET subject = EXCLUDED.subject, body = EXCLUDED.body, updated_at = NOW()",
+        )
+        .bind(school_id)
+        .bind(template_key)
+        .bind(data["subject"].as_str())
+        .bind(body)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(json!({"templateKey": template_key, "subject": data["subject"], "body": body}))
+    }
+
+    async fn delete_template(
+        &self,
+        school_id: &str,
+        template_key: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        sqlx::query("DELETE FROM notification_templates WHERE school_id = $1 AND template_key = $2")
+            .bind(school_id)
+            .bind(template_key)
+            .execute(&self.client.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn claim_notification(
+        &self,
+        school_id: &str,
+        data: &Value,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let claimed = sqlx::query(
+            "INSERT INTO notification_log (notification_id, school_id, channel, recipient, template_key,
+                ref_type, ref_id, dedupe_key, body, status)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, 'sending')
+             ON CONFLICT (school_id, dedupe_key) DO UPDATE SET status = 'sending', created_at = NOW()
+             WHERE notification_log.status = 'failed'
+                OR (notification_log.status = 'sending' AND notification_log.created_at < NOW() - INTERVAL '10 minutes')",
+        )
+        .bind(format!("NT{}", uuid::Uuid::new_v4().simple()))
+        .bind(school_id)
+        .bind(data["channel"].as_str().unwrap_or("sms"))
+        .bind(data["recipient"].as_str().unwrap_or(""))
+        .bind(data["templateKey"].as_str())
+        .bind(data["refType"].as_str())
+        .bind(data["refId"].as_str())
+        .bind(data["dedupeKey"].as_str())
+        .bind(data["body"].as_str().unwrap_or(""))
+        .execute(&self.client.pool)
+        .await?;
+        Ok(claimed.rows_affected() == 1)
+    }
+
+    async fn add_notification_log(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let notification_id = format!("NT{}", uuid::Uuid::new_v4().simple());
+        // Fills in the claimed row, or a failed attempt so that a retry under the same dedupe key is recorded.
+        sqlx::query(
+            "INSERT INTO notification_log (notification_id, school_id, channel, recipient, template_key,
+                ref_type, ref_id, dedupe_key, body, status, provider_ref, error)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+             ON CONFLICT (school_id, dedupe_key) DO UPDATE SET
+                notification_id = EXCLUDED.notification_id, body = EXCLUDED.body, status = EXCLUDED.status,
+                provider_ref = EXCLUDED.provider_ref, error = EXCLUDED.error, created_at = NOW()
+             WHERE notification_log.status IN ('failed', 'sending')",
+        )
+        .bind(&notification_id)
+        .bind(school_id)
+        .bind(data["channel"].as_str().unwrap_or("sms"))
+        .bind(data["recipient"].as_str().unwrap_or(""))
+        .bind(data["templateKey"].as_str())
+        .bind(data["refType"].as_str())
+        .bind(data["refId"].as_str())
+        .bind(data["dedupeKey"].as_str())
+        .bind(data["body"].as_str().unwrap_or(""))
+        .bind(data["status"].as_str().unwrap_or("queued"))
+        .bind(data["providerRef"].as_str())
+        .bind(data["error"].as_str())
+        .execute(&self.client.pool)
+        .await?;
+
+        let mut res = data;
+        res["notificationId"] = json!(notification_id);
+        Ok(res)
+    }
+
+    async fn get_notification_log(
+        &self,
+        school_id: &str,
+        ref_type: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query(
+            "SELECT * FROM notification_log
+             WHERE school_id = $1 AND ($2::TEXT IS NULL OR ref_type = $2)
+             ORDER BY created_at DESC LIMIT $3",
+        )
+        .bind(school_id)
+        .bind(ref_type)
+        .bind(limit)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| json!({
+                "notificationId": r.get::<String, _>("notification_id"),
+                "channel": r.get::<String, _>("channel"),
+                "recipient": r.get::<String, _>("recipient"),
+                "templateKey": r.get::<Option<String>, _>("template_key"),
+                "refType": r.get::<Option<String>, _>("ref_type"),
+                "refId": r.get::<Option<String>, _>("ref_id"),
+                "body": r.get::<String, _>("body"),
+                "status": r.get::<String, _>("status"),
+                "providerRef": r.get::<Option<String>, _>("provider_ref"),
+                "error": r.get::<Option<String>, _>("error"),
+                "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339()
+            }))
+            .collect())
+    }
+
+    async fn update_delivery_status(
+        &self,
+        provider_ref: &str,
+        status: &str,
+        error: Option<&str>,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query(
+            "UPDATE notification_log SET status = $2, error = COALESCE($3, error), status_updated_at = NOW()
+             WHERE provider_ref = $1
+             RETURNING school_id, notification_id, ref_type, ref_id, status",
+        )
+        .bind(provider_ref)
+        .bind(status)
+        .bind(error)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.map(|r| {
+            json!({
+                "schoolId": r.get::<String, _>("school_id"),
+                "notificationId": r.get::<String, _>("notification_id"),
+                "refType": r.get::<Option<String>, _>("ref_type"),
+                "refId": r.get::<Option<String>, _>("ref_id"),
+                "status": r.get::<String, _>("status"),
+            })
+        }))
+    }
+}
+
+// --- DocumentBox Repository ---
+pub struct PostgresDocumentBoxRepository {
+    pub client: Arc<DbClient>,
+}
+
+#[async_trait]
+impl DocumentBoxRepository for PostgresDocumentBoxRepository {
+    async fn add_document(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        sqlx::query("INSERT INTO document_box (school_id, user_id, doc_type, file_url) VALUES ($1, $2, $3, $4)")
+            .bind(school_id)
+            .bind(data["userId"].as_str())
+            .bind(data["docType"].as_str())
+            .bind(data["fileUrl"].as_str())
+            .execute(&self.client.pool).await?;
+        Ok(data)
+    }
+
+    async fn get_documents(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows = sqlx::query("SELECT * FROM document_box WHERE school_id = $1")
+            .bind(school_id)
+            .fetch_all(&self.client.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |r| json!({"id": r.get::<i32, _>("id"), "docType": r.get::<String, _>("doc_type")}),
+            )
+            .collect())
+    }
+}
+
+// --- School Repository ---
+pub struct PostgresSchoolRepository {
+    pub client: Arc<DbClient>,
+}
+
+#[async_trait]
+impl SchoolRepository for PostgresSchoolRepository {
+    async fn get_school(
+        &self,
+        school_id: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let row = sqlx::query(
+            "SELECT school_id, school_name, data, wallet_balance, per_student_rate, billing_status, 
+             trial_ends_at, last_billing_date, status, is_blocked
+             FROM schools WHERE school_id = $1"
+        )
+        .bind(school_id)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(
+            row.map(|r| {
+                json!({
+                    "schoolId": r.get::<String, _>("school_id"), 
+                    "schoolName": r.get::<String, _>("school_name"),
+                    "data": r.get::<Value, _>("data"),
+                    "walletBalance": r.get::<bigdecimal::BigDecimal, _>("wallet_balance").to_string(),
+                    "perStudentRate": r.get::<bigdecimal::BigDecimal, _>("per_student_rate").to_string(),
+                    "billingStatus": r.get::<String, _>("billing_status"),
+                    "trialEndsAt": r.try_get::<chrono::DateTime<chrono::Utc>, _>("trial_ends_at").ok().map(|t| t.to_rfc3339()),
+                    "lastBillingDate": r.try_get::<chrono::DateTime<chrono::Utc>, _>("last_billing_date").ok().map(|t| t.to_rfc3339()),
+                    "status": r.get::<String, _>("status"),
+                    "isBlocked": r.get::<bool, _>("is_blocked")
+                })
+            })
+        )
+    }
+}
+
+// --- Responsibility Repository ---
+pub struct PostgresResponsibilityRepository {
+    pub client: Arc<DbClient>,
+}
+
+#[async_trait]
+impl ResponsibilityRepository for PostgresResponsibilityRepository {
+    async fn get_responsibilities(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT * FROM responsibilities WHERE school_id = $1",
+        )
+        .bind(school_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| json!({
+                "responsibilityId": r.get::<String, _>("responsibility_id"),
+                "schoolId": r.get::<String, _>("school_id"),
+                "name": r.get::<String, _>("name"),
+                "description": r.get::<Option<String>, _>("description"),
+                "perDayPrice": r.get::<bigdecimal::BigDecimal, _>("per_day_price").to_f64().unwrap_or(0.0),
+                "timePeriod": r.get::<i32, _>("time_period"),
+                "spaceCategory": r.get::<Option<String>, _>("space_category").unwrap_or_default(),
+                "responsibilityField": r.get::<Option<String>, _>("responsibility_field").unwrap_or_default(),
+                "spaceId": r.get::<Option<String>, _>("space_id").unwrap_or_default(),
+                "workLevel": r.get::<Option<String>, _>("work_level").unwrap_or_default(),
+                "workAmount": r.try_get::<bigdecimal::BigDecimal, _>("work_amount").unwrap_or_default().to_f64().unwrap_or(0.0),
+                "workPeriod": r.get::<Option<String>, _>("work_period").unwrap_or_default(),
+                "customDates": r.get::<Option<Value>, _>("custom_dates").unwrap_or(json!([])),
+                "totalPrice": r.try_get::<bigdecimal::BigDecimal, _>("total_price").unwrap_or_default().to_f64().unwrap_or(0.0),
+                "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339()
+            }))
+            .collect())
+    }
+
+    async fn add_responsibility(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError> {
+        let res_id = format!("RES{}", chrono::Utc::now().timestamp_millis());
+        let name = data["name"].as_str().ok_or("Name is required")?;
+        let description = data["description"].as_str();
+
+        let per_day_price = data["perDayPrice"].as_f64().unwrap_or(0.0);
+        let time_period = data["timePeriod"].as_i64().unwrap_or(0) as i32;
+        let space_category = data["spaceCategory"].as_str();
+        let responsibility_field = data["responsibilityField"].as_str();
+        let space_id = data["spaceId"].as_str();
+
+        let work_level = data["workLevel"].as_str();
+        let work_amount = data["workAmount"].as_f64().unwrap_or(0.0);
+        let work_period = data["workPeriod"].as_str();
+        let custom_dates = data["customDates"].clone();
+        let total_price = data["totalPrice"].as_f64().unwrap_or(0.0);
+
+        sqlx::query(
+            "INSERT INTO responsibilities (responsibility_id, school_id, name, description, per_day_price, time_period, space_category, responsibility_field, space_id, work_level, work_amount, work_period, custom_dates, total_price)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"
+        )
+        .bind(&res_id)
+        .bind(school_id)
+        .bind(name)
+        .bind(description)
+        .bind(bigdecimal::BigDecimal::from_f64(per_day_price).unwrap_or_default())
+        .bind(time_period)
+        .bind(space_category)
+        .bind(responsibility_field)
+        .bind(space_id)
+        .bind(work_level)
+        .bind(bigdecimal::BigDecimal::from_f64(work_amount).unwrap_or_default())
+        .bind(work_period)
+        .bind(if custom_dates.is_null() { json!([]) } else { custom_dates })
+        .bind(bigdecimal::BigDecimal::from_f64(total_price).unwrap_or_default())
+        .execute(&self.client.pool)
+        .await?;
+
+        if let Some(space_ids) = data["spaceIds"].as_array() {
+            for space_id_val in space_ids {
+                if let Some(sid) = space_id_val.as_str() {
+                    let _ = sqlx::query("INSERT INTO responsibility_spaces (responsibility_id, school_id, space_id) VALUES ($1, $2, $3)")
+                        .bind(&res_id).bind(school_id).bind(sid).execute(&self.client.pool).await;
+                }
+            }
+        }
+
+        let mut ret = data.clone();
+        ret["responsibilityId"] = json!(res_id);
+        Ok(ret)
+    }
+
+    async fn assign_responsibility(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        responsibility_id: &str,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO employee_responsibilities (school_id, employee_id, responsibility_id)
+             VALUES ($1, $2, $3) ON CONFLICT (school_id, employee_id, responsibility_id) DO NOTHING"
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(responsibility_id)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_responsibility(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        responsibility_id: &str,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "DELETE FROM employee_responsibilities WHERE school_id = $1 AND employee_id = $2 AND responsibility_id = $3"
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(responsibility_id)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_employee_responsibilities(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT r.* FROM responsibilities r
+             JOIN employee_responsibilities er ON r.responsibility_id = er.responsibility_id AND r.school_id = er.school_id
+             WHERE er.school_id = $1 AND er.employee_id = $2"
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| json!({
+                "responsibilityId": r.get::<String, _>("responsibility_id"),
+                "name": r.get::<String, _>("name"),
+                "description": r.get::<Option<String>, _>("description"),
+                "perDayPrice": r.get::<bigdecimal::BigDecimal, _>("per_day_price").to_f64().unwrap_or(0.0),
+                "timePeriod": r.get::<i32, _>("time_period"),
+                "spaceCategory": r.get::<Option<String>, _>("space_category").unwrap_or_default(),
+                "responsibilityField": r.get::<Option<String>, _>("responsibility_field").unwrap_or_default(),
+                "spaceId": r.get::<Option<String>, _>("space_id").unwrap_or_default(),
+                "workLevel": r.get::<Option<String>, _>("work_level").unwrap_or_default(),
+                "workAmount": r.try_get::<bigdecimal::BigDecimal, _>("work_amount").unwrap_or_default().to_f64().unwrap_or(0.0),
+                "workPeriod": r.get::<Option<String>, _>("work_period").unwrap_or_default(),
+                "customDates": r.get::<Option<Value>, _>("custom_dates").unwrap_or(json!([])),
+                "totalPrice": r.try_get::<bigdecimal::BigDecimal, _>("total_price").unwrap_or_default().to_f64().unwrap_or(0.0),
+                "createdAt": r.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339()
+            }))
+            .collect())
+    }
+}
+
+
+// --- Task Repository ---
+pub struct PostgresTaskRepository {
+    pub client: Arc<DbClient>,
+}
+
+#[async_trait]
+impl TaskRepository for PostgresTaskRepository {
+    async fn get_tasks(&self, school_id: &str) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rows =
+            sqlx::query("SELECT * FROM audit_logs WHERE school_id = $1 AND target_type = 'task'")
+                .bind(school_id)
+                .fetch_all(&self.client.pool)
+                .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| json!({"id": r.get::<i32, _>("id"), "data": r.get::<Value, _>("data")}))
+            .collect())
+    }
+}
+
+/// Marks leave days on `conn`; see `LeaveRepository::mark_leave_attendance`.
+async fn mark_leave_days(
+    conn: &mut sqlx::PgConnection,
+    school_id: &str,
+    employee_id: &str,
+    dates: &[chrono::NaiveDate],
+    leave_type: &str,
+    leave_id: &str,
+) -> Result<(), AppError> {
+    // A record already there is kept in pre_leave; one saved by an earlier leave is left alone
+    sqlx::query(
+        "INSERT INTO attendance (school_id, role, user_id, date, status, reason, leave_id)
+         SELECT $1, 'employee', $2, d, 'leave', $4, $5 FROM UNNEST($3::DATE[]) AS d
+         ON CONFLICT (school_id, role, user_id, date) DO UPDATE SET
+            pre_leave = CASE WHEN attendance.leave_id IS NULL THEN jsonb_build_object(
+                'status', attendance.status, 'inTime', attendance.in_time, 'outTime', attendance.out_time,
+                'totalTime', attendance.total_time, 'reason', attendance.reason, 'description', attendance.description,
+                'source', attendance.source
+            ) ELSE attendance.pre_leave END,
+            status = 'leave', reason = EXCLUDED.reason, description = NULL, leave_id = EXCLUDED.leave_id, source = NULL,
+            in_time = NULL, out_time = NULL, total_time = NULL, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(school_id)
+    .bind(employee_id)
+    .bind(dates)
+    .bind(leave_type)
+    .bind(leave_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+pub struct PostgresLeaveRepository {
+    pub client: Arc<crate::db::DbClient>,
+}
+
+#[async_trait]
+impl LeaveRepository for PostgresLeaveRepository {
+    async fn add_leave(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError> {
+        let leave_id = format!("LV{}", chrono::Utc::now().timestamp_millis());
+        let employee_id = data["employeeId"].as_str().ok_or("Employee ID is required")?;
+        let employee_name = data["employeeName"].as_str().unwrap_or("");
+        let reason = data["reason"].as_str().unwrap_or("");
+        let leave_type = data["leaveType"].as_str().unwrap_or("casual");
+        let from_date = chrono::NaiveDate::parse_from_str(data["fromDate"].as_str().unwrap_or(""), "%Y-%m-%d").unwrap_or_default();
+        let to_date = chrono::NaiveDate::parse_from_str(data["toDate"].as_str().unwrap_or(""), "%Y-%m-%d").unwrap_or_default();
+
+        sqlx::query(
+            "INSERT INTO leave_applications (
+                leave_id, school_id, employee_id, employee_name, reason, leave_type, from_date, to_date, days,
+                approval_chain, timeline, days_by_year
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9::FLOAT::DECIMAL, $10, $11, $12)",
+        )
+        .bind(&leave_id)
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(employee_name)
+        .bind(reason)
+        .bind(leave_type)
+        .bind(from_date)
+        .bind(to_date)
+        .bind(data["days"].as_f64())
+        .bind(if data["approvalChain"].is_object() { data["approvalChain"].clone() } else { json!({}) })
+        .bind(if data["timeline"].is_array() { data["timeline"].clone() } else { json!([]) })
+        .bind(data["daysByYear"].is_object().then(|| data["daysByYear"].clone()))
+        .execute(&self.client.pool)
+        .await?;
+
+        let mut res = data.clone();
+        res["leaveId"] = json!(leave_id);
+        res["status"] = json!("pending");
+        Ok(res)
+    }
+
+    async fn get_leaves(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM leave_applications WHERE school_id = $1 ORDER BY created_at DESC",
+            LEAVE_COLUMNS
+        ))
+        .bind(school_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+
+        Ok(rows.iter().map(leave_json).collect())
+    }
+
+    async fn get_leave(
+        &self,
+        school_id: &str,
+        leave_id: &str,
+    ) -> Result<Option<Value>, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM leave_applications WHERE school_id = $1 AND leave_id = $2",
+            LEAVE_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(leave_id)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(leave_json))
+    }
+
+    async fn update_leave_status(
+        &self,
+        school_id: &str,
+        leave_id: &str,
+        from: &[&str],
+        status: &str,
+        approved_by: Option<&str>,
+    ) -> Result<bool, AppError> {
+        let from: Vec<String> = from.iter().map(|s| s.to_string()).collect();
+        let res = sqlx::query(
+            "UPDATE leave_applications SET status = $1, approved_by = COALESCE($2, approved_by), updated_at = NOW()
+             WHERE school_id = $3 AND leave_id = $4 AND status = ANY($5)",
+        )
+        .bind(status)
+        .bind(approved_by)
+        .bind(school_id)
+        .bind(leave_id)
+        .bind(&from)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn update_leave_duration(
+        &self,
+        school_id: &str,
+        leave_id: &str,
+        to_date: chrono::NaiveDate,
+        days: f64,
+        days_by_year: &Value,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE leave_applications SET to_date = $1, days = $2::FLOAT::DECIMAL, days_by_year = $5, updated_at = NOW()
+             WHERE school_id = $3 AND leave_id = $4",
+        )
+        .bind(to_date)
+        .bind(days)
+        .bind(school_id)
+        .bind(leave_id)
+        .bind(days_by_year)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_leave_types(&self, school_id: &str) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT code, name, annual_quota::FLOAT AS annual_quota, accrual,
+                    carry_forward_max::FLOAT AS carry_forward_max, paid, count_holidays, gender, active
+             FROM leave_types WHERE school_id = $1 ORDER BY code",
+        )
+        .bind(school_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "code": r.get::<String, _>("code"),
+                    "name": r.get::<String, _>("name"),
+                    "annualQuota": r.get::<f64, _>("annual_quota"),
+                    "accrual": r.get::<String, _>("accrual"),
+                    "carryForwardMax": r.get::<f64, _>("carry_forward_max"),
+                    "paid": r.get::<bool, _>("paid"),
+                    "countHolidays": r.get::<bool, _>("count_holidays"),
+                    "gender": r.get::<Option<String>, _>("gender"),
+                    "active": r.get::<bool, _>("active"),
+                })
+            })
+            .collect())
+    }
+
+    async fn save_leave_type(&self, school_id: &str, leave_type: &Value) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO leave_types (school_id, code, name, annual_quota, accrual, carry_forward_max, paid, count_holidays, gender, active)
+             VALUES ($1, $2, $3, $4::FLOAT::DECIMAL, $5, $6::FLOAT::DECIMAL, $7, $8, $9, $10)
+             ON CONFLICT (school_id, code) DO UPDATE SET
+                name = EXCLUDED.name, annual_quota = EXCLUDED.annual_quota, accrual = EXCLUDED.accrual,
+                carry_forward_max = EXCLUDED.carry_forward_max, paid = EXCLUDED.paid,
+                count_holidays = EXCLUDED.count_holidays, gender = EXCLUDED.gender,
+                active = EXCLUDED.active, updated_at = NOW()",
+        )
+        .bind(school_id)
+        .bind(leave_type["code"].as_str())
+        .bind(leave_type["name"].as_str())
+        .bind(leave_type["annualQuota"].as_f64().unwrap_or(0.0))
+        .bind(leave_type["accrual"].as_str().unwrap_or("yearly"))
+        .bind(leave_type["carryForwardMax"].as_f64().unwrap_or(0.0))
+        .bind(leave_type["paid"].as_bool().unwrap_or(true))
+        .bind(leave_type["countHolidays"].as_bool().unwrap_or(false))
+        .bind(leave_type["gender"].as_str())
+        .bind(leave_type["active"].as_bool().unwrap_or(true))
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_leave_balance_rows(
+        &self,
+        school_id: &str,
+        year: i32,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT employee_id, leave_type, opening::FLOAT AS opening, adjustment::FLOAT AS adjustment
+             FROM leave_balances WHERE school_id = $1 AND year = $2",
+        )
+        .bind(school_id)
+        .bind(year)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "employeeId": r.get::<String, _>("employee_id"),
+                    "leaveType": r.get::<String, _>("leave_type"),
+                    "opening": r.get::<f64, _>("opening"),
+                    "adjustment": r.get::<f64, _>("adjustment"),
+                })
+            })
+            .collect())
+    }
+
+    async fn set_leave_opening(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        leave_type: &str,
+        year: i32,
+        opening: f64,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO leave_balances (school_id, employee_id, leave_type, year, opening)
+             VALUES ($1, $2, $3, $4, $5::FLOAT::DECIMAL)
+             ON CONFLICT (school_id, employee_id, leave_type, year)
+             DO UPDATE SET opening = EXCLUDED.opening, updated_at = NOW()",
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(leave_type)
+        .bind(year)
+        .bind(opening)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn add_leave_adjustment(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        leave_type: &str,
+        year: i32,
+        days: f64,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO leave_balances (school_id, employee_id, leave_type, year, adjustment)
+             VALUES ($1, $2, $3, $4, $5::FLOAT::DECIMAL)
+             ON CONFLICT (school_id, employee_id, leave_type, year)
+             DO UPDATE SET adjustment = leave_balances.adjustment + EXCLUDED.adjustment, updated_at = NOW()",
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(leave_type)
+        .bind(year)
+        .bind(days)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_leave_usage(
+        &self,
+        school_id: &str,
+        year: i32,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT employee_id, leave_type, status,
+                    COALESCE(SUM(CASE
+                        WHEN days_by_year IS NOT NULL THEN COALESCE((days_by_year->>($2::TEXT))::FLOAT, 0)
+                        WHEN EXTRACT(YEAR FROM from_date) = $2 THEN COALESCE(days, to_date - from_date + 1)::FLOAT
+                        ELSE 0
+                    END), 0)::FLOAT AS days
+             FROM leave_applications
+             WHERE school_id = $1 AND EXTRACT(YEAR FROM from_date) <= $2 AND EXTRACT(YEAR FROM to_date) >= $2
+               AND status IN ('approved', 'pending')
+             GROUP BY employee_id, leave_type, status",
+        )
+        .bind(school_id)
+        .bind(year)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "employeeId": r.get::<String, _>("employee_id"),
+                    "leaveType": r.get::<String, _>("leave_type"),
+                    "status": r.get::<String, _>("status"),
+                    "days": r.get::<f64, _>("days"),
+                })
+            })
+            .collect())
+    }
+
+    async fn mark_leave_attendance(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        dates: &[chrono::NaiveDate],
+        leave_type: &str,
+        leave_id: &str,
+    ) -> Result<(), AppError> {
+        let mut tx = self.client.pool.begin().await?;
+        mark_leave_days(&mut tx, school_id, employee_id, dates, leave_type, leave_id).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn approve_leave(
+        &self,
+        school_id: &str,
+        leave: &Value,
+        dates: &[chrono::NaiveDate],
+        approved_by: Option<&str>,
+        history: Value,
+    ) -> Result<bool, AppError> {
+        let leave_id = leave["leaveId"].as_str().unwrap_or("");
+        let employee_id = leave["employeeId"].as_str().unwrap_or("");
+        let mut tx = self.client.pool.begin().await?;
+        let res = sqlx::query(
+            "UPDATE leave_applications SET status = 'approved', approved_by = COALESCE($1, approved_by), updated_at = NOW()
+             WHERE school_id = $2 AND leave_id = $3 AND status = 'pending'",
+        )
+        .bind(approved_by)
+        .bind(school_id)
+        .bind(leave_id)
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Ok(false);
+        }
+        mark_leave_days(&mut tx, school_id, employee_id, dates, leave["leaveType"].as_str().unwrap_or(""), leave_id).await?;
+        sqlx::query("INSERT INTO audit_logs (school_id, target_type, target_id, action, data) VALUES ($1, 'attendance', $2, 'leave_marked', $3)")
+            .bind(school_id)
+            .bind(employee_id)
+            .bind(history)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn clear_leave_attendance(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        leave_id: &str,
+    ) -> Result<(), AppError> {
+        let mut tx = self.client.pool.begin().await?;
+        // Days still on leave get back the record the leave replaced, or go if there was none;
+        // days edited since only lose the tag
+        sqlx::query(
+            "UPDATE attendance SET
+                status = pre_leave->>'status',
+                in_time = (pre_leave->>'inTime')::TIMESTAMPTZ,
+                out_time = (pre_leave->>'outTime')::TIMESTAMPTZ,
+                total_time = pre_leave->>'totalTime',
+                reason = pre_leave->>'reason',
+                description = pre_leave->>'description',
+                source = pre_leave->>'source',
+                leave_id = NULL, pre_leave = NULL, updated_at = CURRENT_TIMESTAMP
+             WHERE school_id = $1 AND role = 'employee' AND user_id = $2 AND leave_id = $3
+               AND status = 'leave' AND pre_leave IS NOT NULL",
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(leave_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM attendance
+             WHERE school_id = $1 AND role = 'employee' AND user_id = $2 AND leave_id = $3 AND status = 'leave'",
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(leave_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE attendance SET leave_id = NULL, pre_leave = NULL
+             WHERE school_id = $1 AND role = 'employee' AND user_id = $2 AND leave_id = $3",
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(leave_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn append_leave_timeline(
+        &self,
+        school_id: &str,
+        leave_id: &str,
+        entry: &Value,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE leave_applications SET timeline = COALESCE(timeline, '[]'::jsonb) || jsonb_build_array($1::jsonb), updated_at = NOW()
+             WHERE school_id = $2 AND leave_id = $3",
+        )
+        .bind(entry)
+        .bind(school_id)
+        .bind(leave_id)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn advance_leave_step(
+        &self,
+        school_id: &str,
+        leave_id: &str,
+        from_step: i32,
+        to_step: i32,
+        entry: &Value,
+    ) -> Result<bool, AppError> {
+        let res = sqlx::query(
+            "UPDATE leave_applications
+             SET approval_step = $1, step_started_at = NOW(), updated_at = NOW(),
+                 timeline = COALESCE(timeline, '[]'::jsonb) || jsonb_build_array($2::jsonb)
+             WHERE school_id = $3 AND leave_id = $4 AND status = 'pending' AND COALESCE(approval_step, 0) = $5",
+        )
+        .bind(to_step)
+        .bind(entry)
+        .bind(school_id)
+        .bind(leave_id)
+        .bind(from_step)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn get_approval_chains(&self, school_id: &str) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT employee_type, steps, escalate_after_hours, updated_by, updated_at
+             FROM leave_approval_chains WHERE school_id = $1 ORDER BY employee_type",
+        )
+        .bind(school_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "employeeType": r.get::<String, _>("employee_type"),
+                    "steps": r.get::<Value, _>("steps"),
+                    "escalateAfterHours": r.get::<Option<i32>, _>("escalate_after_hours"),
+                    "updatedBy": r.get::<Option<String>, _>("updated_by"),
+                    "updatedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("updated_at").map(|t| t.to_rfc3339()),
+                })
+            })
+            .collect())
+    }
+
+    async fn save_approval_chain(&self, school_id: &str, chain: &Value) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO leave_approval_chains (school_id, employee_type, steps, escalate_after_hours, updated_by)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (school_id, employee_type) DO UPDATE SET
+                steps = EXCLUDED.steps, escalate_after_hours = EXCLUDED.escalate_after_hours,
+                updated_by = EXCLUDED.updated_by, updated_at = NOW()",
+        )
+        .bind(school_id)
+        .bind(chain["employeeType"].as_str())
+        .bind(&chain["steps"])
+        .bind(chain["escalateAfterHours"].as_i64().map(|h| h as i32))
+        .bind(chain["updatedBy"].as_str())
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_approval_chain(&self, school_id: &str, employee_type: &str) -> Result<bool, AppError> {
+        let res = sqlx::query("DELETE FROM leave_approval_chains WHERE school_id = $1 AND employee_type = $2")
+            .bind(school_id)
+            .bind(employee_type)
+            .execute(&self.client.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn create_delegation(&self, school_id: &str, data: &Value) -> Result<Value, AppError> {
+        let delegation_id = format!("DLG{}", chrono::Utc::now().timestamp_millis());
+        let row = sqlx::query(&format!(
+            "INSERT INTO leave_delegations (delegation_id, school_id, approver_id, delegate_id, from_date, to_date, reason)
+             VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}",
+            DELEGATION_COLUMNS
+        ))
+        .bind(&delegation_id)
+        .bind(school_id)
+        .bind(data["approverId"].as_str())
+        .bind(data["delegateId"].as_str())
+        .bind(chrono::NaiveDate::parse_from_str(data["fromDate"].as_str().unwrap_or(""), "%Y-%m-%d")?)
+        .bind(chrono::NaiveDate::parse_from_str(data["toDate"].as_str().unwrap_or(""), "%Y-%m-%d")?)
+        .bind(data["reason"].as_str())
+        .fetch_one(&self.client.pool)
+        .await?;
+        Ok(delegation_json(&row))
+    }
+
+    async fn get_delegations(
+        &self,
+        school_id: &str,
+        approver_id: Option<&str>,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM leave_delegations
+             WHERE school_id = $1 AND ($2::TEXT IS NULL OR approver_id = $2)
+             ORDER BY from_date DESC",
+            DELEGATION_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(approver_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(delegation_json).collect())
+    }
+
+    async fn revoke_delegation(&self, school_id: &str, delegation_id: &str) -> Result<bool, AppError> {
+        let res = sqlx::query(
+            "UPDATE leave_delegations SET revoked = TRUE WHERE school_id = $1 AND delegation_id = $2 AND NOT revoked",
+        )
+        .bind(school_id)
+        .bind(delegation_id)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+const LEAVE_COLUMNS: &str = "leave_id, employee_id, employee_name, reason, leave_type, from_date, to_date, status,
+    approved_by, notes, COALESCE(days, to_date - from_date + 1)::FLOAT AS days, created_at,
+    approval_chain, COALESCE(approval_step, 0) AS approval_step, step_started_at, timeline";
+
+const DELEGATION_COLUMNS: &str = "delegation_id, approver_id, delegate_id, from_date, to_date, reason, revoked, created_at";
+
+fn delegation_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "delegationId": r.get::<String, _>("delegation_id"),
+        "approverId": r.get::<String, _>("approver_id"),
+        "delegateId": r.get::<String, _>("delegate_id"),
+        "fromDate": r.get::<chrono::NaiveDate, _>("from_date").to_string(),
+        "toDate": r.get::<chrono::NaiveDate, _>("to_date").to_string(),
+        "reason": r.get::<Option<String>, _>("reason"),
+        "revoked": r.get::<bool, _>("revoked"),
+        "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+    })
+}
+
+fn leave_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "leaveId": r.get::<String, _>("leave_id"),
+        "employeeId": r.get::<String, _>("employee_id"),
+        "employeeName": r.get::<Option<String>, _>("employee_name").unwrap_or_default(),
+        "reason": r.get::<String, _>("reason"),
+        "leaveType": r.get::<String, _>("leave_type"),
+        "fromDate": r.get::<chrono::NaiveDate, _>("from_date").to_string(),
+        "toDate": r.get::<chrono::NaiveDate, _>("to_date").to_string(),
+        "status": r.get::<String, _>("status"),
+        "approvedBy": r.get::<Option<String>, _>("approved_by"),
+        "notes": r.get::<Option<String>, _>("notes"),
+        "days": r.get::<f64, _>("days"),
+        "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+        "approvalChain": r.get::<Option<Value>, _>("approval_chain").unwrap_or(json!({})),
+        "approvalStep": r.get::<i32, _>("approval_step"),
+        "stepStartedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("step_started_at").map(|t| t.to_rfc3339()),
+        "timeline": r.get::<Option<Value>, _>("timeline").unwrap_or(json!([])),
+    })
+}
+
+// --- Report Repository ---
+pub struct PostgresReportRepository {
+    pub client: Arc<DbClient>,
+}
+
+fn parse_report_date(d: &str) -> Result<chrono::NaiveDate, AppError> {
+    chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD", d).into())
+}
+
+#[async_trait]
+impl ReportRepository for PostgresReportRepository {
+    async fn get_fee_collections(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT p.receipt_no, p.student_id, s.name, s.class_name, s.section, p.amount::FLOAT AS amount,
+                    p.payment_mode, p.collected_by, p.fee_head, COALESCE(p.fee_id, 'general') AS fee_id, p.reference, p.paid_on
+             FROM fee_payments p
+             LEFT JOIN students s ON s.school_id = p.school_id AND s.student_id = p.student_id
+             WHERE p.school_id = $1 AND p.paid_on BETWEEN $2 AND $3
+             ORDER BY p.paid_on, p.id",
+        )
+        .bind(school_id)
+        .bind(parse_report_date(from)?)
+        .bind(parse_report_date(to)?)
+        .fetch_all(&self.client.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "receiptNo": r.get::<String, _>("receipt_no"),
+                    "studentId": r.get::<String, _>("student_id"),
+                    "studentName": r.get::<Option<String>, _>("name"),
+                    "className": r.get::<Option<String>, _>("class_name"),
+                    "section": r.get::<Option<String>, _>("section"),
+                    "amount": r.get::<f64, _>("amount"),
+                    "paymentMode": r.get::<String, _>("payment_mode"),
+                    "collectedBy": r.get::<Option<String>, _>("collected_by"),
+                    "feeId": r.get::<String, _>("fee_id"),
+                    "feeHead": r.get::<String, _>("fee_head"),
+                    "reference": r.get::<Option<String>, _>("reference"),
+                    "paidOn": r.get::<chrono::NaiveDate, _>("paid_on").to_string(),
+                })
+            })
+            .collect())
+    }
+
+    async fn get_fee_demand(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Value>, AppError> {
+        // Demand = fees charged to students (legacy fee_added log) + custom fee records raised in range.
+        // Legacy charges all settle against the one student_fees balance, so they share the
+        // 'general' fee that pay_fee receipts are booked to.
+        let rows = sqlx::query(
+            "SELECT s.class_name, 'general' AS fee_id, 'General' AS fee_head,
+                    SUM((a.data->>'amount')::FLOAT) AS demand
+             FROM audit_logs a
+             JOIN students s ON s.school_id = a.school_id AND s.student_id = a.target_id
+             WHERE a.school_id = $1 AND a.target_type = 'fee' AND a.action = 'fee_added'
+               AND a.created_at::date BETWEEN $2 AND $3
+             GROUP BY 1
+             UNION ALL
+             SELECT s.class_name, cf.fee_id, cf.fee_name AS fee_head, SUM(r.amount)::FLOAT AS demand
+             FROM custom_fee_records r
+             JOIN custom_fees cf ON cf.school_id = r.school_id AND cf.fee_id = r.fee_id
+             JOIN students s ON s.school_id = r.school_id AND s.student_id = r.student_id
+             WHERE r.school_id = $1 AND r.created_at::date BETWEEN $2 AND $3
+             GROUP BY 1, 2, 3",
+        )
+        .bind(school_id)
+        .bind(parse_report_date(from)?)
+        .bind(parse_report_date(to)?)
+        .fetch_all(&self.client.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "className": r.get::<String, _>("class_name"),
+                    "feeId": r.get::<String, _>("fee_id"),
+                    "feeHead": r.get::<String, _>("fee_head"),
+                    "demand": r.get::<Option<f64>, _>("demand").unwrap_or(0.0),
+                })
+            })
+            .collect())
+    }
+
+    async fn get_fee_defaulters(
+        &self,
+        school_id: &str,
+        as_of: &str,
+    ) -> Result<Vec<Value>, AppError> {
+        let as_of = parse_report_date(as_of)?;
+        // The running balance as it stood at the end of `as_of`: later charges and concessions
+        // are taken back out and later receipts added back in
+        let students = sqlx::query(
+            "SELECT s.student_id, s.name, s.class_name, s.section, s.contact,
+                    GREATEST(COALESCE(sf.pending_amount, 0)::FLOAT - COALESCE(l.change, 0) + COALESCE(p.paid, 0), 0) AS pending_amount
+             FROM students s
+             LEFT JOIN student_fees sf ON sf.school_id = s.school_id AND sf.student_id = s.student_id
+             LEFT JOIN (
+                SELECT target_id AS student_id,
+                       SUM(CASE WHEN action = 'fee_added' THEN (data->>'amount')::FLOAT
+                                ELSE COALESCE((data->>'previousDiscount')::FLOAT, 0) - COALESCE((data->>'newDiscount')::FLOAT, 0) END) AS change
+                FROM audit_logs
+                WHERE school_id = $1 AND target_type = 'fee' AND action IN ('fee_added', 'discount_applied') AND created_at::date > $2
+                GROUP BY 1
+             ) l ON l.student_id = s.student_id
+             LEFT JOIN (
+                SELECT student_id, SUM(amount)::FLOAT AS paid FROM fee_payments
+                WHERE school_id = $1 AND fee_id = 'general' AND paid_on > $2
+                GROUP BY 1
+             ) p ON p.student_id = s.student_id
+             WHERE s.school_id = $1",
+        )
+        .bind(school_id)
+        .bind(as_of)
+        .fetch_all(&self.client.pool)
+        .await?;
+
+        // Charges made by `as_of`, newest first; the balance is settled against them FIFO below
+        let charges = sqlx::query(
+            "SELECT target_id AS student_id, (data->>'amount')::FLOAT AS amount, created_at::date AS charged_on
+             FROM audit_logs
+             WHERE school_id = $1 AND target_type = 'fee' AND action = 'fee_added' AND created_at::date <= $2
+             ORDER BY target_id, created_at DESC, id DESC",
+        )
+        .bind(school_id)
+        .bind(as_of)
+        .fetch_all(&self.client.pool)
+        .await?;
+        let mut charges_by_student: std::collections::HashMap<String, Vec<&sqlx::postgres::PgRow>> = std::collections::HashMap::new();
+        for c in &charges {
+            charges_by_student.entry(c.get("student_id")).or_default().push(c);
+        }
+
+        // Custom fee records raised by `as_of`, less what had been paid on them by then. Waivers
+        // and carry-forwards aren't dated, so those records are left out whenever they happened.
+        let custom = sqlx::query(
+            "SELECT r.student_id, (r.amount - COALESCE(p.paid, 0))::FLOAT AS outstanding,
+                    COALESCE(cf.due_date, r.created_at::date) AS due_on
+             FROM custom_fee_records r
+             JOIN custom_fees cf ON cf.school_id = r.school_id AND cf.fee_id = r.fee_id
+             LEFT JOIN (
+                SELECT student_id, fee_id, SUM(amount) AS paid FROM fee_payments
+                WHERE school_id = $1 AND paid_on <= $2
+                GROUP BY 1, 2
+             ) p ON p.student_id = r.student_id AND p.fee_id = r.fee_id
+             WHERE r.school_id = $1 AND r.status NOT IN ('waived', 'carried_forward')
+               AND r.created_at::date <= $2 AND r.amount > COALESCE(p.paid, 0)",
+        )
+        .bind(school_id)
+        .bind(as_of)
+        .fetch_all(&self.client.pool)
+        .await?;
+
+        let mut result = Vec::new();
+        for s in students {
+            let student_id: String = s.get("student_id");
+            let legacy_pending: f64 = s.get("pending_amount");
+
+            let mut outstanding = Vec::new();
+            let mut remaining = legacy_pending;
+            for c in charges_by_student.get(&student_id).into_iter().flatten() {
+                if remaining <= 0.0 {
+                    break;
+                }
+                // Only as far back as the balance reaches, the oldest charge partly
+                let amount = c.get::<Option<f64>, _>("amount").unwrap_or(0.0).min(remaining);
+                remaining -= amount;
+                outstanding.push(json!({
+                    "amount": amount,
+                    "date": c.get::<chrono::NaiveDate, _>("charged_on").to_string()
+                }));
+            }
+            if remaining > 0.0 {
+                // Balance with no matching charge history (e.g. imported opening balance)
+                outstanding.push(json!({"amount": remaining, "date": Value::Null}));
+            }
+
+            let mut custom_pending = 0.0;
+            for c in custom.iter().filter(|c| c.get::<String, _>("student_id") == student_id) {
+                let amount = c.get::<f64, _>("outstanding");
+                custom_pending += amount;
+                outstanding.push(json!({
+                    "amount": amount,
+                    "date": c.get::<chrono::NaiveDate, _>("due_on").to_string()
+                }));
+            }
+
+            let pending = legacy_pending + custom_pending;
+            if pending <= 0.0 {
+                continue;
+            }
+            result.push(json!({
+                "studentId": student_id,
+                "studentName": s.get::<Option<String>, _>("name"),
+                "className": s.get::<String, _>("class_name"),
+                "section": s.get::<Option<String>, _>("section"),
+                "contact": s.get::<Option<String>, _>("contact"),
+                "pendingAmount": pending,
+                "charges": outstanding,
+            }));
+        }
+        Ok(result)
+    }
+
+    async fn get_fee_concessions(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT 'concession' AS kind, a.target_id AS student_id, s.name, s.class_name,
+                    COALESCE((a.data->>'newDiscount')::FLOAT, 0) - COALESCE((a.data->>'previousDiscount')::FLOAT, 0) AS amount,
+                    a.created_at::date AS on_date, NULL::TEXT AS reference
+             FROM audit_logs a
+             LEFT JOIN students s ON s.school_id = a.school_id AND s.student_id = a.target_id
+             WHERE a.school_id = $1 AND a.target_type = 'fee' AND a.action = 'discount_applied'
+               AND a.created_at::date BETWEEN $2 AND $3
+             UNION ALL
+             SELECT 'coupon', u.student_id, s.name, s.class_name, u.discount_applied::FLOAT,
+                    u.created_at::date, rc.coupon_name::TEXT
+             FROM coupon_usage_log u
+             LEFT JOIN referral_coupons rc ON rc.school_id = u.school_id AND rc.coupon_id = u.coupon_id
+             LEFT JOIN students s ON s.school_id = u.school_id AND s.student_id = u.student_id
+             WHERE u.school_id = $1 AND u.created_at::date BETWEEN $2 AND $3
+             UNION ALL
+             SELECT 'waiver', r.student_id, s.name, s.class_name, (r.amount - r.paid_amount)::FLOAT,
+                    r.updated_at::date, cf.fee_name
+             FROM custom_fee_records r
+             JOIN custom_fees cf ON cf.school_id = r.school_id AND cf.fee_id = r.fee_id
+             LEFT JOIN students s ON s.school_id = r.school_id AND s.student_id = r.student_id
+             WHERE r.school_id = $1 AND r.status = 'waived' AND r.updated_at::date BETWEEN $2 AND $3
+             ORDER BY on_date",
+        )
+        .bind(school_id)
+        .bind(parse_report_date(from)?)
+        .bind(parse_report_date(to)?)
+        .fetch_all(&self.client.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "type": r.get::<String, _>("kind"),
+                    "studentId": r.get::<String, _>("student_id"),
+                    "studentName": r.get::<Option<String>, _>("name"),
+                    "className": r.get::<Option<String>, _>("class_name"),
+                    "amount": r.get::<Option<f64>, _>("amount").unwrap_or(0.0),
+                    "date": r.get::<chrono::NaiveDate, _>("on_date").to_string(),
+                    "reference": r.get::<Option<String>, _>("reference"),
+                })
+            })
+            .collect())
+    }
+
+    async fn get_coupon_usage(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Value>, AppError> {
+        // Older log rows have no referrer of their own, so fall back to the coupon's current one
+        let rows = sqlx::query(
+            "SELECT u.coupon_id, rc.coupon_name, u.student_id, s.name, s.class_name,
+                    u.discount_applied::FLOAT AS discount, u.fee_amount::FLOAT AS fee_amount,
+                    u.reward_paid::FLOAT AS reward, u.employee_id,
+                    COALESCE(u.referrer_type, rc.referrer_type) AS referrer_type,
+                    COALESCE(u.referrer_id, rc.referrer_id) AS referrer_id,
+                    rc.referrer_name, COALESCE(u.is_admission, FALSE) AS is_admission,
+                    u.created_at::date AS used_on
+             FROM coupon_usage_log u
+             LEFT JOIN referral_coupons rc ON rc.school_id = u.school_id AND rc.coupon_id = u.coupon_id
+             LEFT JOIN students s ON s.school_id = u.school_id AND s.student_id = u.student_id
+             WHERE u.school_id = $1 AND u.created_at::date BETWEEN $2 AND $3
+             ORDER BY u.created_at",
+        )
+        .bind(school_id)
+        .bind(parse_report_date(from)?)
+        .bind(parse_report_date(to)?)
+        .fetch_all(&self.client.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "couponId": r.get::<String, _>("coupon_id"),
+                    "couponName": r.get::<Option<String>, _>("coupon_name"),
+                    "studentId": r.get::<String, _>("student_id"),
+                    "studentName": r.get::<Option<String>, _>("name"),
+                    "className": r.get::<Option<String>, _>("class_name"),
+                    "discount": r.get::<Option<f64>, _>("discount").unwrap_or(0.0),
+                    "feeAmount": r.get::<Option<f64>, _>("fee_amount"),
+                    "rewardPaid": r.get::<Option<f64>, _>("reward").unwrap_or(0.0),
+                    "employeeId": r.get::<Option<String>, _>("employee_id"),
+                    "referrerType": r.get::<Option<String>, _>("referrer_type"),
+                    "referrerId": r.get::<Option<String>, _>("referrer_id"),
+                    "referrerName": r.get::<Option<String>, _>("referrer_name"),
+                    "isAdmission": r.get::<bool, _>("is_admission"),
+                    "date": r.get::<chrono::NaiveDate, _>("used_on").to_string(),
+                })
+            })
+            .collect())
+    }
+}
+
+// --- Timetable Repository ---
+pub struct PostgresTimetableRepository {
+    pub client: Arc<DbClient>,
+}
+
+const SUBSTITUTION_COLUMNS: &str = "substitution_id, date, period_id, class_id, subject_id,
+    TO_CHAR(start_time, 'HH24:MI:SS') AS start_time, TO_CHAR(end_time, 'HH24:MI:SS') AS end_time,
+    absent_teacher_id, substitute_id, reason, status, assigned_by, created_at";
+
+fn substitution_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "substitutionId": r.get::<String, _>("substitution_id"),
+        "date": r.get::<chrono::NaiveDate, _>("date").to_string(),
+        "periodId": r.get::<i32, _>("period_id"),
+        "classId": r.get::<Option<String>, _>("class_id"),
+        "subjectId": r.get::<Option<String>, _>("subject_id"),
+        "startTime": r.get::<Option<String>, _>("start_time"),
+        "endTime": r.get::<Option<String>, _>("end_time"),
+        "absentTeacherId": r.get::<String, _>("absent_teacher_id"),
+        "substituteId": r.get::<String, _>("substitute_id"),
+        "reason": r.get::<Option<String>, _>("reason"),
+        "status": r.get::<String, _>("status"),
+        "assignedBy": r.get::<Option<String>, _>("assigned_by"),
+        "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+    })
+}
+
+#[async_trait]
+impl TimetableRepository for PostgresTimetableRepository {
+    async fn get_periods(
+        &self,
+        school_id: &str,
+        day_of_week: Option<i32>,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT p.id, p.class_id, p.name, TO_CHAR(p.start_time, 'HH24:MI:SS') AS start_time,
+                    TO_CHAR(p.end_time, 'HH24:MI:SS') AS end_time, p.teacher_id, p.subject_id,
+                    p.day_of_week, p.section, p.room_id, s.name AS subject_name
+             FROM class_periods p
+             LEFT JOIN subjects s ON s.school_id = p.school_id AND s.id = p.subject_id
+             WHERE p.school_id = $1 AND ($2::INT IS NULL OR p.day_of_week IS NULL OR p.day_of_week = $2)
+             ORDER BY p.start_time, p.class_id",
+        )
+        .bind(school_id)
+        .bind(day_of_week)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "periodId": r.get::<i32, _>("id"),
+                    "classId": r.get::<String, _>("class_id"),
+                    "name": r.get::<Option<String>, _>("name"),
+                    "startTime": r.get::<Option<String>, _>("start_time"),
+                    "endTime": r.get::<Option<String>, _>("end_time"),
+                    "teacherId": r.get::<Option<String>, _>("teacher_id"),
+                    "subjectId": r.get::<Option<String>, _>("subject_id"),
+                    "subjectName": r.get::<Option<String>, _>("subject_name"),
+                    "dayOfWeek": r.get::<Option<i16>, _>("day_of_week"),
+                    "section": r.get::<Option<String>, _>("section"),
+                    "roomId": r.get::<Option<String>, _>("room_id"),
+                })
+            })
+            .collect())
+    }
+
+    async fn get_absent_employees(
+        &self,
+        school_id: &str,
+        date: chrono::NaiveDate,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT user_id, status, reason FROM attendance
+             WHERE school_id = $1 AND role = 'employee' AND date = $2 AND status IN ('absent', 'leave')",
+        )
+        .bind(school_id)
+        .bind(date)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "employeeId": r.get::<String, _>("user_id"),
+                    "status": r.get::<String, _>("status"),
+                    "reason": r.get::<Option<String>, _>("reason"),
+                })
+            })
+            .collect())
+    }
+
+    async fn get_substitutions(
+        &self,
+        school_id: &str,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+        substitute_id: Option<&str>,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM substitutions
+             WHERE school_id = $1 AND date BETWEEN $2 AND $3 AND status = 'assigned'
+               AND ($4::TEXT IS NULL OR substitute_id = $4)
+             ORDER BY date, start_time",
+            SUBSTITUTION_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(from)
+        .bind(to)
+        .bind(substitute_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(substitution_json).collect())
+    }
+
+    async fn create_substitution(&self, school_id: &str, data: &Value) -> Result<Value, AppError> {
+        let substitution_id = format!("SUB{}{}", chrono::Utc::now().timestamp_millis(), data["periodId"]);
+        let time = |k: &str| data[k].as_str().and_then(|t| t.parse::<chrono::NaiveTime>().ok());
+        let row = sqlx::query(&format!(
+            "INSERT INTO substitutions (substitution_id, school_id, date, period_id, class_id, subject_id, start_time,
+                end_time, absent_teacher_id, substitute_id, reason, assigned_by)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+             ON CONFLICT DO NOTHING
+             RETURNING {}",
+            SUBSTITUTION_COLUMNS
+        ))
+        .bind(&substitution_id)
+        .bind(school_id)
+        .bind(chrono::NaiveDate::parse_from_str(data["date"].as_str().unwrap_or(""), "%Y-%m-%d")?)
+        .bind(data["periodId"].as_i64().map(|p| p as i32))
+        .bind(data["classId"].as_str())
+        .bind(data["subjectId"].as_str())
+        .bind(time("startTime"))
+        .bind(time("endTime"))
+        .bind(data["absentTeacherId"].as_str())
+        .bind(data["substituteId"].as_str())
+        .bind(data["reason"].as_str())
+        .bind(data["assignedBy"].as_str())
+        .fetch_optional(&self.client.pool)
+        .await?;
+        row.as_ref()
+            .map(substitution_json)
+            .ok_or_else(|| "This period already has a substitute".into())
+    }
+
+    async fn cancel_substitution(&self, school_id: &str, substitution_id: &str) -> Result<Option<Value>, AppError> {
+        let row = sqlx::query(&format!(
+            "UPDATE substitutions SET status = 'cancelled'
+             WHERE school_id = $1 AND substitution_id = $2 AND status = 'assigned'
+             RETURNING {}",
+            SUBSTITUTION_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(substitution_id)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(substitution_json))
+    }
+
+    async fn get_timetable_config(&self, school_id: &str) -> Result<Value, AppError> {
+        let row = sqlx::query("SELECT config FROM timetable_settings WHERE school_id = $1")
+            .bind(school_id)
+            .fetch_optional(&self.client.pool)
+            .await?;
+        Ok(row.map(|r| r.get::<Value, _>("config")).unwrap_or(Value::Null))
+    }
+
+    async fn save_timetable_config(&self, school_id: &str, config: &Value, updated_by: Option<&str>) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO timetable_settings (school_id, config, updated_by) VALUES ($1, $2, $3)
+             ON CONFLICT (school_id) DO UPDATE SET
+                config = EXCLUDED.config, updated_by = EXCLUDED.updated_by, updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(school_id)
+        .bind(config)
+        .bind(updated_by)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn add_timetable_draft(&self, school_id: &str, data: &Value) -> Result<Value, AppError> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO timetable_drafts (draft_id, school_id, config, pins, entries, unsatisfied, stats, seed, created_by)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             RETURNING {}",
+            TIMETABLE_DRAFT_COLUMNS
+        ))
+        .bind(data["draftId"].as_str())
+        .bind(school_id)
+        .bind(&data["config"])
+        .bind(&data["pins"])
+        .bind(&data["entries"])
+        .bind(&data["unsatisfied"])
+        .bind(&data["stats"])
+        .bind(data["seed"].as_i64())
+        .bind(data["createdBy"].as_str())
+        .fetch_one(&self.client.pool)
+        .await?;
+        Ok(timetable_draft_json(&row, true))
+    }
+
+    async fn get_timetable_drafts(&self, school_id: &str) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM timetable_drafts WHERE school_id = $1 ORDER BY created_at DESC LIMIT 50",
+            TIMETABLE_DRAFT_COLUMNS
+        ))
+        .bind(school_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(|r| timetable_draft_json(r, false)).collect())
+    }
+
+    async fn get_timetable_draft(&self, school_id: &str, draft_id: &str) -> Result<Option<Value>, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM timetable_drafts WHERE school_id = $1 AND draft_id = $2",
+            TIMETABLE_DRAFT_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(draft_id)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(|r| timetable_draft_json(r, true)))
+    }
+
+    async fn save_timetable_pins(&self, school_id: &str, draft_id: &str, pins: &Value) -> Result<bool, AppError> {
+        let result = sqlx::query("UPDATE timetable_drafts SET pins = $3 WHERE school_id = $1 AND draft_id = $2 AND status = 'draft'")
+            .bind(school_id)
+            .bind(draft_id)
+            .bind(pins)
+            .execute(&self.client.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn publish_timetable(
+        &self,
+        school_id: &str,
+        draft_id: &str,
+        class_ids: &[String],
+        periods: &[Value],
+        published_by: Option<&str>,
+    ) -> Result<bool, AppError> {
+        let text = |key: &str| -> Vec<Option<String>> { periods.iter().map(|p| p[key].as_str().map(|s| s.to_string())).collect() };
+        let time = |key: &str| -> Vec<Option<chrono::NaiveTime>> {
+            periods.iter().map(|p| p[key].as_str().and_then(|t| chrono::NaiveTime::parse_from_str(t, "%H:%M").ok())).collect()
+        };
+        let mut tx = self.client.pool.begin().await?;
+        let claimed = sqlx::query(
+            "UPDATE timetable_drafts SET status = 'published', published_by = $3, published_at = CURRENT_TIMESTAMP
+             WHERE school_id = $1 AND draft_id = $2 AND status = 'draft'",
+        )
+        .bind(school_id)
+        .bind(draft_id)
+        .bind(published_by)
+        .execute(&mut *tx)
+        .await?;
+        if claimed.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query("UPDATE timetable_drafts SET status = 'superseded' WHERE school_id = $1 AND status = 'published' AND draft_id <> $2")
+            .bind(school_id)
+            .bind(draft_id)
+            .execute(&mut *tx)
+            .await?;
+        // Periods keep their IDs across publishes, keyed by class, section, day and start time, so
+        // period attendance and substitutions stay attached to the slot they were recorded against
+        sqlx::query(
+            "CREATE TEMP TABLE publish_periods (class_id TEXT, section TEXT, name TEXT, start_time TIME, end_time TIME,
+                teacher_id TEXT, subject_id TEXT, day_of_week SMALLINT, room_id TEXT) ON COMMIT DROP",
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO publish_periods
+             SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[], $3::TEXT[], $4::TIME[], $5::TIME[], $6::TEXT[], $7::TEXT[], $8::SMALLINT[], $9::TEXT[])",
+        )
+        .bind(text("classId"))
+        .bind(text("section"))
+        .bind(text("name"))
+        .bind(time("startTime"))
+        .bind(time("endTime"))
+        .bind(text("teacherId"))
+        .bind(text("subjectId"))
+        .bind(periods.iter().map(|p| p["day"].as_i64().map(|d| d as i16)).collect::<Vec<_>>())
+        .bind(text("roomId"))
+        .execute(&mut *tx)
+        .await?;
+        const SAME_SLOT: &str = "n.class_id = p.class_id AND n.section IS NOT DISTINCT FROM p.section
+            AND n.day_of_week IS NOT DISTINCT FROM p.day_of_week AND n.start_time IS NOT DISTINCT FROM p.start_time";
+
+        // Cover booked from today on for a slot that goes away or changes teacher no longer applies
+        sqlx::query(&format!(
+            "UPDATE substitutions SET status = 'cancelled'
+             WHERE school_id = $1 AND status = 'assigned' AND date >= CURRENT_DATE
+               AND period_id IN (
+                   SELECT p.id FROM class_periods p
+                   WHERE p.school_id = $1 AND p.class_id = ANY($2)
+                     AND NOT EXISTS (SELECT 1 FROM publish_periods n WHERE {} AND n.teacher_id IS NOT DISTINCT FROM p.teacher_id))",
+            SAME_SLOT
+        ))
+        .bind(school_id)
+        .bind(class_ids)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(&format!(
+            "DELETE FROM class_periods p
+             WHERE p.school_id = $1 AND p.class_id = ANY($2) AND NOT EXISTS (SELECT 1 FROM publish_periods n WHERE {})",
+            SAME_SLOT
+        ))
+        .bind(school_id)
+        .bind(class_ids)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(&format!(
+            "UPDATE class_periods p SET name = n.name, end_time = n.end_time, teacher_id = n.teacher_id,
+                subject_id = n.subject_id, room_id = n.room_id
+             FROM publish_periods n
+             WHERE p.school_id = $1 AND {}",
+            SAME_SLOT
+        ))
+        .bind(school_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(&format!(
+            "INSERT INTO class_periods (school_id, class_id, section, name, start_time, end_time, teacher_id, subject_id, day_of_week, room_id)
+             SELECT $1, n.class_id, n.section, n.name, n.start_time, n.end_time, n.teacher_id, n.subject_id, n.day_of_week, n.room_id
+             FROM publish_periods n
+             WHERE NOT EXISTS (SELECT 1 FROM class_periods p WHERE p.school_id = $1 AND {})",
+            SAME_SLOT
+        ))
+        .bind(school_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE classes c SET total_periods = (SELECT COUNT(*) FROM class_periods p WHERE p.school_id = c.school_id AND p.class_id = c.id)
+             WHERE c.school_id = $1 AND c.id = ANY($2)",
+        )
+        .bind(school_id)
+        .bind(class_ids)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+}
+
+const TIMETABLE_DRAFT_COLUMNS: &str =
+    "draft_id, status, config, pins, entries, unsatisfied, stats, seed, created_by, created_at, published_by, published_at";
+
+/// A generated timetable; list views leave out the entries and settings.
+fn timetable_draft_json(r: &sqlx::postgres::PgRow, full: bool) -> Value {
+    let mut draft = json!({
+        "draftId": r.get::<String, _>("draft_id"),
+        "status": r.get::<String, _>("status"),
+        "pins": r.get::<Value, _>("pins"),
+        "unsatisfied": r.get::<Value, _>("unsatisfied"),
+        "stats": r.get::<Value, _>("stats"),
+        "seed": r.get::<Option<i64>, _>("seed"),
+        "createdBy": r.get::<Option<String>, _>("created_by"),
+        "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+        "publishedBy": r.get::<Option<String>, _>("published_by"),
+        "publishedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("published_at").map(|t| t.to_rfc3339()),
+    });
+    if full {
+        draft["config"] = r.get::<Value, _>("config");
+        draft["entries"] = r.get::<Value, _>("entries");
+    }
+    draft
+}
+
+// --- Employee Document Repository ---
+pub struct PostgresEmployeeDocumentRepository {
+    pub client: Arc<DbClient>,
+}
+
+const EMPLOYEE_DOCUMENT_COLUMNS: &str = "document_id, employee_id, document_type, title, file_name, content_type,
+    size_bytes, storage_key, education_id, experience_id, document_number, issued_on, expires_on,
+    verification_status, verified_by, verified_at, verification_remarks, uploaded_by, created_at";
+
+fn employee_document_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "documentId": r.get::<String, _>("document_id"),
+        "employeeId": r.get::<String, _>("employee_id"),
+        "documentType": r.get::<String, _>("document_type"),
+        "title": r.get::<Option<String>, _>("title"),
+        "fileName": r.get::<String, _>("file_name"),
+        "contentType": r.get::<Option<String>, _>("content_type"),
+        "sizeBytes": r.get::<i64, _>("size_bytes"),
+        "storageKey": r.get::<String, _>("storage_key"),
+        "educationId": r.get::<Option<i32>, _>("education_id"),
+        "experienceId": r.get::<Option<i32>, _>("experience_id"),
+        "documentNumber": r.get::<Option<String>, _>("document_number"),
+        "issuedOn": r.get::<Option<chrono::NaiveDate>, _>("issued_on").map(|d| d.to_string()),
+        "expiresOn": r.get::<Option<chrono::NaiveDate>, _>("expires_on").map(|d| d.to_string()),
+        "verificationStatus": r.get::<String, _>("verification_status"),
+        "verifiedBy": r.get::<Option<String>, _>("verified_by"),
+        "verifiedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("verified_at").map(|t| t.to_rfc3339()),
+        "verificationRemarks": r.get::<Option<String>, _>("verification_remarks"),
+        "uploadedBy": r.get::<Option<String>, _>("uploaded_by"),
+        "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+    })
+}
+
+#[async_trait]
+impl EmployeeDocumentRepository for PostgresEmployeeDocumentRepository {
+    async fn add_document(&self, school_id: &str, data: &Value) -> Result<Value, AppError> {
+        let date = |k: &str| data[k].as_str().and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        let row = sqlx::query(&format!(
+            "INSERT INTO employee_documents (document_id, school_id, employee_id, document_type, title, file_name,
+                content_type, size_bytes, storage_key, education_id, experience_id, document_number, issued_on,
+                expires_on, uploaded_by)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+             RETURNING {}",
+            EMPLOYEE_DOCUMENT_COLUMNS
+        ))
+        .bind(data["documentId"].as_str())
+        .bind(school_id)
+        .bind(data["employeeId"].as_str())
+        .bind(data["documentType"].as_str())
+        .bind(data["title"].as_str())
+        .bind(data["fileName"].as_str())
+        .bind(data["contentType"].as_str())
+        .bind(data["sizeBytes"].as_i64().unwrap_or(0))
+        .bind(data["storageKey"].as_str())
+        .bind(data["educationId"].as_i64().map(|id| id as i32))
+        .bind(data["experienceId"].as_i64().map(|id| id as i32))
+        .bind(data["documentNumber"].as_str())
+        .bind(date("issuedOn"))
+        .bind(date("expiresOn"))
+        .bind(data["uploadedBy"].as_str())
+        .fetch_one(&self.client.pool)
+        .await?;
+        Ok(employee_document_json(&row))
+    }
+
+    async fn get_documents(&self, school_id: &str, employee_id: Option<&str>) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM employee_documents
+             WHERE school_id = $1 AND ($2::TEXT IS NULL OR employee_id = $2)
+             ORDER BY created_at DESC",
+            EMPLOYEE_DOCUMENT_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(employee_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(employee_document_json).collect())
+    }
+
+    async fn get_document(&self, school_id: &str, document_id: &str) -> Result<Option<Value>, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM employee_documents WHERE school_id = $1 AND document_id = $2",
+            EMPLOYEE_DOCUMENT_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(document_id)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(employee_document_json))
+    }
+
+    async fn set_document_verification(
+        &self,
+        school_id: &str,
+        document_id: &str,
+        status: &str,
+        verified_by: &str,
+        remarks: Option<&str>,
+    ) -> Result<Option<Value>, AppError> {
+        let row = sqlx::query(&format!(
+            "UPDATE employee_documents
+             SET verification_status = $3, verified_by = $4, verified_at = NOW(), verification_remarks = $5
+             WHERE school_id = $1 AND document_id = $2
+             RETURNING {}",
+            EMPLOYEE_DOCUMENT_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(document_id)
+        .bind(status)
+        .bind(verified_by)
+        .bind(remarks)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(employee_document_json))
+    }
+
+    async fn delete_document(&self, school_id: &str, document_id: &str) -> Result<Option<Value>, AppError> {
+        let row = sqlx::query(&format!(
+            "DELETE FROM employee_documents WHERE school_id = $1 AND document_id = $2 RETURNING {}",
+            EMPLOYEE_DOCUMENT_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(document_id)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(employee_document_json))
+    }
+
+    async fn link_education_document(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        education_id: i32,
+        url: Option<&str>,
+    ) -> Result<bool, AppError> {
+        let res = sqlx::query(
+            "UPDATE employee_education SET document_url = $4 WHERE school_id = $1 AND employee_id = $2 AND id = $3",
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(education_id)
+        .bind(url)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn link_experience_document(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        experience_id: i32,
+        url: Option<&str>,
+    ) -> Result<bool, AppError> {
+        let res = sqlx::query(
+            "UPDATE employee_experience SET experience_letter_url = $4 WHERE school_id = $1 AND employee_id = $2 AND id = $3",
+        )
+        .bind(school_id)
+        .bind(employee_id)
+        .bind(experience_id)
+        .bind(url)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+// --- Attendance Repository ---
+pub struct PostgresAttendanceRepository {
+    pub client: Arc<DbClient>,
+}
+
+#[async_trait]
+impl AttendanceRepository for PostgresAttendanceRepository {
+    async fn get_attendance_settings(&self, school_id: &str) -> Result<Value, AppError> {
+        let row = sqlx::query("SELECT settings FROM attendance_settings WHERE school_id = $1")
+            .bind(school_id)
+            .fetch_optional(&self.client.pool)
+            .await?;
+        Ok(row.map(|r| r.get::<Value, _>("settings")).unwrap_or(Value::Null))
+    }
+
+    async fn save_attendance_settings(
+        &self,
+        school_id: &str,
+        settings: &Value,
+        updated_by: Option<&str>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO attendance_settings (school_id, settings, updated_by)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (school_id) DO UPDATE SET settings = $2, updated_by = $3, updated_at = NOW()",
+        )
+        .bind(school_id)
+        .bind(settings)
+        .bind(updated_by)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_attendance_for_users(
+        &self,
+        school_id: &str,
+        role: &str,
+        date: chrono::NaiveDate,
+        user_ids: &[String],
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT user_id, status, reason, in_time, out_time, source, created_at, updated_at FROM attendance
+             WHERE school_id = $1 AND role = $2 AND date = $3 AND user_id = ANY($4)",
+        )
+        .bind(school_id)
+        .bind(role)
+        .bind(date)
+        .bind(user_ids)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "userId": r.get::<String, _>("user_id"),
+                    "status": r.get::<String, _>("status"),
+                    "reason": r.get::<Option<String>, _>("reason"),
+                    "inTime": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("in_time").map(|t| t.to_rfc3339()),
+                    "outTime": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("out_time").map(|t| t.to_rfc3339()),
+                    "source": r.get::<Option<String>, _>("source"),
+                    "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+                    "updatedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("updated_at").map(|t| t.to_rfc3339()),
+                })
+            })
+            .collect())
+    }
+
+    async fn save_bulk_attendance(
+        &self,
+        school_id: &str,
+        role: &str,
+        date: chrono::NaiveDate,
+        rows: &[Value],
+        history: &[Value],
+    ) -> Result<(), AppError> {
+        let text = |list: &[Value], key: &str| -> Vec<Option<String>> {
+            list.iter().map(|v| v[key].as_str().map(|s| s.to_string())).collect()
+        };
+        let mut tx = self.client.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO attendance (school_id, role, user_id, date, status, reason)
+             SELECT $1, $2, u, $3, s, r FROM UNNEST($4::TEXT[], $5::TEXT[], $6::TEXT[]) AS t(u, s, r)
+             ON CONFLICT (school_id, role, user_id, date) DO UPDATE SET
+                status = EXCLUDED.status, reason = EXCLUDED.reason, source = NULL, updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(school_id)
+        .bind(role)
+        .bind(date)
+        .bind(text(rows, "userId"))
+        .bind(text(rows, "status"))
+        .bind(text(rows, "reason"))
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO audit_logs (school_id, target_type, target_id, action, data)
+             SELECT $1, 'attendance', u, a, d FROM UNNEST($2::TEXT[], $3::TEXT[], $4::JSONB[]) AS t(u, a, d)",
+        )
+        .bind(school_id)
+        .bind(text(history, "userId"))
+        .bind(text(history, "action"))
+        .bind(history.iter().map(|h| h["data"].clone()).collect::<Vec<Value>>())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn save_timed_attendance(
+        &self,
+        school_id: &str,
+        role: &str,
+        user_id: &str,
+        date: chrono::NaiveDate,
+        data: &Value,
+    ) -> Result<(), AppError> {
+        let time = |k: &str| {
+            data[k]
+                .as_str()
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.with_timezone(&chrono::Utc))
+        };
+        sqlx::query(
+            "INSERT INTO attendance (school_id, role, user_id, date, status, in_time, out_time, total_time, reason, source)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, 'device')
+             ON CONFLICT (school_id, role, user_id, date) DO UPDATE SET
+                status = EXCLUDED.status, in_time = EXCLUDED.in_time, out_time = EXCLUDED.out_time,
+                total_time = EXCLUDED.total_time, reason = EXCLUDED.reason, source = 'device', updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(school_id)
+        .bind(role)
+        .bind(user_id)
+        .bind(date)
+        .bind(data["status"].as_str())
+        .bind(time("inTime"))
+        .bind(time("outTime"))
+        .bind(data["totalTime"].as_str())
+        .bind(data["reason"].as_str())
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_attendance_between(
+        &self,
+        school_id: &str,
+        role: &str,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+        user_ids: &[String],
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT user_id, date, status, reason FROM attendance
+             WHERE school_id = $1 AND role = $2 AND date BETWEEN $3 AND $4 AND user_id = ANY($5)
+             ORDER BY date",
+        )
+        .bind(school_id)
+        .bind(role)
+        .bind(from)
+        .bind(to)
+        .bind(user_ids)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "userId": r.get::<String, _>("user_id"),
+                    "date": r.get::<chrono::NaiveDate, _>("date").to_string(),
+                    "status": r.get::<String, _>("status"),
+                    "reason": r.get::<Option<String>, _>("reason"),
+                })
+            })
+            .collect())
+    }
+
+    async fn get_school_holidays(
+        &self,
+        school_id: &str,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+    ) -> Result<Vec<Value>, AppError> {
+        // Dates are YYYY-MM-DD text, which compares in date order
+        let rows = sqlx::query(
+            "SELECT id, title, description, from_date, to_date, classes, exempt_employees, exempt_students FROM school_holidays
+             WHERE school_id = $1 AND from_date <= $3 AND COALESCE(NULLIF(to_date, ''), from_date) >= $2
+             ORDER BY from_date",
+        )
+        .bind(school_id)
+        .bind(from.to_string())
+        .bind(to.to_string())
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                let from_date = r.get::<String, _>("from_date");
+                let to_date = r.get::<String, _>("to_date");
+                json!({
+                    "id": r.get::<String, _>("id"),
+                    "title": r.get::<String, _>("title"),
+                    "description": r.get::<Option<String>, _>("description").unwrap_or_default(),
+                    "toDate": if to_date.is_empty() { from_date.clone() } else { to_date },
+                    "fromDate": from_date,
+                    "classes": r.get::<Option<Value>, _>("classes").unwrap_or(json!([])),
+                    "exemptEmployees": r.get::<Option<Value>, _>("exempt_employees").unwrap_or(json!([])),
+                    "exemptStudents": r.get::<Option<Value>, _>("exempt_students").unwrap_or(json!([])),
+                })
+            })
+            .collect())
+    }
+
+    async fn get_absence_alerts(&self, school_id: &str, date: chrono::NaiveDate) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT a.student_id, a.date, a.attendance_status, a.channel, a.recipient, a.notification_id, a.status,
+                    a.attempts, a.last_attempt_at, a.reply, a.replied_via, a.replied_at, a.created_at,
+                    s.name, s.class_name, s.section,
+                    n.status AS delivery_status, n.status_updated_at
+             FROM absence_alerts a
+             LEFT JOIN students s ON s.school_id = a.school_id AND s.student_id = a.student_id
+             LEFT JOIN notification_log n ON n.notification_id = a.notification_id
+             WHERE a.school_id = $1 AND a.date = $2
+             ORDER BY s.class_name, s.section, s.roll_number, s.name",
+        )
+        .bind(school_id)
+        .bind(date)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "studentId": r.get::<String, _>("student_id"),
+                    "name": r.get::<Option<String>, _>("name"),
+                    "className": r.get::<Option<String>, _>("class_name"),
+                    "section": r.get::<Option<String>, _>("section"),
+                    "date": r.get::<chrono::NaiveDate, _>("date").to_string(),
+                    "attendanceStatus": r.get::<String, _>("attendance_status"),
+                    "channel": r.get::<String, _>("channel"),
+                    "recipient": r.get::<Option<String>, _>("recipient"),
+                    "notificationId": r.get::<Option<String>, _>("notification_id"),
+                    "status": r.get::<String, _>("status"),
+                    "attempts": r.get::<i32, _>("attempts"),
+                    "lastAttemptAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("last_attempt_at").map(|t| t.to_rfc3339()),
+                    "deliveryStatus": r.get::<Option<String>, _>("delivery_status"),
+                    "deliveryUpdatedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("status_updated_at").map(|t| t.to_rfc3339()),
+                    "reply": r.get::<Option<String>, _>("reply"),
+                    "repliedVia": r.get::<Option<String>, _>("replied_via"),
+                    "repliedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("replied_at").map(|t| t.to_rfc3339()),
+                    "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+                })
+            })
+            .collect())
+    }
+
+    async fn save_absence_alert(&self, school_id: &str, data: &Value) -> Result<(), AppError> {
+        let date = data["date"]
+            .as_str()
+            .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            .ok_or("date is required")?;
+        sqlx::query(
+            "INSERT INTO absence_alerts (school_id, student_id, date, attendance_status, channel, recipient, notification_id, status,
+                attempts, last_attempt_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 1, NOW())
+             ON CONFLICT (school_id, student_id, date) DO UPDATE SET
+                attendance_status = EXCLUDED.attendance_status, channel = EXCLUDED.channel, recipient = EXCLUDED.recipient,
+                notification_id = EXCLUDED.notification_id, status = EXCLUDED.status, created_at = NOW(),
+                attempts = absence_alerts.attempts + 1, last_attempt_at = NOW()",
+        )
+        .bind(school_id)
+        .bind(data["studentId"].as_str())
+        .bind(date)
+        .bind(data["attendanceStatus"].as_str().unwrap_or("absent"))
+        .bind(data["channel"].as_str().unwrap_or("sms"))
+        .bind(data["recipient"].as_str())
+        .bind(data["notificationId"].as_str())
+        .bind(data["status"].as_str().unwrap_or("sent"))
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn find_absence_alert_for_reply(
+        &self,
+        sender: &str,
+        since: chrono::NaiveDate,
+    ) -> Result<Option<Value>, AppError> {
+        let sender = sender.trim();
+        let (clause, key) = if sender.contains('@') {
+            ("LOWER(recipient) = LOWER($1)", sender.to_string())
+        } else {
+            let digits: String = sender.chars().filter(|c| c.is_ascii_digit()).collect();
+            let tail = digits[digits.len().saturating_sub(10)..].to_string();
+            ("RIGHT(regexp_replace(recipient, '[^0-9]', '', 'g'), 10) = $1", tail)
+        };
+        if key.is_empty() {
+            return Ok(None);
+        }
+        let row = sqlx::query(&format!(
+            "SELECT school_id, student_id, date FROM absence_alerts
+             WHERE {} AND date >= $2 AND status = 'sent'
+             ORDER BY (reply IS NULL) DESC, date DESC, created_at DESC LIMIT 1",
+            clause
+        ))
+        .bind(key)
+        .bind(since)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.map(|r| {
+            json!({
+                "schoolId": r.get::<String, _>("school_id"),
+                "studentId": r.get::<String, _>("student_id"),
+                "date": r.get::<chrono::NaiveDate, _>("date").to_string(),
+            })
+        }))
+    }
+
+    async fn save_absence_reason(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        date: chrono::NaiveDate,
+        reason: &str,
+        via: &str,
+    ) -> Result<(), AppError> {
+        let mut tx = self.client.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO attendance (school_id, role, user_id, date, status, reason)
+             VALUES ($1, 'student', $2, $3, 'absent', $4)
+             ON CONFLICT (school_id, role, user_id, date) DO UPDATE SET
+                reason = EXCLUDED.reason, updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(school_id)
+        .bind(student_id)
+        .bind(date)
+        .bind(reason)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE absence_alerts SET reply = $4, replied_via = $5, replied_at = NOW()
+             WHERE school_id = $1 AND student_id = $2 AND date = $3",
+        )
+        .bind(school_id)
+        .bind(student_id)
+        .bind(date)
+        .bind(reason)
+        .bind(via)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO audit_logs (school_id, target_type, target_id, action, data)
+             VALUES ($1, 'attendance', $2, 'absence_reason', $3)",
+        )
+        .bind(school_id)
+        .bind(student_id)
+        .bind(json!({"date": date.to_string(), "reason": reason, "via": via}))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_holiday_rules(&self, school_id: &str) -> Result<Value, AppError> {
+        let row = sqlx::query("SELECT rules FROM holiday_rules WHERE school_id = $1")
+            .bind(school_id)
+            .fetch_optional(&self.client.pool)
+            .await?;
+        Ok(row.map(|r| r.get::<Value, _>("rules")).unwrap_or(Value::Null))
+    }
+
+    async fn save_holiday_rules(&self, school_id: &str, rules: &Value, updated_by: Option<&str>) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO holiday_rules (school_id, rules, updated_by)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (school_id) DO UPDATE SET rules = $2, updated_by = $3, updated_at = NOW()",
+        )
+        .bind(school_id)
+        .bind(rules)
+        .bind(updated_by)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn add_school_holidays(&self, school_id: &str, holidays: &[Value]) -> Result<Vec<Value>, AppError> {
+        let mut tx = self.client.pool.begin().await?;
+        let created_at = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let mut added = Vec::new();
+        for h in holidays {
+            let title = h["title"].as_str().unwrap_or("Holiday").trim();
+            let from_date = h["fromDate"].as_str().unwrap_or("");
+            let to_date = h["toDate"].as_str().unwrap_or(from_date);
+            let exists = sqlx::query(
+                "SELECT 1 FROM school_holidays WHERE school_id = $1 AND from_date = $2 AND LOWER(TRIM(title)) = LOWER($3)",
+            )
+            .bind(school_id)
+            .bind(from_date)
+            .bind(title)
+            .fetch_optional(&mut *tx)
+            .await?;
+            if exists.is_some() {
+                continue;
+            }
+            let id = uuid::Uuid::new_v4().to_string();
+            sqlx::query(
+                "INSERT INTO school_holidays (id, school_id, title, description, from_date, to_date, classes, exempt_employees, exempt_students, created_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, '[]', '[]', '[]', $7)",
+            )
+            .bind(&id)
+            .bind(school_id)
+            .bind(title)
+            .bind(h["description"].as_str().unwrap_or(""))
+            .bind(from_date)
+            .bind(to_date)
+            .bind(&created_at)
+            .execute(&mut *tx)
+            .await?;
+            added.push(json!({"id": id, "title": title, "fromDate": from_date, "toDate": to_date}));
+        }
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    async fn add_self_checkin(&self, school_id: &str, data: &Value) -> Result<Value, AppError> {
+        let date = data["date"]
+            .as_str()
+            .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            .ok_or("date is required")?;
+        let row = sqlx::query(&format!(
+            "INSERT INTO self_checkins (school_id, employee_id, date, action, method, accepted, reason, device_id,
+                latitude, longitude, accuracy_meters, distance_meters, flags, display_id)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+             RETURNING {}",
+            SELF_CHECKIN_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(data["employeeId"].as_str())
+        .bind(date)
+        .bind(data["action"].as_str())
+        .bind(data["method"].as_str())
+        .bind(data["accepted"].as_bool().unwrap_or(false))
+        .bind(data["reason"].as_str())
+        .bind(data["deviceId"].as_str())
+        .bind(data["latitude"].as_f64())
+        .bind(data["longitude"].as_f64())
+        .bind(data["accuracyMeters"].as_f64())
+        .bind(data["distanceMeters"].as_f64())
+        .bind(if data["flags"].is_array() { data["flags"].clone() } else { json!([]) })
+        .bind(data["displayId"].as_str())
+        .fetch_one(&self.client.pool)
+        .await?;
+        Ok(self_checkin_json(&row))
+    }
+
+    async fn get_self_checkins(
+        &self,
+        school_id: &str,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+        employee_id: Option<&str>,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM self_checkins
+             WHERE school_id = $1 AND date BETWEEN $2 AND $3 AND ($4::TEXT IS NULL OR employee_id = $4)
+             ORDER BY created_at DESC",
+            SELF_CHECKIN_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(from)
+        .bind(to)
+        .bind(employee_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(self_checkin_json).collect())
+    }
+
+    async fn get_device_checkin_employees(
+        &self,
+        school_id: &str,
+        device_id: &str,
+        since: chrono::NaiveDate,
+        exclude_employee_id: &str,
+    ) -> Result<Vec<String>, AppError> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT employee_id FROM self_checkins
+             WHERE school_id = $1 AND device_id = $2 AND date >= $3 AND employee_id <> $4 AND accepted
+             ORDER BY employee_id",
+        )
+        .bind(school_id)
+        .bind(device_id)
+        .bind(since)
+        .bind(exclude_employee_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.into_iter().map(|r| r.get::<String, _>("employee_id")).collect())
+    }
+
+    async fn add_correction_request(&self, school_id: &str, data: &Value) -> Result<Value, AppError> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO attendance_corrections
+                (request_id, school_id, role, user_id, date, requested, current_record, locked, reason, requested_by, requested_via)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+             RETURNING {}",
+            CORRECTION_COLUMNS
+        ))
+        .bind(data["requestId"].as_str())
+        .bind(school_id)
+        .bind(data["role"].as_str())
+        .bind(data["userId"].as_str())
+        .bind(data["date"].as_str().and_then(|d| d.parse::<chrono::NaiveDate>().ok()))
+        .bind(&data["requested"])
+        .bind(&data["currentRecord"])
+        .bind(data["locked"].as_bool().unwrap_or(false))
+        .bind(data["reason"].as_str())
+        .bind(data["requestedBy"].as_str())
+        .bind(data["requestedVia"].as_str())
+        .fetch_one(&self.client.pool)
+        .await?;
+        Ok(correction_json(&row))
+    }
+
+    async fn get_correction_requests(&self, school_id: &str, filter: &Value) -> Result<Vec<Value>, AppError> {
+        let date = |key: &str| filter[key].as_str().and_then(|d| d.parse::<chrono::NaiveDate>().ok());
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM attendance_corrections
+             WHERE school_id = $1
+               AND ($2::TEXT IS NULL OR status = $2)
+               AND ($3::TEXT IS NULL OR role = $3)
+               AND ($4::TEXT IS NULL OR user_id = $4)
+               AND ($5::DATE IS NULL OR date >= $5)
+               AND ($6::DATE IS NULL OR date <= $6)
+             ORDER BY created_at DESC
+             LIMIT 500",
+            CORRECTION_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(filter["status"].as_str())
+        .bind(filter["role"].as_str())
+        .bind(filter["userId"].as_str())
+        .bind(date("from"))
+        .bind(date("to"))
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(correction_json).collect())
+    }
+
+    async fn get_correction_request(&self, school_id: &str, request_id: &str) -> Result<Option<Value>, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM attendance_corrections WHERE school_id = $1 AND request_id = $2",
+            CORRECTION_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(request_id)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(correction_json))
+    }
+
+    async fn save_correction_evidence(&self, school_id: &str, request_id: &str, evidence: &Value) -> Result<(), AppError> {
+        sqlx::query("UPDATE attendance_corrections SET evidence = $3 WHERE school_id = $1 AND request_id = $2")
+            .bind(school_id)
+            .bind(request_id)
+            .bind(evidence)
+            .execute(&self.client.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn close_correction_request(
+        &self,
+        school_id: &str,
+        request_id: &str,
+        status: &str,
+        decided_by: Option<&str>,
+        note: Option<&str>,
+    ) -> Result<bool, AppError> {
+        let done = sqlx::query(
+            "UPDATE attendance_corrections SET status = $3, decided_by = $4, decision_note = $5, decided_at = NOW()
+             WHERE school_id = $1 AND request_id = $2 AND status = 'pending'",
+        )
+        .bind(school_id)
+        .bind(request_id)
+        .bind(status)
+        .bind(decided_by)
+        .bind(note)
+        .execute(&self.client.pool)
+        .await?;
+        Ok(done.rows_affected() > 0)
+    }
+
+    async fn apply_attendance_correction(
+        &self,
+        school_id: &str,
+        request: &Value,
+        after: Option<&Value>,
+        decision: &Value,
+        history: &Value,
+    ) -> Result<(), AppError> {
+        let role = request["role"].as_str().unwrap_or("");
+        let user_id = request["userId"].as_str().unwrap_or("");
+        let date = request["date"].as_str().unwrap_or("").parse::<chrono::NaiveDate>()?;
+        let time = |v: &Value| {
+            v.as_str()
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.with_timezone(&chrono::Utc))
+        };
+
+        let mut tx = self.client.pool.begin().await?;
+        let approved = sqlx::query(
+            "UPDATE attendance_corrections SET status = 'approved', decided_by = $3, decision_note = $4, decided_at = NOW()
+             WHERE school_id = $1 AND request_id = $2 AND status = 'pending'",
+        )
+        .bind(school_id)
+        .bind(request["requestId"].as_str())
+        .bind(decision["decidedBy"].as_str())
+        .bind(decision["note"].as_str())
+        .execute(&mut *tx)
+        .await?;
+        if approved.rows_affected() == 0 {
+            return Err("The request is no longer pending".into());
+        }
+        match after {
+            Some(record) => {
+                sqlx::query(
+                    "INSERT INTO attendance (school_id, role, user_id, date, status, in_time, out_time, total_time, reason)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                     ON CONFLICT (school_id, role, user_id, date) DO UPDATE SET
+                        status = EXCLUDED.status, in_time = EXCLUDED.in_time, out_time = EXCLUDED.out_time,
+                        total_time = EXCLUDED.total_time, reason = EXCLUDED.reason, source = NULL, updated_at = CURRENT_TIMESTAMP",
+                )
+                .bind(school_id)
+                .bind(role)
+                .bind(user_id)
+                .bind(date)
+                .bind(record["status"].as_str())
+                .bind(time(&record["inTime"]))
+                .bind(time(&record["outTime"]))
+                .bind(record["totalTime"].as_str())
+                .bind(record["reason"].as_str())
+                .execute(&mut *tx)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM attendance WHERE school_id = $1 AND role = $2 AND user_id = $3 AND date = $4")
+                    .bind(school_id)
+                    .bind(role)
+                    .bind(user_id)
+                    .bind(date)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        sqlx::query(
+            "INSERT INTO audit_logs (school_id, target_type, target_id, action, data)
+             VALUES ($1, 'attendance', $2, 'correction_applied', $3)",
+        )
+        .bind(school_id)
+        .bind(user_id)
+        .bind(history)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_attendance_history(
+        &self,
+        school_id: &str,
+        user_id: &str,
+        from: Option<chrono::NaiveDate>,
+        to: Option<chrono::NaiveDate>,
+    ) -> Result<Vec<Value>, AppError> {
+        // Entries carry the attendance date as YYYY-MM-DD text, which compares in date order
+        let rows = sqlx::query(
+            "SELECT action, data, created_at FROM audit_logs
+             WHERE school_id = $1 AND target_type = 'attendance' AND target_id = $2
+               AND ($3::TEXT IS NULL OR data->>'date' >= $3)
+               AND ($4::TEXT IS NULL OR data->>'date' <= $4)
+             ORDER BY created_at DESC
+             LIMIT 500",
+        )
+        .bind(school_id)
+        .bind(user_id)
+        .bind(from.map(|d| d.to_string()))
+        .bind(to.map(|d| d.to_string()))
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "action": r.get::<String, _>("action"),
+                    "data": r.get::<Option<Value>, _>("data"),
+                    "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+                })
+            })
+            .collect())
+    }
+
+    async fn get_period_attendance(&self, school_id: &str, date: chrono::NaiveDate, period_id: i32) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT student_id, status, remarks, marked_by, created_at, updated_at FROM period_attendance
+             WHERE school_id = $1 AND date = $2 AND period_id = $3",
+        )
+        .bind(school_id)
+        .bind(date)
+        .bind(period_id)
         .fetch_all(&self.client.pool)
         .await?;
-
         Ok(rows
             .into_iter()
             .map(|r| {
                 json!({
-                    "leaveId": r.get::<String, _>("leave_id"),
-                    "employeeId": r.get::<String, _>("employee_id"),
-                    "employeeName": r.get::<Option<String>, _>("employee_name").unwrap_or_default(),
-                    "reason": r.get::<String, _>("reason"),
-                    "leaveType": r.get::<String, _>("leave_type"),
-                    "fromDate": r.get::<chrono::NaiveDate, _>("from_date").to_string(),
-                    "toDate": r.get::<chrono::NaiveDate, _>("to_date").to_string(),
+                    "studentId": r.get::<String, _>("student_id"),
                     "status": r.get::<String, _>("status"),
+                    "remarks": r.get::<Option<String>, _>("remarks"),
+                    "markedBy": r.get::<Option<String>, _>("marked_by"),
+                    "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+                    "updatedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("updated_at").map(|t| t.to_rfc3339()),
                 })
             })
             .collect())
     }
 
-    async fn update_leave_status(
+    async fn save_period_attendance(
         &self,
         school_id: &str,
-        leave_id: &str,
-        status: &str,
+        period: &Value,
+        date: chrono::NaiveDate,
+        rows: &[Value],
+        history: &[Value],
     ) -> Result<(), AppError> {
+        let text = |list: &[Value], key: &str| -> Vec<Option<String>> {
+            list.iter().map(|v| v[key].as_str().map(|s| s.to_string())).collect()
+        };
+        let mut tx = self.client.pool.begin().await?;
         sqlx::query(
-            "UPDATE leave_applications SET status = $1 WHERE school_id = $2 AND leave_id = $3",
+            "INSERT INTO period_attendance (school_id, date, period_id, class_id, subject_id, student_id, status, remarks, marked_by)
+             SELECT $1, $2, $3, $4, $5, u, s, r, m FROM UNNEST($6::TEXT[], $7::TEXT[], $8::TEXT[], $9::TEXT[]) AS t(u, s, r, m)
+             ON CONFLICT (school_id, date, period_id, student_id) DO UPDATE SET
+                status = EXCLUDED.status, remarks = EXCLUDED.remarks, marked_by = EXCLUDED.marked_by,
+                subject_id = EXCLUDED.subject_id, updated_at = CURRENT_TIMESTAMP",
         )
-        .bind(status)
         .bind(school_id)
-        .bind(leave_id)
-        .execute(&self.client.pool)
+        .bind(date)
+        .bind(period["periodId"].as_i64().map(|p| p as i32))
+        .bind(period["classId"].as_str())
+        .bind(period["subjectId"].as_str())
+        .bind(text(rows, "studentId"))
+        .bind(text(rows, "status"))
+        .bind(text(rows, "remarks"))
+        .bind(text(rows, "markedBy"))
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO audit_logs (school_id, target_type, target_id, action, data)
+             SELECT $1, 'period_attendance', u, a, d FROM UNNEST($2::TEXT[], $3::TEXT[], $4::JSONB[]) AS t(u, a, d)",
+        )
+        .bind(school_id)
+        .bind(text(history, "userId"))
+        .bind(text(history, "action"))
+        .bind(history.iter().map(|h| h["data"].clone()).collect::<Vec<Value>>())
+        .execute(&mut *tx)
         .await?;
+        tx.commit().await?;
         Ok(())
     }
 
-    async fn update_leave_duration(
+    async fn get_period_attendance_range(
         &self,
         school_id: &str,
-        leave_id: &str,
-        action: &str,
-        days: i32,
-    ) -> Result<(), AppError> {
-        let op = if action == "extend" { "+" } else { "-" };
-        let q = format!("UPDATE leave_applications SET to_date = to_date {} ($1 || ' days')::interval WHERE school_id = $2 AND leave_id = $3", op);
-        sqlx::query(&q)
-            .bind(days.to_string())
+        class_id: Option<&str>,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT date, period_id, class_id, subject_id, student_id, status FROM period_attendance
+             WHERE school_id = $1 AND date BETWEEN $2 AND $3 AND ($4::TEXT IS NULL OR class_id = $4)
+             ORDER BY date, period_id",
+        )
+        .bind(school_id)
+        .bind(from)
+        .bind(to)
+        .bind(class_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "date": r.get::<chrono::NaiveDate, _>("date").to_string(),
+                    "periodId": r.get::<i32, _>("period_id"),
+                    "classId": r.get::<String, _>("class_id"),
+                    "subjectId": r.get::<Option<String>, _>("subject_id"),
+                    "studentId": r.get::<String, _>("student_id"),
+                    "status": r.get::<String, _>("status"),
+                })
+            })
+            .collect())
+    }
+}
+
+const CORRECTION_COLUMNS: &str = "request_id, role, user_id, date, requested, current_record, locked, reason, evidence, status, requested_by, requested_via, decided_by, decision_note, decided_at, created_at";
+
+fn correction_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "requestId": r.get::<String, _>("request_id"),
+        "role": r.get::<String, _>("role"),
+        "userId": r.get::<String, _>("user_id"),
+        "date": r.get::<chrono::NaiveDate, _>("date").to_string(),
+        "requested": r.get::<Value, _>("requested"),
+        "currentRecord": r.get::<Option<Value>, _>("current_record"),
+        "locked": r.get::<bool, _>("locked"),
+        "reason": r.get::<String, _>("reason"),
+        "evidence": r.get::<Value, _>("evidence"),
+        "status": r.get::<String, _>("status"),
+        "requestedBy": r.get::<Option<String>, _>("requested_by"),
+        "requestedVia": r.get::<Option<String>, _>("requested_via"),
+        "decidedBy": r.get::<Option<String>, _>("decided_by"),
+        "decisionNote": r.get::<Option<String>, _>("decision_note"),
+        "decidedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("decided_at").map(|t| t.to_rfc3339()),
+        "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+    })
+}
+
+const SELF_CHECKIN_COLUMNS: &str = "id, employee_id, date, action, method, accepted, reason, device_id, display_id, latitude, longitude, accuracy_meters, distance_meters, flags, created_at";
+
+fn self_checkin_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "id": r.get::<i32, _>("id"),
+        "employeeId": r.get::<String, _>("employee_id"),
+        "date": r.get::<chrono::NaiveDate, _>("date").to_string(),
+        "action": r.get::<String, _>("action"),
+        "method": r.get::<String, _>("method"),
+        "accepted": r.get::<bool, _>("accepted"),
+        "reason": r.get::<Option<String>, _>("reason"),
+        "deviceId": r.get::<Option<String>, _>("device_id"),
+        "displayId": r.get::<Option<String>, _>("display_id"),
+        "latitude": r.get::<Option<f64>, _>("latitude"),
+        "longitude": r.get::<Option<f64>, _>("longitude"),
+        "accuracyMeters": r.get::<Option<f64>, _>("accuracy_meters"),
+        "distanceMeters": r.get::<Option<f64>, _>("distance_meters"),
+        "flags": r.get::<Value, _>("flags"),
+        "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+    })
+}
+
+// --- Device Repository ---
+pub struct PostgresDeviceRepository {
+    pub client: Arc<DbClient>,
+}
+
+const DEVICE_COLUMNS: &str = "device_id, school_id, serial_number, name, protocol, location, api_key, active, last_seen_at, created_at";
+
+fn device_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "deviceId": r.get::<String, _>("device_id"),
+        "serialNumber": r.get::<String, _>("serial_number"),
+        "name": r.get::<Option<String>, _>("name"),
+        "protocol": r.get::<String, _>("protocol"),
+        "location": r.get::<Option<String>, _>("location"),
+        "active": r.get::<bool, _>("active"),
+        "lastSeenAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("last_seen_at").map(|t| t.to_rfc3339()),
+        "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+    })
+}
+
+const PUNCH_COLUMNS: &str = "id, device_id, enroll_id, punch_time, punch_type, verify_mode, role, user_id, received_at";
+
+fn punch_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "punchId": r.get::<i32, _>("id"),
+        "deviceId": r.get::<String, _>("device_id"),
+        "enrollId": r.get::<String, _>("enroll_id"),
+        "time": r.get::<chrono::NaiveDateTime, _>("punch_time").format("%Y-%m-%d %H:%M:%S").to_string(),
+        "type": r.get::<String, _>("punch_type"),
+        "verifyMode": r.get::<Option<String>, _>("verify_mode"),
+        "role": r.get::<Option<String>, _>("role"),
+        "userId": r.get::<Option<String>, _>("user_id"),
+        "receivedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("received_at").map(|t| t.to_rfc3339()),
+    })
+}
+
+#[async_trait]
+impl DeviceRepository for PostgresDeviceRepository {
+    async fn create_device(&self, school_id: &str, data: &Value) -> Result<Value, AppError> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO attendance_devices (device_id, school_id, serial_number, name, protocol, location, api_key)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             RETURNING {}",
+            DEVICE_COLUMNS
+        ))
+        .bind(data["deviceId"].as_str())
+        .bind(school_id)
+        .bind(data["serialNumber"].as_str())
+        .bind(data["name"].as_str())
+        .bind(data["protocol"].as_str().unwrap_or("adms"))
+        .bind(data["location"].as_str())
+        .bind(data["apiKey"].as_str())
+        .fetch_one(&self.client.pool)
+        .await?;
+        Ok(device_json(&row))
+    }
+
+    async fn get_devices(&self, school_id: &str) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM attendance_devices WHERE school_id = $1 ORDER BY created_at",
+            DEVICE_COLUMNS
+        ))
+        .bind(school_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(device_json).collect())
+    }
+
+    async fn find_device_by_serial(&self, serial_number: &str) -> Result<Option<Value>, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM attendance_devices WHERE serial_number = $1",
+            DEVICE_COLUMNS
+        ))
+        .bind(serial_number)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.map(|r| {
+            let mut device = device_json(&r);
+            device["schoolId"] = json!(r.get::<String, _>("school_id"));
+            device["apiKey"] = json!(r.get::<String, _>("api_key"));
+            device
+        }))
+    }
+
+    async fn update_device(&self, school_id: &str, device_id: &str, data: &Value) -> Result<Option<Value>, AppError> {
+        let row = sqlx::query(&format!(
+            "UPDATE attendance_devices SET
+                name = COALESCE($3, name), location = COALESCE($4, location),
+                active = COALESCE($5, active), api_key = COALESCE($6, api_key)
+             WHERE school_id = $1 AND device_id = $2
+             RETURNING {}",
+            DEVICE_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(device_id)
+        .bind(data["name"].as_str())
+        .bind(data["location"].as_str())
+        .bind(data["active"].as_bool())
+        .bind(data["apiKey"].as_str())
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(device_json))
+    }
+
+    async fn delete_device(&self, school_id: &str, device_id: &str) -> Result<bool, AppError> {
+        let res = sqlx::query("DELETE FROM attendance_devices WHERE school_id = $1 AND device_id = $2")
+            .bind(school_id)
+            .bind(device_id)
+            .execute(&self.client.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn touch_device(&self, device_id: &str) -> Result<(), AppError> {
+        sqlx::query("UPDATE attendance_devices SET last_seen_at = NOW() WHERE device_id = $1")
+            .bind(device_id)
+            .execute(&self.client.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_enrollments(&self, school_id: &str) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT enroll_id, role, user_id, created_at FROM device_enrollments WHERE school_id = $1 ORDER BY enroll_id",
+        )
+        .bind(school_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "enrollId": r.get::<String, _>("enroll_id"),
+                    "role": r.get::<String, _>("role"),
+                    "userId": r.get::<String, _>("user_id"),
+                    "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+                })
+            })
+            .collect())
+    }
+
+    async fn save_enrollments(&self, school_id: &str, entries: &[Value]) -> Result<(), AppError> {
+        let text = |key: &str| -> Vec<String> { entries.iter().map(|e| e[key].as_str().unwrap_or("").to_string()).collect() };
+        sqlx::query(
+            "INSERT INTO device_enrollments (school_id, enroll_id, role, user_id)
+             SELECT $1, e, r, u FROM UNNEST($2::TEXT[], $3::TEXT[], $4::TEXT[]) AS t(e, r, u)
+             ON CONFLICT (school_id, enroll_id) DO UPDATE SET role = EXCLUDED.role, user_id = EXCLUDED.user_id",
+        )
+        .bind(school_id)
+        .bind(text("enrollId"))
+        .bind(text("role"))
+        .bind(text("userId"))
+        .execute(&self.client.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_enrollment(&self, school_id: &str, enroll_id: &str) -> Result<bool, AppError> {
+        let res = sqlx::query("DELETE FROM device_enrollments WHERE school_id = $1 AND enroll_id = $2")
             .bind(school_id)
-            .bind(leave_id)
+            .bind(enroll_id)
             .execute(&self.client.pool)
             .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn add_punches(&self, school_id: &str, device_id: &str, punches: &[Value]) -> Result<Vec<Value>, AppError> {
+        let text = |key: &str| -> Vec<Option<String>> { punches.iter().map(|p| p[key].as_str().map(|s| s.to_string())).collect() };
+        let times: Vec<Option<chrono::NaiveDateTime>> = punches
+            .iter()
+            .map(|p| p["time"].as_str().and_then(|t| chrono::NaiveDateTime::parse_from_str(t, "%Y-%m-%d %H:%M:%S").ok()))
+            .collect();
+        let rows = sqlx::query(&format!(
+            "INSERT INTO device_punches (school_id, device_id, enroll_id, punch_time, punch_type, verify_mode, role, user_id, raw)
+             SELECT $1, $2, t.e, t.pt, COALESCE(t.ty, 'unknown'), t.v, en.role, en.user_id, t.raw
+             FROM UNNEST($3::TEXT[], $4::TIMESTAMP[], $5::TEXT[], $6::TEXT[], $7::TEXT[]) AS t(e, pt, ty, v, raw)
+             LEFT JOIN device_enrollments en ON en.school_id = $1 AND en.enroll_id = t.e
+             WHERE t.pt IS NOT NULL
+             ON CONFLICT (device_id, enroll_id, punch_time) DO NOTHING
+             RETURNING {}",
+            PUNCH_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(device_id)
+        .bind(text("enrollId"))
+        .bind(times)
+        .bind(text("type"))
+        .bind(text("verifyMode"))
+        .bind(text("raw"))
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(punch_json).collect())
+    }
+
+    async fn get_punches(
+        &self,
+        school_id: &str,
+        date: chrono::NaiveDate,
+        unmatched_only: bool,
+    ) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM device_punches
+             WHERE school_id = $1 AND punch_time::DATE = $2 AND (NOT $3 OR user_id IS NULL)
+             ORDER BY punch_time",
+            PUNCH_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(date)
+        .bind(unmatched_only)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(punch_json).collect())
+    }
+
+    async fn get_user_punch_times(
+        &self,
+        school_id: &str,
+        role: &str,
+        user_id: &str,
+        date: chrono::NaiveDate,
+    ) -> Result<Vec<chrono::NaiveDateTime>, AppError> {
+        let rows = sqlx::query(
+            "SELECT punch_time FROM device_punches
+             WHERE school_id = $1 AND role = $2 AND user_id = $3 AND punch_time::DATE = $4
+             ORDER BY punch_time",
+        )
+        .bind(school_id)
+        .bind(role)
+        .bind(user_id)
+        .bind(date)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(|r| r.get::<chrono::NaiveDateTime, _>("punch_time")).collect())
+    }
+
+    async fn map_unmatched_punches(&self, school_id: &str) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "UPDATE device_punches p SET role = en.role, user_id = en.user_id
+             FROM device_enrollments en
+             WHERE p.school_id = $1 AND p.user_id IS NULL AND en.school_id = p.school_id AND en.enroll_id = p.enroll_id
+             RETURNING p.role, p.user_id, p.punch_time::DATE AS date",
+        )
+        .bind(school_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        let mut affected: Vec<Value> = rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "role": r.get::<String, _>("role"),
+                    "userId": r.get::<String, _>("user_id"),
+                    "date": r.get::<chrono::NaiveDate, _>("date").to_string(),
+                })
+            })
+            .collect();
+        affected.sort_by_key(|a| a.to_string());
+        affected.dedup();
+        Ok(affected)
+    }
+}
+
+// --- Session Repository ---
+pub struct PostgresSessionRepository {
+    pub client: Arc<DbClient>,
+}
+
+const SESSION_COLUMNS: &str = "session_id, name, start_date, end_date, status, created_by, created_at, archived_by, archived_at";
+
+fn session_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "sessionId": r.get::<String, _>("session_id"),
+        "name": r.get::<String, _>("name"),
+        "startDate": r.get::<chrono::NaiveDate, _>("start_date").to_string(),
+        "endDate": r.get::<chrono::NaiveDate, _>("end_date").to_string(),
+        "status": r.get::<String, _>("status"),
+        "createdBy": r.get::<Option<String>, _>("created_by"),
+        "createdAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("created_at").map(|t| t.to_rfc3339()),
+        "archivedBy": r.get::<Option<String>, _>("archived_by"),
+        "archivedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("archived_at").map(|t| t.to_rfc3339()),
+    })
+}
+
+fn enrollment_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "studentId": r.get::<String, _>("student_id"),
+        "classId": r.get::<String, _>("class_id"),
+        "section": r.get::<Option<String>, _>("section"),
+        "rollNumber": r.get::<Option<i32>, _>("roll_number"),
+        "outcome": r.get::<Option<String>, _>("outcome"),
+        "nextClassId": r.get::<Option<String>, _>("next_class_id"),
+        "nextSection": r.get::<Option<String>, _>("next_section"),
+        "carriedBalance": r.get::<f64, _>("carried_balance"),
+        "decidedBy": r.get::<Option<String>, _>("decided_by"),
+        "decidedAt": r.get::<Option<chrono::DateTime<chrono::Utc>>, _>("decided_at").map(|t| t.to_rfc3339()),
+    })
+}
+
+#[async_trait]
+impl SessionRepository for PostgresSessionRepository {
+    async fn create_session(&self, school_id: &str, data: &Value) -> Result<Value, AppError> {
+        let date = |key: &str| data[key].as_str().and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        let row = sqlx::query(&format!(
+            "INSERT INTO academic_sessions (session_id, school_id, name, start_date, end_date, status, created_by)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             RETURNING {}",
+            SESSION_COLUMNS
+        ))
+        .bind(data["sessionId"].as_str())
+        .bind(school_id)
+        .bind(data["name"].as_str())
+        .bind(date("startDate"))
+        .bind(date("endDate"))
+        .bind(data["status"].as_str().unwrap_or("upcoming"))
+        .bind(data["createdBy"].as_str())
+        .fetch_one(&self.client.pool)
+        .await?;
+        Ok(session_json(&row))
+    }
+
+    async fn get_sessions(&self, school_id: &str) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM academic_sessions WHERE school_id = $1 ORDER BY start_date DESC",
+            SESSION_COLUMNS
+        ))
+        .bind(school_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(session_json).collect())
+    }
+
+    async fn get_session(&self, school_id: &str, session_id: &str) -> Result<Option<Value>, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM academic_sessions WHERE school_id = $1 AND session_id = $2",
+            SESSION_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(session_id)
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(session_json))
+    }
+
+    async fn update_session(&self, school_id: &str, session_id: &str, data: &Value) -> Result<Option<Value>, AppError> {
+        let date = |key: &str| data[key].as_str().and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        let row = sqlx::query(&format!(
+            "UPDATE academic_sessions
+             SET name = COALESCE($3, name), start_date = COALESCE($4, start_date), end_date = COALESCE($5, end_date)
+             WHERE school_id = $1 AND session_id = $2 AND status <> 'archived'
+             RETURNING {}",
+            SESSION_COLUMNS
+        ))
+        .bind(school_id)
+        .bind(session_id)
+        .bind(data["name"].as_str())
+        .bind(date("startDate"))
+        .bind(date("endDate"))
+        .fetch_optional(&self.client.pool)
+        .await?;
+        Ok(row.as_ref().map(session_json))
+    }
+
+    async fn get_session_enrollments(&self, school_id: &str, session_id: &str) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "SELECT student_id, class_id, section, roll_number, outcome, next_class_id, next_section,
+                    carried_balance::FLOAT8 AS carried_balance, decided_by, decided_at
+             FROM session_enrollments
+             WHERE school_id = $1 AND session_id = $2
+             ORDER BY class_id, section, roll_number NULLS LAST, student_id",
+        )
+        .bind(school_id)
+        .bind(session_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows.iter().map(enrollment_json).collect())
+    }
+
+    async fn save_promotions(
+        &self,
+        school_id: &str,
+        session_id: &str,
+        rows: &[Value],
+        decided_by: Option<&str>,
+    ) -> Result<(), AppError> {
+        let text = |key: &str| -> Vec<Option<String>> { rows.iter().map(|r| r[key].as_str().map(|s| s.to_string())).collect() };
+        sqlx::query(
+            "INSERT INTO session_enrollments
+                (school_id, session_id, student_id, class_id, section, roll_number, outcome, next_class_id, next_section, decided_by, decided_at)
+             SELECT $1, $2, st, c, sec, rn, o, nc, ns, $10, NOW()
+             FROM UNNEST($3::TEXT[], $4::TEXT[], $5::TEXT[], $6::INT[], $7::TEXT[], $8::TEXT[], $9::TEXT[])
+                AS x(st, c, sec, rn, o, nc, ns)
+             ON CONFLICT (school_id, session_id, student_id) DO UPDATE
+             SET class_id = EXCLUDED.class_id, section = EXCLUDED.section, roll_number = EXCLUDED.roll_number,
+                 outcome = EXCLUDED.outcome, next_class_id = EXCLUDED.next_class_id, next_section = EXCLUDED.next_section,
+                 decided_by = EXCLUDED.decided_by, decided_at = EXCLUDED.decided_at",
+        )
+        .bind(school_id)
+        .bind(session_id)
+        .bind(text("studentId"))
+        .bind(text("classId"))
+        .bind(text("section"))
+        .bind(rows.iter().map(|r| r["rollNumber"].as_i64().map(|n| n as i32)).collect::<Vec<_>>())
+        .bind(text("outcome"))
+        .bind(text("nextClassId"))
+        .bind(text("nextSection"))
+        .bind(decided_by)
+        .execute(&self.client.pool)
+        .await?;
         Ok(())
     }
+
+    async fn get_session_fee_balances(&self, school_id: &str, session_id: &str) -> Result<Vec<Value>, AppError> {
+        let rows = sqlx::query(
+            "WITH custom AS (
+                SELECT r.student_id, SUM(r.amount + COALESCE(r.penalty_accrued, 0) - COALESCE(r.paid_amount, 0))::FLOAT8 AS balance
+                FROM custom_fee_records r
+                JOIN custom_fees f ON f.school_id = r.school_id AND f.fee_id = r.fee_id
+                WHERE r.school_id = $1 AND (f.session_id = $2 OR f.session_id IS NULL)
+                  AND r.status NOT IN ('paid', 'waived', 'carried_forward')
+                  AND r.amount + COALESCE(r.penalty_accrued, 0) > COALESCE(r.paid_amount, 0)
+                GROUP BY r.student_id
+             ), general AS (
+                SELECT student_id, pending_amount::FLOAT8 AS balance FROM student_fees WHERE school_id = $1 AND pending_amount > 0
+             )
+             SELECT COALESCE(c.student_id, g.student_id) AS student_id,
+                    COALESCE(c.balance, 0) AS custom_balance, COALESCE(g.balance, 0) AS general_balance
+             FROM custom c FULL OUTER JOIN general g ON g.student_id = c.student_id",
+        )
+        .bind(school_id)
+        .bind(session_id)
+        .fetch_all(&self.client.pool)
+        .await?;
+        Ok(rows
+            .iter()
+            .map(|r| {
+                let (custom, general) = (r.get::<f64, _>("custom_balance"), r.get::<f64, _>("general_balance"));
+                json!({
+                    "studentId": r.get::<String, _>("student_id"),
+                    "customBalance": custom,
+                    "generalBalance": general,
+                    "balance": custom + general
+                })
+            })
+            .collect())
+    }
+
+    async fn complete_rollover(&self, school_id: &str, from: &str, to: &str, data: &Value) -> Result<Option<Value>, AppError> {
+        let mut tx = self.client.pool.begin().await?;
+        let closed = sqlx::query(
+            "UPDATE academic_sessions SET status = 'archived', archived_by = $3, archived_at = NOW()
+             WHERE school_id = $1 AND session_id = $2 AND status = 'active'",
+        )
+        .bind(school_id)
+        .bind(from)
+        .bind(data["completedBy"].as_str())
+        .execute(&mut *tx)
+        .await?;
+        let opened = sqlx::query(
+            "UPDATE academic_sessions SET status = 'active' WHERE school_id = $1 AND session_id = $2 AND status = 'upcoming'",
+        )
+        .bind(school_id)
+        .bind(to)
+        .execute(&mut *tx)
+        .await?;
+        if closed.rows_affected() == 0 || opened.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        // Fees and exams from before the school had sessions belong to the year being closed
+        for table in ["custom_fees", "exams"] {
+            sqlx::query(&format!("UPDATE {} SET session_id = $2 WHERE school_id = $1 AND session_id IS NULL", table))
+                .bind(school_id)
+                .bind(from)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let promoted = sqlx::query(
+            "UPDATE students s SET class_name = c.name, section = COALESCE(e.next_section, s.section), updated_at = NOW()
+             FROM session_enrollments e
+             JOIN classes c ON c.school_id = e.school_id AND c.id = e.next_class_id
+             WHERE e.school_id = $1 AND e.session_id = $2 AND e.outcome = 'pass'
+               AND s.school_id = e.school_id AND s.student_id = e.student_id",
+        )
+        .bind(school_id)
+        .bind(from)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let left = sqlx::query(
+            "UPDATE students s SET status = 'inactive', updated_at = NOW()
+             FROM session_enrollments e
+             WHERE e.school_id = $1 AND e.session_id = $2 AND e.outcome = 'leave'
+               AND s.school_id = e.school_id AND s.student_id = e.student_id",
+        )
+        .bind(school_id)
+        .bind(from)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let enrolled = sqlx::query(
+            "INSERT INTO session_enrollments (school_id, session_id, student_id, class_id, section, roll_number)
+             SELECT school_id, $3, student_id,
+                    CASE WHEN outcome = 'pass' THEN next_class_id ELSE class_id END,
+                    CASE WHEN outcome = 'pass' THEN COALESCE(next_section, section) ELSE section END,
+                    CASE WHEN outcome = 'detain' THEN roll_number END
+             FROM session_enrollments
+             WHERE school_id = $1 AND session_id = $2 AND outcome IN ('pass', 'detain')
+             ON CONFLICT (school_id, session_id, student_id) DO NOTHING",
+        )
+        .bind(school_id)
+        .bind(from)
+        .bind(to)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        let mut carried = json!({"students": 0, "amount": 0.0, "feeId": null});
+        let mut balances: std::collections::BTreeMap<String, f64> = std::collections::BTreeMap::new();
+        if data["carryBalances"].as_bool().unwrap_or(true) {
+            let rows = sqlx::query(
+                "UPDATE custom_fee_records r SET status = 'carried_forward', updated_at = NOW()
+                 FROM custom_fees f
+                 WHERE f.school_id = r.school_id AND f.fee_id = r.fee_id AND r.school_id = $1 AND f.session_id = $2
+                   AND r.status NOT IN ('paid', 'waived', 'carried_forward')
+                   AND r.amount + COALESCE(r.penalty_accrued, 0) > COALESCE(r.paid_amount, 0)
+                 RETURNING r.student_id, (r.amount + COALESCE(r.penalty_accrued, 0) - COALESCE(r.paid_amount, 0))::FLOAT8 AS balance",
+            )
+            .bind(school_id)
+            .bind(from)
+            .fetch_all(&mut *tx)
+            .await?;
+            for r in &rows {
+                *balances.entry(r.get::<String, _>("student_id")).or_default() += r.get::<f64, _>("balance");
+            }
+            if !balances.is_empty() {
+                let fee_id = data["feeId"].as_str().ok_or("feeId is required")?;
+                let students: Vec<String> = balances.keys().cloned().collect();
+                let amounts: Vec<f64> = balances.values().map(|b| (b * 100.0).round() / 100.0).collect();
+                sqlx::query(
+                    "INSERT INTO custom_fees (fee_id, school_id, fee_name, fee_type, amount, scope, target_students, description, session_id)
+                     VALUES ($1, $2, $3, 'one_time', 0, 'student', $4, 'Unpaid balances carried forward; the amount differs per student', $5)",
+                )
+                .bind(fee_id)
+                .bind(school_id)
+                .bind(data["feeName"].as_str().unwrap_or("Balance brought forward"))
+                .bind(json!(students))
+                .bind(to)
+                .execute(&mut *tx)
+                .await?;
+                sqlx::query(
+                    "INSERT INTO custom_fee_records (school_id, fee_id, student_id, amount, status)
+                     SELECT $1, $2, s, a, 'pending' FROM UNNEST($3::TEXT[], $4::FLOAT8[]) AS x(s, a)",
+                )
+                .bind(school_id)
+                .bind(fee_id)
+                .bind(&students)
+                .bind(&amounts)
+                .execute(&mut *tx)
+                .await?;
+                carried = json!({"students": students.len(), "amount": amounts.iter().sum::<f64>(), "feeId": fee_id});
+            }
+        }
+
+        // The general balance on student_fees runs across sessions, so it isn't moved: it stays
+        // due as before and is only added to what the enrolment records as carried.
+        let general = sqlx::query(
+            "SELECT student_id, pending_amount::FLOAT8 AS pending FROM student_fees WHERE school_id = $1 AND pending_amount > 0",
+        )
+        .bind(school_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut general_total = 0.0;
+        for r in &general {
+            let pending = r.get::<f64, _>("pending");
+            general_total += pending;
+            *balances.entry(r.get::<String, _>("student_id")).or_default() += pending;
+        }
+        carried["generalStudents"] = json!(general.len());
+        carried["generalAmount"] = json!((general_total * 100.0).round() / 100.0);
+        if !balances.is_empty() {
+            let students: Vec<String> = balances.keys().cloned().collect();
+            let amounts: Vec<f64> = balances.values().map(|b| (b * 100.0).round() / 100.0).collect();
+            sqlx::query(
+                "UPDATE session_enrollments e SET carried_balance = x.a
+                 FROM UNNEST($3::TEXT[], $4::FLOAT8[]) AS x(s, a)
+                 WHERE e.school_id = $1 AND e.session_id = $2 AND e.student_id = x.s",
+            )
+            .bind(school_id)
+            .bind(from)
+            .bind(&students)
+            .bind(&amounts)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query("UPDATE custom_fees SET status = 'archived', updated_at = NOW() WHERE school_id = $1 AND session_id = $2")
+            .bind(school_id)
+            .bind(from)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(Some(json!({
+            "promoted": promoted,
+            "left": left,
+            "enrolled": enrolled,
+            "carried": carried
+        })))
+    }
 }
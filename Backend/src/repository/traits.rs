@@ -51,6 +51,12 @@ pub trait StudentRepository: Send + Sync {
         school_id: &str,
         student_id: &str,
     ) -> Result<Option<Value>, AppError>;
+    /// Guardian contact details for the given students: `{studentId, contact, alternativeContact, email}`.
+    async fn get_student_contacts(
+        &self,
+        school_id: &str,
+        student_ids: &[String],
+    ) -> Result<Vec<Value>, AppError>;
     async fn update_student(
         &self,
         school_id: &str,
@@ -98,6 +104,12 @@ pub trait EmployeeRepository: Send + Sync {
         employee_id: &str,
     ) -> Result<(), AppError>;
     async fn generate_employee_id(&self) -> Result<String, AppError>;
+    /// Resolves a mobile login identifier (employee ID or phone number) to an employee ID.
+    async fn find_employee_id(
+        &self,
+        school_id: &str,
+        ident: &str,
+    ) -> Result<Option<String>, AppError>;
 }
 
 #[async_trait]
@@ -263,6 +275,33 @@ pub trait OperationsRepository: Send + Sync {
         action: &str,
         data: Value,
     ) -> Result<(), AppError>;
+    async fn add_fee_payment(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    /// Books payments `[{feeId: "general" | custom fee ID, feeName, amount}]` in one transaction:
+    /// each lowers the student's balance, gets a day-book receipt and a history entry, and nothing
+    /// is booked if any item fails. `details` gives `paymentMode`, `collectedBy`, `reference`,
+    /// `paidOn` and optionally the base `receiptNo` (else the next from `fee_receipt_seq`); with
+    /// several items each receipt gets a `-1`, `-2`... suffix. Returns `{receiptNo, lines}`.
+    async fn post_fee_payments(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        items: &[Value],
+        details: &Value,
+    ) -> Result<Value, AppError>;
+    /// Credits `amount` to a student's custom fee record and returns the updated record.
+    async fn pay_custom_fee(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        fee_id: &str,
+        amount: f64,
+        payment: Value,
+    ) -> Result<Value, AppError>;
 
     // Custom Fees (ad-hoc: tour, paper, fines)
     async fn add_custom_fee(
@@ -300,8 +339,11 @@ pub trait OperationsRepository: Send + Sync {
     async fn get_coupons(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
     async fn delete_coupon(&self, school_id: &str, coupon_id: &str) -> Result<(), AppError>;
     async fn block_coupon(&self, school_id: &str, coupon_id: &str, blocked: bool) -> Result<(), AppError>;
-    async fn validate_coupon(&self, school_id: &str, coupon_name: &str) -> Result<Option<Value>, AppError>;
-    async fn use_coupon(&self, school_id: &str, coupon_id: &str, student_id: &str, discount: f64) -> Result<Value, AppError>;
+    async fn update_coupon(&self, school_id: &str, coupon_id: &str, data: Value) -> Result<Value, AppError>;
+    async fn get_coupon(&self, school_id: &str, coupon_id: &str) -> Result<Option<Value>, AppError>;
+    async fn get_coupon_by_name(&self, school_id: &str, coupon_name: &str) -> Result<Option<Value>, AppError>;
+    async fn count_student_coupon_uses(&self, school_id: &str, coupon_id: &str, student_id: &str) -> Result<i64, AppError>;
+    async fn use_coupon(&self, school_id: &str, coupon_id: &str, student_id: &str, discount: f64, details: Value) -> Result<Value, AppError>;
 
     // Employee Payroll
     async fn update_employee_salary_params(
@@ -522,6 +564,388 @@ pub trait ReminderRepository: Send + Sync {
         &self,
         school_id: &str,
     ) -> Result<Vec<Value>, AppError>;
+
+    // Fee reminder rules
+    async fn add_reminder_rule(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn get_reminder_rules(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn delete_reminder_rule(
+        &self,
+        school_id: &str,
+        rule_id: &str,
+    ) -> Result<(), AppError>;
+    /// Unpaid fee items that carry a due date, with the student's contact details.
+    async fn get_fee_due_items(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn get_active_school_ids(&self) -> Result<Vec<String>, AppError>;
+}
+
+#[async_trait]
+pub trait PaymentRepository: Send + Sync {
+    /// A new order ID from `payment_order_seq`.
+    async fn next_order_id(&self) -> Result<String, AppError>;
+    async fn create_order(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn get_order(
+        &self,
+        order_id: &str,
+    ) -> Result<Option<Value>, AppError>;
+    async fn get_order_by_gateway_ref(
+        &self,
+        gateway: &str,
+        gateway_order_id: &str,
+    ) -> Result<Option<Value>, AppError>;
+    async fn get_student_orders(
+        &self,
+        school_id: &str,
+        student_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Moves a `created` order to `processing`. Returns false if another callback already claimed it.
+    async fn claim_order(
+        &self,
+        order_id: &str,
+        gateway_payment_id: &str,
+    ) -> Result<bool, AppError>;
+    /// Orders paid at the gateway whose posting to the student's fees failed, oldest first.
+    async fn get_failed_orders(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    /// Moves a `posting_failed` order to `status` (`processing` for another attempt, or `closed`)
+    /// with `error` as its note. Returns false if it is no longer `posting_failed`.
+    async fn resolve_failed_order(&self, order_id: &str, status: &str, error: Option<String>) -> Result<bool, AppError>;
+    async fn complete_order(
+        &self,
+        order_id: &str,
+        status: &str,
+        receipt: Option<Value>,
+        error: Option<String>,
+    ) -> Result<(), AppError>;
+}
+
+#[async_trait]
+pub trait ReconciliationRepository: Send + Sync {
+    async fn create_import(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn get_imports(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn statement_line_exists(
+        &self,
+        school_id: &str,
+        line_hash: &str,
+    ) -> Result<bool, AppError>;
+    async fn add_statement_line(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn get_statement_lines(
+        &self,
+        school_id: &str,
+        import_id: Option<String>,
+        status: Option<String>,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn get_statement_line(
+        &self,
+        school_id: &str,
+        line_id: &str,
+    ) -> Result<Option<Value>, AppError>;
+    /// Updates a line that is still in review; errors if it has been posted or ignored meanwhile.
+    async fn update_statement_line(
+        &self,
+        school_id: &str,
+        line_id: &str,
+        data: Value,
+    ) -> Result<(), AppError>;
+    /// Claims a line in review and books its fee `items` (as `OperationsRepository::post_fee_payments`)
+    /// in one transaction, so two resolvers can't post the same credit and a failed item books
+    /// nothing. `data` gives `studentId`, `matchMethod` and `resolvedBy`; `details` the receipt
+    /// fields. Returns the receipt stored on the line.
+    async fn post_statement_line(
+        &self,
+        school_id: &str,
+        line_id: &str,
+        items: &[Value],
+        data: &Value,
+        details: &Value,
+    ) -> Result<Value, AppError>;
+    /// Students with a pending general fee balance.
+    async fn get_pending_balances(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+}
+
+#[async_trait]
+pub trait PayrollRepository: Send + Sync {
+    async fn create_run(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn get_runs(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn get_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Option<Value>, AppError>;
+    async fn get_run_for_month(
+        &self,
+        school_id: &str,
+        month: i32,
+        year: i32,
+    ) -> Result<Option<Value>, AppError>;
+    /// Replaces the computed lines of a run and stores its totals.
+    async fn save_run_lines(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        lines: Vec<Value>,
+        totals: Value,
+    ) -> Result<(), AppError>;
+    async fn get_run_lines(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Moves a run from `from_status` to `to_status`; returns false if it was not in `from_status`.
+    async fn transition_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        from_status: &str,
+        to_status: &str,
+        data: Value,
+    ) -> Result<bool, AppError>;
+    /// In one transaction: moves the run from `from_status` to approved, books each of
+    /// `recoveries` (as `apply_advance_transaction`) and logs `payroll_approved` with each
+    /// `{employeeId, data}` of `history`. Returns false, booking nothing, if the run was not in
+    /// `from_status`; errors, booking nothing, if an advance can't take its recovery.
+    async fn approve_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        from_status: &str,
+        approved_by: &str,
+        recoveries: &[Value],
+        history: &[Value],
+    ) -> Result<bool, AppError>;
+    /// True when an approved run covers the month.
+    async fn is_month_locked(
+        &self,
+        school_id: &str,
+        month: i32,
+        year: i32,
+    ) -> Result<bool, AppError>;
+    /// Absent days per employee for the month, keyed by employee ID. Days on leave of one of
+    /// the `unpaid_leave_types` count as absent.
+    async fn get_employee_absences(
+        &self,
+        school_id: &str,
+        month: i32,
+        year: i32,
+        unpaid_leave_types: &[String],
+    ) -> Result<Value, AppError>;
+    async fn get_run_line(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        employee_id: &str,
+    ) -> Result<Option<Value>, AppError>;
+    /// Payroll months an employee appears in, newest first.
+    async fn get_employee_run_lines(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        approved_only: bool,
+    ) -> Result<Vec<Value>, AppError>;
+    /// The school's saved statutory settings, `Value::Null` when none are saved.
+    async fn get_statutory_settings(
+        &self,
+        school_id: &str,
+    ) -> Result<Value, AppError>;
+    async fn save_statutory_settings(
+        &self,
+        school_id: &str,
+        settings: Value,
+        updated_by: Option<String>,
+    ) -> Result<(), AppError>;
+    /// Lines of approved runs between two months (inclusive, as `year * 12 + month`), oldest first.
+    async fn get_approved_lines_between(
+        &self,
+        school_id: &str,
+        employee_id: Option<&str>,
+        from_key: i32,
+        to_key: i32,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn create_advance(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn get_advances(
+        &self,
+        school_id: &str,
+        employee_id: Option<&str>,
+        status: Option<&str>,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn get_advance(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+    ) -> Result<Option<Value>, AppError>;
+    /// Moves an advance from `from_status` to `to_status`; returns false if it was not in `from_status`.
+    async fn set_advance_status(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+        from_status: &str,
+        to_status: &str,
+    ) -> Result<bool, AppError>;
+    /// Records a recovery, prepayment or waiver and takes its principal part off the
+    /// outstanding balance; the advance closes (or is marked waived) once nothing is left.
+    async fn apply_advance_transaction(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    /// Undoes the payroll recoveries recorded for a run, putting their principal back.
+    async fn reverse_run_recoveries(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<(), AppError>;
+    async fn get_advance_transactions(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn create_salary_revision(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    /// An employee's salary revisions, oldest effective month first.
+    async fn get_salary_revisions(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Stores `bankAccount` on the employee record.
+    async fn set_employee_bank_account(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        account: Value,
+    ) -> Result<(), AppError>;
+    async fn create_transfer_batch(
+        &self,
+        school_id: &str,
+        batch: Value,
+        lines: Vec<Value>,
+    ) -> Result<Value, AppError>;
+    async fn get_transfer_batches(
+        &self,
+        school_id: &str,
+        run_id: Option<&str>,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn get_transfer_batch(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+    ) -> Result<Option<Value>, AppError>;
+    async fn get_transfer_lines(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Employees of a run already sent to the bank (pending or paid in any batch).
+    async fn get_run_transferred_employees(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Vec<String>, AppError>;
+    /// Sets one line's status from the bank response; returns false if the line does not exist.
+    async fn update_transfer_line(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+        employee_id: &str,
+        data: Value,
+    ) -> Result<bool, AppError>;
+    /// Recomputes the batch status from its lines after a response import.
+    async fn refresh_transfer_batch(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+    ) -> Result<Option<Value>, AppError>;
+}
+
+#[async_trait]
+pub trait NotificationRepository: Send + Sync {
+    async fn get_template(
+        &self,
+        school_id: &str,
+        template_key: &str,
+    ) -> Result<Option<Value>, AppError>;
+    async fn get_templates(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn upsert_template(
+        &self,
+        school_id: &str,
+        template_key: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn delete_template(
+        &self,
+        school_id: &str,
+        template_key: &str,
+    ) -> Result<(), AppError>;
+    /// Holds `data.dedupeKey` as `sending` while the message goes out. `false` when the key is
+    /// already sent or being sent; a failed attempt, or one stuck sending, can be claimed again.
+    async fn claim_notification(
+        &self,
+        school_id: &str,
+        data: &Value,
+    ) -> Result<bool, AppError>;
+    async fn add_notification_log(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn get_notification_log(
+        &self,
+        school_id: &str,
+        ref_type: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Applies a provider delivery receipt to the message with `provider_ref`; `None` when no
+    /// message has that reference.
+    async fn update_delivery_status(
+        &self,
+        provider_ref: &str,
+        status: &str,
+        error: Option<&str>,
+    ) -> Result<Option<Value>, AppError>;
 }
 
 #[async_trait]
@@ -587,6 +1011,7 @@ pub trait TaskRepository: Send + Sync {
 
 #[async_trait]
 pub trait LeaveRepository: Send + Sync {
+    /// Stores a pending application; `data.days` is the working-day count worked out by the service.
     async fn add_leave(
         &self,
         school_id: &str,
@@ -596,18 +1021,465 @@ pub trait LeaveRepository: Send + Sync {
         &self,
         school_id: &str,
     ) -> Result<Vec<Value>, AppError>;
+    async fn get_leave(
+        &self,
+        school_id: &str,
+        leave_id: &str,
+    ) -> Result<Option<Value>, AppError>;
+    /// Moves the application from one of `from` to `status`; false when it was in another state.
     async fn update_leave_status(
         &self,
         school_id: &str,
         leave_id: &str,
+        from: &[&str],
         status: &str,
-    ) -> Result<(), AppError>;
+        approved_by: Option<&str>,
+    ) -> Result<bool, AppError>;
     async fn update_leave_duration(
         &self,
         school_id: &str,
         leave_id: &str,
-        action: &str,
-        days: i32,
+        to_date: chrono::NaiveDate,
+        days: f64,
+        days_by_year: &Value,
+    ) -> Result<(), AppError>;
+    async fn get_leave_types(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    async fn save_leave_type(&self, school_id: &str, leave_type: &Value) -> Result<(), AppError>;
+    /// Opening and adjustment rows for `year`: `{employeeId, leaveType, opening, adjustment}`
+    async fn get_leave_balance_rows(
+        &self,
+        school_id: &str,
+        year: i32,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn set_leave_opening(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        leave_type: &str,
+        year: i32,
+        opening: f64,
+    ) -> Result<(), AppError>;
+    async fn add_leave_adjustment(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        leave_type: &str,
+        year: i32,
+        days: f64,
+    ) -> Result<(), AppError>;
+    /// Approved and pending days per employee and type falling in `year`, taking each leave's
+    /// share of that year (older leaves without a split count wholly in their start year):
+    /// `{employeeId, leaveType, status, days}`
+    async fn get_leave_usage(
+        &self,
+        school_id: &str,
+        year: i32,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Marks the employee on leave for each date, tagging the rows with the leave type and id and
+    /// keeping any record a row replaces.
+    async fn mark_leave_attendance(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        dates: &[chrono::NaiveDate],
+        leave_type: &str,
+        leave_id: &str,
+    ) -> Result<(), AppError>;
+    /// In one transaction: moves the pending `leave` to approved, marks `dates` for it (as
+    /// `mark_leave_attendance`) and logs `leave_marked` with `history`. Returns false, marking
+    /// nothing, if the leave was no longer pending.
+    async fn approve_leave(
+        &self,
+        school_id: &str,
+        leave: &Value,
+        dates: &[chrono::NaiveDate],
+        approved_by: Option<&str>,
+        history: Value,
+    ) -> Result<bool, AppError>;
+    /// Undoes an approved leave's marks: each day still on leave gets back the record it replaced,
+    /// or is removed if there was none.
+    async fn clear_leave_attendance(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        leave_id: &str,
+    ) -> Result<(), AppError>;
+    async fn append_leave_timeline(
+        &self,
+        school_id: &str,
+        leave_id: &str,
+        entry: &Value,
     ) -> Result<(), AppError>;
+    /// Moves a pending leave from approval step `from_step` to `to_step`, recording `entry`;
+    /// false when the leave is no longer pending at `from_step`.
+    async fn advance_leave_step(
+        &self,
+        school_id: &str,
+        leave_id: &str,
+        from_step: i32,
+        to_step: i32,
+        entry: &Value,
+    ) -> Result<bool, AppError>;
+    /// `{employeeType, steps, escalateAfterHours, updatedBy, updatedAt}`
+    async fn get_approval_chains(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    async fn save_approval_chain(&self, school_id: &str, chain: &Value) -> Result<(), AppError>;
+    async fn delete_approval_chain(&self, school_id: &str, employee_type: &str) -> Result<bool, AppError>;
+    async fn create_delegation(&self, school_id: &str, data: &Value) -> Result<Value, AppError>;
+    async fn get_delegations(
+        &self,
+        school_id: &str,
+        approver_id: Option<&str>,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn revoke_delegation(&self, school_id: &str, delegation_id: &str) -> Result<bool, AppError>;
 }
 
+
+#[async_trait]
+pub trait ReportRepository: Send + Sync {
+    // Fee reports (dates are inclusive YYYY-MM-DD)
+    async fn get_fee_collections(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn get_fee_demand(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Students with a balance at the end of `as_of`, each with the charges it is made of.
+    async fn get_fee_defaulters(
+        &self,
+        school_id: &str,
+        as_of: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn get_fee_concessions(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn get_coupon_usage(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Value>, AppError>;
+}
+
+#[async_trait]
+pub trait TimetableRepository: Send + Sync {
+    /// Periods that run on `day_of_week` (1 = Monday), including those set for every day;
+    /// `None` returns the whole timetable.
+    async fn get_periods(
+        &self,
+        school_id: &str,
+        day_of_week: Option<i32>,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Employees marked absent or on leave on `date`: `{employeeId, status, reason}`
+    async fn get_absent_employees(
+        &self,
+        school_id: &str,
+        date: chrono::NaiveDate,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Live substitutions between `from` and `to`, optionally for one substitute.
+    async fn get_substitutions(
+        &self,
+        school_id: &str,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+        substitute_id: Option<&str>,
+    ) -> Result<Vec<Value>, AppError>;
+    /// `{date, periodId, classId, subjectId, startTime, endTime, absentTeacherId, substituteId, reason, assignedBy}`
+    async fn create_substitution(&self, school_id: &str, data: &Value) -> Result<Value, AppError>;
+    async fn cancel_substitution(&self, school_id: &str, substitution_id: &str) -> Result<Option<Value>, AppError>;
+    /// The saved generator settings, `Null` until the school saves some.
+    async fn get_timetable_config(&self, school_id: &str) -> Result<Value, AppError>;
+    async fn save_timetable_config(&self, school_id: &str, config: &Value, updated_by: Option<&str>) -> Result<(), AppError>;
+    /// `{draftId, config, pins, entries, unsatisfied, stats, seed, createdBy}`
+    async fn add_timetable_draft(&self, school_id: &str, data: &Value) -> Result<Value, AppError>;
+    /// Newest first, without entries.
+    async fn get_timetable_drafts(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    async fn get_timetable_draft(&self, school_id: &str, draft_id: &str) -> Result<Option<Value>, AppError>;
+    /// Replaces a draft's pins. Returns false when it is no longer a draft.
+    async fn save_timetable_pins(&self, school_id: &str, draft_id: &str, pins: &Value) -> Result<bool, AppError>;
+    /// Makes the draft the live timetable in one transaction: the periods of `class_ids` become
+    /// `periods` (`{classId, section, name, startTime, endTime, teacherId, subjectId, day,
+    /// roomId}`) and the previously published draft is superseded. A slot (class, section, day,
+    /// start time) that stays keeps its period ID; upcoming substitutions for slots that go away
+    /// or change teacher are cancelled. Returns false when the draft was already published or
+    /// superseded.
+    async fn publish_timetable(
+        &self,
+        school_id: &str,
+        draft_id: &str,
+        class_ids: &[String],
+        periods: &[Value],
+        published_by: Option<&str>,
+    ) -> Result<bool, AppError>;
+}
+
+#[async_trait]
+pub trait EmployeeDocumentRepository: Send + Sync {
+    /// `{documentId, employeeId, documentType, title, fileName, contentType, sizeBytes, storageKey,
+    /// educationId, experienceId, documentNumber, issuedOn, expiresOn, uploadedBy}`
+    async fn add_document(&self, school_id: &str, data: &Value) -> Result<Value, AppError>;
+    /// Newest first; all employees when `employee_id` is `None`.
+    async fn get_documents(&self, school_id: &str, employee_id: Option<&str>) -> Result<Vec<Value>, AppError>;
+    async fn get_document(&self, school_id: &str, document_id: &str) -> Result<Option<Value>, AppError>;
+    async fn set_document_verification(
+        &self,
+        school_id: &str,
+        document_id: &str,
+        status: &str,
+        verified_by: &str,
+        remarks: Option<&str>,
+    ) -> Result<Option<Value>, AppError>;
+    /// Removes the record and returns it so the stored file can be deleted too.
+    async fn delete_document(&self, school_id: &str, document_id: &str) -> Result<Option<Value>, AppError>;
+    /// Points `employee_education.document_url` at a vault file (`None` clears it).
+    async fn link_education_document(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        education_id: i32,
+        url: Option<&str>,
+    ) -> Result<bool, AppError>;
+    /// Points `employee_experience.experience_letter_url` at a vault file (`None` clears it).
+    async fn link_experience_document(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        experience_id: i32,
+        url: Option<&str>,
+    ) -> Result<bool, AppError>;
+}
+
+#[async_trait]
+pub trait AttendanceRepository: Send + Sync {
+    /// Saved settings, `Null` when the school has none.
+    async fn get_attendance_settings(&self, school_id: &str) -> Result<Value, AppError>;
+    async fn save_attendance_settings(
+        &self,
+        school_id: &str,
+        settings: &Value,
+        updated_by: Option<&str>,
+    ) -> Result<(), AppError>;
+    /// Rows on `date` for the given users:
+    /// `{userId, status, reason, inTime, outTime, source, createdAt, updatedAt}`; `source` is
+    /// `device` on rows built from punches
+    async fn get_attendance_for_users(
+        &self,
+        school_id: &str,
+        role: &str,
+        date: chrono::NaiveDate,
+        user_ids: &[String],
+    ) -> Result<Vec<Value>, AppError>;
+    /// Upserts `rows` (`{userId, status, reason}`) for `date` and writes the matching
+    /// `history` entries (`{userId, action, data}`) to the audit log, all in one transaction.
+    async fn save_bulk_attendance(
+        &self,
+        school_id: &str,
+        role: &str,
+        date: chrono::NaiveDate,
+        rows: &[Value],
+        history: &[Value],
+    ) -> Result<(), AppError>;
+    /// Upserts a row built from device punches: `{status, inTime, outTime (RFC 3339), totalTime, reason}`.
+    async fn save_timed_attendance(
+        &self,
+        school_id: &str,
+        role: &str,
+        user_id: &str,
+        date: chrono::NaiveDate,
+        data: &Value,
+    ) -> Result<(), AppError>;
+    /// Rows between `from` and `to` for the given users: `{userId, date, status, reason}`.
+    async fn get_attendance_between(
+        &self,
+        school_id: &str,
+        role: &str,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+        user_ids: &[String],
+    ) -> Result<Vec<Value>, AppError>;
+    /// School holidays overlapping the range:
+    /// `{id, title, description, fromDate, toDate, classes, exemptEmployees, exemptStudents}`.
+    async fn get_school_holidays(
+        &self,
+        school_id: &str,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Alerts sent on `date` with the student's name and class and the message's delivery status.
+    async fn get_absence_alerts(&self, school_id: &str, date: chrono::NaiveDate) -> Result<Vec<Value>, AppError>;
+    /// Records (or replaces) the alert for one student and day: `{studentId, date,
+    /// attendanceStatus, channel, recipient, notificationId, status}`.
+    async fn save_absence_alert(&self, school_id: &str, data: &Value) -> Result<(), AppError>;
+    /// The latest alert since `since` sent to `sender` (a phone number, compared on its last ten
+    /// digits, or an email address), in any school: `{schoolId, studentId, date}`.
+    async fn find_absence_alert_for_reply(
+        &self,
+        sender: &str,
+        since: chrono::NaiveDate,
+    ) -> Result<Option<Value>, AppError>;
+    /// Attaches a guardian's reason to the student's attendance for `date` (creating an absent
+    /// row if none was marked) and to the alert, with an audit entry, in one transaction.
+    async fn save_absence_reason(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        date: chrono::NaiveDate,
+        reason: &str,
+        via: &str,
+    ) -> Result<(), AppError>;
+    /// Saved weekly-off rules, `Null` when the school has none.
+    async fn get_holiday_rules(&self, school_id: &str) -> Result<Value, AppError>;
+    async fn save_holiday_rules(&self, school_id: &str, rules: &Value, updated_by: Option<&str>) -> Result<(), AppError>;
+    /// Adds holidays (`{title, description, fromDate, toDate}`) in one transaction, skipping any
+    /// that already exist with the same start date and title. Returns the ones added.
+    async fn add_school_holidays(&self, school_id: &str, holidays: &[Value]) -> Result<Vec<Value>, AppError>;
+    /// Stores a correction request: `{requestId, role, userId, date, requested, currentRecord,
+    /// locked, reason, requestedBy, requestedVia}`.
+    async fn add_correction_request(&self, school_id: &str, data: &Value) -> Result<Value, AppError>;
+    /// Requests newest first, filtered by `{status?, role?, userId?, from?, to?}`.
+    async fn get_correction_requests(&self, school_id: &str, filter: &Value) -> Result<Vec<Value>, AppError>;
+    async fn get_correction_request(&self, school_id: &str, request_id: &str) -> Result<Option<Value>, AppError>;
+    async fn save_correction_evidence(&self, school_id: &str, request_id: &str, evidence: &Value) -> Result<(), AppError>;
+    /// Rejects or cancels a pending request. Returns false when it was no longer pending.
+    async fn close_correction_request(
+        &self,
+        school_id: &str,
+        request_id: &str,
+        status: &str,
+        decided_by: Option<&str>,
+        note: Option<&str>,
+    ) -> Result<bool, AppError>;
+    /// Approves a pending request in one transaction: writes `after` to the day (removes the
+    /// record when `None`), records `decision` (`{decidedBy, note}`) and logs `history`.
+    async fn apply_attendance_correction(
+        &self,
+        school_id: &str,
+        request: &Value,
+        after: Option<&Value>,
+        decision: &Value,
+        history: &Value,
+    ) -> Result<(), AppError>;
+    /// Attendance history entries for one person, newest first: `{action, data, createdAt}`.
+    /// `from`/`to` filter on the attendance date the entry is about.
+    async fn get_attendance_history(
+        &self,
+        school_id: &str,
+        user_id: &str,
+        from: Option<chrono::NaiveDate>,
+        to: Option<chrono::NaiveDate>,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Logs a self check-in attempt: `{employeeId, date, action, method, accepted, reason,
+    /// deviceId, latitude, longitude, accuracyMeters, distanceMeters, flags}`.
+    async fn add_self_checkin(&self, school_id: &str, data: &Value) -> Result<Value, AppError>;
+    async fn get_self_checkins(
+        &self,
+        school_id: &str,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+        employee_id: Option<&str>,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Other employees who checked in from `device_id` on or after `since`.
+    async fn get_device_checkin_employees(
+        &self,
+        school_id: &str,
+        device_id: &str,
+        since: chrono::NaiveDate,
+        exclude_employee_id: &str,
+    ) -> Result<Vec<String>, AppError>;
+    /// One period's marks on `date`: `{studentId, status, remarks, markedBy, createdAt, updatedAt}`.
+    async fn get_period_attendance(&self, school_id: &str, date: chrono::NaiveDate, period_id: i32) -> Result<Vec<Value>, AppError>;
+    /// Upserts `{studentId, status, remarks, markedBy}` rows against `period` (`{periodId, classId,
+    /// subjectId}`) and logs `history` (`{userId, action, data}`) in one transaction.
+    async fn save_period_attendance(
+        &self,
+        school_id: &str,
+        period: &Value,
+        date: chrono::NaiveDate,
+        rows: &[Value],
+        history: &[Value],
+    ) -> Result<(), AppError>;
+    /// Every period mark between `from` and `to`, optionally for one class:
+    /// `{date, periodId, classId, subjectId, studentId, status}`.
+    async fn get_period_attendance_range(
+        &self,
+        school_id: &str,
+        class_id: Option<&str>,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+    ) -> Result<Vec<Value>, AppError>;
+}
+
+#[async_trait]
+pub trait DeviceRepository: Send + Sync {
+    /// `{deviceId, serialNumber, name, protocol, location, apiKey}`
+    async fn create_device(&self, school_id: &str, data: &Value) -> Result<Value, AppError>;
+    async fn get_devices(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    /// Any school's device by serial, with `schoolId` and `apiKey`; used to authenticate pushes.
+    async fn find_device_by_serial(&self, serial_number: &str) -> Result<Option<Value>, AppError>;
+    /// `{name?, location?, active?, apiKey?}`
+    async fn update_device(&self, school_id: &str, device_id: &str, data: &Value) -> Result<Option<Value>, AppError>;
+    async fn delete_device(&self, school_id: &str, device_id: &str) -> Result<bool, AppError>;
+    async fn touch_device(&self, device_id: &str) -> Result<(), AppError>;
+    async fn get_enrollments(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    /// Upserts `[{enrollId, role, userId}]`.
+    async fn save_enrollments(&self, school_id: &str, entries: &[Value]) -> Result<(), AppError>;
+    async fn delete_enrollment(&self, school_id: &str, enroll_id: &str) -> Result<bool, AppError>;
+    /// Stores `[{enrollId, time, type, verifyMode, raw}]`, mapped to users through the school's
+    /// enrollments. Punches already received are skipped; the new ones are returned.
+    async fn add_punches(&self, school_id: &str, device_id: &str, punches: &[Value]) -> Result<Vec<Value>, AppError>;
+    async fn get_punches(
+        &self,
+        school_id: &str,
+        date: chrono::NaiveDate,
+        unmatched_only: bool,
+    ) -> Result<Vec<Value>, AppError>;
+    /// Local punch times of one user on `date`.
+    async fn get_user_punch_times(
+        &self,
+        school_id: &str,
+        role: &str,
+        user_id: &str,
+        date: chrono::NaiveDate,
+    ) -> Result<Vec<chrono::NaiveDateTime>, AppError>;
+    /// Maps earlier unmatched punches through current enrollments; returns the affected
+    /// `{role, userId, date}`.
+    async fn map_unmatched_punches(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+}
+
+#[async_trait]
+pub trait SessionRepository: Send + Sync {
+    /// `{sessionId, name, startDate, endDate, status, createdBy}`
+    async fn create_session(&self, school_id: &str, data: &Value) -> Result<Value, AppError>;
+    /// Newest first.
+    async fn get_sessions(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    async fn get_session(&self, school_id: &str, session_id: &str) -> Result<Option<Value>, AppError>;
+    /// `{name?, startDate?, endDate?}`. Archived sessions aren't changed and give `None`.
+    async fn update_session(&self, school_id: &str, session_id: &str, data: &Value) -> Result<Option<Value>, AppError>;
+    async fn get_session_enrollments(&self, school_id: &str, session_id: &str) -> Result<Vec<Value>, AppError>;
+    /// Upserts year-end decisions `[{studentId, classId, section, rollNumber, outcome, nextClassId, nextSection}]`.
+    async fn save_promotions(
+        &self,
+        school_id: &str,
+        session_id: &str,
+        rows: &[Value],
+        decided_by: Option<&str>,
+    ) -> Result<(), AppError>;
+    /// Unpaid balances `[{studentId, customBalance, generalBalance, balance}]`: custom fees of the
+    /// session (counting fees raised before the school had sessions) and the running general fee.
+    async fn get_session_fee_balances(&self, school_id: &str, session_id: &str) -> Result<Vec<Value>, AppError>;
+    /// In one transaction: archives `from` and activates `to`, applies the saved decisions to the
+    /// students, enrols them in `to`, and (with `carryBalances`) moves unpaid custom-fee balances
+    /// onto a new `feeId`/`feeName` fee in `to`. The general `student_fees` balance stays where it
+    /// is, but counts towards each enrolment's `carriedBalance`. `{feeId, feeName, carryBalances,
+    /// completedBy}`.
+    /// `None` when `from` is no longer active or `to` no longer upcoming.
+    async fn complete_rollover(&self, school_id: &str, from: &str, to: &str, data: &Value) -> Result<Option<Value>, AppError>;
+}
@@ -1,3 +1,4 @@
+use crate::routes::employee_documents::file_response;
 use crate::AppState;
 use axum::{
     extract::{Path, Query, State},
@@ -155,10 +156,18 @@ pub async fn list_attendance(
     }
 }
 
-// DELETE /:schoolId/:role/:userId/:date
+#[derive(Deserialize)]
+pub struct DeleteAttendanceQuery {
+    #[serde(rename = "deletedBy")]
+    pub deleted_by: Option<String>,
+    pub reason: Option<String>,
+}
+
+// DELETE /:schoolId/:role/:userId/:date?deletedBy=&reason=
 pub async fn delete_attendance(
     State(state): State<AppState>,
     Path((school_id, role, user_id, date)): Path<(String, String, String, String)>,
+    Query(q): Query<DeleteAttendanceQuery>,
 ) -> impl IntoResponse {
     if let Err(e) = validate_role(&role) {
         return (
@@ -169,7 +178,7 @@ pub async fn delete_attendance(
     match state
         .services
         .operations
-        .delete_attendance(&school_id, &role, &user_id, &date)
+        .delete_attendance(&school_id, &role, &user_id, &date, json!({"deletedBy": q.deleted_by, "reason": q.reason}))
         .await
     {
         Ok(()) => Json(json!({"success": true, "message": "Attendance deleted successfully"})).into_response(),
@@ -298,24 +307,19 @@ pub async fn check_school_holiday(
     Path(school_id): Path<String>,
     Query(q): Query<DateQuery>,
 ) -> impl IntoResponse {
-    // Sunday is always a holiday
-    if let Ok(d) = chrono::NaiveDate::parse_from_str(&q.date, "%Y-%m-%d") {
-        if d.weekday() == chrono::Weekday::Sun {
-            return Json(json!({"success":true,"isHoliday":true,"isSunday":true,"reason":"Sunday"})).into_response();
-        }
-    }
-    let _ = ensure_holidays_table(&state).await;
-    match sqlx::query(
-        "SELECT id,title FROM school_holidays WHERE school_id=$1 AND from_date<=$2 AND to_date>=$2 LIMIT 1"
-    )
-    .bind(&school_id).bind(&q.date)
-    .fetch_optional(&state.db.pool).await
-    {
-        Ok(Some(r)) => Json(json!({"success":true,"isHoliday":true,"isSunday":false,
-            "holidayId": r.try_get::<String,_>("id").unwrap_or_default(),
-            "reason": r.try_get::<String,_>("title").unwrap_or_default()})).into_response(),
-        Ok(None) => Json(json!({"success":true,"isHoliday":false})).into_response(),
-        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"success":false,"message":e.to_string()}))).into_response(),
+    match state.services.holidays.holiday_calendar(&school_id, &q.date, &q.date).await {
+        Ok(cal) => match cal["daysOff"].get(0) {
+            Some(day) => {
+                let weekly = day["kind"] == "weekly";
+                Json(json!({"success":true,"isHoliday":true,
+                    "isSunday": weekly && day["weekday"] == "Sun",
+                    "isWeeklyOff": weekly,
+                    "holidayId": if weekly { serde_json::Value::Null } else { cal["holidays"][0]["id"].clone() },
+                    "reason": day["reason"]})).into_response()
+            }
+            None => Json(json!({"success":true,"isHoliday":false})).into_response(),
+        },
+        Err(e) => (axum::http::StatusCode::BAD_REQUEST, Json(json!({"success":false,"message":e.to_string()}))).into_response(),
     }
 }
 
@@ -334,53 +338,718 @@ async fn ensure_holidays_table(state: &AppState) -> Result<(), sqlx::Error> {
     Ok(())
 }
 
+/// Why `date` is off for this person: a weekly off, or a holiday they aren't exempt from.
 async fn is_holiday_check(
     state: &AppState,
     school_id: &str,
     date: &str,
     user_id: &str,
     role: &str,
-) -> Result<Option<String>, sqlx::Error> {
-    // 1. Sunday check
-    if let Ok(d) = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") {
-        if d.weekday() == chrono::Weekday::Sun {
-            return Ok(Some("Sunday".to_string()));
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    state.services.holidays.day_off_reason(school_id, date, role, user_id).await
+}
+
+// ─── Holiday calendar ─────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct CalendarRangeQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub role: Option<String>,
+    #[serde(rename = "userId")]
+    pub user_id: Option<String>,
+}
+
+// GET /api/operations/attendance/:schoolId/holidays/rules
+pub async fn get_holiday_rules(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+) -> impl IntoResponse {
+    attendance_result(state.services.holidays.get_holiday_rules(&school_id).await)
+}
+
+// PUT /api/operations/attendance/:schoolId/holidays/rules
+//   { rules: [{ name?, weekday: "saturday", weeks: [2, 4] }], updatedBy? }
+pub async fn update_holiday_rules(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    attendance_result(state.services.holidays.save_holiday_rules(&school_id, payload).await)
+}
+
+// GET /api/operations/attendance/:schoolId/holidays/calendar?from=&to=
+pub async fn get_holiday_calendar(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<CalendarRangeQuery>,
+) -> impl IntoResponse {
+    attendance_result(
+        state
+            .services
+            .holidays
+            .holiday_calendar(&school_id, q.from.as_deref().unwrap_or(""), q.to.as_deref().unwrap_or(""))
+            .await,
+    )
+}
+
+// GET /api/operations/attendance/:schoolId/holidays/working-days?from=&to=&role=&userId=
+// Without role and userId the count is school-wide.
+pub async fn get_working_days(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<CalendarRangeQuery>,
+) -> impl IntoResponse {
+    let person = match (q.role.as_deref(), q.user_id.as_deref()) {
+        (Some(role), Some(user)) if !user.is_empty() => Some((role, user)),
+        _ => None,
+    };
+    attendance_result(
+        state
+            .services
+            .holidays
+            .working_days(&school_id, q.from.as_deref().unwrap_or(""), q.to.as_deref().unwrap_or(""), person)
+            .await,
+    )
+}
+
+// GET /api/operations/attendance/:schoolId/holidays/ics?from=&to=
+// Defaults to the current academic year.
+pub async fn export_holidays_ics(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<CalendarRangeQuery>,
+) -> impl IntoResponse {
+    match state.services.holidays.export_ics(&school_id, q.from.as_deref(), q.to.as_deref()).await {
+        Ok(ics) => axum::response::Response::builder()
+            .status(200)
+            .header("Content-Type", "text/calendar; charset=utf-8")
+            .header("Content-Disposition", "attachment; filename=\"holidays.ics\"")
+            .body(axum::body::Body::from(ics))
+            .unwrap(),
+        Err(e) => (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct IcsImportQuery {
+    #[serde(rename = "dryRun")]
+    pub dry_run: Option<bool>,
+    #[serde(rename = "updatedBy")]
+    pub updated_by: Option<String>,
+}
+
+// POST /api/operations/attendance/:schoolId/holidays/ics?dryRun=&updatedBy=   (body: the .ics file)
+pub async fn import_holidays_ics(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<IcsImportQuery>,
+    body: String,
+) -> impl IntoResponse {
+    attendance_result(
+        state
+            .services
+            .holidays
+            .import_ics(&school_id, &body, q.dry_run.unwrap_or(false), q.updated_by.as_deref())
+            .await,
+    )
+}
+
+#[derive(Deserialize)]
+pub struct GazettedQuery {
+    #[serde(rename = "academicYear")]
+    pub academic_year: Option<String>,
+    pub state: Option<String>,
+}
+
+// GET /api/operations/attendance/:schoolId/holidays/gazetted?academicYear=2026-27&state=MH
+// Preview only; nothing is saved.
+pub async fn preview_gazetted_holidays(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<GazettedQuery>,
+) -> impl IntoResponse {
+    let data = json!({"academicYear": q.academic_year, "state": q.state, "dryRun": true});
+    attendance_result(state.services.holidays.gazetted_holidays(&school_id, data).await)
+}
+
+// POST /api/operations/attendance/:schoolId/holidays/gazetted
+//   { academicYear | from, to, state?, dryRun? }  (dryRun defaults to true)
+pub async fn import_gazetted_holidays(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    attendance_result(state.services.holidays.gazetted_holidays(&school_id, payload).await)
+}
+
+// ─── Correction requests and history ──────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct CorrectionQuery {
+    pub status: Option<String>,
+    pub role: Option<String>,
+    #[serde(rename = "userId")]
+    pub user_id: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+// GET /api/operations/attendance/:schoolId/corrections?status=&role=&userId=&from=&to=
+pub async fn list_corrections(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<CorrectionQuery>,
+) -> impl IntoResponse {
+    let filter = json!({"status": q.status, "role": q.role, "userId": q.user_id, "from": q.from, "to": q.to});
+    attendance_result(state.services.attendance_corrections.list_corrections(&school_id, filter).await)
+}
+
+// POST /api/operations/attendance/:schoolId/corrections
+//   { role, userId, date, status? | remove?, inTime?, outTime?, reason, requestedBy }  (raised by the office)
+pub async fn create_correction(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(mut payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let role = payload["role"].as_str().unwrap_or("").to_string();
+    let user_id = payload["userId"].as_str().unwrap_or("").to_string();
+    payload["via"] = json!("office");
+    attendance_result(
+        state
+            .services
+            .attendance_corrections
+            .request_correction(&school_id, &role, &user_id, payload)
+            .await,
+    )
+}
+
+// GET /api/operations/attendance/:schoolId/corrections/:requestId
+pub async fn get_correction(
+    State(state): State<AppState>,
+    Path((school_id, request_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    attendance_result(state.services.attendance_corrections.get_correction(&school_id, &request_id).await)
+}
+
+// POST /api/operations/attendance/:schoolId/corrections/:requestId/decision
+//   { decision: "approve" | "reject", decidedBy, note? }
+pub async fn decide_correction(
+    State(state): State<AppState>,
+    Path((school_id, request_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    attendance_result(
+        state
+            .services
+            .attendance_corrections
+            .decide_correction(&school_id, &request_id, payload)
+            .await,
+    )
+}
+
+// POST /api/operations/attendance/:schoolId/corrections/:requestId/cancel  { cancelledBy }
+pub async fn cancel_correction(
+    State(state): State<AppState>,
+    Path((school_id, request_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    attendance_result(
+        state
+            .services
+            .attendance_corrections
+            .cancel_correction(&school_id, &request_id, payload["cancelledBy"].as_str().unwrap_or(""))
+            .await,
+    )
+}
+
+/// Reads the `file` part of a multipart upload plus its text fields.
+pub(crate) async fn evidence_upload(
+    multipart: &mut axum::extract::Multipart,
+) -> Result<(String, Option<String>, Vec<u8>, serde_json::Map<String, serde_json::Value>), String> {
+    let mut file = None;
+    let mut fields = serde_json::Map::new();
+    while let Some(field) = multipart.next_field().await.unwrap_or(None) {
+        let name = field.name().unwrap_or("").to_string();
+        if name == "file" {
+            let file_name = field.file_name().unwrap_or("evidence").to_string();
+            let content_type = field.content_type().map(|c| c.to_string());
+            let data = field.bytes().await.unwrap_or_default();
+            file = Some((file_name, content_type, data.to_vec()));
+        } else if let Ok(text) = field.text().await {
+            fields.insert(name, json!(text));
         }
     }
+    match file {
+        Some((name, content_type, bytes)) if !bytes.is_empty() => Ok((name, content_type, bytes, fields)),
+        _ => Err("No evidence file uploaded".to_string()),
+    }
+}
 
-    // 2. Database check
-    let _ = ensure_holidays_table(state).await;
-    let row = sqlx::query(
-        "SELECT title, exempt_employees, exempt_students FROM school_holidays \
-         WHERE school_id = $1 AND from_date <= $2 AND to_date >= $2 LIMIT 1"
+// POST /api/operations/attendance/:schoolId/corrections/:requestId/evidence  (multipart: file, uploadedBy)
+pub async fn upload_correction_evidence(
+    State(state): State<AppState>,
+    Path((school_id, request_id)): Path<(String, String)>,
+    mut multipart: axum::extract::Multipart,
+) -> impl IntoResponse {
+    let (file_name, content_type, bytes, fields) = match evidence_upload(&mut multipart).await {
+        Ok(upload) => upload,
+        Err(e) => return attendance_result::<()>(Err(e.into())),
+    };
+    attendance_result(
+        state
+            .services
+            .attendance_corrections
+            .add_evidence(&school_id, &request_id, &file_name, content_type.as_deref(), bytes, fields.get("uploadedBy").and_then(|v| v.as_str()))
+            .await,
     )
-    .bind(school_id)
-    .bind(date)
-    .fetch_optional(&state.db.pool)
-    .await?;
-
-    if let Some(r) = row {
-        let title: String = r.get("title");
-        let exempt_employees: serde_json::Value = r.get("exempt_employees");
-        let exempt_students: serde_json::Value = r.get("exempt_students");
-
-        // Check if user_id is in exempt lists based on role
-        let is_exempt = if role == "employee" {
-            if let Some(arr) = exempt_employees.as_array() {
-                arr.iter().any(|v| v.as_str() == Some(user_id))
-            } else { false }
-        } else if role == "student" {
-            if let Some(arr) = exempt_students.as_array() {
-                arr.iter().any(|v| v.as_str() == Some(user_id))
-            } else { false }
-        } else {
-            false
-        };
-
-        if !is_exempt {
-            return Ok(Some(title));
+}
+
+// GET /api/operations/attendance/:schoolId/corrections/:requestId/evidence/:index
+pub async fn download_correction_evidence(
+    State(state): State<AppState>,
+    Path((school_id, request_id, index)): Path<(String, String, usize)>,
+) -> impl IntoResponse {
+    match state.services.attendance_corrections.evidence_file(&school_id, &request_id, index).await {
+        Ok((file, bytes)) => file_response(file["contentType"].as_str(), file["fileName"].as_str().unwrap_or("evidence"), bytes),
+        Err(e) => (
+            axum::http::StatusCode::NOT_FOUND,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// GET /api/operations/attendance/:schoolId/history?userId=&from=&to=
+// Every mark, edit, deletion and approved correction for one person.
+pub async fn get_attendance_history(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<CorrectionQuery>,
+) -> impl IntoResponse {
+    let Some(user_id) = q.user_id.filter(|u| !u.is_empty()) else {
+        return attendance_result::<()>(Err("userId is required".into()));
+    };
+    attendance_result(
+        state
+            .services
+            .attendance_corrections
+            .attendance_history(&school_id, &user_id, q.from.as_deref(), q.to.as_deref())
+            .await,
+    )
+}
+
+// ─── Class attendance sheets ──────────────────────────────────────────────────
+
+fn attendance_result<T: serde::Serialize>(result: Result<T, Box<dyn std::error::Error + Send + Sync>>) -> axum::response::Response {
+    match result {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// GET /api/operations/attendance/:schoolId/settings
+pub async fn get_attendance_settings(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+) -> impl IntoResponse {
+    attendance_result(state.services.attendance.get_attendance_settings(&school_id).await)
+}
+
+// PUT /api/operations/attendance/:schoolId/settings
+//   { correctionWindowHours?, subjectAttendanceThreshold?, selfCheckIn?, absenceAlerts?, updatedBy }
+pub async fn update_attendance_settings(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    attendance_result(state.services.attendance.update_attendance_settings(&school_id, payload).await)
+}
+
+#[derive(Deserialize)]
+pub struct SectionQuery {
+    pub section: Option<String>,
+}
+
+// GET /api/operations/attendance/:schoolId/classes/:classId/:date?section=
+pub async fn get_class_attendance(
+    State(state): State<AppState>,
+    Path((school_id, class_id, date)): Path<(String, String, String)>,
+    Query(q): Query<SectionQuery>,
+) -> impl IntoResponse {
+    attendance_result(
+        state
+            .services
+            .attendance
+            .class_attendance(&school_id, &class_id, q.section.as_deref(), &date)
+            .await,
+    )
+}
+
+// PUT /api/operations/attendance/:schoolId/classes/:classId/:date
+//   { section?, markedBy, defaultStatus?, entries: [{ studentId, status, remarks? }] }
+pub async fn mark_class_attendance(
+    State(state): State<AppState>,
+    Path((school_id, class_id, date)): Path<(String, String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    attendance_result(
+        state
+            .services
+            .attendance
+            .mark_class_attendance(&school_id, &class_id, &date, payload)
+            .await,
+    )
+}
+
+// ─── Period-wise subject attendance ───────────────────────────────────────────
+
+// GET /api/operations/attendance/:schoolId/periods/:periodId/:date
+pub async fn get_period_attendance(
+    State(state): State<AppState>,
+    Path((school_id, period_id, date)): Path<(String, i32, String)>,
+) -> impl IntoResponse {
+    attendance_result(state.services.subject_attendance.period_attendance(&school_id, period_id, &date).await)
+}
+
+// PUT /api/operations/attendance/:schoolId/periods/:periodId/:date
+//   { markedBy, defaultStatus?, entries: [{ studentId, status: present|absent|late|excused, remarks? }] }
+pub async fn mark_period_attendance(
+    State(state): State<AppState>,
+    Path((school_id, period_id, date)): Path<(String, i32, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    attendance_result(
+        state
+            .services
+            .subject_attendance
+            .mark_period_attendance(&school_id, period_id, &date, payload)
+            .await,
+    )
+}
+
+#[derive(Deserialize)]
+pub struct SubjectReportQuery {
+    #[serde(rename = "classId")]
+    pub class_id: Option<String>,
+    pub section: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub threshold: Option<f64>,
+    /// json (default), csv or pdf
+    pub format: Option<String>,
+}
+
+// GET /api/operations/attendance/:schoolId/subjects/report?classId=&section=&from=&to=&format=
+// from/to default to the academic year so far.
+pub async fn get_subject_attendance_report(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<SubjectReportQuery>,
+) -> impl IntoResponse {
+    let Some(class_id) = q.class_id.filter(|c| !c.is_empty()) else {
+        return attendance_result::<()>(Err("classId is required".into()));
+    };
+    let result = state
+        .services
+        .subject_attendance
+        .subject_report(&school_id, &class_id, q.section.as_deref(), (q.from.as_deref(), q.to.as_deref()))
+        .await;
+
+    // One percentage column per subject
+    let mut columns: Vec<(String, String)> = vec![("Roll".to_string(), "rollNumber".to_string()), ("Name".to_string(), "name".to_string())];
+    if let Ok(data) = result.as_ref() {
+        for s in data["subjects"].as_array().into_iter().flatten() {
+            let id = s["subjectId"].as_str().unwrap_or("");
+            columns.push((format!("{} %", s["subjectName"].as_str().unwrap_or(id)), format!("pct_{}", id)));
+        }
+    }
+    columns.push(("Overall %".to_string(), "overallPercent".to_string()));
+    let columns: Vec<(&str, &str)> = columns.iter().map(|(h, k)| (h.as_str(), k.as_str())).collect();
+    let (class_name, from, to) = match result.as_ref() {
+        Ok(d) => (
+            d["className"].as_str().unwrap_or("").to_string(),
+            d["from"].as_str().unwrap_or("").to_string(),
+            d["to"].as_str().unwrap_or("").to_string(),
+        ),
+        Err(_) => Default::default(),
+    };
+    crate::routes::reports::report_response(
+        result,
+        q.format.as_deref(),
+        &format!("Subject Attendance - {} - {} to {}", class_name, from, to),
+        &format!("subject-attendance-{}-{}-{}", class_name.to_lowercase().replace(' ', "-"), from, to),
+        &columns,
+    )
+}
+
+// GET /api/operations/attendance/:schoolId/subjects/eligibility?classId=&section=&from=&to=&threshold=&format=
+// Students short of the per-subject threshold, for checking exam eligibility.
+pub async fn get_subject_eligibility(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<SubjectReportQuery>,
+) -> impl IntoResponse {
+    let result = state
+        .services
+        .subject_attendance
+        .eligibility_report(
+            &school_id,
+            q.class_id.as_deref().filter(|c| !c.is_empty()),
+            q.section.as_deref(),
+            (q.from.as_deref(), q.to.as_deref()),
+            q.threshold,
+        )
+        .await;
+    let (from, to) = match result.as_ref() {
+        Ok(d) => (d["from"].as_str().unwrap_or("").to_string(), d["to"].as_str().unwrap_or("").to_string()),
+        Err(_) => Default::default(),
+    };
+    crate::routes::reports::report_response(
+        result,
+        q.format.as_deref(),
+        &format!("Subject Attendance Shortfall {} to {}", from, to),
+        &format!("subject-attendance-shortfall-{}-{}", from, to),
+        &[
+            ("Class", "className"),
+            ("Section", "section"),
+            ("Roll", "rollNumber"),
+            ("Name", "name"),
+            ("Subject", "subjectName"),
+            ("Held", "held"),
+            ("Attended", "attended"),
+            ("Excused", "excused"),
+            ("%", "attendancePercent"),
+            ("Periods Needed", "periodsNeeded"),
+        ],
+    )
+}
+
+#[derive(Deserialize)]
+pub struct CheckInQrQuery {
+    #[serde(rename = "serialNumber")]
+    pub serial_number: Option<String>,
+}
+
+// GET /api/operations/attendance/:schoolId/check-in/qr?serialNumber=   (X-Device-Key: <apiKey>)
+// Polled by a registered `display` device; the code rotates every `qrRotationSeconds`.
+pub async fn get_check_in_qr(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<CheckInQrQuery>,
+    headers: axum::http::HeaderMap,
+) -> impl IntoResponse {
+    let api_key = headers.get("x-device-key").and_then(|k| k.to_str().ok()).unwrap_or("");
+    attendance_result(
+        state
+            .services
+            .attendance
+            .check_in_qr(&school_id, q.serial_number.as_deref().unwrap_or(""), api_key)
+            .await,
+    )
+}
+
+#[derive(Deserialize)]
+pub struct CheckInLogQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub flagged: Option<bool>,
+}
+
+// GET /api/operations/attendance/:schoolId/check-in/log?from=&to=&flagged=true
+pub async fn get_check_in_log(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<CheckInLogQuery>,
+) -> impl IntoResponse {
+    attendance_result(
+        state
+            .services
+            .attendance
+            .self_check_in_log(&school_id, q.from.as_deref(), q.to.as_deref(), q.flagged.unwrap_or(false))
+            .await,
+    )
+}
+
+// ─── Register and analytics ───────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct RegisterQuery {
+    /// student (default, needs classId) or employee
+    pub role: Option<String>,
+    #[serde(rename = "classId")]
+    pub class_id: Option<String>,
+    pub section: Option<String>,
+    pub month: Option<u32>,
+    pub year: Option<i32>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Attendance percentage below which a student counts as a chronic absentee
+    pub threshold: Option<f64>,
+    /// json (default), csv or pdf
+    pub format: Option<String>,
+}
+
+// GET /api/operations/attendance/:schoolId/register?role=&classId=&section=&month=&year=&format=
+pub async fn get_attendance_register(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<RegisterQuery>,
+) -> impl IntoResponse {
+    let today = Local::now().date_naive();
+    let role = q.role.as_deref().unwrap_or("student");
+    let (month, year) = (q.month.unwrap_or(today.month()), q.year.unwrap_or(today.year()));
+    let mut result = state
+        .services
+        .attendance
+        .monthly_register(&school_id, role, q.class_id.as_deref(), q.section.as_deref(), month, year)
+        .await;
+
+    // The PDF grid is tight, so long names are cut short there
+    if q.format.as_deref() == Some("pdf") {
+        if let Ok(data) = result.as_mut() {
+            for row in data["rows"].as_array_mut().into_iter().flatten() {
+                row["name"] = json!(row["name"].as_str().unwrap_or("").chars().take(20).collect::<String>());
+            }
         }
     }
 
-    Ok(None)
+    let days = result.as_ref().ok().and_then(|d| d["days"].as_array().map(|a| a.len())).unwrap_or(0);
+    let mut columns: Vec<(String, String)> = vec![if role == "employee" {
+        ("Designation".to_string(), "designation".to_string())
+    } else {
+        ("Roll".to_string(), "rollNumber".to_string())
+    }];
+    columns.push(("Name".to_string(), "name".to_string()));
+    columns.extend((1..=days).map(|d| (format!("{:02}", d), format!("d{:02}", d))));
+    for (h, k) in [("P", "present"), ("A", "absent"), ("L", "late"), ("HD", "halfDay"), ("LV", "leave"), ("Days", "workingDays"), ("%", "attendancePercent")] {
+        columns.push((h.to_string(), k.to_string()));
+    }
+    let columns: Vec<(&str, &str)> = columns.iter().map(|(h, k)| (h.as_str(), k.as_str())).collect();
+    let scope = match (role, result.as_ref()) {
+        ("employee", _) => "Staff".to_string(),
+        (_, Ok(data)) => match data["scope"]["section"].as_str() {
+            Some(section) => format!("{} {}", data["scope"]["className"].as_str().unwrap_or(""), section),
+            None => data["scope"]["className"].as_str().unwrap_or("").to_string(),
+        },
+        _ => String::new(),
+    };
+    crate::routes::reports::report_response(
+        result,
+        q.format.as_deref(),
+        &format!("Attendance Register - {} - {:02}/{}", scope, month, year),
+        &format!("attendance-register-{}-{}-{:02}", scope.to_lowercase().replace(' ', "-"), year, month),
+        &columns,
+    )
+}
+
+// GET /api/operations/attendance/:schoolId/analytics?role=&classId=&section=&from=&to=&threshold=75&format=
+pub async fn get_attendance_analytics(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<RegisterQuery>,
+) -> impl IntoResponse {
+    let today = Local::now().date_naive();
+    let from = q.from.clone().unwrap_or_else(|| today.with_day(1).unwrap_or(today).to_string());
+    let to = q.to.clone().unwrap_or_else(|| today.to_string());
+    let role = q.role.as_deref().unwrap_or("student");
+    let result = state
+        .services
+        .attendance
+        .attendance_analytics(
+            &school_id,
+            role,
+            q.class_id.as_deref(),
+            q.section.as_deref(),
+            (&from, &to),
+            q.threshold.unwrap_or(75.0),
+        )
+        .await;
+    crate::routes::reports::report_response(
+        result,
+        q.format.as_deref(),
+        &format!("Chronic Absentees {} to {}", from, to),
+        &format!("chronic-absentees-{}-{}", from, to),
+        &[
+            ("Name", "name"),
+            ("Roll", "rollNumber"),
+            ("Section", "section"),
+            ("Days", "workingDays"),
+            ("Present", "present"),
+            ("Absent", "absent"),
+            ("Late", "late"),
+            ("Half Day", "halfDay"),
+            ("Unmarked", "unmarked"),
+            ("%", "attendancePercent"),
+            ("Most Absent On", "mostAbsentOn"),
+        ],
+    )
+}
+
+// ─── Absence alerts ───────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct AlertDateQuery {
+    pub date: Option<String>,
+}
+
+// GET /api/operations/attendance/:schoolId/absence-alerts?date=
+pub async fn list_absence_alerts(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<AlertDateQuery>,
+) -> impl IntoResponse {
+    let date = q.date.unwrap_or_else(|| Local::now().date_naive().to_string());
+    attendance_result(state.services.absence_alerts.list_absence_alerts(&school_id, &date).await)
+}
+
+// POST /api/operations/attendance/:schoolId/absence-alerts/run  { date?, dryRun? }
+// Sends now instead of waiting for the scheduled run after the cutoff time.
+pub async fn run_absence_alerts(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let date = payload["date"]
+        .as_str()
+        .map(|d| d.to_string())
+        .unwrap_or_else(|| Local::now().date_naive().to_string());
+    attendance_result(
+        state
+            .services
+            .absence_alerts
+            .run_absence_alerts(&school_id, &date, payload["dryRun"].as_bool().unwrap_or(false))
+            .await,
+    )
+}
+
+// POST /api/operations/attendance/:schoolId/absence-alerts/reason
+//   { studentId, date, reason, recordedBy? }  (a reason given to the office, e.g. by phone)
+pub async fn record_absence_reason(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let via = format!("office:{}", payload["recordedBy"].as_str().unwrap_or("staff"));
+    attendance_result(
+        state
+            .services
+            .absence_alerts
+            .submit_absence_reason(
+                &school_id,
+                payload["studentId"].as_str().unwrap_or(""),
+                payload["date"].as_str().unwrap_or(""),
+                payload["reason"].as_str().unwrap_or(""),
+                &via,
+            )
+            .await,
+    )
 }
@@ -0,0 +1,167 @@
+use crate::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+fn device_result<T: serde::Serialize>(result: Result<T, Box<dyn std::error::Error + Send + Sync>>) -> Response {
+    match result {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+/// Terminals expect plain-text replies; unknown or disabled devices get a 404 so they back off.
+fn adms_reply(result: Result<String, Box<dyn std::error::Error + Send + Sync>>) -> Response {
+    match result {
+        Ok(text) => (StatusCode::OK, [("Content-Type", "text/plain")], text).into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, [("Content-Type", "text/plain")], format!("ERROR: {}", e)).into_response(),
+    }
+}
+
+// ─── ADMS / iClock push protocol ──────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct AdmsQuery {
+    #[serde(rename = "SN")]
+    pub sn: String,
+    pub table: Option<String>,
+}
+
+// GET /iclock/cdata?SN=...  (handshake)
+pub async fn adms_handshake(State(state): State<AppState>, Query(q): Query<AdmsQuery>) -> impl IntoResponse {
+    adms_reply(state.services.devices.adms_handshake(&q.sn).await)
+}
+
+// POST /iclock/cdata?SN=...&table=ATTLOG  (tab-separated punch lines)
+pub async fn adms_push(State(state): State<AppState>, Query(q): Query<AdmsQuery>, body: String) -> impl IntoResponse {
+    let table = q.table.unwrap_or_default();
+    adms_reply(state.services.devices.adms_push(&q.sn, &table, &body).await)
+}
+
+// GET /iclock/getrequest?SN=...  and  POST /iclock/devicecmd?SN=...
+pub async fn adms_poll(State(state): State<AppState>, Query(q): Query<AdmsQuery>) -> impl IntoResponse {
+    adms_reply(state.services.devices.adms_poll(&q.sn).await)
+}
+
+// ─── Device management ────────────────────────────────────────────────────────
+
+// POST /api/devices/:schoolId  { serialNumber, name, protocol: adms|json|display, location? }
+pub async fn register_device(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    device_result(state.services.devices.register_device(&school_id, payload).await)
+}
+
+// GET /api/devices/:schoolId
+pub async fn list_devices(State(state): State<AppState>, Path(school_id): Path<String>) -> impl IntoResponse {
+    device_result(state.services.devices.list_devices(&school_id).await)
+}
+
+// PUT /api/devices/:schoolId/:deviceId  { name?, location?, active?, regenerateKey? }
+pub async fn update_device(
+    State(state): State<AppState>,
+    Path((school_id, device_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    device_result(state.services.devices.update_device(&school_id, &device_id, payload).await)
+}
+
+// DELETE /api/devices/:schoolId/:deviceId
+pub async fn delete_device(
+    State(state): State<AppState>,
+    Path((school_id, device_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    device_result(state.services.devices.delete_device(&school_id, &device_id).await)
+}
+
+// GET /api/devices/:schoolId/enrollments
+pub async fn list_enrollments(State(state): State<AppState>, Path(school_id): Path<String>) -> impl IntoResponse {
+    device_result(state.services.devices.list_enrollments(&school_id).await)
+}
+
+// PUT /api/devices/:schoolId/enrollments  { enrollments: [{ enrollId, role, userId }] }
+pub async fn save_enrollments(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    device_result(state.services.devices.save_enrollments(&school_id, payload).await)
+}
+
+// DELETE /api/devices/:schoolId/enrollments/:enrollId
+pub async fn delete_enrollment(
+    State(state): State<AppState>,
+    Path((school_id, enroll_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    device_result(state.services.devices.delete_enrollment(&school_id, &enroll_id).await)
+}
+
+// POST /api/devices/:schoolId/punches  { serialNumber, apiKey, punches: [{ enrollId, time, type?, verifyMode? }] }
+//   The key may also be sent as an X-Device-Key header.
+pub async fn push_punches(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(mut payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    if let Some(key) = headers.get("x-device-key").and_then(|k| k.to_str().ok()) {
+        payload["apiKey"] = json!(key);
+    }
+    device_result(state.services.devices.push_punches(&school_id, payload).await)
+}
+
+#[derive(Deserialize)]
+pub struct PunchQuery {
+    pub date: Option<String>,
+    pub unmatched: Option<bool>,
+}
+
+// GET /api/devices/:schoolId/punches?date=&unmatched=true
+pub async fn list_punches(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<PunchQuery>,
+) -> impl IntoResponse {
+    let date = q.date.unwrap_or_else(|| chrono::Local::now().date_naive().to_string());
+    device_result(
+        state
+            .services
+            .devices
+            .list_punches(&school_id, &date, q.unmatched.unwrap_or(false))
+            .await,
+    )
+}
+
+// POST /api/devices/:schoolId/reprocess  { date }
+pub async fn reprocess_punches(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let date = payload["date"]
+        .as_str()
+        .map(|d| d.to_string())
+        .unwrap_or_else(|| chrono::Local::now().date_naive().to_string());
+    device_result(state.services.devices.reprocess_punches(&school_id, &date).await)
+}
+
+// POST /api/devices/:schoolId/:deviceId/simulate  { date?, enrollIds? }
+pub async fn simulate_device(
+    State(state): State<AppState>,
+    Path((school_id, device_id)): Path<(String, String)>,
+    payload: Option<Json<serde_json::Value>>,
+) -> impl IntoResponse {
+    let data = payload.map(|Json(v)| v).unwrap_or(serde_json::Value::Null);
+    device_result(state.services.devices.simulate_device(&school_id, &device_id, data).await)
+}
@@ -0,0 +1,174 @@
+use crate::AppState;
+use axum::{
+    body::Body,
+    extract::{Multipart, Path, Query, State},
+    http::HeaderValue,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Serves a stored upload inline. The saved content type and file name came from the uploader,
+/// so anything that isn't a valid header is replaced rather than failing the response.
+pub(crate) fn file_response(content_type: Option<&str>, file_name: &str, bytes: Vec<u8>) -> Response {
+    let content_type = content_type
+        .and_then(|t| HeaderValue::from_str(t).ok())
+        .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+    let file_name: String = file_name
+        .chars()
+        .map(|c| if c == ' ' || (c.is_ascii_graphic() && c != '"' && c != '\\') { c } else { '_' })
+        .collect();
+    Response::builder()
+        .status(200)
+        .header("Content-Type", content_type)
+        .header("Content-Disposition", format!("inline; filename=\"{}\"", file_name))
+        .body(Body::from(bytes))
+        .unwrap_or_else(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+fn document_result<T: serde::Serialize>(result: Result<T, Box<dyn std::error::Error + Send + Sync>>) -> Response {
+    match result {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// GET /api/employees/:schoolId/documents/types
+pub async fn list_document_types(State(state): State<AppState>) -> impl IntoResponse {
+    document_result(state.services.employee_documents.document_types().await)
+}
+
+// POST /api/employees/:schoolId/employees/:employeeId/documents
+//   multipart: file, documentType, title?, educationId?, experienceId?, documentNumber?,
+//   issuedOn?, expiresOn?, uploadedBy?
+pub async fn upload_document(
+    State(state): State<AppState>,
+    Path((school_id, employee_id)): Path<(String, String)>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let mut file: Option<(String, Option<String>, Vec<u8>)> = None;
+    let mut meta = serde_json::Map::new();
+
+    while let Some(field) = multipart.next_field().await.unwrap_or(None) {
+        let name = field.name().unwrap_or("").to_string();
+        if name == "file" {
+            let file_name = field.file_name().unwrap_or("document").to_string();
+            let content_type = field.content_type().map(|c| c.to_string());
+            let data = field.bytes().await.unwrap_or_default();
+            file = Some((file_name, content_type, data.to_vec()));
+        } else if let Ok(text) = field.text().await {
+            meta.insert(name, json!(text));
+        }
+    }
+
+    let (file_name, content_type, bytes) = match file {
+        Some(f) if !f.2.is_empty() => f,
+        _ => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(json!({"success": false, "message": "No document file uploaded"})),
+            )
+                .into_response()
+        }
+    };
+    document_result(
+        state
+            .services
+            .employee_documents
+            .upload_document(
+                &school_id,
+                &employee_id,
+                serde_json::Value::Object(meta),
+                &file_name,
+                content_type.as_deref(),
+                bytes,
+            )
+            .await,
+    )
+}
+
+// GET /api/employees/:schoolId/employees/:employeeId/documents
+pub async fn list_documents(
+    State(state): State<AppState>,
+    Path((school_id, employee_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    document_result(state.services.employee_documents.list_documents(&school_id, &employee_id).await)
+}
+
+// GET /api/employees/:schoolId/employees/:employeeId/documents/:documentId/file
+pub async fn download_document(
+    State(state): State<AppState>,
+    Path((school_id, employee_id, document_id)): Path<(String, String, String)>,
+) -> impl IntoResponse {
+    match state
+        .services
+        .employee_documents
+        .document_file(&school_id, &employee_id, &document_id)
+        .await
+    {
+        Ok((doc, bytes)) => file_response(doc["contentType"].as_str(), doc["fileName"].as_str().unwrap_or("document"), bytes),
+        Err(e) => (
+            axum::http::StatusCode::NOT_FOUND,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /api/employees/:schoolId/employees/:employeeId/documents/:documentId/verification
+//   { status: verified|rejected|pending, verifiedBy, remarks? }
+pub async fn verify_document(
+    State(state): State<AppState>,
+    Path((school_id, employee_id, document_id)): Path<(String, String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    document_result(
+        state
+            .services
+            .employee_documents
+            .verify_document(&school_id, &employee_id, &document_id, payload)
+            .await,
+    )
+}
+
+// DELETE /api/employees/:schoolId/employees/:employeeId/documents/:documentId
+pub async fn delete_document(
+    State(state): State<AppState>,
+    Path((school_id, employee_id, document_id)): Path<(String, String, String)>,
+) -> impl IntoResponse {
+    document_result(
+        state
+            .services
+            .employee_documents
+            .delete_document(&school_id, &employee_id, &document_id)
+            .await,
+    )
+}
+
+#[derive(Deserialize)]
+pub struct ComplianceQuery {
+    #[serde(rename = "employeeId")]
+    pub employee_id: Option<String>,
+    #[serde(rename = "withinDays")]
+    pub within_days: Option<i64>,
+}
+
+// GET /api/employees/:schoolId/documents/compliance?employeeId=&withinDays=30
+pub async fn compliance_report(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<ComplianceQuery>,
+) -> impl IntoResponse {
+    document_result(
+        state
+            .services
+            .employee_documents
+            .compliance_report(&school_id, q.employee_id.as_deref(), q.within_days.unwrap_or(30))
+            .await,
+    )
+}
@@ -1,9 +1,13 @@
+use crate::routes::reports::report_response;
 use crate::AppState;
 use axum::{
-    extract::{Path, State},
-    response::IntoResponse,
+    body::Body,
+    extract::{Multipart, Path, Query, State},
+    response::{IntoResponse, Response},
     Json,
 };
+use chrono::Datelike;
+use serde::Deserialize;
 use serde_json::json;
 
 pub async fn set_base_salary(
@@ -21,7 +25,7 @@ pub async fn set_base_salary(
             Json(json!({"success": true, "message": "Salary parameters updated"})).into_response()
         }
         Err(e) => (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            axum::http::StatusCode::BAD_REQUEST,
             Json(json!({"success": false, "message": e.to_string()})),
         )
             .into_response(),
@@ -71,3 +75,521 @@ pub async fn add_aid(
             .into_response(),
     }
 }
+
+// ─── Payroll Runs ─────────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct RegisterQuery {
+    /// json (default), csv or pdf
+    pub format: Option<String>,
+}
+
+fn payroll_result(result: Result<serde_json::Value, crate::services::traits::AppError>) -> axum::response::Response {
+    match result {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// GET /api/payroll/:schoolId/runs
+pub async fn list_payroll_runs(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+) -> impl IntoResponse {
+    match state.services.payroll.list_runs(&school_id).await {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /api/payroll/:schoolId/runs  { month, year, createdBy } — defaults to last month
+pub async fn create_payroll_run(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let today = chrono::Local::now().date_naive();
+    let (default_month, default_year) = if today.month() == 1 {
+        (12, today.year() - 1)
+    } else {
+        (today.month() as i32 - 1, today.year())
+    };
+    let month = payload["month"].as_i64().map(|m| m as i32).unwrap_or(default_month);
+    let year = payload["year"].as_i64().map(|y| y as i32).unwrap_or(default_year);
+    let created_by = payload["createdBy"].as_str().map(|s| s.to_string());
+    payroll_result(state.services.payroll.create_run(&school_id, month, year, created_by).await)
+}
+
+// GET /api/payroll/:schoolId/runs/:runId
+pub async fn get_payroll_run(
+    State(state): State<AppState>,
+    Path((school_id, run_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.get_run(&school_id, &run_id).await)
+}
+
+// POST /api/payroll/:schoolId/runs/:runId/recompute
+pub async fn recompute_payroll_run(
+    State(state): State<AppState>,
+    Path((school_id, run_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.recompute_run(&school_id, &run_id).await)
+}
+
+// POST /api/payroll/:schoolId/runs/:runId/approve  { approvedBy }
+pub async fn approve_payroll_run(
+    State(state): State<AppState>,
+    Path((school_id, run_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let approved_by = payload["approvedBy"].as_str().unwrap_or("");
+    payroll_result(state.services.payroll.approve_run(&school_id, &run_id, approved_by).await)
+}
+
+// POST /api/payroll/:schoolId/runs/:runId/reopen  { reopenedBy, reason }
+pub async fn reopen_payroll_run(
+    State(state): State<AppState>,
+    Path((school_id, run_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let reopened_by = payload["reopenedBy"].as_str().unwrap_or("");
+    let reason = payload["reason"].as_str().unwrap_or("");
+    payroll_result(
+        state
+            .services
+            .payroll
+            .reopen_run(&school_id, &run_id, reopened_by, reason)
+            .await,
+    )
+}
+
+// GET /api/payroll/:schoolId/runs/:runId/register?format=json|csv|pdf
+pub async fn payroll_register(
+    State(state): State<AppState>,
+    Path((school_id, run_id)): Path<(String, String)>,
+    Query(q): Query<RegisterQuery>,
+) -> impl IntoResponse {
+    let result = state.services.payroll.payroll_register(&school_id, &run_id).await;
+    let period = result
+        .as_ref()
+        .map(|r| format!("{:02}-{}", r["month"].as_i64().unwrap_or(0), r["year"]))
+        .unwrap_or_default();
+    report_response(
+        result,
+        q.format.as_deref(),
+        &format!("Payroll Register {}", period),
+        &format!("payroll_register_{}", period),
+        &[
+            ("Employee ID", "employeeId"),
+            ("Employee", "employeeName"),
+            ("Base", "baseSalary"),
+            ("Allowances", "allowances"),
+            ("Bonus", "bonus"),
+            ("Aid", "aid"),
+            ("Arrears", "arrears"),
+            ("Gross", "grossSalary"),
+            ("Absent Days", "absentDays"),
+            ("Absence Ded.", "absenceDeduction"),
+            ("Statutory", "statutoryDeductions"),
+            ("Advance", "advanceRecovery"),
+            ("Total Ded.", "deductions"),
+            ("Net Pay", "netPay"),
+        ],
+    )
+}
+
+// ─── Payslips ─────────────────────────────────────────────────────────────────
+
+/// Sends a payslip as a PDF attachment.
+pub(crate) fn payslip_pdf_response(slip: &serde_json::Value) -> Response {
+    let filename = format!(
+        "payslip_{}_{}_{:02}",
+        slip["employeeId"].as_str().unwrap_or("employee"),
+        slip["year"],
+        slip["month"].as_i64().unwrap_or(0)
+    );
+    Response::builder()
+        .status(200)
+        .header("Content-Type", "application/pdf")
+        .header("Content-Disposition", format!("attachment; filename=\"{}.pdf\"", filename))
+        .body(Body::from(crate::logic::payslip::payslip_pdf(slip)))
+        .unwrap()
+}
+
+// GET /api/payroll/:schoolId/employees/:employeeId/payslips
+pub async fn list_employee_payslips(
+    State(state): State<AppState>,
+    Path((school_id, employee_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match state.services.payroll.list_payslips(&school_id, &employee_id, false).await {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// GET /api/payroll/:schoolId/runs/:runId/payslips/:employeeId?format=json|pdf
+pub async fn get_employee_payslip(
+    State(state): State<AppState>,
+    Path((school_id, run_id, employee_id)): Path<(String, String, String)>,
+    Query(q): Query<RegisterQuery>,
+) -> impl IntoResponse {
+    match state
+        .services
+        .payroll
+        .get_payslip(&school_id, &run_id, &employee_id, false)
+        .await
+    {
+        Ok(slip) if q.format.as_deref() == Some("pdf") => payslip_pdf_response(&slip),
+        Ok(slip) => Json(json!({"success": true, "data": slip})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::NOT_FOUND,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// ─── Statutory deductions ─────────────────────────────────────────────────────
+
+// GET /api/payroll/:schoolId/statutory-settings
+pub async fn get_statutory_settings(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.get_statutory_settings(&school_id).await)
+}
+
+// PUT /api/payroll/:schoolId/statutory-settings  { pf?, esi?, pt?, tds?, updatedBy }
+pub async fn update_statutory_settings(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let updated_by = payload["updatedBy"].as_str().map(|s| s.to_string());
+    payroll_result(
+        state
+            .services
+            .payroll
+            .update_statutory_settings(&school_id, payload, updated_by)
+            .await,
+    )
+}
+
+// GET /api/payroll/:schoolId/runs/:runId/challans?format=json|csv|pdf
+pub async fn statutory_challans(
+    State(state): State<AppState>,
+    Path((school_id, run_id)): Path<(String, String)>,
+    Query(q): Query<RegisterQuery>,
+) -> impl IntoResponse {
+    let result = state.services.payroll.statutory_challans(&school_id, &run_id).await;
+    let period = result
+        .as_ref()
+        .map(|r| format!("{:02}-{}", r["month"].as_i64().unwrap_or(0), r["year"]))
+        .unwrap_or_default();
+    report_response(
+        result,
+        q.format.as_deref(),
+        &format!("Statutory Challan Summary {}", period),
+        &format!("statutory_challans_{}", period),
+        &[
+            ("Employee ID", "employeeId"),
+            ("Employee", "employeeName"),
+            ("UAN", "uan"),
+            ("PF Wages", "pfWages"),
+            ("PF Emp.", "pfEmployee"),
+            ("EPF Er.", "pfEmployerEpf"),
+            ("EPS Er.", "pfEmployerEps"),
+            ("ESI Wages", "esiWages"),
+            ("ESI Emp.", "esiEmployee"),
+            ("ESI Er.", "esiEmployer"),
+            ("PT", "professionalTax"),
+            ("TDS", "tds"),
+        ],
+    )
+}
+
+#[derive(Deserialize)]
+pub struct TaxStatementQuery {
+    /// Start year of the financial year (2025 for 2025-26); defaults to the last completed one
+    pub fy: Option<i32>,
+    /// json (default), csv or pdf
+    pub format: Option<String>,
+}
+
+// GET /api/payroll/:schoolId/employees/:employeeId/tax-statement?fy=2025&format=json|csv|pdf
+pub async fn annual_tax_statement(
+    State(state): State<AppState>,
+    Path((school_id, employee_id)): Path<(String, String)>,
+    Query(q): Query<TaxStatementQuery>,
+) -> impl IntoResponse {
+    let today = chrono::Local::now().date_naive();
+    let fy_start = q
+        .fy
+        .unwrap_or(crate::logic::statutory::fy_start_year(today.month(), today.year()) - 1);
+    let result = state
+        .services
+        .payroll
+        .annual_tax_statement(&school_id, &employee_id, fy_start)
+        .await;
+    let filename = format!("tax_statement_{}_{}", employee_id, fy_start);
+    match (&result, q.format.as_deref()) {
+        (Ok(stmt), Some("pdf")) => Response::builder()
+            .status(200)
+            .header("Content-Type", "application/pdf")
+            .header("Content-Disposition", format!("attachment; filename=\"{}.pdf\"", filename))
+            .body(Body::from(crate::logic::payslip::annual_statement_pdf(stmt)))
+            .unwrap(),
+        _ => report_response(
+            result,
+            q.format.as_deref(),
+            &format!("Annual Tax Statement FY {}", fy_start),
+            &filename,
+            &[
+                ("Month", "period"),
+                ("Gross", "grossSalary"),
+                ("Absence Ded.", "absenceDeduction"),
+                ("Earned", "earnedGross"),
+                ("PF", "pfEmployee"),
+                ("ESI", "esiEmployee"),
+                ("PT", "professionalTax"),
+                ("TDS", "tds"),
+                ("Net Pay", "netPay"),
+            ],
+        ),
+    }
+}
+
+// ─── Salary advances & loans ──────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct AdvanceQuery {
+    #[serde(rename = "employeeId")]
+    pub employee_id: Option<String>,
+    /// active, paused, closed or waived
+    pub status: Option<String>,
+}
+
+// GET /api/payroll/:schoolId/advances?employeeId=&status=
+pub async fn list_advances(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<AdvanceQuery>,
+) -> impl IntoResponse {
+    match state
+        .services
+        .payroll
+        .list_advances(&school_id, q.employee_id.as_deref(), q.status.as_deref())
+        .await
+    {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /api/payroll/:schoolId/employees/:employeeId/advances
+//   { amount, kind, interestRate, tenureMonths, emiAmount?, monthlyCap?, startMonth?, startYear?, reason, createdBy }
+pub async fn create_advance(
+    State(state): State<AppState>,
+    Path((school_id, employee_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.create_advance(&school_id, &employee_id, payload).await)
+}
+
+// GET /api/payroll/:schoolId/employees/:employeeId/advances
+pub async fn employee_advance_summary(
+    State(state): State<AppState>,
+    Path((school_id, employee_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.employee_advance_summary(&school_id, &employee_id).await)
+}
+
+// GET /api/payroll/:schoolId/advances/:advanceId
+pub async fn get_advance(
+    State(state): State<AppState>,
+    Path((school_id, advance_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.get_advance(&school_id, &advance_id).await)
+}
+
+// POST /api/payroll/:schoolId/advances/:advanceId/prepay  { amount, note, by }
+pub async fn prepay_advance(
+    State(state): State<AppState>,
+    Path((school_id, advance_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.prepay_advance(&school_id, &advance_id, payload).await)
+}
+
+// POST /api/payroll/:schoolId/advances/:advanceId/pause  { by }
+pub async fn pause_advance(
+    State(state): State<AppState>,
+    Path((school_id, advance_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let by = payload["by"].as_str().unwrap_or("");
+    payroll_result(state.services.payroll.set_advance_paused(&school_id, &advance_id, true, by).await)
+}
+
+// POST /api/payroll/:schoolId/advances/:advanceId/resume  { by }
+pub async fn resume_advance(
+    State(state): State<AppState>,
+    Path((school_id, advance_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let by = payload["by"].as_str().unwrap_or("");
+    payroll_result(state.services.payroll.set_advance_paused(&school_id, &advance_id, false, by).await)
+}
+
+// POST /api/payroll/:schoolId/advances/:advanceId/waive  { amount?, reason, by }
+pub async fn waive_advance(
+    State(state): State<AppState>,
+    Path((school_id, advance_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.waive_advance(&school_id, &advance_id, payload).await)
+}
+
+// ─── Salary revisions ─────────────────────────────────────────────────────────
+
+// GET /api/payroll/:schoolId/employees/:employeeId/salary-revisions
+pub async fn list_salary_revisions(
+    State(state): State<AppState>,
+    Path((school_id, employee_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.list_salary_revisions(&school_id, &employee_id).await)
+}
+
+// POST /api/payroll/:schoolId/employees/:employeeId/salary-revisions
+//   { effectiveFrom: YYYY-MM-DD, baseSalary?, incrementPercent?, ..., allowances?: {label: amount}, reason, createdBy }
+pub async fn create_salary_revision(
+    State(state): State<AppState>,
+    Path((school_id, employee_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.create_salary_revision(&school_id, &employee_id, payload).await)
+}
+
+// ─── Bank salary transfers ────────────────────────────────────────────────────
+
+// PUT /api/payroll/:schoolId/employees/:employeeId/bank-account
+//   { accountHolder, accountNumber, ifsc, bankName, accountType }
+pub async fn set_bank_account(
+    State(state): State<AppState>,
+    Path((school_id, employee_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.set_bank_account(&school_id, &employee_id, payload).await)
+}
+
+// GET /api/payroll/:schoolId/runs/:runId/bank-transfers
+pub async fn list_transfer_batches(
+    State(state): State<AppState>,
+    Path((school_id, run_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match state.services.payroll.list_transfer_batches(&school_id, Some(&run_id)).await {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /api/payroll/:schoolId/runs/:runId/bank-transfers
+//   { bankFormat: sbi|hdfc|icici|axis|generic, debitAccount, debitIfsc, paymentDate, mode?, narration?, createdBy }
+pub async fn create_transfer_batch(
+    State(state): State<AppState>,
+    Path((school_id, run_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.create_transfer_batch(&school_id, &run_id, payload).await)
+}
+
+// GET /api/payroll/:schoolId/bank-transfers/:batchId
+pub async fn get_transfer_batch(
+    State(state): State<AppState>,
+    Path((school_id, batch_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    payroll_result(state.services.payroll.get_transfer_batch(&school_id, &batch_id).await)
+}
+
+// GET /api/payroll/:schoolId/bank-transfers/:batchId/file
+pub async fn download_transfer_file(
+    State(state): State<AppState>,
+    Path((school_id, batch_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match state.services.payroll.transfer_file(&school_id, &batch_id).await {
+        Ok(file) => Response::builder()
+            .status(200)
+            .header("Content-Type", "text/csv; charset=utf-8")
+            .header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", file["fileName"].as_str().unwrap_or("transfer.csv")),
+            )
+            .body(Body::from(file["content"].as_str().unwrap_or("").to_string()))
+            .unwrap(),
+        Err(e) => (
+            axum::http::StatusCode::NOT_FOUND,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /api/payroll/:schoolId/bank-transfers/:batchId/response  (multipart: file, importedBy)
+pub async fn import_transfer_response(
+    State(state): State<AppState>,
+    Path((school_id, batch_id)): Path<(String, String)>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let mut file: Option<(String, Vec<u8>)> = None;
+    let mut imported_by: Option<String> = None;
+
+    while let Some(field) = multipart.next_field().await.unwrap_or(None) {
+        match field.name().unwrap_or("") {
+            "file" => {
+                let file_name = field.file_name().unwrap_or("response.csv").to_string();
+                let data = field.bytes().await.unwrap_or_default();
+                file = Some((file_name, data.to_vec()));
+            }
+            "importedBy" => imported_by = field.text().await.ok(),
+            _ => {}
+        }
+    }
+
+    let (file_name, bytes) = match file {
+        Some(f) if !f.1.is_empty() => f,
+        _ => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(json!({"success": false, "message": "No response file uploaded"})),
+            )
+                .into_response()
+        }
+    };
+    payroll_result(
+        state
+            .services
+            .payroll
+            .import_transfer_response(&school_id, &batch_id, &file_name, bytes, imported_by)
+            .await,
+    )
+}
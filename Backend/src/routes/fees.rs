@@ -96,7 +96,7 @@ pub async fn pay_fee(
     match state
         .services
         .operations
-        .pay_fee(&school_id, &student_id, amount)
+        .pay_fee(&school_id, &student_id, amount, payload)
         .await
     {
         Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
@@ -229,6 +229,17 @@ pub async fn list_coupons(
     }
 }
 
+pub async fn update_coupon(
+    State(state): State<AppState>,
+    Path((school_id, coupon_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    match state.services.operations.update_coupon(&school_id, &coupon_id, payload).await {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (axum::http::StatusCode::BAD_REQUEST, Json(json!({"success": false, "message": e.to_string()}))).into_response(),
+    }
+}
+
 pub async fn delete_coupon(
     State(state): State<AppState>,
     Path((school_id, coupon_id)): Path<(String, String)>,
@@ -257,7 +268,7 @@ pub async fn validate_coupon(
     Json(payload): Json<serde_json::Value>,
 ) -> impl IntoResponse {
     let coupon_name = payload["couponName"].as_str().unwrap_or("");
-    match state.services.operations.validate_coupon(&school_id, coupon_name).await {
+    match state.services.operations.validate_coupon(&school_id, coupon_name, payload.clone()).await {
         Ok(Some(data)) => Json(json!({"success": true, "data": data})).into_response(),
         Ok(None) => (axum::http::StatusCode::NOT_FOUND, Json(json!({"success": false, "message": "Coupon not found"}))).into_response(),
         Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"success": false, "message": e.to_string()}))).into_response(),
@@ -271,8 +282,8 @@ pub async fn use_coupon(
 ) -> impl IntoResponse {
     let student_id = payload["studentId"].as_str().unwrap_or("");
     let discount = payload["discount"].as_f64().unwrap_or(0.0);
-    match state.services.operations.use_coupon(&school_id, &coupon_id, student_id, discount).await {
+    match state.services.operations.use_coupon(&school_id, &coupon_id, student_id, discount, payload.clone()).await {
         Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
-        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"success": false, "message": e.to_string()}))).into_response(),
+        Err(e) => (axum::http::StatusCode::BAD_REQUEST, Json(json!({"success": false, "message": e.to_string()}))).into_response(),
     }
 }
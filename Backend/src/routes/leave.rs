@@ -1,9 +1,11 @@
 use crate::AppState;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::IntoResponse,
     Json,
 };
+use chrono::Datelike;
+use serde::Deserialize;
 use serde_json::json;
 
 pub async fn create_leave(
@@ -14,7 +16,7 @@ pub async fn create_leave(
     match state.services.leave.create_leave(&school_id, payload).await {
         Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
         Err(e) => (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            axum::http::StatusCode::BAD_REQUEST,
             Json(json!({"success": false, "message": e.to_string()})),
         )
             .into_response(),
@@ -35,28 +37,34 @@ pub async fn list_leaves(
     }
 }
 
+// POST /api/leave/:schoolId/:leaveId/approve  { approverId?, comment?, adminOverride? }
 pub async fn approve_leave(
     State(state): State<AppState>,
     Path((school_id, leave_id)): Path<(String, String)>,
+    payload: Option<Json<serde_json::Value>>,
 ) -> impl IntoResponse {
-    match state.services.leave.update_leave_status(&school_id, &leave_id, "approved").await {
-        Ok(_) => Json(json!({"success": true, "message": "Leave approved"})).into_response(),
+    let data = payload.map(|Json(v)| v).unwrap_or(serde_json::Value::Null);
+    match state.services.leave.update_leave_status(&school_id, &leave_id, "approved", data).await {
+        Ok(leave) => Json(json!({"success": true, "message": "Leave approved", "data": leave})).into_response(),
         Err(e) => (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            axum::http::StatusCode::BAD_REQUEST,
             Json(json!({"success": false, "message": e.to_string()})),
         )
             .into_response(),
     }
 }
 
+// POST /api/leave/:schoolId/:leaveId/reject  { approverId?, comment?, adminOverride? }
 pub async fn reject_leave(
     State(state): State<AppState>,
     Path((school_id, leave_id)): Path<(String, String)>,
+    payload: Option<Json<serde_json::Value>>,
 ) -> impl IntoResponse {
-    match state.services.leave.update_leave_status(&school_id, &leave_id, "rejected").await {
-        Ok(_) => Json(json!({"success": true, "message": "Leave rejected"})).into_response(),
+    let data = payload.map(|Json(v)| v).unwrap_or(serde_json::Value::Null);
+    match state.services.leave.update_leave_status(&school_id, &leave_id, "rejected", data).await {
+        Ok(leave) => Json(json!({"success": true, "message": "Leave rejected", "data": leave})).into_response(),
         Err(e) => (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            axum::http::StatusCode::BAD_REQUEST,
             Json(json!({"success": false, "message": e.to_string()})),
         )
             .into_response(),
@@ -72,7 +80,7 @@ pub async fn extend_leave(
     match state.services.leave.update_leave_duration(&school_id, &leave_id, "extend", days).await {
         Ok(_) => Json(json!({"success": true, "message": "Leave duration extended"})).into_response(),
         Err(e) => (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            axum::http::StatusCode::BAD_REQUEST,
             Json(json!({"success": false, "message": e.to_string()})),
         )
             .into_response(),
@@ -88,9 +96,172 @@ pub async fn reduce_leave(
     match state.services.leave.update_leave_duration(&school_id, &leave_id, "reduce", days).await {
         Ok(_) => Json(json!({"success": true, "message": "Leave duration reduced"})).into_response(),
         Err(e) => (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+fn leave_result<T: serde::Serialize>(result: Result<T, Box<dyn std::error::Error + Send + Sync>>) -> axum::response::Response {
+    match result {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::BAD_REQUEST,
             Json(json!({"success": false, "message": e.to_string()})),
         )
             .into_response(),
     }
 }
+
+// GET /api/leave/:schoolId/types
+pub async fn list_leave_types(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.get_leave_types(&school_id).await)
+}
+
+// PUT /api/leave/:schoolId/types
+//   { code, name, annualQuota, accrual: yearly|monthly|none, carryForwardMax, paid, countHolidays, gender, active }
+pub async fn save_leave_type(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.save_leave_type(&school_id, payload).await)
+}
+
+#[derive(Deserialize)]
+pub struct BalanceQuery {
+    #[serde(rename = "employeeId")]
+    pub employee_id: Option<String>,
+    pub year: Option<i32>,
+}
+
+// GET /api/leave/:schoolId/balances?employeeId=&year=
+pub async fn list_leave_balances(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<BalanceQuery>,
+) -> impl IntoResponse {
+    let year = q.year.unwrap_or_else(|| chrono::Local::now().year());
+    leave_result(
+        state
+            .services
+            .leave
+            .get_leave_balances(&school_id, q.employee_id.as_deref(), year)
+            .await,
+    )
+}
+
+// POST /api/leave/:schoolId/employees/:employeeId/balance-adjustments
+//   { leaveType, days, year?, reason, by }
+pub async fn adjust_leave_balance(
+    State(state): State<AppState>,
+    Path((school_id, employee_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.adjust_leave_balance(&school_id, &employee_id, payload).await)
+}
+
+// POST /api/leave/:schoolId/carry-forward  { year }  (the year being closed; default last year)
+pub async fn carry_forward_leave(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let year = payload["year"].as_i64().map(|y| y as i32).unwrap_or_else(|| chrono::Local::now().year() - 1);
+    leave_result(state.services.leave.carry_forward_leave(&school_id, year).await)
+}
+
+// GET /api/leave/:schoolId/:leaveId
+pub async fn get_leave(
+    State(state): State<AppState>,
+    Path((school_id, leave_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.get_leave(&school_id, &leave_id).await)
+}
+
+// POST /api/leave/:schoolId/:leaveId/comments  { by, comment }
+pub async fn add_leave_comment(
+    State(state): State<AppState>,
+    Path((school_id, leave_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.add_leave_comment(&school_id, &leave_id, payload).await)
+}
+
+// GET /api/leave/:schoolId/approval-chains
+pub async fn list_approval_chains(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.get_approval_chains(&school_id).await)
+}
+
+// PUT /api/leave/:schoolId/approval-chains
+//   { employeeType, steps: [{ name, approvers: [employeeId] }], escalateAfterHours?, updatedBy }
+pub async fn save_approval_chain(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.save_approval_chain(&school_id, payload).await)
+}
+
+// DELETE /api/leave/:schoolId/approval-chains/:employeeType
+pub async fn delete_approval_chain(
+    State(state): State<AppState>,
+    Path((school_id, employee_type)): Path<(String, String)>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.delete_approval_chain(&school_id, &employee_type).await)
+}
+
+// GET /api/leave/:schoolId/approvals/:approverId
+pub async fn pending_approvals(
+    State(state): State<AppState>,
+    Path((school_id, approver_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.pending_approvals(&school_id, &approver_id).await)
+}
+
+#[derive(Deserialize)]
+pub struct DelegationQuery {
+    #[serde(rename = "approverId")]
+    pub approver_id: Option<String>,
+}
+
+// GET /api/leave/:schoolId/delegations?approverId=
+pub async fn list_delegations(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<DelegationQuery>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.list_delegations(&school_id, q.approver_id.as_deref()).await)
+}
+
+// POST /api/leave/:schoolId/delegations  { approverId, delegateId, fromDate, toDate, reason }
+pub async fn create_delegation(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.create_delegation(&school_id, payload).await)
+}
+
+// DELETE /api/leave/:schoolId/delegations/:delegationId
+pub async fn revoke_delegation(
+    State(state): State<AppState>,
+    Path((school_id, delegation_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.revoke_delegation(&school_id, &delegation_id).await)
+}
+
+// POST /api/leave/:schoolId/escalate  (also run hourly)
+pub async fn escalate_overdue_leaves(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+) -> impl IntoResponse {
+    leave_result(state.services.leave.escalate_overdue_leaves(&school_id).await)
+}
@@ -1,11 +1,13 @@
 use axum::{
     extract::{Path, State},
-    routing::post,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use jsonwebtoken::{encode, Header, EncodingKey};
+use jsonwebtoken::{decode, encode, DecodingKey, Header, EncodingKey, Validation};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::AppState;
@@ -52,6 +54,75 @@ fn create_long_lived_token(ident: &str, role: &str, school_id: &str) -> String {
     ).unwrap_or_else(|_| "failed_to_generate_token".to_string())
 }
 
+// ─── Token Check (student app) ──────────────────────────────────────
+/// Returns the student ID from a `chatra` app token issued for `school_id`.
+fn authenticate_student(headers: &HeaderMap, school_id: &str) -> Result<String, &'static str> {
+    let token = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or("Missing token")?;
+
+    let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "super_secret_key_12345".to_string());
+    let claims = decode::<Value>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map_err(|_| "Invalid token")?
+    .claims;
+
+    if claims["role"] != "student" || claims["schoolId"] != school_id {
+        return Err("Token is not valid for this school");
+    }
+    claims["sub"].as_str().map(|s| s.to_string()).ok_or("Invalid token")
+}
+
+// ─── Token Check (teacher app) ──────────────────────────────────────
+/// Returns the employee ID behind an `adhyapk` app token issued for `school_id`.
+/// Teachers sign in with their phone number, so the token subject is resolved against the employee records.
+async fn authenticate_teacher(
+    state: &AppState,
+    headers: &HeaderMap,
+    school_id: &str,
+) -> Result<String, &'static str> {
+    let token = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or("Missing token")?;
+
+    let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "super_secret_key_12345".to_string());
+    let claims = decode::<Value>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map_err(|_| "Invalid token")?
+    .claims;
+
+    if claims["role"] != "teacher" || claims["schoolId"] != school_id {
+        return Err("Token is not valid for this school");
+    }
+    let ident = claims["sub"].as_str().ok_or("Invalid token")?;
+    state
+        .repos
+        .employee
+        .find_employee_id(school_id, ident)
+        .await
+        .ok()
+        .flatten()
+        .ok_or("No employee record for this login")
+}
+
+fn unauthorized(message: &str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(json!({"success": false, "message": message})),
+    )
+        .into_response()
+}
+
 // ─── LOGIN (Request OTP) ────────────────────────────────────────────────
 pub async fn mobile_login(
     Path(school_id): Path<String>,
@@ -105,8 +176,351 @@ pub async fn mobile_verify(
     })))
 }
 
+// ─── FEES (parent pays from the student app) ────────────────────────────
+pub async fn mobile_payable_fees(
+    Path(school_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let student_id = match authenticate_student(&headers, &school_id) {
+        Ok(id) => id,
+        Err(msg) => return unauthorized(msg),
+    };
+    match state.services.payment.payable_items(&school_id, &student_id).await {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /:school_id/mobile/fees/orders  { "items": [{ "feeId": "general", "amount": 500 }] }
+pub async fn mobile_create_fee_order(
+    Path(school_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<Value>,
+) -> Response {
+    let student_id = match authenticate_student(&headers, &school_id) {
+        Ok(id) => id,
+        Err(msg) => return unauthorized(msg),
+    };
+    match state.services.payment.create_order(&school_id, &student_id, payload).await {
+        Ok(order) => Json(json!({"success": true, "data": order})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn mobile_list_fee_orders(
+    Path(school_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let student_id = match authenticate_student(&headers, &school_id) {
+        Ok(id) => id,
+        Err(msg) => return unauthorized(msg),
+    };
+    match state.services.payment.list_orders(&school_id, &student_id).await {
+        Ok(list) => Json(json!({"success": true, "data": list})).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn mobile_get_fee_order(
+    Path((school_id, order_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let student_id = match authenticate_student(&headers, &school_id) {
+        Ok(id) => id,
+        Err(msg) => return unauthorized(msg),
+    };
+    match state.services.payment.get_order(&order_id).await {
+        Ok(Some(order)) if order["schoolId"] == school_id && order["studentId"] == student_id => {
+            Json(json!({"success": true, "data": order})).into_response()
+        }
+        Ok(_) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"success": false, "message": "Order not found"})),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// ─── PAYSLIPS (teacher self-service) ────────────────────────────────────
+pub async fn mobile_list_payslips(
+    Path(school_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let employee_id = match authenticate_teacher(&state, &headers, &school_id).await {
+        Ok(id) => id,
+        Err(msg) => return unauthorized(msg),
+    };
+    match state.services.payroll.list_payslips(&school_id, &employee_id, true).await {
+        Ok(list) => Json(json!({"success": true, "data": list})).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn mobile_get_payslip(
+    Path((school_id, run_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let employee_id = match authenticate_teacher(&state, &headers, &school_id).await {
+        Ok(id) => id,
+        Err(msg) => return unauthorized(msg),
+    };
+    match state.services.payroll.get_payslip(&school_id, &run_id, &employee_id, true).await {
+        Ok(slip) => Json(json!({"success": true, "data": slip})).into_response(),
+        Err(e) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn mobile_download_payslip(
+    Path((school_id, run_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let employee_id = match authenticate_teacher(&state, &headers, &school_id).await {
+        Ok(id) => id,
+        Err(msg) => return unauthorized(msg),
+    };
+    match state.services.payroll.get_payslip(&school_id, &run_id, &employee_id, true).await {
+        Ok(slip) => crate::routes::emppay::payslip_pdf_response(&slip),
+        Err(e) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// ─── ADVANCES (teacher self-service) ────────────────────────────────────
+pub async fn mobile_advances(
+    Path(school_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let employee_id = match authenticate_teacher(&state, &headers, &school_id).await {
+        Ok(id) => id,
+        Err(msg) => return unauthorized(msg),
+    };
+    match state.services.payroll.employee_advance_summary(&school_id, &employee_id).await {
+        Ok(summary) => Json(json!({"success": true, "data": summary})).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// ─── SELF CHECK-IN (teacher app) ────────────────────────────────────────
+// POST /:school_id/mobile/attendance/check-in | check-out
+//   { deviceId, qrToken?, latitude?, longitude?, accuracy?, mockLocation? }
+async fn self_check_in(state: AppState, school_id: String, headers: HeaderMap, action: &str, payload: Value) -> Response {
+    let employee_id = match authenticate_teacher(&state, &headers, &school_id).await {
+        Ok(id) => id,
+        Err(msg) => return unauthorized(msg),
+    };
+    match state
+        .services
+        .attendance
+        .self_check_in(&school_id, &employee_id, action, payload)
+        .await
+    {
+        Ok(result) => Json(json!({"success": true, "data": result})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn mobile_check_in(
+    Path(school_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<Value>,
+) -> Response {
+    self_check_in(state, school_id, headers, "in", payload).await
+}
+
+pub async fn mobile_check_out(
+    Path(school_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<Value>,
+) -> Response {
+    self_check_in(state, school_id, headers, "out", payload).await
+}
+
+// ─── ABSENCE REASON (student app) ───────────────────────────────────────
+// POST /:school_id/mobile/absences/:date/reason  { reason }
+pub async fn mobile_absence_reason(
+    Path((school_id, date)): Path<(String, String)>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<Value>,
+) -> Response {
+    let student_id = match authenticate_student(&headers, &school_id) {
+        Ok(id) => id,
+        Err(msg) => return unauthorized(msg),
+    };
+    match state
+        .services
+        .absence_alerts
+        .submit_absence_reason(&school_id, &student_id, &date, payload["reason"].as_str().unwrap_or(""), "app")
+        .await
+    {
+        Ok(result) => Json(json!({"success": true, "data": result})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// ─── ATTENDANCE CORRECTIONS (both apps) ─────────────────────────────────
+/// The attendance role and ID of whoever holds the token, student or teacher.
+async fn attendance_user(state: &AppState, headers: &HeaderMap, school_id: &str) -> Result<(&'static str, String), &'static str> {
+    if let Ok(student_id) = authenticate_student(headers, school_id) {
+        return Ok(("student", student_id));
+    }
+    authenticate_teacher(state, headers, school_id).await.map(|id| ("employee", id))
+}
+
+fn correction_response(result: Result<Value, crate::services::traits::AppError>) -> Response {
+    match result {
+        Ok(result) => Json(json!({"success": true, "data": result})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /:school_id/mobile/attendance/corrections
+//   { date, status? | remove?, inTime?, outTime?, reason }
+pub async fn mobile_request_correction(
+    Path(school_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(mut payload): Json<Value>,
+) -> Response {
+    let (role, user_id) = match attendance_user(&state, &headers, &school_id).await {
+        Ok(user) => user,
+        Err(msg) => return unauthorized(msg),
+    };
+    payload["requestedBy"] = json!(user_id);
+    payload["via"] = json!("app");
+    correction_response(
+        state
+            .services
+            .attendance_corrections
+            .request_correction(&school_id, role, &user_id, payload)
+            .await,
+    )
+}
+
+// GET /:school_id/mobile/attendance/corrections — the caller's own requests
+pub async fn mobile_list_corrections(
+    Path(school_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let (role, user_id) = match attendance_user(&state, &headers, &school_id).await {
+        Ok(user) => user,
+        Err(msg) => return unauthorized(msg),
+    };
+    correction_response(
+        state
+            .services
+            .attendance_corrections
+            .list_corrections(&school_id, json!({"role": role, "userId": user_id}))
+            .await
+            .map(|list| json!(list)),
+    )
+}
+
+// POST /:school_id/mobile/attendance/corrections/:request_id/evidence  (multipart: file)
+pub async fn mobile_correction_evidence(
+    Path((school_id, request_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    mut multipart: axum::extract::Multipart,
+) -> Response {
+    let (role, user_id) = match attendance_user(&state, &headers, &school_id).await {
+        Ok(user) => user,
+        Err(msg) => return unauthorized(msg),
+    };
+    let corrections = &state.services.attendance_corrections;
+    match corrections.get_correction(&school_id, &request_id).await {
+        Ok(request) if request["role"] == role && request["userId"] == user_id.as_str() => {}
+        _ => return unauthorized("This correction request isn't yours"),
+    }
+    let (file_name, content_type, bytes, _) = match super::attendance::evidence_upload(&mut multipart).await {
+        Ok(upload) => upload,
+        Err(msg) => return correction_response(Err(msg.into())),
+    };
+    correction_response(
+        corrections
+            .add_evidence(&school_id, &request_id, &file_name, content_type.as_deref(), bytes, Some(&user_id))
+            .await,
+    )
+}
+
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/:school_id/mobile/login", post(mobile_login))
         .route("/:school_id/mobile/verify", post(mobile_verify))
+        .route("/:school_id/mobile/fees", get(mobile_payable_fees))
+        .route(
+            "/:school_id/mobile/fees/orders",
+            get(mobile_list_fee_orders).post(mobile_create_fee_order),
+        )
+        .route("/:school_id/mobile/fees/orders/:order_id", get(mobile_get_fee_order))
+        .route("/:school_id/mobile/payslips", get(mobile_list_payslips))
+        .route("/:school_id/mobile/payslips/:run_id", get(mobile_get_payslip))
+        .route("/:school_id/mobile/payslips/:run_id/pdf", get(mobile_download_payslip))
+        .route("/:school_id/mobile/advances", get(mobile_advances))
+        .route("/:school_id/mobile/attendance/check-in", post(mobile_check_in))
+        .route("/:school_id/mobile/attendance/check-out", post(mobile_check_out))
+        .route("/:school_id/mobile/absences/:date/reason", post(mobile_absence_reason))
+        .route(
+            "/:school_id/mobile/attendance/corrections",
+            get(mobile_list_corrections).post(mobile_request_correction),
+        )
+        .route(
+            "/:school_id/mobile/attendance/corrections/:request_id/evidence",
+            post(mobile_correction_evidence),
+        )
 }
@@ -5,8 +5,10 @@ pub mod auth;
 pub mod award;
 pub mod class;
 pub mod complains;
+pub mod devices;
 pub mod documentUpload;
 pub mod documentbox;
+pub mod employee_documents;
 pub mod employees;
 pub mod emppay;
 pub mod events;
@@ -15,13 +17,19 @@ pub mod fees;
 pub mod leave;
 pub mod materials;
 pub mod mobile;
+pub mod notifications;
 pub mod ocr;
+pub mod payments;
+pub mod reconciliation;
 pub mod reminder;
+pub mod reports;
 pub mod responsibility;
 pub mod school;
+pub mod sessions;
 pub mod setup;
 pub mod spaces;
 pub mod students;
 pub mod subjects;
 pub mod task;
+pub mod timetable;
 pub mod topic;
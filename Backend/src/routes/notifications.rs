@@ -0,0 +1,155 @@
+use crate::logic::self_checkin::constant_time_eq;
+use crate::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+pub struct LogQuery {
+    #[serde(rename = "refType")]
+    pub ref_type: Option<String>,
+    pub limit: Option<i64>,
+}
+
+// GET /api/notifications/:schoolId/templates
+pub async fn list_templates(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+) -> impl IntoResponse {
+    match state.services.notification.list_templates(&school_id).await {
+        Ok(list) => Json(json!({"success": true, "data": list})).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /api/notifications/:schoolId/templates  { "templateKey", "subject", "body" }
+pub async fn save_template(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let template_key = match payload["templateKey"].as_str() {
+        Some(k) if !k.trim().is_empty() => k.trim().to_string(),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"success": false, "message": "templateKey is required"})),
+            )
+                .into_response()
+        }
+    };
+    match state
+        .services
+        .notification
+        .save_template(&school_id, &template_key, payload)
+        .await
+    {
+        Ok(t) => Json(json!({"success": true, "data": t})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// DELETE /api/notifications/:schoolId/templates/:templateKey  (reverts to the built-in text)
+pub async fn delete_template(
+    State(state): State<AppState>,
+    Path((school_id, template_key)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match state
+        .services
+        .notification
+        .delete_template(&school_id, &template_key)
+        .await
+    {
+        Ok(_) => Json(json!({"success": true})).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// GET /api/notifications/:schoolId/log?refType=&limit=
+pub async fn list_log(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<LogQuery>,
+) -> impl IntoResponse {
+    match state
+        .services
+        .notification
+        .list_log(&school_id, q.ref_type, q.limit.unwrap_or(100))
+        .await
+    {
+        Ok(list) => Json(json!({"success": true, "data": list})).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+/// Calls from the messaging relay carry `X-Notify-Token`, checked against `NOTIFY_WEBHOOK_TOKEN`.
+/// Without a token configured every call is refused.
+fn relay_authorized(headers: &HeaderMap) -> bool {
+    let Some(expected) = std::env::var("NOTIFY_WEBHOOK_TOKEN").ok().filter(|t| !t.is_empty()) else {
+        return false;
+    };
+    headers
+        .get("x-notify-token")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|t| constant_time_eq(t.as_bytes(), expected.as_bytes()))
+}
+
+// POST /api/notifications/delivery  { providerRef, status: sent|delivered|read|failed, error? }
+pub async fn delivery_receipt(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    if !relay_authorized(&headers) {
+        return (StatusCode::UNAUTHORIZED, Json(json!({"success": false, "message": "Invalid relay token"}))).into_response();
+    }
+    match state.services.notification.record_delivery(payload).await {
+        Ok(n) => Json(json!({"success": true, "data": n})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /api/notifications/inbound  { from, body, channel? }
+// A guardian's reply; today it is matched to the absence alert last sent to the sender.
+pub async fn inbound_message(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    if !relay_authorized(&headers) {
+        return (StatusCode::UNAUTHORIZED, Json(json!({"success": false, "message": "Invalid relay token"}))).into_response();
+    }
+    match state.services.absence_alerts.record_guardian_reply(payload).await {
+        Ok(r) => Json(json!({"success": true, "data": r})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,87 @@
+use crate::AppState;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::{json, Value};
+
+// POST /api/payments/:gateway/callback
+// Called by the payment provider; the payload is trusted only after signature verification.
+pub async fn payment_callback(
+    State(state): State<AppState>,
+    Path(gateway): Path<String>,
+    headers: HeaderMap,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let signature = headers
+        .get("x-payment-signature")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    match state
+        .services
+        .payment
+        .handle_callback(&gateway, payload, signature)
+        .await
+    {
+        Ok(result) => Json(json!({"success": true, "data": result})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /api/payments/mock/checkout/:orderId  { "success": true }
+// Stands in for the provider's hosted checkout page; mounted only with PAYMENT_GATEWAY=mock.
+pub async fn mock_checkout(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let success = payload["success"].as_bool().unwrap_or(true);
+    match state.services.payment.mock_checkout(&order_id, success).await {
+        Ok(result) => Json(json!({"success": true, "data": result})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// GET /api/fees/:schoolId/payment-orders/failed
+// Online payments taken by the gateway that could not be posted to the student's fees.
+pub async fn failed_orders(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+) -> impl IntoResponse {
+    match state.services.payment.failed_orders(&school_id).await {
+        Ok(list) => Json(json!({"success": true, "data": list})).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /api/fees/:schoolId/payment-orders/:orderId/resolve
+// { "action": "retry" }
+// { "action": "close", "note": "Refunded at the gateway", "resolvedBy": "..." }
+pub async fn resolve_order(
+    State(state): State<AppState>,
+    Path((school_id, order_id)): Path<(String, String)>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    match state.services.payment.resolve_order(&school_id, &order_id, payload).await {
+        Ok(order) => Json(json!({"success": true, "data": order})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
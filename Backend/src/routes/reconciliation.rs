@@ -0,0 +1,140 @@
+use crate::AppState;
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+pub struct LinesQuery {
+    pub status: Option<String>,
+}
+
+// POST /api/fees/:schoolId/bank-statements  (multipart: file, importedBy)
+pub async fn import_bank_statement(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let mut file: Option<(String, Vec<u8>)> = None;
+    let mut imported_by: Option<String> = None;
+
+    while let Some(field) = multipart.next_field().await.unwrap_or(None) {
+        match field.name().unwrap_or("") {
+            "file" => {
+                let file_name = field.file_name().unwrap_or("statement.csv").to_string();
+                let data = field.bytes().await.unwrap_or_default();
+                file = Some((file_name, data.to_vec()));
+            }
+            "importedBy" => imported_by = field.text().await.ok(),
+            _ => {}
+        }
+    }
+
+    let (file_name, bytes) = match file {
+        Some(f) if !f.1.is_empty() => f,
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"success": false, "message": "No statement file uploaded"})),
+            )
+                .into_response()
+        }
+    };
+
+    match state
+        .services
+        .reconciliation
+        .import_statement(&school_id, &file_name, bytes, imported_by)
+        .await
+    {
+        Ok(summary) => Json(json!({"success": true, "data": summary})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// GET /api/fees/:schoolId/bank-statements
+pub async fn list_bank_statements(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+) -> impl IntoResponse {
+    match state.services.reconciliation.list_imports(&school_id).await {
+        Ok(list) => Json(json!({"success": true, "data": list})).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// GET /api/fees/:schoolId/bank-statements/:importId/lines?status=
+pub async fn list_statement_lines(
+    State(state): State<AppState>,
+    Path((school_id, import_id)): Path<(String, String)>,
+    Query(q): Query<LinesQuery>,
+) -> impl IntoResponse {
+    match state
+        .services
+        .reconciliation
+        .list_lines(&school_id, Some(import_id), q.status)
+        .await
+    {
+        Ok(list) => Json(json!({"success": true, "data": list})).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// GET /api/fees/:schoolId/reconciliation/queue
+pub async fn review_queue(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+) -> impl IntoResponse {
+    match state
+        .services
+        .reconciliation
+        .list_lines(&school_id, None, Some("review".to_string()))
+        .await
+    {
+        Ok(list) => Json(json!({"success": true, "data": list})).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /api/fees/:schoolId/reconciliation/lines/:lineId/resolve
+// { "action": "post", "studentId": "...", "items": [{ "feeId", "amount" }], "resolvedBy": "..." }
+// { "action": "ignore", "note": "...", "resolvedBy": "..." }
+pub async fn resolve_statement_line(
+    State(state): State<AppState>,
+    Path((school_id, line_id)): Path<(String, String)>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    match state
+        .services
+        .reconciliation
+        .resolve_line(&school_id, &line_id, payload)
+        .await
+    {
+        Ok(line) => Json(json!({"success": true, "data": line})).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
@@ -18,3 +18,67 @@ pub async fn list_reminders(
             .into_response(),
     }
 }
+
+pub async fn list_reminder_rules(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+) -> impl IntoResponse {
+    match state.services.reminder.list_reminder_rules(&school_id).await {
+        Ok(list) => Json(serde_json::json!({"success": true, "data": list})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn create_reminder_rule(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    match state.services.reminder.create_reminder_rule(&school_id, payload).await {
+        Ok(rule) => Json(serde_json::json!({"success": true, "data": rule})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn delete_reminder_rule(
+    State(state): State<AppState>,
+    Path((school_id, rule_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match state.services.reminder.delete_reminder_rule(&school_id, &rule_id).await {
+        Ok(_) => Json(serde_json::json!({"success": true})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// POST /api/reminder/:schoolId/fees/run  { "date": "YYYY-MM-DD", "dryRun": true }
+pub async fn run_fee_reminders(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let date = payload["date"]
+        .as_str()
+        .map(|d| d.to_string())
+        .unwrap_or_else(|| chrono::Local::now().format("%Y-%m-%d").to_string());
+    let dry_run = payload["dryRun"].as_bool().unwrap_or(false);
+    match state.services.reminder.run_fee_reminders(&school_id, &date, dry_run).await {
+        Ok(summary) => Json(serde_json::json!({"success": true, "data": summary})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,227 @@
+use crate::logic::export::{to_csv, to_pdf, Table};
+use crate::services::traits::AppError;
+use crate::AppState;
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Datelike, Local};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+pub struct ReportQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    #[serde(rename = "asOf")]
+    pub as_of: Option<String>,
+    #[serde(rename = "className")]
+    pub class_name: Option<String>,
+    /// json (default), csv or pdf
+    pub format: Option<String>,
+}
+
+impl ReportQuery {
+    /// Defaults to the current month up to today.
+    fn range(&self) -> (String, String) {
+        let today = Local::now().date_naive();
+        let to = self.to.clone().unwrap_or_else(|| today.to_string());
+        let from = self.from.clone().unwrap_or_else(|| {
+            today.with_day(1).unwrap_or(today).to_string()
+        });
+        (from, to)
+    }
+}
+
+/// Sends the report as JSON, or as a CSV/PDF attachment built from `data["rows"]`.
+pub(crate) fn report_response(
+    result: Result<Value, AppError>,
+    format: Option<&str>,
+    title: &str,
+    filename: &str,
+    columns: &[(&str, &str)],
+) -> Response {
+    let data = match result {
+        Ok(d) => d,
+        Err(e) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(json!({"success": false, "message": e.to_string()})),
+            )
+                .into_response()
+        }
+    };
+
+    let rows = data["rows"].as_array().cloned().unwrap_or_default();
+    let (content_type, ext, body) = match format.unwrap_or("json") {
+        "csv" => (
+            "text/csv",
+            "csv",
+            to_csv(&Table::from_values(title, columns, &rows)).into_bytes(),
+        ),
+        "pdf" => (
+            "application/pdf",
+            "pdf",
+            to_pdf(&Table::from_values(title, columns, &rows)),
+        ),
+        _ => return Json(json!({"success": true, "data": data})).into_response(),
+    };
+
+    axum::response::Response::builder()
+        .status(200)
+        .header("Content-Type", content_type)
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{}.{}\"", filename, ext),
+        )
+        .body(Body::from(body))
+        .unwrap()
+}
+
+// ─── Fee Reports ──────────────────────────────────────────────────────────────
+
+// GET /api/fees/:schoolId/reports/daybook?from=&to=&format=
+pub async fn fee_day_book(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<ReportQuery>,
+) -> impl IntoResponse {
+    let (from, to) = q.range();
+    let result = state.services.report.fee_day_book(&school_id, &from, &to).await;
+    report_response(
+        result,
+        q.format.as_deref(),
+        &format!("Fee Collection Day-Book {} to {}", from, to),
+        &format!("daybook_{}_{}", from, to),
+        &[
+            ("Date", "paidOn"),
+            ("Receipt", "receiptNo"),
+            ("Student ID", "studentId"),
+            ("Student", "studentName"),
+            ("Class", "className"),
+            ("Fee Head", "feeHead"),
+            ("Mode", "paymentMode"),
+            ("Collected By", "collectedBy"),
+            ("Reference", "reference"),
+            ("Amount", "amount"),
+        ],
+    )
+}
+
+// GET /api/fees/:schoolId/reports/collection?from=&to=&format=
+pub async fn fee_collection_vs_demand(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<ReportQuery>,
+) -> impl IntoResponse {
+    let (from, to) = q.range();
+    let result = state
+        .services
+        .report
+        .fee_collection_vs_demand(&school_id, &from, &to)
+        .await;
+    report_response(
+        result,
+        q.format.as_deref(),
+        &format!("Collection vs Demand {} to {}", from, to),
+        &format!("collection_vs_demand_{}_{}", from, to),
+        &[
+            ("Class", "className"),
+            ("Fee Head", "feeHead"),
+            ("Demand", "demand"),
+            ("Collected", "collected"),
+            ("Balance", "balance"),
+            ("Collection %", "collectionPercent"),
+        ],
+    )
+}
+
+// GET /api/fees/:schoolId/reports/defaulters?asOf=&className=&format=
+pub async fn fee_defaulters(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<ReportQuery>,
+) -> impl IntoResponse {
+    let as_of = q
+        .as_of
+        .clone()
+        .or_else(|| q.to.clone())
+        .unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
+    let result = state
+        .services
+        .report
+        .fee_defaulters(&school_id, &as_of, q.class_name.clone())
+        .await;
+    report_response(
+        result,
+        q.format.as_deref(),
+        &format!("Fee Defaulters as of {}", as_of),
+        &format!("defaulters_{}", as_of),
+        &[
+            ("Student ID", "studentId"),
+            ("Student", "studentName"),
+            ("Class", "className"),
+            ("Section", "section"),
+            ("Contact", "contact"),
+            ("0-30", "days0To30"),
+            ("31-60", "days31To60"),
+            ("61-90", "days61To90"),
+            ("90+", "days90Plus"),
+            ("Pending", "pendingAmount"),
+            ("Oldest Due", "oldestDueDate"),
+        ],
+    )
+}
+
+// GET /api/fees/:schoolId/reports/concessions?from=&to=&format=
+pub async fn fee_concessions(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<ReportQuery>,
+) -> impl IntoResponse {
+    let (from, to) = q.range();
+    let result = state.services.report.fee_concessions(&school_id, &from, &to).await;
+    report_response(
+        result,
+        q.format.as_deref(),
+        &format!("Concessions and Waivers {} to {}", from, to),
+        &format!("concessions_{}_{}", from, to),
+        &[
+            ("Date", "date"),
+            ("Type", "type"),
+            ("Student ID", "studentId"),
+            ("Student", "studentName"),
+            ("Class", "className"),
+            ("Reference", "reference"),
+            ("Amount", "amount"),
+        ],
+    )
+}
+
+// GET /api/fees/:schoolId/reports/referrals?from=&to=&format=
+pub async fn referral_report(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<ReportQuery>,
+) -> impl IntoResponse {
+    let (from, to) = q.range();
+    let result = state.services.report.referral_report(&school_id, &from, &to).await;
+    report_response(
+        result,
+        q.format.as_deref(),
+        &format!("Referral Coupons {} to {}", from, to),
+        &format!("referrals_{}_{}", from, to),
+        &[
+            ("Referrer Type", "referrerType"),
+            ("Referrer ID", "referrerId"),
+            ("Referrer", "referrerName"),
+            ("Coupons", "couponNames"),
+            ("Uses", "uses"),
+            ("Admissions", "admissions"),
+            ("Discount Given", "discountGiven"),
+            ("Rewards Paid", "rewardsPaid"),
+        ],
+    )
+}
@@ -0,0 +1,93 @@
+use crate::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+fn session_result<T: serde::Serialize>(result: Result<T, Box<dyn std::error::Error + Send + Sync>>) -> axum::response::Response {
+    match result {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SessionQuery {
+    pub status: Option<String>,
+}
+
+// GET /api/sessions/:schoolId?status=upcoming|active|archived
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<SessionQuery>,
+) -> impl IntoResponse {
+    session_result(state.services.sessions.list_sessions(&school_id, q.status.as_deref()).await)
+}
+
+// POST /api/sessions/:schoolId   { name, startDate?, endDate?, createdBy }
+pub async fn create_session(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    session_result(state.services.sessions.create_session(&school_id, payload).await)
+}
+
+// GET /api/sessions/:schoolId/:sessionId
+pub async fn get_session(
+    State(state): State<AppState>,
+    Path((school_id, session_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    session_result(state.services.sessions.get_session(&school_id, &session_id).await)
+}
+
+// PUT /api/sessions/:schoolId/:sessionId   { name?, startDate?, endDate? }
+pub async fn update_session(
+    State(state): State<AppState>,
+    Path((school_id, session_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    session_result(state.services.sessions.update_session(&school_id, &session_id, payload).await)
+}
+
+// GET /api/sessions/:schoolId/:sessionId/rollover
+pub async fn rollover_overview(
+    State(state): State<AppState>,
+    Path((school_id, session_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    session_result(state.services.sessions.rollover_overview(&school_id, &session_id).await)
+}
+
+// PUT /api/sessions/:schoolId/:sessionId/rollover/classes/:classId
+//   { nextClassId?, nextSection?, defaultOutcome?, decisions?: [{ studentId, outcome: pass|detain|leave,
+//     nextClassId?, nextSection? }], decidedBy }
+pub async fn save_class_promotions(
+    State(state): State<AppState>,
+    Path((school_id, session_id, class_id)): Path<(String, String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    session_result(
+        state
+            .services
+            .sessions
+            .save_class_promotions(&school_id, &session_id, &class_id, payload)
+            .await,
+    )
+}
+
+// POST /api/sessions/:schoolId/:sessionId/rollover/complete   { toSessionId, carryBalances?, completedBy }
+pub async fn complete_rollover(
+    State(state): State<AppState>,
+    Path((school_id, session_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    session_result(state.services.sessions.complete_rollover(&school_id, &session_id, payload).await)
+}
@@ -0,0 +1,231 @@
+use crate::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{Datelike, Duration};
+use serde::Deserialize;
+use serde_json::json;
+
+fn timetable_result<T: serde::Serialize>(result: Result<T, Box<dyn std::error::Error + Send + Sync>>) -> axum::response::Response {
+    match result {
+        Ok(data) => Json(json!({"success": true, "data": data})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+fn today() -> String {
+    chrono::Local::now().date_naive().to_string()
+}
+
+// POST /api/timetable/:schoolId/classes/:classId/periods
+//   { name, startTime, endTime, teacherId, subjectId, dayOfWeek? }  (no dayOfWeek = every day)
+pub async fn add_period(
+    State(state): State<AppState>,
+    Path((school_id, class_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    match state.services.academic.add_period(&school_id, &class_id, payload).await {
+        Ok(()) => Json(json!({"success": true, "message": "Period added"})).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({"success": false, "message": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PeriodQuery {
+    pub day: Option<i32>,
+}
+
+// GET /api/timetable/:schoolId/periods?day=1..7
+pub async fn list_periods(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<PeriodQuery>,
+) -> impl IntoResponse {
+    timetable_result(state.services.timetable.list_periods(&school_id, q.day).await)
+}
+
+#[derive(Deserialize)]
+pub struct DateQuery {
+    pub date: Option<String>,
+}
+
+// GET /api/timetable/:schoolId/substitutions/proposals?date=YYYY-MM-DD  (default today)
+pub async fn substitution_proposals(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<DateQuery>,
+) -> impl IntoResponse {
+    let date = q.date.unwrap_or_else(today);
+    timetable_result(state.services.timetable.substitution_proposals(&school_id, &date).await)
+}
+
+// POST /api/timetable/:schoolId/substitutions
+//   { date, assignments: [{ periodId, substituteId }] | auto: true, assignedBy }
+pub async fn assign_substitutions(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    timetable_result(state.services.timetable.assign_substitutions(&school_id, payload).await)
+}
+
+#[derive(Deserialize)]
+pub struct RangeQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    #[serde(rename = "substituteId")]
+    pub substitute_id: Option<String>,
+}
+
+impl RangeQuery {
+    /// Defaults to the current week, Monday to Sunday.
+    fn range(&self) -> (String, String) {
+        let today = chrono::Local::now().date_naive();
+        let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+        (
+            self.from.clone().unwrap_or_else(|| monday.to_string()),
+            self.to.clone().unwrap_or_else(|| (monday + Duration::days(6)).to_string()),
+        )
+    }
+}
+
+// GET /api/timetable/:schoolId/substitutions?from=&to=&substituteId=
+pub async fn list_substitutions(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<RangeQuery>,
+) -> impl IntoResponse {
+    let (from, to) = q.range();
+    timetable_result(
+        state
+            .services
+            .timetable
+            .list_substitutions(&school_id, &from, &to, q.substitute_id.as_deref())
+            .await,
+    )
+}
+
+// DELETE /api/timetable/:schoolId/substitutions/:substitutionId
+pub async fn cancel_substitution(
+    State(state): State<AppState>,
+    Path((school_id, substitution_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    timetable_result(state.services.timetable.cancel_substitution(&school_id, &substitution_id).await)
+}
+
+// GET /api/timetable/:schoolId/substitutions/load?from=&to=
+pub async fn substitution_load(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Query(q): Query<RangeQuery>,
+) -> impl IntoResponse {
+    let (from, to) = q.range();
+    timetable_result(state.services.timetable.substitution_load(&school_id, &from, &to).await)
+}
+
+// GET /api/timetable/:schoolId/generator/settings
+pub async fn get_generator_settings(State(state): State<AppState>, Path(school_id): Path<String>) -> impl IntoResponse {
+    timetable_result(state.services.timetable.get_generator_settings(&school_id).await)
+}
+
+// PUT /api/timetable/:schoolId/generator/settings
+//   { days?, slots: [{ name?, startTime, endTime }], classes?: [{ classId, section? }],
+//     requirements: [{ classId, section?, subjectId, periodsPerWeek, teacherId?, roomCategory?, maxPerDay? }],
+//     teachers?: [{ employeeId, maxPeriodsPerDay?, maxPeriodsPerWeek?, unavailable?: [{ day, slot? }] }],
+//     teacherMaxPerDay?, fixed?: [{ classId?, section?, day?, slot, label?, subjectId?, teacherId?, roomId? }],
+//     updatedBy }
+pub async fn save_generator_settings(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    timetable_result(state.services.timetable.save_generator_settings(&school_id, payload).await)
+}
+
+// POST /api/timetable/:schoolId/generator/drafts   { fromDraftId?, seed?, createdBy }
+pub async fn generate_timetable(
+    State(state): State<AppState>,
+    Path(school_id): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    timetable_result(state.services.timetable.generate_timetable(&school_id, payload).await)
+}
+
+// GET /api/timetable/:schoolId/generator/drafts
+pub async fn list_timetable_drafts(State(state): State<AppState>, Path(school_id): Path<String>) -> impl IntoResponse {
+    timetable_result(state.services.timetable.list_timetable_drafts(&school_id).await)
+}
+
+// GET /api/timetable/:schoolId/generator/drafts/:draftId
+pub async fn get_timetable_draft(
+    State(state): State<AppState>,
+    Path((school_id, draft_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    timetable_result(state.services.timetable.get_timetable_draft(&school_id, &draft_id).await)
+}
+
+// PUT /api/timetable/:schoolId/generator/drafts/:draftId/pins
+//   { pins: [{ classId, section?, day, slot, subjectId, teacherId?, roomId? }] }
+pub async fn pin_lessons(
+    State(state): State<AppState>,
+    Path((school_id, draft_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    timetable_result(state.services.timetable.pin_lessons(&school_id, &draft_id, payload).await)
+}
+
+// POST /api/timetable/:schoolId/generator/drafts/:draftId/publish   { publishedBy, acceptUnsatisfied? }
+pub async fn publish_timetable(
+    State(state): State<AppState>,
+    Path((school_id, draft_id)): Path<(String, String)>,
+    Json(payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    timetable_result(state.services.timetable.publish_timetable(&school_id, &draft_id, payload).await)
+}
+
+#[derive(Deserialize)]
+pub struct ViewQuery {
+    pub section: Option<String>,
+    #[serde(rename = "draftId")]
+    pub draft_id: Option<String>,
+}
+
+// GET /api/timetable/:schoolId/views/classes/:classId?section=&draftId=  (no draftId = published)
+pub async fn class_timetable(
+    State(state): State<AppState>,
+    Path((school_id, class_id)): Path<(String, String)>,
+    Query(q): Query<ViewQuery>,
+) -> impl IntoResponse {
+    timetable_result(
+        state
+            .services
+            .timetable
+            .class_timetable(&school_id, &class_id, q.section.as_deref(), q.draft_id.as_deref())
+            .await,
+    )
+}
+
+// GET /api/timetable/:schoolId/views/teachers/:teacherId?draftId=
+pub async fn teacher_timetable(
+    State(state): State<AppState>,
+    Path((school_id, teacher_id)): Path<(String, String)>,
+    Query(q): Query<ViewQuery>,
+) -> impl IntoResponse {
+    timetable_result(
+        state
+            .services
+            .timetable
+            .teacher_timetable(&school_id, &teacher_id, q.draft_id.as_deref())
+            .await,
+    )
+}
@@ -0,0 +1,326 @@
+use crate::repository::Repositories;
+use crate::services::attendance_service::attendance_settings;
+use crate::services::holiday_service::load_calendar;
+use crate::services::traits::*;
+use crate::AppState;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, Utc};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+/// How long after an alert a guardian's reply is still matched to it.
+const REPLY_WINDOW_DAYS: i64 = 3;
+const MAX_REASON_CHARS: usize = 500;
+/// A failed alert is retried up to this many sends in all, waiting twice as long each time
+/// from `RETRY_AFTER_MINUTES` after the first failure.
+const MAX_ALERT_ATTEMPTS: i64 = 4;
+const RETRY_AFTER_MINUTES: i64 = 15;
+
+pub struct PostgresAbsenceAlertService {
+    pub repos: Arc<Repositories>,
+    pub notifier: Arc<dyn NotificationService>,
+}
+
+fn parse_date(v: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(v, "%Y-%m-%d").map_err(|_| "date must be YYYY-MM-DD".into())
+}
+
+/// Where a guardian is reached on `channel`: the student's contact number (or the alternative
+/// one), their email, or the student app for push.
+fn guardian_recipient(channel: &str, student: &Value) -> Option<String> {
+    let fields: &[&str] = match channel {
+        "email" => &["email"],
+        "push" => &["studentId"],
+        _ => &["contact", "alternativeContact"],
+    };
+    fields
+        .iter()
+        .filter_map(|f| student[*f].as_str().map(|s| s.trim()))
+        .find(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+/// Whether a failed alert is due another try: under the attempt cap and past its backoff.
+fn retry_due(prev: &Value, now: DateTime<Utc>) -> bool {
+    let attempts = prev["attempts"].as_i64().unwrap_or(1).max(1);
+    if attempts >= MAX_ALERT_ATTEMPTS {
+        return false;
+    }
+    let wait = Duration::minutes(RETRY_AFTER_MINUTES << (attempts - 1));
+    prev["lastAttemptAt"]
+        .as_str()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .is_none_or(|last| now >= last.with_timezone(&Utc) + wait)
+}
+
+fn key(s: &Value) -> String {
+    s.as_str().unwrap_or("").trim().to_lowercase()
+}
+
+#[async_trait]
+impl AbsenceAlertService for PostgresAbsenceAlertService {
+    async fn run_absence_alerts(&self, school_id: &str, date: &str, dry_run: bool) -> Result<Value, AppError> {
+        let date = parse_date(date)?;
+        let settings = attendance_settings(&self.repos, school_id).await?;
+        let config = &settings["absenceAlerts"];
+        let channel = config["channel"].as_str().unwrap_or("sms").to_string();
+        let calendar = load_calendar(&self.repos, school_id, date, date).await?;
+        if let Some(rule) = calendar.weekly_off(date) {
+            return Ok(json!({"date": date.to_string(), "dryRun": dry_run, "skippedDay": rule, "sent": 0, "results": []}));
+        }
+
+        let students: Vec<Value> = self
+            .repos
+            .student
+            .get_students(school_id)
+            .await?
+            .into_iter()
+            .filter(|s| s["status"] != "inactive")
+            .collect();
+        let ids: Vec<String> = students.iter().filter_map(|s| s["studentId"].as_str().map(|s| s.to_string())).collect();
+        let marked = self.repos.attendance.get_attendance_for_users(school_id, "student", date, &ids).await?;
+        let status_of: HashMap<&str, &str> = marked
+            .iter()
+            .filter_map(|r| Some((r["userId"].as_str()?, r["status"].as_str()?)))
+            .collect();
+
+        // A class counts as taken once anyone in it (class and section) has been marked
+        let taken: HashSet<(String, String)> = students
+            .iter()
+            .filter(|s| s["studentId"].as_str().is_some_and(|id| status_of.contains_key(id)))
+            .map(|s| (key(&s["className"]), key(&s["section"])))
+            .collect();
+        let already: HashMap<String, Value> = self
+            .repos
+            .attendance
+            .get_absence_alerts(school_id, date)
+            .await?
+            .into_iter()
+            .filter_map(|a| Some((a["studentId"].as_str()?.to_string(), a)))
+            .collect();
+
+        let contacts: HashMap<String, Value> = self
+            .repos
+            .student
+            .get_student_contacts(school_id, &ids)
+            .await?
+            .into_iter()
+            .filter_map(|c| Some((c["studentId"].as_str()?.to_string(), c)))
+            .collect();
+        let school_name = self
+            .repos
+            .school
+            .get_school(school_id)
+            .await?
+            .and_then(|s| s["schoolName"].as_str().map(|n| n.to_string()))
+            .unwrap_or_default();
+
+        let now = Utc::now();
+        let mut results = Vec::new();
+        let (mut sent, mut skipped, mut failed) = (0, 0, 0);
+        for student in &students {
+            let Some(student_id) = student["studentId"].as_str() else { continue };
+            if calendar.off_reason_for(date, "student", student_id).is_some() {
+                continue;
+            }
+            let absence = match status_of.get(student_id) {
+                Some(&"absent") => "absent",
+                None if config["includeUnmarked"] != false
+                    && taken.contains(&(key(&student["className"]), key(&student["section"]))) =>
+                {
+                    "unmarked"
+                }
+                _ => continue,
+            };
+            // A failed send is retried with backoff until the attempts run out; anything else is only sent once
+            if let Some(prev) = already.get(student_id) {
+                let failed_before = prev["status"] == "failed" || prev["deliveryStatus"] == "failed";
+                if !failed_before || !retry_due(prev, now) {
+                    continue;
+                }
+            }
+
+            let vars = json!({
+                "studentName": student["name"],
+                "studentId": student_id,
+                "className": match student["section"].as_str().filter(|s| !s.trim().is_empty()) {
+                    Some(section) => format!("{} {}", student["className"].as_str().unwrap_or(""), section),
+                    None => student["className"].as_str().unwrap_or("").to_string(),
+                },
+                "date": date.format("%d %b %Y").to_string(),
+                "absence": if absence == "absent" { "was marked absent" } else { "has not been marked present" },
+                "schoolName": school_name
+            });
+            let recipient = contacts.get(student_id).and_then(|c| guardian_recipient(&channel, c));
+            if dry_run {
+                results.push(json!({"studentId": student_id, "attendanceStatus": absence, "channel": channel, "recipient": recipient, "vars": vars, "status": "preview"}));
+                continue;
+            }
+            let Some(recipient) = recipient else {
+                skipped += 1;
+                self.repos
+                    .attendance
+                    .save_absence_alert(
+                        school_id,
+                        &json!({"studentId": student_id, "date": date.to_string(), "attendanceStatus": absence, "channel": channel, "status": "no_recipient"}),
+                    )
+                    .await?;
+                results.push(json!({"studentId": student_id, "status": "skipped", "reason": "no_recipient"}));
+                continue;
+            };
+
+            let meta = json!({
+                "dedupeKey": format!("absence_alert:{}:{}", student_id, date),
+                "refType": "absence_alert",
+                "refId": student_id
+            });
+            let outcome = self
+                .notifier
+                .send_templated(school_id, "absence_alert", &channel, &recipient, &vars, meta)
+                .await?;
+            let status = outcome["status"].as_str().unwrap_or("failed");
+            match status {
+                "sent" => sent += 1,
+                "failed" => failed += 1,
+                _ => skipped += 1,
+            }
+            if status != "skipped" {
+                self.repos
+                    .attendance
+                    .save_absence_alert(
+                        school_id,
+                        &json!({
+                            "studentId": student_id,
+                            "date": date.to_string(),
+                            "attendanceStatus": absence,
+                            "channel": channel,
+                            "recipient": recipient,
+                            "notificationId": outcome["notificationId"],
+                            "status": status
+                        }),
+                    )
+                    .await?;
+            }
+            results.push(json!({"studentId": student_id, "attendanceStatus": absence, "channel": channel, "recipient": recipient, "status": status}));
+        }
+
+        Ok(json!({
+            "date": date.to_string(),
+            "dryRun": dry_run,
+            "sent": sent,
+            "skipped": skipped,
+            "failed": failed,
+            "results": results
+        }))
+    }
+
+    async fn list_absence_alerts(&self, school_id: &str, date: &str) -> Result<Vec<Value>, AppError> {
+        self.repos.attendance.get_absence_alerts(school_id, parse_date(date)?).await
+    }
+
+    async fn record_guardian_reply(&self, data: Value) -> Result<Value, AppError> {
+        let from = data["from"].as_str().map(|s| s.trim()).filter(|s| !s.is_empty()).ok_or("from is required")?;
+        let body = data["body"].as_str().map(|s| s.trim()).filter(|s| !s.is_empty()).ok_or("body is required")?;
+        let since = Local::now().date_naive() - Duration::days(REPLY_WINDOW_DAYS);
+        let alert = self
+            .repos
+            .attendance
+            .find_absence_alert_for_reply(from, since)
+            .await?
+            .ok_or("No recent absence alert was sent to this sender")?;
+        let school_id = alert["schoolId"].as_str().unwrap_or("");
+        let student_id = alert["studentId"].as_str().unwrap_or("");
+        let date = alert["date"].as_str().unwrap_or("");
+        let mut result = self
+            .submit_absence_reason(school_id, student_id, date, body, &format!("reply:{}", from))
+            .await?;
+        result["schoolId"] = json!(school_id);
+        Ok(result)
+    }
+
+    async fn submit_absence_reason(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        date: &str,
+        reason: &str,
+        via: &str,
+    ) -> Result<Value, AppError> {
+        let date = parse_date(date)?;
+        if date > Local::now().date_naive() {
+            return Err("A reason can't be given for a future date".into());
+        }
+        let reason: String = reason.trim().chars().take(MAX_REASON_CHARS).collect();
+        if reason.is_empty() {
+            return Err("reason is required".into());
+        }
+        self.repos
+            .student
+            .get_student(school_id, student_id)
+            .await?
+            .ok_or("Student not found")?;
+        let existing = self
+            .repos
+            .attendance
+            .get_attendance_for_users(school_id, "student", date, &[student_id.to_string()])
+            .await?;
+        if let Some(status) = existing.first().and_then(|r| r["status"].as_str()).filter(|s| *s != "absent") {
+            return Err(format!("The student is marked {} on {}", status.replace('_', " "), date).into());
+        }
+        self.repos
+            .attendance
+            .save_absence_reason(school_id, student_id, date, &reason, via)
+            .await?;
+        Ok(json!({"studentId": student_id, "date": date.to_string(), "reason": reason, "via": via}))
+    }
+}
+
+/// Checks every five minutes and sends the day's absence alerts for each school once its
+/// cutoff time has passed. Students marked absent later in the day are picked up by later runs.
+pub async fn start_absence_alert_job(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(5 * 60));
+
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+            let now = Local::now();
+            let schools = match state.repos.reminder.get_active_school_ids().await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!("[Absence Alerts] Failed to load schools: {}", e);
+                    continue;
+                }
+            };
+            for school_id in schools {
+                let config = match attendance_settings(&state.repos, &school_id).await {
+                    Ok(s) => s["absenceAlerts"].clone(),
+                    Err(e) => {
+                        tracing::error!("[Absence Alerts] {} settings failed: {}", school_id, e);
+                        continue;
+                    }
+                };
+                let cutoff = config["cutoffTime"].as_str().and_then(|t| NaiveTime::parse_from_str(t, "%H:%M").ok());
+                if config["enabled"] != true || cutoff.is_none_or(|c| now.time() < c) {
+                    continue;
+                }
+                match state
+                    .services
+                    .absence_alerts
+                    .run_absence_alerts(&school_id, &now.date_naive().to_string(), false)
+                    .await
+                {
+                    Ok(r) if r["sent"].as_u64().unwrap_or(0) + r["failed"].as_u64().unwrap_or(0) > 0 => tracing::info!(
+                        "[Absence Alerts] {}: {} sent, {} failed",
+                        school_id,
+                        r["sent"],
+                        r["failed"]
+                    ),
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("[Absence Alerts] {} failed: {}", school_id, e),
+                }
+            }
+        }
+    });
+}
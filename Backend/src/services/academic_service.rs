@@ -1,5 +1,6 @@
 use crate::repository::traits::*;
 use crate::repository::Repositories;
+use crate::services::session_service::{ensure_open_class, ensure_open_session};
 use crate::services::traits::*;
 use async_trait::async_trait;
 use serde_json::{json, Value};
@@ -17,6 +18,7 @@ impl AcademicService for PostgresAcademicService {
         school_id: &str,
         data: Value,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        ensure_open_session(&self.repos, school_id, data["sessionId"].as_str()).await?;
         let res = self
             .repos
             .academic
@@ -42,6 +44,7 @@ impl AcademicService for PostgresAcademicService {
         class_id: &str,
         data: Value,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        ensure_open_class(&self.repos, school_id, class_id).await?;
         self.repos
             .academic
             .update_class(school_id, class_id, data)
@@ -56,6 +59,7 @@ impl AcademicService for PostgresAcademicService {
         class_id: &str,
         data: Value,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        ensure_open_class(&self.repos, school_id, class_id).await?;
         self.repos
             .academic
             .add_stream(school_id, class_id, data.clone())
@@ -70,6 +74,7 @@ impl AcademicService for PostgresAcademicService {
         class_id: &str,
         data: Value,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        ensure_open_class(&self.repos, school_id, class_id).await?;
         self.repos
             .academic
             .add_period(school_id, class_id, data)
@@ -107,6 +112,7 @@ impl AcademicService for PostgresAcademicService {
         school_id: &str,
         data: Value,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        ensure_open_session(&self.repos, school_id, data["sessionId"].as_str()).await?;
         self.repos.academic.add_exam(school_id, data).await
     }
 
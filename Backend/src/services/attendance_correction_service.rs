@@ -0,0 +1,328 @@
+use crate::logic::attendance::{change_entry, correction_locked, format_duration, CORRECTABLE_STATUSES};
+use crate::repository::Repositories;
+use crate::services::attendance_service::attendance_settings;
+use crate::services::employee_document_service::{key_segment, DocumentStorage};
+use crate::services::payroll_service::ensure_payroll_open;
+use crate::services::session_service::ensure_open_date;
+use crate::services::traits::*;
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+const MAX_REASON_CHARS: usize = 1000;
+const MAX_EVIDENCE_FILES: usize = 5;
+const MAX_EVIDENCE_BYTES: usize = 5 * 1024 * 1024;
+
+pub struct PostgresAttendanceCorrectionService {
+    pub repos: Arc<Repositories>,
+    pub storage: Arc<dyn DocumentStorage>,
+}
+
+fn parse_date(v: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(v.trim(), "%Y-%m-%d").map_err(|_| "date must be YYYY-MM-DD".into())
+}
+
+fn parse_time(v: &Value, field: &str) -> Result<Option<DateTime<Utc>>, AppError> {
+    match v.as_str().filter(|s| !s.trim().is_empty()) {
+        None => Ok(None),
+        Some(t) => DateTime::parse_from_rfc3339(t.trim())
+            .map(|t| Some(t.with_timezone(&Utc)))
+            .map_err(|_| format!("{} must be an RFC 3339 timestamp", field).into()),
+    }
+}
+
+/// Evidence as shown to callers: the download path instead of the storage key.
+fn with_evidence_urls(school_id: &str, mut request: Value) -> Value {
+    let request_id = request["requestId"].as_str().unwrap_or("").to_string();
+    if let Some(files) = request["evidence"].as_array_mut() {
+        for (i, file) in files.iter_mut().enumerate() {
+            file["fileUrl"] = json!(format!(
+                "/api/operations/attendance/{}/corrections/{}/evidence/{}",
+                school_id, request_id, i
+            ));
+            if let Some(obj) = file.as_object_mut() {
+                obj.remove("storageKey");
+            }
+        }
+    }
+    request
+}
+
+impl PostgresAttendanceCorrectionService {
+    async fn request(&self, school_id: &str, request_id: &str) -> Result<Value, AppError> {
+        self.repos
+            .attendance
+            .get_correction_request(school_id, request_id)
+            .await?
+            .ok_or_else(|| "Correction request not found".into())
+    }
+
+    async fn current_record(&self, school_id: &str, role: &str, user_id: &str, date: NaiveDate) -> Result<Option<Value>, AppError> {
+        Ok(self
+            .repos
+            .attendance
+            .get_attendance_for_users(school_id, role, date, &[user_id.to_string()])
+            .await?
+            .into_iter()
+            .next())
+    }
+}
+
+#[async_trait]
+impl AttendanceCorrectionService for PostgresAttendanceCorrectionService {
+    async fn request_correction(&self, school_id: &str, role: &str, user_id: &str, data: Value) -> Result<Value, AppError> {
+        let exists = match role {
+            "employee" => self.repos.employee.get_employee(school_id, user_id).await?.is_some(),
+            "student" => self.repos.student.get_student(school_id, user_id).await?.is_some(),
+            _ => return Err("role must be employee or student".into()),
+        };
+        if !exists {
+            return Err(format!("No {} {}", role, user_id).into());
+        }
+        let date = parse_date(data["date"].as_str().unwrap_or(""))?;
+        if date > Local::now().date_naive() {
+            return Err("Attendance can't be corrected for a future date".into());
+        }
+        let reason: String = data["reason"].as_str().unwrap_or("").trim().chars().take(MAX_REASON_CHARS).collect();
+        if reason.is_empty() {
+            return Err("reason is required".into());
+        }
+
+        let remove = data["remove"].as_bool().unwrap_or(false);
+        let status = data["status"].as_str().map(|s| s.trim().to_lowercase());
+        let in_time = parse_time(&data["inTime"], "inTime")?;
+        let out_time = parse_time(&data["outTime"], "outTime")?;
+        if !remove && status.is_none() && in_time.is_none() && out_time.is_none() {
+            return Err("Give the status or times the record should have, or remove: true".into());
+        }
+        if let Some(s) = status.as_deref().filter(|s| !CORRECTABLE_STATUSES.contains(s)) {
+            return Err(format!("status must be one of {}, not {}", CORRECTABLE_STATUSES.join(", "), s).into());
+        }
+        if let (Some(i), Some(o)) = (in_time, out_time) {
+            if o <= i {
+                return Err("outTime must be after inTime".into());
+            }
+        }
+
+        let current = self.current_record(school_id, role, user_id, date).await?;
+        match &current {
+            None if remove => return Err(format!("Nothing is recorded for {}", date).into()),
+            None if status.is_none() => return Err(format!("Nothing is recorded for {}; give the status it should have", date).into()),
+            Some(c) if !remove
+                && status.as_deref().is_none_or(|s| c["status"] == s)
+                && in_time.is_none()
+                && out_time.is_none() =>
+            {
+                return Err(format!("Attendance for {} is already {}", date, c["status"].as_str().unwrap_or("")).into());
+            }
+            _ => {}
+        }
+
+        let pending = self
+            .repos
+            .attendance
+            .get_correction_requests(
+                school_id,
+                &json!({"status": "pending", "role": role, "userId": user_id, "from": date.to_string(), "to": date.to_string()}),
+            )
+            .await?;
+        if let Some(p) = pending.first() {
+            return Err(format!("Request {} for {} is already waiting for a decision", p["requestId"].as_str().unwrap_or(""), date).into());
+        }
+
+        let settings = attendance_settings(&self.repos, school_id).await?;
+        let locked = date < Local::now().date_naive()
+            && current.as_ref().is_some_and(|c| correction_locked(&settings, c["createdAt"].as_str(), Utc::now()));
+        let requested = if remove {
+            json!({"remove": true})
+        } else {
+            json!({
+                "status": status,
+                "inTime": in_time.map(|t| t.to_rfc3339()),
+                "outTime": out_time.map(|t| t.to_rfc3339())
+            })
+        };
+        let record = json!({
+            "requestId": format!("ACR{}", Utc::now().timestamp_millis()),
+            "role": role,
+            "userId": user_id,
+            "date": date.to_string(),
+            "requested": requested,
+            "currentRecord": current,
+            "locked": locked,
+            "reason": reason,
+            "requestedBy": data["requestedBy"].as_str().unwrap_or(user_id),
+            "requestedVia": data["via"].as_str().unwrap_or("office")
+        });
+        let saved = self.repos.attendance.add_correction_request(school_id, &record).await?;
+        Ok(with_evidence_urls(school_id, saved))
+    }
+
+    async fn list_corrections(&self, school_id: &str, filter: Value) -> Result<Vec<Value>, AppError> {
+        Ok(self
+            .repos
+            .attendance
+            .get_correction_requests(school_id, &filter)
+            .await?
+            .into_iter()
+            .map(|r| with_evidence_urls(school_id, r))
+            .collect())
+    }
+
+    async fn get_correction(&self, school_id: &str, request_id: &str) -> Result<Value, AppError> {
+        Ok(with_evidence_urls(school_id, self.request(school_id, request_id).await?))
+    }
+
+    async fn add_evidence(
+        &self,
+        school_id: &str,
+        request_id: &str,
+        file_name: &str,
+        content_type: Option<&str>,
+        bytes: Vec<u8>,
+        uploaded_by: Option<&str>,
+    ) -> Result<Value, AppError> {
+        if bytes.is_empty() {
+            return Err("No file uploaded".into());
+        }
+        if bytes.len() > MAX_EVIDENCE_BYTES {
+            return Err(format!("Evidence files must be under {} MB", MAX_EVIDENCE_BYTES / (1024 * 1024)).into());
+        }
+        let request = self.request(school_id, request_id).await?;
+        if request["status"] != "pending" {
+            return Err("Evidence can only be added while the request is pending".into());
+        }
+        let mut evidence = request["evidence"].as_array().cloned().unwrap_or_default();
+        if evidence.len() >= MAX_EVIDENCE_FILES {
+            return Err(format!("A request can have at most {} evidence files", MAX_EVIDENCE_FILES).into());
+        }
+
+        let storage_key = format!(
+            "attendance-corrections/{}/{}/{}-{}",
+            key_segment(school_id),
+            key_segment(request_id),
+            evidence.len(),
+            key_segment(file_name)
+        );
+        self.storage.put(&storage_key, &bytes, content_type).await?;
+        evidence.push(json!({
+            "fileName": file_name,
+            "contentType": content_type,
+            "sizeBytes": bytes.len(),
+            "storageKey": storage_key,
+            "uploadedBy": uploaded_by,
+            "uploadedAt": Utc::now().to_rfc3339()
+        }));
+        if let Err(e) = self.repos.attendance.save_correction_evidence(school_id, request_id, &json!(evidence)).await {
+            // Don't leave an orphaned file behind
+            let _ = self.storage.delete(&storage_key).await;
+            return Err(e);
+        }
+        self.get_correction(school_id, request_id).await
+    }
+
+    async fn evidence_file(&self, school_id: &str, request_id: &str, index: usize) -> Result<(Value, Vec<u8>), AppError> {
+        let request = self.request(school_id, request_id).await?;
+        let file = request["evidence"].get(index).cloned().ok_or("Evidence file not found")?;
+        let bytes = self.storage.get(file["storageKey"].as_str().unwrap_or("")).await?;
+        Ok((file, bytes))
+    }
+
+    async fn decide_correction(&self, school_id: &str, request_id: &str, data: Value) -> Result<Value, AppError> {
+        let request = self.request(school_id, request_id).await?;
+        if request["status"] != "pending" {
+            return Err(format!("The request is already {}", request["status"].as_str().unwrap_or("")).into());
+        }
+        let decided_by = data["decidedBy"].as_str().map(|s| s.trim()).filter(|s| !s.is_empty()).ok_or("decidedBy is required")?;
+        if request["requestedBy"].as_str() == Some(decided_by) || request["userId"].as_str() == Some(decided_by) {
+            return Err("A correction can't be decided by the person it is for or who raised it".into());
+        }
+        let note = data["note"].as_str().map(|s| s.trim()).filter(|s| !s.is_empty());
+
+        match data["decision"].as_str() {
+            Some("reject") => {
+                let note = note.ok_or("Give a note saying why the request is rejected")?;
+                if !self
+                    .repos
+                    .attendance
+                    .close_correction_request(school_id, request_id, "rejected", Some(decided_by), Some(note))
+                    .await?
+                {
+                    return Err("The request is no longer pending".into());
+                }
+            }
+            Some("approve") => {
+                let role = request["role"].as_str().unwrap_or("");
+                let user_id = request["userId"].as_str().unwrap_or("");
+                let date = parse_date(request["date"].as_str().unwrap_or(""))?;
+                ensure_open_date(&self.repos, school_id, date).await?;
+                if role == "employee" {
+                    ensure_payroll_open(&self.repos, school_id, date.month() as i32, date.year()).await?;
+                }
+
+                // Apply to the record as it is now, which may have changed since the request
+                let before = self.current_record(school_id, role, user_id, date).await?;
+                let requested = &request["requested"];
+                let after = if requested["remove"] == true {
+                    None
+                } else {
+                    let mut record = before.clone().unwrap_or_else(|| json!({}));
+                    for key in ["status", "inTime", "outTime"] {
+                        if !requested[key].is_null() {
+                            record[key] = requested[key].clone();
+                        }
+                    }
+                    if record["status"].is_null() {
+                        return Err("The request doesn't say which status the record should have".into());
+                    }
+                    let times = (parse_time(&record["inTime"], "inTime")?, parse_time(&record["outTime"], "outTime")?);
+                    if let (Some(i), Some(o)) = times {
+                        record["totalTime"] = json!(format_duration((o - i).num_minutes().max(0)));
+                    }
+                    Some(record)
+                };
+
+                let mut history = change_entry(
+                    &date.to_string(),
+                    before.as_ref(),
+                    after.as_ref(),
+                    Some(decided_by),
+                    request["reason"].as_str(),
+                );
+                history["requestId"] = json!(request_id);
+                history["requestedBy"] = request["requestedBy"].clone();
+                history["note"] = json!(note);
+                self.repos
+                    .attendance
+                    .apply_attendance_correction(school_id, &request, after.as_ref(), &json!({"decidedBy": decided_by, "note": note}), &history)
+                    .await?;
+            }
+            _ => return Err("decision must be approve or reject".into()),
+        }
+        self.get_correction(school_id, request_id).await
+    }
+
+    async fn cancel_correction(&self, school_id: &str, request_id: &str, cancelled_by: &str) -> Result<Value, AppError> {
+        let request = self.request(school_id, request_id).await?;
+        let owner = request["requestedBy"].as_str() == Some(cancelled_by) || request["userId"].as_str() == Some(cancelled_by);
+        if !owner {
+            return Err("Only the person who raised the request can cancel it".into());
+        }
+        if !self
+            .repos
+            .attendance
+            .close_correction_request(school_id, request_id, "cancelled", Some(cancelled_by), None)
+            .await?
+        {
+            return Err(format!("The request is already {}", request["status"].as_str().unwrap_or("")).into());
+        }
+        self.get_correction(school_id, request_id).await
+    }
+
+    async fn attendance_history(&self, school_id: &str, user_id: &str, from: Option<&str>, to: Option<&str>) -> Result<Vec<Value>, AppError> {
+        let from = from.filter(|s| !s.is_empty()).map(parse_date).transpose()?;
+        let to = to.filter(|s| !s.is_empty()).map(parse_date).transpose()?;
+        self.repos.attendance.get_attendance_history(school_id, user_id, from, to).await
+    }
+}
@@ -0,0 +1,870 @@
+use crate::logic::attendance::{
+    attendance_percent, correction_locked, default_settings, from_punches, merge_settings, normalize_status, register_code, status_summary,
+    validate_absence_alerts, CLASS_STATUSES,
+};
+use crate::logic::self_checkin::{constant_time_eq, distance_meters, geofence, qr_token, validate_settings, verify_qr_token};
+use crate::repository::Repositories;
+use crate::services::holiday_service::load_calendar;
+use crate::services::payroll_service::{employee_name, ensure_payroll_open};
+use crate::services::session_service::ensure_open_date;
+use crate::services::traits::*;
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, Local, Months, NaiveDate, Utc, Weekday};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+
+pub struct PostgresAttendanceService {
+    pub repos: Arc<Repositories>,
+    pub operations: Arc<dyn OperationsService>,
+}
+
+/// The school's attendance settings with defaults filled in.
+pub(crate) async fn attendance_settings(repos: &Repositories, school_id: &str) -> Result<Value, AppError> {
+    Ok(merge_settings(&repos.attendance.get_attendance_settings(school_id).await?))
+}
+
+/// Refuses attendance writes on a day in an archived session and, for employees, in a month
+/// whose payroll run is approved.
+pub(crate) async fn ensure_attendance_open(repos: &Repositories, school_id: &str, role: &str, date: NaiveDate) -> Result<(), AppError> {
+    ensure_open_date(repos, school_id, date).await?;
+    if role == "employee" {
+        ensure_payroll_open(repos, school_id, date.month() as i32, date.year()).await?;
+    }
+    Ok(())
+}
+
+/// Whether a saved record on a past day is past its correction window; it then changes only
+/// through an approved correction request.
+pub(crate) fn record_locked(settings: &Value, record: &Value, date: NaiveDate) -> bool {
+    date < Local::now().date_naive() && correction_locked(settings, record["createdAt"].as_str(), Utc::now())
+}
+
+fn weekday_name(days_from_monday: u32) -> &'static str {
+    ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"][days_from_monday as usize % 7]
+}
+
+fn parse_date(v: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(v, "%Y-%m-%d").map_err(|_| "date must be YYYY-MM-DD".into())
+}
+
+/// A day in a register. `off` is why it isn't a working day (the weekly-off rule's name or the
+/// holiday's title); people on a holiday's exempt list still work it. `taken` is whether anyone in scope has
+/// attendance recorded that day.
+struct RegisterDay {
+    date: NaiveDate,
+    key: String,
+    off: Option<String>,
+    weekly: bool,
+    exempt: Vec<String>,
+    taken: bool,
+}
+
+/// Students on a class roster are stored against the class name (older records use the ID).
+pub(crate) fn in_class(student: &Value, class: &Value, section: Option<&str>) -> bool {
+    let class_name = student["className"].as_str().unwrap_or("").trim();
+    let matches_class = ["id", "name"]
+        .iter()
+        .filter_map(|k| class[*k].as_str())
+        .any(|c| c.trim().eq_ignore_ascii_case(class_name));
+    let matches_section = section.is_none_or(|s| student["section"].as_str().is_some_and(|st| st.trim().eq_ignore_ascii_case(s.trim())));
+    matches_class && matches_section && student["status"] != "inactive"
+}
+
+/// The class and its active students (optionally one section) in roll-number order.
+pub(crate) async fn class_roster(
+    repos: &Repositories,
+    school_id: &str,
+    class_id: &str,
+    section: Option<&str>,
+) -> Result<(Value, Vec<Value>), AppError> {
+    let class = repos.academic.get_class(school_id, class_id).await?.ok_or("Class not found")?;
+    let mut students: Vec<Value> = repos
+        .student
+        .get_students(school_id)
+        .await?
+        .into_iter()
+        .filter(|s| in_class(s, &class, section))
+        .collect();
+    students.sort_by_key(|s| (s["rollNumber"].as_i64().unwrap_or(i64::MAX), s["name"].as_str().unwrap_or("").to_string()));
+    Ok((class, students))
+}
+
+impl PostgresAttendanceService {
+    async fn roster(&self, school_id: &str, class_id: &str, section: Option<&str>) -> Result<(Value, Vec<Value>), AppError> {
+        class_roster(&self.repos, school_id, class_id, section).await
+    }
+
+    /// When the sheet was first taken and until when it can be corrected (`None` = no limit).
+    fn correction_window(settings: &Value, existing: &[Value]) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        let taken_at = existing
+            .iter()
+            .filter_map(|r| r["createdAt"].as_str().and_then(|t| DateTime::parse_from_rfc3339(t).ok()))
+            .map(|t| t.with_timezone(&Utc))
+            .min();
+        let until = match (taken_at, settings["correctionWindowHours"].as_f64()) {
+            (Some(t), Some(hours)) => Some(t + Duration::minutes((hours * 60.0) as i64)),
+            _ => None,
+        };
+        (taken_at, until)
+    }
+
+    /// Writes a self check-in or check-out through the regular attendance marking, so the payroll
+    /// lock and history apply. Returns the day's rule outcome with the saved attendance.
+    async fn record_self_check_in(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        action: &str,
+        now: DateTime<Local>,
+        settings: &Value,
+    ) -> Result<Value, AppError> {
+        let date = now.date_naive();
+        if let Some(reason) = self.holiday(school_id, date).await? {
+            return Err(format!("Check-in isn't open on {}", reason).into());
+        }
+        let existing = self
+            .repos
+            .attendance
+            .get_attendance_for_users(school_id, "employee", date, &[employee_id.to_string()])
+            .await?
+            .into_iter()
+            .next();
+        let status = existing.as_ref().and_then(|r| r["status"].as_str().map(|s| s.to_string()));
+        if let Some(s) = status.as_deref().filter(|s| !["present", "late", "half_day"].contains(s)) {
+            return Err(format!("You are marked {} today", s.replace('_', " ")).into());
+        }
+        let checked_in = existing
+            .as_ref()
+            .and_then(|r| r["inTime"].as_str())
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Local));
+
+        let (rule, data) = match (action, checked_in) {
+            ("in", Some(t)) => return Err(format!("Already checked in at {}", t.format("%H:%M")).into()),
+            ("in", None) => {
+                let rule = from_punches(&[now.naive_local()], settings).unwrap_or_default();
+                let status = status.unwrap_or_else(|| rule["status"].as_str().unwrap_or("present").to_string());
+                let data = json!({"date": date.to_string(), "status": status, "inTime": now.to_rfc3339()});
+                (rule, data)
+            }
+            ("out", None) => return Err("Check in before checking out".into()),
+            ("out", Some(t)) => {
+                let rule = from_punches(&[t.naive_local(), now.naive_local()], settings).unwrap_or_default();
+                let data = json!({
+                    "date": date.to_string(),
+                    "status": status.unwrap_or_else(|| "present".to_string()),
+                    "inTime": t.to_rfc3339(),
+                    "outTime": now.to_rfc3339()
+                });
+                (rule, data)
+            }
+            _ => return Err("action must be in or out".into()),
+        };
+        let attendance = self.operations.mark_attendance(school_id, "employee", employee_id, data).await?;
+        Ok(json!({
+            "date": date.to_string(),
+            "late": rule["late"],
+            "earlyLeave": action == "out" && rule["earlyLeave"] == true,
+            "attendance": attendance
+        }))
+    }
+
+    /// Everyone in scope with their register over `from..=to`. Each person gets `cells` (`dNN` →
+    /// register code), `marks` (counted working day → status) and totals. Unmarked working days
+    /// on which attendance was taken count against the person; leave and days off don't count.
+    async fn register_data(
+        &self,
+        school_id: &str,
+        role: &str,
+        class_id: Option<&str>,
+        section: Option<&str>,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<(Value, Vec<RegisterDay>, Vec<Value>), AppError> {
+        let (scope, mut people) = match role {
+            "student" => {
+                let class_id = class_id.ok_or("classId is required for a student register")?;
+                let (class, students) = self.roster(school_id, class_id, section).await?;
+                let people: Vec<Value> = students
+                    .iter()
+                    .map(|s| json!({"userId": s["studentId"], "name": s["name"], "rollNumber": s["rollNumber"], "section": s["section"]}))
+                    .collect();
+                (json!({"role": role, "classId": class["id"], "className": class["name"], "section": section}), people)
+            }
+            "employee" => {
+                let mut staff: Vec<Value> = self
+                    .repos
+                    .employee
+                    .get_employees(school_id)
+                    .await?
+                    .iter()
+                    .filter(|e| e["status"] != "inactive")
+                    .map(|e| json!({"userId": e["employeeId"], "name": employee_name(e), "designation": e["designation"]}))
+                    .collect();
+                staff.sort_by_key(|e| e["name"].as_str().unwrap_or("").to_lowercase());
+                (json!({"role": role}), staff)
+            }
+            _ => return Err("role must be student or employee".into()),
+        };
+
+        let ids: Vec<String> = people.iter().filter_map(|p| p["userId"].as_str().map(|s| s.to_string())).collect();
+        let mut marks: HashMap<(String, String), String> = HashMap::new();
+        for r in self.repos.attendance.get_attendance_between(school_id, role, from, to, &ids).await? {
+            if let (Some(u), Some(d), Some(st)) = (r["userId"].as_str(), r["date"].as_str(), r["status"].as_str()) {
+                marks.insert((u.to_string(), d.to_string()), st.to_string());
+            }
+        }
+        let taken_dates: HashSet<&str> = marks.iter().filter(|(_, s)| *s != "holiday").map(|((_, d), _)| d.as_str()).collect();
+        let calendar = load_calendar(&self.repos, school_id, from, to).await?;
+        let today = Local::now().date_naive();
+
+        let days: Vec<RegisterDay> = from
+            .iter_days()
+            .take_while(|d| *d <= to)
+            .map(|date| {
+                let key = date.to_string();
+                let weekly = calendar.weekly_off(date).is_some();
+                RegisterDay {
+                    date,
+                    off: calendar.off_reason(date),
+                    weekly,
+                    exempt: if weekly { Vec::new() } else { calendar.exempt_on(date, role) },
+                    taken: date <= today && taken_dates.contains(key.as_str()),
+                    key,
+                }
+            })
+            .collect();
+
+        for p in people.iter_mut() {
+            let id = p["userId"].as_str().unwrap_or("").to_string();
+            let mut cells = serde_json::Map::new();
+            let mut counted = serde_json::Map::new();
+            let mut unmarked = 0;
+            for day in &days {
+                let status = marks.get(&(id.clone(), day.key.clone())).map(|s| s.as_str());
+                let off = day.off.is_some() && (day.weekly || !day.exempt.contains(&id));
+                let cell = match status {
+                    Some(s) if s != "holiday" || !off => register_code(s),
+                    _ if off && day.weekly && day.date.weekday() == Weekday::Sun => "S",
+                    _ if off && day.weekly => "WO",
+                    _ if off => "H",
+                    _ => "",
+                };
+                cells.insert(format!("d{:02}", day.date.day()), json!(cell));
+                if off || day.date > today {
+                    continue;
+                }
+                match status {
+                    Some("holiday") => {}
+                    Some(s) => {
+                        counted.insert(day.key.clone(), json!(s));
+                    }
+                    None if day.taken => unmarked += 1,
+                    None => {}
+                }
+            }
+            let count = |st: &str| counted.values().filter(|v| *v == st).count();
+            let (present, absent, late, half_day, leave) = (count("present"), count("absent"), count("late"), count("half_day"), count("leave"));
+            let working = present + absent + late + half_day + unmarked;
+            p["cells"] = Value::Object(cells);
+            p["marks"] = Value::Object(counted);
+            p["present"] = json!(present);
+            p["absent"] = json!(absent);
+            p["late"] = json!(late);
+            p["halfDay"] = json!(half_day);
+            p["leave"] = json!(leave);
+            p["unmarked"] = json!(unmarked);
+            p["workingDays"] = json!(working);
+            p["attendancePercent"] = json!(attendance_percent(present, late, half_day, working));
+        }
+        Ok((scope, days, people))
+    }
+
+    async fn holiday(&self, school_id: &str, date: NaiveDate) -> Result<Option<String>, AppError> {
+        Ok(load_calendar(&self.repos, school_id, date, date).await?.off_reason(date))
+    }
+}
+
+#[async_trait]
+impl AttendanceService for PostgresAttendanceService {
+    async fn get_attendance_settings(&self, school_id: &str) -> Result<Value, AppError> {
+        attendance_settings(&self.repos, school_id).await
+    }
+
+    async fn update_attendance_settings(&self, school_id: &str, data: Value) -> Result<Value, AppError> {
+        let mut saved = self.repos.attendance.get_attendance_settings(school_id).await?;
+        if !saved.is_object() {
+            saved = json!({});
+        }
+        let updated_by = data["updatedBy"].as_str().map(|s| s.to_string());
+        for (k, v) in data.as_object().into_iter().flatten().filter(|(k, _)| k.as_str() != "updatedBy") {
+            saved[k] = v.clone();
+        }
+        // A partial selfCheckIn / absenceAlerts block updates only the keys it names
+        let current = merge_settings(&self.repos.attendance.get_attendance_settings(school_id).await?);
+        for key in ["selfCheckIn", "absenceAlerts"] {
+            let Some(block) = data.get(key).filter(|b| b.is_object()) else { continue };
+            let mut merged = current[key].clone();
+            if !merged.is_object() {
+                merged = default_settings()[key].clone();
+            }
+            for (k, v) in block.as_object().into_iter().flatten() {
+                merged[k] = v.clone();
+            }
+            match key {
+                "selfCheckIn" => validate_settings(&merged)?,
+                _ => validate_absence_alerts(&merged)?,
+            }
+            saved[key] = merged;
+        }
+        if let Some(threshold) = saved.get("subjectAttendanceThreshold") {
+            if threshold.as_f64().is_none_or(|t| !(0.0..=100.0).contains(&t)) {
+                return Err("subjectAttendanceThreshold must be a percentage between 0 and 100".into());
+            }
+        }
+        if let Some(hours) = saved.get("correctionWindowHours").filter(|h| !h.is_null()) {
+            if hours.as_f64().is_none_or(|h| h < 0.0) {
+                return Err("correctionWindowHours must be a non-negative number, or null for no limit".into());
+            }
+        }
+        self.repos
+            .attendance
+            .save_attendance_settings(school_id, &saved, updated_by.as_deref())
+            .await?;
+        Ok(merge_settings(&saved))
+    }
+
+    async fn class_attendance(
+        &self,
+        school_id: &str,
+        class_id: &str,
+        section: Option<&str>,
+        date: &str,
+    ) -> Result<Value, AppError> {
+        let date = parse_date(date)?;
+        let (class, students) = self.roster(school_id, class_id, section).await?;
+        let ids: Vec<String> = students.iter().filter_map(|s| s["studentId"].as_str().map(|s| s.to_string())).collect();
+        let existing = self.repos.attendance.get_attendance_for_users(school_id, "student", date, &ids).await?;
+        let settings = attendance_settings(&self.repos, school_id).await?;
+        let (taken_at, until) = Self::correction_window(&settings, &existing);
+
+        let by_id: HashMap<&str, &Value> = existing.iter().filter_map(|r| Some((r["userId"].as_str()?, r))).collect();
+        let sheet: Vec<Value> = students
+            .iter()
+            .map(|s| {
+                let row = s["studentId"].as_str().and_then(|id| by_id.get(id));
+                json!({
+                    "studentId": s["studentId"],
+                    "name": s["name"],
+                    "rollNumber": s["rollNumber"],
+                    "section": s["section"],
+                    "status": row.map(|r| r["status"].clone()).unwrap_or(Value::Null),
+                    "remarks": row.map(|r| r["reason"].clone()).unwrap_or(Value::Null)
+                })
+            })
+            .collect();
+        let mut summary = status_summary(&sheet);
+        summary["total"] = json!(sheet.len());
+        Ok(json!({
+            "date": date.to_string(),
+            "classId": class["id"],
+            "className": class["name"],
+            "section": section,
+            "holiday": self.holiday(school_id, date).await?,
+            "takenAt": taken_at.map(|t| t.to_rfc3339()),
+            "editableUntil": until.map(|t| t.to_rfc3339()),
+            "editable": until.is_none_or(|u| Utc::now() <= u),
+            "summary": summary,
+            "students": sheet
+        }))
+    }
+
+    async fn mark_class_attendance(
+        &self,
+        school_id: &str,
+        class_id: &str,
+        date: &str,
+        data: Value,
+    ) -> Result<Value, AppError> {
+        let date = parse_date(date)?;
+        if date > chrono::Local::now().date_naive() {
+            return Err("Attendance can't be marked for a future date".into());
+        }
+        ensure_open_date(&self.repos, school_id, date).await?;
+        if let Some(reason) = self.holiday(school_id, date).await? {
+            return Err(format!("Cannot mark attendance on {}", reason).into());
+        }
+        let section = data["section"].as_str().filter(|s| !s.trim().is_empty());
+        let marked_by = data["markedBy"].as_str();
+        let default_status = match data["defaultStatus"].as_str() {
+            Some(s) => Some(normalize_status(s).ok_or_else(|| format!("defaultStatus must be one of {}", CLASS_STATUSES.join(", ")))?),
+            None => None,
+        };
+
+        let (class, students) = self.roster(school_id, class_id, section).await?;
+        let ids: Vec<String> = students.iter().filter_map(|s| s["studentId"].as_str().map(|s| s.to_string())).collect();
+
+        // Validate every entry before writing anything
+        let mut marks: HashMap<String, (&'static str, Option<String>)> = HashMap::new();
+        let mut unknown = Vec::new();
+        for e in data["entries"].as_array().ok_or("entries is required")? {
+            let id = e["studentId"].as_str().ok_or("Each entry needs a studentId")?;
+            if !ids.iter().any(|s| s == id) {
+                unknown.push(id.to_string());
+                continue;
+            }
+            let status = normalize_status(e["status"].as_str().unwrap_or(""))
+                .ok_or_else(|| format!("Invalid status for {}; use {}", id, CLASS_STATUSES.join(", ")))?;
+            let remarks = e["remarks"].as_str().filter(|r| !r.trim().is_empty()).map(|r| r.to_string());
+            marks.insert(id.to_string(), (status, remarks));
+        }
+        if !unknown.is_empty() {
+            return Err(format!("Not on this class roster: {}", unknown.join(", ")).into());
+        }
+        if let Some(status) = default_status {
+            for id in &ids {
+                marks.entry(id.clone()).or_insert((status, None));
+            }
+        }
+        if marks.is_empty() {
+            return Err("No attendance entries to save".into());
+        }
+
+        let existing = self.repos.attendance.get_attendance_for_users(school_id, "student", date, &ids).await?;
+        let settings = attendance_settings(&self.repos, school_id).await?;
+        let (taken_at, until) = Self::correction_window(&settings, &existing);
+        let previous: HashMap<&str, &Value> = existing.iter().filter_map(|r| Some((r["userId"].as_str()?, r))).collect();
+
+        let mut rows = Vec::new();
+        let mut history = Vec::new();
+        for id in &ids {
+            let Some((status, remarks)) = marks.get(id) else { continue };
+            let before = previous.get(id.as_str());
+            let unchanged = before.is_some_and(|b| b["status"] == *status && b["reason"].as_str() == remarks.as_deref());
+            if unchanged {
+                continue;
+            }
+            rows.push(json!({"userId": id, "status": status, "reason": remarks}));
+            history.push(json!({
+                "userId": id,
+                "action": if before.is_some() { "class_correction" } else { "class_mark" },
+                "data": {
+                    "date": date.to_string(),
+                    "status": status,
+                    "previousStatus": before.map(|b| b["status"].clone()),
+                    "remarks": remarks,
+                    "classId": class["id"],
+                    "section": section,
+                    "markedBy": marked_by
+                }
+            }));
+        }
+
+        // Changing what was already taken is only allowed inside the correction window
+        let corrections = history.iter().filter(|h| h["action"] == "class_correction").count();
+        if corrections > 0 {
+            if let Some(until) = until.filter(|u| Utc::now() > *u) {
+                return Err(format!(
+                    "Attendance for {} was taken at {}; the correction window closed at {}. Raise a correction request instead",
+                    date,
+                    taken_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                    until.to_rfc3339()
+                )
+                .into());
+            }
+        }
+
+        if !rows.is_empty() {
+            self.repos
+                .attendance
+                .save_bulk_attendance(school_id, "student", date, &rows, &history)
+                .await?;
+        }
+
+        let sheet: Vec<Value> = ids
+            .iter()
+            .filter_map(|id| match marks.get(id) {
+                Some((status, _)) => Some(json!({"studentId": id, "status": status})),
+                None => previous.get(id.as_str()).map(|r| json!({"studentId": id, "status": r["status"]})),
+            })
+            .collect();
+        let unmarked: Vec<&String> = ids
+            .iter()
+            .filter(|id| !marks.contains_key(*id) && !previous.contains_key(id.as_str()))
+            .collect();
+        let mut summary = status_summary(&sheet);
+        summary["total"] = json!(ids.len());
+        summary["saved"] = json!(rows.len() - corrections);
+        summary["corrected"] = json!(corrections);
+        summary["unchanged"] = json!(marks.len() - rows.len());
+        Ok(json!({
+            "date": date.to_string(),
+            "classId": class["id"],
+            "className": class["name"],
+            "section": section,
+            "summary": summary,
+            "absentees": sheet.iter().filter(|s| s["status"] == "absent").map(|s| s["studentId"].clone()).collect::<Vec<_>>(),
+            "unmarked": unmarked
+        }))
+    }
+
+    async fn check_in_qr(&self, school_id: &str, serial_number: &str, api_key: &str) -> Result<Value, AppError> {
+        let display = self
+            .repos
+            .devices
+            .find_device_by_serial(serial_number.trim())
+            .await?
+            .filter(|d| d["schoolId"] == school_id && d["protocol"] == "display")
+            .ok_or("Unknown check-in display")?;
+        if !constant_time_eq(display["apiKey"].as_str().unwrap_or("").as_bytes(), api_key.as_bytes()) {
+            return Err("Device key does not match".into());
+        }
+        if display["active"] != true {
+            return Err("This check-in display is disabled".into());
+        }
+        let settings = attendance_settings(&self.repos, school_id).await?;
+        let rotation = settings["selfCheckIn"]["qrRotationSeconds"].as_i64().unwrap_or(30);
+        let display_id = display["deviceId"].as_str().unwrap_or("");
+        let (token, expires_at) = qr_token(school_id, display_id, Utc::now().timestamp(), rotation)?;
+        Ok(json!({
+            "payload": json!({"type": "staff_check_in", "schoolId": school_id, "token": token}).to_string(),
+            "token": token,
+            "rotationSeconds": rotation,
+            "expiresAt": DateTime::from_timestamp(expires_at, 0).map(|t| t.to_rfc3339())
+        }))
+    }
+
+    async fn self_check_in(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        action: &str,
+        data: Value,
+    ) -> Result<Value, AppError> {
+        if action != "in" && action != "out" {
+            return Err("action must be in or out".into());
+        }
+        let now = Local::now();
+        let date = now.date_naive();
+        let settings = attendance_settings(&self.repos, school_id).await?;
+        let config = &settings["selfCheckIn"];
+        if config["enabled"] == false {
+            return Err("Self check-in is turned off for this school".into());
+        }
+        let device_id = data["deviceId"]
+            .as_str()
+            .map(|d| d.trim())
+            .filter(|d| !d.is_empty())
+            .ok_or("deviceId is required")?;
+        let token = data["qrToken"].as_str().filter(|t| !t.trim().is_empty());
+        let location = data["latitude"].as_f64().zip(data["longitude"].as_f64());
+        let accuracy = data["accuracy"].as_f64();
+        let fence = geofence(&settings);
+        let distance = fence.zip(location).map(|((lat, lon, _), at)| distance_meters((lat, lon), at));
+        let outside = fence.zip(distance).is_some_and(|((_, _, radius), d)| d > radius);
+
+        // A QR token from an active display is enough; without one the phone must be inside the geofence
+        let mut display_id = None;
+        let verdict: Result<(), String> = match (token, fence, distance) {
+            (Some(t), _, _) => match verify_qr_token(school_id, t, now.timestamp(), config["qrRotationSeconds"].as_i64().unwrap_or(30)) {
+                Ok(id) => {
+                    let active = self
+                        .repos
+                        .devices
+                        .get_devices(school_id)
+                        .await?
+                        .iter()
+                        .any(|d| d["deviceId"] == id.as_str() && d["protocol"] == "display" && d["active"] == true);
+                    display_id = Some(id);
+                    if active { Ok(()) } else { Err("QR code is from a display that is no longer active".to_string()) }
+                }
+                Err(e) => Err(e.to_string()),
+            },
+            (None, None, _) => Err("Location check-in isn't set up for this school; scan the QR code instead".to_string()),
+            (None, Some(_), None) => Err("Location is required to check in without the QR code".to_string()),
+            (None, Some((_, _, radius)), Some(d)) if d > radius => Err(format!(
+                "You are {:.0} m from school; check-in is allowed within {:.0} m",
+                d, radius
+            )),
+            _ => Ok(()),
+        };
+
+        let mut flags = Vec::new();
+        if token.is_some() && outside {
+            flags.push(json!({
+                "code": "qr_outside_geofence",
+                "message": format!("QR code scanned {:.0} m from school", distance.unwrap_or_default())
+            }));
+        }
+        // A photo of the code works anywhere, so a QR scan without a location can't be placed
+        if token.is_some() && fence.is_some() && location.is_none() {
+            flags.push(json!({
+                "code": "qr_without_location",
+                "message": "QR code scanned without sharing a location"
+            }));
+        }
+        if let Some((a, max)) = accuracy.zip(config["maxLocationAccuracyMeters"].as_f64()).filter(|(a, max)| a > max) {
+            flags.push(json!({
+                "code": "low_location_accuracy",
+                "message": format!("Location accurate to {:.0} m (limit {:.0} m)", a, max)
+            }));
+        }
+        if data["mockLocation"] == true {
+            flags.push(json!({"code": "mock_location", "message": "The phone reported a simulated location"}));
+        }
+        let since = date - Duration::days(config["sharedDeviceLookbackDays"].as_i64().unwrap_or(30));
+        let others = self
+            .repos
+            .attendance
+            .get_device_checkin_employees(school_id, device_id, since, employee_id)
+            .await?;
+        if !others.is_empty() {
+            flags.push(json!({
+                "code": "shared_device",
+                "message": format!("This phone was also used to check in {}", others.join(", ")),
+                "employees": others
+            }));
+        }
+
+        let outcome = match verdict {
+            Ok(()) => self
+                .record_self_check_in(school_id, employee_id, action, now, &settings)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+        self.repos
+            .attendance
+            .add_self_checkin(
+                school_id,
+                &json!({
+                    "employeeId": employee_id,
+                    "date": date.to_string(),
+                    "action": action,
+                    "method": if token.is_some() { "qr" } else { "geofence" },
+                    "accepted": outcome.is_ok(),
+                    "reason": outcome.as_ref().err(),
+                    "deviceId": device_id,
+                    "displayId": display_id,
+                    "latitude": location.map(|l| l.0),
+                    "longitude": location.map(|l| l.1),
+                    "accuracyMeters": accuracy,
+                    "distanceMeters": distance.map(|d| d.round()),
+                    "flags": flags
+                }),
+            )
+            .await?;
+        let mut result = outcome?;
+        result["action"] = json!(action);
+        result["time"] = json!(now.to_rfc3339());
+        Ok(result)
+    }
+
+    async fn self_check_in_log(
+        &self,
+        school_id: &str,
+        from: Option<&str>,
+        to: Option<&str>,
+        flagged_only: bool,
+    ) -> Result<Value, AppError> {
+        let today = Local::now().date_naive();
+        let from = from.map(parse_date).transpose()?.unwrap_or(today);
+        let to = to.map(parse_date).transpose()?.unwrap_or(today);
+        if to < from {
+            return Err("to must not be before from".into());
+        }
+        let names: HashMap<String, String> = self
+            .repos
+            .employee
+            .get_employees(school_id)
+            .await?
+            .iter()
+            .filter_map(|e| Some((e["employeeId"].as_str()?.to_string(), employee_name(e))))
+            .collect();
+        let mut entries = self.repos.attendance.get_self_checkins(school_id, from, to, None).await?;
+
+        // Phones that checked in more than one teacher over the period
+        let mut devices: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for e in entries.iter().filter(|e| e["accepted"] == true) {
+            if let (Some(d), Some(emp)) = (e["deviceId"].as_str(), e["employeeId"].as_str()) {
+                devices.entry(d.to_string()).or_default().insert(emp.to_string());
+            }
+        }
+        let shared: Vec<Value> = devices
+            .into_iter()
+            .filter(|(_, emps)| emps.len() > 1)
+            .map(|(device, emps)| {
+                json!({
+                    "deviceId": device,
+                    "employees": emps.iter().map(|id| json!({"employeeId": id, "name": names.get(id)})).collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        let flagged = |e: &Value| e["flags"].as_array().is_some_and(|f| !f.is_empty());
+        let summary = json!({
+            "attempts": entries.len(),
+            "rejected": entries.iter().filter(|e| e["accepted"] == false).count(),
+            "flagged": entries.iter().filter(|e| flagged(e)).count(),
+            "sharedDevices": shared.len()
+        });
+        if flagged_only {
+            entries.retain(|e| flagged(e) || e["accepted"] == false);
+        }
+        for e in entries.iter_mut() {
+            e["name"] = json!(e["employeeId"].as_str().and_then(|id| names.get(id)));
+        }
+        Ok(json!({
+            "from": from.to_string(),
+            "to": to.to_string(),
+            "summary": summary,
+            "sharedDevices": shared,
+            "entries": entries
+        }))
+    }
+
+    async fn monthly_register(
+        &self,
+        school_id: &str,
+        role: &str,
+        class_id: Option<&str>,
+        section: Option<&str>,
+        month: u32,
+        year: i32,
+    ) -> Result<Value, AppError> {
+        let from = NaiveDate::from_ymd_opt(year, month, 1).ok_or("month must be 1-12")?;
+        let to = from
+            .checked_add_months(Months::new(1))
+            .and_then(|d| d.pred_opt())
+            .ok_or("Invalid month")?;
+        let (scope, days, people) = self.register_data(school_id, role, class_id, section, from, to).await?;
+
+        let counted: Vec<f64> = people
+            .iter()
+            .filter(|p| p["workingDays"].as_u64().unwrap_or(0) > 0)
+            .filter_map(|p| p["attendancePercent"].as_f64())
+            .collect();
+        let average = if counted.is_empty() { 0.0 } else { (counted.iter().sum::<f64>() * 100.0 / counted.len() as f64).round() / 100.0 };
+        let rows: Vec<Value> = people
+            .into_iter()
+            .map(|mut p| {
+                let cells = p["cells"].take();
+                if let Some(obj) = p.as_object_mut() {
+                    obj.remove("cells");
+                    obj.remove("marks");
+                    obj.extend(cells.as_object().cloned().unwrap_or_default());
+                }
+                p
+            })
+            .collect();
+        Ok(json!({
+            "month": month,
+            "year": year,
+            "from": from.to_string(),
+            "to": to.to_string(),
+            "scope": scope,
+            "days": days
+                .iter()
+                .map(|d| json!({"date": d.key, "day": d.date.day(), "weekday": d.date.format("%a").to_string(), "off": d.off, "taken": d.taken}))
+                .collect::<Vec<_>>(),
+            "summary": {
+                "people": rows.len(),
+                "workingDays": days.iter().filter(|d| d.off.is_none() && d.taken).count(),
+                "holidays": days.iter().filter(|d| d.off.is_some() && !d.weekly).count(),
+                "averagePercent": average
+            },
+            "rows": rows
+        }))
+    }
+
+    async fn attendance_analytics(
+        &self,
+        school_id: &str,
+        role: &str,
+        class_id: Option<&str>,
+        section: Option<&str>,
+        (from, to): (&str, &str),
+        threshold: f64,
+    ) -> Result<Value, AppError> {
+        let (from, to) = (parse_date(from)?, parse_date(to)?);
+        if to < from {
+            return Err("to must not be before from".into());
+        }
+        if (to - from).num_days() > 366 {
+            return Err("The range can't be longer than a year".into());
+        }
+        if !(0.0..=100.0).contains(&threshold) {
+            return Err("threshold must be a percentage between 0 and 100".into());
+        }
+        let (scope, _, people) = self.register_data(school_id, role, class_id, section, from, to).await?;
+        let weekday_of = |d: &str| parse_date(d).ok().map(|d| d.weekday());
+
+        // Marks grouped by weekday across everyone in scope
+        let mut by_weekday: BTreeMap<u32, Vec<Value>> = BTreeMap::new();
+        for p in &people {
+            for (date, status) in p["marks"].as_object().into_iter().flatten() {
+                if let Some(w) = weekday_of(date) {
+                    by_weekday.entry(w.num_days_from_monday()).or_default().push(json!({"status": status}));
+                }
+            }
+        }
+        let day_of_week: Vec<Value> = by_weekday
+            .iter()
+            .map(|(w, rows)| {
+                let mut summary = status_summary(rows);
+                summary["weekday"] = json!(weekday_name(*w));
+                summary
+            })
+            .collect();
+        let lowest = day_of_week
+            .iter()
+            .filter(|d| d["marked"].as_u64().unwrap_or(0) > 0)
+            .min_by(|a, b| a["attendancePercent"].as_f64().unwrap_or(0.0).total_cmp(&b["attendancePercent"].as_f64().unwrap_or(0.0)))
+            .map(|d| d["weekday"].clone());
+
+        let mut rows: Vec<Value> = people
+            .iter()
+            .filter(|p| p["workingDays"].as_u64().unwrap_or(0) > 0 && p["attendancePercent"].as_f64().unwrap_or(0.0) < threshold)
+            .map(|p| {
+                // The weekday the person is most often absent on, if it stands out
+                let mut absences: BTreeMap<u32, usize> = BTreeMap::new();
+                for (date, status) in p["marks"].as_object().into_iter().flatten() {
+                    if status == "absent" {
+                        if let Some(w) = weekday_of(date) {
+                            *absences.entry(w.num_days_from_monday()).or_default() += 1;
+                        }
+                    }
+                }
+                let total: usize = absences.values().sum();
+                let most = absences
+                    .iter()
+                    .max_by_key(|(w, n)| (**n, std::cmp::Reverse(**w)))
+                    .filter(|(_, n)| total >= 3 && **n * 100 >= total * 40)
+                    .map(|(w, _)| weekday_name(*w));
+                let mut row = p.clone();
+                if let Some(obj) = row.as_object_mut() {
+                    obj.remove("cells");
+                    obj.remove("marks");
+                }
+                row["mostAbsentOn"] = json!(most);
+                row
+            })
+            .collect();
+        rows.sort_by(|a, b| a["attendancePercent"].as_f64().unwrap_or(0.0).total_cmp(&b["attendancePercent"].as_f64().unwrap_or(0.0)));
+
+        Ok(json!({
+            "from": from.to_string(),
+            "to": to.to_string(),
+            "scope": scope,
+            "threshold": threshold,
+            "summary": {
+                "people": people.len(),
+                "chronicAbsentees": rows.len(),
+                "lowestAttendanceDay": lowest
+            },
+            "dayOfWeek": day_of_week,
+            "rows": rows
+        }))
+    }
+}
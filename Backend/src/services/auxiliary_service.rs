@@ -43,23 +43,6 @@ impl ComplainService for PostgresAuxiliaryService {
     }
 }
 
-#[async_trait]
-impl ReminderService for PostgresAuxiliaryService {
-    async fn create_reminder(
-        &self,
-        school_id: &str,
-        data: Value,
-    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
-        self.repos.reminder.add_reminder(school_id, data).await
-    }
-    async fn list_reminders(
-        &self,
-        school_id: &str,
-    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
-        self.repos.reminder.get_reminders(school_id).await
-    }
-}
-
 #[async_trait]
 impl DocumentBoxService for PostgresAuxiliaryService {
     async fn upload_document(
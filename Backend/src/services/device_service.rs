@@ -0,0 +1,391 @@
+use crate::logic::attendance::{format_duration, from_punches, merge_settings};
+use crate::logic::device_punch::{adms_handshake, parse_adms_attlog, parse_json_punches, punch_json, simulate_attlog, Punch};
+use crate::logic::self_checkin::constant_time_eq;
+use crate::repository::Repositories;
+use crate::services::attendance_service::{attendance_settings, ensure_attendance_open, record_locked};
+use crate::services::traits::*;
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+pub struct PostgresDeviceService {
+    pub repos: Arc<Repositories>,
+}
+
+/// `adms` and `json` terminals push punches; a `display` only shows the self check-in QR code.
+const PROTOCOLS: &[&str] = &["adms", "json", "display"];
+
+/// Leave and holiday rows always stay; other rows are rebuilt from punches only when punches made them.
+const KEEP_STATUSES: &[&str] = &["leave", "holiday"];
+
+/// Whether `DEVICE_SIMULATOR` is on; the simulate route is mounted only then.
+pub fn simulator_enabled() -> bool {
+    matches!(std::env::var("DEVICE_SIMULATOR").as_deref(), Ok("1") | Ok("true") | Ok("on"))
+}
+
+fn new_api_key() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+fn parse_date(v: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(v, "%Y-%m-%d").map_err(|_| "date must be YYYY-MM-DD".into())
+}
+
+fn local_rfc3339(t: NaiveDateTime) -> Option<String> {
+    chrono::Local.from_local_datetime(&t).earliest().map(|t| t.to_rfc3339())
+}
+
+/// Distinct `{role, userId, date}` of the matched punches.
+fn user_days(punches: &[Value]) -> Vec<Value> {
+    let days: BTreeSet<(String, String, String)> = punches
+        .iter()
+        .filter_map(|p| {
+            Some((
+                p["role"].as_str()?.to_string(),
+                p["userId"].as_str()?.to_string(),
+                p["time"].as_str()?.get(..10)?.to_string(),
+            ))
+        })
+        .collect();
+    days.into_iter()
+        .map(|(role, user_id, date)| json!({"role": role, "userId": user_id, "date": date}))
+        .collect()
+}
+
+impl PostgresDeviceService {
+    async fn device_by_serial(&self, serial: &str) -> Result<Value, AppError> {
+        let device = self
+            .repos
+            .devices
+            .find_device_by_serial(serial.trim())
+            .await?
+            .ok_or_else(|| format!("Unknown device {}", serial))?;
+        if device["active"] != true {
+            return Err(format!("Device {} is disabled", serial).into());
+        }
+        if device["protocol"] == "display" {
+            return Err(format!("Device {} is a check-in display and can't push punches", serial).into());
+        }
+        Ok(device)
+    }
+
+    /// Stores the punches and rebuilds attendance for everyone they belong to.
+    async fn ingest(&self, school_id: &str, device_id: &str, punches: &[Punch], rejected: Vec<String>) -> Result<Value, AppError> {
+        self.repos.devices.touch_device(device_id).await?;
+        let rows: Vec<Value> = punches
+            .iter()
+            .map(|p| {
+                let mut row = punch_json(p);
+                row["raw"] = json!(p.raw);
+                row
+            })
+            .collect();
+        let stored = self.repos.devices.add_punches(school_id, device_id, &rows).await?;
+
+        let unmatched: BTreeSet<String> = stored
+            .iter()
+            .filter(|p| p["userId"].is_null())
+            .filter_map(|p| p["enrollId"].as_str().map(|s| s.to_string()))
+            .collect();
+        let mut result = self.rebuild(school_id, &user_days(&stored)).await?;
+        result["received"] = json!(punches.len() + rejected.len());
+        result["stored"] = json!(stored.len());
+        result["duplicates"] = json!(punches.len() - stored.len());
+        result["rejected"] = json!(rejected);
+        result["unmatchedEnrollIds"] = json!(unmatched);
+        Ok(result)
+    }
+
+    /// Recomputes attendance for each `{role, userId, date}` from all of that day's punches.
+    async fn rebuild(&self, school_id: &str, affected: &[Value]) -> Result<Value, AppError> {
+        let settings = attendance_settings(&self.repos, school_id).await?;
+        let mut updated = Vec::new();
+        let mut skipped = Vec::new();
+        for a in affected {
+            let (Some(role), Some(user_id), Some(date)) = (
+                a["role"].as_str(),
+                a["userId"].as_str(),
+                a["date"].as_str().and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()),
+            ) else {
+                continue;
+            };
+            match self.rebuild_one(school_id, role, user_id, date, &settings).await {
+                Ok(Some(row)) => updated.push(row),
+                Ok(None) => {}
+                Err(e) => skipped.push(json!({"role": role, "userId": user_id, "date": date.to_string(), "reason": e.to_string()})),
+            }
+        }
+        Ok(json!({ "attendanceUpdated": updated, "skipped": skipped }))
+    }
+
+    async fn rebuild_one(
+        &self,
+        school_id: &str,
+        role: &str,
+        user_id: &str,
+        date: NaiveDate,
+        settings: &Value,
+    ) -> Result<Option<Value>, AppError> {
+        let existing = self
+            .repos
+            .attendance
+            .get_attendance_for_users(school_id, role, date, &[user_id.to_string()])
+            .await?;
+        if let Some(row) = existing.first() {
+            let status = row["status"].as_str().unwrap_or("");
+            if KEEP_STATUSES.contains(&status) {
+                return Err(format!("already marked {}", status).into());
+            }
+            if row["source"] != "device" {
+                return Err(format!("already marked {} by hand", status.replace('_', " ")).into());
+            }
+            if record_locked(settings, row, date) {
+                return Err("past its correction window".into());
+            }
+        }
+        ensure_attendance_open(&self.repos, school_id, role, date).await?;
+        let times = self.repos.devices.get_user_punch_times(school_id, role, user_id, date).await?;
+        let Some(derived) = from_punches(&times, settings) else { return Ok(None) };
+
+        let time = |k: &str| derived[k].as_str().and_then(|t| NaiveDateTime::parse_from_str(t, "%Y-%m-%dT%H:%M:%S%.f").ok()).and_then(local_rfc3339);
+        let data = json!({
+            "status": derived["status"],
+            "inTime": time("inTime"),
+            "outTime": time("outTime"),
+            "totalTime": derived["totalMinutes"].as_i64().map(format_duration),
+            "reason": if derived["earlyLeave"] == true { json!("early_leave") } else { Value::Null }
+        });
+        self.repos.attendance.save_timed_attendance(school_id, role, user_id, date, &data).await?;
+        self.repos
+            .operations
+            .add_attendance_history(
+                school_id,
+                role,
+                user_id,
+                "device_punch",
+                json!({"date": date.to_string(), "status": data["status"], "inTime": data["inTime"], "outTime": data["outTime"], "earlyLeave": derived["earlyLeave"]}),
+            )
+            .await?;
+        Ok(Some(json!({
+            "role": role,
+            "userId": user_id,
+            "date": date.to_string(),
+            "status": derived["status"],
+            "late": derived["late"],
+            "earlyLeave": derived["earlyLeave"],
+            "inTime": data["inTime"],
+            "outTime": data["outTime"]
+        })))
+    }
+}
+
+#[async_trait]
+impl DeviceService for PostgresDeviceService {
+    async fn register_device(&self, school_id: &str, data: Value) -> Result<Value, AppError> {
+        let serial = data["serialNumber"].as_str().map(|s| s.trim()).filter(|s| !s.is_empty()).ok_or("serialNumber is required")?;
+        let protocol = data["protocol"].as_str().unwrap_or("adms");
+        if !PROTOCOLS.contains(&protocol) {
+            return Err("protocol must be adms, json or display".into());
+        }
+        if self.repos.devices.find_device_by_serial(serial).await?.is_some() {
+            return Err(format!("Device {} is already registered", serial).into());
+        }
+        let api_key = new_api_key();
+        let mut device = self
+            .repos
+            .devices
+            .create_device(
+                school_id,
+                &json!({
+                    "deviceId": format!("DEV{}", chrono::Utc::now().timestamp_millis()),
+                    "serialNumber": serial,
+                    "name": data["name"].as_str().unwrap_or(serial),
+                    "protocol": protocol,
+                    "location": data["location"],
+                    "apiKey": api_key
+                }),
+            )
+            .await?;
+        device["apiKey"] = json!(api_key);
+        Ok(device)
+    }
+
+    async fn list_devices(&self, school_id: &str) -> Result<Vec<Value>, AppError> {
+        self.repos.devices.get_devices(school_id).await
+    }
+
+    async fn update_device(&self, school_id: &str, device_id: &str, data: Value) -> Result<Value, AppError> {
+        let mut patch = json!({
+            "name": data["name"],
+            "location": data["location"],
+            "active": data["active"]
+        });
+        let new_key = data["regenerateKey"].as_bool().unwrap_or(false).then(new_api_key);
+        if let Some(key) = &new_key {
+            patch["apiKey"] = json!(key);
+        }
+        let mut device = self
+            .repos
+            .devices
+            .update_device(school_id, device_id, &patch)
+            .await?
+            .ok_or("Device not found")?;
+        if let Some(key) = new_key {
+            device["apiKey"] = json!(key);
+        }
+        Ok(device)
+    }
+
+    async fn delete_device(&self, school_id: &str, device_id: &str) -> Result<(), AppError> {
+        if !self.repos.devices.delete_device(school_id, device_id).await? {
+            return Err("Device not found".into());
+        }
+        Ok(())
+    }
+
+    async fn list_enrollments(&self, school_id: &str) -> Result<Vec<Value>, AppError> {
+        self.repos.devices.get_enrollments(school_id).await
+    }
+
+    async fn save_enrollments(&self, school_id: &str, data: Value) -> Result<Value, AppError> {
+        let entries = data["enrollments"].as_array().ok_or("enrollments is required")?;
+        let mut clean = Vec::new();
+        for e in entries {
+            let enroll_id = e["enrollId"]
+                .as_str()
+                .map(|s| s.trim().to_string())
+                .or_else(|| e["enrollId"].as_i64().map(|n| n.to_string()))
+                .filter(|s| !s.is_empty())
+                .ok_or("Each enrollment needs an enrollId")?;
+            let user_id = e["userId"].as_str().ok_or_else(|| format!("Enrollment {} needs a userId", enroll_id))?;
+            let exists = match e["role"].as_str() {
+                Some("student") => self.repos.student.get_student(school_id, user_id).await?.is_some(),
+                Some("employee") => self.repos.employee.get_employee(school_id, user_id).await?.is_some(),
+                _ => return Err(format!("Enrollment {} needs role student or employee", enroll_id).into()),
+            };
+            if !exists {
+                return Err(format!("No {} {} in this school", e["role"].as_str().unwrap_or(""), user_id).into());
+            }
+            clean.push(json!({"enrollId": enroll_id, "role": e["role"], "userId": user_id}));
+        }
+        self.repos.devices.save_enrollments(school_id, &clean).await?;
+
+        // Punches that arrived before the ID was enrolled
+        let affected = self.repos.devices.map_unmatched_punches(school_id).await?;
+        let mut result = self.rebuild(school_id, &affected).await?;
+        result["saved"] = json!(clean.len());
+        Ok(result)
+    }
+
+    async fn delete_enrollment(&self, school_id: &str, enroll_id: &str) -> Result<(), AppError> {
+        if !self.repos.devices.delete_enrollment(school_id, enroll_id).await? {
+            return Err("Enrollment not found".into());
+        }
+        Ok(())
+    }
+
+    async fn adms_handshake(&self, serial: &str) -> Result<String, AppError> {
+        let device = self.device_by_serial(serial).await?;
+        self.repos.devices.touch_device(device["deviceId"].as_str().unwrap_or("")).await?;
+        Ok(adms_handshake(serial))
+    }
+
+    async fn adms_push(&self, serial: &str, table: &str, body: &str) -> Result<String, AppError> {
+        let device = self.device_by_serial(serial).await?;
+        let device_id = device["deviceId"].as_str().unwrap_or("");
+        if !table.eq_ignore_ascii_case("ATTLOG") {
+            // OPERLOG, ATTPHOTO and the like carry nothing we store
+            self.repos.devices.touch_device(device_id).await?;
+            return Ok(format!("OK: {}", body.lines().filter(|l| !l.trim().is_empty()).count()));
+        }
+        let (punches, rejected) = parse_adms_attlog(body);
+        let result = self.ingest(device["schoolId"].as_str().unwrap_or(""), device_id, &punches, rejected).await?;
+        tracing::info!("[device {}] ATTLOG {}", serial, result);
+        Ok(format!("OK: {}", punches.len()))
+    }
+
+    async fn adms_poll(&self, serial: &str) -> Result<String, AppError> {
+        let device = self.device_by_serial(serial).await?;
+        self.repos.devices.touch_device(device["deviceId"].as_str().unwrap_or("")).await?;
+        Ok("OK".to_string())
+    }
+
+    async fn push_punches(&self, school_id: &str, data: Value) -> Result<Value, AppError> {
+        let device = self.device_by_serial(data["serialNumber"].as_str().unwrap_or("")).await?;
+        let key_matches = constant_time_eq(
+            device["apiKey"].as_str().unwrap_or("").as_bytes(),
+            data["apiKey"].as_str().unwrap_or("").as_bytes(),
+        );
+        if device["schoolId"] != school_id || device["apiKey"].as_str().is_none_or(|k| k.is_empty()) || !key_matches {
+            return Err("Device key does not match".into());
+        }
+        let (punches, rejected) = parse_json_punches(data["punches"].as_array().ok_or("punches is required")?);
+        self.ingest(school_id, device["deviceId"].as_str().unwrap_or(""), &punches, rejected).await
+    }
+
+    async fn list_punches(&self, school_id: &str, date: &str, unmatched_only: bool) -> Result<Vec<Value>, AppError> {
+        self.repos.devices.get_punches(school_id, parse_date(date)?, unmatched_only).await
+    }
+
+    async fn reprocess_punches(&self, school_id: &str, date: &str) -> Result<Value, AppError> {
+        let punches = self.repos.devices.get_punches(school_id, parse_date(date)?, false).await?;
+        self.rebuild(school_id, &user_days(&punches)).await
+    }
+
+    async fn simulate_device(&self, school_id: &str, device_id: &str, data: Value) -> Result<Value, AppError> {
+        if !simulator_enabled() {
+            return Err("The device simulator is off; set DEVICE_SIMULATOR=1 to use it".into());
+        }
+        let device = self
+            .repos
+            .devices
+            .get_devices(school_id)
+            .await?
+            .into_iter()
+            .find(|d| d["deviceId"] == device_id)
+            .ok_or("Device not found")?;
+        let date = match data["date"].as_str() {
+            Some(d) => parse_date(d)?,
+            None => chrono::Local::now().date_naive(),
+        };
+        let enroll_ids: Vec<String> = match data["enrollIds"].as_array() {
+            Some(ids) => ids.iter().filter_map(|i| i.as_str().map(|s| s.to_string())).collect(),
+            None => self
+                .repos
+                .devices
+                .get_enrollments(school_id)
+                .await?
+                .iter()
+                .filter_map(|e| e["enrollId"].as_str().map(|s| s.to_string()))
+                .collect(),
+        };
+        if enroll_ids.is_empty() {
+            return Err("No enrolled IDs to simulate; enroll users or pass enrollIds".into());
+        }
+        let settings = merge_settings(&self.repos.attendance.get_attendance_settings(school_id).await?);
+        let shift = |k: &str, default: NaiveTime| {
+            settings[k].as_str().and_then(|t| NaiveTime::parse_from_str(t, "%H:%M").ok()).unwrap_or(default)
+        };
+        let attlog = simulate_attlog(
+            &enroll_ids,
+            date,
+            shift("shiftStart", NaiveTime::from_hms_opt(8, 0, 0).unwrap_or_default()),
+            shift("shiftEnd", NaiveTime::from_hms_opt(14, 0, 0).unwrap_or_default()),
+            |range| (rand::random::<u32>() as i64) % range.max(1),
+        );
+        let serial = device["serialNumber"].as_str().unwrap_or("");
+        let reply = self.adms_push(serial, "ATTLOG", &attlog).await?;
+        Ok(json!({
+            "serialNumber": serial,
+            "date": date.to_string(),
+            "attlog": attlog,
+            "deviceReply": reply,
+            "punches": self.repos.devices.get_punches(school_id, date, false).await?
+                .into_iter()
+                .filter(|p| p["deviceId"] == device_id)
+                .collect::<Vec<_>>()
+        }))
+    }
+}
@@ -0,0 +1,488 @@
+use crate::logic::employee_documents::{document_type, document_types, employee_compliance, expiry_date, expiry_state};
+use crate::repository::Repositories;
+use crate::services::payroll_service::employee_name;
+use crate::services::traits::*;
+use crate::AppState;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+/// Where uploaded employee documents are kept.
+#[async_trait]
+pub trait DocumentStorage: Send + Sync {
+    async fn put(&self, key: &str, bytes: &[u8], content_type: Option<&str>) -> Result<(), AppError>;
+    async fn get(&self, key: &str) -> Result<Vec<u8>, AppError>;
+    async fn delete(&self, key: &str) -> Result<(), AppError>;
+}
+
+/// Keeps files on the server's disk under `root`.
+pub struct LocalDocumentStorage {
+    pub root: PathBuf,
+}
+
+#[async_trait]
+impl DocumentStorage for LocalDocumentStorage {
+    async fn put(&self, key: &str, bytes: &[u8], _content_type: Option<&str>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let path = self.root.join(key);
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        tokio::fs::write(path, bytes).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        Ok(tokio::fs::read(self.root.join(key)).await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match tokio::fs::remove_file(self.root.join(key)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Keeps files in an object store that accepts plain `PUT`/`GET`/`DELETE` on `{url}/{key}`
+/// (MinIO, an S3 bucket behind a signing proxy, a CDN origin), with an optional bearer token.
+pub struct HttpDocumentStorage {
+    pub url: String,
+    pub token: Option<String>,
+    pub client: reqwest::Client,
+}
+
+impl HttpDocumentStorage {
+    fn request(&self, method: reqwest::Method, key: &str) -> reqwest::RequestBuilder {
+        let req = self.client.request(method, format!("{}/{}", self.url.trim_end_matches('/'), key));
+        match &self.token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+}
+
+#[async_trait]
+impl DocumentStorage for HttpDocumentStorage {
+    async fn put(&self, key: &str, bytes: &[u8], content_type: Option<&str>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let res = self
+            .request(reqwest::Method::PUT, key)
+            .header("Content-Type", content_type.unwrap_or("application/octet-stream"))
+            .body(bytes.to_vec())
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(format!("Document store returned {}", res.status()).into());
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let res = self.request(reqwest::Method::GET, key).send().await?;
+        if !res.status().is_success() {
+            return Err(format!("Document store returned {}", res.status()).into());
+        }
+        Ok(res.bytes().await?.to_vec())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let res = self.request(reqwest::Method::DELETE, key).send().await?;
+        if !res.status().is_success() && res.status() != reqwest::StatusCode::NOT_FOUND {
+            return Err(format!("Document store returned {}", res.status()).into());
+        }
+        Ok(())
+    }
+}
+
+/// Picks the store from `DOCUMENT_STORAGE` (`local` by default, under `DOCUMENT_STORAGE_DIR`,
+/// or `http` with `DOCUMENT_STORAGE_URL` and optional `DOCUMENT_STORAGE_TOKEN`).
+pub fn storage_from_env() -> Arc<dyn DocumentStorage> {
+    let local = || -> Arc<dyn DocumentStorage> {
+        Arc::new(LocalDocumentStorage {
+            root: PathBuf::from(std::env::var("DOCUMENT_STORAGE_DIR").unwrap_or_else(|_| "uploads/documents".to_string())),
+        })
+    };
+    match std::env::var("DOCUMENT_STORAGE").as_deref() {
+        Ok("http") => match std::env::var("DOCUMENT_STORAGE_URL") {
+            Ok(url) => Arc::new(HttpDocumentStorage {
+                url,
+                token: std::env::var("DOCUMENT_STORAGE_TOKEN").ok(),
+                client: reqwest::Client::new(),
+            }),
+            Err(_) => {
+                tracing::warn!("DOCUMENT_STORAGE_URL not set, falling back to local document storage");
+                local()
+            }
+        },
+        _ => local(),
+    }
+}
+
+/// Largest file accepted into the vault
+const MAX_DOCUMENT_BYTES: usize = 10 * 1024 * 1024;
+
+/// Days ahead that count as "expiring" for reminders
+const EXPIRY_REMINDER_DAYS: i64 = 30;
+
+/// Keeps storage keys to safe path segments whatever the IDs or file name contain.
+pub(crate) fn key_segment(s: &str) -> String {
+    let cleaned: String = s
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+    cleaned.trim_start_matches('.').to_string()
+}
+
+fn optional_date(v: &Value, field: &str) -> Result<Option<NaiveDate>, AppError> {
+    match v.as_str().filter(|s| !s.is_empty()) {
+        None => Ok(None),
+        Some(d) => NaiveDate::parse_from_str(d, "%Y-%m-%d")
+            .map(Some)
+            .map_err(|_| format!("{} must be YYYY-MM-DD", field).into()),
+    }
+}
+
+/// Path the file is downloaded from, also written into linked education/experience records.
+fn file_url(school_id: &str, doc: &Value) -> String {
+    format!(
+        "/api/employees/{}/employees/{}/documents/{}/file",
+        school_id,
+        doc["employeeId"].as_str().unwrap_or(""),
+        doc["documentId"].as_str().unwrap_or("")
+    )
+}
+
+fn with_url(school_id: &str, mut doc: Value) -> Value {
+    doc["fileUrl"] = json!(file_url(school_id, &doc));
+    if let Some(obj) = doc.as_object_mut() {
+        obj.remove("storageKey");
+    }
+    doc
+}
+
+pub struct PostgresEmployeeDocumentService {
+    pub repos: Arc<Repositories>,
+    pub storage: Arc<dyn DocumentStorage>,
+    pub notifier: Arc<dyn NotificationService>,
+}
+
+impl PostgresEmployeeDocumentService {
+    async fn employee(&self, school_id: &str, employee_id: &str) -> Result<Value, AppError> {
+        self.repos
+            .employee
+            .get_employee(school_id, employee_id)
+            .await?
+            .ok_or_else(|| "Employee not found".into())
+    }
+
+    /// The document, provided it belongs to the employee in the path.
+    async fn owned_document(&self, school_id: &str, employee_id: &str, document_id: &str) -> Result<Value, AppError> {
+        self.repos
+            .employee_documents
+            .get_document(school_id, document_id)
+            .await?
+            .filter(|d| d["employeeId"] == employee_id)
+            .ok_or_else(|| "Document not found".into())
+    }
+}
+
+#[async_trait]
+impl EmployeeDocumentService for PostgresEmployeeDocumentService {
+    async fn document_types(&self) -> Result<Vec<Value>, AppError> {
+        Ok(document_types())
+    }
+
+    async fn upload_document(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        meta: Value,
+        file_name: &str,
+        content_type: Option<&str>,
+        bytes: Vec<u8>,
+    ) -> Result<Value, AppError> {
+        if bytes.is_empty() {
+            return Err("No file uploaded".into());
+        }
+        if bytes.len() > MAX_DOCUMENT_BYTES {
+            return Err(format!("Documents must be under {} MB", MAX_DOCUMENT_BYTES / (1024 * 1024)).into());
+        }
+        let code = meta["documentType"].as_str().unwrap_or("");
+        let doc_type = document_type(code).ok_or_else(|| format!("Unknown document type '{}'", code))?;
+        let employee = self.employee(school_id, employee_id).await?;
+
+        // Degrees and experience letters belong to one of the employee's records
+        let id_field = |key: &str| meta[key].as_i64().or_else(|| meta[key].as_str().and_then(|s| s.parse().ok()));
+        let education_id = id_field("educationId");
+        let experience_id = id_field("experienceId");
+        let (link_key, link_id) = match doc_type["linkedTo"].as_str() {
+            Some("education") => ("education", education_id.ok_or("educationId is required for this document type")?),
+            Some("experience") => ("experience", experience_id.ok_or("experienceId is required for this document type")?),
+            _ => ("", 0),
+        };
+        if !link_key.is_empty() && !employee[link_key].as_array().into_iter().flatten().any(|r| r["id"] == link_id) {
+            return Err(format!("No {} record {} for this employee", link_key, link_id).into());
+        }
+
+        let issued_on = optional_date(&meta["issuedOn"], "issuedOn")?;
+        let expires_on = expiry_date(&doc_type, issued_on, optional_date(&meta["expiresOn"], "expiresOn")?);
+        if let (Some(i), Some(e)) = (issued_on, expires_on) {
+            if e <= i {
+                return Err("expiresOn must be after issuedOn".into());
+            }
+        }
+
+        let document_id = format!("EDOC{}", chrono::Utc::now().timestamp_millis());
+        let storage_key = format!(
+            "{}/{}/{}-{}",
+            key_segment(school_id),
+            key_segment(employee_id),
+            document_id,
+            key_segment(file_name)
+        );
+        self.storage.put(&storage_key, &bytes, content_type).await?;
+
+        let record = json!({
+            "documentId": document_id,
+            "employeeId": employee_id,
+            "documentType": code,
+            "title": meta["title"].as_str().unwrap_or(doc_type["name"].as_str().unwrap_or("")),
+            "fileName": file_name,
+            "contentType": content_type,
+            "sizeBytes": bytes.len(),
+            "storageKey": storage_key,
+            "educationId": if link_key == "education" { json!(link_id) } else { Value::Null },
+            "experienceId": if link_key == "experience" { json!(link_id) } else { Value::Null },
+            "documentNumber": meta["documentNumber"],
+            "issuedOn": issued_on.map(|d| d.to_string()),
+            "expiresOn": expires_on.map(|d| d.to_string()),
+            "uploadedBy": meta["uploadedBy"]
+        });
+        let doc = match self.repos.employee_documents.add_document(school_id, &record).await {
+            Ok(doc) => doc,
+            Err(e) => {
+                // Don't leave an orphaned file behind
+                let _ = self.storage.delete(&storage_key).await;
+                return Err(e);
+            }
+        };
+
+        let url = file_url(school_id, &doc);
+        match link_key {
+            "education" => {
+                self.repos.employee_documents.link_education_document(school_id, employee_id, link_id as i32, Some(&url)).await?;
+            }
+            "experience" => {
+                self.repos.employee_documents.link_experience_document(school_id, employee_id, link_id as i32, Some(&url)).await?;
+            }
+            _ => {}
+        }
+        Ok(with_url(school_id, doc))
+    }
+
+    async fn list_documents(&self, school_id: &str, employee_id: &str) -> Result<Vec<Value>, AppError> {
+        let today = chrono::Local::now().date_naive();
+        Ok(self
+            .repos
+            .employee_documents
+            .get_documents(school_id, Some(employee_id))
+            .await?
+            .into_iter()
+            .map(|d| {
+                let state = expiry_state(&d, today, EXPIRY_REMINDER_DAYS);
+                let mut d = with_url(school_id, d);
+                d["expiryState"] = json!(state);
+                d
+            })
+            .collect())
+    }
+
+    async fn document_file(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        document_id: &str,
+    ) -> Result<(Value, Vec<u8>), AppError> {
+        let doc = self.owned_document(school_id, employee_id, document_id).await?;
+        let bytes = self.storage.get(doc["storageKey"].as_str().unwrap_or("")).await?;
+        Ok((with_url(school_id, doc), bytes))
+    }
+
+    async fn verify_document(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        document_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError> {
+        let status = data["status"].as_str().unwrap_or("verified");
+        if !["verified", "rejected", "pending"].contains(&status) {
+            return Err("status must be verified, rejected or pending".into());
+        }
+        let verified_by = data["verifiedBy"].as_str().filter(|s| !s.trim().is_empty()).ok_or("verifiedBy is required")?;
+        let remarks = data["remarks"].as_str().filter(|s| !s.trim().is_empty());
+        if status == "rejected" && remarks.is_none() {
+            return Err("remarks are required when rejecting a document".into());
+        }
+        self.owned_document(school_id, employee_id, document_id).await?;
+        let doc = self
+            .repos
+            .employee_documents
+            .set_document_verification(school_id, document_id, status, verified_by, remarks)
+            .await?
+            .ok_or("Document not found")?;
+        Ok(with_url(school_id, doc))
+    }
+
+    async fn delete_document(&self, school_id: &str, employee_id: &str, document_id: &str) -> Result<Value, AppError> {
+        self.owned_document(school_id, employee_id, document_id).await?;
+        let doc = self
+            .repos
+            .employee_documents
+            .delete_document(school_id, document_id)
+            .await?
+            .ok_or("Document not found")?;
+
+        // Point linked records at the newest remaining file for them, if any
+        let remaining = self.repos.employee_documents.get_documents(school_id, Some(employee_id)).await?;
+        for (key, field) in [("educationId", "education"), ("experienceId", "experience")] {
+            let Some(link_id) = doc[key].as_i64() else { continue };
+            let replacement = remaining.iter().find(|d| d[key] == link_id).map(|d| file_url(school_id, d));
+            if field == "education" {
+                self.repos.employee_documents.link_education_document(school_id, employee_id, link_id as i32, replacement.as_deref()).await?;
+            } else {
+                self.repos.employee_documents.link_experience_document(school_id, employee_id, link_id as i32, replacement.as_deref()).await?;
+            }
+        }
+        if let Err(e) = self.storage.delete(doc["storageKey"].as_str().unwrap_or("")).await {
+            tracing::warn!("Failed to delete stored file for {}: {}", document_id, e);
+        }
+        Ok(with_url(school_id, doc))
+    }
+
+    async fn compliance_report(
+        &self,
+        school_id: &str,
+        employee_id: Option<&str>,
+        within_days: i64,
+    ) -> Result<Value, AppError> {
+        let today = chrono::Local::now().date_naive();
+        let documents = self.repos.employee_documents.get_documents(school_id, employee_id).await?;
+        let employees: Vec<Value> = match employee_id {
+            Some(id) => vec![self.employee(school_id, id).await?],
+            None => self.repos.employee.get_employees(school_id).await?,
+        };
+
+        let mut rows = Vec::new();
+        for emp in employees.iter().filter(|e| e["status"] != "inactive") {
+            let Some(id) = emp["employeeId"].as_str() else { continue };
+            let docs: Vec<Value> = documents.iter().filter(|d| d["employeeId"] == id).cloned().collect();
+            let mut row = employee_compliance(emp, &docs, today, within_days);
+            row["employeeId"] = json!(id);
+            row["name"] = json!(employee_name(emp));
+            row["employeeType"] = emp["employeeType"].clone();
+            rows.push(row);
+        }
+        rows.sort_by_key(|r| (r["compliant"].as_bool().unwrap_or(false), r["employeeId"].as_str().unwrap_or("").to_string()));
+
+        let count = |issue: &str| {
+            rows.iter()
+                .flat_map(|r| r["issues"].as_array().into_iter().flatten())
+                .filter(|i| i["issue"] == issue)
+                .count()
+        };
+        let summary = json!({
+            "employees": rows.len(),
+            "compliant": rows.iter().filter(|r| r["compliant"] == true).count(),
+            "missing": count("missing"),
+            "expired": count("expired"),
+            "expiring": count("expiring"),
+            "pendingVerification": count("pending_verification"),
+            "rejected": count("rejected")
+        });
+        Ok(json!({
+            "asOf": today.to_string(),
+            "expiringWithinDays": within_days,
+            "summary": summary,
+            "employees": rows
+        }))
+    }
+
+    async fn remind_expiring_documents(&self, school_id: &str, within_days: i64) -> Result<Value, AppError> {
+        let today = chrono::Local::now().date_naive();
+        let documents = self.repos.employee_documents.get_documents(school_id, None).await?;
+        let mut sent = 0;
+        for doc in documents.iter().filter(|d| d["verificationStatus"] != "rejected") {
+            let state = expiry_state(doc, today, within_days);
+            if state == "valid" {
+                continue;
+            }
+            // Only the newest document of a kind matters; an older one replaced by a renewal doesn't
+            let renewed = documents.iter().any(|d| {
+                d["employeeId"] == doc["employeeId"]
+                    && d["documentType"] == doc["documentType"]
+                    && d["educationId"] == doc["educationId"]
+                    && d["experienceId"] == doc["experienceId"]
+                    && d["createdAt"].as_str() > doc["createdAt"].as_str()
+            });
+            if renewed {
+                continue;
+            }
+            let employee_id = doc["employeeId"].as_str().unwrap_or("");
+            let employee = self.repos.employee.get_employee(school_id, employee_id).await?;
+            let document_id = doc["documentId"].as_str().unwrap_or("");
+            let vars = json!({
+                "employeeName": employee.as_ref().map(employee_name).unwrap_or_default(),
+                "documentTitle": doc["title"].as_str().unwrap_or(""),
+                "expiresOn": doc["expiresOn"],
+                "state": if state == "expired" { "expired on" } else { "expires on" }
+            });
+            let meta = json!({
+                "dedupeKey": format!("document:{}:{}", document_id, state),
+                "refType": "employee_document",
+                "refId": document_id
+            });
+            match self
+                .notifier
+                .send_templated(school_id, "document_expiry_reminder", "push", employee_id, &vars, meta)
+                .await
+            {
+                Ok(result) if result["status"] != "skipped" => sent += 1,
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Document expiry reminder for {} failed: {}", document_id, e),
+            }
+        }
+        Ok(json!({ "reminded": sent }))
+    }
+}
+
+/// Daily job reminding employees of documents that are about to expire or have expired.
+pub async fn start_document_expiry_job(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(24 * 60 * 60));
+
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+            let schools = match state.repos.reminder.get_active_school_ids().await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!("[Documents] Failed to load schools: {}", e);
+                    continue;
+                }
+            };
+            for school_id in schools {
+                if let Err(e) = state
+                    .services
+                    .employee_documents
+                    .remind_expiring_documents(&school_id, EXPIRY_REMINDER_DAYS)
+                    .await
+                {
+                    tracing::error!("[Documents] {} expiry reminders failed: {}", school_id, e);
+                }
+            }
+        }
+    });
+}
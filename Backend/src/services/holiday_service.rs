@@ -0,0 +1,279 @@
+use crate::logic::holidays::{
+    academic_year_range, default_rules, gazetted_holidays, parse_ics, rule_from_rrule, supported_states, to_ics, validate_rule,
+    HolidayCalendar,
+};
+use crate::repository::Repositories;
+use crate::services::traits::*;
+use async_trait::async_trait;
+use chrono::{Datelike, Local, NaiveDate, Utc};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Longest range the calendar and working-day endpoints will walk.
+const MAX_RANGE_DAYS: i64 = 731;
+const MAX_IMPORT_EVENTS: usize = 1000;
+
+pub struct PostgresHolidayService {
+    pub repos: Arc<Repositories>,
+}
+
+/// The school's weekly-off rules, every Sunday when it hasn't saved any.
+pub(crate) async fn holiday_rules(repos: &Repositories, school_id: &str) -> Result<Vec<Value>, AppError> {
+    Ok(match repos.attendance.get_holiday_rules(school_id).await? {
+        Value::Array(rules) => rules,
+        _ => default_rules(),
+    })
+}
+
+/// Weekly offs and the holidays overlapping `from`..`to`, for working out days off.
+pub(crate) async fn load_calendar(repos: &Repositories, school_id: &str, from: NaiveDate, to: NaiveDate) -> Result<HolidayCalendar, AppError> {
+    Ok(HolidayCalendar::new(
+        holiday_rules(repos, school_id).await?,
+        repos.attendance.get_school_holidays(school_id, from, to).await?,
+    ))
+}
+
+fn parse_date(v: &str, field: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(v.trim(), "%Y-%m-%d").map_err(|_| format!("{} must be YYYY-MM-DD", field).into())
+}
+
+fn parse_range(from: &str, to: &str) -> Result<(NaiveDate, NaiveDate), AppError> {
+    let (from, to) = (parse_date(from, "from")?, parse_date(to, "to")?);
+    if to < from {
+        return Err("to must not be before from".into());
+    }
+    if (to - from).num_days() >= MAX_RANGE_DAYS {
+        return Err(format!("The range can be at most {} days", MAX_RANGE_DAYS).into());
+    }
+    Ok((from, to))
+}
+
+/// The academic year (April to March) containing today.
+fn current_academic_year() -> (NaiveDate, NaiveDate) {
+    let today = Local::now().date_naive();
+    let start = if today.month() >= 4 { today.year() } else { today.year() - 1 };
+    academic_year_range(&start.to_string()).expect("April and March always exist")
+}
+
+fn holiday_key(from_date: &str, title: &str) -> (String, String) {
+    (from_date.to_string(), title.trim().to_lowercase())
+}
+
+fn same_rule(a: &Value, b: &Value) -> bool {
+    a["weekday"] == b["weekday"] && a["weeks"] == b["weeks"]
+}
+
+impl PostgresHolidayService {
+    /// `(fromDate, lowercased title)` of the holidays already saved between `from` and `to`.
+    async fn existing_keys(&self, school_id: &str, from: NaiveDate, to: NaiveDate) -> Result<HashSet<(String, String)>, AppError> {
+        Ok(self
+            .repos
+            .attendance
+            .get_school_holidays(school_id, from, to)
+            .await?
+            .iter()
+            .map(|h| holiday_key(h["fromDate"].as_str().unwrap_or(""), h["title"].as_str().unwrap_or("")))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl HolidayService for PostgresHolidayService {
+    async fn get_holiday_rules(&self, school_id: &str) -> Result<Vec<Value>, AppError> {
+        holiday_rules(&self.repos, school_id).await
+    }
+
+    async fn save_holiday_rules(&self, school_id: &str, data: Value) -> Result<Vec<Value>, AppError> {
+        let rules = data["rules"].as_array().ok_or("rules must be a list")?;
+        let mut saved: Vec<Value> = Vec::new();
+        for rule in rules {
+            let rule = validate_rule(rule)?;
+            if !saved.iter().any(|r| same_rule(r, &rule)) {
+                saved.push(rule);
+            }
+        }
+        self.repos
+            .attendance
+            .save_holiday_rules(school_id, &json!(saved), data["updatedBy"].as_str())
+            .await?;
+        Ok(saved)
+    }
+
+    async fn holiday_calendar(&self, school_id: &str, from: &str, to: &str) -> Result<Value, AppError> {
+        let (from, to) = parse_range(from, to)?;
+        let calendar = load_calendar(&self.repos, school_id, from, to).await?;
+        let days: Vec<Value> = from
+            .iter_days()
+            .take_while(|d| *d <= to)
+            .filter_map(|d| {
+                let reason = calendar.off_reason(d)?;
+                let kind = if calendar.weekly_off(d).is_some() { "weekly" } else { "holiday" };
+                Some(json!({"date": d.to_string(), "weekday": d.format("%a").to_string(), "reason": reason, "kind": kind}))
+            })
+            .collect();
+        Ok(json!({
+            "from": from.to_string(),
+            "to": to.to_string(),
+            "rules": holiday_rules(&self.repos, school_id).await?,
+            "holidays": self.repos.attendance.get_school_holidays(school_id, from, to).await?,
+            "daysOff": days
+        }))
+    }
+
+    async fn working_days(&self, school_id: &str, from: &str, to: &str, person: Option<(&str, &str)>) -> Result<Value, AppError> {
+        let (from, to) = parse_range(from, to)?;
+        if let Some((role, _)) = person {
+            if role != "employee" && role != "student" {
+                return Err("role must be employee or student".into());
+            }
+        }
+        let calendar = load_calendar(&self.repos, school_id, from, to).await?;
+        let working = calendar.working_days(from, to, person);
+        let total = (to - from).num_days() + 1;
+        let weekly = from.iter_days().take_while(|d| *d <= to).filter(|d| calendar.weekly_off(*d).is_some()).count();
+        Ok(json!({
+            "from": from.to_string(),
+            "to": to.to_string(),
+            "role": person.map(|p| p.0),
+            "userId": person.map(|p| p.1),
+            "totalDays": total,
+            "workingDays": working.len(),
+            "weeklyOffs": weekly,
+            "holidays": total as usize - working.len() - weekly
+        }))
+    }
+
+    async fn day_off_reason(&self, school_id: &str, date: &str, role: &str, user_id: &str) -> Result<Option<String>, AppError> {
+        let date = parse_date(date, "date")?;
+        let calendar = load_calendar(&self.repos, school_id, date, date).await?;
+        Ok(calendar.off_reason_for(date, role, user_id))
+    }
+
+    async fn export_ics(&self, school_id: &str, from: Option<&str>, to: Option<&str>) -> Result<String, AppError> {
+        let (from, to) = match (from.filter(|s| !s.is_empty()), to.filter(|s| !s.is_empty())) {
+            (Some(f), Some(t)) => parse_range(f, t)?,
+            (None, None) => current_academic_year(),
+            _ => return Err("Give both from and to, or neither for the current academic year".into()),
+        };
+        let holidays = self.repos.attendance.get_school_holidays(school_id, from, to).await?;
+        let rules = holiday_rules(&self.repos, school_id).await?;
+        let school_name = self
+            .repos
+            .school
+            .get_school(school_id)
+            .await?
+            .and_then(|s| s["schoolName"].as_str().map(|n| n.to_string()))
+            .unwrap_or_else(|| school_id.to_string());
+        let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        Ok(to_ics(school_id, &format!("{} holidays", school_name), &holidays, &rules, from, &stamp))
+    }
+
+    async fn import_ics(&self, school_id: &str, ics: &str, dry_run: bool, updated_by: Option<&str>) -> Result<Value, AppError> {
+        let (events, mut skipped) = parse_ics(ics)?;
+        if events.len() > MAX_IMPORT_EVENTS {
+            return Err(format!("The file has {} events; import at most {} at a time", events.len(), MAX_IMPORT_EVENTS).into());
+        }
+
+        let mut rules = holiday_rules(&self.repos, school_id).await?;
+        let mut new_rules = Vec::new();
+        let mut holidays = Vec::new();
+        let mut warnings = Vec::new();
+        for event in events {
+            let title = event["title"].as_str().unwrap_or("Holiday");
+            if let Some(rrule) = event["rrule"].as_str() {
+                match rule_from_rrule(rrule, title) {
+                    Some(rule) => {
+                        if !rules.iter().any(|r| same_rule(r, &rule)) {
+                            rules.push(rule.clone());
+                            new_rules.push(rule);
+                        }
+                        continue;
+                    }
+                    None => warnings.push(json!({
+                        "title": title,
+                        "message": format!("Repeat rule {} isn't a weekly off; only the first occurrence was imported", rrule)
+                    })),
+                }
+            }
+            let (Some(from), Some(to)) = (event["fromDate"].as_str(), event["toDate"].as_str()) else { continue };
+            let span = parse_date(to, "toDate")? - parse_date(from, "fromDate")?;
+            if span.num_days() > 366 {
+                skipped.push(json!({"title": title, "reason": "Lasts more than a year"}));
+                continue;
+            }
+            holidays.push(json!({"title": title, "description": event["description"], "fromDate": from, "toDate": to}));
+        }
+
+        // Holidays already on the calendar are reported rather than duplicated
+        let dates: Vec<NaiveDate> = holidays
+            .iter()
+            .filter_map(|h| h["fromDate"].as_str().and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()))
+            .collect();
+        let existing = match (dates.iter().min(), dates.iter().max()) {
+            (Some(min), Some(max)) => self.existing_keys(school_id, *min, *max).await?,
+            _ => HashSet::new(),
+        };
+        let (duplicates, fresh): (Vec<Value>, Vec<Value>) = holidays.into_iter().partition(|h| {
+            existing.contains(&holiday_key(h["fromDate"].as_str().unwrap_or(""), h["title"].as_str().unwrap_or("")))
+        });
+
+        let added = if dry_run || fresh.is_empty() {
+            fresh
+        } else {
+            self.repos.attendance.add_school_holidays(school_id, &fresh).await?
+        };
+        if !dry_run && !new_rules.is_empty() {
+            self.repos.attendance.save_holiday_rules(school_id, &json!(rules), updated_by).await?;
+        }
+        Ok(json!({
+            "dryRun": dry_run,
+            "holidays": added,
+            "rulesAdded": new_rules,
+            "alreadyPresent": duplicates,
+            "skipped": skipped,
+            "warnings": warnings
+        }))
+    }
+
+    async fn gazetted_holidays(&self, school_id: &str, data: Value) -> Result<Value, AppError> {
+        let (from, to) = match (data["academicYear"].as_str(), data["from"].as_str(), data["to"].as_str()) {
+            (Some(year), _, _) => academic_year_range(year).ok_or("academicYear must look like 2026-27")?,
+            (None, Some(f), Some(t)) => parse_range(f, t)?,
+            _ => current_academic_year(),
+        };
+        let dry_run = data["dryRun"].as_bool().unwrap_or(true);
+        let (list, missing) = gazetted_holidays(from, to, data["state"].as_str())?;
+        let existing = self.existing_keys(school_id, from, to).await?;
+
+        let mut fresh = Vec::new();
+        let holidays: Vec<Value> = list
+            .into_iter()
+            .map(|mut h| {
+                let date = h["date"].as_str().unwrap_or("").to_string();
+                let exists = existing.contains(&holiday_key(&date, h["title"].as_str().unwrap_or("")));
+                if !exists {
+                    fresh.push(json!({"title": h["title"], "description": "Gazetted holiday", "fromDate": date, "toDate": date}));
+                }
+                h["alreadyPresent"] = json!(exists);
+                h
+            })
+            .collect();
+        let added = if dry_run || fresh.is_empty() {
+            Vec::new()
+        } else {
+            self.repos.attendance.add_school_holidays(school_id, &fresh).await?
+        };
+        Ok(json!({
+            "from": from.to_string(),
+            "to": to.to_string(),
+            "state": data["state"],
+            "states": supported_states(),
+            "dryRun": dry_run,
+            "holidays": holidays,
+            "added": added,
+            // Lunar festivals aren't known for these years yet and need adding by hand
+            "festivalListMissing": missing
+        }))
+    }
+}
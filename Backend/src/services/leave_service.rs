@@ -1,13 +1,329 @@
-use crate::repository::traits::*;
+use crate::logic::leave::{accrued, balance, carry_forward, days_by_year, is_unlimited, leave_dates, merge_leave_types};
 use crate::repository::Repositories;
+use crate::services::holiday_service::load_calendar;
+use crate::services::payroll_service::{employee_name, ensure_payroll_open};
+use crate::services::session_service::ensure_open_dates;
 use crate::services::traits::*;
+use crate::AppState;
 use async_trait::async_trait;
+use chrono::{Datelike, NaiveDate};
 use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::sync::Arc;
+use std::time::Duration as StdDuration;
 
 pub struct PostgresLeaveService {
     pub repos: Arc<Repositories>,
+    pub notifier: Arc<dyn NotificationService>,
+}
+
+/// The school's leave types with the built-in defaults filled in.
+pub(crate) async fn leave_types(repos: &Repositories, school_id: &str) -> Result<Vec<Value>, AppError> {
+    Ok(merge_leave_types(&repos.leave.get_leave_types(school_id).await?))
+}
+
+/// Codes of leave types that are unpaid; days on these count as absence in payroll.
+pub(crate) async fn unpaid_leave_types(repos: &Repositories, school_id: &str) -> Result<Vec<String>, AppError> {
+    Ok(leave_types(repos, school_id)
+        .await?
+        .iter()
+        .filter(|t| t["paid"] == false)
+        .filter_map(|t| t["code"].as_str().map(|c| c.to_string()))
+        .collect())
+}
+
+fn parse_date(v: &Value, field: &str) -> Result<NaiveDate, AppError> {
+    v.as_str()
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        .ok_or_else(|| format!("{} must be YYYY-MM-DD", field).into())
+}
+
+/// Whether a gender-restricted type (maternity) applies to the employee. Employees with no
+/// recorded gender are not restricted.
+fn type_applies(leave_type: &Value, employee: &Value) -> bool {
+    match (leave_type["gender"].as_str(), employee["gender"].as_str()) {
+        (Some(g), Some(e)) if !g.is_empty() && !e.is_empty() => g.eq_ignore_ascii_case(e),
+        _ => true,
+    }
+}
+
+fn delegation_active(d: &Value, today: NaiveDate) -> bool {
+    let today = today.to_string();
+    d["revoked"] == false
+        && d["fromDate"].as_str().is_some_and(|f| f <= today.as_str())
+        && d["toDate"].as_str().is_some_and(|t| t >= today.as_str())
+}
+
+/// Adds the current step's name and approvers to a leave for display.
+fn with_progress(mut leave: Value) -> Value {
+    let step = leave["approvalStep"].as_i64().unwrap_or(0) as usize;
+    let current = leave["approvalChain"]["steps"].get(step).cloned();
+    if leave["status"] == "pending" {
+        leave["pendingWith"] = match current {
+            Some(s) => json!({"step": step + 1, "name": s["name"], "approvers": s["approvers"]}),
+            None => json!({"step": 1, "name": "Admin", "approvers": []}),
+        };
+    }
+    leave
+}
+
+impl PostgresLeaveService {
+    fn find_type<'a>(&self, types: &'a [Value], code: &str) -> Result<&'a Value, AppError> {
+        types
+            .iter()
+            .find(|t| t["code"] == code && t["active"] != false)
+            .ok_or_else(|| format!("Unknown leave type '{}'", code).into())
+    }
+
+    async fn find_leave(&self, school_id: &str, leave_id: &str) -> Result<Value, AppError> {
+        self.repos
+            .leave
+            .get_leave(school_id, leave_id)
+            .await?
+            .ok_or_else(|| "Leave application not found".into())
+    }
+
+    /// Balances of every applicable type for the given employees in `year`.
+    async fn balances_for(
+        &self,
+        school_id: &str,
+        employees: &[Value],
+        year: i32,
+    ) -> Result<Vec<Value>, AppError> {
+        let types = leave_types(&self.repos, school_id).await?;
+        let rows = self.repos.leave.get_leave_balance_rows(school_id, year).await?;
+        let usage = self.repos.leave.get_leave_usage(school_id, year).await?;
+        let as_of = chrono::Local::now().date_naive();
+
+        let mut out = Vec::new();
+        for emp in employees {
+            let employee_id = emp["employeeId"].as_str().unwrap_or("");
+            let mut balances = Vec::new();
+            for t in types.iter().filter(|t| t["active"] != false && type_applies(t, emp)) {
+                let code = t["code"].as_str().unwrap_or("");
+                let row = rows.iter().find(|r| r["employeeId"] == employee_id && r["leaveType"] == code);
+                let days = |status: &str| {
+                    usage
+                        .iter()
+                        .filter(|u| u["employeeId"] == employee_id && u["leaveType"] == code && u["status"] == status)
+                        .map(|u| u["days"].as_f64().unwrap_or(0.0))
+                        .sum::<f64>()
+                };
+                balances.push(balance(
+                    t,
+                    row.and_then(|r| r["opening"].as_f64()).unwrap_or(0.0),
+                    row.and_then(|r| r["adjustment"].as_f64()).unwrap_or(0.0),
+                    accrued(t, year, as_of),
+                    days("approved"),
+                    days("pending"),
+                ));
+            }
+            out.push(json!({
+                "employeeId": employee_id,
+                "employeeName": employee_name(emp),
+                "year": year,
+                "balances": balances
+            }));
+        }
+        Ok(out)
+    }
+
+    async fn employee(&self, school_id: &str, employee_id: &str) -> Result<Value, AppError> {
+        let mut emp = self
+            .repos
+            .employee
+            .get_employee(school_id, employee_id)
+            .await?
+            .ok_or("Employee not found")?;
+        emp["employeeId"] = json!(employee_id);
+        Ok(emp)
+    }
+
+    /// One type's balance for one employee.
+    async fn type_balance(&self, school_id: &str, employee: &Value, code: &str, year: i32) -> Result<Value, AppError> {
+        let rows = self.balances_for(school_id, std::slice::from_ref(employee), year).await?;
+        Ok(rows[0]["balances"]
+            .as_array()
+            .and_then(|b| b.iter().find(|b| b["leaveType"] == code).cloned())
+            .unwrap_or(Value::Null))
+    }
+
+    /// Checks each year's share of a leave against that year's balance. `field` is `available`
+    /// for pending days, or `closing` once the leave's own days are already held back.
+    async fn ensure_year_balances(
+        &self,
+        school_id: &str,
+        employee: &Value,
+        code: &str,
+        split: &BTreeMap<i32, f64>,
+        field: &str,
+    ) -> Result<(), AppError> {
+        for (year, days) in split {
+            if *days <= 0.0 {
+                continue;
+            }
+            let bal = self.type_balance(school_id, employee, code, *year).await?;
+            let left = bal[field].as_f64().unwrap_or(0.0);
+            if !bal.is_null() && *days > left {
+                return Err(format!(
+                    "Insufficient {} balance for {}: {} day(s) left, {} requested",
+                    bal["name"].as_str().unwrap_or(code),
+                    year,
+                    left,
+                    days
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    async fn dates_of(&self, school_id: &str, leave_type: &Value, from: NaiveDate, to: NaiveDate) -> Result<Vec<NaiveDate>, AppError> {
+        let calendar = load_calendar(&self.repos, school_id, from, to).await?;
+        Ok(leave_dates(leave_type, from, to, &calendar))
+    }
+
+    /// Checks that `actor` may decide at `step`. Returns the approver they act for when they
+    /// hold a delegation rather than being an approver themselves.
+    async fn authorise(&self, school_id: &str, step: Option<&Value>, actor: &str) -> Result<Option<String>, AppError> {
+        let approvers: Vec<&str> = step
+            .and_then(|s| s["approvers"].as_array())
+            .map(|a| a.iter().filter_map(|x| x.as_str()).collect())
+            .unwrap_or_default();
+        if approvers.is_empty() || approvers.contains(&actor) {
+            return Ok(None);
+        }
+        let today = chrono::Local::now().date_naive();
+        let delegations = self.repos.leave.get_delegations(school_id, None).await?;
+        delegations
+            .iter()
+            .find(|d| d["delegateId"] == actor && delegation_active(d, today) && approvers.iter().any(|a| d["approverId"] == *a))
+            .map(|d| Some(d["approverId"].as_str().unwrap_or("").to_string()))
+            .ok_or_else(|| {
+                format!(
+                    "{} is not an approver for the {} step",
+                    actor,
+                    step.and_then(|s| s["name"].as_str()).unwrap_or("current")
+                )
+                .into()
+            })
+    }
+
+    /// The chain for the employee's type, else the school-wide ('*') chain. Without either the
+    /// leave goes straight to the admin, as before chains existed.
+    async fn chain_for(&self, school_id: &str, employee: &Value) -> Result<Value, AppError> {
+        let chains = self.repos.leave.get_approval_chains(school_id).await?;
+        let employee_type = employee["employeeType"].as_str().or(employee["type"].as_str()).unwrap_or("");
+        Ok(chains
+            .iter()
+            .find(|c| !employee_type.is_empty() && c["employeeType"].as_str().is_some_and(|t| t.eq_ignore_ascii_case(employee_type)))
+            .or_else(|| chains.iter().find(|c| c["employeeType"] == "*"))
+            .map(|c| json!({"employeeType": c["employeeType"], "steps": c["steps"], "escalateAfterHours": c["escalateAfterHours"]}))
+            .unwrap_or(json!({"steps": []})))
+    }
+
+    async fn leave_type_of(&self, school_id: &str, code: &str) -> Result<Value, AppError> {
+        let types = leave_types(&self.repos, school_id).await?;
+        // A type removed since the application was made is treated as paid, without a balance
+        Ok(types.iter().find(|t| t["code"] == code).cloned().unwrap_or(json!({"code": code, "paid": true})))
+    }
+
+    /// Final approval: checks the balance again, marks attendance for the leave days.
+    async fn finalise_approval(&self, school_id: &str, leave: &Value, actor: Option<&str>) -> Result<(), AppError> {
+        let leave_id = leave["leaveId"].as_str().unwrap_or("");
+        let employee_id = leave["employeeId"].as_str().unwrap_or("");
+        let code = leave["leaveType"].as_str().unwrap_or("");
+        let leave_type = self.leave_type_of(school_id, code).await?;
+        let from = parse_date(&leave["fromDate"], "fromDate")?;
+        let to = parse_date(&leave["toDate"], "toDate")?;
+        let dates = self.dates_of(school_id, &leave_type, from, to).await?;
+
+        if !is_unlimited(&leave_type) {
+            let employee = self.employee(school_id, employee_id).await?;
+            self.ensure_year_balances(school_id, &employee, code, &days_by_year(&dates), "closing")
+                .await?;
+        }
+        self.ensure_months_open(school_id, &dates).await?;
+        let history = json!({"leaveId": leave_id, "leaveType": code, "dates": dates.iter().map(|d| d.to_string()).collect::<Vec<_>>()});
+        if !self
+            .repos
+            .leave
+            .approve_leave(school_id, leave, &dates, actor, history)
+            .await?
+        {
+            return Err("Leave is no longer pending".into());
+        }
+        Ok(())
+    }
+
+    /// Rejects a pending leave or cancels an approved one, removing its attendance marks.
+    async fn finalise_rejection(&self, school_id: &str, leave: &Value, actor: Option<&str>) -> Result<(), AppError> {
+        let leave_id = leave["leaveId"].as_str().unwrap_or("");
+        let employee_id = leave["employeeId"].as_str().unwrap_or("");
+        let was_approved = leave["status"] == "approved";
+        if was_approved {
+            let leave_type = self.leave_type_of(school_id, leave["leaveType"].as_str().unwrap_or("")).await?;
+            let from = parse_date(&leave["fromDate"], "fromDate")?;
+            let to = parse_date(&leave["toDate"], "toDate")?;
+            let dates = self.dates_of(school_id, &leave_type, from, to).await?;
+            self.ensure_months_open(school_id, &dates).await?;
+        }
+        if !self
+            .repos
+            .leave
+            .update_leave_status(school_id, leave_id, &["pending", "approved"], "rejected", actor)
+            .await?
+        {
+            return Err(format!("Leave is already {}", leave["status"].as_str().unwrap_or("")).into());
+        }
+        if was_approved {
+            self.repos.leave.clear_leave_attendance(school_id, employee_id, leave_id).await?;
+            self.repos
+                .operations
+                .add_attendance_history(school_id, "employee", employee_id, "leave_cleared", json!({"leaveId": leave_id}))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Tells the applicant (push to their employee ID) where the leave stands. Delivery
+    /// problems are logged by the notifier and never fail the leave action.
+    async fn notify_applicant(&self, school_id: &str, leave: &Value, update: &str, comment: Option<&str>, stage: &str) {
+        let leave_id = leave["leaveId"].as_str().unwrap_or("");
+        let vars = json!({
+            "employeeName": leave["employeeName"],
+            "leaveType": leave["leaveType"],
+            "fromDate": leave["fromDate"],
+            "toDate": leave["toDate"],
+            "update": update,
+            "comment": comment.map(|c| format!(" Comment: {}", c)).unwrap_or_default()
+        });
+        let meta = json!({
+            "dedupeKey": format!("leave:{}:{}", leave_id, stage),
+            "refType": "leave",
+            "refId": leave_id
+        });
+        if let Err(e) = self
+            .notifier
+            .send_templated(school_id, "leave_status_update", "push", leave["employeeId"].as_str().unwrap_or(""), &vars, meta)
+            .await
+        {
+            tracing::warn!("Leave notification for {} failed: {}", leave_id, e);
+        }
+    }
+
+    /// Attendance in an approved payroll month or an archived session can't change, so neither can
+    /// leave marked there.
+    async fn ensure_months_open(&self, school_id: &str, dates: &[NaiveDate]) -> Result<(), AppError> {
+        ensure_open_dates(&self.repos, school_id, dates).await?;
+        let mut months: Vec<(i32, i32)> = dates.iter().map(|d| (d.month() as i32, d.year())).collect();
+        months.dedup();
+        for (month, year) in months {
+            ensure_payroll_open(&self.repos, school_id, month, year).await?;
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -17,14 +333,73 @@ impl LeaveService for PostgresLeaveService {
         school_id: &str,
         data: Value,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
-        self.repos.leave.add_leave(school_id, data).await
+        let employee_id = data["employeeId"].as_str().ok_or("Employee ID is required")?;
+        let employee = self.employee(school_id, employee_id).await?;
+        let types = leave_types(&self.repos, school_id).await?;
+        let code = data["leaveType"].as_str().unwrap_or("casual");
+        let leave_type = self.find_type(&types, code)?;
+        if !type_applies(leave_type, &employee) {
+            return Err(format!("{} does not apply to this employee", leave_type["name"].as_str().unwrap_or(code)).into());
+        }
+
+        let from = parse_date(&data["fromDate"], "fromDate")?;
+        let to = parse_date(&data["toDate"], "toDate")?;
+        if to < from {
+            return Err("toDate cannot be before fromDate".into());
+        }
+        let dates = self.dates_of(school_id, leave_type, from, to).await?;
+        let days = dates.len() as f64;
+        let split = days_by_year(&dates);
+        if days == 0.0 {
+            return Err("The selected dates are all holidays".into());
+        }
+
+        let overlapping = self.repos.leave.get_leaves(school_id).await?.into_iter().find(|l| {
+            l["employeeId"] == employee_id
+                && (l["status"] == "pending" || l["status"] == "approved")
+                && l["fromDate"].as_str().unwrap_or("") <= to.to_string().as_str()
+                && l["toDate"].as_str().unwrap_or("") >= from.to_string().as_str()
+        });
+        if let Some(l) = overlapping {
+            return Err(format!("Overlaps leave {} ({} to {})", l["leaveId"], l["fromDate"], l["toDate"]).into());
+        }
+
+        if !is_unlimited(leave_type) {
+            self.ensure_year_balances(school_id, &employee, code, &split, "available").await?;
+        }
+
+        let mut record = data.clone();
+        record["leaveType"] = json!(code);
+        record["days"] = json!(days);
+        record["daysByYear"] = json!(split);
+        if record["employeeName"].as_str().unwrap_or("").is_empty() {
+            record["employeeName"] = json!(employee_name(&employee));
+        }
+        let chain = self.chain_for(school_id, &employee).await?;
+        let first = chain["steps"][0]["name"].as_str().unwrap_or("Admin").to_string();
+        record["approvalChain"] = chain;
+        record["timeline"] = json!([{
+            "action": "applied",
+            "by": employee_id,
+            "comment": data["reason"],
+            "at": chrono::Utc::now().to_rfc3339()
+        }]);
+        let created = self.repos.leave.add_leave(school_id, record).await?;
+        self.notify_applicant(school_id, &created, &format!("submitted and awaiting {}", first), None, "applied")
+            .await;
+        Ok(with_progress(created))
     }
 
     async fn get_leaves(
         &self,
         school_id: &str,
     ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
-        self.repos.leave.get_leaves(school_id).await
+        Ok(self.repos.leave.get_leaves(school_id).await?.into_iter().map(with_progress).collect())
+    }
+
+    async fn get_leave(&self, school_id: &str, leave_id: &str) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let leave = self.find_leave(school_id, leave_id).await?;
+        Ok(with_progress(leave))
     }
 
     async fn update_leave_status(
@@ -32,8 +407,270 @@ impl LeaveService for PostgresLeaveService {
         school_id: &str,
         leave_id: &str,
         status: &str,
-    ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        self.repos.leave.update_leave_status(school_id, leave_id, status).await
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let leave = self.find_leave(school_id, leave_id).await?;
+        let employee_id = leave["employeeId"].as_str().unwrap_or("");
+        let actor = data["approverId"].as_str().map(|a| a.trim()).filter(|a| !a.is_empty());
+        let comment = data["comment"].as_str().map(|c| c.trim()).filter(|c| !c.is_empty());
+        if actor == Some(employee_id) {
+            return Err("Approvers cannot decide their own leave".into());
+        }
+        let steps = leave["approvalChain"]["steps"].as_array().cloned().unwrap_or_default();
+        let step = leave["approvalStep"].as_i64().unwrap_or(0) as usize;
+        let step_name = |i: usize| steps.get(i).and_then(|s| s["name"].as_str()).unwrap_or("Admin").to_string();
+
+        // On a chain the actor must be the step's approver (or their delegate). The admin can
+        // still decide at any step, but only by saying so, and the timeline records it.
+        let admin_override = data["adminOverride"].as_bool().unwrap_or(false);
+        if !steps.is_empty() && actor.is_none() && !admin_override {
+            return Err("approverId is required; set adminOverride to decide as the admin".into());
+        }
+        let overriding = admin_override && !steps.is_empty();
+        let on_behalf = match actor {
+            Some(a) if !overriding => {
+                let acting_step = if leave["status"] == "approved" { steps.len().saturating_sub(1) } else { step };
+                self.authorise(school_id, steps.get(acting_step), a).await?
+            }
+            _ => None,
+        };
+        let mut entry = json!({
+            "action": status,
+            "by": actor.unwrap_or("admin"),
+            "onBehalfOf": on_behalf,
+            "override": overriding,
+            "step": step + 1,
+            "stepName": step_name(step),
+            "comment": comment,
+            "at": chrono::Utc::now().to_rfc3339()
+        });
+
+        match status {
+            "approved" => {
+                if leave["status"] != "pending" {
+                    return Err(format!("Leave is already {}", leave["status"].as_str().unwrap_or("")).into());
+                }
+                if !overriding && step + 1 < steps.len() {
+                    entry["forwardedTo"] = json!(step_name(step + 1));
+                    if !self
+                        .repos
+                        .leave
+                        .advance_leave_step(school_id, leave_id, step as i32, step as i32 + 1, &entry)
+                        .await?
+                    {
+                        return Err("Leave has already moved on; reload it".into());
+                    }
+                    self.notify_applicant(
+                        school_id,
+                        &leave,
+                        &format!("approved by {}, now awaiting {}", step_name(step), step_name(step + 1)),
+                        comment,
+                        &format!("step{}", step + 1),
+                    )
+                    .await;
+                } else {
+                    self.finalise_approval(school_id, &leave, actor).await?;
+                    self.repos.leave.append_leave_timeline(school_id, leave_id, &entry).await?;
+                    self.notify_applicant(school_id, &leave, "approved", comment, "approved").await;
+                }
+            }
+            "rejected" => {
+                self.finalise_rejection(school_id, &leave, actor).await?;
+                self.repos.leave.append_leave_timeline(school_id, leave_id, &entry).await?;
+                self.notify_applicant(school_id, &leave, &format!("rejected by {}", step_name(step)), comment, "rejected")
+                    .await;
+            }
+            other => return Err(format!("Unsupported leave status '{}'", other).into()),
+        }
+        self.get_leave(school_id, leave_id).await
+    }
+
+    async fn add_leave_comment(
+        &self,
+        school_id: &str,
+        leave_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let leave = self.find_leave(school_id, leave_id).await?;
+        let comment = data["comment"].as_str().map(|c| c.trim()).filter(|c| !c.is_empty()).ok_or("comment is required")?;
+        let by = data["by"].as_str().filter(|b| !b.is_empty()).ok_or("by is required")?;
+        let entry = json!({
+            "action": "comment",
+            "by": by,
+            "step": leave["approvalStep"].as_i64().unwrap_or(0) + 1,
+            "comment": comment,
+            "at": chrono::Utc::now().to_rfc3339()
+        });
+        self.repos.leave.append_leave_timeline(school_id, leave_id, &entry).await?;
+        if leave["employeeId"] != by {
+            let count = leave["timeline"].as_array().map(|t| t.len()).unwrap_or(0);
+            self.notify_applicant(school_id, &leave, "has a new comment", Some(comment), &format!("comment{}", count))
+                .await;
+        }
+        self.get_leave(school_id, leave_id).await
+    }
+
+    async fn get_approval_chains(&self, school_id: &str) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos.leave.get_approval_chains(school_id).await
+    }
+
+    async fn save_approval_chain(&self, school_id: &str, data: Value) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let employee_type = data["employeeType"].as_str().map(|t| t.trim()).filter(|t| !t.is_empty()).unwrap_or("*");
+        let raw = data["steps"].as_array().ok_or("steps is required: [{ name, approvers: [employeeId] }]")?;
+        if raw.is_empty() || raw.len() > 5 {
+            return Err("An approval chain needs between 1 and 5 steps".into());
+        }
+        let mut steps = Vec::new();
+        for (i, st) in raw.iter().enumerate() {
+            let name = st["name"].as_str().map(|n| n.trim()).filter(|n| !n.is_empty()).ok_or(format!("Step {} needs a name", i + 1))?;
+            let approvers: Vec<String> = st["approvers"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|a| a.as_str().map(|a| a.trim().to_string()))
+                .filter(|a| !a.is_empty())
+                .collect();
+            if approvers.is_empty() {
+                return Err(format!("Step '{}' needs at least one approver", name).into());
+            }
+            for a in &approvers {
+                self.employee(school_id, a).await.map_err(|_| format!("Approver {} is not an employee", a))?;
+            }
+            steps.push(json!({"name": name, "approvers": approvers}));
+        }
+        let hours = data["escalateAfterHours"].as_i64();
+        if hours.is_some_and(|h| h <= 0) {
+            return Err("escalateAfterHours must be positive".into());
+        }
+        let chain = json!({
+            "employeeType": employee_type,
+            "steps": steps,
+            "escalateAfterHours": hours,
+            "updatedBy": data["updatedBy"]
+        });
+        self.repos.leave.save_approval_chain(school_id, &chain).await?;
+        Ok(chain)
+    }
+
+    async fn delete_approval_chain(&self, school_id: &str, employee_type: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !self.repos.leave.delete_approval_chain(school_id, employee_type).await? {
+            return Err("Approval chain not found".into());
+        }
+        Ok(())
+    }
+
+    async fn pending_approvals(&self, school_id: &str, approver_id: &str) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let today = chrono::Local::now().date_naive();
+        let delegations = self.repos.leave.get_delegations(school_id, None).await?;
+        // Approvers this person can act for today: themselves plus anyone delegating to them
+        let mut acting_for = vec![approver_id.to_string()];
+        acting_for.extend(
+            delegations
+                .iter()
+                .filter(|d| d["delegateId"] == approver_id && delegation_active(d, today))
+                .filter_map(|d| d["approverId"].as_str().map(|a| a.to_string())),
+        );
+        Ok(self
+            .repos
+            .leave
+            .get_leaves(school_id)
+            .await?
+            .into_iter()
+            .filter(|l| l["status"] == "pending" && l["employeeId"] != approver_id)
+            .filter(|l| {
+                let step = l["approvalStep"].as_i64().unwrap_or(0) as usize;
+                l["approvalChain"]["steps"][step]["approvers"]
+                    .as_array()
+                    .is_some_and(|a| a.iter().any(|x| acting_for.iter().any(|f| x == f.as_str())))
+            })
+            .map(with_progress)
+            .collect())
+    }
+
+    async fn create_delegation(&self, school_id: &str, data: Value) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let approver = data["approverId"].as_str().filter(|a| !a.is_empty()).ok_or("approverId is required")?;
+        let delegate = data["delegateId"].as_str().filter(|d| !d.is_empty()).ok_or("delegateId is required")?;
+        if approver == delegate {
+            return Err("Cannot delegate to yourself".into());
+        }
+        self.employee(school_id, approver).await?;
+        self.employee(school_id, delegate).await.map_err(|_| "Delegate is not an employee")?;
+        let from = parse_date(&data["fromDate"], "fromDate")?;
+        let to = parse_date(&data["toDate"], "toDate")?;
+        if to < from {
+            return Err("toDate cannot be before fromDate".into());
+        }
+        // Chains of delegation would make it unclear who is acting for whom
+        let existing = self.repos.leave.get_delegations(school_id, Some(delegate)).await?;
+        if existing.iter().any(|d| d["revoked"] == false && d["fromDate"].as_str().unwrap_or("") <= to.to_string().as_str() && d["toDate"].as_str().unwrap_or("") >= from.to_string().as_str()) {
+            return Err("The delegate has delegated their own approvals for these dates".into());
+        }
+        self.repos.leave.create_delegation(school_id, &data).await
+    }
+
+    async fn list_delegations(&self, school_id: &str, approver_id: Option<&str>) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos.leave.get_delegations(school_id, approver_id).await
+    }
+
+    async fn revoke_delegation(&self, school_id: &str, delegation_id: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !self.repos.leave.revoke_delegation(school_id, delegation_id).await? {
+            return Err("Delegation not found or already revoked".into());
+        }
+        Ok(())
+    }
+
+    async fn escalate_overdue_leaves(&self, school_id: &str) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let now = chrono::Utc::now();
+        let mut escalated = Vec::new();
+        for leave in self.repos.leave.get_leaves(school_id).await? {
+            if leave["status"] != "pending" {
+                continue;
+            }
+            let steps = leave["approvalChain"]["steps"].as_array().cloned().unwrap_or_default();
+            let step = leave["approvalStep"].as_i64().unwrap_or(0) as usize;
+            let hours = match leave["approvalChain"]["escalateAfterHours"].as_i64() {
+                Some(h) if h > 0 && step + 1 < steps.len() => h,
+                _ => continue,
+            };
+            let started = leave["stepStartedAt"]
+                .as_str()
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.with_timezone(&chrono::Utc));
+            if started.is_none_or(|t| now - t < chrono::Duration::hours(hours)) {
+                continue;
+            }
+            let (from_name, to_name) = (
+                steps[step]["name"].as_str().unwrap_or("").to_string(),
+                steps[step + 1]["name"].as_str().unwrap_or("").to_string(),
+            );
+            let entry = json!({
+                "action": "escalated",
+                "by": "system",
+                "step": step + 1,
+                "stepName": from_name,
+                "forwardedTo": to_name,
+                "comment": format!("No decision within {} hours", hours),
+                "at": now.to_rfc3339()
+            });
+            let leave_id = leave["leaveId"].as_str().unwrap_or("");
+            if self
+                .repos
+                .leave
+                .advance_leave_step(school_id, leave_id, step as i32, step as i32 + 1, &entry)
+                .await?
+            {
+                self.notify_applicant(
+                    school_id,
+                    &leave,
+                    &format!("escalated from {} to {}", from_name, to_name),
+                    None,
+                    &format!("escalated{}", step + 1),
+                )
+                .await;
+                escalated.push(json!({"leaveId": leave_id, "from": from_name, "to": to_name}));
+            }
+        }
+        Ok(json!({"escalated": escalated.len(), "leaves": escalated}))
     }
 
     async fn update_leave_duration(
@@ -43,6 +680,241 @@ impl LeaveService for PostgresLeaveService {
         action: &str,
         days: i32,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        self.repos.leave.update_leave_duration(school_id, leave_id, action, days).await
+        let leave = self.find_leave(school_id, leave_id).await?;
+        let status = leave["status"].as_str().unwrap_or("");
+        if status != "pending" && status != "approved" {
+            return Err(format!("Cannot change a {} leave", status).into());
+        }
+        if days <= 0 {
+            return Err("days must be positive".into());
+        }
+        let employee_id = leave["employeeId"].as_str().unwrap_or("");
+        let code = leave["leaveType"].as_str().unwrap_or("");
+        let leave_type = self.leave_type_of(school_id, code).await?;
+        let from = parse_date(&leave["fromDate"], "fromDate")?;
+        let old_to = parse_date(&leave["toDate"], "toDate")?;
+        let delta = chrono::Duration::days(days as i64);
+        let to = if action == "extend" { old_to + delta } else { old_to - delta };
+        if to < from {
+            return Err("Cannot reduce the leave to before its start date".into());
+        }
+
+        let old_dates = self.dates_of(school_id, &leave_type, from, old_to).await?;
+        let dates = self.dates_of(school_id, &leave_type, from, to).await?;
+        let split = days_by_year(&dates);
+        if dates.len() > old_dates.len() && !is_unlimited(&leave_type) {
+            let employee = self.employee(school_id, employee_id).await?;
+            let old_split = days_by_year(&old_dates);
+            let extra: BTreeMap<i32, f64> =
+                split.iter().map(|(y, d)| (*y, d - old_split.get(y).copied().unwrap_or(0.0))).collect();
+            // A pending leave is already held back from `available`; an approved one is in `closing`
+            let field = if status == "approved" { "closing" } else { "available" };
+            self.ensure_year_balances(school_id, &employee, code, &extra, field).await?;
+        }
+
+        if status == "approved" {
+            let touched: Vec<NaiveDate> = if to > old_to { dates.clone() } else { old_dates.clone() };
+            self.ensure_months_open(school_id, &touched).await?;
+        }
+        self.repos
+            .leave
+            .update_leave_duration(school_id, leave_id, to, dates.len() as f64, &json!(split))
+            .await?;
+        self.repos
+            .leave
+            .append_leave_timeline(
+                school_id,
+                leave_id,
+                &json!({
+                    "action": if action == "extend" { "extended" } else { "reduced" },
+                    "by": "admin",
+                    "comment": format!("{} to {}", leave["toDate"].as_str().unwrap_or(""), to),
+                    "at": chrono::Utc::now().to_rfc3339()
+                }),
+            )
+            .await?;
+        if status == "approved" {
+            self.repos.leave.clear_leave_attendance(school_id, employee_id, leave_id).await?;
+            self.repos
+                .leave
+                .mark_leave_attendance(school_id, employee_id, &dates, code, leave_id)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_leave_types(&self, school_id: &str) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        leave_types(&self.repos, school_id).await
+    }
+
+    async fn save_leave_type(&self, school_id: &str, data: Value) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let code = data["code"]
+            .as_str()
+            .map(|c| c.trim().to_lowercase())
+            .filter(|c| !c.is_empty() && c.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_'))
+            .ok_or("code is required (letters, digits and underscores)")?;
+        let types = leave_types(&self.repos, school_id).await?;
+        let mut leave_type = types.iter().find(|t| t["code"] == code.as_str()).cloned().unwrap_or(json!({
+            "code": code, "annualQuota": 0.0, "accrual": "yearly", "carryForwardMax": 0.0,
+            "paid": true, "countHolidays": false, "gender": null, "active": true
+        }));
+        for key in ["name", "annualQuota", "accrual", "carryForwardMax", "paid", "countHolidays", "gender", "active"] {
+            if data.get(key).is_some() {
+                leave_type[key] = data[key].clone();
+            }
+        }
+        leave_type["code"] = json!(code);
+        if leave_type["name"].as_str().unwrap_or("").trim().is_empty() {
+            return Err("name is required".into());
+        }
+        if !["yearly", "monthly", "none"].contains(&leave_type["accrual"].as_str().unwrap_or("")) {
+            return Err("accrual must be yearly, monthly or none".into());
+        }
+        for key in ["annualQuota", "carryForwardMax"] {
+            if leave_type[key].as_f64().is_none_or(|v| v < 0.0) {
+                return Err(format!("{} must be a non-negative number", key).into());
+            }
+        }
+        self.repos.leave.save_leave_type(school_id, &leave_type).await?;
+        Ok(leave_type)
+    }
+
+    async fn get_leave_balances(
+        &self,
+        school_id: &str,
+        employee_id: Option<&str>,
+        year: i32,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let employees: Vec<Value> = match employee_id {
+            Some(id) => vec![self.employee(school_id, id).await?],
+            None => self
+                .repos
+                .employee
+                .get_employees(school_id)
+                .await?
+                .into_iter()
+                .filter(|e| e["status"] != "inactive" && e["employeeId"].is_string())
+                .collect(),
+        };
+        self.balances_for(school_id, &employees, year).await
+    }
+
+    async fn adjust_leave_balance(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let employee = self.employee(school_id, employee_id).await?;
+        let types = leave_types(&self.repos, school_id).await?;
+        let code = data["leaveType"].as_str().ok_or("leaveType is required")?;
+        let leave_type = self.find_type(&types, code)?;
+        if is_unlimited(leave_type) {
+            return Err("This leave type has no balance to adjust".into());
+        }
+        let days = data["days"].as_f64().filter(|d| *d != 0.0).ok_or("days is required (negative to deduct)")?;
+        let reason = data["reason"].as_str().filter(|r| !r.trim().is_empty()).ok_or("reason is required")?;
+        let year = data["year"].as_i64().map(|y| y as i32).unwrap_or(chrono::Local::now().year());
+
+        self.repos
+            .leave
+            .add_leave_adjustment(school_id, employee_id, code, year, days)
+            .await?;
+        self.repos
+            .operations
+            .add_payment_history(
+                school_id,
+                employee_id,
+                "leave_balance_adjusted",
+                json!({"leaveType": code, "year": year, "days": days, "reason": reason, "by": data["by"]}),
+            )
+            .await?;
+        self.type_balance(school_id, &employee, code, year).await
+    }
+
+    async fn carry_forward_leave(
+        &self,
+        school_id: &str,
+        from_year: i32,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let types = leave_types(&self.repos, school_id).await?;
+        let balances = self.get_leave_balances(school_id, None, from_year).await?;
+        let mut updated = 0;
+        for emp in &balances {
+            let employee_id = emp["employeeId"].as_str().unwrap_or("");
+            for b in emp["balances"].as_array().into_iter().flatten() {
+                let leave_type = match types.iter().find(|t| t["code"] == b["leaveType"]) {
+                    Some(t) if !is_unlimited(t) && t["carryForwardMax"].as_f64().unwrap_or(0.0) > 0.0 => t,
+                    _ => continue,
+                };
+                // Monthly accrual is only complete at year end
+                let closing = b["closing"].as_f64().unwrap_or(0.0) - b["accrued"].as_f64().unwrap_or(0.0)
+                    + accrued(leave_type, from_year, NaiveDate::from_ymd_opt(from_year, 12, 31).unwrap_or_default());
+                self.repos
+                    .leave
+                    .set_leave_opening(
+                        school_id,
+                        employee_id,
+                        b["leaveType"].as_str().unwrap_or(""),
+                        from_year + 1,
+                        carry_forward(leave_type, closing),
+                    )
+                    .await?;
+                updated += 1;
+            }
+        }
+        Ok(json!({"year": from_year + 1, "balancesUpdated": updated}))
     }
 }
+
+/// Daily job that carries each school's unused leave into the current year. Re-running picks
+/// up late approvals or adjustments to last year's leave.
+pub async fn start_leave_carry_forward_job(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(24 * 60 * 60));
+
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+            let year = chrono::Local::now().year();
+            let schools = match state.repos.reminder.get_active_school_ids().await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!("[Leave] Failed to load schools: {}", e);
+                    continue;
+                }
+            };
+            for school_id in schools {
+                if let Err(e) = state.services.leave.carry_forward_leave(&school_id, year - 1).await {
+                    tracing::error!("[Leave] {} carry-forward failed: {}", school_id, e);
+                }
+            }
+        }
+    });
+}
+
+/// Hourly job that moves leave past its step's escalation window on to the next approver.
+pub async fn start_leave_escalation_job(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(60 * 60));
+
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+            let schools = match state.repos.reminder.get_active_school_ids().await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!("[Leave] Failed to load schools: {}", e);
+                    continue;
+                }
+            };
+            for school_id in schools {
+                match state.services.leave.escalate_overdue_leaves(&school_id).await {
+                    Ok(r) if r["escalated"].as_u64().unwrap_or(0) > 0 => {
+                        tracing::info!("[Leave] {}: escalated {} application(s)", school_id, r["escalated"])
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("[Leave] {} escalation failed: {}", school_id, e),
+                }
+            }
+        }
+    });
+}
@@ -1,23 +1,53 @@
+pub mod absence_alert_service;
 pub mod academic_service;
+pub mod attendance_correction_service;
+pub mod attendance_service;
 pub mod auth_service;
 pub mod auxiliary_service;
+pub mod device_service;
+pub mod employee_document_service;
 pub mod employee_service;
+pub mod holiday_service;
 pub mod leave_service;
+pub mod notification_service;
 pub mod operations_service;
+pub mod payment_service;
+pub mod payroll_service;
+pub mod reconciliation_service;
+pub mod reminder_service;
+pub mod report_service;
 pub mod resource_service;
+pub mod session_service;
 pub mod setup_service;
 pub mod student_service;
+pub mod subject_attendance_service;
+pub mod timetable_service;
 pub mod traits;
 
 use crate::repository::Repositories;
+use crate::services::absence_alert_service::PostgresAbsenceAlertService;
 use crate::services::academic_service::PostgresAcademicService;
+use crate::services::attendance_correction_service::PostgresAttendanceCorrectionService;
+use crate::services::attendance_service::PostgresAttendanceService;
 use crate::services::auth_service::PostgresAuthService;
+use crate::services::device_service::PostgresDeviceService;
+use crate::services::employee_document_service::{storage_from_env, PostgresEmployeeDocumentService};
 use crate::services::employee_service::PostgresEmployeeService;
+use crate::services::holiday_service::PostgresHolidayService;
+use crate::services::notification_service::{gateway_from_env, PostgresNotificationService};
 use crate::services::operations_service::PostgresOperationsService;
+use crate::services::payment_service::{payment_gateway_from_env, PostgresPaymentService};
+use crate::services::payroll_service::PostgresPayrollService;
+use crate::services::reconciliation_service::PostgresReconciliationService;
+use crate::services::reminder_service::PostgresReminderService;
+use crate::services::report_service::PostgresReportService;
 use crate::services::resource_service::{PostgresOCRService, PostgresResourceService};
+use crate::services::session_service::PostgresSessionService;
 use crate::services::setup_service::PostgresSetupService;
 use crate::services::student_service::PostgresStudentService;
+use crate::services::subject_attendance_service::PostgresSubjectAttendanceService;
 use crate::services::leave_service::PostgresLeaveService;
+use crate::services::timetable_service::PostgresTimetableService;
 use crate::services::traits::*;
 use std::sync::Arc;
 
@@ -38,6 +68,20 @@ pub struct Services {
     pub responsibility: Arc<dyn ResponsibilityService>,
     pub task: Arc<dyn TaskService>,
     pub leave: Arc<dyn LeaveService>,
+    pub report: Arc<dyn ReportService>,
+    pub notification: Arc<dyn NotificationService>,
+    pub payment: Arc<dyn PaymentService>,
+    pub reconciliation: Arc<dyn ReconciliationService>,
+    pub payroll: Arc<dyn PayrollService>,
+    pub timetable: Arc<dyn TimetableService>,
+    pub employee_documents: Arc<dyn EmployeeDocumentService>,
+    pub attendance: Arc<dyn AttendanceService>,
+    pub devices: Arc<dyn DeviceService>,
+    pub absence_alerts: Arc<dyn AbsenceAlertService>,
+    pub holidays: Arc<dyn HolidayService>,
+    pub attendance_corrections: Arc<dyn AttendanceCorrectionService>,
+    pub subject_attendance: Arc<dyn SubjectAttendanceService>,
+    pub sessions: Arc<dyn SessionService>,
 }
 
 pub fn initialize_services(repos: Arc<Repositories>) -> Services {
@@ -46,6 +90,17 @@ pub fn initialize_services(repos: Arc<Repositories>) -> Services {
             repos: repos.clone(),
         },
     );
+    let notification_service: Arc<dyn NotificationService> = Arc::new(PostgresNotificationService {
+        repos: repos.clone(),
+        gateway: gateway_from_env(),
+    });
+    let operations_service: Arc<dyn OperationsService> = Arc::new(PostgresOperationsService {
+        repos: repos.clone(),
+    });
+    let payment_service: Arc<dyn PaymentService> = Arc::new(PostgresPaymentService {
+        repos: repos.clone(),
+        gateway: payment_gateway_from_env(),
+    });
 
     Services {
         auth: Arc::new(PostgresAuthService {
@@ -63,9 +118,7 @@ pub fn initialize_services(repos: Arc<Repositories>) -> Services {
         academic: Arc::new(PostgresAcademicService {
             repos: repos.clone(),
         }),
-        operations: Arc::new(PostgresOperationsService {
-            repos: repos.clone(),
-        }),
+        operations: operations_service.clone(),
         resource: Arc::new(PostgresResourceService {
             repos: repos.clone(),
         }),
@@ -74,13 +127,62 @@ pub fn initialize_services(repos: Arc<Repositories>) -> Services {
         }),
         award: auxiliary_service.clone() as Arc<dyn AwardService>,
         complain: auxiliary_service.clone() as Arc<dyn ComplainService>,
-        reminder: auxiliary_service.clone() as Arc<dyn ReminderService>,
+        reminder: Arc::new(PostgresReminderService {
+            repos: repos.clone(),
+            notifier: notification_service.clone(),
+        }),
         document_box: auxiliary_service.clone() as Arc<dyn DocumentBoxService>,
         school: auxiliary_service.clone() as Arc<dyn SchoolService>,
         responsibility: auxiliary_service.clone() as Arc<dyn ResponsibilityService>,
         task: auxiliary_service as Arc<dyn TaskService>,
         leave: Arc::new(PostgresLeaveService {
             repos: repos.clone(),
+            notifier: notification_service.clone(),
+        }),
+        report: Arc::new(PostgresReportService {
+            repos: repos.clone(),
+        }),
+        timetable: Arc::new(PostgresTimetableService {
+            repos: repos.clone(),
+            notifier: notification_service.clone(),
+        }),
+        employee_documents: Arc::new(PostgresEmployeeDocumentService {
+            repos: repos.clone(),
+            storage: storage_from_env(),
+            notifier: notification_service.clone(),
+        }),
+        attendance: Arc::new(PostgresAttendanceService {
+            repos: repos.clone(),
+            operations: operations_service.clone(),
+        }),
+        devices: Arc::new(PostgresDeviceService {
+            repos: repos.clone(),
+        }),
+        absence_alerts: Arc::new(PostgresAbsenceAlertService {
+            repos: repos.clone(),
+            notifier: notification_service.clone(),
+        }),
+        holidays: Arc::new(PostgresHolidayService {
+            repos: repos.clone(),
+        }),
+        attendance_corrections: Arc::new(PostgresAttendanceCorrectionService {
+            repos: repos.clone(),
+            storage: storage_from_env(),
+        }),
+        subject_attendance: Arc::new(PostgresSubjectAttendanceService {
+            repos: repos.clone(),
+        }),
+        sessions: Arc::new(PostgresSessionService {
+            repos: repos.clone(),
+        }),
+        notification: notification_service,
+        reconciliation: Arc::new(PostgresReconciliationService {
+            repos: repos.clone(),
+            payments: payment_service.clone(),
+        }),
+        payment: payment_service,
+        payroll: Arc::new(PostgresPayrollService {
+            repos: repos.clone(),
         }),
     }
 }
@@ -0,0 +1,294 @@
+use crate::repository::Repositories;
+use crate::services::traits::*;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::Arc;
+
+/// Delivery channel behind SMS / WhatsApp / email / push notifications.
+#[async_trait]
+pub trait NotificationGateway: Send + Sync {
+    /// Delivers one message and returns the provider's reference for it.
+    async fn send(
+        &self,
+        channel: &str,
+        recipient: &str,
+        subject: Option<&str>,
+        body: &str,
+    ) -> Result<String, AppError>;
+}
+
+/// Local stand-in used in development: writes the message to the log instead of sending it.
+pub struct LocalNotificationGateway;
+
+#[async_trait]
+impl NotificationGateway for LocalNotificationGateway {
+    async fn send(
+        &self,
+        channel: &str,
+        recipient: &str,
+        subject: Option<&str>,
+        body: &str,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        tracing::info!(
+            "[notify:{}] to {} {}: {}",
+            channel,
+            recipient,
+            subject.unwrap_or(""),
+            body
+        );
+        Ok(format!("LOCAL{}", chrono::Utc::now().timestamp_millis()))
+    }
+}
+
+/// Hands messages to an external relay (SMS/WhatsApp/email/push provider bridge) over HTTP.
+pub struct WebhookNotificationGateway {
+    pub url: String,
+    pub client: reqwest::Client,
+}
+
+#[async_trait]
+impl NotificationGateway for WebhookNotificationGateway {
+    async fn send(
+        &self,
+        channel: &str,
+        recipient: &str,
+        subject: Option<&str>,
+        body: &str,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let res = self
+            .client
+            .post(&self.url)
+            .json(&json!({
+                "channel": channel,
+                "to": recipient,
+                "subject": subject,
+                "body": body
+            }))
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(format!("Notification relay returned {}", res.status()).into());
+        }
+        let reply: Value = res.json().await.unwrap_or(Value::Null);
+        Ok(reply["id"]
+            .as_str()
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| format!("WH{}", chrono::Utc::now().timestamp_millis())))
+    }
+}
+
+/// Picks the gateway from `NOTIFY_GATEWAY` (`local` by default, or `webhook` with `NOTIFY_WEBHOOK_URL`).
+pub fn gateway_from_env() -> Arc<dyn NotificationGateway> {
+    match std::env::var("NOTIFY_GATEWAY").as_deref() {
+        Ok("webhook") => match std::env::var("NOTIFY_WEBHOOK_URL") {
+            Ok(url) => Arc::new(WebhookNotificationGateway {
+                url,
+                client: reqwest::Client::new(),
+            }),
+            Err(_) => {
+                tracing::warn!("NOTIFY_WEBHOOK_URL not set, falling back to local notifications");
+                Arc::new(LocalNotificationGateway)
+            }
+        },
+        _ => Arc::new(LocalNotificationGateway),
+    }
+}
+
+/// Built-in templates used until a school saves its own copy.
+fn default_template(template_key: &str) -> Option<Value> {
+    let (subject, body) = match template_key {
+        "fee_due_reminder" => (
+            "Fee due reminder",
+            "Dear Parent, {{feeName}} of Rs. {{amount}} for {{studentName}} is due on {{dueDate}}. Pay online: {{paymentLink}} - {{schoolName}}",
+        ),
+        "fee_overdue_reminder" => (
+            "Fee overdue",
+            "Dear Parent, {{feeName}} of Rs. {{amount}} for {{studentName}} was due on {{dueDate}} and is still unpaid. Pay online: {{paymentLink}} - {{schoolName}}",
+        ),
+        "leave_status_update" => (
+            "Leave update",
+            "Dear {{employeeName}}, your {{leaveType}} leave from {{fromDate}} to {{toDate}} has been {{update}}.{{comment}}",
+        ),
+        "substitution_assigned" => (
+            "Substitution assigned",
+            "Dear {{employeeName}}, please take {{periodName}} ({{subjectName}}) for {{className}} on {{date}}, {{startTime}} to {{endTime}}.",
+        ),
+        "document_expiry_reminder" => (
+            "Document expiring",
+            "Dear {{employeeName}}, your {{documentTitle}} {{state}} {{expiresOn}}. Please submit a renewed copy to the office.",
+        ),
+        "absence_alert" => (
+            "Absent today",
+            "Dear Parent, {{studentName}} ({{className}}) {{absence}} at school today, {{date}}. Please reply with the reason for the absence. - {{schoolName}}",
+        ),
+        _ => return None,
+    };
+    Some(json!({"templateKey": template_key, "subject": subject, "body": body, "isDefault": true}))
+}
+
+/// Replaces `{{name}}` placeholders with values from `vars`; unknown placeholders are left empty.
+pub fn render_template(template: &str, vars: &Value) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        match rest[start + 2..].find("}}") {
+            Some(end) => {
+                let key = rest[start + 2..start + 2 + end].trim();
+                match &vars[key] {
+                    Value::String(s) => out.push_str(s),
+                    Value::Null => {}
+                    v => out.push_str(&v.to_string()),
+                }
+                rest = &rest[start + 2 + end + 2..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+pub struct PostgresNotificationService {
+    pub repos: Arc<Repositories>,
+    pub gateway: Arc<dyn NotificationGateway>,
+}
+
+#[async_trait]
+impl NotificationService for PostgresNotificationService {
+    async fn list_templates(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let mut templates = self.repos.notification.get_templates(school_id).await?;
+        for key in ["fee_due_reminder", "fee_overdue_reminder", "leave_status_update", "substitution_assigned", "document_expiry_reminder", "absence_alert"] {
+            if !templates.iter().any(|t| t["templateKey"] == key) {
+                templates.extend(default_template(key));
+            }
+        }
+        Ok(templates)
+    }
+
+    async fn save_template(
+        &self,
+        school_id: &str,
+        template_key: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        self.repos
+            .notification
+            .upsert_template(school_id, template_key, data)
+            .await
+    }
+
+    async fn delete_template(
+        &self,
+        school_id: &str,
+        template_key: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.repos
+            .notification
+            .delete_template(school_id, template_key)
+            .await
+    }
+
+    async fn send_templated(
+        &self,
+        school_id: &str,
+        template_key: &str,
+        channel: &str,
+        recipient: &str,
+        vars: &Value,
+        meta: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let dedupe_key = meta["dedupeKey"].as_str();
+        let template = match self.repos.notification.get_template(school_id, template_key).await? {
+            Some(t) => t,
+            None => default_template(template_key)
+                .ok_or_else(|| format!("Unknown notification template '{}'", template_key))?,
+        };
+        let subject = template["subject"].as_str().map(|s| render_template(s, vars));
+        let body = render_template(template["body"].as_str().unwrap_or(""), vars);
+
+        // The key is claimed before sending so concurrent runs can't both send; a failed send releases it
+        if let Some(key) = dedupe_key {
+            let claim = json!({
+                "channel": channel,
+                "recipient": recipient,
+                "templateKey": template_key,
+                "refType": meta["refType"],
+                "refId": meta["refId"],
+                "dedupeKey": key,
+                "body": body
+            });
+            if !self.repos.notification.claim_notification(school_id, &claim).await? {
+                return Ok(json!({"status": "skipped", "reason": "already_sent", "dedupeKey": key}));
+            }
+        }
+
+        let (status, provider_ref, error) = match self
+            .gateway
+            .send(channel, recipient, subject.as_deref(), &body)
+            .await
+        {
+            Ok(r) => ("sent", Some(r), None),
+            Err(e) => {
+                tracing::warn!("Notification to {} on {} failed: {}", recipient, channel, e);
+                ("failed", None, Some(e.to_string()))
+            }
+        };
+
+        self.repos
+            .notification
+            .add_notification_log(
+                school_id,
+                json!({
+                    "channel": channel,
+                    "recipient": recipient,
+                    "templateKey": template_key,
+                    "refType": meta["refType"],
+                    "refId": meta["refId"],
+                    "dedupeKey": dedupe_key,
+                    "body": body,
+                    "status": status,
+                    "providerRef": provider_ref,
+                    "error": error
+                }),
+            )
+            .await
+    }
+
+    async fn list_log(
+        &self,
+        school_id: &str,
+        ref_type: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos
+            .notification
+            .get_notification_log(school_id, ref_type, limit.clamp(1, 500))
+            .await
+    }
+
+    async fn record_delivery(&self, data: Value) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let provider_ref = data["providerRef"]
+            .as_str()
+            .or_else(|| data["id"].as_str())
+            .filter(|r| !r.trim().is_empty())
+            .ok_or("providerRef is required")?;
+        let status = match data["status"].as_str().map(|s| s.trim().to_lowercase()).as_deref() {
+            Some(s @ ("sent" | "delivered" | "read" | "failed")) => s.to_string(),
+            Some("undelivered" | "rejected" | "bounced") => "failed".to_string(),
+            _ => return Err("status must be sent, delivered, read or failed".into()),
+        };
+        self.repos
+            .notification
+            .update_delivery_status(provider_ref.trim(), &status, data["error"].as_str())
+            .await?
+            .ok_or_else(|| format!("No notification with provider reference {}", provider_ref).into())
+    }
+}
@@ -1,8 +1,14 @@
+use crate::logic::attendance::change_entry;
+use crate::logic::payroll::{apply_revision, revision_in_force, REVISION_KEYS};
 use crate::repository::traits::*;
 use crate::repository::Repositories;
+use crate::services::attendance_service::{attendance_settings, ensure_attendance_open, record_locked};
+use crate::services::leave_service::unpaid_leave_types;
+use crate::services::payroll_service::{employee_salary, new_advance, revise_salary, PayrollMonth};
+use crate::services::session_service::{ensure_open_fee, ensure_open_session};
 use crate::services::traits::*;
 use async_trait::async_trait;
-use chrono::{Datelike, Local};
+use chrono::{Datelike, Local, NaiveDate};
 use serde_json::{json, Value};
 use std::error::Error;
 use std::sync::Arc;
@@ -25,6 +31,9 @@ impl OperationsService for PostgresOperationsService {
             .unwrap_or(&Local::now().format("%Y-%m-%d").to_string())
             .to_string();
 
+        self.ensure_attendance_open(school_id, role, &date).await?;
+        let before = self.unlocked_record(school_id, role, user_id, &date).await?;
+
         let mut final_data = data.clone();
 
         // 1. Calculate Duration if applicable
@@ -62,10 +71,13 @@ impl OperationsService for PostgresOperationsService {
         response_data["createdAt"] = json!({});
         response_data["updatedAt"] = json!({});
 
-        // 3. Log History (Audit Parity)
+        // 3. Log History (Audit Parity), with what it replaced
+        let mut entry = final_data.clone();
+        entry["before"] = json!(before);
+        entry["changedBy"] = data.get("markedBy").or(data.get("updatedBy")).cloned().unwrap_or(Value::Null);
         self.repos
             .operations
-            .add_attendance_history(school_id, role, user_id, "mark", final_data.clone())
+            .add_attendance_history(school_id, role, user_id, "mark", entry)
             .await?;
 
         Ok(response_data)
@@ -88,6 +100,9 @@ impl OperationsService for PostgresOperationsService {
             .unwrap_or("Holiday")
             .to_string();
 
+        self.ensure_attendance_open(school_id, role, &date).await?;
+        let before = self.unlocked_record(school_id, role, user_id, &date).await?;
+
         let holiday_data = json!({
             "status": "holiday",
             "date": date,
@@ -101,7 +116,13 @@ impl OperationsService for PostgresOperationsService {
 
         self.repos
             .operations
-            .add_attendance_history(school_id, role, user_id, "holiday_marked", holiday_data.clone())
+            .add_attendance_history(
+                school_id,
+                role,
+                user_id,
+                "holiday_marked",
+                change_entry(&date, before.as_ref(), Some(&holiday_data), data["markedBy"].as_str(), Some(&description)),
+            )
             .await?;
 
         Ok(holiday_data)
@@ -120,6 +141,9 @@ impl OperationsService for PostgresOperationsService {
             .ok_or("outTime is required")?
             .to_string();
 
+        self.ensure_attendance_open(school_id, role, date).await?;
+        self.unlocked_record(school_id, role, user_id, date).await?;
+
         // Fetch existing to compute duration
         let existing_list = self
             .repos
@@ -151,7 +175,13 @@ impl OperationsService for PostgresOperationsService {
 
         self.repos
             .operations
-            .add_attendance_history(school_id, role, user_id, "attendance_updated", json!({"outTime": out_time, "totalTime": total_time}))
+            .add_attendance_history(
+                school_id,
+                role,
+                user_id,
+                "attendance_updated",
+                change_entry(date, Some(&existing), Some(&updated), data["updatedBy"].as_str(), data["reason"].as_str()),
+            )
             .await?;
 
         Ok(updated)
@@ -163,7 +193,11 @@ impl OperationsService for PostgresOperationsService {
         role: &str,
         user_id: &str,
         date: &str,
+        data: Value,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.ensure_attendance_open(school_id, role, date).await?;
+        let before = self.unlocked_record(school_id, role, user_id, date).await?;
+
         self.repos
             .operations
             .delete_attendance(school_id, role, user_id, date)
@@ -171,7 +205,13 @@ impl OperationsService for PostgresOperationsService {
 
         self.repos
             .operations
-            .add_attendance_history(school_id, role, user_id, "attendance_deleted", json!({"date": date}))
+            .add_attendance_history(
+                school_id,
+                role,
+                user_id,
+                "attendance_deleted",
+                change_entry(date, before.as_ref(), None, data["deletedBy"].as_str(), data["reason"].as_str()),
+            )
             .await?;
 
         Ok(())
@@ -326,39 +366,23 @@ impl OperationsService for PostgresOperationsService {
         school_id: &str,
         student_id: &str,
         amount: i64,
+        details: Value,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
         let mut fee_record = self.get_student_fee(school_id, student_id).await?;
-        let pending = fee_record["pendingAmount"].as_f64().unwrap_or(0.0);
-
-        if amount as f64 > pending {
-            return Err("Pay amount exceeds pending amount".into());
+        if amount <= 0 {
+            return Err("Pay amount must be positive".into());
         }
 
-        let new_pending = pending - amount as f64;
-        fee_record["pendingAmount"] = json!(new_pending);
-
-        // Update DB
-        self.repos
-            .operations
-            .update_student_fee(school_id, student_id, fee_record.clone())
-            .await?;
-
-        // Log History (Parity with Node.js)
-        self.repos
+        // Balance, receipt and history are written together; the balance check is part of the update
+        let item = json!({"feeId": "general", "feeName": details["feeHead"].as_str().unwrap_or("General"), "amount": amount as f64});
+        let posted = self
+            .repos
             .operations
-            .add_fee_history(
-                school_id,
-                student_id,
-                "payment",
-                json!({
-                    "payAmount": amount,
-                    "previousPending": pending,
-                    "newPending": new_pending,
-                    "date": Local::now().to_rfc3339()
-                }),
-            )
+            .post_fee_payments(school_id, student_id, &[item], &details)
             .await?;
-
+        let receipt = posted["lines"][0].clone();
+        fee_record["pendingAmount"] = receipt["newPending"].clone();
+        fee_record["receipt"] = receipt;
         Ok(fee_record)
     }
 
@@ -368,9 +392,19 @@ impl OperationsService for PostgresOperationsService {
         employee_id: &str,
         data: Value,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // Salary fields become a dated revision; the statutory profile is stored as-is
+        if REVISION_KEYS.iter().any(|k| !data[*k].is_null()) {
+            revise_salary(&self.repos, school_id, employee_id, data.clone()).await?;
+        }
+        let mut profile = data;
+        if let Some(obj) = profile.as_object_mut() {
+            for key in REVISION_KEYS {
+                obj.remove(key);
+            }
+        }
         self.repos
             .operations
-            .update_employee_salary_params(school_id, employee_id, data)
+            .update_employee_salary_params(school_id, employee_id, profile)
             .await
     }
 
@@ -386,51 +420,17 @@ impl OperationsService for PostgresOperationsService {
             .await?
             .ok_or("Employee not found")?;
 
-        let base_salary = emp["baseSalary"].as_f64().unwrap_or(0.0);
-        let bonus = emp["bonus"].as_f64().unwrap_or(0.0);
-        let aid = emp["aid"].as_f64().unwrap_or(0.0);
-        
-        // Experience components (configurable, defaulting to flat value if not configured)
-        let experience_years = emp["experienceYears"].as_f64().unwrap_or(0.0);
-        let experience_rate = emp["experienceRate"].as_f64().unwrap_or(0.0);
-        let tenure_months = emp["tenureMonths"].as_f64().unwrap_or(0.0);
-        let tenure_rate = emp["tenureRate"].as_f64().unwrap_or(0.0);
-
-        let exp_component = experience_years * experience_rate;
-        let tenure_component = tenure_months * tenure_rate;
-
-        // Space / Responsibility component
-        let mut spaces_component: f64 = 0.0;
-        let responsibilities = self.repos.responsibility.get_employee_responsibilities(school_id, employee_id).await.unwrap_or_default();
-        for r in responsibilities {
-            spaces_component += r["totalPrice"].as_f64().unwrap_or(0.0);
-        }
-
-        let gross_salary = spaces_component + exp_component + tenure_component + bonus + aid;
-
-        // Deductions
+        // Previous month's absences, same as the payroll run for that month
         let now = Local::now();
-        let (month, year) = if now.month() == 1 { (12, now.year() - 1) } else { (now.month() - 1, now.year()) };
-        let attendance = self.repos.operations.get_attendance(school_id, "employee", employee_id).await.unwrap_or_default();
-        let absent_days = attendance.iter().filter(|a| a["status"] == "absent" && a["month"] == json!(month) && a["year"] == json!(year)).count() as f64;
-        
-        let daily_rate = gross_salary / 30.0;
-        let deductions = absent_days * daily_rate;
-
-        let net_monthly_salary = gross_salary - deductions;
-
-        Ok(json!({
-            "baseSalary": base_salary,
-            "spacesComponent": spaces_component,
-            "experienceComponent": exp_component,
-            "tenureComponent": tenure_component,
-            "bonus": bonus,
-            "aid": aid,
-            "grossSalary": gross_salary,
-            "deductions": deductions,
-            "absentDays": absent_days,
-            "netMonthlySalary": net_monthly_salary.max(0.0)
-        }))
+        let (month, year) = if now.month() == 1 { (12, now.year() - 1) } else { (now.month() as i32 - 1, now.year()) };
+        let unpaid = unpaid_leave_types(&self.repos, school_id).await?;
+        let absences = self.repos.payroll.get_employee_absences(school_id, month, year, &unpaid).await?;
+        let absent_days = absences[employee_id].as_f64().unwrap_or(0.0);
+
+        let mut emp = emp;
+        emp["employeeId"] = json!(employee_id);
+        let period = PayrollMonth::load(&self.repos, school_id, Some(employee_id), month, year).await?;
+        employee_salary(&self.repos, school_id, &emp, absent_days, &period).await
     }
 
     async fn add_bonus(
@@ -439,19 +439,8 @@ impl OperationsService for PostgresOperationsService {
         employee_id: &str,
         data: Value,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
-        let mut emp = self
-            .repos
-            .employee
-            .get_employee(school_id, employee_id)
-            .await?
-            .ok_or("Employee not found")?;
-
-        let current_bonus = emp["bonus"].as_f64().unwrap_or(0.0);
-        let add_amount = data["amount"].as_f64().unwrap_or(0.0);
-        emp["bonus"] = json!(current_bonus + add_amount);
-        
-        self.repos.employee.update_employee(school_id, employee_id, emp).await?;
-        Ok(json!({"newBonus": current_bonus + add_amount}))
+        let bonus = self.raise_salary_field(school_id, employee_id, "bonus", data).await?;
+        Ok(json!({"newBonus": bonus}))
     }
 
     async fn add_aid(
@@ -460,115 +449,8 @@ impl OperationsService for PostgresOperationsService {
         employee_id: &str,
         data: Value,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
-        let mut emp = self
-            .repos
-            .employee
-            .get_employee(school_id, employee_id)
-            .await?
-            .ok_or("Employee not found")?;
-
-        let current_aid = emp["aid"].as_f64().unwrap_or(0.0);
-        let add_amount = data["amount"].as_f64().unwrap_or(0.0);
-        emp["aid"] = json!(current_aid + add_amount);
-        
-        self.repos.employee.update_employee(school_id, employee_id, emp).await?;
-        Ok(json!({"newAid": current_aid + add_amount}))
-    }
-
-    async fn auto_close_month(
-        &self,
-        school_id: &str,
-        employee_id: &str,
-    ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let now = Local::now();
-        let (month, year) = if now.month() == 1 {
-            (12, now.year() - 1)
-        } else {
-            (now.month() - 1, now.year())
-        };
-
-        // 1. Get Employee Data
-        let emp = self
-            .repos
-            .employee
-            .get_employee(school_id, employee_id)
-            .await?
-            .ok_or("Employee not found")?;
-
-        let base_salary = emp["baseSalary"].as_f64().unwrap_or(0.0);
-        let bonus = emp["bonus"].as_f64().unwrap_or(0.0);
-        let increment = emp["incrementPercent"].as_f64().unwrap_or(0.0);
-        let mut advance_balance = emp["advanceBalance"].as_f64().unwrap_or(0.0);
-
-        // 2. Calculate Absent Deduction
-        let per_day = (base_salary + (base_salary * increment / 100.0)) / 30.0;
-        let attendance = self
-            .repos
-            .operations
-            .get_attendance(school_id, "employee", employee_id)
-            .await?;
-        let absent_days = attendance
-            .iter()
-            .filter(|a| a["status"] == "absent" && a["month"] == month && a["year"] == year)
-            .count() as f64;
-
-        let deduction = per_day * absent_days;
-        let total_salary = (base_salary + bonus + (base_salary * increment / 100.0)) - deduction;
-
-        // 3. Adjust Advance
-        let advance_applied = f64::min(advance_balance, total_salary);
-        let due_amount = total_salary - advance_applied;
-        advance_balance -= advance_applied;
-
-        // 4. Status logic
-        let status = if due_amount <= 0.0 {
-            "PAID"
-        } else if due_amount < total_salary {
-            "PARTIALLY_PAID"
-        } else {
-            "DUE"
-        };
-
-        let salary_data = json!({
-            "month": month,
-            "year": year,
-            "baseSalary": base_salary,
-            "totalSalary": total_salary,
-            "dueAmount": due_amount,
-            "advanceAdjusted": advance_applied,
-            "status": status,
-            "absentDays": absent_days
-        });
-
-        // 5. Atomic Update via Repository
-        self.repos
-            .operations
-            .add_payroll_salary(school_id, employee_id, salary_data)
-            .await?;
-        self.repos
-            .employee
-            .update_employee(
-                school_id,
-                employee_id,
-                json!({"advanceBalance": advance_balance}),
-            )
-            .await?;
-
-        self.repos
-            .operations
-            .add_payment_history(
-                school_id,
-                employee_id,
-                "auto_close_month",
-                json!({
-                    "salary": total_salary,
-                    "due": due_amount,
-                    "advanceApplied": advance_applied
-                }),
-            )
-            .await?;
-
-        Ok(())
+        let aid = self.raise_salary_field(school_id, employee_id, "aid", data).await?;
+        Ok(json!({"newAid": aid}))
     }
 
     async fn add_payment(
@@ -580,33 +462,11 @@ impl OperationsService for PostgresOperationsService {
         let p_type = data["type"].as_str().ok_or("Missing payment type")?;
         let amount = data["amount"].as_f64().ok_or("Missing amount")?;
 
-        let mut emp = self
-            .repos
-            .employee
-            .get_employee(school_id, employee_id)
-            .await?
-            .ok_or("Employee not found")?;
-        let mut advance_balance = emp["advanceBalance"].as_f64().unwrap_or(0.0);
-
         if p_type == "advance" {
-            advance_balance += amount;
-            self.repos
-                .employee
-                .update_employee(
-                    school_id,
-                    employee_id,
-                    json!({"advanceBalance": advance_balance}),
-                )
-                .await?;
-            self.repos
-                .operations
-                .add_payment_history(
-                    school_id,
-                    employee_id,
-                    "advance_received",
-                    json!({"amount": amount, "newBalance": advance_balance}),
-                )
-                .await?;
+            // Advances are their own records with an EMI schedule (a single installment by default)
+            let mut details = data.clone();
+            details["amount"] = json!(amount);
+            return new_advance(&self.repos, school_id, employee_id, details).await;
         } else if p_type == "salary" {
             let salary_id = data["salaryId"]
                 .as_str()
@@ -628,6 +488,7 @@ impl OperationsService for PostgresOperationsService {
         school_id: &str,
         data: Value,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        ensure_open_session(&self.repos, school_id, data["sessionId"].as_str()).await?;
         self.repos.operations.add_custom_fee(school_id, data).await
     }
 
@@ -643,6 +504,7 @@ impl OperationsService for PostgresOperationsService {
         school_id: &str,
         fee_id: &str,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        ensure_open_fee(&self.repos, school_id, fee_id).await?;
         self.repos.operations.delete_custom_fee(school_id, fee_id).await
     }
 
@@ -651,6 +513,7 @@ impl OperationsService for PostgresOperationsService {
         school_id: &str,
         fee_id: &str,
     ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        ensure_open_fee(&self.repos, school_id, fee_id).await?;
         self.repos.operations.apply_custom_fee(school_id, fee_id).await
     }
 
@@ -676,15 +539,123 @@ impl OperationsService for PostgresOperationsService {
     async fn toggle_block_coupon(&self, school_id: &str, coupon_id: &str, blocked: bool) -> Result<(), Box<dyn Error + Send + Sync>> {
         self.repos.operations.block_coupon(school_id, coupon_id, blocked).await
     }
-    async fn validate_coupon(&self, school_id: &str, coupon_name: &str) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
-        self.repos.operations.validate_coupon(school_id, coupon_name).await
+    async fn update_coupon(&self, school_id: &str, coupon_id: &str, data: Value) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        self.repos.operations.update_coupon(school_id, coupon_id, data).await
     }
-    async fn use_coupon(&self, school_id: &str, coupon_id: &str, student_id: &str, discount: f64) -> Result<Value, Box<dyn Error + Send + Sync>> {
-        self.repos.operations.use_coupon(school_id, coupon_id, student_id, discount).await
+    async fn validate_coupon(&self, school_id: &str, coupon_name: &str, context: Value) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let coupon = match self.repos.operations.get_coupon_by_name(school_id, coupon_name).await? {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let student_uses = match context["studentId"].as_str() {
+            Some(sid) => self.repos.operations.count_student_coupon_uses(school_id, coupon["couponId"].as_str().unwrap_or(""), sid).await?,
+            None => 0,
+        };
+        let fee_amount = context["feeAmount"].as_f64();
+        Ok(Some(match check_coupon(&coupon, student_uses, fee_amount) {
+            Ok(discount) => json!({
+                "valid": true,
+                "couponId": coupon["couponId"],
+                "couponName": coupon["couponName"],
+                "discountType": coupon["discountType"],
+                "discountValue": coupon["discountValue"],
+                "discountAmount": discount,
+                "assignedEmployeeId": coupon["assignedEmployeeId"],
+                "employeeReward": coupon["employeeReward"],
+                "referrerType": coupon["referrerType"],
+                "referrerName": coupon["referrerName"],
+                "validUntil": coupon["validUntil"]
+            }),
+            Err(reason) => json!({"valid": false, "reason": reason}),
+        }))
+    }
+    async fn use_coupon(&self, school_id: &str, coupon_id: &str, student_id: &str, discount: f64, details: Value) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let coupon = self.repos.operations.get_coupon(school_id, coupon_id).await?.ok_or("Coupon not found")?;
+        let student_uses = self.repos.operations.count_student_coupon_uses(school_id, coupon_id, student_id).await?;
+        let fee_amount = details["feeAmount"].as_f64();
+        let allowed = check_coupon(&coupon, student_uses, fee_amount)?;
+        // The caller's discount is capped by the rules; it is only trusted when nothing bounds it
+        let discount = match allowed {
+            Some(max) if discount <= 0.0 || discount > max => max,
+            _ => discount,
+        };
+        self.repos.operations.use_coupon(school_id, coupon_id, student_id, discount, details).await
     }
 }
 
 impl PostgresOperationsService {
+    /// Adds `data.amount` to a monthly salary component through a new revision, effective
+    /// from `data.effectiveFrom` (default: this month). Returns the new value.
+    async fn raise_salary_field(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        key: &str,
+        data: Value,
+    ) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        let emp = self
+            .repos
+            .employee
+            .get_employee(school_id, employee_id)
+            .await?
+            .ok_or("Employee not found")?;
+        let add_amount = data["amount"].as_f64().unwrap_or(0.0);
+        let now = Local::now();
+        let (month, year) = match data["effectiveFrom"].as_str().and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()) {
+            Some(d) => (d.month() as i32, d.year()),
+            None => (now.month() as i32, now.year()),
+        };
+        let revisions = self.repos.payroll.get_salary_revisions(school_id, employee_id).await?;
+        let current = apply_revision(&emp, revision_in_force(&revisions, month, year))[key].as_f64().unwrap_or(0.0);
+        let mut revision = json!({
+            "effectiveMonth": month,
+            "effectiveYear": year,
+            "reason": data["reason"].as_str().unwrap_or(if key == "bonus" { "Bonus added" } else { "Aid added" }),
+            "createdBy": data["createdBy"]
+        });
+        revision[key] = json!(current + add_amount);
+        revise_salary(&self.repos, school_id, employee_id, revision).await?;
+        Ok(current + add_amount)
+    }
+
+    /// The saved record for the day, refusing when it is a past day whose correction window
+    /// has closed; those change only through an approved correction request.
+    async fn unlocked_record(
+        &self,
+        school_id: &str,
+        role: &str,
+        user_id: &str,
+        date: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        let Ok(day) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else { return Ok(None) };
+        let existing = self
+            .repos
+            .attendance
+            .get_attendance_for_users(school_id, role, day, &[user_id.to_string()])
+            .await?
+            .into_iter()
+            .next();
+        if let Some(record) = &existing {
+            let settings = attendance_settings(&self.repos, school_id).await?;
+            if record_locked(&settings, record, day) {
+                return Err(format!("Attendance for {} is locked; raise a correction request to change it", date).into());
+            }
+        }
+        Ok(existing)
+    }
+
+    /// Attendance in an archived session is read-only, and employee attendance feeds payroll, so it
+    /// cannot change once that month's run is approved.
+    async fn ensure_attendance_open(
+        &self,
+        school_id: &str,
+        role: &str,
+        date: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let d = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| "date must be YYYY-MM-DD")?;
+        ensure_attendance_open(&self.repos, school_id, role, d).await
+    }
+
     fn calculate_duration(&self, in_time: &str, out_time: &str) -> String {
         match (
             chrono::DateTime::parse_from_rfc3339(in_time),
@@ -700,3 +671,62 @@ impl PostgresOperationsService {
         }
     }
 }
+
+/// Applies a coupon's validity window, usage caps, budget and minimum-fee rules.
+/// Returns the most discount allowed, on `fee_amount` when given (`None` for a percentage coupon
+/// with no fee and no cap), or the reason the coupon cannot be used.
+fn check_coupon(coupon: &Value, student_uses: i64, fee_amount: Option<f64>) -> Result<Option<f64>, String> {
+    let today = chrono::Local::now().date_naive().to_string();
+    let num = |k: &str| coupon[k].as_f64().or_else(|| coupon[k].as_str().and_then(|s| s.parse().ok())).unwrap_or(0.0);
+
+    if coupon["status"] != "active" {
+        return Err("Coupon is blocked".into());
+    }
+    if let Some(from) = coupon["validFrom"].as_str() {
+        if today.as_str() < from {
+            return Err(format!("Coupon is valid from {}", from));
+        }
+    }
+    if let Some(until) = coupon["validUntil"].as_str() {
+        if today.as_str() > until {
+            return Err(format!("Coupon expired on {}", until));
+        }
+    }
+    let max_uses = coupon["maxUses"].as_i64().unwrap_or(0);
+    if max_uses > 0 && coupon["currentUses"].as_i64().unwrap_or(0) >= max_uses {
+        return Err("Coupon usage limit reached".into());
+    }
+    let per_student = coupon["perStudentLimit"].as_i64().unwrap_or(0);
+    if per_student > 0 && student_uses >= per_student {
+        return Err("Student has already used this coupon".into());
+    }
+    let budget = num("budget");
+    let remaining_budget = budget - num("discountGiven");
+    if budget > 0.0 && remaining_budget <= 0.0 {
+        return Err("Coupon budget exhausted".into());
+    }
+
+    if let Some(fee) = fee_amount {
+        let min_fee = num("minFeeAmount");
+        if fee < min_fee {
+            return Err(format!("Coupon needs a minimum fee of {:.2}", min_fee));
+        }
+    }
+    // A percentage needs the fee; without it only the caps below bound the discount
+    let mut discount = match (coupon["discountType"] == "percentage", fee_amount) {
+        (true, Some(fee)) => Some(fee * num("discountValue") / 100.0),
+        (true, None) => None,
+        (false, _) => Some(num("discountValue")),
+    };
+    let max_discount = num("maxDiscount");
+    if max_discount > 0.0 {
+        discount = Some(discount.map_or(max_discount, |d| d.min(max_discount)));
+    }
+    if budget > 0.0 {
+        discount = Some(discount.map_or(remaining_budget, |d| d.min(remaining_budget)));
+    }
+    if let Some(fee) = fee_amount {
+        discount = discount.map(|d| d.min(fee));
+    }
+    Ok(discount.map(|d| (d * 100.0).round() / 100.0))
+}
@@ -0,0 +1,458 @@
+use crate::repository::Repositories;
+use crate::services::traits::*;
+use async_trait::async_trait;
+use chrono::Local;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::error::Error;
+use std::sync::Arc;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A payment reported by a gateway callback after its signature has been checked.
+pub struct VerifiedPayment {
+    pub gateway_order_id: String,
+    pub payment_id: String,
+    pub captured: bool,
+    pub amount_paise: i64,
+}
+
+/// Online payment provider (Razorpay, PayU, ...). Amounts are exchanged in paise.
+#[async_trait]
+pub trait PaymentGateway: Send + Sync {
+    fn name(&self) -> &str;
+    /// Registers the order with the provider. Returns `gatewayOrderId` and the `checkout`
+    /// parameters the app needs to open the provider's payment page.
+    async fn create_order(
+        &self,
+        order_id: &str,
+        amount_paise: i64,
+        currency: &str,
+    ) -> Result<Value, AppError>;
+    fn verify_callback(
+        &self,
+        payload: &Value,
+        signature: Option<&str>,
+    ) -> Result<VerifiedPayment, AppError>;
+    /// A signed callback for `gateway_order_id`, as if the payer had finished checkout. Only
+    /// test gateways can do this.
+    fn simulate_callback(&self, _gateway_order_id: &str, _amount_paise: i64, _success: bool) -> Option<Value> {
+        None
+    }
+}
+
+/// Local stand-in for a real provider. Callbacks are signed with HMAC-SHA256 over
+/// `gatewayOrderId|paymentId|status|amount` using `MOCK_GATEWAY_SECRET`.
+pub struct MockPaymentGateway {
+    pub secret: String,
+}
+
+impl MockPaymentGateway {
+    fn sign(&self, gateway_order_id: &str, payment_id: &str, status: &str, amount_paise: i64) -> String {
+        let mut mac = HmacSha256::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(format!("{}|{}|{}|{}", gateway_order_id, payment_id, status, amount_paise).as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+#[async_trait]
+impl PaymentGateway for MockPaymentGateway {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    async fn create_order(
+        &self,
+        order_id: &str,
+        amount_paise: i64,
+        currency: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let gateway_order_id = format!("mock_order_{}", uuid::Uuid::new_v4().simple());
+        Ok(json!({
+            "gatewayOrderId": gateway_order_id,
+            "checkout": {
+                "gateway": "mock",
+                "orderId": order_id,
+                "gatewayOrderId": gateway_order_id,
+                "amount": amount_paise,
+                "currency": currency,
+                "completeUrl": format!("/api/payments/mock/checkout/{}", order_id)
+            }
+        }))
+    }
+
+    fn verify_callback(
+        &self,
+        payload: &Value,
+        signature: Option<&str>,
+    ) -> Result<VerifiedPayment, Box<dyn Error + Send + Sync>> {
+        let gateway_order_id = payload["gatewayOrderId"].as_str().ok_or("gatewayOrderId missing")?;
+        let payment_id = payload["paymentId"].as_str().ok_or("paymentId missing")?;
+        let status = payload["status"].as_str().ok_or("status missing")?;
+        let amount_paise = payload["amount"].as_i64().ok_or("amount missing")?;
+        let signature = signature
+            .or_else(|| payload["signature"].as_str())
+            .ok_or("Signature missing")?;
+
+        let mut mac = HmacSha256::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(format!("{}|{}|{}|{}", gateway_order_id, payment_id, status, amount_paise).as_bytes());
+        let provided = hex::decode(signature).map_err(|_| "Invalid signature")?;
+        mac.verify_slice(&provided).map_err(|_| "Invalid signature")?;
+
+        Ok(VerifiedPayment {
+            gateway_order_id: gateway_order_id.to_string(),
+            payment_id: payment_id.to_string(),
+            captured: status == "captured",
+            amount_paise,
+        })
+    }
+
+    fn simulate_callback(&self, gateway_order_id: &str, amount_paise: i64, success: bool) -> Option<Value> {
+        let payment_id = format!("mockpay_{}", uuid::Uuid::new_v4().simple());
+        let status = if success { "captured" } else { "failed" };
+        Some(json!({
+            "gatewayOrderId": gateway_order_id,
+            "paymentId": payment_id,
+            "status": status,
+            "amount": amount_paise,
+            "signature": self.sign(gateway_order_id, &payment_id, status, amount_paise)
+        }))
+    }
+}
+
+/// Whether `PAYMENT_GATEWAY=mock` was set; the mock checkout route is mounted only then.
+pub fn mock_gateway_enabled() -> bool {
+    std::env::var("PAYMENT_GATEWAY").is_ok_and(|name| name == "mock")
+}
+
+/// Picks the gateway from `PAYMENT_GATEWAY`. Only the mock gateway ships today, and only when
+/// asked for by name; without a gateway, online payments are switched off.
+///
+/// Panics when the mock gateway is chosen without `MOCK_GATEWAY_SECRET`, so the server
+/// doesn't start with a guessable signing key.
+pub fn payment_gateway_from_env() -> Option<Arc<dyn PaymentGateway>> {
+    match std::env::var("PAYMENT_GATEWAY") {
+        Ok(name) if name == "mock" => {
+            let secret = std::env::var("MOCK_GATEWAY_SECRET")
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .expect("PAYMENT_GATEWAY=mock needs MOCK_GATEWAY_SECRET");
+            Some(Arc::new(MockPaymentGateway { secret }))
+        }
+        Ok(name) => {
+            tracing::warn!("Payment gateway '{}' is not available; online payments are off", name);
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+fn to_paise(amount: f64) -> i64 {
+    (amount * 100.0).round() as i64
+}
+
+pub struct PostgresPaymentService {
+    pub repos: Arc<Repositories>,
+    pub gateway: Option<Arc<dyn PaymentGateway>>,
+}
+
+/// Posts fee installments (`feeId` "general" or a custom fee ID, with `amount`) to a student's
+/// record and the collection day-book in one transaction, so a failure part-way leaves nothing
+/// booked. `details` supplies the base `receiptNo` (a new one is issued when absent),
+/// `paymentMode`, `collectedBy`, `reference` and `paidOn`. Returns `receiptNo` and the `lines`.
+pub(crate) async fn post_fee_items(
+    repos: &Repositories,
+    school_id: &str,
+    student_id: &str,
+    items: &[Value],
+    details: &Value,
+) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    // The general fee is kept in whole rupees
+    let items: Vec<Value> = items
+        .iter()
+        .map(|item| {
+            let mut item = item.clone();
+            if item["feeId"].as_str().unwrap_or("general") == "general" {
+                item["amount"] = json!(item["amount"].as_f64().unwrap_or(0.0).round());
+            }
+            item
+        })
+        .collect();
+    repos
+        .operations
+        .post_fee_payments(school_id, student_id, &items, details)
+        .await
+}
+
+impl PostgresPaymentService {
+    fn gateway(&self) -> Result<&dyn PaymentGateway, Box<dyn Error + Send + Sync>> {
+        self.gateway.as_deref().ok_or_else(|| "Online payments are not configured".into())
+    }
+
+    /// Posts every installment of a claimed order and returns the combined receipt.
+    async fn post_order(
+        &self,
+        order: &Value,
+        payment_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let school_id = order["schoolId"].as_str().unwrap_or("");
+        let student_id = order["studentId"].as_str().unwrap_or("");
+        let paid_on = Local::now().format("%Y-%m-%d").to_string();
+        let items = order["items"].as_array().cloned().unwrap_or_default();
+
+        let posted = post_fee_items(
+            &self.repos,
+            school_id,
+            student_id,
+            &items,
+            &json!({
+                "paymentMode": "online",
+                "collectedBy": format!("online:{}", self.gateway()?.name()),
+                "reference": payment_id,
+                "paidOn": paid_on
+            }),
+        )
+        .await?;
+
+        Ok(json!({
+            "receiptNo": posted["receiptNo"],
+            "orderId": order["orderId"],
+            "paymentId": payment_id,
+            "studentId": student_id,
+            "amount": order["amount"],
+            "paymentMode": "online",
+            "paidOn": paid_on,
+            "lines": posted["lines"]
+        }))
+    }
+
+    /// Posts an order this caller has claimed and records the outcome. A posting failure leaves
+    /// the order `posting_failed` for staff to retry or close.
+    async fn settle_claimed(&self, order: &Value, payment_id: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let order_id = order["orderId"].as_str().unwrap_or("");
+        match self.post_order(order, payment_id).await {
+            Ok(receipt) => {
+                self.repos
+                    .payment
+                    .complete_order(order_id, "paid", Some(receipt), None)
+                    .await
+            }
+            Err(e) => {
+                tracing::error!("Posting online payment {} failed: {}", order_id, e);
+                self.repos
+                    .payment
+                    .complete_order(order_id, "posting_failed", None, Some(e.to_string()))
+                    .await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl PaymentService for PostgresPaymentService {
+    async fn payable_items(
+        &self,
+        school_id: &str,
+        student_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut items = Vec::new();
+        if let Some(fee) = self.repos.operations.get_student_fee(school_id, student_id).await? {
+            let pending = fee["pendingAmount"].as_f64().unwrap_or(0.0).floor();
+            if pending > 0.0 {
+                items.push(json!({"feeId": "general", "feeName": "General", "due": pending, "dueDate": null}));
+            }
+        }
+        for f in self.repos.operations.get_student_custom_fees(school_id, student_id).await? {
+            let due = f["totalDue"].as_f64().unwrap_or(0.0);
+            if due > 0.0 && f["status"] != "waived" {
+                items.push(json!({
+                    "feeId": f["feeId"],
+                    "feeName": f["feeName"],
+                    "due": (due * 100.0).round() / 100.0,
+                    "dueDate": f["dueDate"]
+                }));
+            }
+        }
+        let total: f64 = items.iter().map(|i| i["due"].as_f64().unwrap_or(0.0)).sum();
+        Ok(json!({"studentId": student_id, "items": items, "total": total}))
+    }
+
+    async fn create_order(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let payable = self.payable_items(school_id, student_id).await?;
+        let payable = payable["items"].as_array().cloned().unwrap_or_default();
+        let requested = data["items"].as_array().cloned().unwrap_or_default();
+        if requested.is_empty() {
+            return Err("Select at least one installment to pay".into());
+        }
+
+        let mut items = Vec::new();
+        for req in &requested {
+            let fee_id = req["feeId"].as_str().ok_or("feeId is required for every item")?;
+            let due_item = payable
+                .iter()
+                .find(|p| p["feeId"] == fee_id)
+                .ok_or_else(|| format!("Nothing is due for fee '{}'", fee_id))?;
+            let due = due_item["due"].as_f64().unwrap_or(0.0);
+            let amount = req["amount"].as_f64().unwrap_or(due);
+            if amount <= 0.0 || amount > due + 0.001 {
+                return Err(format!("Amount for '{}' must be between 0 and {:.2}", fee_id, due).into());
+            }
+            if fee_id == "general" && amount.fract() != 0.0 {
+                return Err("General fee payments must be in whole rupees".into());
+            }
+            if items.iter().any(|i: &Value| i["feeId"] == fee_id) {
+                return Err(format!("Fee '{}' is selected more than once", fee_id).into());
+            }
+            items.push(json!({"feeId": fee_id, "feeName": due_item["feeName"], "amount": amount}));
+        }
+
+        let amount: f64 = items.iter().map(|i| i["amount"].as_f64().unwrap_or(0.0)).sum();
+        let order_id = self.repos.payment.next_order_id().await?;
+        let currency = "INR";
+        let created = self
+            .gateway()?
+            .create_order(&order_id, to_paise(amount), currency)
+            .await?;
+
+        let order = json!({
+            "orderId": order_id,
+            "studentId": student_id,
+            "amount": amount,
+            "currency": currency,
+            "gateway": self.gateway()?.name(),
+            "gatewayOrderId": created["gatewayOrderId"],
+            "items": items,
+            "status": "created"
+        });
+        let mut order = self.repos.payment.create_order(school_id, order).await?;
+        order["checkout"] = created["checkout"].clone();
+        Ok(order)
+    }
+
+    async fn list_orders(
+        &self,
+        school_id: &str,
+        student_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos.payment.get_student_orders(school_id, student_id).await
+    }
+
+    async fn get_order(
+        &self,
+        order_id: &str,
+    ) -> Result<Option<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos.payment.get_order(order_id).await
+    }
+
+    async fn handle_callback(
+        &self,
+        gateway: &str,
+        payload: Value,
+        signature: Option<String>,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let gateway_impl = self.gateway()?;
+        if gateway != gateway_impl.name() {
+            return Err(format!("Unknown payment gateway '{}'", gateway).into());
+        }
+        let payment = gateway_impl.verify_callback(&payload, signature.as_deref())?;
+        let order = self
+            .repos
+            .payment
+            .get_order_by_gateway_ref(gateway, &payment.gateway_order_id)
+            .await?
+            .ok_or("Payment order not found")?;
+        let order_id = order["orderId"].as_str().unwrap_or("").to_string();
+
+        // Only a callback that claims a fresh order posts anything; repeats see the stored result
+        if order["status"] != "created" {
+            return Ok(json!({"duplicate": true, "order": order}));
+        }
+        if !payment.captured {
+            if self.repos.payment.claim_order(&order_id, &payment.payment_id).await? {
+                self.repos
+                    .payment
+                    .complete_order(&order_id, "failed", None, Some("Payment not captured".to_string()))
+                    .await?;
+            }
+            return Ok(json!({"duplicate": false, "order": self.repos.payment.get_order(&order_id).await?}));
+        }
+        if payment.amount_paise != to_paise(order["amount"].as_f64().unwrap_or(0.0)) {
+            return Err("Paid amount does not match the order".into());
+        }
+        if !self.repos.payment.claim_order(&order_id, &payment.payment_id).await? {
+            return Ok(json!({"duplicate": true, "order": self.repos.payment.get_order(&order_id).await?}));
+        }
+
+        self.settle_claimed(&order, &payment.payment_id).await?;
+        Ok(json!({"duplicate": false, "order": self.repos.payment.get_order(&order_id).await?}))
+    }
+
+    async fn mock_checkout(
+        &self,
+        order_id: &str,
+        success: bool,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let gateway = self.gateway()?;
+        let order = self
+            .repos
+            .payment
+            .get_order(order_id)
+            .await?
+            .filter(|o| o["gateway"] == gateway.name())
+            .ok_or("Mock order not found")?;
+        let callback = gateway
+            .simulate_callback(
+                order["gatewayOrderId"].as_str().unwrap_or(""),
+                to_paise(order["amount"].as_f64().unwrap_or(0.0)),
+                success,
+            )
+            .ok_or("The configured gateway has no test checkout")?;
+        self.handle_callback(gateway.name(), callback, None).await
+    }
+
+    async fn failed_orders(&self, school_id: &str) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos.payment.get_failed_orders(school_id).await
+    }
+
+    async fn resolve_order(
+        &self,
+        school_id: &str,
+        order_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let order = self
+            .repos
+            .payment
+            .get_order(order_id)
+            .await?
+            .filter(|o| o["schoolId"] == school_id)
+            .ok_or("Payment order not found")?;
+        if order["status"] != "posting_failed" {
+            return Err(format!("Order is {}; only orders whose posting failed can be resolved", order["status"].as_str().unwrap_or("")).into());
+        }
+        let (status, note) = match data["action"].as_str().unwrap_or("retry") {
+            "retry" => ("processing", None),
+            "close" => {
+                let note = data["note"].as_str().filter(|n| !n.trim().is_empty()).ok_or("A note is required to close an order")?;
+                ("closed", Some(format!("Closed by {}: {}", data["resolvedBy"].as_str().unwrap_or("staff"), note)))
+            }
+            other => return Err(format!("Unknown action '{}'", other).into()),
+        };
+        if !self.repos.payment.resolve_failed_order(order_id, status, note).await? {
+            return Err("Order changed while resolving; reload and try again".into());
+        }
+        if status == "processing" {
+            self.settle_claimed(&order, order["gatewayPaymentId"].as_str().unwrap_or("")).await?;
+        }
+        self.repos.payment.get_order(order_id).await?.ok_or_else(|| "Payment order not found".into())
+    }
+}
@@ -0,0 +1,1453 @@
+use crate::logic::advances::{emi, installment_due, project_schedule};
+use crate::logic::export::to_csv;
+use crate::logic::payroll::{
+    apply_revision, compute_salary, monthly_gross, revision_in_force, SalaryInputs, PAYROLL_DAYS, REVISION_KEYS,
+};
+use crate::logic::payslip::build_payslip;
+use crate::logic::salary_transfer::{
+    build_transfer_file, parse_transfer_response, transfer_mode, valid_account_number, valid_ifsc, BANK_FORMATS,
+};
+use crate::logic::statutory::{annual_tax, fy_start_year, merge_settings, taxable_income};
+use crate::repository::Repositories;
+use crate::services::holiday_service::load_calendar;
+use crate::services::leave_service::unpaid_leave_types;
+use crate::services::traits::*;
+use crate::AppState;
+use async_trait::async_trait;
+use chrono::Datelike;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+pub struct PostgresPayrollService {
+    pub repos: Arc<Repositories>,
+}
+
+/// Rejects changes that would alter an approved (locked) payroll month.
+pub(crate) async fn ensure_payroll_open(
+    repos: &Repositories,
+    school_id: &str,
+    month: i32,
+    year: i32,
+) -> Result<(), AppError> {
+    if repos.payroll.is_month_locked(school_id, month, year).await? {
+        return Err(format!(
+            "Payroll for {:02}/{} is approved and locked; reopen the run before making changes",
+            month, year
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// The school's statutory settings with defaults filled in.
+pub(crate) async fn statutory_settings(repos: &Repositories, school_id: &str) -> Result<Value, AppError> {
+    Ok(merge_settings(&repos.payroll.get_statutory_settings(school_id).await?))
+}
+
+/// Per-employee totals of approved months earlier in the same financial year, keyed by
+/// employee ID: `{gross, pfEmployee, professionalTax, tds}`.
+pub(crate) async fn statutory_ytd(
+    repos: &Repositories,
+    school_id: &str,
+    employee_id: Option<&str>,
+    month: i32,
+    year: i32,
+) -> Result<Value, AppError> {
+    let fy_start = fy_start_year(month as u32, year) * 12 + 4;
+    let lines = repos
+        .payroll
+        .get_approved_lines_between(school_id, employee_id, fy_start, year * 12 + month - 1)
+        .await?;
+    let mut out = serde_json::Map::new();
+    for l in &lines {
+        let b = &l["breakdown"];
+        let entry = out
+            .entry(l["employeeId"].as_str().unwrap_or("").to_string())
+            .or_insert_with(|| json!({"gross": 0.0, "pfEmployee": 0.0, "professionalTax": 0.0, "tds": 0.0}));
+        // Runs approved before statutory deductions existed have no earnedGross
+        let earned = b["earnedGross"].as_f64().unwrap_or_else(|| {
+            b["grossSalary"].as_f64().unwrap_or(0.0) - b["absenceDeduction"].as_f64().unwrap_or(0.0)
+        });
+        entry["gross"] = json!(entry["gross"].as_f64().unwrap_or(0.0) + earned);
+        for k in ["pfEmployee", "professionalTax", "tds"] {
+            entry[k] = json!(entry[k].as_f64().unwrap_or(0.0) + b["statutory"][k].as_f64().unwrap_or(0.0));
+        }
+    }
+    Ok(Value::Object(out))
+}
+
+/// Month-wide inputs shared by every employee in a payroll computation.
+pub(crate) struct PayrollMonth {
+    pub month: i32,
+    pub year: i32,
+    pub statutory: Value,
+    /// Year-to-date statutory totals keyed by employee ID.
+    pub ytd: Value,
+}
+
+impl PayrollMonth {
+    /// Loads the settings and YTD totals, for one employee or (with `None`) the whole school.
+    pub(crate) async fn load(
+        repos: &Repositories,
+        school_id: &str,
+        employee_id: Option<&str>,
+        month: i32,
+        year: i32,
+    ) -> Result<Self, AppError> {
+        Ok(PayrollMonth {
+            month,
+            year,
+            statutory: statutory_settings(repos, school_id).await?,
+            ytd: statutory_ytd(repos, school_id, employee_id, month, year).await?,
+        })
+    }
+}
+
+/// Salary breakdown for one employee using the shared payroll formula.
+pub(crate) async fn employee_salary(
+    repos: &Repositories,
+    school_id: &str,
+    employee: &Value,
+    absent_days: f64,
+    period: &PayrollMonth,
+) -> Result<Value, AppError> {
+    let employee_id = employee["employeeId"].as_str().unwrap_or("");
+    let responsibilities_total: f64 = repos
+        .responsibility
+        .get_employee_responsibilities(school_id, employee_id)
+        .await
+        .unwrap_or_default()
+        .iter()
+        .map(|r| r["totalPrice"].as_f64().unwrap_or(0.0))
+        .sum();
+    let advances = repos
+        .payroll
+        .get_advances(school_id, Some(employee_id), Some("active"))
+        .await?
+        .iter()
+        .filter_map(|a| {
+            installment_due(a, period.month, period.year)
+                .map(|(due, interest)| json!({"advanceId": a["advanceId"], "due": due, "interest": interest}))
+        })
+        .collect();
+    let revisions = repos.payroll.get_salary_revisions(school_id, employee_id).await?;
+    let arrears = salary_arrears(repos, school_id, employee, &revisions, period.month, period.year).await?;
+    let in_force = apply_revision(employee, revision_in_force(&revisions, period.month, period.year));
+    Ok(compute_salary(
+        &in_force,
+        &SalaryInputs {
+            responsibilities_total,
+            absent_days,
+            month: period.month as u32,
+            statutory: period.statutory.clone(),
+            ytd: period.ytd[employee_id].clone(),
+            advances,
+            arrears,
+        },
+    ))
+}
+
+/// How far back approved months are checked for arrears from backdated revisions.
+const ARREARS_LOOKBACK_MONTHS: i32 = 24;
+
+/// Arrears owed for approved months before `month`/`year`: what the revision now in force for
+/// each month would have paid, less what was paid then and any arrears already settled.
+/// Months with no revision in force are left alone.
+pub(crate) async fn salary_arrears(
+    repos: &Repositories,
+    school_id: &str,
+    employee: &Value,
+    revisions: &[Value],
+    month: i32,
+    year: i32,
+) -> Result<Vec<Value>, AppError> {
+    if revisions.is_empty() {
+        return Ok(Vec::new());
+    }
+    let key = year * 12 + month;
+    let employee_id = employee["employeeId"].as_str().unwrap_or("");
+    let lines = repos
+        .payroll
+        .get_approved_lines_between(school_id, Some(employee_id), key - ARREARS_LOOKBACK_MONTHS, key - 1)
+        .await?;
+    let f = |v: &Value| v.as_f64().unwrap_or(0.0);
+
+    let mut arrears = Vec::new();
+    for line in &lines {
+        let (m, y) = (line["month"].as_i64().unwrap_or(0) as i32, line["year"].as_i64().unwrap_or(0) as i32);
+        let revision = match revision_in_force(revisions, m, y) {
+            Some(r) => r,
+            None => continue,
+        };
+        let b = &line["breakdown"];
+        let should = monthly_gross(&apply_revision(employee, Some(revision)), f(&b["spacesComponent"]));
+        let paid = f(&b["grossSalary"]) - f(&b["arrearsTotal"]);
+        let share = (1.0 - f(&b["absentDays"]) / PAYROLL_DAYS).max(0.0);
+        let settled: f64 = lines
+            .iter()
+            .flat_map(|l| l["breakdown"]["arrears"].as_array().cloned().unwrap_or_default())
+            .filter(|a| a["month"] == m && a["year"] == y)
+            .map(|a| f(&a["amount"]))
+            .sum();
+        let due = ((should - paid) * share - settled) * 100.0;
+        if due.abs() >= 1.0 {
+            arrears.push(json!({"month": m, "year": y, "amount": due.round() / 100.0}));
+        }
+    }
+    Ok(arrears)
+}
+
+/// Records a dated salary revision. Fields not given are carried over from the revision in
+/// force at the effective month (or the employee record), so each revision is a full snapshot.
+/// Returns the revision with the arrears it would add to the next run.
+pub(crate) async fn revise_salary(
+    repos: &Repositories,
+    school_id: &str,
+    employee_id: &str,
+    data: Value,
+) -> Result<Value, AppError> {
+    let mut employee = repos
+        .employee
+        .get_employee(school_id, employee_id)
+        .await?
+        .ok_or("Employee not found")?;
+    employee["employeeId"] = json!(employee_id);
+
+    let now = chrono::Local::now();
+    let (month, year) = match data["effectiveFrom"].as_str() {
+        Some(d) => {
+            let d = chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| "effectiveFrom must be YYYY-MM-DD")?;
+            (d.month() as i32, d.year())
+        }
+        None => (
+            data["effectiveMonth"].as_i64().or(data["month"].as_i64()).map(|m| m as i32).unwrap_or(now.month() as i32),
+            data["effectiveYear"].as_i64().or(data["year"].as_i64()).map(|y| y as i32).unwrap_or(now.year()),
+        ),
+    };
+    if !(1..=12).contains(&month) {
+        return Err("effective month must be between 1 and 12".into());
+    }
+
+    let revisions = repos.payroll.get_salary_revisions(school_id, employee_id).await?;
+    let current = apply_revision(&employee, revision_in_force(&revisions, month, year));
+    let mut params = serde_json::Map::new();
+    for key in REVISION_KEYS {
+        if !current[key].is_null() {
+            params.insert(key.to_string(), current[key].clone());
+        }
+    }
+    let mut changed = false;
+    for key in REVISION_KEYS {
+        let v = &data[key];
+        let valid = if key == "allowances" { v.is_object() } else { v.as_f64().is_some_and(|n| n >= 0.0) };
+        if valid {
+            params.insert(key.to_string(), v.clone());
+            changed = true;
+        }
+    }
+    if !changed {
+        return Err(format!("Give at least one of {} to revise", REVISION_KEYS.join(", ")).into());
+    }
+
+    let mut revision = repos
+        .payroll
+        .create_salary_revision(
+            school_id,
+            json!({
+                "employeeId": employee_id,
+                "effectiveMonth": month,
+                "effectiveYear": year,
+                "params": Value::Object(params.clone()),
+                "reason": data["reason"],
+                "createdBy": data["createdBy"]
+            }),
+        )
+        .await?;
+
+    // Keep the record's salary fields in step when this is the revision in force today
+    let revisions = repos.payroll.get_salary_revisions(school_id, employee_id).await?;
+    let today = revision_in_force(&revisions, now.month() as i32, now.year());
+    if today.is_some_and(|r| r["revisionId"] == revision["revisionId"]) {
+        repos
+            .operations
+            .update_employee_salary_params(school_id, employee_id, Value::Object(params))
+            .await?;
+    }
+
+    let next = if now.month() == 12 { (1, now.year() + 1) } else { (now.month() as i32 + 1, now.year()) };
+    revision["arrearsPreview"] = json!(salary_arrears(repos, school_id, &employee, &revisions, next.0, next.1).await?);
+    Ok(revision)
+}
+
+/// Records a new advance or loan with its EMI. Used by the advances API and by
+/// `add_payment` with `type: "advance"`.
+pub(crate) async fn new_advance(
+    repos: &Repositories,
+    school_id: &str,
+    employee_id: &str,
+    data: Value,
+) -> Result<Value, AppError> {
+    repos
+        .employee
+        .get_employee(school_id, employee_id)
+        .await?
+        .ok_or("Employee not found")?;
+    let principal = data["amount"].as_f64().filter(|a| *a > 0.0).ok_or("amount must be greater than zero")?;
+    let interest_rate = data["interestRate"].as_f64().unwrap_or(0.0);
+    if interest_rate < 0.0 {
+        return Err("interestRate cannot be negative".into());
+    }
+    let tenure = data["tenureMonths"].as_i64().unwrap_or(1);
+    if !(1..=120).contains(&tenure) {
+        return Err("tenureMonths must be between 1 and 120".into());
+    }
+    let emi_amount = data["emiAmount"]
+        .as_f64()
+        .filter(|e| *e > 0.0)
+        .unwrap_or_else(|| emi(principal, interest_rate, tenure as u32));
+    let monthly_cap = data["monthlyCap"].as_f64().filter(|c| *c > 0.0);
+    if monthly_cap.is_some_and(|c| c <= principal * interest_rate / 1200.0) {
+        return Err("monthlyCap must be more than the monthly interest or the advance is never repaid".into());
+    }
+    let now = chrono::Local::now();
+    let start_month = data["startMonth"].as_i64().unwrap_or(now.month() as i64);
+    let start_year = data["startYear"].as_i64().unwrap_or(now.year() as i64);
+    if !(1..=12).contains(&start_month) {
+        return Err("startMonth must be between 1 and 12".into());
+    }
+    let kind = match data["kind"].as_str() {
+        Some("loan") => "loan",
+        _ => "advance",
+    };
+
+    let advance = repos
+        .payroll
+        .create_advance(
+            school_id,
+            json!({
+                "employeeId": employee_id,
+                "kind": kind,
+                "principal": principal,
+                "interestRate": interest_rate,
+                "tenureMonths": tenure,
+                "emiAmount": emi_amount,
+                "monthlyCap": monthly_cap,
+                "startMonth": start_month,
+                "startYear": start_year,
+                "reason": data["reason"],
+                "createdBy": data["createdBy"]
+            }),
+        )
+        .await?;
+    repos
+        .operations
+        .add_payment_history(
+            school_id,
+            employee_id,
+            "advance_received",
+            json!({"advanceId": advance["advanceId"], "kind": kind, "amount": principal, "emiAmount": emi_amount}),
+        )
+        .await?;
+    Ok(advance)
+}
+
+pub(crate) fn employee_name(emp: &Value) -> String {
+    ["name", "employeeName", "fullName"]
+        .iter()
+        .find_map(|k| emp[*k].as_str().filter(|s| !s.trim().is_empty()))
+        .unwrap_or("")
+        .to_string()
+}
+
+fn round2(v: f64) -> f64 {
+    (v * 100.0).round() / 100.0
+}
+
+fn register_totals(lines: &[Value]) -> Value {
+    let sum = |k: &str| -> f64 {
+        let total: f64 = lines.iter().map(|l| l["breakdown"][k].as_f64().unwrap_or(0.0)).sum();
+        (total * 100.0).round() / 100.0
+    };
+    json!({
+        "employees": lines.len(),
+        "grossSalary": sum("grossSalary"),
+        "absenceDeduction": sum("absenceDeduction"),
+        "statutoryDeductions": sum("statutoryTotal"),
+        "advanceRecovery": sum("advanceRecovery"),
+        "deductions": sum("deductions"),
+        "netPay": sum("netMonthlySalary")
+    })
+}
+
+impl PostgresPayrollService {
+    async fn find_run(&self, school_id: &str, run_id: &str) -> Result<Value, AppError> {
+        self.repos
+            .payroll
+            .get_run(school_id, run_id)
+            .await?
+            .ok_or_else(|| "Payroll run not found".into())
+    }
+
+    async fn find_advance(&self, school_id: &str, advance_id: &str) -> Result<Value, AppError> {
+        self.repos
+            .payroll
+            .get_advance(school_id, advance_id)
+            .await?
+            .ok_or_else(|| "Advance not found".into())
+    }
+
+    /// Books a prepayment or waiver against the principal and logs it on the employee.
+    async fn settle_advance(
+        &self,
+        school_id: &str,
+        advance: &Value,
+        kind: &str,
+        amount: f64,
+        data: &Value,
+    ) -> Result<Value, AppError> {
+        let advance_id = advance["advanceId"].as_str().unwrap_or("");
+        let updated = self
+            .repos
+            .payroll
+            .apply_advance_transaction(
+                school_id,
+                advance_id,
+                json!({
+                    "kind": kind,
+                    "amount": amount,
+                    "principalPart": amount,
+                    "note": data["reason"].as_str().or(data["note"].as_str()),
+                    "by": data["by"]
+                }),
+            )
+            .await?;
+        self.repos
+            .operations
+            .add_payment_history(
+                school_id,
+                advance["employeeId"].as_str().unwrap_or(""),
+                if kind == "waiver" { "advance_waived" } else { "advance_prepaid" },
+                json!({
+                    "advanceId": advance_id,
+                    "amount": amount,
+                    "outstanding": updated["principalOutstanding"],
+                    "by": data["by"],
+                    "reason": data["reason"]
+                }),
+            )
+            .await?;
+        Ok(updated)
+    }
+
+    /// Computes every employee for the run's month with the shared formula and stores the lines.
+    async fn compute_run(&self, school_id: &str, run: &Value) -> Result<(), AppError> {
+        let run_id = run["runId"].as_str().unwrap_or("");
+        let month = run["month"].as_i64().unwrap_or(0) as i32;
+        let year = run["year"].as_i64().unwrap_or(0) as i32;
+        let unpaid = unpaid_leave_types(&self.repos, school_id).await?;
+        let absences = self.repos.payroll.get_employee_absences(school_id, month, year, &unpaid).await?;
+        let employees = self.repos.employee.get_employees(school_id).await?;
+        let period = PayrollMonth::load(&self.repos, school_id, None, month, year).await?;
+        let first = chrono::NaiveDate::from_ymd_opt(year, month as u32, 1).ok_or("Invalid payroll month")?;
+        let last = (first + chrono::Months::new(1)).pred_opt().unwrap_or(first);
+        let calendar = load_calendar(&self.repos, school_id, first, last).await?;
+
+        let mut lines = Vec::new();
+        for emp in employees.iter().filter(|e| e["status"] != "inactive") {
+            let employee_id = match emp["employeeId"].as_str() {
+                Some(id) => id,
+                None => continue,
+            };
+            let absent_days = absences[employee_id].as_f64().unwrap_or(0.0);
+            let mut breakdown = employee_salary(&self.repos, school_id, emp, absent_days, &period).await?;
+            // Recorded for the register; salary is still prorated on the fixed 30-day basis
+            breakdown["workingDays"] = json!(calendar.working_days(first, last, Some(("employee", employee_id))).len());
+            lines.push(json!({
+                "employeeId": employee_id,
+                "employeeName": employee_name(emp),
+                "breakdown": breakdown
+            }));
+        }
+        let totals = register_totals(&lines);
+        self.repos
+            .payroll
+            .save_run_lines(school_id, run_id, lines, totals)
+            .await
+    }
+}
+
+#[async_trait]
+impl PayrollService for PostgresPayrollService {
+    async fn list_runs(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos.payroll.get_runs(school_id).await
+    }
+
+    async fn get_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut run = self.find_run(school_id, run_id).await?;
+        run["lines"] = json!(self.repos.payroll.get_run_lines(school_id, run_id).await?);
+        Ok(run)
+    }
+
+    async fn create_run(
+        &self,
+        school_id: &str,
+        month: i32,
+        year: i32,
+        created_by: Option<String>,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        if !(1..=12).contains(&month) || year < 2000 {
+            return Err("Invalid payroll month".into());
+        }
+        let run = match self.repos.payroll.get_run_for_month(school_id, month, year).await? {
+            Some(existing) => existing,
+            None => {
+                self.repos
+                    .payroll
+                    .create_run(school_id, json!({"month": month, "year": year, "createdBy": created_by}))
+                    .await?
+            }
+        };
+        let run_id = run["runId"].as_str().unwrap_or("").to_string();
+        self.recompute_run(school_id, &run_id).await
+    }
+
+    async fn recompute_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let run = self.find_run(school_id, run_id).await?;
+        if run["status"] == "approved" {
+            return Err("Payroll run is approved and locked; reopen it to recompute".into());
+        }
+        self.compute_run(school_id, &run).await?;
+        self.get_run(school_id, run_id).await
+    }
+
+    async fn approve_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        approved_by: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let run = self.find_run(school_id, run_id).await?;
+        let from = run["status"].as_str().unwrap_or("").to_string();
+        if from == "approved" {
+            return Err("Payroll run is already approved".into());
+        }
+
+        // Advances paused, prepaid or waived since the run was computed would be over-recovered
+        let lines = self.repos.payroll.get_run_lines(school_id, run_id).await?;
+        for line in &lines {
+            for rec in line["breakdown"]["advanceRecoveries"].as_array().into_iter().flatten() {
+                let advance = self
+                    .repos
+                    .payroll
+                    .get_advance(school_id, rec["advanceId"].as_str().unwrap_or(""))
+                    .await?;
+                let principal_part = rec["amount"].as_f64().unwrap_or(0.0) - rec["interest"].as_f64().unwrap_or(0.0);
+                let stale = !advance.is_some_and(|a| {
+                    a["status"] == "active"
+                        && a["principalOutstanding"].as_f64().unwrap_or(0.0) + 0.005 >= principal_part
+                });
+                if stale {
+                    return Err(format!(
+                        "Advance {} for {} has changed since the run was computed; recompute the run",
+                        rec["advanceId"].as_str().unwrap_or(""),
+                        line["employeeName"].as_str().unwrap_or("")
+                    )
+                    .into());
+                }
+            }
+        }
+
+        // Scheduled installments recovered in this run are booked against the advances as it locks
+        let mut recoveries = Vec::new();
+        let mut history = Vec::new();
+        for line in &lines {
+            for rec in line["breakdown"]["advanceRecoveries"].as_array().into_iter().flatten() {
+                let amount = rec["amount"].as_f64().unwrap_or(0.0);
+                let interest = rec["interest"].as_f64().unwrap_or(0.0);
+                recoveries.push(json!({
+                    "advanceId": rec["advanceId"],
+                    "kind": "recovery",
+                    "amount": amount,
+                    "principalPart": amount - interest,
+                    "interestPart": interest,
+                    "runId": run_id,
+                    "month": run["month"],
+                    "year": run["year"],
+                    "by": approved_by
+                }));
+            }
+            history.push(json!({
+                "employeeId": line["employeeId"],
+                "data": {
+                    "runId": run_id,
+                    "month": run["month"],
+                    "year": run["year"],
+                    "netPay": line["breakdown"]["netMonthlySalary"],
+                    "advanceRecovered": line["breakdown"]["advanceRecovery"].as_f64().unwrap_or(0.0),
+                    "approvedBy": approved_by
+                }
+            }));
+        }
+        if !self
+            .repos
+            .payroll
+            .approve_run(school_id, run_id, &from, approved_by, &recoveries, &history)
+            .await?
+        {
+            return Err("Payroll run changed while approving; reload and try again".into());
+        }
+        self.get_run(school_id, run_id).await
+    }
+
+    async fn reopen_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        reopened_by: &str,
+        reason: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        if reason.trim().is_empty() {
+            return Err("A reason is required to reopen a payroll run".into());
+        }
+        if !self
+            .repos
+            .payroll
+            .transition_run(
+                school_id,
+                run_id,
+                "approved",
+                "reopened",
+                json!({"by": reopened_by, "reason": reason}),
+            )
+            .await?
+        {
+            return Err("Only an approved payroll run can be reopened".into());
+        }
+
+        // Give back the advance recoveries so re-approval does not take them twice
+        self.repos.payroll.reverse_run_recoveries(school_id, run_id).await?;
+        let run = self.find_run(school_id, run_id).await?;
+        let lines = self.repos.payroll.get_run_lines(school_id, run_id).await?;
+        for line in &lines {
+            let employee_id = line["employeeId"].as_str().unwrap_or("");
+            self.repos
+                .operations
+                .add_payment_history(
+                    school_id,
+                    employee_id,
+                    "payroll_reopened",
+                    json!({"runId": run_id, "month": run["month"], "year": run["year"], "reopenedBy": reopened_by, "reason": reason}),
+                )
+                .await?;
+        }
+        self.get_run(school_id, run_id).await
+    }
+
+    async fn list_payslips(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        approved_only: bool,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos
+            .payroll
+            .get_employee_run_lines(school_id, employee_id, approved_only)
+            .await
+    }
+
+    async fn get_payslip(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        employee_id: &str,
+        approved_only: bool,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let run = self.find_run(school_id, run_id).await?;
+        if approved_only && run["status"] != "approved" {
+            return Err("Payslip not found".into());
+        }
+        let line = self
+            .repos
+            .payroll
+            .get_run_line(school_id, run_id, employee_id)
+            .await?
+            .ok_or("Payslip not found")?;
+        let employee = self
+            .repos
+            .employee
+            .get_employee(school_id, employee_id)
+            .await?
+            .unwrap_or(Value::Null);
+        let school_name = self
+            .repos
+            .school
+            .get_school(school_id)
+            .await?
+            .and_then(|s| s["schoolName"].as_str().map(|n| n.to_string()))
+            .unwrap_or_default();
+        Ok(build_payslip(&school_name, &run, &line, &employee))
+    }
+
+    async fn payroll_register(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let run = self.find_run(school_id, run_id).await?;
+        let lines = self.repos.payroll.get_run_lines(school_id, run_id).await?;
+        let rows: Vec<Value> = lines
+            .iter()
+            .map(|l| {
+                let b = &l["breakdown"];
+                let allowances = ["incrementAmount", "spacesComponent", "experienceComponent", "tenureComponent", "allowancesTotal"]
+                    .iter()
+                    .map(|k| b[*k].as_f64().unwrap_or(0.0))
+                    .sum::<f64>();
+                json!({
+                    "employeeId": l["employeeId"],
+                    "employeeName": l["employeeName"],
+                    "baseSalary": b["baseSalary"],
+                    "allowances": (allowances * 100.0).round() / 100.0,
+                    "bonus": b["bonus"],
+                    "aid": b["aid"],
+                    "arrears": b["arrearsTotal"].as_f64().unwrap_or(0.0),
+                    "grossSalary": b["grossSalary"],
+                    "absentDays": b["absentDays"],
+                    "absenceDeduction": b["absenceDeduction"],
+                    "statutoryDeductions": b["statutoryTotal"].as_f64().unwrap_or(0.0),
+                    "advanceRecovery": b["advanceRecovery"],
+                    "deductions": b["deductions"],
+                    "netPay": b["netMonthlySalary"]
+                })
+            })
+            .collect();
+        Ok(json!({
+            "runId": run_id,
+            "month": run["month"],
+            "year": run["year"],
+            "status": run["status"],
+            "rows": rows,
+            "totals": register_totals(&lines)
+        }))
+    }
+
+    async fn get_statutory_settings(
+        &self,
+        school_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        statutory_settings(&self.repos, school_id).await
+    }
+
+    async fn update_statutory_settings(
+        &self,
+        school_id: &str,
+        settings: Value,
+        updated_by: Option<String>,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        // Only the known sections are kept; each one overrides the defaults key by key
+        let mut saved = self.repos.payroll.get_statutory_settings(school_id).await?;
+        if !saved.is_object() {
+            saved = json!({});
+        }
+        for section in ["pf", "esi", "pt", "tds"] {
+            match &settings[section] {
+                Value::Null => {}
+                Value::Object(patch) => {
+                    if !saved[section].is_object() {
+                        saved[section] = json!({});
+                    }
+                    for (k, v) in patch {
+                        saved[section][k] = v.clone();
+                    }
+                }
+                _ => return Err(format!("'{}' settings must be an object", section).into()),
+            }
+        }
+        if let Some(regime) = saved["tds"]["defaultRegime"].as_str() {
+            if regime != "old" && regime != "new" {
+                return Err("defaultRegime must be 'old' or 'new'".into());
+            }
+        }
+        self.repos
+            .payroll
+            .save_statutory_settings(school_id, saved.clone(), updated_by)
+            .await?;
+        Ok(merge_settings(&saved))
+    }
+
+    async fn statutory_challans(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let run = self.find_run(school_id, run_id).await?;
+        let lines = self.repos.payroll.get_run_lines(school_id, run_id).await?;
+        let employees = self.repos.employee.get_employees(school_id).await?;
+        let employee = |id: &Value| employees.iter().find(|e| e["employeeId"] == *id).cloned().unwrap_or(Value::Null);
+
+        let mut rows = Vec::new();
+        let mut pt_by_state = serde_json::Map::new();
+        for l in &lines {
+            let st = &l["breakdown"]["statutory"];
+            let emp = employee(&l["employeeId"]);
+            let pt = st["professionalTax"].as_f64().unwrap_or(0.0);
+            if pt > 0.0 {
+                let state = st["ptState"].as_str().filter(|s| !s.is_empty()).unwrap_or("-").to_string();
+                let entry = pt_by_state.entry(state).or_insert_with(|| json!({"employees": 0, "amount": 0.0}));
+                entry["employees"] = json!(entry["employees"].as_i64().unwrap_or(0) + 1);
+                entry["amount"] = json!(entry["amount"].as_f64().unwrap_or(0.0) + pt);
+            }
+            rows.push(json!({
+                "employeeId": l["employeeId"],
+                "employeeName": l["employeeName"],
+                "uan": emp["uan"],
+                "esiNumber": emp["esiNumber"],
+                "pan": emp["pan"],
+                "pfWages": st["pfWages"].as_f64().unwrap_or(0.0),
+                "pfEmployee": st["pfEmployee"].as_f64().unwrap_or(0.0),
+                "pfEmployerEpf": st["pfEmployerEpf"].as_f64().unwrap_or(0.0),
+                "pfEmployerEps": st["pfEmployerEps"].as_f64().unwrap_or(0.0),
+                "esiWages": st["esiWages"].as_f64().unwrap_or(0.0),
+                "esiEmployee": st["esiEmployee"].as_f64().unwrap_or(0.0),
+                "esiEmployer": st["esiEmployer"].as_f64().unwrap_or(0.0),
+                "ptState": st["ptState"],
+                "professionalTax": pt,
+                "tds": st["tds"].as_f64().unwrap_or(0.0)
+            }));
+        }
+
+        let sum = |k: &str| round2(rows.iter().map(|r| r[k].as_f64().unwrap_or(0.0)).sum());
+        let count = |k: &str| rows.iter().filter(|r| r[k].as_f64().unwrap_or(0.0) > 0.0).count();
+        let challans = json!({
+            "pf": {
+                "members": count("pfWages"),
+                "wages": sum("pfWages"),
+                "employeeShare": sum("pfEmployee"),
+                "employerEpf": sum("pfEmployerEpf"),
+                "employerEps": sum("pfEmployerEps"),
+                "total": round2(sum("pfEmployee") + sum("pfEmployerEpf") + sum("pfEmployerEps"))
+            },
+            "esi": {
+                "members": count("esiWages"),
+                "wages": sum("esiWages"),
+                "employeeShare": sum("esiEmployee"),
+                "employerShare": sum("esiEmployer"),
+                "total": round2(sum("esiEmployee") + sum("esiEmployer"))
+            },
+            "pt": {
+                "employees": count("professionalTax"),
+                "byState": pt_by_state,
+                "total": sum("professionalTax")
+            },
+            "tds": {
+                "employees": count("tds"),
+                "total": sum("tds")
+            }
+        });
+        Ok(json!({
+            "runId": run_id,
+            "month": run["month"],
+            "year": run["year"],
+            "status": run["status"],
+            "challans": challans,
+            "rows": rows
+        }))
+    }
+
+    async fn list_advances(
+        &self,
+        school_id: &str,
+        employee_id: Option<&str>,
+        status: Option<&str>,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos.payroll.get_advances(school_id, employee_id, status).await
+    }
+
+    async fn create_advance(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        new_advance(&self.repos, school_id, employee_id, data).await
+    }
+
+    async fn get_advance(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut advance = self.find_advance(school_id, advance_id).await?;
+        let now = chrono::Local::now();
+        advance["schedule"] = json!(project_schedule(&advance, now.month() as i32, now.year()));
+        advance["transactions"] = json!(self.repos.payroll.get_advance_transactions(school_id, advance_id).await?);
+        Ok(advance)
+    }
+
+    async fn prepay_advance(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let advance = self.find_advance(school_id, advance_id).await?;
+        let outstanding = advance["principalOutstanding"].as_f64().unwrap_or(0.0);
+        let amount = data["amount"].as_f64().filter(|a| *a > 0.0).ok_or("amount must be greater than zero")?;
+        if amount > outstanding + 0.005 {
+            return Err(format!("Prepayment exceeds the outstanding balance of {:.2}", outstanding).into());
+        }
+        self.settle_advance(school_id, &advance, "prepayment", amount, &data).await
+    }
+
+    async fn set_advance_paused(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+        paused: bool,
+        by: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let (from, to) = if paused { ("active", "paused") } else { ("paused", "active") };
+        if !self.repos.payroll.set_advance_status(school_id, advance_id, from, to).await? {
+            return Err(format!("Only an {} advance can be {}", from, if paused { "paused" } else { "resumed" }).into());
+        }
+        let advance = self.find_advance(school_id, advance_id).await?;
+        self.repos
+            .operations
+            .add_payment_history(
+                school_id,
+                advance["employeeId"].as_str().unwrap_or(""),
+                if paused { "advance_paused" } else { "advance_resumed" },
+                json!({"advanceId": advance_id, "by": by}),
+            )
+            .await?;
+        Ok(advance)
+    }
+
+    async fn waive_advance(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let advance = self.find_advance(school_id, advance_id).await?;
+        let outstanding = advance["principalOutstanding"].as_f64().unwrap_or(0.0);
+        if data["reason"].as_str().is_none_or(|r| r.trim().is_empty()) {
+            return Err("A reason is required to waive an advance".into());
+        }
+        let amount = data["amount"].as_f64().unwrap_or(outstanding);
+        if amount <= 0.0 || amount > outstanding + 0.005 {
+            return Err(format!("Waiver must be between 0 and the outstanding {:.2}", outstanding).into());
+        }
+        self.settle_advance(school_id, &advance, "waiver", amount, &data).await
+    }
+
+    async fn employee_advance_summary(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let now = chrono::Local::now();
+        let advances = self.repos.payroll.get_advances(school_id, Some(employee_id), None).await?;
+        let open: Vec<Value> = advances
+            .into_iter()
+            .filter(|a| a["status"] == "active" || a["status"] == "paused")
+            .map(|mut a| {
+                let schedule = project_schedule(&a, now.month() as i32, now.year());
+                a["nextInstallment"] = schedule.first().cloned().unwrap_or(Value::Null);
+                a["remainingInstallments"] = json!(schedule.len());
+                a
+            })
+            .collect();
+        let outstanding: f64 = open.iter().map(|a| a["principalOutstanding"].as_f64().unwrap_or(0.0)).sum();
+        let monthly: f64 = open
+            .iter()
+            .filter(|a| a["status"] == "active")
+            .map(|a| a["nextInstallment"]["amount"].as_f64().unwrap_or(0.0))
+            .sum();
+        Ok(json!({
+            "employeeId": employee_id,
+            "totalOutstanding": round2(outstanding),
+            "monthlyRecovery": round2(monthly),
+            "advances": open
+        }))
+    }
+
+    async fn set_bank_account(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let account_number: String = data["accountNumber"]
+            .as_str()
+            .unwrap_or("")
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        let ifsc = data["ifsc"].as_str().unwrap_or("").trim().to_uppercase();
+        if !valid_account_number(&account_number) {
+            return Err("accountNumber must be 6 to 18 digits".into());
+        }
+        if !valid_ifsc(&ifsc) {
+            return Err("ifsc must look like ABCD0123456".into());
+        }
+        let emp = self
+            .repos
+            .employee
+            .get_employee(school_id, employee_id)
+            .await?
+            .ok_or("Employee not found")?;
+        let account = json!({
+            "accountHolder": data["accountHolder"].as_str().map(|s| s.to_string()).unwrap_or_else(|| employee_name(&emp)),
+            "accountNumber": account_number,
+            "ifsc": ifsc,
+            "bankName": data["bankName"],
+            "accountType": data["accountType"].as_str().unwrap_or("savings")
+        });
+        self.repos
+            .payroll
+            .set_employee_bank_account(school_id, employee_id, account.clone())
+            .await?;
+        Ok(account)
+    }
+
+    async fn create_transfer_batch(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let run = self.find_run(school_id, run_id).await?;
+        if run["status"] != "approved" {
+            return Err("Only an approved payroll run can be sent to the bank".into());
+        }
+        let format = data["bankFormat"].as_str().unwrap_or("generic").to_lowercase();
+        if !BANK_FORMATS.contains(&format.as_str()) {
+            return Err(format!("bankFormat must be one of {}", BANK_FORMATS.join(", ")).into());
+        }
+        let debit_account = data["debitAccount"].as_str().unwrap_or("").trim().to_string();
+        if !valid_account_number(&debit_account) {
+            return Err("debitAccount (the school's account number) is required".into());
+        }
+        let debit_ifsc = data["debitIfsc"].as_str().unwrap_or("").trim().to_uppercase();
+        if !debit_ifsc.is_empty() && !valid_ifsc(&debit_ifsc) {
+            return Err("debitIfsc must look like ABCD0123456".into());
+        }
+        let payment_date = data["paymentDate"]
+            .as_str()
+            .map(|d| d.to_string())
+            .unwrap_or_else(|| chrono::Local::now().date_naive().to_string());
+        if chrono::NaiveDate::parse_from_str(&payment_date, "%Y-%m-%d").is_err() {
+            return Err("paymentDate must be YYYY-MM-DD".into());
+        }
+        let narration = data["narration"]
+            .as_str()
+            .map(|n| n.to_string())
+            .unwrap_or_else(|| format!("SALARY {:02}/{}", run["month"].as_i64().unwrap_or(0), run["year"]));
+
+        let already_sent = self.repos.payroll.get_run_transferred_employees(school_id, run_id).await?;
+        let employees = self.repos.employee.get_employees(school_id).await?;
+        let mut lines = Vec::new();
+        let mut skipped = Vec::new();
+        for line in self.repos.payroll.get_run_lines(school_id, run_id).await? {
+            let employee_id = line["employeeId"].as_str().unwrap_or("").to_string();
+            let amount = line["breakdown"]["netMonthlySalary"].as_f64().unwrap_or(0.0);
+            if amount <= 0.0 || already_sent.contains(&employee_id) {
+                continue;
+            }
+            let bank = employees
+                .iter()
+                .find(|e| e["employeeId"] == employee_id.as_str())
+                .map(|e| e["bankAccount"].clone())
+                .unwrap_or(Value::Null);
+            let account = bank["accountNumber"].as_str().unwrap_or("");
+            let ifsc = bank["ifsc"].as_str().unwrap_or("");
+            if !valid_account_number(account) || !valid_ifsc(ifsc) {
+                skipped.push(json!({
+                    "employeeId": employee_id,
+                    "employeeName": line["employeeName"],
+                    "amount": amount,
+                    "reason": "Missing or invalid bank account details"
+                }));
+                continue;
+            }
+            lines.push(json!({
+                "employeeId": employee_id,
+                "employeeName": bank["accountHolder"].as_str().or(line["employeeName"].as_str()),
+                "accountNumber": account,
+                "ifsc": ifsc,
+                "bankName": bank["bankName"],
+                "amount": amount,
+                "mode": transfer_mode(data["mode"].as_str(), amount, ifsc, &debit_ifsc),
+                "reference": format!("{}-{}", run_id, employee_id)
+            }));
+        }
+        if lines.is_empty() {
+            return Err(format!(
+                "Nothing to transfer: every employee is already sent or lacks bank details ({} skipped)",
+                skipped.len()
+            )
+            .into());
+        }
+
+        let mut batch = self
+            .repos
+            .payroll
+            .create_transfer_batch(
+                school_id,
+                json!({
+                    "runId": run_id,
+                    "bankFormat": format,
+                    "debitAccount": debit_account,
+                    "debitIfsc": debit_ifsc,
+                    "paymentDate": payment_date,
+                    "narration": narration,
+                    "createdBy": data["createdBy"]
+                }),
+                lines,
+            )
+            .await?;
+        let batch_id = batch["batchId"].as_str().unwrap_or("").to_string();
+        batch["lines"] = json!(self.repos.payroll.get_transfer_lines(school_id, &batch_id).await?);
+        batch["skipped"] = json!(skipped);
+        Ok(batch)
+    }
+
+    async fn list_transfer_batches(
+        &self,
+        school_id: &str,
+        run_id: Option<&str>,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos.payroll.get_transfer_batches(school_id, run_id).await
+    }
+
+    async fn get_transfer_batch(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut batch = self
+            .repos
+            .payroll
+            .get_transfer_batch(school_id, batch_id)
+            .await?
+            .ok_or("Transfer batch not found")?;
+        batch["lines"] = json!(self.repos.payroll.get_transfer_lines(school_id, batch_id).await?);
+        Ok(batch)
+    }
+
+    async fn transfer_file(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let batch = self.get_transfer_batch(school_id, batch_id).await?;
+        let lines = batch["lines"].as_array().cloned().unwrap_or_default();
+        let table = build_transfer_file(batch["bankFormat"].as_str().unwrap_or("generic"), &batch, &lines)?;
+        Ok(json!({
+            "fileName": format!("{}_{}.csv", batch_id, batch["bankFormat"].as_str().unwrap_or("generic")),
+            "content": to_csv(&table)
+        }))
+    }
+
+    async fn import_transfer_response(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+        file_name: &str,
+        bytes: Vec<u8>,
+        imported_by: Option<String>,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let batch = self.get_transfer_batch(school_id, batch_id).await?;
+        let lines = batch["lines"].as_array().cloned().unwrap_or_default();
+        let results = parse_transfer_response(file_name, &bytes)?;
+
+        let (mut paid, mut failed, mut pending) = (0, 0, 0);
+        let mut unmatched = Vec::new();
+        for r in &results {
+            // Our customer reference first, then account number and amount
+            let line = lines
+                .iter()
+                .find(|l| !r.reference.is_empty() && l["reference"] == r.reference.as_str())
+                .or_else(|| {
+                    lines.iter().find(|l| {
+                        l["accountNumber"] == r.account_number.as_str()
+                            && (r.amount == 0.0 || (l["amount"].as_f64().unwrap_or(0.0) - r.amount).abs() < 0.01)
+                    })
+                });
+            let line = match line {
+                Some(l) => l,
+                None => {
+                    unmatched.push(json!({"reference": r.reference, "accountNumber": r.account_number, "amount": r.amount, "status": r.status}));
+                    continue;
+                }
+            };
+            let employee_id = line["employeeId"].as_str().unwrap_or("");
+            // A paid line stays paid if a later file repeats it as pending
+            if line["status"] == "paid" && r.status != "failed" {
+                paid += 1;
+                continue;
+            }
+            self.repos
+                .payroll
+                .update_transfer_line(
+                    school_id,
+                    batch_id,
+                    employee_id,
+                    json!({"status": r.status, "utr": r.utr, "reason": if r.status == "failed" { r.reason.as_str() } else { "" }}),
+                )
+                .await?;
+            match r.status.as_str() {
+                "paid" => paid += 1,
+                "failed" => failed += 1,
+                _ => pending += 1,
+            }
+            if r.status != "pending" && line["status"] != r.status.as_str() {
+                self.repos
+                    .operations
+                    .add_payment_history(
+                        school_id,
+                        employee_id,
+                        if r.status == "paid" { "salary_paid" } else { "salary_transfer_failed" },
+                        json!({
+                            "runId": batch["runId"],
+                            "batchId": batch_id,
+                            "amount": line["amount"],
+                            "utr": r.utr,
+                            "reason": r.reason,
+                            "importedBy": imported_by
+                        }),
+                    )
+                    .await?;
+            }
+        }
+        let batch = self
+            .repos
+            .payroll
+            .refresh_transfer_batch(school_id, batch_id)
+            .await?
+            .ok_or("Transfer batch not found")?;
+        Ok(json!({
+            "batch": batch,
+            "rows": results.len(),
+            "paid": paid,
+            "failed": failed,
+            "pending": pending,
+            "unmatched": unmatched
+        }))
+    }
+
+    async fn annual_tax_statement(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        fy_start: i32,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+        let emp = self
+            .repos
+            .employee
+            .get_employee(school_id, employee_id)
+            .await?
+            .ok_or("Employee not found")?;
+        let settings = statutory_settings(&self.repos, school_id).await?;
+        let lines = self
+            .repos
+            .payroll
+            .get_approved_lines_between(school_id, Some(employee_id), fy_start * 12 + 4, (fy_start + 1) * 12 + 3)
+            .await?;
+
+        let rows: Vec<Value> = lines
+            .iter()
+            .map(|l| {
+                let b = &l["breakdown"];
+                let st = &b["statutory"];
+                let f = |v: &Value| v.as_f64().unwrap_or(0.0);
+                let earned = b["earnedGross"].as_f64().unwrap_or(f(&b["grossSalary"]) - f(&b["absenceDeduction"]));
+                let month = l["month"].as_i64().unwrap_or(1).clamp(1, 12) as usize;
+                json!({
+                    "period": format!("{} {}", MONTHS[month - 1], l["year"]),
+                    "grossSalary": f(&b["grossSalary"]),
+                    "absenceDeduction": f(&b["absenceDeduction"]),
+                    "earnedGross": round2(earned),
+                    "pfEmployee": f(&st["pfEmployee"]),
+                    "esiEmployee": f(&st["esiEmployee"]),
+                    "professionalTax": f(&st["professionalTax"]),
+                    "tds": f(&st["tds"]),
+                    "netPay": f(&b["netMonthlySalary"])
+                })
+            })
+            .collect();
+        let sum = |k: &str| round2(rows.iter().map(|r| r[k].as_f64().unwrap_or(0.0)).sum());
+
+        let tds_cfg = &settings["tds"];
+        let regime = emp["taxRegime"]
+            .as_str()
+            .filter(|r| *r == "old" || *r == "new")
+            .or(tds_cfg["defaultRegime"].as_str())
+            .unwrap_or("new");
+        let regime_cfg = &tds_cfg[if regime == "old" { "oldRegime" } else { "newRegime" }];
+        let gross = sum("earnedGross");
+        let standard_deduction = regime_cfg["standardDeduction"].as_f64().unwrap_or(0.0).min(gross);
+        let taxable = taxable_income(tds_cfg, regime, &emp, gross, sum("pfEmployee"), sum("professionalTax"));
+        let tax = annual_tax(regime_cfg, taxable, tds_cfg["cessRate"].as_f64().unwrap_or(0.0)).round();
+        let tds = sum("tds");
+        // Everything between gross and taxable income apart from the standard deduction
+        let other_deductions = round2((gross - standard_deduction - taxable).max(0.0));
+
+        let school_name = self
+            .repos
+            .school
+            .get_school(school_id)
+            .await?
+            .and_then(|s| s["schoolName"].as_str().map(|n| n.to_string()))
+            .unwrap_or_default();
+        Ok(json!({
+            "financialYear": format!("{}-{:02}", fy_start, (fy_start + 1) % 100),
+            "assessmentYear": format!("{}-{:02}", fy_start + 1, (fy_start + 2) % 100),
+            "employer": {"schoolId": school_id, "name": school_name},
+            "employee": {
+                "employeeId": employee_id,
+                "name": employee_name(&emp),
+                "pan": emp["pan"],
+                "uan": emp["uan"],
+                "designation": emp["designation"].as_str().or(emp["employeeType"].as_str())
+            },
+            "taxRegime": regime,
+            "rows": rows,
+            "totals": {
+                "grossSalary": sum("grossSalary"),
+                "absenceDeduction": sum("absenceDeduction"),
+                "earnedGross": gross,
+                "pfEmployee": sum("pfEmployee"),
+                "esiEmployee": sum("esiEmployee"),
+                "professionalTax": sum("professionalTax"),
+                "tds": tds,
+                "netPay": sum("netPay")
+            },
+            "computation": {
+                "grossSalary": gross,
+                "standardDeduction": standard_deduction,
+                "otherDeductions": other_deductions,
+                "taxableIncome": taxable.round(),
+                "taxPayable": tax,
+                "tdsDeducted": tds,
+                // Positive: still payable by the employee; negative: excess deducted
+                "balance": round2(tax - tds)
+            }
+        }))
+    }
+
+    async fn list_salary_revisions(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut employee = self
+            .repos
+            .employee
+            .get_employee(school_id, employee_id)
+            .await?
+            .ok_or("Employee not found")?;
+        employee["employeeId"] = json!(employee_id);
+        let revisions = self.repos.payroll.get_salary_revisions(school_id, employee_id).await?;
+        let now = chrono::Local::now();
+        let in_force = revision_in_force(&revisions, now.month() as i32, now.year()).map(|r| r["revisionId"].clone());
+        let next = if now.month() == 12 { (1, now.year() + 1) } else { (now.month() as i32 + 1, now.year()) };
+        let pending_arrears = salary_arrears(&self.repos, school_id, &employee, &revisions, next.0, next.1).await?;
+
+        let mut rows = revisions.clone();
+        rows.sort_by_key(|r| {
+            std::cmp::Reverse((
+                r["effectiveYear"].as_i64().unwrap_or(0) * 12 + r["effectiveMonth"].as_i64().unwrap_or(0),
+                r["createdAt"].as_str().unwrap_or("").to_string(),
+            ))
+        });
+        for r in rows.iter_mut() {
+            r["inForce"] = json!(in_force.as_ref() == Some(&r["revisionId"]));
+        }
+        let effective = apply_revision(&employee, revision_in_force(&revisions, now.month() as i32, now.year()));
+        let current: serde_json::Map<String, Value> = REVISION_KEYS
+            .iter()
+            .filter(|k| !effective[**k].is_null())
+            .map(|k| (k.to_string(), effective[*k].clone()))
+            .collect();
+        Ok(json!({
+            "employeeId": employee_id,
+            "current": current,
+            "revisions": rows,
+            "pendingArrears": pending_arrears
+        }))
+    }
+
+    async fn create_salary_revision(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        revise_salary(&self.repos, school_id, employee_id, data).await
+    }
+}
+
+/// Daily job that prepares last month's draft payroll for every active school.
+pub async fn start_payroll_draft_job(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(24 * 60 * 60));
+
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+            let today = chrono::Local::now().date_naive();
+            let (month, year) = if today.month() == 1 {
+                (12, today.year() - 1)
+            } else {
+                (today.month() as i32 - 1, today.year())
+            };
+            let schools = match state.repos.reminder.get_active_school_ids().await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!("[Payroll] Failed to load schools: {}", e);
+                    continue;
+                }
+            };
+            for school_id in schools {
+                match state.repos.payroll.get_run_for_month(&school_id, month, year).await {
+                    Ok(Some(_)) => continue,
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::error!("[Payroll] {} lookup failed: {}", school_id, e);
+                        continue;
+                    }
+                }
+                match state
+                    .services
+                    .payroll
+                    .create_run(&school_id, month, year, Some("system".to_string()))
+                    .await
+                {
+                    Ok(r) => tracing::info!(
+                        "[Payroll] {} draft for {:02}/{}: net {}",
+                        school_id,
+                        month,
+                        year,
+                        r["totals"]["netPay"]
+                    ),
+                    Err(e) => tracing::error!("[Payroll] {} draft failed: {}", school_id, e),
+                }
+            }
+        }
+    });
+}
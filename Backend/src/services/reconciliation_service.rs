@@ -0,0 +1,426 @@
+use crate::logic::bank_statement::{parse_statement, StatementLine};
+use crate::repository::Repositories;
+use crate::services::traits::*;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::sync::Arc;
+
+/// Credits within this many days of a fee's due date are considered for amount matching.
+const DUE_DATE_WINDOW_DAYS: i64 = 15;
+
+/// Shortest student ID that is posted on sight when quoted in a narration. Shorter or
+/// all-digit IDs collide with UTR fragments and account numbers, so they only suggest a student.
+const MIN_POSTABLE_ID_LEN: usize = 6;
+
+pub struct PostgresReconciliationService {
+    pub repos: Arc<Repositories>,
+    pub payments: Arc<dyn PaymentService>,
+}
+
+/// Outcome of matching one credit line against the school's students and dues.
+enum Match {
+    Student { student_id: String, method: &'static str, fee_id: Option<String> },
+    Ambiguous { method: &'static str, candidates: Vec<String> },
+    /// One student matches, but on evidence too weak to post without a look.
+    Suggested { student_id: String, method: &'static str },
+    None,
+}
+
+fn postable_id(id: &str) -> bool {
+    id.len() >= MIN_POSTABLE_ID_LEN
+        && id.chars().any(|c| c.is_ascii_alphabetic())
+        && id.chars().any(|c| c.is_ascii_digit())
+}
+
+fn tokens(text: &str) -> Vec<String> {
+    text.to_uppercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_string())
+        .collect()
+}
+
+fn payment_mode(narration: &str, reference: &str) -> &'static str {
+    let text = format!("{} {}", narration, reference).to_uppercase();
+    if text.contains("UPI") {
+        "upi"
+    } else if text.contains("IMPS") {
+        "imps"
+    } else if text.contains("NEFT") {
+        "neft"
+    } else if text.contains("RTGS") {
+        "rtgs"
+    } else {
+        "bank_transfer"
+    }
+}
+
+/// Order of preference: a student ID quoted as a whole token in the reference or narration
+/// (short or all-digit IDs only suggest the student), the student's name
+/// in the UPI note, then a unique outstanding due of the same amount near the credit date.
+fn match_line(line: &StatementLine, students: &[Value], custom_dues: &[Value], balances: &[Value]) -> Match {
+    let words = tokens(&format!("{} {}", line.narration, line.reference));
+    let word_set: BTreeSet<&str> = words.iter().map(|w| w.as_str()).collect();
+
+    let by_id: BTreeSet<String> = students
+        .iter()
+        .filter_map(|s| s["studentId"].as_str())
+        .filter(|id| id.len() >= 3 && word_set.contains(id.to_uppercase().as_str()))
+        .map(|id| id.to_string())
+        .collect();
+    match by_id.len() {
+        1 => {
+            let student_id = by_id.into_iter().next().unwrap_or_default();
+            if !postable_id(&student_id) {
+                return Match::Suggested { student_id, method: "reference" };
+            }
+            return Match::Student { student_id, method: "reference", fee_id: None };
+        }
+        n if n > 1 => {
+            return Match::Ambiguous { method: "reference", candidates: by_id.into_iter().collect() }
+        }
+        _ => {}
+    }
+
+    let by_name: BTreeSet<String> = students
+        .iter()
+        .filter(|s| {
+            let name_tokens = tokens(s["name"].as_str().unwrap_or(""));
+            // A lone short first name is too weak to act on
+            let strong = name_tokens.len() >= 2 || name_tokens.first().map(|t| t.len() >= 5).unwrap_or(false);
+            strong && name_tokens.iter().all(|t| word_set.contains(t.as_str()))
+        })
+        .filter_map(|s| s["studentId"].as_str().map(|id| id.to_string()))
+        .collect();
+    match by_name.len() {
+        1 => {
+            return Match::Student {
+                student_id: by_name.into_iter().next().unwrap_or_default(),
+                method: "upi_note",
+                fee_id: None,
+            }
+        }
+        n if n > 1 => {
+            return Match::Ambiguous { method: "upi_note", candidates: by_name.into_iter().collect() }
+        }
+        _ => {}
+    }
+
+    let near_due = |d: &Value| {
+        d["dueDate"]
+            .as_str()
+            .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+            .map(|due| (line.date - due).num_days().abs() <= DUE_DATE_WINDOW_DAYS)
+            .unwrap_or(false)
+    };
+    let mut by_amount: Vec<(String, Option<String>)> = custom_dues
+        .iter()
+        .filter(|d| (d["amount"].as_f64().unwrap_or(0.0) - line.credit).abs() < 0.005 && near_due(d))
+        .map(|d| {
+            (
+                d["studentId"].as_str().unwrap_or("").to_string(),
+                d["feeId"].as_str().map(|f| f.to_string()),
+            )
+        })
+        .collect();
+    by_amount.extend(
+        balances
+            .iter()
+            .filter(|b| (b["pendingAmount"].as_f64().unwrap_or(0.0) - line.credit).abs() < 0.005)
+            .map(|b| (b["studentId"].as_str().unwrap_or("").to_string(), None)),
+    );
+    let distinct: BTreeSet<&String> = by_amount.iter().map(|(s, _)| s).collect();
+    match distinct.len() {
+        1 if by_amount.len() == 1 => {
+            let (student_id, fee_id) = by_amount.remove(0);
+            Match::Student { student_id, method: "amount_date", fee_id }
+        }
+        0 => Match::None,
+        _ => Match::Ambiguous {
+            method: "amount_date",
+            candidates: distinct.into_iter().cloned().collect(),
+        },
+    }
+}
+
+impl PostgresReconciliationService {
+    /// Splits `amount` over the student's dues: the matched fee first, then oldest due date,
+    /// with the general balance last and only in whole rupees.
+    async fn allocate(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        amount: f64,
+        preferred_fee: Option<&str>,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let payable = self.payments.payable_items(school_id, student_id).await?;
+        let mut dues = payable["items"].as_array().cloned().unwrap_or_default();
+        dues.sort_by_key(|d| {
+            (
+                Some(d["feeId"].as_str().unwrap_or("")) != preferred_fee,
+                d["feeId"] == "general",
+                d["dueDate"].as_str().map(|s| s.to_string()).unwrap_or_else(|| "9999".to_string()),
+            )
+        });
+
+        let mut remaining = amount;
+        let mut items = Vec::new();
+        for due in dues {
+            if remaining < 0.005 {
+                break;
+            }
+            let due_amount = due["due"].as_f64().unwrap_or(0.0);
+            let mut part = remaining.min(due_amount);
+            if due["feeId"] == "general" {
+                part = part.floor();
+            }
+            if part <= 0.0 {
+                continue;
+            }
+            remaining -= part;
+            items.push(json!({"feeId": due["feeId"], "feeName": due["feeName"], "amount": (part * 100.0).round() / 100.0}));
+        }
+        if remaining >= 0.005 {
+            return Err(format!("Amount exceeds the student's outstanding dues by {:.2}", remaining).into());
+        }
+        Ok(items)
+    }
+
+    /// Claims the line and books its items in one transaction; returns the receipt.
+    async fn post_line(
+        &self,
+        school_id: &str,
+        line: &Value,
+        student_id: &str,
+        items: &[Value],
+        match_method: &str,
+        resolved_by: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let line_id = line["lineId"].as_str().unwrap_or("");
+        let reference = line["reference"]
+            .as_str()
+            .filter(|r| !r.is_empty())
+            .or_else(|| line["narration"].as_str())
+            .unwrap_or("");
+        self.repos
+            .reconciliation
+            .post_statement_line(
+                school_id,
+                line_id,
+                items,
+                &json!({"studentId": student_id, "matchMethod": match_method, "resolvedBy": resolved_by}),
+                &json!({
+                    "receiptNo": format!("RCPT{}", line_id),
+                    "paymentMode": line["paymentMode"].as_str().unwrap_or("bank_transfer"),
+                    "collectedBy": resolved_by,
+                    "reference": reference,
+                    "paidOn": line["txnDate"]
+                }),
+            )
+            .await
+    }
+}
+
+#[async_trait]
+impl ReconciliationService for PostgresReconciliationService {
+    async fn import_statement(
+        &self,
+        school_id: &str,
+        file_name: &str,
+        bytes: Vec<u8>,
+        imported_by: Option<String>,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let parsed = parse_statement(file_name, &bytes)?;
+        let import_id = format!("BSI{}", chrono::Utc::now().timestamp_millis());
+        let students = self.repos.student.get_students(school_id).await?;
+        let mut custom_dues = self.repos.reminder.get_fee_due_items(school_id).await?;
+        let mut balances = self.repos.reconciliation.get_pending_balances(school_id).await?;
+
+        let (mut credit_lines, mut posted, mut review, mut duplicates) = (0, 0, 0, 0);
+        let mut occurrences: HashMap<String, usize> = HashMap::new();
+        for (idx, line) in parsed.lines.iter().enumerate() {
+            if line.credit <= 0.0 {
+                continue;
+            }
+            credit_lines += 1;
+
+            // Identical rows in one file are told apart by their position among the repeats,
+            // so re-importing an overlapping statement skips what was already seen.
+            let key = format!("{}|{:.2}|{}|{}", line.date, line.credit, line.narration.trim(), line.reference.trim());
+            let seen = occurrences.entry(key.clone()).or_insert(0);
+            *seen += 1;
+            let line_hash = hex::encode(Sha256::digest(format!("{}|{}", key, seen).as_bytes()));
+            if self.repos.reconciliation.statement_line_exists(school_id, &line_hash).await? {
+                duplicates += 1;
+                continue;
+            }
+
+            let mut record = json!({
+                "lineId": format!("BL{}-{}", import_id, idx + 1),
+                "importId": import_id,
+                "txnDate": line.date.to_string(),
+                "narration": line.narration,
+                "reference": line.reference,
+                "amount": line.credit,
+                "lineHash": line_hash,
+                "paymentMode": payment_mode(&line.narration, &line.reference),
+                "status": "review"
+            });
+
+            match match_line(line, &students, &custom_dues, &balances) {
+                Match::Student { student_id, method, fee_id } => {
+                    record["studentId"] = json!(student_id);
+                    record["matchMethod"] = json!(method);
+                    match self.allocate(school_id, &student_id, line.credit, fee_id.as_deref()).await {
+                        Ok(items) => {
+                            self.repos.reconciliation.add_statement_line(school_id, record.clone()).await?;
+                            match self.post_line(school_id, &record, &student_id, &items, method, "bank_import").await {
+                                Ok(_) => {
+                                    posted += 1;
+                                    // Keep later lines from matching a due this credit just settled
+                                    custom_dues = self.repos.reminder.get_fee_due_items(school_id).await?;
+                                    balances = self.repos.reconciliation.get_pending_balances(school_id).await?;
+                                }
+                                Err(e) => {
+                                    self.repos
+                                        .reconciliation
+                                        .update_statement_line(
+                                            school_id,
+                                            record["lineId"].as_str().unwrap_or(""),
+                                            json!({"status": "review", "reason": format!("Posting failed: {}", e)}),
+                                        )
+                                        .await?;
+                                    review += 1;
+                                }
+                            }
+                            continue;
+                        }
+                        Err(e) => record["reason"] = json!(e.to_string()),
+                    }
+                }
+                Match::Ambiguous { method, candidates } => {
+                    record["matchMethod"] = json!(method);
+                    record["candidates"] = json!(candidates);
+                    record["reason"] = json!("More than one student matches");
+                }
+                Match::Suggested { student_id, method } => {
+                    record["studentId"] = json!(student_id);
+                    record["matchMethod"] = json!(method);
+                    record["reason"] = json!("Only a short or all-digit student ID matched; check before posting");
+                }
+                Match::None => record["reason"] = json!("No matching student"),
+            }
+            self.repos.reconciliation.add_statement_line(school_id, record).await?;
+            review += 1;
+        }
+
+        let summary = json!({
+            "importId": import_id,
+            "fileName": file_name,
+            "bankFormat": parsed.format,
+            "totalLines": parsed.lines.len(),
+            "creditLines": credit_lines,
+            "posted": posted,
+            "review": review,
+            "duplicates": duplicates,
+            "totalCredit": parsed.lines.iter().map(|l| l.credit).sum::<f64>(),
+            "totalDebit": parsed.lines.iter().map(|l| l.debit).sum::<f64>(),
+            "importedBy": imported_by
+        });
+        self.repos.reconciliation.create_import(school_id, summary).await
+    }
+
+    async fn list_imports(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos.reconciliation.get_imports(school_id).await
+    }
+
+    async fn list_lines(
+        &self,
+        school_id: &str,
+        import_id: Option<String>,
+        status: Option<String>,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos
+            .reconciliation
+            .get_statement_lines(school_id, import_id, status)
+            .await
+    }
+
+    async fn resolve_line(
+        &self,
+        school_id: &str,
+        line_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut line = self
+            .repos
+            .reconciliation
+            .get_statement_line(school_id, line_id)
+            .await?
+            .ok_or("Statement line not found")?;
+        if line["status"] != "review" {
+            return Err(format!("Line is already {}", line["status"].as_str().unwrap_or("resolved")).into());
+        }
+        let resolved_by = data["resolvedBy"].as_str().unwrap_or("staff").to_string();
+
+        match data["action"].as_str().unwrap_or("post") {
+            "ignore" => {
+                self.repos
+                    .reconciliation
+                    .update_statement_line(
+                        school_id,
+                        line_id,
+                        json!({
+                            "status": "ignored",
+                            "reason": data["note"].as_str().unwrap_or("Not a fee payment"),
+                            "resolvedBy": resolved_by
+                        }),
+                    )
+                    .await?;
+            }
+            "post" => {
+                let student_id = data["studentId"]
+                    .as_str()
+                    .or_else(|| line["studentId"].as_str())
+                    .ok_or("studentId is required to post a line")?
+                    .to_string();
+                self.repos
+                    .student
+                    .get_student(school_id, &student_id)
+                    .await?
+                    .ok_or("Student not found")?;
+                let amount = line["amount"].as_f64().unwrap_or(0.0);
+                let items = match data["items"].as_array() {
+                    Some(items) if !items.is_empty() => {
+                        let total: f64 = items.iter().map(|i| i["amount"].as_f64().unwrap_or(0.0)).sum();
+                        if (total - amount).abs() >= 0.005 {
+                            return Err(format!("Items add up to {:.2} but the credit is {:.2}", total, amount).into());
+                        }
+                        items.clone()
+                    }
+                    _ => self.allocate(school_id, &student_id, amount, None).await?,
+                };
+                line["paymentMode"] = json!(payment_mode(
+                    line["narration"].as_str().unwrap_or(""),
+                    line["reference"].as_str().unwrap_or(""),
+                ));
+                self.post_line(school_id, &line, &student_id, &items, "manual", &resolved_by)
+                    .await?;
+            }
+            other => return Err(format!("Unknown action '{}'", other).into()),
+        }
+
+        self.repos
+            .reconciliation
+            .get_statement_line(school_id, line_id)
+            .await?
+            .ok_or_else(|| "Statement line not found".into())
+    }
+}
@@ -0,0 +1,245 @@
+use crate::repository::Repositories;
+use crate::services::traits::*;
+use crate::AppState;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+pub struct PostgresReminderService {
+    pub repos: Arc<Repositories>,
+    pub notifier: Arc<dyn NotificationService>,
+}
+
+/// Rules applied when a school has not configured its own:
+/// 7 days before the due date, on the due date, and weekly while overdue.
+fn default_rules() -> Vec<Value> {
+    vec![
+        json!({"ruleId": "default_before", "name": "7 days before due date", "offsetDays": -7, "repeatEveryDays": 0, "channel": "sms", "templateKey": "fee_due_reminder", "active": true}),
+        json!({"ruleId": "default_due", "name": "On due date", "offsetDays": 0, "repeatEveryDays": 0, "channel": "sms", "templateKey": "fee_due_reminder", "active": true}),
+        json!({"ruleId": "default_overdue", "name": "Weekly while overdue", "offsetDays": 7, "repeatEveryDays": 7, "channel": "sms", "templateKey": "fee_overdue_reminder", "active": true}),
+    ]
+}
+
+/// `days_from_due` is negative before the due date. A rule fires on its offset and then every
+/// `repeatEveryDays` after it.
+fn rule_fires(rule: &Value, days_from_due: i64) -> bool {
+    let offset = rule["offsetDays"].as_i64().unwrap_or(0);
+    let repeat = rule["repeatEveryDays"].as_i64().unwrap_or(0);
+    days_from_due == offset || (repeat > 0 && days_from_due > offset && (days_from_due - offset) % repeat == 0)
+}
+
+fn recipient_for(channel: &str, item: &Value) -> Option<String> {
+    let field = match channel {
+        "email" => "email",
+        "push" => "studentId",
+        _ => "contact",
+    };
+    item[field]
+        .as_str()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+#[async_trait]
+impl ReminderService for PostgresReminderService {
+    async fn create_reminder(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        self.repos.reminder.add_reminder(school_id, data).await
+    }
+
+    async fn list_reminders(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        self.repos.reminder.get_reminders(school_id).await
+    }
+
+    async fn list_reminder_rules(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, Box<dyn Error + Send + Sync>> {
+        let rules = self.repos.reminder.get_reminder_rules(school_id).await?;
+        if rules.is_empty() {
+            return Ok(default_rules());
+        }
+        Ok(rules)
+    }
+
+    async fn create_reminder_rule(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        if let Some(channel) = data["channel"].as_str() {
+            if !["sms", "whatsapp", "email", "push"].contains(&channel) {
+                return Err(format!("Unsupported channel '{}'", channel).into());
+            }
+        }
+        self.repos.reminder.add_reminder_rule(school_id, data).await
+    }
+
+    async fn delete_reminder_rule(
+        &self,
+        school_id: &str,
+        rule_id: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.repos.reminder.delete_reminder_rule(school_id, rule_id).await
+    }
+
+    async fn run_fee_reminders(
+        &self,
+        school_id: &str,
+        date: &str,
+        dry_run: bool,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let run_date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| "Invalid date, expected YYYY-MM-DD")?;
+        let rules: Vec<Value> = self
+            .list_reminder_rules(school_id)
+            .await?
+            .into_iter()
+            .filter(|r| r["active"].as_bool().unwrap_or(true))
+            .collect();
+        let items = self.repos.reminder.get_fee_due_items(school_id).await?;
+
+        // One message per student and channel, covering every fee a rule fires for today
+        let mut batches: BTreeMap<(String, String), (String, Vec<Value>)> = BTreeMap::new();
+        for item in items {
+            let due = match item["dueDate"]
+                .as_str()
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            {
+                Some(d) => d,
+                None => continue,
+            };
+            let days_from_due = (run_date - due).num_days();
+            for rule in rules.iter().filter(|r| rule_fires(r, days_from_due)) {
+                let channel = rule["channel"].as_str().unwrap_or("sms").to_string();
+                let template = rule["templateKey"].as_str().unwrap_or("fee_due_reminder");
+                let key = (item["studentId"].as_str().unwrap_or("").to_string(), channel);
+                let batch = batches
+                    .entry(key)
+                    .or_insert_with(|| (template.to_string(), Vec::new()));
+                // An overdue notice takes precedence over a plain due reminder
+                if days_from_due > 0 && template != "fee_due_reminder" {
+                    batch.0 = template.to_string();
+                }
+                if !batch.1.iter().any(|i| i["feeId"] == item["feeId"]) {
+                    batch.1.push(item.clone());
+                }
+            }
+        }
+
+        let school_name = self
+            .repos
+            .school
+            .get_school(school_id)
+            .await?
+            .and_then(|s| s["schoolName"].as_str().map(|n| n.to_string()))
+            .unwrap_or_default();
+        let link_base = std::env::var("FEE_PAYMENT_LINK_BASE")
+            .unwrap_or_else(|_| "https://pay.example.com/fees".to_string());
+
+        let mut results = Vec::new();
+        let (mut sent, mut skipped, mut failed) = (0, 0, 0);
+        for ((student_id, channel), (template_key, fees)) in batches {
+            let first = &fees[0];
+            let amount: f64 = fees.iter().map(|f| f["amount"].as_f64().unwrap_or(0.0)).sum();
+            let fee_names: Vec<&str> = fees.iter().filter_map(|f| f["feeName"].as_str()).collect();
+            let due_date = fees.iter().filter_map(|f| f["dueDate"].as_str()).min().unwrap_or("");
+            let vars = json!({
+                "studentName": first["studentName"],
+                "studentId": student_id,
+                "className": first["className"],
+                "amount": format!("{:.2}", amount),
+                "feeName": fee_names.join(", "),
+                "dueDate": due_date,
+                "schoolName": school_name,
+                "paymentLink": format!("{}/{}/{}", link_base.trim_end_matches('/'), school_id, student_id)
+            });
+
+            let recipient = match recipient_for(&channel, first) {
+                Some(r) => r,
+                None => {
+                    skipped += 1;
+                    results.push(json!({"studentId": student_id, "channel": channel, "status": "skipped", "reason": "no_recipient"}));
+                    continue;
+                }
+            };
+
+            if dry_run {
+                results.push(json!({"studentId": student_id, "channel": channel, "recipient": recipient, "templateKey": template_key, "vars": vars, "status": "preview"}));
+                continue;
+            }
+
+            let meta = json!({
+                "dedupeKey": format!("fee_reminder:{}:{}:{}", student_id, channel, date),
+                "refType": "fee_reminder",
+                "refId": student_id
+            });
+            let outcome = self
+                .notifier
+                .send_templated(school_id, &template_key, &channel, &recipient, &vars, meta)
+                .await?;
+            match outcome["status"].as_str() {
+                Some("sent") => sent += 1,
+                Some("failed") => failed += 1,
+                _ => skipped += 1,
+            }
+            results.push(json!({"studentId": student_id, "channel": channel, "recipient": recipient, "status": outcome["status"]}));
+        }
+
+        Ok(json!({
+            "date": date,
+            "dryRun": dry_run,
+            "sent": sent,
+            "skipped": skipped,
+            "failed": failed,
+            "results": results
+        }))
+    }
+}
+
+/// Daily job that sends fee due reminders for every active school.
+pub async fn start_fee_reminder_job(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(24 * 60 * 60));
+
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+            let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+            let schools = match state.repos.reminder.get_active_school_ids().await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::error!("[Fee Reminders] Failed to load schools: {}", e);
+                    continue;
+                }
+            };
+            for school_id in schools {
+                match state
+                    .services
+                    .reminder
+                    .run_fee_reminders(&school_id, &today, false)
+                    .await
+                {
+                    Ok(r) => tracing::info!(
+                        "[Fee Reminders] {}: {} sent, {} skipped, {} failed",
+                        school_id,
+                        r["sent"],
+                        r["skipped"],
+                        r["failed"]
+                    ),
+                    Err(e) => tracing::error!("[Fee Reminders] {} failed: {}", school_id, e),
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,303 @@
+use crate::repository::Repositories;
+use crate::services::traits::*;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::Arc;
+
+pub struct PostgresReportService {
+    pub repos: Arc<Repositories>,
+}
+
+fn sum_by(rows: &[Value], key: &str, amount_key: &str) -> Value {
+    let mut totals: BTreeMap<String, f64> = BTreeMap::new();
+    for r in rows {
+        let k = r[key].as_str().unwrap_or("Unassigned").to_string();
+        *totals.entry(k).or_insert(0.0) += r[amount_key].as_f64().unwrap_or(0.0);
+    }
+    json!(totals)
+}
+
+#[async_trait]
+impl ReportService for PostgresReportService {
+    async fn fee_day_book(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let entries = self.repos.report.get_fee_collections(school_id, from, to).await?;
+
+        // Group receipts into day / mode / collector lines
+        let mut days: BTreeMap<(String, String, String), (i64, f64)> = BTreeMap::new();
+        for e in &entries {
+            let key = (
+                e["paidOn"].as_str().unwrap_or("").to_string(),
+                e["paymentMode"].as_str().unwrap_or("cash").to_string(),
+                e["collectedBy"].as_str().unwrap_or("Unassigned").to_string(),
+            );
+            let line = days.entry(key).or_insert((0, 0.0));
+            line.0 += 1;
+            line.1 += e["amount"].as_f64().unwrap_or(0.0);
+        }
+        let summary: Vec<Value> = days
+            .into_iter()
+            .map(|((date, mode, collector), (count, amount))| {
+                json!({
+                    "date": date,
+                    "paymentMode": mode,
+                    "collectedBy": collector,
+                    "receipts": count,
+                    "amount": amount
+                })
+            })
+            .collect();
+
+        let total: f64 = entries.iter().filter_map(|e| e["amount"].as_f64()).sum();
+        Ok(json!({
+            "from": from,
+            "to": to,
+            "rows": entries,
+            "daily": summary,
+            "byMode": sum_by(&entries, "paymentMode", "amount"),
+            "byCollector": sum_by(&entries, "collectedBy", "amount"),
+            "totalReceipts": entries.len(),
+            "totalAmount": total
+        }))
+    }
+
+    async fn fee_collection_vs_demand(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let demand = self.repos.report.get_fee_demand(school_id, from, to).await?;
+        let collections = self.repos.report.get_fee_collections(school_id, from, to).await?;
+
+        // Matched by fee ID; the head is just the label, taken from the demand side when there is one
+        let mut lines: BTreeMap<(String, String), (String, f64, f64)> = BTreeMap::new();
+        let key = |v: &Value| {
+            (
+                v["className"].as_str().unwrap_or("").to_string(),
+                v["feeId"].as_str().unwrap_or("general").to_string(),
+            )
+        };
+        for d in &demand {
+            let line = lines.entry(key(d)).or_insert((String::new(), 0.0, 0.0));
+            line.0 = d["feeHead"].as_str().unwrap_or("General").to_string();
+            line.1 += d["demand"].as_f64().unwrap_or(0.0);
+        }
+        for c in &collections {
+            let line = lines.entry(key(c)).or_insert((String::new(), 0.0, 0.0));
+            if line.0.is_empty() {
+                line.0 = c["feeHead"].as_str().unwrap_or("General").to_string();
+            }
+            line.2 += c["amount"].as_f64().unwrap_or(0.0);
+        }
+
+        let (mut total_demand, mut total_collected) = (0.0, 0.0);
+        let rows: Vec<Value> = lines
+            .into_iter()
+            .map(|((class_name, fee_id), (fee_head, demand, collected))| {
+                total_demand += demand;
+                total_collected += collected;
+                json!({
+                    "className": class_name,
+                    "feeId": fee_id,
+                    "feeHead": fee_head,
+                    "demand": demand,
+                    "collected": collected,
+                    "balance": demand - collected,
+                    "collectionPercent": if demand > 0.0 { collected / demand * 100.0 } else { 0.0 }
+                })
+            })
+            .collect();
+
+        Ok(json!({
+            "from": from,
+            "to": to,
+            "rows": rows,
+            "totalDemand": total_demand,
+            "totalCollected": total_collected,
+            "totalBalance": total_demand - total_collected
+        }))
+    }
+
+    async fn fee_defaulters(
+        &self,
+        school_id: &str,
+        as_of: &str,
+        class_name: Option<String>,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let as_of_date = chrono::NaiveDate::parse_from_str(as_of, "%Y-%m-%d")
+            .map_err(|_| "asOf must be YYYY-MM-DD")?;
+        let defaulters = self.repos.report.get_fee_defaulters(school_id, as_of).await?;
+
+        let mut totals = [0.0f64; 4];
+        let mut rows = Vec::new();
+        for d in defaulters {
+            if let Some(ref c) = class_name {
+                if d["className"].as_str() != Some(c.as_str()) {
+                    continue;
+                }
+            }
+            // Buckets: 0-30, 31-60, 61-90, 90+ days since the charge fell due
+            let mut buckets = [0.0f64; 4];
+            let mut oldest: Option<chrono::NaiveDate> = None;
+            for c in d["charges"].as_array().cloned().unwrap_or_default() {
+                let amount = c["amount"].as_f64().unwrap_or(0.0);
+                let date = c["date"]
+                    .as_str()
+                    .and_then(|s| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").ok());
+                let days = date.map(|dt| (as_of_date - dt).num_days().max(0)).unwrap_or(0);
+                let idx = match days {
+                    0..=30 => 0,
+                    31..=60 => 1,
+                    61..=90 => 2,
+                    _ => 3,
+                };
+                buckets[idx] += amount;
+                if let Some(dt) = date {
+                    oldest = Some(oldest.map_or(dt, |o| o.min(dt)));
+                }
+            }
+            for (t, b) in totals.iter_mut().zip(buckets.iter()) {
+                *t += b;
+            }
+            rows.push(json!({
+                "studentId": d["studentId"],
+                "studentName": d["studentName"],
+                "className": d["className"],
+                "section": d["section"],
+                "contact": d["contact"],
+                "pendingAmount": d["pendingAmount"],
+                "days0To30": buckets[0],
+                "days31To60": buckets[1],
+                "days61To90": buckets[2],
+                "days90Plus": buckets[3],
+                "oldestDueDate": oldest.map(|o| o.to_string()),
+                "daysOverdue": oldest.map(|o| (as_of_date - o).num_days().max(0))
+            }));
+        }
+        rows.sort_by(|a, b| {
+            b["pendingAmount"]
+                .as_f64()
+                .unwrap_or(0.0)
+                .total_cmp(&a["pendingAmount"].as_f64().unwrap_or(0.0))
+        });
+
+        Ok(json!({
+            "asOf": as_of,
+            "rows": rows,
+            "totals": {
+                "days0To30": totals[0],
+                "days31To60": totals[1],
+                "days61To90": totals[2],
+                "days90Plus": totals[3],
+                "pendingAmount": totals.iter().sum::<f64>()
+            }
+        }))
+    }
+
+    async fn fee_concessions(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let rows = self.repos.report.get_fee_concessions(school_id, from, to).await?;
+        let total: f64 = rows.iter().filter_map(|r| r["amount"].as_f64()).sum();
+        Ok(json!({
+            "from": from,
+            "to": to,
+            "byType": sum_by(&rows, "type", "amount"),
+            "byClass": sum_by(&rows, "className", "amount"),
+            "rows": rows,
+            "totalAmount": total
+        }))
+    }
+
+    async fn referral_report(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let usage = self.repos.report.get_coupon_usage(school_id, from, to).await?;
+
+        // Coupons without a student/parent referrer are credited to their assigned employee
+        let mut referrers: BTreeMap<(String, String), Value> = BTreeMap::new();
+        let mut coupons: BTreeMap<String, Value> = BTreeMap::new();
+        for u in &usage {
+            let (kind, id) = match (u["referrerType"].as_str(), u["referrerId"].as_str()) {
+                (Some(t), Some(i)) => (t.to_string(), i.to_string()),
+                _ => match u["employeeId"].as_str() {
+                    Some(e) => ("employee".to_string(), e.to_string()),
+                    None => ("none".to_string(), String::new()),
+                },
+            };
+            let discount = u["discount"].as_f64().unwrap_or(0.0);
+            let reward = u["rewardPaid"].as_f64().unwrap_or(0.0);
+            let admission = u["isAdmission"].as_bool().unwrap_or(false) as i64;
+
+            let r = referrers.entry((kind.clone(), id.clone())).or_insert_with(|| {
+                json!({
+                    "referrerType": kind,
+                    "referrerId": id,
+                    "referrerName": u["referrerName"],
+                    "coupons": [],
+                    "uses": 0,
+                    "admissions": 0,
+                    "discountGiven": 0.0,
+                    "rewardsPaid": 0.0
+                })
+            });
+            r["uses"] = json!(r["uses"].as_i64().unwrap_or(0) + 1);
+            r["admissions"] = json!(r["admissions"].as_i64().unwrap_or(0) + admission);
+            r["discountGiven"] = json!(r["discountGiven"].as_f64().unwrap_or(0.0) + discount);
+            r["rewardsPaid"] = json!(r["rewardsPaid"].as_f64().unwrap_or(0.0) + reward);
+            if let Some(list) = r["coupons"].as_array_mut() {
+                if !list.contains(&u["couponName"]) {
+                    list.push(u["couponName"].clone());
+                }
+            }
+
+            let c = coupons
+                .entry(u["couponId"].as_str().unwrap_or("").to_string())
+                .or_insert_with(|| json!({"couponId": u["couponId"], "couponName": u["couponName"], "uses": 0, "admissions": 0, "discountGiven": 0.0}));
+            c["uses"] = json!(c["uses"].as_i64().unwrap_or(0) + 1);
+            c["admissions"] = json!(c["admissions"].as_i64().unwrap_or(0) + admission);
+            c["discountGiven"] = json!(c["discountGiven"].as_f64().unwrap_or(0.0) + discount);
+        }
+
+        let mut rows: Vec<Value> = referrers
+            .into_values()
+            .map(|mut r| {
+                let names: Vec<&str> = r["coupons"].as_array().map(|l| l.iter().filter_map(|c| c.as_str()).collect()).unwrap_or_default();
+                r["couponNames"] = json!(names.join(", "));
+                r
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            b["admissions"].as_i64().cmp(&a["admissions"].as_i64()).then(
+                b["discountGiven"].as_f64().unwrap_or(0.0).total_cmp(&a["discountGiven"].as_f64().unwrap_or(0.0)),
+            )
+        });
+
+        Ok(json!({
+            "from": from,
+            "to": to,
+            "rows": rows,
+            "byCoupon": coupons.into_values().collect::<Vec<_>>(),
+            "usage": usage,
+            "totals": {
+                "uses": usage.len(),
+                "admissions": usage.iter().filter(|u| u["isAdmission"].as_bool().unwrap_or(false)).count(),
+                "discountGiven": usage.iter().filter_map(|u| u["discount"].as_f64()).sum::<f64>(),
+                "rewardsPaid": usage.iter().filter_map(|u| u["rewardPaid"].as_f64()).sum::<f64>()
+            }
+        }))
+    }
+}
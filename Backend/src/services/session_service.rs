@@ -0,0 +1,427 @@
+use crate::logic::sessions::{normalize_outcome, suggest_next_class, validate_session, PROMOTION_OUTCOMES, SESSION_STATUSES};
+use crate::repository::Repositories;
+use crate::services::attendance_service::class_roster;
+use crate::services::traits::*;
+use async_trait::async_trait;
+use chrono::{NaiveDate, Utc};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+pub struct PostgresSessionService {
+    pub repos: Arc<Repositories>,
+}
+
+/// Refuses writes aimed at an archived session; `None` means the active one.
+pub(crate) async fn ensure_open_session(repos: &Repositories, school_id: &str, session_id: Option<&str>) -> Result<(), AppError> {
+    let Some(session_id) = session_id else { return Ok(()) };
+    let session = repos.sessions.get_session(school_id, session_id).await?.ok_or("Academic session not found")?;
+    if session["status"] == "archived" {
+        return Err(format!("Session {} is archived and read-only", session["name"].as_str().unwrap_or(session_id)).into());
+    }
+    Ok(())
+}
+
+/// Refuses changes to a stored class whose session is archived.
+pub(crate) async fn ensure_open_class(repos: &Repositories, school_id: &str, class_id: &str) -> Result<(), AppError> {
+    let classes = repos.academic.get_classes(school_id).await?;
+    let session_id = classes.iter().find(|c| c["id"] == class_id).and_then(|c| c["sessionId"].as_str());
+    ensure_open_session(repos, school_id, session_id).await
+}
+
+/// Refuses changes to a custom fee, or its records, once the fee's session is archived.
+pub(crate) async fn ensure_open_fee(repos: &Repositories, school_id: &str, fee_id: &str) -> Result<(), AppError> {
+    let fees = repos.operations.get_custom_fees(school_id).await?;
+    let Some(fee) = fees.iter().find(|f| f["feeId"] == fee_id) else { return Ok(()) };
+    if fee["status"] == "archived" {
+        return Err("This fee belongs to an archived session".into());
+    }
+    ensure_open_session(repos, school_id, fee["sessionId"].as_str()).await
+}
+
+/// Refuses attendance changes on a date that falls in an archived session.
+pub(crate) async fn ensure_open_date(repos: &Repositories, school_id: &str, date: NaiveDate) -> Result<(), AppError> {
+    ensure_open_dates(repos, school_id, &[date]).await
+}
+
+/// `ensure_open_date` for several days at once, reading the sessions a single time.
+pub(crate) async fn ensure_open_dates(repos: &Repositories, school_id: &str, dates: &[NaiveDate]) -> Result<(), AppError> {
+    let sessions = repos.sessions.get_sessions(school_id).await?;
+    for date in dates {
+        let day = date.to_string();
+        if let Some(s) = sessions.iter().find(|s| {
+            s["status"] == "archived"
+                && s["startDate"].as_str().is_some_and(|d| d <= day.as_str())
+                && s["endDate"].as_str().is_some_and(|d| d >= day.as_str())
+        }) {
+            return Err(format!("{} falls in session {}, which is archived and read-only", day, s["name"].as_str().unwrap_or("")).into());
+        }
+    }
+    Ok(())
+}
+
+/// Whether a class, fee or exam belongs to `session`. Classes without a session run every year;
+/// fees and exams without one predate sessions and count towards the active one.
+fn in_session(item: &Value, session: &Value, every_year: bool) -> bool {
+    match item["sessionId"].as_str() {
+        Some(id) => session["sessionId"] == id,
+        None => every_year || session["status"] == "active",
+    }
+}
+
+fn outcome_counts(enrollments: &[Value]) -> Value {
+    let mut counts: BTreeMap<&str, usize> = PROMOTION_OUTCOMES.iter().map(|o| (*o, 0)).collect();
+    for e in enrollments {
+        if let Some(o) = e["outcome"].as_str() {
+            *counts.entry(o).or_default() += 1;
+        }
+    }
+    json!(counts)
+}
+
+impl PostgresSessionService {
+    async fn session(&self, school_id: &str, session_id: &str) -> Result<Value, AppError> {
+        self.repos
+            .sessions
+            .get_session(school_id, session_id)
+            .await?
+            .ok_or_else(|| "Academic session not found".into())
+    }
+
+    async fn active_session(&self, school_id: &str, session_id: &str) -> Result<Value, AppError> {
+        let session = self.session(school_id, session_id).await?;
+        if session["status"] != "active" {
+            return Err("Only the active session can be rolled over".into());
+        }
+        Ok(session)
+    }
+
+    /// Another session whose dates overlap `start`..`end`, if any.
+    async fn overlapping(&self, school_id: &str, except: Option<&str>, start: &str, end: &str) -> Result<Option<Value>, AppError> {
+        Ok(self
+            .repos
+            .sessions
+            .get_sessions(school_id)
+            .await?
+            .into_iter()
+            .filter(|s| except.is_none_or(|id| s["sessionId"] != id))
+            .find(|s| s["startDate"].as_str() <= Some(end) && s["endDate"].as_str() >= Some(start)))
+    }
+
+    /// Classes this session's students sit in, and the classes they can move into next year
+    /// (every class not tied to this session).
+    async fn session_classes(&self, school_id: &str, session: &Value) -> Result<(Vec<Value>, Vec<Value>), AppError> {
+        let classes = self.repos.academic.get_classes(school_id).await?;
+        let current = classes.iter().filter(|c| in_session(c, session, true)).cloned().collect();
+        let next = classes.into_iter().filter(|c| c["sessionId"] != session["sessionId"]).collect();
+        Ok((current, next))
+    }
+}
+
+#[async_trait]
+impl SessionService for PostgresSessionService {
+    async fn list_sessions(&self, school_id: &str, status: Option<&str>) -> Result<Vec<Value>, AppError> {
+        if let Some(status) = status.filter(|s| !SESSION_STATUSES.contains(s)) {
+            return Err(format!("status must be one of {}, not {}", SESSION_STATUSES.join(", "), status).into());
+        }
+        let sessions = self.repos.sessions.get_sessions(school_id).await?;
+        Ok(sessions.into_iter().filter(|s| status.is_none_or(|st| s["status"] == st)).collect())
+    }
+
+    async fn create_session(&self, school_id: &str, data: Value) -> Result<Value, AppError> {
+        let (name, start, end) = validate_session(&data)?;
+        let (start, end) = (start.to_string(), end.to_string());
+        if let Some(other) = self.overlapping(school_id, None, &start, &end).await? {
+            return Err(format!("The dates overlap session {}", other["name"].as_str().unwrap_or("")).into());
+        }
+        // The school's first session starts out active; later ones wait for the rollover
+        let sessions = self.repos.sessions.get_sessions(school_id).await?;
+        let status = if sessions.iter().any(|s| s["status"] == "active") { "upcoming" } else { "active" };
+        if status == "active" && sessions.iter().any(|s| s["endDate"].as_str() > Some(start.as_str())) {
+            return Err("A new session must start after the archived ones".into());
+        }
+        self.repos
+            .sessions
+            .create_session(
+                school_id,
+                &json!({
+                    "sessionId": format!("AS{}", Utc::now().timestamp_millis()),
+                    "name": name,
+                    "startDate": start,
+                    "endDate": end,
+                    "status": status,
+                    "createdBy": data["createdBy"]
+                }),
+            )
+            .await
+    }
+
+    async fn get_session(&self, school_id: &str, session_id: &str) -> Result<Value, AppError> {
+        let mut session = self.session(school_id, session_id).await?;
+        let (classes, _) = self.session_classes(school_id, &session).await?;
+        let fees: Vec<Value> = self
+            .repos
+            .operations
+            .get_custom_fees(school_id)
+            .await?
+            .into_iter()
+            .filter(|f| in_session(f, &session, false))
+            .collect();
+        let exams: Vec<Value> = self
+            .repos
+            .academic
+            .get_exams(school_id)
+            .await?
+            .into_iter()
+            .filter(|e| in_session(e, &session, false))
+            .collect();
+        let enrollments = self.repos.sessions.get_session_enrollments(school_id, session_id).await?;
+        session["outcomes"] = outcome_counts(&enrollments);
+        session["classes"] = json!(classes);
+        session["fees"] = json!(fees);
+        session["exams"] = json!(exams);
+        session["enrollments"] = json!(enrollments);
+        Ok(session)
+    }
+
+    async fn update_session(&self, school_id: &str, session_id: &str, data: Value) -> Result<Value, AppError> {
+        let session = self.session(school_id, session_id).await?;
+        if session["status"] == "archived" {
+            return Err("Archived sessions are read-only".into());
+        }
+        let mut merged = session.clone();
+        for key in ["name", "startDate", "endDate"] {
+            if !data[key].is_null() {
+                merged[key] = data[key].clone();
+            }
+        }
+        let (name, start, end) = validate_session(&merged)?;
+        let (start, end) = (start.to_string(), end.to_string());
+        if let Some(other) = self.overlapping(school_id, Some(session_id), &start, &end).await? {
+            return Err(format!("The dates overlap session {}", other["name"].as_str().unwrap_or("")).into());
+        }
+        self.repos
+            .sessions
+            .update_session(school_id, session_id, &json!({"name": name, "startDate": start, "endDate": end}))
+            .await?
+            .ok_or_else(|| "Archived sessions are read-only".into())
+    }
+
+    async fn rollover_overview(&self, school_id: &str, session_id: &str) -> Result<Value, AppError> {
+        let session = self.active_session(school_id, session_id).await?;
+        let (classes, next_classes) = self.session_classes(school_id, &session).await?;
+        let decisions: HashMap<String, Value> = self
+            .repos
+            .sessions
+            .get_session_enrollments(school_id, session_id)
+            .await?
+            .into_iter()
+            .filter_map(|e| Some((e["studentId"].as_str()?.to_string(), e)))
+            .collect();
+        let balances: HashMap<String, f64> = self
+            .repos
+            .sessions
+            .get_session_fee_balances(school_id, session_id)
+            .await?
+            .into_iter()
+            .filter_map(|b| Some((b["studentId"].as_str()?.to_string(), b["balance"].as_f64()?)))
+            .collect();
+
+        let mut placed = Vec::new();
+        let mut rows = Vec::new();
+        let (mut total, mut decided) = (0, 0);
+        for class in &classes {
+            let class_id = class["id"].as_str().unwrap_or("");
+            let (_, students) = class_roster(&self.repos, school_id, class_id, None).await?;
+            let students: Vec<Value> = students
+                .iter()
+                .map(|s| {
+                    let id = s["studentId"].as_str().unwrap_or("");
+                    placed.push(id.to_string());
+                    let decision = decisions.get(id).filter(|d| d["classId"] == class_id && !d["outcome"].is_null());
+                    json!({
+                        "studentId": id,
+                        "name": s["name"],
+                        "section": s["section"],
+                        "rollNumber": s["rollNumber"],
+                        "pendingFees": balances.get(id).copied().unwrap_or(0.0),
+                        "outcome": decision.map(|d| d["outcome"].clone()),
+                        "nextClassId": decision.map(|d| d["nextClassId"].clone()),
+                        "nextSection": decision.map(|d| d["nextSection"].clone())
+                    })
+                })
+                .collect();
+            let class_decided = students.iter().filter(|s| !s["outcome"].is_null()).count();
+            total += students.len();
+            decided += class_decided;
+            let suggested = suggest_next_class(class, &next_classes);
+            rows.push(json!({
+                "classId": class_id,
+                "className": class["name"],
+                "suggestedNextClassId": suggested.map(|c| c["id"].clone()),
+                "suggestedNextClassName": suggested.map(|c| c["name"].clone()),
+                "students": students,
+                "decided": class_decided,
+                "complete": class_decided == students.len()
+            }));
+        }
+
+        // Active students whose class isn't one of this session's classes can't be promoted
+        let unassigned: Vec<Value> = self
+            .repos
+            .student
+            .get_students(school_id)
+            .await?
+            .into_iter()
+            .filter(|s| s["status"] != "inactive" && !placed.iter().any(|p| s["studentId"] == p.as_str()))
+            .map(|s| json!({"studentId": s["studentId"], "name": s["name"], "className": s["className"]}))
+            .collect();
+        let upcoming = self.list_sessions(school_id, Some("upcoming")).await?;
+        Ok(json!({
+            "session": session,
+            "upcomingSessions": upcoming,
+            "classes": rows,
+            "nextClasses": next_classes.iter().map(|c| json!({"classId": c["id"], "className": c["name"]})).collect::<Vec<_>>(),
+            "unassigned": unassigned,
+            "students": total,
+            "decided": decided,
+            "pendingFees": balances.values().sum::<f64>(),
+            "ready": decided == total && !upcoming.is_empty()
+        }))
+    }
+
+    async fn save_class_promotions(&self, school_id: &str, session_id: &str, class_id: &str, data: Value) -> Result<Value, AppError> {
+        let session = self.active_session(school_id, session_id).await?;
+        let (classes, next_classes) = self.session_classes(school_id, &session).await?;
+        let class = classes.iter().find(|c| c["id"] == class_id).ok_or("Class not found in this session")?;
+        let (_, students) = class_roster(&self.repos, school_id, class_id, None).await?;
+
+        let find_next = |id: &str| -> Result<&Value, AppError> {
+            next_classes
+                .iter()
+                .find(|c| c["id"] == id && c["id"] != class_id)
+                .ok_or_else(|| format!("{} isn't a class students can move into", id).into())
+        };
+        let class_next = match data["nextClassId"].as_str() {
+            Some(id) => Some(find_next(id)?),
+            None => suggest_next_class(class, &next_classes),
+        };
+        let default_outcome = match data["defaultOutcome"].as_str() {
+            Some(o) => normalize_outcome(o).ok_or_else(|| format!("defaultOutcome must be one of {}", PROMOTION_OUTCOMES.join(", ")))?,
+            // The top class passes out unless told otherwise
+            None if class_next.is_none() => "leave",
+            None => "pass",
+        };
+
+        let mut decisions: HashMap<&str, &Value> = HashMap::new();
+        for d in data["decisions"].as_array().into_iter().flatten() {
+            let id = d["studentId"].as_str().ok_or("Each decision needs a studentId")?;
+            if !students.iter().any(|s| s["studentId"] == id) {
+                return Err(format!("Student {} isn't on the roll of {}", id, class["name"].as_str().unwrap_or(class_id)).into());
+            }
+            decisions.insert(id, d);
+        }
+
+        let mut rows = Vec::new();
+        for s in &students {
+            let id = s["studentId"].as_str().unwrap_or("");
+            let decision = decisions.get(id);
+            let outcome = match decision.and_then(|d| d["outcome"].as_str()) {
+                Some(o) => normalize_outcome(o)
+                    .ok_or_else(|| format!("Outcome for {} must be one of {}", id, PROMOTION_OUTCOMES.join(", ")))?,
+                None => default_outcome,
+            };
+            let (next_class, next_section) = match outcome {
+                "pass" => {
+                    let next = match decision.and_then(|d| d["nextClassId"].as_str()) {
+                        Some(nid) => find_next(nid)?,
+                        None => class_next.ok_or_else(|| {
+                            format!("Choose the class {} students pass into", class["name"].as_str().unwrap_or(class_id))
+                        })?,
+                    };
+                    let section = decision
+                        .and_then(|d| d["nextSection"].as_str())
+                        .or(data["nextSection"].as_str())
+                        .or(s["section"].as_str());
+                    (Some(next["id"].clone()), section)
+                }
+                "detain" => (Some(json!(class_id)), s["section"].as_str()),
+                _ => (None, None),
+            };
+            rows.push(json!({
+                "studentId": id,
+                "classId": class_id,
+                "section": s["section"],
+                "rollNumber": s["rollNumber"],
+                "outcome": outcome,
+                "nextClassId": next_class,
+                "nextSection": next_section
+            }));
+        }
+        self.repos
+            .sessions
+            .save_promotions(school_id, session_id, &rows, data["decidedBy"].as_str())
+            .await?;
+        Ok(json!({
+            "classId": class_id,
+            "className": class["name"],
+            "decided": rows.len(),
+            "outcomes": outcome_counts(&rows),
+            "students": rows
+        }))
+    }
+
+    async fn complete_rollover(&self, school_id: &str, session_id: &str, data: Value) -> Result<Value, AppError> {
+        let session = self.active_session(school_id, session_id).await?;
+        let to_id = data["toSessionId"].as_str().ok_or("toSessionId is required")?;
+        let to = self.session(school_id, to_id).await?;
+        if to["status"] != "upcoming" {
+            return Err("Roll over into an upcoming session".into());
+        }
+        if to["startDate"].as_str() <= session["startDate"].as_str() {
+            return Err("The next session must start after this one".into());
+        }
+
+        // Every student on this session's rolls needs a decision first
+        let (classes, _) = self.session_classes(school_id, &session).await?;
+        let enrollments = self.repos.sessions.get_session_enrollments(school_id, session_id).await?;
+        let mut pending = Vec::new();
+        for class in &classes {
+            let class_id = class["id"].as_str().unwrap_or("");
+            let (_, students) = class_roster(&self.repos, school_id, class_id, None).await?;
+            let missing = students
+                .iter()
+                .filter(|s| !enrollments.iter().any(|e| e["studentId"] == s["studentId"] && e["classId"] == class_id && !e["outcome"].is_null()))
+                .count();
+            if missing > 0 {
+                pending.push(format!("{} in {}", missing, class["name"].as_str().unwrap_or(class_id)));
+            }
+        }
+        if !pending.is_empty() {
+            return Err(format!("Students still need a decision: {}", pending.join(", ")).into());
+        }
+
+        let summary = self
+            .repos
+            .sessions
+            .complete_rollover(
+                school_id,
+                session_id,
+                to_id,
+                &json!({
+                    "feeId": format!("CF{}", Utc::now().timestamp_millis()),
+                    "feeName": format!("Balance brought forward ({})", session["name"].as_str().unwrap_or("")),
+                    "carryBalances": data["carryBalances"].as_bool().unwrap_or(true),
+                    "completedBy": data["completedBy"]
+                }),
+            )
+            .await?
+            .ok_or("The sessions changed during the rollover; reload and try again")?;
+        Ok(json!({
+            "from": self.session(school_id, session_id).await?,
+            "to": self.session(school_id, to_id).await?,
+            "outcomes": outcome_counts(&enrollments),
+            "summary": summary
+        }))
+    }
+}
@@ -0,0 +1,431 @@
+use crate::logic::attendance::correction_locked;
+use crate::logic::holidays::academic_year_range;
+use crate::logic::subject_attendance::{normalize_period_status, tally, takes_subject, SubjectTally, PERIOD_STATUSES};
+use crate::repository::Repositories;
+use crate::services::attendance_service::{attendance_settings, class_roster, in_class};
+use crate::services::holiday_service::load_calendar;
+use crate::services::session_service::ensure_open_date;
+use crate::services::traits::*;
+use async_trait::async_trait;
+use chrono::{Datelike, Local, NaiveDate, Utc};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+pub struct PostgresSubjectAttendanceService {
+    pub repos: Arc<Repositories>,
+}
+
+fn parse_date(v: &str, field: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(v.trim(), "%Y-%m-%d").map_err(|_| format!("{} must be YYYY-MM-DD", field).into())
+}
+
+/// `from`..`to`, defaulting to the start of the current academic year (April) through today.
+fn report_range(from: Option<&str>, to: Option<&str>) -> Result<(NaiveDate, NaiveDate), AppError> {
+    let today = Local::now().date_naive();
+    let from = match from.filter(|f| !f.is_empty()) {
+        Some(f) => parse_date(f, "from")?,
+        None => {
+            let start = if today.month() >= 4 { today.year() } else { today.year() - 1 };
+            academic_year_range(&start.to_string()).map(|(from, _)| from).unwrap_or(today)
+        }
+    };
+    let to = match to.filter(|t| !t.is_empty()) {
+        Some(t) => parse_date(t, "to")?,
+        None => today,
+    };
+    if to < from {
+        return Err("to must not be before from".into());
+    }
+    if (to - from).num_days() > 366 {
+        return Err("The range can't be longer than a year".into());
+    }
+    Ok((from, to))
+}
+
+fn subject_name(subjects: &HashMap<String, Value>, subject_id: &str) -> String {
+    subjects
+        .get(subject_id)
+        .and_then(|s| s["name"].as_str())
+        .unwrap_or(if subject_id.is_empty() { "No subject" } else { subject_id })
+        .to_string()
+}
+
+impl PostgresSubjectAttendanceService {
+    /// The period, checked to run on `date`'s weekday.
+    async fn period_on(&self, school_id: &str, period_id: i32, date: NaiveDate) -> Result<Value, AppError> {
+        let period = self
+            .repos
+            .timetable
+            .get_periods(school_id, None)
+            .await?
+            .into_iter()
+            .find(|p| p["periodId"] == period_id)
+            .ok_or("Period not found")?;
+        let weekday = date.weekday().number_from_monday() as i64;
+        if period["dayOfWeek"].as_i64().is_some_and(|d| d != weekday) {
+            return Err(format!("{} doesn't run on {}", period["name"].as_str().unwrap_or("This period"), date.format("%A")).into());
+        }
+        Ok(period)
+    }
+
+    async fn subjects_by_id(&self, school_id: &str) -> Result<HashMap<String, Value>, AppError> {
+        Ok(self
+            .repos
+            .academic
+            .get_subjects(school_id)
+            .await?
+            .into_iter()
+            .filter_map(|s| Some((s["id"].as_str()?.to_string(), s)))
+            .collect())
+    }
+
+    /// The class roster narrowed to the students who sit `period`'s subject.
+    async fn period_roster(&self, school_id: &str, period: &Value) -> Result<(Value, Vec<Value>), AppError> {
+        let (class, students) = class_roster(&self.repos, school_id, period["classId"].as_str().unwrap_or(""), None).await?;
+        let subjects = self.subjects_by_id(school_id).await?;
+        let subject = period["subjectId"].as_str().and_then(|id| subjects.get(id));
+        Ok((class, students.into_iter().filter(|s| takes_subject(s, subject)).collect()))
+    }
+
+    /// Per-student subject tallies for `students` against `records`, as report rows. Subjects a
+    /// student doesn't take and has no marks in are left out of their row.
+    fn subject_rows(
+        students: &[Value],
+        records: &[Value],
+        subject_ids: &BTreeSet<String>,
+        subjects: &HashMap<String, Value>,
+        threshold: f64,
+    ) -> Vec<Value> {
+        let tallies = tally(records);
+        students
+            .iter()
+            .map(|s| {
+                let student_id = s["studentId"].as_str().unwrap_or("");
+                let mut overall = SubjectTally::default();
+                let mut row = json!({
+                    "studentId": student_id,
+                    "name": s["name"],
+                    "rollNumber": s["rollNumber"],
+                    "className": s["className"],
+                    "section": s["section"]
+                });
+                let mut by_subject = serde_json::Map::new();
+                for subject_id in subject_ids {
+                    let counts = tallies.get(&(student_id.to_string(), subject_id.clone()));
+                    if counts.is_none() && !takes_subject(s, subjects.get(subject_id)) {
+                        continue;
+                    }
+                    let counts = counts.copied().unwrap_or_default();
+                    overall.present += counts.present;
+                    overall.late += counts.late;
+                    overall.absent += counts.absent;
+                    overall.excused += counts.excused;
+                    let mut entry = counts.to_json(threshold);
+                    entry["subjectName"] = json!(subject_name(subjects, subject_id));
+                    row[format!("pct_{}", subject_id)] = if counts.held() > 0 { json!(counts.percent()) } else { Value::Null };
+                    by_subject.insert(subject_id.clone(), entry);
+                }
+                row["subjects"] = Value::Object(by_subject);
+                row["overallPercent"] = json!(overall.percent());
+                row
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl SubjectAttendanceService for PostgresSubjectAttendanceService {
+    async fn period_attendance(&self, school_id: &str, period_id: i32, date: &str) -> Result<Value, AppError> {
+        let date = parse_date(date, "date")?;
+        let period = self.period_on(school_id, period_id, date).await?;
+        let (class, students) = self.period_roster(school_id, &period).await?;
+        let existing = self.repos.attendance.get_period_attendance(school_id, date, period_id).await?;
+        let substitute = self
+            .repos
+            .timetable
+            .get_substitutions(school_id, date, date, None)
+            .await?
+            .into_iter()
+            .find(|s| s["periodId"] == period_id)
+            .map(|s| s["substituteId"].clone());
+        let settings = attendance_settings(&self.repos, school_id).await?;
+        let taken_at = existing.iter().filter_map(|r| r["createdAt"].as_str()).min();
+
+        let by_id: HashMap<&str, &Value> = existing.iter().filter_map(|r| Some((r["studentId"].as_str()?, r))).collect();
+        let sheet: Vec<Value> = students
+            .iter()
+            .map(|s| {
+                let row = s["studentId"].as_str().and_then(|id| by_id.get(id));
+                json!({
+                    "studentId": s["studentId"],
+                    "name": s["name"],
+                    "rollNumber": s["rollNumber"],
+                    "section": s["section"],
+                    "status": row.map(|r| r["status"].clone()).unwrap_or(Value::Null),
+                    "remarks": row.map(|r| r["remarks"].clone()).unwrap_or(Value::Null)
+                })
+            })
+            .collect();
+        let calendar = load_calendar(&self.repos, school_id, date, date).await?;
+        Ok(json!({
+            "date": date.to_string(),
+            "period": period,
+            "className": class["name"],
+            "substituteId": substitute,
+            "holiday": calendar.off_reason(date),
+            "takenAt": taken_at,
+            "editable": !correction_locked(&settings, taken_at, Utc::now()),
+            "students": sheet
+        }))
+    }
+
+    async fn mark_period_attendance(&self, school_id: &str, period_id: i32, date: &str, data: Value) -> Result<Value, AppError> {
+        let date = parse_date(date, "date")?;
+        if date > Local::now().date_naive() {
+            return Err("Attendance can't be marked for a future date".into());
+        }
+        ensure_open_date(&self.repos, school_id, date).await?;
+        if let Some(reason) = load_calendar(&self.repos, school_id, date, date).await?.off_reason(date) {
+            return Err(format!("Cannot mark attendance on {}", reason).into());
+        }
+        let period = self.period_on(school_id, period_id, date).await?;
+        let marked_by = data["markedBy"].as_str();
+        let invalid = || format!("use {}", PERIOD_STATUSES.join(", "));
+        let default_status = match data["defaultStatus"].as_str() {
+            Some(s) => Some(normalize_period_status(s).ok_or_else(|| format!("Invalid defaultStatus; {}", invalid()))?),
+            None => None,
+        };
+
+        let (_, students) = self.period_roster(school_id, &period).await?;
+        let ids: Vec<String> = students.iter().filter_map(|s| s["studentId"].as_str().map(|s| s.to_string())).collect();
+
+        // Validate every entry before writing anything
+        let mut marks: HashMap<String, (&'static str, Option<String>)> = HashMap::new();
+        let mut unknown = Vec::new();
+        for e in data["entries"].as_array().ok_or("entries is required")? {
+            let id = e["studentId"].as_str().ok_or("Each entry needs a studentId")?;
+            if !ids.iter().any(|s| s == id) {
+                unknown.push(id.to_string());
+                continue;
+            }
+            let status = normalize_period_status(e["status"].as_str().unwrap_or(""))
+                .ok_or_else(|| format!("Invalid status for {}; {}", id, invalid()))?;
+            let remarks = e["remarks"].as_str().filter(|r| !r.trim().is_empty()).map(|r| r.to_string());
+            marks.insert(id.to_string(), (status, remarks));
+        }
+        if !unknown.is_empty() {
+            return Err(format!("Not taking this period: {}", unknown.join(", ")).into());
+        }
+        if let Some(status) = default_status {
+            for id in &ids {
+                marks.entry(id.clone()).or_insert((status, None));
+            }
+        }
+        if marks.is_empty() {
+            return Err("No attendance entries to save".into());
+        }
+
+        let existing = self.repos.attendance.get_period_attendance(school_id, date, period_id).await?;
+        let previous: HashMap<&str, &Value> = existing.iter().filter_map(|r| Some((r["studentId"].as_str()?, r))).collect();
+        let mut rows = Vec::new();
+        let mut history = Vec::new();
+        for id in &ids {
+            let Some((status, remarks)) = marks.get(id) else { continue };
+            let before = previous.get(id.as_str());
+            if before.is_some_and(|b| b["status"] == *status && b["remarks"].as_str() == remarks.as_deref()) {
+                continue;
+            }
+            rows.push(json!({"studentId": id, "status": status, "remarks": remarks, "markedBy": marked_by}));
+            history.push(json!({
+                "userId": id,
+                "action": if before.is_some() { "period_correction" } else { "period_mark" },
+                "data": {
+                    "date": date.to_string(),
+                    "periodId": period_id,
+                    "subjectId": period["subjectId"],
+                    "status": status,
+                    "previousStatus": before.map(|b| b["status"].clone()),
+                    "remarks": remarks,
+                    "markedBy": marked_by
+                }
+            }));
+        }
+
+        // Changing marks already taken is only allowed inside the correction window
+        let corrections = history.iter().filter(|h| h["action"] == "period_correction").count();
+        let taken_at = existing.iter().filter_map(|r| r["createdAt"].as_str()).min();
+        if corrections > 0 && correction_locked(&attendance_settings(&self.repos, school_id).await?, taken_at, Utc::now()) {
+            return Err(format!(
+                "Attendance for this period on {} was taken at {} and the correction window has closed",
+                date,
+                taken_at.unwrap_or_default()
+            )
+            .into());
+        }
+
+        if !rows.is_empty() {
+            self.repos
+                .attendance
+                .save_period_attendance(school_id, &period, date, &rows, &history)
+                .await?;
+        }
+        let count = |s: &str| ids.iter().filter(|id| marks.get(*id).map(|m| m.0).or(previous.get(id.as_str()).and_then(|r| r["status"].as_str())) == Some(s)).count();
+        Ok(json!({
+            "date": date.to_string(),
+            "periodId": period_id,
+            "classId": period["classId"],
+            "subjectId": period["subjectId"],
+            "summary": {
+                "total": ids.len(),
+                "present": count("present"),
+                "late": count("late"),
+                "absent": count("absent"),
+                "excused": count("excused"),
+                "saved": rows.len() - corrections,
+                "corrected": corrections,
+                "unchanged": marks.len() - rows.len()
+            },
+            "unmarked": ids.iter().filter(|id| !marks.contains_key(*id) && !previous.contains_key(id.as_str())).collect::<Vec<_>>()
+        }))
+    }
+
+    async fn subject_report(
+        &self,
+        school_id: &str,
+        class_id: &str,
+        section: Option<&str>,
+        (from, to): (Option<&str>, Option<&str>),
+    ) -> Result<Value, AppError> {
+        let (from, to) = report_range(from, to)?;
+        let (class, students) = class_roster(&self.repos, school_id, class_id, section).await?;
+        let records = self.repos.attendance.get_period_attendance_range(school_id, Some(class_id), from, to).await?;
+        let subjects = self.subjects_by_id(school_id).await?;
+        let threshold = attendance_settings(&self.repos, school_id).await?["subjectAttendanceThreshold"].as_f64().unwrap_or(75.0);
+
+        // Subjects on the class timetable plus any that were marked under it before a change
+        let mut subject_ids: BTreeSet<String> = self
+            .repos
+            .timetable
+            .get_periods(school_id, None)
+            .await?
+            .iter()
+            .filter(|p| p["classId"] == class_id)
+            .filter_map(|p| p["subjectId"].as_str().map(|s| s.to_string()))
+            .collect();
+        subject_ids.extend(records.iter().filter_map(|r| r["subjectId"].as_str().map(|s| s.to_string())));
+
+        let rows = Self::subject_rows(&students, &records, &subject_ids, &subjects, threshold);
+        let summary: Vec<Value> = subject_ids
+            .iter()
+            .map(|id| {
+                let held: BTreeSet<(&str, i64)> = records
+                    .iter()
+                    .filter(|r| r["subjectId"] == id.as_str())
+                    .filter_map(|r| Some((r["date"].as_str()?, r["periodId"].as_i64()?)))
+                    .collect();
+                let percents: Vec<f64> = rows
+                    .iter()
+                    .filter(|r| r["subjects"][id]["held"].as_u64().unwrap_or(0) > 0)
+                    .filter_map(|r| r["subjects"][id]["attendancePercent"].as_f64())
+                    .collect();
+                let average = if percents.is_empty() { 0.0 } else { (percents.iter().sum::<f64>() * 100.0 / percents.len() as f64).round() / 100.0 };
+                json!({
+                    "subjectId": id,
+                    "subjectName": subject_name(&subjects, id),
+                    "periodsHeld": held.len(),
+                    "averagePercent": average,
+                    "belowThreshold": rows.iter().filter(|r| r["subjects"][id]["belowThreshold"] == true).count()
+                })
+            })
+            .collect();
+        Ok(json!({
+            "from": from.to_string(),
+            "to": to.to_string(),
+            "classId": class["id"],
+            "className": class["name"],
+            "section": section,
+            "threshold": threshold,
+            "subjects": summary,
+            "rows": rows
+        }))
+    }
+
+    async fn eligibility_report(
+        &self,
+        school_id: &str,
+        class_id: Option<&str>,
+        section: Option<&str>,
+        (from, to): (Option<&str>, Option<&str>),
+        threshold: Option<f64>,
+    ) -> Result<Value, AppError> {
+        let (from, to) = report_range(from, to)?;
+        let threshold = match threshold {
+            Some(t) => t,
+            None => attendance_settings(&self.repos, school_id).await?["subjectAttendanceThreshold"].as_f64().unwrap_or(75.0),
+        };
+        if !(0.0..=100.0).contains(&threshold) {
+            return Err("threshold must be a percentage between 0 and 100".into());
+        }
+        let classes: Vec<Value> = match class_id {
+            Some(id) => vec![class_roster(&self.repos, school_id, id, None).await?.0],
+            None => self.repos.academic.get_classes(school_id).await?,
+        };
+        let students = self.repos.student.get_students(school_id).await?;
+        let records = self.repos.attendance.get_period_attendance_range(school_id, class_id, from, to).await?;
+        let subjects = self.subjects_by_id(school_id).await?;
+
+        let mut rows = Vec::new();
+        let mut checked = 0;
+        let mut by_subject: BTreeMap<String, usize> = BTreeMap::new();
+        for class in &classes {
+            let id = class["id"].as_str().or(class["classId"].as_str()).unwrap_or("");
+            let class_records: Vec<Value> = records.iter().filter(|r| r["classId"] == id).cloned().collect();
+            if class_records.is_empty() {
+                continue;
+            }
+            let roster: Vec<Value> = students.iter().filter(|s| in_class(s, class, section)).cloned().collect();
+            let subject_ids: BTreeSet<String> = class_records.iter().filter_map(|r| r["subjectId"].as_str().map(|s| s.to_string())).collect();
+            checked += roster.len();
+            for student in Self::subject_rows(&roster, &class_records, &subject_ids, &subjects, threshold) {
+                for (subject_id, entry) in student["subjects"].as_object().into_iter().flatten() {
+                    if entry["belowThreshold"] != true {
+                        continue;
+                    }
+                    *by_subject.entry(entry["subjectName"].as_str().unwrap_or("").to_string()).or_default() += 1;
+                    rows.push(json!({
+                        "studentId": student["studentId"],
+                        "name": student["name"],
+                        "rollNumber": student["rollNumber"],
+                        "className": class["name"],
+                        "section": student["section"],
+                        "subjectId": subject_id,
+                        "subjectName": entry["subjectName"],
+                        "held": entry["held"],
+                        "attended": entry["attended"],
+                        "excused": entry["excused"],
+                        "attendancePercent": entry["attendancePercent"],
+                        "periodsNeeded": entry["periodsNeeded"]
+                    }));
+                }
+            }
+        }
+        rows.sort_by(|a, b| {
+            a["attendancePercent"].as_f64().unwrap_or(0.0).total_cmp(&b["attendancePercent"].as_f64().unwrap_or(0.0))
+        });
+        let students_below: BTreeSet<&str> = rows.iter().filter_map(|r| r["studentId"].as_str()).collect();
+        Ok(json!({
+            "from": from.to_string(),
+            "to": to.to_string(),
+            "classId": class_id,
+            "section": section,
+            "threshold": threshold,
+            "summary": {
+                "studentsChecked": checked,
+                "studentsBelow": students_below.len(),
+                "bySubject": by_subject
+            },
+            "rows": rows
+        }))
+    }
+}
@@ -0,0 +1,582 @@
+use crate::logic::substitution::{propose, SubstitutionDay};
+use crate::logic::timetable::{clashes, generate, validate_config, week_grid, TimetableInput};
+use crate::repository::Repositories;
+use crate::services::payroll_service::employee_name;
+use crate::services::traits::*;
+use async_trait::async_trait;
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+pub struct PostgresTimetableService {
+    pub repos: Arc<Repositories>,
+    pub notifier: Arc<dyn NotificationService>,
+}
+
+/// Candidates listed per uncovered period
+const MAX_CANDIDATES: usize = 5;
+
+fn parse_date(v: &str, field: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(v, "%Y-%m-%d").map_err(|_| format!("{} must be YYYY-MM-DD", field).into())
+}
+
+/// Monday to Sunday of the week containing `date`; substitution load is balanced per week.
+fn week_of(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let monday = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+    (monday, monday + Duration::days(6))
+}
+
+fn load_by_teacher(substitutions: &[Value]) -> HashMap<String, i64> {
+    let mut load = HashMap::new();
+    for s in substitutions {
+        if let Some(id) = s["substituteId"].as_str() {
+            *load.entry(id.to_string()).or_insert(0) += 1;
+        }
+    }
+    load
+}
+
+/// Everything needed to propose substitutes on one date.
+struct DayContext {
+    date: NaiveDate,
+    periods: Vec<Value>,
+    all_periods: Vec<Value>,
+    teachers: Vec<Value>,
+    absent: HashMap<String, String>,
+    assigned: Vec<Value>,
+    load: HashMap<String, i64>,
+}
+
+impl DayContext {
+    fn day(&self) -> SubstitutionDay<'_> {
+        SubstitutionDay {
+            periods: &self.periods,
+            all_periods: &self.all_periods,
+            teachers: &self.teachers,
+            absent: &self.absent,
+            assigned: &self.assigned,
+            load: &self.load,
+        }
+    }
+}
+
+impl PostgresTimetableService {
+    async fn with_class_names(&self, school_id: &str, mut periods: Vec<Value>) -> Result<Vec<Value>, AppError> {
+        let classes = self.repos.academic.get_classes(school_id).await?;
+        for p in periods.iter_mut() {
+            let name = classes.iter().find(|c| c["classId"] == p["classId"]).map(|c| c["name"].clone());
+            p["className"] = name.unwrap_or(Value::Null);
+        }
+        Ok(periods)
+    }
+
+    async fn load_day(&self, school_id: &str, date: NaiveDate) -> Result<DayContext, AppError> {
+        let weekday = date.weekday().number_from_monday() as i32;
+        let periods = self.with_class_names(school_id, self.repos.timetable.get_periods(school_id, Some(weekday)).await?).await?;
+        let all_periods = self.repos.timetable.get_periods(school_id, None).await?;
+
+        // Teaching staff: anyone typed as a teacher, plus anyone who has a period
+        let teachers: Vec<Value> = self
+            .repos
+            .employee
+            .get_employees(school_id)
+            .await?
+            .into_iter()
+            .filter(|e| e["status"] != "inactive")
+            .filter(|e| {
+                let employee_type = e["employeeType"].as_str().or(e["type"].as_str()).unwrap_or("").to_lowercase();
+                employee_type.contains("teach") || all_periods.iter().any(|p| p["teacherId"] == e["employeeId"])
+            })
+            .map(|mut e| {
+                e["name"] = json!(employee_name(&e));
+                e
+            })
+            .collect();
+
+        let absent = self
+            .repos
+            .timetable
+            .get_absent_employees(school_id, date)
+            .await?
+            .into_iter()
+            .filter_map(|a| {
+                let id = a["employeeId"].as_str()?.to_string();
+                let reason = if a["status"] == "leave" { a["reason"].as_str().unwrap_or("leave") } else { "absent" };
+                Some((id, reason.to_string()))
+            })
+            .collect();
+
+        let (week_start, week_end) = week_of(date);
+        let week = self.repos.timetable.get_substitutions(school_id, week_start, week_end, None).await?;
+        let load = load_by_teacher(&week);
+        let assigned = week.into_iter().filter(|s| s["date"] == date.to_string()).collect();
+        Ok(DayContext { date, periods, all_periods, teachers, absent, assigned, load })
+    }
+
+    async fn notify_substitute(&self, school_id: &str, substitution: &Value, period: &Value, teacher: Option<&Value>) {
+        let substitution_id = substitution["substitutionId"].as_str().unwrap_or("");
+        let vars = json!({
+            "employeeName": teacher.map(employee_name).unwrap_or_default(),
+            "date": substitution["date"],
+            "periodName": period["name"].as_str().unwrap_or("period"),
+            "className": period["className"].as_str().or(period["classId"].as_str()).unwrap_or(""),
+            "subjectName": period["subjectName"].as_str().or(period["subjectId"].as_str()).unwrap_or(""),
+            "startTime": substitution["startTime"],
+            "endTime": substitution["endTime"]
+        });
+        let meta = json!({
+            "dedupeKey": format!("substitution:{}", substitution_id),
+            "refType": "substitution",
+            "refId": substitution_id
+        });
+        if let Err(e) = self
+            .notifier
+            .send_templated(school_id, "substitution_assigned", "push", substitution["substituteId"].as_str().unwrap_or(""), &vars, meta)
+            .await
+        {
+            tracing::warn!("Substitution notification for {} failed: {}", substitution_id, e);
+        }
+    }
+}
+
+/// Section names from a class record; older records keep plain strings, newer ones `{sectionName}`.
+fn class_sections(class: &Value) -> Vec<String> {
+    class["sections"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s.as_str().or(s["sectionName"].as_str()).map(|s| s.trim().to_string()))
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// What a pin must say: the class and section, the slot, and the subject.
+fn check_pin(pin: &Value, config: &Value) -> Result<Value, AppError> {
+    let class_id = pin["classId"].as_str().filter(|c| !c.is_empty()).ok_or("Each pin needs a classId")?;
+    let subject_id = pin["subjectId"].as_str().filter(|s| !s.is_empty()).ok_or("Each pin needs a subjectId")?;
+    let day = pin["day"].as_u64().filter(|d| config["days"].as_array().is_some_and(|days| days.contains(&json!(d))));
+    let slots = config["slots"].as_array().map(|s| s.len() as u64).unwrap_or(0);
+    let slot = pin["slot"].as_u64().filter(|s| (1..=slots).contains(s));
+    let (Some(day), Some(slot)) = (day, slot) else {
+        return Err(format!("Pin for {} must be on a teaching day and slot 1 to {}", class_id, slots).into());
+    };
+    let opt = |k: &str| pin[k].as_str().filter(|v| !v.is_empty());
+    Ok(json!({
+        "classId": class_id,
+        "section": opt("section"),
+        "day": day,
+        "slot": slot,
+        "subjectId": subject_id,
+        "teacherId": opt("teacherId"),
+        "roomId": opt("roomId")
+    }))
+}
+
+impl PostgresTimetableService {
+    /// The class groups the settings cover: the listed classes, or every class, one per section.
+    async fn timetable_groups(&self, school_id: &str, config: &Value) -> Result<Vec<Value>, AppError> {
+        let classes = self.repos.academic.get_classes(school_id).await?;
+        let listed = config["classes"].as_array().cloned().unwrap_or_default();
+        let mut groups = Vec::new();
+        for class in &classes {
+            let class_id = class["id"].as_str().unwrap_or("");
+            let wanted: Vec<&Value> = listed.iter().filter(|c| c["classId"] == class_id).collect();
+            if !listed.is_empty() && wanted.is_empty() {
+                continue;
+            }
+            // A class listed with sections covers just those sections
+            let named: Vec<String> = wanted.iter().filter_map(|c| c["section"].as_str().map(|s| s.to_string())).collect();
+            let sections = if named.is_empty() { class_sections(class) } else { named };
+            let group = |section: Option<&str>| json!({"classId": class_id, "className": class["name"], "section": section});
+            if sections.is_empty() {
+                groups.push(group(None));
+            } else {
+                groups.extend(sections.iter().map(|s| group(Some(s))));
+            }
+        }
+        let missing: Vec<&str> = listed
+            .iter()
+            .filter_map(|c| c["classId"].as_str())
+            .filter(|id| !classes.iter().any(|c| c["id"] == *id))
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("Unknown classes: {}", missing.join(", ")).into());
+        }
+        Ok(groups)
+    }
+
+    /// Every class, subject, teacher and room the settings name must exist.
+    async fn check_references(&self, school_id: &str, config: &Value) -> Result<(), AppError> {
+        let classes = self.repos.academic.get_classes(school_id).await?;
+        let subjects = self.repos.academic.get_subjects(school_id).await?;
+        let employees = self.repos.employee.get_employees(school_id).await?;
+        let rooms = self.repos.resource.get_spaces(school_id).await?;
+        let mut unknown = BTreeSet::new();
+        let lists = ["classes", "requirements", "fixed", "teachers"];
+        for item in lists.iter().flat_map(|k| config[*k].as_array().cloned().unwrap_or_default()) {
+            let checks: [(&str, &str, &[Value]); 4] = [
+                ("classId", "class", &classes),
+                ("subjectId", "subject", &subjects),
+                ("teacherId", "teacher", &employees),
+                ("roomId", "room", &rooms),
+            ];
+            for (key, what, list) in checks {
+                let Some(id) = item[key].as_str() else { continue };
+                let found = list.iter().any(|v| v["id"] == id || v["employeeId"] == id || v["spaceId"] == id);
+                if !found {
+                    unknown.insert(format!("{} {}", what, id));
+                }
+            }
+            if let Some(id) = item["employeeId"].as_str() {
+                if !employees.iter().any(|e| e["employeeId"] == id) {
+                    unknown.insert(format!("teacher {}", id));
+                }
+            }
+        }
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Not found: {}", unknown.into_iter().collect::<Vec<_>>().join(", ")).into())
+        }
+    }
+
+    async fn draft(&self, school_id: &str, draft_id: &str) -> Result<Value, AppError> {
+        self.repos
+            .timetable
+            .get_timetable_draft(school_id, draft_id)
+            .await?
+            .ok_or_else(|| "Timetable draft not found".into())
+    }
+
+    /// Periods to show in a view: the draft's entries, or the live timetable.
+    async fn view_periods(&self, school_id: &str, draft_id: Option<&str>) -> Result<(Vec<Value>, Vec<u64>), AppError> {
+        if let Some(id) = draft_id {
+            let draft = self.draft(school_id, id).await?;
+            let days = draft["config"]["days"].as_array().into_iter().flatten().filter_map(|d| d.as_u64()).collect();
+            let periods = draft["entries"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|e| {
+                    let mut p = e.clone();
+                    p["dayOfWeek"] = e["day"].clone();
+                    p
+                })
+                .collect();
+            return Ok((periods, days));
+        }
+        let periods = self.with_class_names(school_id, self.repos.timetable.get_periods(school_id, None).await?).await?;
+        let config = self.repos.timetable.get_timetable_config(school_id).await?;
+        let mut days: Vec<u64> = config["days"].as_array().into_iter().flatten().filter_map(|d| d.as_u64()).collect();
+        if days.is_empty() {
+            days = (1..=6).collect();
+        }
+        days.extend(periods.iter().filter_map(|p| p["dayOfWeek"].as_u64()));
+        days.sort();
+        days.dedup();
+        Ok((periods, days))
+    }
+
+    async fn with_teacher_names(&self, school_id: &str, mut periods: Vec<Value>) -> Result<Vec<Value>, AppError> {
+        let employees = self.repos.employee.get_employees(school_id).await?;
+        for p in periods.iter_mut() {
+            let name = employees.iter().find(|e| e["employeeId"] == p["teacherId"]).map(employee_name);
+            p["teacherName"] = json!(name);
+        }
+        Ok(periods)
+    }
+}
+
+#[async_trait]
+impl TimetableService for PostgresTimetableService {
+    async fn list_periods(&self, school_id: &str, day_of_week: Option<i32>) -> Result<Vec<Value>, AppError> {
+        if day_of_week.is_some_and(|d| !(1..=7).contains(&d)) {
+            return Err("day must be 1 (Monday) to 7 (Sunday)".into());
+        }
+        let periods = self.repos.timetable.get_periods(school_id, day_of_week).await?;
+        self.with_class_names(school_id, periods).await
+    }
+
+    async fn substitution_proposals(&self, school_id: &str, date: &str) -> Result<Value, AppError> {
+        let ctx = self.load_day(school_id, parse_date(date, "date")?).await?;
+        let proposals = propose(&ctx.day(), MAX_CANDIDATES);
+        Ok(json!({
+            "date": ctx.date.to_string(),
+            "absentTeachers": ctx.absent.iter().map(|(id, reason)| json!({"employeeId": id, "reason": reason})).collect::<Vec<_>>(),
+            "proposals": proposals,
+            "assigned": ctx.assigned
+        }))
+    }
+
+    async fn assign_substitutions(&self, school_id: &str, data: Value) -> Result<Value, AppError> {
+        let date = parse_date(data["date"].as_str().unwrap_or(""), "date")?;
+        let ctx = self.load_day(school_id, date).await?;
+        let proposals = propose(&ctx.day(), usize::MAX);
+
+        let requested: Vec<(Value, String)> = if data["auto"].as_bool().unwrap_or(false) {
+            proposals
+                .iter()
+                .filter_map(|p| Some((p["period"]["periodId"].clone(), p["suggested"].as_str()?.to_string())))
+                .collect()
+        } else {
+            data["assignments"]
+                .as_array()
+                .ok_or("assignments or auto is required")?
+                .iter()
+                .map(|a| {
+                    let substitute = a["substituteId"].as_str().ok_or("Each assignment needs a substituteId")?;
+                    Ok((a["periodId"].clone(), substitute.to_string()))
+                })
+                .collect::<Result<_, AppError>>()?
+        };
+
+        // Validate the whole batch against the candidates before recording anything
+        let mut taken: Vec<(String, String, String)> = Vec::new();
+        let mut plan = Vec::new();
+        for (period_id, substitute_id) in &requested {
+            let proposal = proposals
+                .iter()
+                .find(|p| p["period"]["periodId"] == *period_id)
+                .ok_or_else(|| format!("Period {} is not uncovered on {}", period_id, date))?;
+            let period = &proposal["period"];
+            if !proposal["candidates"].as_array().into_iter().flatten().any(|c| c["employeeId"] == substitute_id.as_str()) {
+                return Err(format!("{} is not free for period {} on {}", substitute_id, period_id, date).into());
+            }
+            let start = period["startTime"].as_str().unwrap_or("").to_string();
+            let end = period["endTime"].as_str().unwrap_or("").to_string();
+            if taken.iter().any(|(id, s, e)| id == substitute_id && *s < end && start < *e) {
+                return Err(format!("{} is given two periods at the same time", substitute_id).into());
+            }
+            taken.push((substitute_id.clone(), start, end));
+            plan.push((period.clone(), proposal["reason"].clone(), substitute_id.clone()));
+        }
+
+        let mut created = Vec::new();
+        for (period, reason, substitute_id) in plan {
+            let substitution = self
+                .repos
+                .timetable
+                .create_substitution(
+                    school_id,
+                    &json!({
+                        "date": date.to_string(),
+                        "periodId": period["periodId"],
+                        "classId": period["classId"],
+                        "subjectId": period["subjectId"],
+                        "startTime": period["startTime"],
+                        "endTime": period["endTime"],
+                        "absentTeacherId": period["teacherId"],
+                        "substituteId": substitute_id,
+                        "reason": reason,
+                        "assignedBy": data["assignedBy"]
+                    }),
+                )
+                .await?;
+            let teacher = ctx.teachers.iter().find(|t| t["employeeId"] == substitute_id.as_str());
+            self.notify_substitute(school_id, &substitution, &period, teacher).await;
+            created.push(substitution);
+        }
+        Ok(json!({ "date": date.to_string(), "assigned": created }))
+    }
+
+    async fn list_substitutions(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+        substitute_id: Option<&str>,
+    ) -> Result<Vec<Value>, AppError> {
+        let (from, to) = (parse_date(from, "from")?, parse_date(to, "to")?);
+        self.repos.timetable.get_substitutions(school_id, from, to, substitute_id).await
+    }
+
+    async fn cancel_substitution(&self, school_id: &str, substitution_id: &str) -> Result<Value, AppError> {
+        self.repos
+            .timetable
+            .cancel_substitution(school_id, substitution_id)
+            .await?
+            .ok_or_else(|| "Substitution not found or already cancelled".into())
+    }
+
+    async fn substitution_load(&self, school_id: &str, from: &str, to: &str) -> Result<Vec<Value>, AppError> {
+        let (from, to) = (parse_date(from, "from")?, parse_date(to, "to")?);
+        let substitutions = self.repos.timetable.get_substitutions(school_id, from, to, None).await?;
+        let employees = self.repos.employee.get_employees(school_id).await?;
+        let mut load: Vec<(String, i64)> = load_by_teacher(&substitutions).into_iter().collect();
+        load.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(load
+            .into_iter()
+            .map(|(id, count)| {
+                let name = employees.iter().find(|e| e["employeeId"] == id.as_str()).map(employee_name);
+                json!({ "employeeId": id, "name": name, "substitutions": count })
+            })
+            .collect())
+    }
+
+    async fn get_generator_settings(&self, school_id: &str) -> Result<Value, AppError> {
+        self.repos.timetable.get_timetable_config(school_id).await
+    }
+
+    async fn save_generator_settings(&self, school_id: &str, data: Value) -> Result<Value, AppError> {
+        let config = validate_config(&data)?;
+        self.check_references(school_id, &config).await?;
+        self.timetable_groups(school_id, &config).await?;
+        self.repos
+            .timetable
+            .save_timetable_config(school_id, &config, data["updatedBy"].as_str())
+            .await?;
+        Ok(config)
+    }
+
+    async fn generate_timetable(&self, school_id: &str, data: Value) -> Result<Value, AppError> {
+        let config = self.repos.timetable.get_timetable_config(school_id).await?;
+        if config.is_null() {
+            return Err("Save the timetable settings before generating".into());
+        }
+        // Regenerating from a draft keeps its pins
+        let pins = match data["fromDraftId"].as_str() {
+            Some(id) => self.draft(school_id, id).await?["pins"].clone(),
+            None => json!([]),
+        };
+        let pins: Vec<Value> = pins.as_array().into_iter().flatten().map(|p| check_pin(p, &config)).collect::<Result<_, _>>()?;
+        let groups = self.timetable_groups(school_id, &config).await?;
+        let subjects: HashMap<String, Value> = self
+            .repos
+            .academic
+            .get_subjects(school_id)
+            .await?
+            .into_iter()
+            .filter_map(|s| Some((s["id"].as_str()?.to_string(), s)))
+            .collect();
+        let rooms: Vec<Value> = self
+            .repos
+            .resource
+            .get_spaces(school_id)
+            .await?
+            .iter()
+            .map(|r| json!({"spaceId": r["spaceId"], "name": r["name"], "category": r["spaceCategory"]}))
+            .collect();
+        let seed = data["seed"].as_u64().unwrap_or_else(|| Utc::now().timestamp_millis() as u64);
+        let result = generate(&TimetableInput { config: &config, groups: &groups, subjects: &subjects, rooms: &rooms, pins: &pins, seed });
+        self.repos
+            .timetable
+            .add_timetable_draft(
+                school_id,
+                &json!({
+                    "draftId": format!("TT{}", Utc::now().timestamp_millis()),
+                    "config": config,
+                    "pins": pins,
+                    "entries": result["entries"],
+                    "unsatisfied": result["unsatisfied"],
+                    "stats": result["stats"],
+                    "seed": seed as i64,
+                    "createdBy": data["createdBy"]
+                }),
+            )
+            .await
+    }
+
+    async fn list_timetable_drafts(&self, school_id: &str) -> Result<Vec<Value>, AppError> {
+        self.repos.timetable.get_timetable_drafts(school_id).await
+    }
+
+    async fn get_timetable_draft(&self, school_id: &str, draft_id: &str) -> Result<Value, AppError> {
+        self.draft(school_id, draft_id).await
+    }
+
+    async fn pin_lessons(&self, school_id: &str, draft_id: &str, data: Value) -> Result<Value, AppError> {
+        let draft = self.draft(school_id, draft_id).await?;
+        if draft["status"] != "draft" {
+            return Err("Only unpublished drafts can be pinned".into());
+        }
+        let pins: Vec<Value> = data["pins"]
+            .as_array()
+            .ok_or("pins must be a list")?
+            .iter()
+            .map(|p| check_pin(p, &draft["config"]))
+            .collect::<Result<_, _>>()?;
+        // Pins can't double-book a class, teacher or room between themselves
+        if let Some(clash) = clashes(&pins).into_iter().next() {
+            return Err(format!("Pins clash: {}", clash).into());
+        }
+        if !self.repos.timetable.save_timetable_pins(school_id, draft_id, &json!(pins)).await? {
+            return Err("Only unpublished drafts can be pinned".into());
+        }
+        self.draft(school_id, draft_id).await
+    }
+
+    async fn publish_timetable(&self, school_id: &str, draft_id: &str, data: Value) -> Result<Value, AppError> {
+        let draft = self.draft(school_id, draft_id).await?;
+        if draft["status"] != "draft" {
+            return Err(format!("This draft is already {}", draft["status"].as_str().unwrap_or("")).into());
+        }
+        let unsatisfied = draft["unsatisfied"].as_array().map(|u| u.len()).unwrap_or(0);
+        if unsatisfied > 0 && !data["acceptUnsatisfied"].as_bool().unwrap_or(false) {
+            return Err(format!(
+                "{} constraint(s) weren't met; fix and regenerate, or publish with acceptUnsatisfied",
+                unsatisfied
+            )
+            .into());
+        }
+        let entries = draft["entries"].as_array().cloned().unwrap_or_default();
+        if let Some(clash) = clashes(&entries).into_iter().next() {
+            return Err(format!("The draft has a clash: {}", clash).into());
+        }
+        let class_ids: Vec<String> = self
+            .timetable_groups(school_id, &draft["config"])
+            .await?
+            .iter()
+            .filter_map(|g| g["classId"].as_str().map(|c| c.to_string()))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let periods: Vec<Value> = entries
+            .iter()
+            .map(|e| {
+                let mut p = e.clone();
+                if p["subjectId"].is_null() {
+                    p["name"] = e["label"].clone();
+                }
+                p
+            })
+            .collect();
+        if !self
+            .repos
+            .timetable
+            .publish_timetable(school_id, draft_id, &class_ids, &periods, data["publishedBy"].as_str())
+            .await?
+        {
+            return Err("This draft was published or replaced meanwhile".into());
+        }
+        Ok(json!({"draftId": draft_id, "classes": class_ids, "periods": periods.len()}))
+    }
+
+    async fn class_timetable(&self, school_id: &str, class_id: &str, section: Option<&str>, draft_id: Option<&str>) -> Result<Value, AppError> {
+        let (periods, days) = self.view_periods(school_id, draft_id).await?;
+        let periods: Vec<Value> = periods
+            .into_iter()
+            .filter(|p| p["classId"] == class_id)
+            .filter(|p| section.is_none() || p["section"].is_null() || p["section"].as_str() == section)
+            .collect();
+        let periods = self.with_teacher_names(school_id, periods).await?;
+        Ok(json!({"classId": class_id, "section": section, "draftId": draft_id, "days": week_grid(&periods, &days)}))
+    }
+
+    async fn teacher_timetable(&self, school_id: &str, teacher_id: &str, draft_id: Option<&str>) -> Result<Value, AppError> {
+        let (periods, days) = self.view_periods(school_id, draft_id).await?;
+        let periods: Vec<Value> = periods.into_iter().filter(|p| p["teacherId"] == teacher_id).collect();
+        let periods = self.with_teacher_names(school_id, periods).await?;
+        let load = periods
+            .iter()
+            .map(|p| days.iter().filter(|d| p["dayOfWeek"].as_u64().is_none_or(|pd| pd == **d)).count())
+            .sum::<usize>();
+        Ok(json!({
+            "teacherId": teacher_id,
+            "teacherName": periods.first().map(|p| p["teacherName"].clone()),
+            "draftId": draft_id,
+            "periodsPerWeek": load,
+            "days": week_grid(&periods, &days)
+        }))
+    }
+}
@@ -194,12 +194,14 @@ pub trait OperationsService: Send + Sync {
         date: &str,
         data: Value,
     ) -> Result<Value, AppError>;
+    /// `data` is `{ deletedBy?, reason? }`, kept in the attendance history.
     async fn delete_attendance(
         &self,
         school_id: &str,
         role: &str,
         user_id: &str,
         date: &str,
+        data: Value,
     ) -> Result<(), AppError>;
     async fn list_attendance(
         &self,
@@ -239,6 +241,7 @@ pub trait OperationsService: Send + Sync {
         school_id: &str,
         student_id: &str,
         amount: i64,
+        details: Value,
     ) -> Result<Value, AppError>;
     async fn apply_discount(
         &self,
@@ -252,11 +255,6 @@ pub trait OperationsService: Send + Sync {
         employee_id: &str,
         data: Value,
     ) -> Result<(), AppError>;
-    async fn auto_close_month(
-        &self,
-        school_id: &str,
-        employee_id: &str,
-    ) -> Result<(), AppError>;
     async fn add_payment(
         &self,
         school_id: &str,
@@ -313,8 +311,10 @@ pub trait OperationsService: Send + Sync {
     async fn list_coupons(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
     async fn remove_coupon(&self, school_id: &str, coupon_id: &str) -> Result<(), AppError>;
     async fn toggle_block_coupon(&self, school_id: &str, coupon_id: &str, blocked: bool) -> Result<(), AppError>;
-    async fn validate_coupon(&self, school_id: &str, coupon_name: &str) -> Result<Option<Value>, AppError>;
-    async fn use_coupon(&self, school_id: &str, coupon_id: &str, student_id: &str, discount: f64) -> Result<Value, AppError>;
+    async fn update_coupon(&self, school_id: &str, coupon_id: &str, data: Value) -> Result<Value, AppError>;
+    /// `context` may carry `studentId` and `feeAmount` to check per-student limits and the minimum fee.
+    async fn validate_coupon(&self, school_id: &str, coupon_name: &str, context: Value) -> Result<Option<Value>, AppError>;
+    async fn use_coupon(&self, school_id: &str, coupon_id: &str, student_id: &str, discount: f64, details: Value) -> Result<Value, AppError>;
 }
 
 #[async_trait]
@@ -465,6 +465,67 @@ pub trait ReminderService: Send + Sync {
         &self,
         school_id: &str,
     ) -> Result<Vec<Value>, AppError>;
+
+    // Fee due reminders
+    async fn list_reminder_rules(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn create_reminder_rule(
+        &self,
+        school_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn delete_reminder_rule(
+        &self,
+        school_id: &str,
+        rule_id: &str,
+    ) -> Result<(), AppError>;
+    /// Sends the fee reminders due on `date` (YYYY-MM-DD). With `dry_run` nothing is sent or logged.
+    async fn run_fee_reminders(
+        &self,
+        school_id: &str,
+        date: &str,
+        dry_run: bool,
+    ) -> Result<Value, AppError>;
+}
+
+#[async_trait]
+pub trait NotificationService: Send + Sync {
+    async fn list_templates(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn save_template(
+        &self,
+        school_id: &str,
+        template_key: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn delete_template(
+        &self,
+        school_id: &str,
+        template_key: &str,
+    ) -> Result<(), AppError>;
+    /// Renders `template_key` with `vars` and sends it on `channel`.
+    /// `meta` carries `dedupeKey` (sent at most once per key) and the `refType` / `refId` kept in the log.
+    async fn send_templated(
+        &self,
+        school_id: &str,
+        template_key: &str,
+        channel: &str,
+        recipient: &str,
+        vars: &Value,
+        meta: Value,
+    ) -> Result<Value, AppError>;
+    async fn list_log(
+        &self,
+        school_id: &str,
+        ref_type: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<Value>, AppError>;
+    /// A delivery receipt from the provider: `{ providerRef, status: sent|delivered|read|failed, error? }`.
+    async fn record_delivery(&self, data: Value) -> Result<Value, AppError>;
 }
 
 #[async_trait]
@@ -540,12 +601,26 @@ pub trait LeaveService: Send + Sync {
         &self,
         school_id: &str,
     ) -> Result<Vec<Value>, AppError>;
+    /// The application with its timeline and the step it is waiting on.
+    async fn get_leave(&self, school_id: &str, leave_id: &str) -> Result<Value, AppError>;
+    /// Approves (`approved`) or rejects (`rejected`) at the current step. `data` is
+    /// `{ approverId?, comment?, adminOverride? }`; an approver's approval forwards the leave to
+    /// the next step. On a chain, deciding without being the step's approver needs
+    /// `adminOverride: true`, is final and is marked as an override in the timeline.
     async fn update_leave_status(
         &self,
         school_id: &str,
         leave_id: &str,
         status: &str,
-    ) -> Result<(), AppError>;
+        data: Value,
+    ) -> Result<Value, AppError>;
+    /// `{ by, comment }`
+    async fn add_leave_comment(
+        &self,
+        school_id: &str,
+        leave_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
     async fn update_leave_duration(
         &self,
         school_id: &str,
@@ -553,5 +628,649 @@ pub trait LeaveService: Send + Sync {
         action: &str,
         days: i32,
     ) -> Result<(), AppError>;
+    /// Active and inactive leave types, built-in defaults merged with the school's own.
+    async fn get_leave_types(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    /// `{ code, name, annualQuota, accrual: yearly|monthly|none, carryForwardMax, paid,
+    /// countHolidays, gender, active }`; unspecified fields keep their current value.
+    async fn save_leave_type(&self, school_id: &str, data: Value) -> Result<Value, AppError>;
+    /// Per-employee balances for `year`, for one employee or everyone active.
+    async fn get_leave_balances(
+        &self,
+        school_id: &str,
+        employee_id: Option<&str>,
+        year: i32,
+    ) -> Result<Vec<Value>, AppError>;
+    /// `{ leaveType, days (negative to deduct), year?, reason, by }`
+    async fn adjust_leave_balance(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    /// Sets next year's opening balances from `from_year`'s closing, up to each type's limit.
+    async fn carry_forward_leave(
+        &self,
+        school_id: &str,
+        from_year: i32,
+    ) -> Result<Value, AppError>;
+    async fn get_approval_chains(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    /// `{ employeeType ('*' for everyone else), steps: [{ name, approvers: [employeeId] }],
+    /// escalateAfterHours?, updatedBy }`
+    async fn save_approval_chain(&self, school_id: &str, data: Value) -> Result<Value, AppError>;
+    async fn delete_approval_chain(&self, school_id: &str, employee_type: &str) -> Result<(), AppError>;
+    /// Pending leave waiting on `approver_id`, directly or through a delegation active today.
+    async fn pending_approvals(&self, school_id: &str, approver_id: &str) -> Result<Vec<Value>, AppError>;
+    /// `{ approverId, delegateId, fromDate, toDate, reason }`
+    async fn create_delegation(&self, school_id: &str, data: Value) -> Result<Value, AppError>;
+    async fn list_delegations(&self, school_id: &str, approver_id: Option<&str>) -> Result<Vec<Value>, AppError>;
+    async fn revoke_delegation(&self, school_id: &str, delegation_id: &str) -> Result<(), AppError>;
+    /// Forwards pending leave that has waited longer than its chain's `escalateAfterHours`.
+    async fn escalate_overdue_leaves(&self, school_id: &str) -> Result<Value, AppError>;
+}
+
+
+#[async_trait]
+pub trait ReportService: Send + Sync {
+    async fn fee_day_book(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Value, AppError>;
+    async fn fee_collection_vs_demand(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Value, AppError>;
+    async fn fee_defaulters(
+        &self,
+        school_id: &str,
+        as_of: &str,
+        class_name: Option<String>,
+    ) -> Result<Value, AppError>;
+    async fn fee_concessions(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Value, AppError>;
+    /// Coupon uses, discount given and admissions grouped by referrer (or assigned employee).
+    async fn referral_report(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Value, AppError>;
+}
+
+#[async_trait]
+pub trait PaymentService: Send + Sync {
+    /// Outstanding installments a parent can pay online: the general balance and each custom fee.
+    async fn payable_items(
+        &self,
+        school_id: &str,
+        student_id: &str,
+    ) -> Result<Value, AppError>;
+    async fn create_order(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn list_orders(
+        &self,
+        school_id: &str,
+        student_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn get_order(
+        &self,
+        order_id: &str,
+    ) -> Result<Option<Value>, AppError>;
+    /// Verifies a gateway callback and posts the payment once; repeated callbacks return the same receipt.
+    async fn handle_callback(
+        &self,
+        gateway: &str,
+        payload: Value,
+        signature: Option<String>,
+    ) -> Result<Value, AppError>;
+    /// Completes a mock-gateway order as the hosted checkout page would; fails with any other gateway.
+    async fn mock_checkout(&self, order_id: &str, success: bool) -> Result<Value, AppError>;
+    /// Orders the gateway took payment for but that could not be posted to the student's fees.
+    async fn failed_orders(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    /// Settles a failed order: `data.action` "retry" posts its installments again; "close" ends it
+    /// without posting, recording `note` (e.g. refunded at the gateway) and `resolvedBy`.
+    async fn resolve_order(&self, school_id: &str, order_id: &str, data: Value) -> Result<Value, AppError>;
 }
 
+#[async_trait]
+pub trait ReconciliationService: Send + Sync {
+    /// Imports a bank statement (CSV/Excel), posts credits that match a student and
+    /// queues the rest for review.
+    async fn import_statement(
+        &self,
+        school_id: &str,
+        file_name: &str,
+        bytes: Vec<u8>,
+        imported_by: Option<String>,
+    ) -> Result<Value, AppError>;
+    async fn list_imports(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn list_lines(
+        &self,
+        school_id: &str,
+        import_id: Option<String>,
+        status: Option<String>,
+    ) -> Result<Vec<Value>, AppError>;
+    /// `data.action` is "post" (with `studentId` and optional `items`) or "ignore".
+    async fn resolve_line(
+        &self,
+        school_id: &str,
+        line_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+}
+
+#[async_trait]
+pub trait PayrollService: Send + Sync {
+    async fn list_runs(
+        &self,
+        school_id: &str,
+    ) -> Result<Vec<Value>, AppError>;
+    /// The run with its employee lines.
+    async fn get_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Value, AppError>;
+    /// Creates the month's draft run, or recomputes it if it is still a draft or reopened.
+    async fn create_run(
+        &self,
+        school_id: &str,
+        month: i32,
+        year: i32,
+        created_by: Option<String>,
+    ) -> Result<Value, AppError>;
+    async fn recompute_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Value, AppError>;
+    /// Approves and locks the run.
+    async fn approve_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        approved_by: &str,
+    ) -> Result<Value, AppError>;
+    async fn reopen_run(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        reopened_by: &str,
+        reason: &str,
+    ) -> Result<Value, AppError>;
+    /// Payslips available to an employee; self-service only sees approved months.
+    async fn list_payslips(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        approved_only: bool,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn get_payslip(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        employee_id: &str,
+        approved_only: bool,
+    ) -> Result<Value, AppError>;
+    /// One row per employee with earnings, deductions and net pay, plus register totals.
+    async fn payroll_register(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Value, AppError>;
+    /// PF, ESI, professional tax and TDS settings with defaults filled in.
+    async fn get_statutory_settings(
+        &self,
+        school_id: &str,
+    ) -> Result<Value, AppError>;
+    async fn update_statutory_settings(
+        &self,
+        school_id: &str,
+        settings: Value,
+        updated_by: Option<String>,
+    ) -> Result<Value, AppError>;
+    /// Per-employee statutory rows for a run with PF, ESI, PT and TDS challan totals.
+    async fn statutory_challans(
+        &self,
+        school_id: &str,
+        run_id: &str,
+    ) -> Result<Value, AppError>;
+    async fn list_advances(
+        &self,
+        school_id: &str,
+        employee_id: Option<&str>,
+        status: Option<&str>,
+    ) -> Result<Vec<Value>, AppError>;
+    /// `{ amount, kind: advance|loan, interestRate, tenureMonths, emiAmount?, monthlyCap?,
+    /// startMonth?, startYear?, reason, createdBy }`
+    async fn create_advance(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    /// The advance with its remaining schedule and transaction history.
+    async fn get_advance(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+    ) -> Result<Value, AppError>;
+    /// Pays off part or all of the outstanding principal outside payroll.
+    async fn prepay_advance(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    /// Stops (or resumes) payroll recovery without changing the balance.
+    async fn set_advance_paused(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+        paused: bool,
+        by: &str,
+    ) -> Result<Value, AppError>;
+    /// Writes off `amount` (default: everything outstanding).
+    async fn waive_advance(
+        &self,
+        school_id: &str,
+        advance_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    /// Outstanding balance, next installment and open advances for one employee.
+    async fn employee_advance_summary(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+    ) -> Result<Value, AppError>;
+    /// `{ accountHolder, accountNumber, ifsc, bankName, accountType }`
+    async fn set_bank_account(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    /// Creates a bulk-transfer batch for an approved run. Employees already sent (pending or
+    /// paid) are left out, as are those without valid bank details, which are listed as skipped.
+    async fn create_transfer_batch(
+        &self,
+        school_id: &str,
+        run_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn list_transfer_batches(
+        &self,
+        school_id: &str,
+        run_id: Option<&str>,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn get_transfer_batch(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+    ) -> Result<Value, AppError>;
+    /// The batch rendered in its bank's bulk-upload CSV layout: `{ fileName, content }`.
+    async fn transfer_file(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+    ) -> Result<Value, AppError>;
+    /// Applies a bank response file to the batch lines and returns what matched.
+    async fn import_transfer_response(
+        &self,
+        school_id: &str,
+        batch_id: &str,
+        file_name: &str,
+        bytes: Vec<u8>,
+        imported_by: Option<String>,
+    ) -> Result<Value, AppError>;
+    /// Form 16-style yearly statement of salary, deductions and tax for the financial year
+    /// starting in April of `fy_start`.
+    async fn annual_tax_statement(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        fy_start: i32,
+    ) -> Result<Value, AppError>;
+    /// Dated salary revisions, newest first, with the one in force this month marked.
+    async fn list_salary_revisions(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+    ) -> Result<Value, AppError>;
+    /// `{ effectiveFrom | effectiveMonth + effectiveYear, baseSalary?, incrementPercent?, ...,
+    /// allowances?, reason, createdBy }`. Unlisted fields carry over from the revision it replaces.
+    async fn create_salary_revision(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+}
+
+#[async_trait]
+pub trait TimetableService: Send + Sync {
+    /// Periods running on `day_of_week` (1 = Monday), or the whole timetable, with class names.
+    async fn list_periods(&self, school_id: &str, day_of_week: Option<i32>) -> Result<Vec<Value>, AppError>;
+    /// Uncovered periods on `date` whose teacher is absent or on leave, each with ranked free
+    /// teachers and a suggested substitute that keeps the week's substitution load even.
+    async fn substitution_proposals(&self, school_id: &str, date: &str) -> Result<Value, AppError>;
+    /// `{ date, assignments: [{ periodId, substituteId }] | auto: true, assignedBy }`. Each
+    /// substitute is checked to be present and free, and is notified of the period.
+    async fn assign_substitutions(&self, school_id: &str, data: Value) -> Result<Value, AppError>;
+    async fn list_substitutions(
+        &self,
+        school_id: &str,
+        from: &str,
+        to: &str,
+        substitute_id: Option<&str>,
+    ) -> Result<Vec<Value>, AppError>;
+    async fn cancel_substitution(&self, school_id: &str, substitution_id: &str) -> Result<Value, AppError>;
+    /// Substitutions taken per teacher between `from` and `to`, most loaded first.
+    async fn substitution_load(&self, school_id: &str, from: &str, to: &str) -> Result<Vec<Value>, AppError>;
+    /// The generator settings, `null` until saved.
+    async fn get_generator_settings(&self, school_id: &str) -> Result<Value, AppError>;
+    /// `{ days?, slots, classes?, requirements, teachers?, teacherMaxPerDay?, fixed?, updatedBy }`;
+    /// see `logic::timetable::validate_config`.
+    async fn save_generator_settings(&self, school_id: &str, data: Value) -> Result<Value, AppError>;
+    /// `{ fromDraftId?, seed?, createdBy }`. Builds a clash-free draft from the saved settings,
+    /// keeping the pins of `fromDraftId`, and lists what it couldn't satisfy.
+    async fn generate_timetable(&self, school_id: &str, data: Value) -> Result<Value, AppError>;
+    async fn list_timetable_drafts(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    async fn get_timetable_draft(&self, school_id: &str, draft_id: &str) -> Result<Value, AppError>;
+    /// `{ pins: [{ classId, section?, day, slot, subjectId, teacherId?, roomId? }] }` replaces the
+    /// lessons regeneration must keep where they are.
+    async fn pin_lessons(&self, school_id: &str, draft_id: &str, data: Value) -> Result<Value, AppError>;
+    /// `{ publishedBy, acceptUnsatisfied? }`. Replaces the periods of the draft's classes.
+    async fn publish_timetable(&self, school_id: &str, draft_id: &str, data: Value) -> Result<Value, AppError>;
+    /// A class's week, from the live timetable or a draft.
+    async fn class_timetable(&self, school_id: &str, class_id: &str, section: Option<&str>, draft_id: Option<&str>) -> Result<Value, AppError>;
+    /// A teacher's week, from the live timetable or a draft.
+    async fn teacher_timetable(&self, school_id: &str, teacher_id: &str, draft_id: Option<&str>) -> Result<Value, AppError>;
+}
+
+#[async_trait]
+pub trait EmployeeDocumentService: Send + Sync {
+    /// Document types with whether they are required, what they link to and how long they last.
+    async fn document_types(&self) -> Result<Vec<Value>, AppError>;
+    /// Stores the file and records it against the employee. `meta` is `{ documentType, title?,
+    /// educationId?, experienceId?, documentNumber?, issuedOn?, expiresOn?, uploadedBy? }`; a
+    /// linked education or experience record gets the file's URL.
+    async fn upload_document(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        meta: Value,
+        file_name: &str,
+        content_type: Option<&str>,
+        bytes: Vec<u8>,
+    ) -> Result<Value, AppError>;
+    async fn list_documents(&self, school_id: &str, employee_id: &str) -> Result<Vec<Value>, AppError>;
+    /// The document record and its file contents.
+    async fn document_file(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        document_id: &str,
+    ) -> Result<(Value, Vec<u8>), AppError>;
+    /// `{ status: verified|rejected|pending, verifiedBy, remarks? }`
+    async fn verify_document(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        document_id: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    async fn delete_document(&self, school_id: &str, employee_id: &str, document_id: &str) -> Result<Value, AppError>;
+    /// Missing, rejected, unverified, expired and soon-to-expire documents per active employee.
+    async fn compliance_report(
+        &self,
+        school_id: &str,
+        employee_id: Option<&str>,
+        within_days: i64,
+    ) -> Result<Value, AppError>;
+    /// Reminds employees whose documents expire within `within_days` or have expired.
+    async fn remind_expiring_documents(&self, school_id: &str, within_days: i64) -> Result<Value, AppError>;
+}
+
+#[async_trait]
+pub trait AttendanceService: Send + Sync {
+    /// The school's attendance settings with defaults filled in.
+    async fn get_attendance_settings(&self, school_id: &str) -> Result<Value, AppError>;
+    /// `{ correctionWindowHours?, ..., updatedBy }`; unspecified keys keep their current value.
+    async fn update_attendance_settings(&self, school_id: &str, data: Value) -> Result<Value, AppError>;
+    /// The class roster for `date` with each student's status, the summary and whether the
+    /// sheet can still be edited.
+    async fn class_attendance(
+        &self,
+        school_id: &str,
+        class_id: &str,
+        section: Option<&str>,
+        date: &str,
+    ) -> Result<Value, AppError>;
+    /// `{ section?, markedBy, defaultStatus?, entries: [{ studentId, status, remarks? }] }` where
+    /// status is present, absent, late or half_day. Written in one transaction; once taken, the
+    /// sheet can be changed only within the correction window.
+    async fn mark_class_attendance(
+        &self,
+        school_id: &str,
+        class_id: &str,
+        date: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    /// The rotating check-in QR code for a registered `display` device, which authenticates with
+    /// its serial number and API key: `{payload, token, expiresAt}`. Tokens name the display.
+    async fn check_in_qr(&self, school_id: &str, serial_number: &str, api_key: &str) -> Result<Value, AppError>;
+    /// A teacher checking `in` or `out` from the app with `{ deviceId, qrToken?, latitude?,
+    /// longitude?, accuracy?, mockLocation? }`: either a valid QR token or a location inside the
+    /// geofence. Every attempt is logged with any suspicious-pattern flags.
+    async fn self_check_in(
+        &self,
+        school_id: &str,
+        employee_id: &str,
+        action: &str,
+        data: Value,
+    ) -> Result<Value, AppError>;
+    /// Self check-in attempts between `from` and `to` (default today), optionally only flagged
+    /// ones, with a summary of phones used by more than one teacher.
+    async fn self_check_in_log(
+        &self,
+        school_id: &str,
+        from: Option<&str>,
+        to: Option<&str>,
+        flagged_only: bool,
+    ) -> Result<Value, AppError>;
+    /// The month's register in grid form for a class (`role` student, `classId` required) or
+    /// for staff (`role` employee): one `dNN` cell per day and per-person totals. Sundays and
+    /// school holidays (unless the person is exempt) are left out of the percentage.
+    async fn monthly_register(
+        &self,
+        school_id: &str,
+        role: &str,
+        class_id: Option<&str>,
+        section: Option<&str>,
+        month: u32,
+        year: i32,
+    ) -> Result<Value, AppError>;
+    /// Chronic absentees (attendance below `threshold` percent) and attendance by weekday over
+    /// the `(from, to)` range for the same scopes as the register.
+    async fn attendance_analytics(
+        &self,
+        school_id: &str,
+        role: &str,
+        class_id: Option<&str>,
+        section: Option<&str>,
+        range: (&str, &str),
+        threshold: f64,
+    ) -> Result<Value, AppError>;
+}
+
+#[async_trait]
+pub trait DeviceService: Send + Sync {
+    /// `{ serialNumber, name, protocol: adms|json|display, location? }`. The response carries the
+    /// device's API key for JSON pushes and QR displays; it isn't shown again.
+    async fn register_device(&self, school_id: &str, data: Value) -> Result<Value, AppError>;
+    async fn list_devices(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    /// `{ name?, location?, active?, regenerateKey? }`
+    async fn update_device(&self, school_id: &str, device_id: &str, data: Value) -> Result<Value, AppError>;
+    async fn delete_device(&self, school_id: &str, device_id: &str) -> Result<(), AppError>;
+    async fn list_enrollments(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    /// `{ enrollments: [{ enrollId, role: student|employee, userId }] }`. Punches already
+    /// received for these IDs are mapped and turned into attendance.
+    async fn save_enrollments(&self, school_id: &str, data: Value) -> Result<Value, AppError>;
+    async fn delete_enrollment(&self, school_id: &str, enroll_id: &str) -> Result<(), AppError>;
+    /// ADMS handshake reply for the terminal with serial `serial`.
+    async fn adms_handshake(&self, serial: &str) -> Result<String, AppError>;
+    /// ADMS data push; only `ATTLOG` is stored, other tables are acknowledged.
+    async fn adms_push(&self, serial: &str, table: &str, body: &str) -> Result<String, AppError>;
+    /// ADMS command poll; nothing is ever queued for terminals.
+    async fn adms_poll(&self, serial: &str) -> Result<String, AppError>;
+    /// Generic JSON push: `{ serialNumber, apiKey, punches: [{ enrollId, time, type?, verifyMode? }] }`.
+    async fn push_punches(&self, school_id: &str, data: Value) -> Result<Value, AppError>;
+    async fn list_punches(&self, school_id: &str, date: &str, unmatched_only: bool) -> Result<Vec<Value>, AppError>;
+    /// Rebuilds device attendance for `date` from the stored punches, e.g. after rule changes.
+    async fn reprocess_punches(&self, school_id: &str, date: &str) -> Result<Value, AppError>;
+    /// Feeds a generated ADMS log for `{ date?, enrollIds? }` through the device's ingestion path.
+    /// Enabled with `DEVICE_SIMULATOR=1`.
+    async fn simulate_device(&self, school_id: &str, device_id: &str, data: Value) -> Result<Value, AppError>;
+}
+
+#[async_trait]
+pub trait AbsenceAlertService: Send + Sync {
+    /// Alerts guardians of students absent on `date` (or unmarked in a class whose attendance
+    /// was taken), skipping weekly offs, holidays, leave and students already alerted. With `dry_run`
+    /// nothing is sent.
+    async fn run_absence_alerts(&self, school_id: &str, date: &str, dry_run: bool) -> Result<Value, AppError>;
+    /// Alerts for `date` with delivery status and any reason a guardian sent back.
+    async fn list_absence_alerts(&self, school_id: &str, date: &str) -> Result<Vec<Value>, AppError>;
+    /// A guardian's reply relayed from the messaging provider: `{ from, body }`. The reason is
+    /// attached to the latest absence alert sent to that number or address.
+    async fn record_guardian_reply(&self, data: Value) -> Result<Value, AppError>;
+    /// A reason for `date` given from the student app or by the office; `via` says where it came from.
+    async fn submit_absence_reason(
+        &self,
+        school_id: &str,
+        student_id: &str,
+        date: &str,
+        reason: &str,
+        via: &str,
+    ) -> Result<Value, AppError>;
+}
+
+#[async_trait]
+pub trait HolidayService: Send + Sync {
+    /// The school's weekly-off rules; every Sunday until it saves its own.
+    async fn get_holiday_rules(&self, school_id: &str) -> Result<Vec<Value>, AppError>;
+    /// Replaces the weekly-off rules: `{ rules: [{ name?, weekday, weeks? }], updatedBy? }`.
+    /// `weeks` picks occurrences in the month (`[2, 4]` for second and fourth); empty is every week.
+    async fn save_holiday_rules(&self, school_id: &str, data: Value) -> Result<Vec<Value>, AppError>;
+    /// Rules, holidays and every day off between `from` and `to` with its reason.
+    async fn holiday_calendar(&self, school_id: &str, from: &str, to: &str) -> Result<Value, AppError>;
+    /// Working days between `from` and `to` inclusive, for the school or for one person
+    /// (`(role, userId)`, whose holiday exemptions then count).
+    async fn working_days(&self, school_id: &str, from: &str, to: &str, person: Option<(&str, &str)>) -> Result<Value, AppError>;
+    /// Why `date` is off for the person, `None` when they work it.
+    async fn day_off_reason(&self, school_id: &str, date: &str, role: &str, user_id: &str) -> Result<Option<String>, AppError>;
+    /// The calendar as an `.ics` file; the current academic year when no range is given.
+    async fn export_ics(&self, school_id: &str, from: Option<&str>, to: Option<&str>) -> Result<String, AppError>;
+    /// Imports an `.ics` file. Dated events become holidays, weekly and nth-weekday repeats
+    /// become weekly-off rules, and holidays already on the calendar are left alone.
+    async fn import_ics(&self, school_id: &str, ics: &str, dry_run: bool, updated_by: Option<&str>) -> Result<Value, AppError>;
+    /// Gazetted national, festival and state holidays for `{ academicYear | from, to, state?, dryRun? }`;
+    /// adds the missing ones unless `dryRun` (the default).
+    async fn gazetted_holidays(&self, school_id: &str, data: Value) -> Result<Value, AppError>;
+}
+
+#[async_trait]
+pub trait AttendanceCorrectionService: Send + Sync {
+    /// Raises a request to change one day's attendance: `{ date, status? | remove?, inTime?,
+    /// outTime?, reason, requestedBy?, via? }`. Requests for locked days are flagged for the approver.
+    async fn request_correction(&self, school_id: &str, role: &str, user_id: &str, data: Value) -> Result<Value, AppError>;
+    /// Requests filtered by `{ status?, role?, userId?, from?, to? }`, newest first.
+    async fn list_corrections(&self, school_id: &str, filter: Value) -> Result<Vec<Value>, AppError>;
+    async fn get_correction(&self, school_id: &str, request_id: &str) -> Result<Value, AppError>;
+    /// Attaches an evidence file to a pending request.
+    async fn add_evidence(
+        &self,
+        school_id: &str,
+        request_id: &str,
+        file_name: &str,
+        content_type: Option<&str>,
+        bytes: Vec<u8>,
+        uploaded_by: Option<&str>,
+    ) -> Result<Value, AppError>;
+    /// The evidence file's details and bytes.
+    async fn evidence_file(&self, school_id: &str, request_id: &str, index: usize) -> Result<(Value, Vec<u8>), AppError>;
+    /// `{ decision: approve | reject, decidedBy, note? }`. Approving applies the change and logs it
+    /// in the attendance history; rejecting needs a note.
+    async fn decide_correction(&self, school_id: &str, request_id: &str, data: Value) -> Result<Value, AppError>;
+    /// Withdraws a pending request; only its requester can.
+    async fn cancel_correction(&self, school_id: &str, request_id: &str, cancelled_by: &str) -> Result<Value, AppError>;
+    /// Everything that changed one person's attendance, newest first, with who changed it and why.
+    async fn attendance_history(&self, school_id: &str, user_id: &str, from: Option<&str>, to: Option<&str>) -> Result<Vec<Value>, AppError>;
+}
+
+#[async_trait]
+pub trait SubjectAttendanceService: Send + Sync {
+    /// One timetable period on `date`: the period, who teaches it (and any substitute), and the
+    /// students who sit it with their marks.
+    async fn period_attendance(&self, school_id: &str, period_id: i32, date: &str) -> Result<Value, AppError>;
+    /// `{ entries: [{ studentId, status, remarks? }], defaultStatus?, markedBy }`. Marks already
+    /// taken can be changed only inside the correction window.
+    async fn mark_period_attendance(&self, school_id: &str, period_id: i32, date: &str, data: Value) -> Result<Value, AppError>;
+    /// Each student's attendance per subject for a class between `from` and `to` (default: the
+    /// academic year so far).
+    async fn subject_report(
+        &self,
+        school_id: &str,
+        class_id: &str,
+        section: Option<&str>,
+        range: (Option<&str>, Option<&str>),
+    ) -> Result<Value, AppError>;
+    /// Students below `threshold` percent (default: the school's `subjectAttendanceThreshold`)
+    /// in any subject, one row per student and subject, with the periods they need to recover.
+    async fn eligibility_report(
+        &self,
+        school_id: &str,
+        class_id: Option<&str>,
+        section: Option<&str>,
+        range: (Option<&str>, Option<&str>),
+        threshold: Option<f64>,
+    ) -> Result<Value, AppError>;
+}
+
+#[async_trait]
+pub trait SessionService: Send + Sync {
+    /// Sessions newest first, optionally only `upcoming`, `active` or `archived` ones.
+    async fn list_sessions(&self, school_id: &str, status: Option<&str>) -> Result<Vec<Value>, AppError>;
+    /// `{ name, startDate?, endDate?, createdBy }`; a `2026-27` style name alone runs April to
+    /// March. The school's first session is active, later ones upcoming until the rollover.
+    async fn create_session(&self, school_id: &str, data: Value) -> Result<Value, AppError>;
+    /// The session with its classes, fees, exams and enrolments.
+    async fn get_session(&self, school_id: &str, session_id: &str) -> Result<Value, AppError>;
+    /// `{ name?, startDate?, endDate? }`; archived sessions are read-only.
+    async fn update_session(&self, school_id: &str, session_id: &str, data: Value) -> Result<Value, AppError>;
+    /// Year-end wizard state for the active session: each class's students with their pending
+    /// fees, the decisions taken so far and the suggested next class.
+    async fn rollover_overview(&self, school_id: &str, session_id: &str) -> Result<Value, AppError>;
+    /// `{ nextClassId?, nextSection?, defaultOutcome?, decisions?: [{ studentId, outcome,
+    /// nextClassId?, nextSection? }], decidedBy }`. Decides every student of one class; students
+    /// not listed get `defaultOutcome` (pass, or leave for the top class).
+    async fn save_class_promotions(&self, school_id: &str, session_id: &str, class_id: &str, data: Value) -> Result<Value, AppError>;
+    /// `{ toSessionId, carryBalances?, completedBy }`. Applies the decisions, carries unpaid
+    /// fees into `toSessionId` (default on), archives this session and activates the next.
+    async fn complete_rollover(&self, school_id: &str, session_id: &str, data: Value) -> Result<Value, AppError>;
+}
@@ -46,7 +46,12 @@ export default function LeaveManagement({ schoolId: propSchoolId }) {
     const updateStatus = async (leaveId, action) => {
         setActionLoading(prev => ({ ...prev, [leaveId]: action }));
         try {
-            await callApiWithBackoff(`${API_BASE_URL}/leave/${schoolId}/${leaveId}/${action}`, { method: 'POST' });
+            // This is the admin's screen, so decisions here override the approval chain
+            await callApiWithBackoff(`${API_BASE_URL}/leave/${schoolId}/${leaveId}/${action}`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ adminOverride: true })
+            });
             setLeaves(prev => prev.map(l => l.leaveId === leaveId ? { ...l, status: action === 'approve' ? 'approved' : 'rejected' } : l));
         } catch (e) {
             setError(`Failed to ${action} leave: ${e.message}`);
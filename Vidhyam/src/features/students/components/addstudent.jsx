@@ -193,7 +193,7 @@ export default function AddStudentPage({ onSuccess, onBack }) {
     try {
       const res = await fetch(`${API_BASE_URL}/fees/${schoolId}/coupons/validate`, {
         method: 'POST', headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify({ couponName: code.trim() })
+        body: JSON.stringify({ couponName: code.trim(), feeAmount: form.totalFees || undefined })
       });
       const d = await res.json();
       if (!res.ok || !d.success) { setCouponData(null); setCouponError('Coupon not found'); return; }
@@ -203,11 +203,12 @@ export default function AddStudentPage({ onSuccess, onBack }) {
     finally { setCouponLoading(false); }
   };
 
-  const couponDiscount = couponData ? (
-    couponData.discountType === 'percentage'
-      ? (form.totalFees * parseFloat(couponData.discountValue)) / 100
-      : parseFloat(couponData.discountValue)
-  ) : 0;
+  // The backend applies the coupon's caps (max discount, budget, minimum fee); re-check when the fee changes
+  useEffect(() => {
+    if (couponData) validateCoupon(referralCode);
+  }, [form.totalFees]);
+
+  const couponDiscount = couponData ? (parseFloat(couponData.discountAmount) || 0) : 0;
   const finalFees = Math.max(0, form.totalFees - couponDiscount);
 
   const handleSubmit = async () => {
@@ -243,7 +244,7 @@ export default function AddStudentPage({ onSuccess, onBack }) {
         try {
           await fetch(`${API_BASE_URL}/fees/${schoolId}/coupons/${couponData.couponId}/use`, {
             method: 'POST', headers: { 'Content-Type': 'application/json' },
-            body: JSON.stringify({ studentId: data.data.studentId, discount: couponDiscount })
+            body: JSON.stringify({ studentId: data.data.studentId, discount: couponDiscount, feeAmount: form.totalFees, isAdmission: true })
           });
         } catch { }
       }